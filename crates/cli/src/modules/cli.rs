@@ -419,6 +419,21 @@ pub enum ServerCommands {
     /// Perform database maintenance
     DatabaseMaintenance {},
 
+    /// Verify the consistency of the data store
+    StoreCheck {
+        /// Account to check, all accounts are checked if omitted
+        account: Option<String>,
+        /// Repair any inconsistencies found
+        #[clap(short, long)]
+        repair: bool,
+    },
+
+    /// Rebuild the full-text search index
+    Reindex {
+        /// Account to reindex, all accounts are reindexed if omitted
+        account: Option<String>,
+    },
+
     /// Reload TLS certificates
     ReloadCertificates {},
 
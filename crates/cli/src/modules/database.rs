@@ -21,6 +21,9 @@
  * for more details.
 */
 
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
 use prettytable::{Attr, Cell, Row, Table};
 use reqwest::Method;
 use serde_json::Value;
@@ -36,6 +39,133 @@ impl ServerCommands {
                     .await;
                 eprintln!("Success.");
             }
+            ServerCommands::StoreCheck { account, repair } => {
+                let mut query = form_urlencoded::Serializer::new("/api/store/fsck?".to_string());
+                if let Some(account) = &account {
+                    query.append_pair("account", account);
+                }
+                if repair {
+                    query.append_pair("repair", "true");
+                }
+
+                client
+                    .http_request::<Value, String>(Method::POST, &query.finish(), None)
+                    .await;
+
+                // Wait for the check to complete
+                let pb = ProgressBar::new(0);
+                pb.set_style(
+                    ProgressStyle::with_template("{bar:40} {pos}/{len} accounts checked").unwrap(),
+                );
+                let status = loop {
+                    let status = client
+                        .http_request::<Value, String>(Method::GET, "/api/store/fsck", None)
+                        .await;
+                    pb.set_length(status.get("total").and_then(|v| v.as_u64()).unwrap_or(0));
+                    pb.set_position(status.get("checked").and_then(|v| v.as_u64()).unwrap_or(0));
+                    if !status
+                        .get("isRunning")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                    {
+                        pb.finish_and_clear();
+                        break status;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                };
+                if let Some(error) = status.get("error").and_then(|v| v.as_str()) {
+                    eprintln!("Store check failed: {error}");
+                    std::process::exit(1);
+                }
+                let reports = status
+                    .get("reports")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                let mut total = 0;
+
+                if !reports.is_empty() {
+                    let mut table = Table::new();
+                    table.add_row(Row::new(vec![
+                        Cell::new("Account Id").with_style(Attr::Bold),
+                        Cell::new("Issue").with_style(Attr::Bold),
+                        Cell::new("Details").with_style(Attr::Bold),
+                    ]));
+
+                    for report in &reports {
+                        let account_id = report.get("accountId").cloned().unwrap_or_default();
+                        for issue in report
+                            .get("issues")
+                            .and_then(|issues| issues.as_array())
+                            .into_iter()
+                            .flatten()
+                        {
+                            let mut issue = issue.clone();
+                            let typ = issue
+                                .as_object_mut()
+                                .and_then(|issue| issue.remove("type"))
+                                .and_then(|typ| typ.as_str().map(|typ| typ.to_string()))
+                                .unwrap_or_default();
+                            table.add_row(Row::new(vec![
+                                Cell::new(&account_id.to_string()),
+                                Cell::new(&typ),
+                                Cell::new(&issue.to_string()),
+                            ]));
+                            total += 1;
+                        }
+                    }
+
+                    eprintln!();
+                    table.printstd();
+                    eprintln!();
+                }
+
+                eprintln!(
+                    "\n\n{} issue{} found.\n",
+                    total,
+                    if total == 1 { "" } else { "s" }
+                );
+                if repair && total > 0 {
+                    eprintln!("Repairable issues have been fixed.");
+                }
+            }
+            ServerCommands::Reindex { account } => {
+                let mut query = form_urlencoded::Serializer::new("/api/store/reindex?".to_string());
+                if let Some(account) = &account {
+                    query.append_pair("account", account);
+                }
+
+                client
+                    .http_request::<Value, String>(Method::POST, &query.finish(), None)
+                    .await;
+
+                // Wait for all accounts to be queued for indexing
+                let pb = ProgressBar::new(0);
+                pb.set_style(
+                    ProgressStyle::with_template("{bar:40} {pos}/{len} accounts queued").unwrap(),
+                );
+                let status = loop {
+                    let status = client
+                        .http_request::<Value, String>(Method::GET, "/api/store/reindex", None)
+                        .await;
+                    pb.set_length(status.get("total").and_then(|v| v.as_u64()).unwrap_or(0));
+                    pb.set_position(status.get("queued").and_then(|v| v.as_u64()).unwrap_or(0));
+                    if !status
+                        .get("isRunning")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false)
+                    {
+                        pb.finish_and_clear();
+                        break status;
+                    }
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                };
+                if let Some(error) = status.get("error").and_then(|v| v.as_str()) {
+                    eprintln!("Reindex failed: {error}");
+                    std::process::exit(1);
+                }
+                eprintln!("Success.");
+            }
             ServerCommands::ReloadCertificates {} => {
                 client
                     .http_request::<Value, String>(Method::GET, "/api/reload/certificates", None)
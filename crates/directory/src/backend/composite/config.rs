@@ -0,0 +1,111 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use utils::config::{
+    utils::{AsKey, ParseValue},
+    Config,
+};
+
+use crate::{Directory, DirectoryInner};
+
+use super::{CollisionPolicy, CompositeDirectory};
+
+impl CompositeDirectory {
+    pub fn from_config(
+        config: &mut Config,
+        prefix: impl AsKey,
+        directories: &AHashMap<String, Arc<Directory>>,
+    ) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let mut members = Vec::new();
+
+        for member_id in config
+            .values((&prefix, "members"))
+            .map(|(_, id)| id.to_string())
+            .collect::<Vec<_>>()
+        {
+            match directories.get(&member_id) {
+                Some(directory) if matches!(directory.store, DirectoryInner::Composite(_)) => {
+                    config.new_parse_error(
+                        (prefix.as_str(), "members"),
+                        format!(
+                            "Directory {member_id:?} is a composite directory and cannot be nested"
+                        ),
+                    );
+                    return None;
+                }
+                Some(directory) => {
+                    if members.iter().any(|(id, _)| id == &member_id) {
+                        config.new_parse_error(
+                            (prefix.as_str(), "members"),
+                            format!("Directory {member_id:?} is listed more than once"),
+                        );
+                        return None;
+                    }
+                    members.push((member_id, directory.clone()));
+                }
+                None => {
+                    config.new_parse_error(
+                        (prefix.as_str(), "members"),
+                        format!("Directory {member_id:?} does not exist"),
+                    );
+                    return None;
+                }
+            }
+        }
+
+        if members.is_empty() {
+            config.new_parse_error(
+                (prefix.as_str(), "members"),
+                "At least one member directory is required",
+            );
+            return None;
+        }
+
+        Some(CompositeDirectory {
+            members,
+            merge_groups: config
+                .property_or_default_((&prefix, "merge.groups"), "false")
+                .unwrap_or_default(),
+            collision: config
+                .property_or_default_((&prefix, "collision"), "first")
+                .unwrap_or(CollisionPolicy::First),
+        })
+    }
+}
+
+impl ParseValue for CollisionPolicy {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        match value {
+            "first" => Ok(CollisionPolicy::First),
+            "reject" => Ok(CollisionPolicy::Reject),
+            _ => Err(format!(
+                "Invalid value for collision policy {key:?}: {value:?}",
+                key = key.as_key(),
+            )),
+        }
+    }
+}
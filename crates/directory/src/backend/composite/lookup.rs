@@ -0,0 +1,198 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use mail_send::Credentials;
+
+use crate::{Directory, DirectoryError, Principal, QueryBy};
+
+use super::{CollisionPolicy, CompositeDirectory};
+
+impl CompositeDirectory {
+    pub async fn query(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        // An account belongs to the first member directory that knows its name, so
+        // credentials rejected by the owner are not retried on lower precedence members.
+        let username = match by {
+            QueryBy::Credentials(
+                Credentials::Plain { username, .. } | Credentials::XOauth2 { username, .. },
+            ) => Some(username.as_str()),
+            _ => None,
+        };
+
+        for (pos, (member_id, member)) in self.members.iter().enumerate() {
+            match Box::pin(member.query(by, return_member_of)).await {
+                Ok(Some(principal)) => {
+                    return self.resolve(principal, pos, return_member_of).await;
+                }
+                Ok(None) => {
+                    if let Some(username) = username {
+                        if is_known(member, username).await? {
+                            tracing::debug!(
+                                context = "directory",
+                                event = "query",
+                                directory = member_id,
+                                account = username,
+                                "Credentials rejected by the directory owning the account"
+                            );
+                            return Ok(None);
+                        }
+                    }
+                }
+                Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn resolve(
+        &self,
+        mut principal: Principal<u32>,
+        pos: usize,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        let merge_groups = self.merge_groups && return_member_of;
+        if principal.name.is_empty() || (!merge_groups && self.collision == CollisionPolicy::First)
+        {
+            return Ok(Some(principal));
+        }
+
+        for (member_id, member) in &self.members[pos + 1..] {
+            match Box::pin(member.query(QueryBy::Name(&principal.name), merge_groups)).await {
+                Ok(Some(other)) => {
+                    if self.collision == CollisionPolicy::Reject {
+                        tracing::warn!(
+                            context = "directory",
+                            event = "collision",
+                            directory = member_id,
+                            account = principal.name,
+                            "Account exists in more than one member directory"
+                        );
+                        return Ok(None);
+                    }
+
+                    if merge_groups {
+                        for id in other.member_of {
+                            if !principal.member_of.contains(&id) {
+                                principal.member_of.push(id);
+                            }
+                        }
+                    }
+                }
+                Ok(None) | Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Some(principal))
+    }
+
+    pub async fn email_to_ids(&self, address: &str) -> crate::Result<Vec<u32>> {
+        let mut result = Vec::new();
+        for (_, member) in &self.members {
+            match Box::pin(member.email_to_ids(address)).await {
+                Ok(ids) => {
+                    for id in ids {
+                        if !result.contains(&id) {
+                            result.push(id);
+                        }
+                    }
+                }
+                Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
+        for (_, member) in &self.members {
+            match Box::pin(member.is_local_domain(domain)).await {
+                Ok(true) => return Ok(true),
+                Ok(false) | Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub async fn rcpt(&self, address: &str) -> crate::Result<bool> {
+        for (_, member) in &self.members {
+            match Box::pin(member.rcpt(address)).await {
+                Ok(true) => return Ok(true),
+                Ok(false) | Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub async fn vrfy(&self, address: &str) -> crate::Result<Vec<String>> {
+        let mut result = Vec::new();
+        for (_, member) in &self.members {
+            match Box::pin(member.vrfy(address)).await {
+                Ok(addresses) => merge_addresses(&mut result, addresses),
+                Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(result)
+    }
+
+    pub async fn expn(&self, address: &str) -> crate::Result<Vec<String>> {
+        let mut result = Vec::new();
+        for (_, member) in &self.members {
+            match Box::pin(member.expn(address)).await {
+                Ok(addresses) => merge_addresses(&mut result, addresses),
+                Err(DirectoryError::Unsupported) => (),
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+async fn is_known(member: &Directory, name: &str) -> crate::Result<bool> {
+    match Box::pin(member.query(QueryBy::Name(name), false)).await {
+        Ok(principal) => Ok(principal.is_some()),
+        Err(DirectoryError::Unsupported) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn merge_addresses(result: &mut Vec<String>, addresses: Vec<String>) {
+    for address in addresses {
+        if !result.contains(&address) {
+            result.push(address);
+        }
+    }
+}
@@ -39,6 +39,7 @@ pub trait DirectoryStore: Sync + Send {
         return_member_of: bool,
     ) -> crate::Result<Option<Principal<u32>>>;
     async fn email_to_ids(&self, email: &str) -> crate::Result<Vec<u32>>;
+    async fn email_to_list(&self, email: &str) -> crate::Result<Option<u32>>;
 
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool>;
     async fn rcpt(&self, address: &str) -> crate::Result<bool>;
@@ -113,6 +114,19 @@ impl DirectoryStore for Store {
         }
     }
 
+    async fn email_to_list(&self, email: &str) -> crate::Result<Option<u32>> {
+        self.get_value::<PrincipalIdType>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::EmailToId(email.as_bytes().to_vec()),
+        )))
+        .await
+        .map(|ptype| {
+            ptype
+                .filter(|ptype| ptype.typ == Type::List)
+                .map(|ptype| ptype.account_id)
+        })
+        .map_err(Into::into)
+    }
+
     async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
         self.get_value::<()>(ValueKey::from(ValueClass::Directory(
             DirectoryClass::Domain(domain.as_bytes().to_vec()),
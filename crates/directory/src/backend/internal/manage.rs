@@ -304,6 +304,13 @@ impl ManageDirectory for Store {
                 DirectoryError::Management(ManagementError::NotFound(account_id.to_string()))
             })?;
 
+        // Accounts under legal hold cannot be deleted
+        if self.legal_hold(account_id).await?.is_some() {
+            return Err(DirectoryError::Management(ManagementError::LegalHold(
+                principal.name,
+            )));
+        }
+
         // Unlink all account's blobs
         self.blob_hash_unlink_account(account_id).await?;
 
@@ -29,7 +29,7 @@ use std::{fmt::Display, slice::Iter, str::FromStr};
 use store::{write::key::KeySerializer, Deserialize, Serialize, U32_LEN};
 use utils::codec::leb128::Leb128Iterator;
 
-use crate::{Principal, Type};
+use crate::{DirectoryError, Principal, Type};
 
 pub(super) struct PrincipalIdType {
     pub account_id: u32,
@@ -155,9 +155,9 @@ pub enum PrincipalField {
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct PrincipalUpdate {
-    action: PrincipalAction,
-    field: PrincipalField,
-    value: PrincipalValue,
+    pub action: PrincipalAction,
+    pub field: PrincipalField,
+    pub value: PrincipalValue,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -204,6 +204,120 @@ impl PrincipalUpdate {
     }
 }
 
+impl Principal<String> {
+    // Applies changes to a principal fetched from an external directory,
+    // returning the members to add (true) or remove (false) from a group.
+    pub(crate) fn apply_changes(
+        &mut self,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<Vec<(bool, String)>> {
+        let mut members = Vec::new();
+
+        for change in changes {
+            match (change.action, change.field, change.value) {
+                (PrincipalAction::Set, PrincipalField::Name, PrincipalValue::String(name)) => {
+                    self.name = name.to_lowercase();
+                }
+                (PrincipalAction::Set, PrincipalField::Type, PrincipalValue::String(new_type)) => {
+                    match Type::parse(&new_type) {
+                        Some(new_type)
+                            if matches!(self.typ, Type::Individual | Type::Superuser)
+                                && matches!(new_type, Type::Individual | Type::Superuser) =>
+                        {
+                            self.typ = new_type;
+                        }
+                        _ => return Err(DirectoryError::Unsupported),
+                    }
+                }
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Secrets,
+                    PrincipalValue::StringList(secrets),
+                ) => {
+                    self.secrets = secrets;
+                }
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Description,
+                    PrincipalValue::String(description),
+                ) => {
+                    self.description = Some(description).filter(|d| !d.is_empty());
+                }
+                (PrincipalAction::Set, PrincipalField::Quota, PrincipalValue::Integer(quota)) => {
+                    self.quota = quota;
+                }
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::Emails,
+                    PrincipalValue::StringList(emails),
+                ) => {
+                    self.emails = emails.into_iter().map(|v| v.to_lowercase()).collect();
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Emails,
+                    PrincipalValue::String(email),
+                ) => {
+                    let email = email.to_lowercase();
+                    if !self.emails.contains(&email) {
+                        self.emails.push(email);
+                    }
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Emails,
+                    PrincipalValue::String(email),
+                ) => {
+                    let email = email.to_lowercase();
+                    self.emails.retain(|v| *v != email);
+                }
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::MemberOf,
+                    PrincipalValue::StringList(member_of),
+                ) => {
+                    self.member_of = member_of;
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::MemberOf,
+                    PrincipalValue::String(member_of),
+                ) => {
+                    if !self.member_of.contains(&member_of) {
+                        self.member_of.push(member_of);
+                    }
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::MemberOf,
+                    PrincipalValue::String(member_of),
+                ) => {
+                    self.member_of.retain(|v| *v != member_of);
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::Members,
+                    PrincipalValue::String(member),
+                ) => {
+                    members.push((true, member));
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::Members,
+                    PrincipalValue::String(member),
+                ) => {
+                    members.push((false, member));
+                }
+                _ => {
+                    return Err(DirectoryError::Unsupported);
+                }
+            }
+        }
+
+        Ok(members)
+    }
+}
+
 impl Display for PrincipalField {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -250,6 +364,18 @@ impl Type {
         }
     }
 
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Type::Individual => "individual",
+            Type::Superuser => "superuser",
+            Type::Group => "group",
+            Type::Resource => "resource",
+            Type::Location => "location",
+            Type::List => "list",
+            Type::Other => "other",
+        }
+    }
+
     pub fn from_u8(value: u8) -> Self {
         match value {
             0 => Type::Individual,
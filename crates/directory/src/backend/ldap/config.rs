@@ -27,9 +27,11 @@ use ldap3::LdapConnSettings;
 use store::Store;
 use utils::config::{utils::AsKey, Config};
 
-use crate::core::config::build_pool;
+use crate::{core::config::build_pool, Type};
 
-use super::{Bind, LdapConnectionManager, LdapDirectory, LdapFilter, LdapMappings};
+use super::{
+    Bind, LdapConnectionManager, LdapDirectory, LdapFilter, LdapManage, LdapMappings, LdapTemplate,
+};
 
 impl LdapDirectory {
     pub fn from_config(config: &mut Config, prefix: impl AsKey, data_store: Store) -> Option<Self> {
@@ -129,6 +131,8 @@ impl LdapDirectory {
             None
         };
 
+        let manage = LdapManage::from_config(config, &prefix);
+
         Some(LdapDirectory {
             mappings,
             pool: build_pool(config, &prefix, manager)
@@ -137,6 +141,7 @@ impl LdapDirectory {
                 })
                 .ok()?,
             auth_bind,
+            manage,
             data_store,
         })
     }
@@ -161,3 +166,59 @@ impl LdapFilter {
         Self::default()
     }
 }
+
+impl LdapManage {
+    fn from_config(config: &mut Config, prefix: &str) -> Option<Self> {
+        let mut templates = Vec::new();
+
+        for typ in [
+            Type::Individual,
+            Type::Superuser,
+            Type::Group,
+            Type::Resource,
+            Type::Location,
+            Type::List,
+        ] {
+            let key = (prefix, "manage", typ.as_str(), "dn");
+            if let Some(value) = config.value(key).map(|v| v.to_string()) {
+                let dn = value.split('?').map(|s| s.to_string()).collect::<Vec<_>>();
+                if dn.len() != 2 {
+                    config.new_parse_error(
+                        key,
+                        format!("Missing '?' parameter placeholder in value {:?}", value),
+                    );
+                    continue;
+                }
+
+                let object_class = config
+                    .values((prefix, "manage", typ.as_str(), "object-class"))
+                    .map(|(_, v)| v.to_string())
+                    .collect::<Vec<_>>();
+                if object_class.is_empty() {
+                    config.new_parse_error(
+                        (prefix, "manage", typ.as_str(), "object-class"),
+                        "Missing object classes",
+                    );
+                    continue;
+                }
+
+                templates.push(LdapTemplate {
+                    typ,
+                    dn,
+                    object_class,
+                });
+            }
+        }
+
+        if !templates.is_empty() {
+            Some(LdapManage {
+                templates,
+                attr_member: config
+                    .value((prefix, "manage.member-attribute"))
+                    .map(|v| v.to_string()),
+            })
+        } else {
+            None
+        }
+    }
+}
@@ -324,7 +324,7 @@ impl LdapDirectory {
 }
 
 impl LdapMappings {
-    fn entry_to_principal(&self, entry: SearchEntry) -> Principal<String> {
+    pub(super) fn entry_to_principal(&self, entry: SearchEntry) -> Principal<String> {
         let mut principal = Principal::default();
 
         tracing::debug!(
@@ -368,7 +368,9 @@ impl LdapMappings {
                         "posixaccount" | "individual" | "person" | "inetorgperson" => {
                             principal.typ = Type::Individual
                         }
-                        "posixgroup" | "group" => principal.typ = Type::Group,
+                        "posixgroup" | "group" | "groupofnames" | "groupofuniquenames" => {
+                            principal.typ = Type::Group
+                        }
                         _ => continue,
                     }
                     break;
@@ -0,0 +1,645 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::collections::{BTreeSet, HashSet};
+
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapError, Mod, Scope, SearchEntry};
+
+use crate::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+
+use super::{LdapDirectory, LdapManage, LdapMappings, LdapTemplate};
+
+impl LdapDirectory {
+    pub async fn create_account(
+        &self,
+        principal: Principal<String>,
+        members: Vec<String>,
+    ) -> crate::Result<u32> {
+        let manage = self.manage("create_account")?;
+        let mut principal = principal;
+        principal.name = principal.name.to_lowercase();
+
+        // Make sure the principal has a name
+        if principal.name.is_empty() {
+            return Err(DirectoryError::Management(ManagementError::MissingField(
+                PrincipalField::Name,
+            )));
+        }
+        let template = manage.template(principal.typ)?;
+        let mut conn = self.pool.get().await?;
+
+        // Make sure new name is not taken
+        if self.find_entry(&mut conn, &principal.name).await?.is_some() {
+            return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Name,
+                value: principal.name,
+            }));
+        }
+
+        // Make sure the e-mail is not taken
+        for email in principal.emails.iter_mut() {
+            *email = email.to_lowercase();
+            if self.rcpt(email).await? {
+                return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                    field: PrincipalField::Emails,
+                    value: email.to_string(),
+                }));
+            }
+        }
+
+        // Obtain group DNs
+        let mut member_of = Vec::with_capacity(principal.member_of.len());
+        if manage.attr_member.is_some() {
+            for group in &principal.member_of {
+                member_of.push(self.find_dn(&mut conn, group).await?);
+            }
+        }
+
+        // Add entry
+        let dn = template.dn(&principal.name);
+        let mut attrs = vec![(
+            "objectClass".to_string(),
+            template
+                .object_class
+                .iter()
+                .cloned()
+                .collect::<HashSet<_>>(),
+        )];
+        attrs.extend(
+            self.mappings
+                .principal_attrs(
+                    &principal,
+                    manage,
+                    &[
+                        PrincipalField::Name,
+                        PrincipalField::Description,
+                        PrincipalField::Secrets,
+                        PrincipalField::Quota,
+                        PrincipalField::Emails,
+                        PrincipalField::MemberOf,
+                    ],
+                )?
+                .into_iter()
+                .filter(|(_, values)| !values.is_empty()),
+        );
+
+        // Add members to the new entry, some object classes (such as
+        // groupOfNames) require at least one member to be present.
+        let members = if let Some(attr_member) = &manage.attr_member {
+            let mut member_dns = HashSet::with_capacity(members.len());
+            for member in &members {
+                member_dns.insert(self.find_dn(&mut conn, member).await?);
+            }
+            if !member_dns.is_empty() {
+                attrs.push((attr_member.clone(), member_dns));
+            }
+            vec![]
+        } else {
+            members
+        };
+        conn.add(&dn, attrs).await?.success()?;
+
+        // Update group memberships
+        if let Some(attr_member) = &manage.attr_member {
+            for group_dn in member_of {
+                modify_members(&mut conn, &group_dn, attr_member, &dn, true).await?;
+            }
+        }
+        for member in members {
+            self.update_member(&mut conn, manage, &principal.name, &dn, &member, true)
+                .await?;
+        }
+
+        self.data_store
+            .get_or_create_account_id(&principal.name)
+            .await
+    }
+
+    pub async fn update_account(
+        &self,
+        by: QueryBy<'_>,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        let manage = self.manage("update_account")?;
+        let name = self.account_name(by).await?;
+        let mut conn = self.pool.get().await?;
+
+        // Fetch principal
+        let (mut dn, mut current) = self
+            .find_entry(&mut conn, &name)
+            .await?
+            .ok_or_else(|| DirectoryError::Management(ManagementError::NotFound(name.clone())))?;
+        current.name = name;
+        let groups = if let Some(attr_member) = &manage.attr_member {
+            let groups = self.find_groups(&mut conn, attr_member, &dn).await?;
+            current.member_of = groups.iter().map(|(_, name)| name.clone()).collect();
+            groups
+        } else {
+            vec![]
+        };
+
+        // Apply changes
+        let mut principal = current.clone();
+        let members = principal.apply_changes(changes)?;
+        if principal.typ != current.typ {
+            return Err(DirectoryError::unsupported("ldap", "update_account"));
+        }
+        let mut fields = Vec::new();
+
+        if principal.name != current.name {
+            // Make sure new name is not taken
+            if self.find_entry(&mut conn, &principal.name).await?.is_some() {
+                return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                    field: PrincipalField::Name,
+                    value: principal.name,
+                }));
+            }
+
+            // Rename entry, keeping it under the same parent
+            let new_dn = manage.template(principal.typ)?.dn(&principal.name);
+            let (new_rdn, _) = split_dn(&new_dn);
+            conn.modifydn(&dn, new_rdn, true, None).await?.success()?;
+            let new_dn = match split_dn(&dn) {
+                (_, Some(parent)) => format!("{new_rdn},{parent}"),
+                (_, None) => new_rdn.to_string(),
+            };
+
+            // Update group references
+            if let Some(attr_member) = &manage.attr_member {
+                for (group_dn, _) in &groups {
+                    modify_members(&mut conn, group_dn, attr_member, &new_dn, true).await?;
+                    modify_members(&mut conn, group_dn, attr_member, &dn, false).await?;
+                }
+            }
+            dn = new_dn;
+
+            // Rename account id mapping
+            if let Some(account_id) = self.data_store.get_account_id(&current.name).await? {
+                self.data_store
+                    .update_account(
+                        QueryBy::Id(account_id),
+                        vec![PrincipalUpdate::set(
+                            PrincipalField::Name,
+                            PrincipalValue::String(principal.name.clone()),
+                        )],
+                    )
+                    .await?;
+            }
+
+            fields.push(PrincipalField::Name);
+        }
+        if principal.secrets != current.secrets {
+            fields.push(PrincipalField::Secrets);
+        }
+        if principal.description != current.description {
+            fields.push(PrincipalField::Description);
+        }
+        if principal.quota != current.quota {
+            fields.push(PrincipalField::Quota);
+        }
+        if principal.emails != current.emails {
+            // Make sure the e-mail is not taken
+            for email in &principal.emails {
+                if !current.emails.contains(email) && self.rcpt(email).await? {
+                    return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                        field: PrincipalField::Emails,
+                        value: email.to_string(),
+                    }));
+                }
+            }
+            fields.push(PrincipalField::Emails);
+        }
+        if principal.member_of != current.member_of && manage.attr_member.is_none() {
+            fields.push(PrincipalField::MemberOf);
+        }
+
+        // Modify entry
+        let mods = self
+            .mappings
+            .principal_attrs(&principal, manage, &fields)?
+            .into_iter()
+            .map(|(attr, values)| Mod::Replace(attr, values))
+            .collect::<Vec<_>>();
+        if !mods.is_empty() {
+            conn.modify(&dn, mods).await?.success()?;
+        }
+
+        // Update group memberships
+        if let Some(attr_member) = &manage.attr_member {
+            for group in &principal.member_of {
+                if !current.member_of.contains(group) {
+                    let group_dn = self.find_dn(&mut conn, group).await?;
+                    modify_members(&mut conn, &group_dn, attr_member, &dn, true).await?;
+                }
+            }
+            for (group_dn, group) in &groups {
+                if !principal.member_of.contains(group) {
+                    modify_members(&mut conn, group_dn, attr_member, &dn, false).await?;
+                }
+            }
+        }
+        for (add, member) in members {
+            self.update_member(&mut conn, manage, &principal.name, &dn, &member, add)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_account(&self, by: QueryBy<'_>) -> crate::Result<()> {
+        let manage = self.manage("delete_account")?;
+        let name = self.account_name(by).await?;
+        let mut conn = self.pool.get().await?;
+
+        let (dn, _) = self
+            .find_entry(&mut conn, &name)
+            .await?
+            .ok_or_else(|| DirectoryError::Management(ManagementError::NotFound(name.clone())))?;
+
+        // Remove group memberships
+        if let Some(attr_member) = &manage.attr_member {
+            for (group_dn, _) in self.find_groups(&mut conn, attr_member, &dn).await? {
+                modify_members(&mut conn, &group_dn, attr_member, &dn, false).await?;
+            }
+        }
+
+        // Delete entry
+        conn.delete(&dn).await?.success()?;
+
+        // Delete account data
+        if self.data_store.get_account_id(&name).await?.is_some() {
+            self.data_store.delete_account(QueryBy::Name(&name)).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_accounts(
+        &self,
+        filter: Option<&str>,
+        typ: Option<Type>,
+    ) -> crate::Result<Vec<String>> {
+        let (rs, _) = self
+            .pool
+            .get()
+            .await?
+            .search(
+                &self.mappings.base_dn,
+                Scope::Subtree,
+                &self.mappings.filter_name.filter.join("*"),
+                &self.mappings.attrs_principal,
+            )
+            .await?
+            .success()?;
+        let filters = filter
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|r| r.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut results = BTreeSet::new();
+        for entry in rs {
+            let principal = self
+                .mappings
+                .entry_to_principal(SearchEntry::construct(entry));
+            if principal.has_name()
+                && typ.map_or(true, |t| principal.typ == t)
+                && filters.iter().all(|f| {
+                    principal.name.to_lowercase().contains(f)
+                        || principal
+                            .description
+                            .as_ref()
+                            .map_or(false, |d| d.to_lowercase().contains(f))
+                        || principal
+                            .emails
+                            .iter()
+                            .any(|email| email.to_lowercase().contains(f))
+                })
+            {
+                results.insert(principal.name);
+            }
+        }
+
+        Ok(results.into_iter().collect())
+    }
+
+    pub async fn list_domains(&self, filter: Option<&str>) -> crate::Result<Vec<String>> {
+        // Domains are not stored in LDAP, they are derived from the e-mail addresses
+        let (rs, _) = self
+            .pool
+            .get()
+            .await?
+            .search(
+                &self.mappings.base_dn,
+                Scope::Subtree,
+                &self.mappings.filter_name.filter.join("*"),
+                &self.mappings.attr_email_address,
+            )
+            .await?
+            .success()?;
+
+        let mut results = BTreeSet::new();
+        for entry in rs {
+            for (_, values) in SearchEntry::construct(entry).attrs {
+                for email in values {
+                    if let Some((_, domain)) = email.rsplit_once('@') {
+                        let domain = domain.to_lowercase();
+                        if filter.map_or(true, |f| domain.contains(f)) {
+                            results.insert(domain);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(results.into_iter().collect())
+    }
+}
+
+impl LdapDirectory {
+    fn manage(&self, method: &str) -> crate::Result<&LdapManage> {
+        self.manage
+            .as_ref()
+            .ok_or_else(|| DirectoryError::unsupported("ldap", method))
+    }
+
+    async fn account_name(&self, by: QueryBy<'_>) -> crate::Result<String> {
+        match by {
+            QueryBy::Name(name) => Ok(name.to_string()),
+            QueryBy::Id(account_id) => self
+                .data_store
+                .get_account_name(account_id)
+                .await?
+                .ok_or_else(|| {
+                    DirectoryError::Management(ManagementError::NotFound(account_id.to_string()))
+                }),
+            QueryBy::Credentials(_) => unreachable!(),
+        }
+    }
+
+    async fn find_entry(
+        &self,
+        conn: &mut Ldap,
+        name: &str,
+    ) -> crate::Result<Option<(String, Principal<String>)>> {
+        conn.search(
+            &self.mappings.base_dn,
+            Scope::Subtree,
+            &self.mappings.filter_name.build(name),
+            &self.mappings.attrs_principal,
+        )
+        .await?
+        .success()
+        .map(|(rs, _)| {
+            rs.into_iter().next().map(|entry| {
+                let entry = SearchEntry::construct(entry);
+                (entry.dn.clone(), self.mappings.entry_to_principal(entry))
+            })
+        })
+        .map_err(Into::into)
+    }
+
+    async fn find_dn(&self, conn: &mut Ldap, name: &str) -> crate::Result<String> {
+        self.find_entry(conn, name)
+            .await?
+            .map(|(dn, _)| dn)
+            .ok_or_else(|| DirectoryError::Management(ManagementError::NotFound(name.to_string())))
+    }
+
+    async fn find_groups(
+        &self,
+        conn: &mut Ldap,
+        attr_member: &str,
+        dn: &str,
+    ) -> crate::Result<Vec<(String, String)>> {
+        let (rs, _) = conn
+            .search(
+                &self.mappings.base_dn,
+                Scope::Subtree,
+                &format!("({}={})", attr_member, ldap_escape(dn)),
+                &self.mappings.attr_name,
+            )
+            .await?
+            .success()?;
+
+        let mut groups = Vec::with_capacity(rs.len());
+        for entry in rs {
+            let entry = SearchEntry::construct(entry);
+            if let Some(name) = self
+                .mappings
+                .attr_name
+                .iter()
+                .find_map(|attr| entry.attrs.get(attr).and_then(|v| v.first()))
+            {
+                groups.push((entry.dn.clone(), name.to_string()));
+            }
+        }
+
+        Ok(groups)
+    }
+
+    async fn update_member(
+        &self,
+        conn: &mut Ldap,
+        manage: &LdapManage,
+        group: &str,
+        group_dn: &str,
+        member: &str,
+        add: bool,
+    ) -> crate::Result<()> {
+        let member_dn = self.find_dn(conn, member).await?;
+
+        if let Some(attr_member) = &manage.attr_member {
+            modify_members(conn, group_dn, attr_member, &member_dn, add).await
+        } else if let Some(attr_groups) = self.mappings.attr_groups.first() {
+            modify_members(conn, &member_dn, attr_groups, group, add).await
+        } else {
+            Err(DirectoryError::unsupported("ldap", "update_member"))
+        }
+    }
+}
+
+async fn modify_members(
+    conn: &mut Ldap,
+    dn: &str,
+    attr: &str,
+    value: &str,
+    add: bool,
+) -> crate::Result<()> {
+    let values = HashSet::from([value.to_string()]);
+    let modification = if add {
+        Mod::Add(attr.to_string(), values)
+    } else {
+        Mod::Delete(attr.to_string(), values)
+    };
+
+    match conn.modify(dn, vec![modification]).await?.success() {
+        Ok(_) => Ok(()),
+        // Ignore missing or duplicate values
+        Err(LdapError::LdapResult { result }) if [16, 20].contains(&result.rc) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl LdapManage {
+    fn template(&self, typ: Type) -> crate::Result<&LdapTemplate> {
+        self.templates
+            .iter()
+            .find(|t| t.typ == typ)
+            .or_else(|| {
+                // Superusers are stored as individuals unless configured otherwise
+                if typ == Type::Superuser {
+                    self.templates.iter().find(|t| t.typ == Type::Individual)
+                } else {
+                    None
+                }
+            })
+            .ok_or_else(|| DirectoryError::unsupported("ldap", typ.as_str()))
+    }
+}
+
+impl LdapTemplate {
+    fn dn(&self, name: &str) -> String {
+        self.dn.join(dn_escape(name).as_ref())
+    }
+}
+
+impl LdapMappings {
+    fn principal_attrs(
+        &self,
+        principal: &Principal<String>,
+        manage: &LdapManage,
+        fields: &[PrincipalField],
+    ) -> crate::Result<Vec<(String, HashSet<String>)>> {
+        let mut attrs = Vec::with_capacity(fields.len());
+
+        for field in fields {
+            let (attr, values): (_, HashSet<String>) = match field {
+                PrincipalField::Name => (
+                    self.attr_name.first(),
+                    HashSet::from([principal.name.clone()]),
+                ),
+                PrincipalField::Description => (
+                    self.attr_description.first(),
+                    principal.description.iter().cloned().collect(),
+                ),
+                PrincipalField::Secrets => (
+                    self.attr_secret.first(),
+                    principal.secrets.iter().cloned().collect(),
+                ),
+                PrincipalField::Quota => (
+                    self.attr_quota.first(),
+                    Some(principal.quota)
+                        .filter(|q| *q > 0)
+                        .map(|q| q.to_string())
+                        .into_iter()
+                        .collect(),
+                ),
+                PrincipalField::Emails => {
+                    // The first address is the primary one, the rest are aliases
+                    if let Some(attr_alias) = self.attr_email_alias.first() {
+                        attrs.push((
+                            attr_alias.clone(),
+                            principal.emails.iter().skip(1).cloned().collect(),
+                        ));
+                        (
+                            self.attr_email_address.first(),
+                            principal.emails.iter().take(1).cloned().collect(),
+                        )
+                    } else {
+                        (
+                            self.attr_email_address.first(),
+                            principal.emails.iter().cloned().collect(),
+                        )
+                    }
+                }
+                PrincipalField::MemberOf if manage.attr_member.is_none() => (
+                    self.attr_groups.first(),
+                    principal.member_of.iter().cloned().collect(),
+                ),
+                _ => continue,
+            };
+
+            match attr {
+                Some(attr) => attrs.push((attr.clone(), values)),
+                None if values.is_empty() => (),
+                None => return Err(DirectoryError::unsupported("ldap", &field.to_string())),
+            }
+        }
+
+        Ok(attrs)
+    }
+}
+
+/// Splits a DN into its first RDN and the parent DN, skipping
+/// escaped and quoted separators (RFC 4514).
+fn split_dn(dn: &str) -> (&str, Option<&str>) {
+    let mut is_escaped = false;
+    let mut is_quoted = false;
+
+    for (pos, ch) in dn.char_indices() {
+        match ch {
+            _ if is_escaped => is_escaped = false,
+            '\\' => is_escaped = true,
+            '"' => is_quoted = !is_quoted,
+            ',' | ';' if !is_quoted => {
+                return (dn[..pos].trim_end(), Some(dn[pos + 1..].trim_start()));
+            }
+            _ => {}
+        }
+    }
+
+    (dn, None)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn split_dn() {
+        for (dn, expected) in [
+            (
+                "uid=john,ou=people,dc=example,dc=org",
+                ("uid=john", Some("ou=people,dc=example,dc=org")),
+            ),
+            (
+                "cn=Doe\\, John,ou=people,dc=example,dc=org",
+                ("cn=Doe\\, John", Some("ou=people,dc=example,dc=org")),
+            ),
+            (
+                "cn=Doe\\2C John,ou=people,dc=example,dc=org",
+                ("cn=Doe\\2C John", Some("ou=people,dc=example,dc=org")),
+            ),
+            ("cn=back\\\\,ou=people", ("cn=back\\\\", Some("ou=people"))),
+            (
+                "cn=\"Doe, John\", ou=people",
+                ("cn=\"Doe, John\"", Some("ou=people")),
+            ),
+            ("dc=org", ("dc=org", None)),
+        ] {
+            assert_eq!(super::split_dn(dn), expected, "{dn}");
+        }
+    }
+}
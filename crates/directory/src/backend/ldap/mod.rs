@@ -25,14 +25,18 @@ use deadpool::managed::Pool;
 use ldap3::{ldap_escape, LdapConnSettings};
 use store::Store;
 
+use crate::Type;
+
 pub mod config;
 pub mod lookup;
+pub mod manage;
 pub mod pool;
 
 pub struct LdapDirectory {
     pool: Pool<LdapConnectionManager>,
     mappings: LdapMappings,
     auth_bind: Option<LdapFilter>,
+    manage: Option<LdapManage>,
     pub(crate) data_store: Store,
 }
 
@@ -55,6 +59,19 @@ pub struct LdapMappings {
     attrs_principal: Vec<String>,
 }
 
+#[derive(Debug, Default)]
+pub struct LdapManage {
+    templates: Vec<LdapTemplate>,
+    attr_member: Option<String>,
+}
+
+#[derive(Debug)]
+struct LdapTemplate {
+    typ: Type,
+    dn: Vec<String>,
+    object_class: Vec<String>,
+}
+
 #[derive(Debug, Default)]
 struct LdapFilter {
     filter: Vec<String>,
@@ -71,6 +71,18 @@ impl SqlDirectory {
             ("verify", &mut mappings.query_verify),
             ("expand", &mut mappings.query_expand),
             ("domains", &mut mappings.query_domains),
+            ("insert-account", &mut mappings.query_insert_account),
+            ("update-account", &mut mappings.query_update_account),
+            ("rename-account", &mut mappings.query_rename_account),
+            ("delete-account", &mut mappings.query_delete_account),
+            ("insert-email", &mut mappings.query_insert_email),
+            ("delete-emails", &mut mappings.query_delete_emails),
+            ("insert-member", &mut mappings.query_insert_member),
+            ("delete-member", &mut mappings.query_delete_member),
+            ("list-accounts", &mut mappings.query_list_accounts),
+            ("list-domains", &mut mappings.query_list_domains),
+            ("insert-domain", &mut mappings.query_insert_domain),
+            ("delete-domain", &mut mappings.query_delete_domain),
         ] {
             *query = config
                 .value(("store", store_id.as_str(), "query", query_id))
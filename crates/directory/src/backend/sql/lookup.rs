@@ -210,11 +210,8 @@ impl SqlMappings {
                         principal.secrets.push(secret.into_owned());
                     }
                 } else if name.eq_ignore_ascii_case(&self.column_type) {
-                    match value.to_str().as_ref() {
-                        "individual" | "person" | "user" => principal.typ = Type::Individual,
-                        "group" => principal.typ = Type::Group,
-                        "admin" | "superuser" | "administrator" => principal.typ = Type::Superuser,
-                        _ => (),
+                    if let Some(typ) = parse_type(value.to_str().as_ref()) {
+                        principal.typ = typ;
                     }
                 } else if name.eq_ignore_ascii_case(&self.column_description) {
                     if let Value::Text(text) = value {
@@ -231,3 +228,13 @@ impl SqlMappings {
         Ok(principal)
     }
 }
+
+pub(super) fn parse_type(value: &str) -> Option<Type> {
+    match value {
+        "individual" | "person" | "user" => Some(Type::Individual),
+        "group" => Some(Type::Group),
+        "list" => Some(Type::List),
+        "admin" | "superuser" | "administrator" => Some(Type::Superuser),
+        _ => None,
+    }
+}
@@ -0,0 +1,496 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use store::{NamedRows, Rows, Value};
+
+use crate::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+
+use super::{lookup::parse_type, SqlDirectory};
+
+impl SqlDirectory {
+    pub async fn create_account(
+        &self,
+        principal: Principal<String>,
+        members: Vec<String>,
+    ) -> crate::Result<u32> {
+        let mut principal = principal;
+        principal.name = principal.name.to_lowercase();
+
+        // Make sure the principal has a name
+        if principal.name.is_empty() {
+            return Err(DirectoryError::Management(ManagementError::MissingField(
+                PrincipalField::Name,
+            )));
+        } else if self.mappings.query_insert_account.is_empty() {
+            return Err(DirectoryError::unsupported("sql", "create_account"));
+        }
+
+        // Make sure new name is not taken
+        if self.exists(&principal.name).await? {
+            return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Name,
+                value: principal.name,
+            }));
+        }
+
+        // Make sure the e-mail is not taken
+        for email in principal.emails.iter_mut() {
+            *email = email.to_lowercase();
+            if self.rcpt(email).await? {
+                return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                    field: PrincipalField::Emails,
+                    value: email.to_string(),
+                }));
+            }
+        }
+
+        // Make sure groups and members exist
+        for name in principal.member_of.iter().chain(members.iter()) {
+            if !self.exists(name).await? {
+                return Err(DirectoryError::Management(ManagementError::NotFound(
+                    name.to_string(),
+                )));
+            }
+        }
+
+        // Insert account
+        self.execute(
+            &self.mappings.query_insert_account,
+            "insert-account",
+            vec![
+                principal.name.as_str().into(),
+                principal.typ.as_str().into(),
+                principal
+                    .secrets
+                    .first()
+                    .map_or(Value::Null, |secret| secret.into()),
+                principal
+                    .description
+                    .as_ref()
+                    .map_or(Value::Null, |description| description.into()),
+                principal.quota.into(),
+            ],
+        )
+        .await?;
+        self.insert_emails(&principal.name, &principal.emails)
+            .await?;
+        for group in &principal.member_of {
+            self.execute(
+                &self.mappings.query_insert_member,
+                "insert-member",
+                vec![principal.name.as_str().into(), group.into()],
+            )
+            .await?;
+        }
+        for member in &members {
+            self.execute(
+                &self.mappings.query_insert_member,
+                "insert-member",
+                vec![member.into(), principal.name.as_str().into()],
+            )
+            .await?;
+        }
+
+        self.data_store
+            .get_or_create_account_id(&principal.name)
+            .await
+    }
+
+    pub async fn update_account(
+        &self,
+        by: QueryBy<'_>,
+        changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        let name = self.account_name(by).await?;
+
+        // Fetch principal
+        let result = self
+            .store
+            .query::<NamedRows>(&self.mappings.query_name, vec![name.as_str().into()])
+            .await?;
+        if result.rows.is_empty() {
+            return Err(DirectoryError::Management(ManagementError::NotFound(name)));
+        }
+        let principal = self.mappings.row_to_principal(result)?;
+        let mut current = Principal {
+            id: principal.id,
+            typ: principal.typ,
+            quota: principal.quota,
+            secrets: principal.secrets,
+            description: principal.description,
+            emails: self.query_list(&self.mappings.query_emails, &name).await?,
+            member_of: self.query_list(&self.mappings.query_members, &name).await?,
+            name,
+        };
+
+        // Apply changes
+        let mut principal = current.clone();
+        let members = principal.apply_changes(changes)?;
+
+        if principal.name != current.name {
+            // Make sure new name is not taken
+            if self.exists(&principal.name).await? {
+                return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                    field: PrincipalField::Name,
+                    value: principal.name,
+                }));
+            }
+
+            self.execute(
+                &self.mappings.query_rename_account,
+                "rename-account",
+                vec![principal.name.as_str().into(), current.name.as_str().into()],
+            )
+            .await?;
+
+            // Rename account id mapping
+            if let Some(account_id) = self.data_store.get_account_id(&current.name).await? {
+                self.data_store
+                    .update_account(
+                        QueryBy::Id(account_id),
+                        vec![PrincipalUpdate::set(
+                            PrincipalField::Name,
+                            PrincipalValue::String(principal.name.clone()),
+                        )],
+                    )
+                    .await?;
+            }
+
+            // Move addresses and group memberships to the new name
+            self.execute(
+                &self.mappings.query_delete_emails,
+                "delete-emails",
+                vec![current.name.as_str().into()],
+            )
+            .await?;
+            current.emails.clear();
+            for group in std::mem::take(&mut current.member_of) {
+                self.execute(
+                    &self.mappings.query_delete_member,
+                    "delete-member",
+                    vec![current.name.as_str().into(), group.into()],
+                )
+                .await?;
+            }
+        }
+
+        if principal.typ != current.typ
+            || principal.secrets != current.secrets
+            || principal.description != current.description
+            || principal.quota != current.quota
+        {
+            self.execute(
+                &self.mappings.query_update_account,
+                "update-account",
+                vec![
+                    principal.typ.as_str().into(),
+                    principal
+                        .secrets
+                        .first()
+                        .map_or(Value::Null, |secret| secret.into()),
+                    principal
+                        .description
+                        .as_ref()
+                        .map_or(Value::Null, |description| description.into()),
+                    principal.quota.into(),
+                    principal.name.as_str().into(),
+                ],
+            )
+            .await?;
+        }
+
+        if principal.emails != current.emails {
+            // Make sure the e-mail is not taken
+            for email in &principal.emails {
+                if !current.emails.contains(email) && self.rcpt(email).await? {
+                    return Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                        field: PrincipalField::Emails,
+                        value: email.to_string(),
+                    }));
+                }
+            }
+
+            self.execute(
+                &self.mappings.query_delete_emails,
+                "delete-emails",
+                vec![principal.name.as_str().into()],
+            )
+            .await?;
+            self.insert_emails(&principal.name, &principal.emails)
+                .await?;
+        }
+
+        // Update group memberships
+        for group in &principal.member_of {
+            if !current.member_of.contains(group) {
+                if !self.exists(group).await? {
+                    return Err(DirectoryError::Management(ManagementError::NotFound(
+                        group.to_string(),
+                    )));
+                }
+                self.execute(
+                    &self.mappings.query_insert_member,
+                    "insert-member",
+                    vec![principal.name.as_str().into(), group.into()],
+                )
+                .await?;
+            }
+        }
+        for group in &current.member_of {
+            if !principal.member_of.contains(group) {
+                self.execute(
+                    &self.mappings.query_delete_member,
+                    "delete-member",
+                    vec![principal.name.as_str().into(), group.into()],
+                )
+                .await?;
+            }
+        }
+        for (add, member) in members {
+            if add {
+                if !self.exists(&member).await? {
+                    return Err(DirectoryError::Management(ManagementError::NotFound(
+                        member,
+                    )));
+                }
+                self.execute(
+                    &self.mappings.query_insert_member,
+                    "insert-member",
+                    vec![member.into(), principal.name.as_str().into()],
+                )
+                .await?;
+            } else {
+                self.execute(
+                    &self.mappings.query_delete_member,
+                    "delete-member",
+                    vec![member.into(), principal.name.as_str().into()],
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_account(&self, by: QueryBy<'_>) -> crate::Result<()> {
+        let name = self.account_name(by).await?;
+        if !self.exists(&name).await? {
+            return Err(DirectoryError::Management(ManagementError::NotFound(name)));
+        }
+
+        // Remove group memberships and addresses
+        if !self.mappings.query_delete_member.is_empty() {
+            for group in self.query_list(&self.mappings.query_members, &name).await? {
+                self.execute(
+                    &self.mappings.query_delete_member,
+                    "delete-member",
+                    vec![name.as_str().into(), group.into()],
+                )
+                .await?;
+            }
+        }
+        if !self.mappings.query_delete_emails.is_empty() {
+            self.execute(
+                &self.mappings.query_delete_emails,
+                "delete-emails",
+                vec![name.as_str().into()],
+            )
+            .await?;
+        }
+
+        // Delete account
+        self.execute(
+            &self.mappings.query_delete_account,
+            "delete-account",
+            vec![name.as_str().into()],
+        )
+        .await?;
+
+        // Delete account data
+        if self.data_store.get_account_id(&name).await?.is_some() {
+            self.data_store.delete_account(QueryBy::Name(&name)).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_accounts(
+        &self,
+        filter: Option<&str>,
+        typ: Option<Type>,
+    ) -> crate::Result<Vec<String>> {
+        if self.mappings.query_list_accounts.is_empty() {
+            return Err(DirectoryError::unsupported("sql", "list-accounts"));
+        }
+        let filters = filter
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|r| r.to_lowercase())
+            .collect::<Vec<_>>();
+
+        let mut results = Vec::new();
+        for row in self
+            .store
+            .query::<Rows>(&self.mappings.query_list_accounts, vec![])
+            .await?
+            .rows
+        {
+            let mut values = row.values.into_iter();
+            if let Some(Value::Text(name)) = values.next() {
+                let name_lower = name.to_lowercase();
+                if typ.map_or(true, |t| {
+                    values
+                        .next()
+                        .and_then(|v| parse_type(v.to_str().as_ref()))
+                        .unwrap_or_default()
+                        == t
+                }) && filters.iter().all(|f| name_lower.contains(f))
+                {
+                    results.push(name.into_owned());
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub async fn list_domains(&self, filter: Option<&str>) -> crate::Result<Vec<String>> {
+        if self.mappings.query_list_domains.is_empty() {
+            return Err(DirectoryError::unsupported("sql", "list-domains"));
+        }
+
+        self.store
+            .query::<Rows>(&self.mappings.query_list_domains, vec![])
+            .await
+            .map(|rows| {
+                Vec::<String>::from(rows)
+                    .into_iter()
+                    .filter(|domain| filter.map_or(true, |f| domain.contains(f)))
+                    .collect()
+            })
+            .map_err(Into::into)
+    }
+
+    pub async fn create_domain(&self, domain: &str) -> crate::Result<()> {
+        if !domain.contains('.') {
+            return Err(DirectoryError::Management(ManagementError::MissingField(
+                PrincipalField::Name,
+            )));
+        }
+
+        self.execute(
+            &self.mappings.query_insert_domain,
+            "insert-domain",
+            vec![domain.to_lowercase().into()],
+        )
+        .await
+    }
+
+    pub async fn delete_domain(&self, domain: &str) -> crate::Result<()> {
+        if !domain.contains('.') {
+            return Err(DirectoryError::Management(ManagementError::MissingField(
+                PrincipalField::Name,
+            )));
+        }
+
+        self.execute(
+            &self.mappings.query_delete_domain,
+            "delete-domain",
+            vec![domain.to_lowercase().into()],
+        )
+        .await
+    }
+}
+
+impl SqlDirectory {
+    async fn execute(
+        &self,
+        query: &str,
+        query_id: &str,
+        params: Vec<Value<'_>>,
+    ) -> crate::Result<()> {
+        if !query.is_empty() {
+            self.store
+                .query::<usize>(query, params)
+                .await
+                .map(|_| ())
+                .map_err(Into::into)
+        } else {
+            Err(DirectoryError::unsupported("sql", query_id))
+        }
+    }
+
+    async fn exists(&self, name: &str) -> crate::Result<bool> {
+        self.store
+            .query::<bool>(&self.mappings.query_name, vec![name.into()])
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn query_list(&self, query: &str, name: &str) -> crate::Result<Vec<String>> {
+        if !query.is_empty() {
+            self.store
+                .query::<Rows>(query, vec![name.into()])
+                .await
+                .map(Into::into)
+                .map_err(Into::into)
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    async fn insert_emails(&self, name: &str, emails: &[String]) -> crate::Result<()> {
+        // The first address is the primary one, the rest are aliases
+        for (pos, email) in emails.iter().enumerate() {
+            self.execute(
+                &self.mappings.query_insert_email,
+                "insert-email",
+                vec![
+                    name.into(),
+                    email.into(),
+                    if pos == 0 { "primary" } else { "alias" }.into(),
+                ],
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn account_name(&self, by: QueryBy<'_>) -> crate::Result<String> {
+        match by {
+            QueryBy::Name(name) => Ok(name.to_string()),
+            QueryBy::Id(account_id) => self
+                .data_store
+                .get_account_name(account_id)
+                .await?
+                .ok_or_else(|| {
+                    DirectoryError::Management(ManagementError::NotFound(account_id.to_string()))
+                }),
+            QueryBy::Credentials(_) => unreachable!(),
+        }
+    }
+}
@@ -25,6 +25,7 @@ use store::{LookupStore, Store};
 
 pub mod config;
 pub mod lookup;
+pub mod manage;
 
 pub struct SqlDirectory {
     store: LookupStore,
@@ -41,6 +42,18 @@ pub(crate) struct SqlMappings {
     query_domains: String,
     query_verify: String,
     query_expand: String,
+    query_insert_account: String,
+    query_update_account: String,
+    query_rename_account: String,
+    query_delete_account: String,
+    query_insert_email: String,
+    query_delete_emails: String,
+    query_insert_member: String,
+    query_delete_member: String,
+    query_list_accounts: String,
+    query_list_domains: String,
+    query_insert_domain: String,
+    query_delete_domain: String,
     column_description: String,
     column_secret: String,
     column_quota: String,
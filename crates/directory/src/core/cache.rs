@@ -92,6 +92,11 @@ impl CachedDirectory {
             self.cached_domains.lock().insert_neg(domain.to_string());
         }
     }
+
+    pub fn clear(&self) {
+        self.cached_domains.lock().clear();
+        self.cached_rcpts.lock().clear();
+    }
 }
 
 impl<T: Hash + Eq> LookupCache<T> {
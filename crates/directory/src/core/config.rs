@@ -26,7 +26,10 @@ use deadpool::{
     Runtime,
 };
 use std::{sync::Arc, time::Duration};
-use store::{Store, Stores};
+use store::{
+    rand::{thread_rng, RngCore},
+    Store, Stores,
+};
 use utils::config::{
     utils::{AsKey, ParseValue},
     Config,
@@ -36,17 +39,18 @@ use ahash::AHashMap;
 
 use crate::{
     backend::{
-        imap::ImapDirectory, internal::manage::ManageDirectory, ldap::LdapDirectory,
-        memory::MemoryDirectory, smtp::SmtpDirectory, sql::SqlDirectory,
+        composite::CompositeDirectory, imap::ImapDirectory, internal::manage::ManageDirectory,
+        ldap::LdapDirectory, memory::MemoryDirectory, smtp::SmtpDirectory, sql::SqlDirectory,
     },
     Directories, Directory, DirectoryInner,
 };
 
-use super::cache::CachedDirectory;
+use super::{cache::CachedDirectory, policy::PasswordPolicy};
 
 impl Directories {
     pub async fn parse(config: &mut Config, stores: &Stores, data_store: Store) -> Self {
         let mut directories = AHashMap::new();
+        let mut composites = Vec::new();
 
         for id in config
             .sub_keys("directory", ".type")
@@ -105,6 +109,11 @@ impl Directories {
                 "memory" => MemoryDirectory::from_config(config, prefix, data_store.clone())
                     .await
                     .map(DirectoryInner::Memory),
+                "composite" => {
+                    // Composite directories are built once all their members are available
+                    composites.push(id.to_string());
+                    continue;
+                }
                 unknown => {
                     let err = format!("Unknown directory type: {unknown:?}");
                     config.new_parse_error(("directory", id, "type"), err);
@@ -114,9 +123,19 @@ impl Directories {
 
             // Build directory
             if let Some(store) = store {
+                let policy_store = match &store {
+                    DirectoryInner::Internal(store) => store.clone(),
+                    _ => data_store.clone(),
+                };
                 let directory = Arc::new(Directory {
                     store,
                     cache: CachedDirectory::try_from_config(config, ("directory", id)),
+                    policy: PasswordPolicy::try_from_config(
+                        config,
+                        ("directory", id),
+                        policy_store,
+                    ),
+                    salt_key: parse_salt_key(config, id),
                 });
 
                 // Add directory
@@ -124,6 +143,22 @@ impl Directories {
             }
         }
 
+        for id in composites {
+            let id = id.as_str();
+            if let Some(store) =
+                CompositeDirectory::from_config(config, ("directory", id), &directories)
+            {
+                let directory = Arc::new(Directory {
+                    store: DirectoryInner::Composite(store),
+                    cache: CachedDirectory::try_from_config(config, ("directory", id)),
+                    policy: None,
+                    salt_key: parse_salt_key(config, id),
+                });
+
+                directories.insert(id.to_string(), directory);
+            }
+        }
+
         Directories { directories }
     }
 }
@@ -146,6 +181,7 @@ impl ConfigDirectory for Config {
         let mut config = Directories {
             directories: AHashMap::new(),
         };
+        let mut composites = Vec::new();
 
         for id in self
             .sub_keys("directory", ".type")
@@ -202,25 +238,63 @@ impl ConfigDirectory for Config {
                         .await
                         .unwrap(),
                 ),
+                "composite" => {
+                    composites.push(id.to_string());
+                    continue;
+                }
                 unknown => {
                     return Err(format!("Unknown directory type: {unknown:?}"));
                 }
             };
 
             // Build directory
+            let policy_store = match &store {
+                DirectoryInner::Internal(store) => store.clone(),
+                _ => data_store.clone(),
+            };
             let directory = Arc::new(Directory {
                 store,
                 cache: CachedDirectory::try_from_config(self, ("directory", id)),
+                policy: PasswordPolicy::try_from_config(self, ("directory", id), policy_store),
+                salt_key: parse_salt_key(self, id),
             });
 
             // Add directory
             config.directories.insert(id.to_string(), directory);
         }
 
+        for id in composites {
+            let id = id.as_str();
+            let store =
+                CompositeDirectory::from_config(self, ("directory", id), &config.directories)
+                    .ok_or_else(|| format!("Failed to build composite directory {id:?}."))?;
+            let directory = Arc::new(Directory {
+                store: DirectoryInner::Composite(store),
+                cache: CachedDirectory::try_from_config(self, ("directory", id)),
+                policy: None,
+                salt_key: parse_salt_key(self, id),
+            });
+
+            config.directories.insert(id.to_string(), directory);
+        }
+
         Ok(config)
     }
 }
 
+// Key used to derive the SCRAM salts of plain text secrets and unknown users,
+// a random key is generated if none is configured.
+fn parse_salt_key(config: &Config, id: &str) -> Vec<u8> {
+    config
+        .value(("directory", id, "scram.salt-key"))
+        .map(|key| key.as_bytes().to_vec())
+        .unwrap_or_else(|| {
+            let mut key = vec![0u8; 32];
+            thread_rng().fill_bytes(&mut key);
+            key
+        })
+}
+
 pub(crate) fn build_pool<M: Manager>(
     config: &mut Config,
     prefix: &str,
@@ -21,8 +21,23 @@
  * for more details.
 */
 
+use mail_send::Credentials;
+use store::{
+    write::{now, BatchBuilder, DirectoryClass, ValueClass},
+    Serialize, ValueKey,
+};
+
 use crate::{
-    backend::internal::lookup::DirectoryStore, Directory, DirectoryInner, Principal, QueryBy,
+    backend::internal::{
+        lookup::DirectoryStore, manage::ManageDirectory, PrincipalAction, PrincipalField,
+        PrincipalUpdate, PrincipalValue,
+    },
+    Directory, DirectoryError, DirectoryInner, ManagementError, Principal, QueryBy, Type,
+};
+
+use super::{
+    policy::HashAlgorithm,
+    secret::{is_password_secret, verify_secret_hash},
 };
 
 impl Directory {
@@ -30,6 +45,60 @@ impl Directory {
         &self,
         by: QueryBy<'_>,
         return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        if matches!(by, QueryBy::Credentials(_)) {
+            self.query_login(by, return_member_of).await
+        } else {
+            self.query_principal(by, return_member_of).await
+        }
+    }
+
+    /// Obtains a principal that is about to log in. Unlike `query`, disabled
+    /// accounts and accounts with an expired password are not returned regardless
+    /// of how the principal is looked up, which is required by mechanisms such as
+    /// SCRAM or EXTERNAL that look up principals by name.
+    pub async fn query_login(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        let principal = if let Some(principal) = self.query_principal(by, return_member_of).await? {
+            principal
+        } else {
+            return Ok(None);
+        };
+
+        // Reject disabled accounts
+        if self.is_disabled(principal.id).await? {
+            tracing::debug!(
+                context = "directory",
+                event = "disabled",
+                account = principal.name,
+                "Account is disabled"
+            );
+            return Ok(None);
+        }
+
+        // Reject expired passwords
+        if let Some(policy) = &self.policy {
+            if policy.is_expired(principal.id).await? {
+                tracing::debug!(
+                    context = "directory",
+                    event = "expired",
+                    account = principal.name,
+                    "Password has expired"
+                );
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(principal))
+    }
+
+    async fn query_principal(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
     ) -> crate::Result<Option<Principal<u32>>> {
         match &self.store {
             DirectoryInner::Internal(store) => store.query(by, return_member_of).await,
@@ -38,6 +107,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.query(by).await,
             DirectoryInner::Smtp(store) => store.query(by).await,
             DirectoryInner::Memory(store) => store.query(by).await,
+            DirectoryInner::Composite(store) => store.query(by, return_member_of).await,
         }
     }
 
@@ -49,7 +119,36 @@ impl Directory {
             DirectoryInner::Imap(store) => store.email_to_ids(email).await,
             DirectoryInner::Smtp(store) => store.email_to_ids(email).await,
             DirectoryInner::Memory(store) => store.email_to_ids(email).await,
+            DirectoryInner::Composite(store) => store.email_to_ids(email).await,
+        }
+    }
+
+    /// Maps the identities of a TLS client certificate to a principal. When an
+    /// authorization identity is requested it has to be one of the certificate identities.
+    pub async fn query_by_certificate(
+        &self,
+        identities: &[String],
+        authzid: &str,
+        return_member_of: bool,
+    ) -> crate::Result<Option<Principal<u32>>> {
+        let authzid = authzid.trim().to_lowercase();
+        for identity in identities {
+            if !authzid.is_empty() && &authzid != identity {
+                continue;
+            }
+            if let Some(principal) = self
+                .query_login(QueryBy::Name(identity), return_member_of)
+                .await?
+            {
+                return Ok(Some(principal));
+            } else if identity.contains('@') {
+                if let [id] = self.email_to_ids(identity).await?.as_slice() {
+                    return self.query_login(QueryBy::Id(*id), return_member_of).await;
+                }
+            }
         }
+
+        Ok(None)
     }
 
     pub async fn is_local_domain(&self, domain: &str) -> crate::Result<bool> {
@@ -67,6 +166,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.is_local_domain(domain).await,
             DirectoryInner::Smtp(store) => store.is_local_domain(domain).await,
             DirectoryInner::Memory(store) => store.is_local_domain(domain).await,
+            DirectoryInner::Composite(store) => store.is_local_domain(domain).await,
         }?;
 
         // Update cache
@@ -92,6 +192,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.rcpt(email).await,
             DirectoryInner::Smtp(store) => store.rcpt(email).await,
             DirectoryInner::Memory(store) => store.rcpt(email).await,
+            DirectoryInner::Composite(store) => store.rcpt(email).await,
         }?;
 
         if result {
@@ -112,6 +213,7 @@ impl Directory {
             DirectoryInner::Imap(store) => store.vrfy(address).await,
             DirectoryInner::Smtp(store) => store.vrfy(address).await,
             DirectoryInner::Memory(store) => store.vrfy(address).await,
+            DirectoryInner::Composite(store) => store.vrfy(address).await,
         }
     }
 
@@ -123,6 +225,371 @@ impl Directory {
             DirectoryInner::Imap(store) => store.expn(address).await,
             DirectoryInner::Smtp(store) => store.expn(address).await,
             DirectoryInner::Memory(store) => store.expn(address).await,
+            DirectoryInner::Composite(store) => store.expn(address).await,
+        }
+    }
+
+    pub async fn create_account(
+        &self,
+        mut principal: Principal<String>,
+        members: Vec<String>,
+    ) -> crate::Result<u32> {
+        // Enforce password policy
+        if let Some(policy) = &self.policy {
+            principal.secrets = policy
+                .prepare_secrets(None, std::mem::take(&mut principal.secrets), &[])
+                .await?;
+        }
+        self.apply_domain_settings(&mut principal).await?;
+        let membership = principal.domain_membership();
+        let secrets = if self.policy.is_some() && !principal.secrets.is_empty() {
+            principal.secrets.clone()
+        } else {
+            vec![]
+        };
+
+        let result = match &self.store {
+            DirectoryInner::Internal(store) => store.create_account(principal, members).await,
+            DirectoryInner::Ldap(store) => store.create_account(principal, members).await,
+            DirectoryInner::Sql(store) => store.create_account(principal, members).await,
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "create_account")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "create_account")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "create_account"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "create_account"))
+            }
+        }?;
+
+        if let (Some(policy), false) = (&self.policy, secrets.is_empty()) {
+            policy.record_change(result, &secrets).await?;
+        }
+        self.update_domain_usage(0, None, membership.into()).await?;
+
+        self.clear_cache();
+
+        Ok(result)
+    }
+
+    pub async fn update_account(
+        &self,
+        by: QueryBy<'_>,
+        mut changes: Vec<PrincipalUpdate>,
+    ) -> crate::Result<()> {
+        // Enforce password policy on any change to the secrets
+        let mut password_change = None;
+        if let (Some(policy), true) = (
+            &self.policy,
+            changes
+                .iter()
+                .any(|change| matches!(change.field, PrincipalField::Secrets)),
+        ) {
+            let principal = self.query_principal(by, false).await?.ok_or_else(|| {
+                DirectoryError::Management(ManagementError::NotFound(match by {
+                    QueryBy::Name(name) => name.to_string(),
+                    QueryBy::Id(account_id) => account_id.to_string(),
+                    QueryBy::Credentials(_) => String::new(),
+                }))
+            })?;
+            let mut new_secrets = Vec::new();
+            for change in changes.iter_mut() {
+                if !matches!(change.field, PrincipalField::Secrets)
+                    || matches!(change.action, PrincipalAction::RemoveItem)
+                {
+                    continue;
+                }
+                let secrets = match &mut change.value {
+                    PrincipalValue::String(secret) => std::slice::from_mut(secret),
+                    PrincipalValue::StringList(secrets) => secrets.as_mut_slice(),
+                    PrincipalValue::Integer(_) => continue,
+                };
+                let prepared = policy
+                    .prepare_secrets(principal.id.into(), secrets.to_vec(), &principal.secrets)
+                    .await?;
+                for (secret, prepared) in secrets.iter_mut().zip(prepared) {
+                    if is_password_secret(&prepared) && !principal.secrets.contains(&prepared) {
+                        new_secrets.push(prepared.clone());
+                    }
+                    *secret = prepared;
+                }
+            }
+            if !new_secrets.is_empty() {
+                password_change = Some((principal.id, new_secrets));
+            }
+        }
+
+        // Changes to the name, addresses or type move the account between domains
+        let before = if changes.iter().any(|change| {
+            matches!(
+                change.field,
+                PrincipalField::Name | PrincipalField::Emails | PrincipalField::Type
+            )
+        }) {
+            self.query_principal(by, false).await?
+        } else {
+            None
+        };
+
+        match &self.store {
+            DirectoryInner::Internal(store) => store.update_account(by, changes).await,
+            DirectoryInner::Ldap(store) => store.update_account(by, changes).await,
+            DirectoryInner::Sql(store) => store.update_account(by, changes).await,
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "update_account")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "update_account")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "update_account"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "update_account"))
+            }
+        }?;
+
+        if let (Some(policy), Some((account_id, secrets))) = (&self.policy, password_change) {
+            policy.record_change(account_id, &secrets).await?;
+        }
+        if let Some(before) = before {
+            let after = self.query_principal(QueryBy::Id(before.id), false).await?;
+            self.update_domain_usage(
+                self.used_quota(before.id).await?,
+                before.domain_membership().into(),
+                after.map(|after| after.domain_membership()),
+            )
+            .await?;
+        }
+
+        self.clear_cache();
+
+        Ok(())
+    }
+
+    pub async fn delete_account(&self, by: QueryBy<'_>) -> crate::Result<()> {
+        let principal = self.query_principal(by, false).await?;
+        let account_id = match by {
+            QueryBy::Id(account_id) => Some(account_id),
+            _ => principal.as_ref().map(|principal| principal.id),
+        };
+
+        // Accounts under legal hold cannot be deleted
+        if let (Some(account_id), Some(store)) = (account_id, self.settings_store()) {
+            if store.legal_hold(account_id).await?.is_some() {
+                return Err(DirectoryError::Management(ManagementError::LegalHold(
+                    account_id.to_string(),
+                )));
+            }
+        }
+
+        let used_quota = match account_id {
+            Some(account_id) => self.used_quota(account_id).await?,
+            None => 0,
+        };
+
+        match &self.store {
+            DirectoryInner::Internal(store) => store.delete_account(by).await,
+            DirectoryInner::Ldap(store) => store.delete_account(by).await,
+            DirectoryInner::Sql(store) => store.delete_account(by).await,
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "delete_account")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "delete_account")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "delete_account"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "delete_account"))
+            }
+        }?;
+
+        if let Some(principal) = principal {
+            self.update_domain_usage(used_quota, principal.domain_membership().into(), None)
+                .await?;
+        }
+        if let Some(account_id) = account_id {
+            if let Some(policy) = &self.policy {
+                policy.remove_history(account_id).await?;
+            }
+            if self.settings_store().is_some() {
+                self.set_retention_rules(account_id, Vec::new()).await?;
+                self.set_disabled(account_id, false).await?;
+            }
+        }
+
+        self.clear_cache();
+
+        Ok(())
+    }
+
+    pub async fn list_accounts(
+        &self,
+        filter: Option<&str>,
+        typ: Option<Type>,
+    ) -> crate::Result<Vec<String>> {
+        match &self.store {
+            DirectoryInner::Internal(store) => store.list_accounts(filter, typ).await,
+            DirectoryInner::Ldap(store) => store.list_accounts(filter, typ).await,
+            DirectoryInner::Sql(store) => store.list_accounts(filter, typ).await,
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "list_accounts")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "list_accounts")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "list_accounts"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "list_accounts"))
+            }
+        }
+    }
+
+    pub async fn create_domain(&self, domain: &str) -> crate::Result<()> {
+        match &self.store {
+            DirectoryInner::Internal(store) => store.create_domain(domain).await,
+            DirectoryInner::Sql(store) => store.create_domain(domain).await,
+            DirectoryInner::Ldap(_) => Err(DirectoryError::unsupported("ldap", "create_domain")),
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "create_domain")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "create_domain")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "create_domain"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "create_domain"))
+            }
+        }?;
+
+        self.clear_cache();
+
+        Ok(())
+    }
+
+    pub async fn delete_domain(&self, domain: &str) -> crate::Result<()> {
+        match &self.store {
+            DirectoryInner::Internal(store) => store.delete_domain(domain).await,
+            DirectoryInner::Sql(store) => store.delete_domain(domain).await,
+            DirectoryInner::Ldap(_) => Err(DirectoryError::unsupported("ldap", "delete_domain")),
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "delete_domain")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "delete_domain")),
+            DirectoryInner::Memory(_) => {
+                Err(DirectoryError::unsupported("memory", "delete_domain"))
+            }
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "delete_domain"))
+            }
+        }?;
+
+        self.remove_domain_settings(domain).await?;
+        self.clear_cache();
+
+        Ok(())
+    }
+
+    pub async fn list_domains(&self, filter: Option<&str>) -> crate::Result<Vec<String>> {
+        match &self.store {
+            DirectoryInner::Internal(store) => store.list_domains(filter).await,
+            DirectoryInner::Ldap(store) => store.list_domains(filter).await,
+            DirectoryInner::Sql(store) => store.list_domains(filter).await,
+            DirectoryInner::Imap(_) => Err(DirectoryError::unsupported("imap", "list_domains")),
+            DirectoryInner::Smtp(_) => Err(DirectoryError::unsupported("smtp", "list_domains")),
+            DirectoryInner::Memory(_) => Err(DirectoryError::unsupported("memory", "list_domains")),
+            DirectoryInner::Composite(_) => {
+                Err(DirectoryError::unsupported("composite", "list_domains"))
+            }
+        }
+    }
+
+    /// Changes the password of an account after verifying the current one.
+    /// Expired passwords are accepted here so that they can be renewed.
+    pub async fn change_password(
+        &self,
+        username: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> crate::Result<bool> {
+        let principal = if let Some(principal) = self
+            .query_principal(
+                QueryBy::Credentials(&Credentials::Plain {
+                    username: username.to_string(),
+                    secret: current_password.to_string(),
+                }),
+                false,
+            )
+            .await?
+        {
+            principal
+        } else {
+            return Ok(false);
+        };
+
+        // Application passwords cannot be used to change the account password
+        let mut is_verified = !principal.secrets.iter().any(|s| is_password_secret(s));
+        for secret in principal.secrets.iter().filter(|s| is_password_secret(s)) {
+            if verify_secret_hash(secret, current_password).await {
+                is_verified = true;
+                break;
+            }
+        }
+        if !is_verified {
+            return Ok(false);
+        }
+
+        // Hash the new password with the configured algorithm
+        let secret = if self.policy.is_some() {
+            format!("{{PLAIN}}{new_password}")
+        } else {
+            HashAlgorithm::Sha512Crypt.hash_secret(new_password).await?
+        };
+
+        // Replace the password, keeping application passwords and OTP secrets
+        let mut secrets = vec![secret];
+        secrets.extend(
+            principal
+                .secrets
+                .into_iter()
+                .filter(|secret| !is_password_secret(secret)),
+        );
+
+        self.update_account(
+            QueryBy::Id(principal.id),
+            vec![PrincipalUpdate {
+                action: PrincipalAction::Set,
+                field: PrincipalField::Secrets,
+                value: PrincipalValue::StringList(secrets),
+            }],
+        )
+        .await
+        .map(|_| true)
+    }
+
+    /// Returns `true` if the account has been disabled, in which case it is
+    /// not allowed to log in although its data and addresses are kept.
+    pub async fn is_disabled(&self, account_id: u32) -> crate::Result<bool> {
+        if let Some(store) = self.settings_store() {
+            store
+                .get_value::<u64>(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::Disabled(account_id),
+                )))
+                .await
+                .map(|disabled| disabled.is_some())
+                .map_err(Into::into)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub async fn set_disabled(&self, account_id: u32, disabled: bool) -> crate::Result<()> {
+        let store = self
+            .settings_store()
+            .ok_or_else(|| DirectoryError::unsupported(self.protocol(), "set_disabled"))?;
+        let mut batch = BatchBuilder::new();
+        let key = ValueClass::Directory(DirectoryClass::Disabled(account_id));
+        if disabled {
+            batch.set(key, now().serialize());
+        } else {
+            batch.clear(key);
+        }
+        store.write(batch.build()).await?;
+
+        Ok(())
+    }
+
+    fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
         }
     }
 }
@@ -0,0 +1,546 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use store::{
+    write::{BatchBuilder, Bincode, DirectoryClass, Operation, ValueClass, ValueOp},
+    Serialize, Store, ValueKey,
+};
+use utils::config::{utils::ParseValue, Rate};
+
+use crate::{
+    core::retention::RetentionRule, Directory, DirectoryError, DirectoryInner, ManagementError,
+    Principal, QueryBy, Type,
+};
+
+/// Settings that override the global configuration for all accounts of a domain.
+/// Zero values and missing entries mean that no domain-wide limit applies.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DomainSettings {
+    #[serde(default)]
+    pub quota: u64,
+    #[serde(default)]
+    #[serde(rename = "maxAccounts")]
+    pub max_accounts: u64,
+    #[serde(default)]
+    #[serde(rename = "defaultQuota")]
+    pub default_quota: u64,
+    #[serde(default)]
+    #[serde(rename = "sendRate")]
+    pub send_rate: Option<String>,
+    #[serde(default)]
+    #[serde(rename = "dkimSelector")]
+    pub dkim_selector: Option<String>,
+    #[serde(default)]
+    #[serde(rename = "sieveBefore")]
+    pub sieve_before: Option<String>,
+    #[serde(default)]
+    #[serde(rename = "sieveAfter")]
+    pub sieve_after: Option<String>,
+    #[serde(default)]
+    pub retention: Vec<RetentionRule>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DomainUsage {
+    pub accounts: u64,
+    pub used_quota: u64,
+}
+
+/// The domains a principal is counted in, and whether it counts as an account.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct DomainMembership {
+    pub domains: Vec<String>,
+    pub is_account: bool,
+}
+
+impl DomainSettings {
+    pub fn send_rate(&self) -> Option<Rate> {
+        self.send_rate
+            .as_deref()
+            .and_then(|rate| Rate::parse_value("sendRate", rate).ok())
+            .filter(|rate| rate.requests > 0)
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        if let Some(rate) = &self.send_rate {
+            if Rate::parse_value("sendRate", rate).is_err() {
+                return Err(DirectoryError::Management(ManagementError::InvalidValue {
+                    field: "sendRate",
+                    value: rate.to_string(),
+                }));
+            }
+        }
+        for rule in &self.retention {
+            rule.validate()?;
+        }
+        if let Some(selector) = &self.dkim_selector {
+            if selector.is_empty()
+                || !selector
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == '.')
+            {
+                return Err(DirectoryError::Management(ManagementError::InvalidValue {
+                    field: "dkimSelector",
+                    value: selector.to_string(),
+                }));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Principal<T> {
+    /// Returns the domains of the principal's name and addresses.
+    pub fn domains(&self) -> Vec<String> {
+        let mut domains = Vec::new();
+        for address in std::iter::once(&self.name).chain(self.emails.iter()) {
+            if let Some((_, domain)) = address.rsplit_once('@') {
+                let domain = domain.to_lowercase();
+                if !domains.contains(&domain) {
+                    domains.push(domain);
+                }
+            }
+        }
+        domains
+    }
+
+    pub(crate) fn domain_membership(&self) -> DomainMembership {
+        DomainMembership {
+            domains: self.domains(),
+            is_account: self.typ == Type::Individual,
+        }
+    }
+}
+
+impl Directory {
+    pub async fn domain_settings(&self, domain: &str) -> crate::Result<Option<DomainSettings>> {
+        if let Some(store) = self.settings_store() {
+            store
+                .get_value::<Bincode<DomainSettings>>(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::DomainSettings(domain.to_lowercase().into_bytes()),
+                )))
+                .await
+                .map(|settings| settings.map(|settings| settings.inner))
+                .map_err(Into::into)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub async fn set_domain_settings(
+        &self,
+        domain: &str,
+        settings: DomainSettings,
+    ) -> crate::Result<()> {
+        settings.validate()?;
+        let domain = domain.to_lowercase();
+        if !self.is_local_domain(&domain).await? {
+            return Err(DirectoryError::Management(ManagementError::NotFound(
+                domain,
+            )));
+        }
+
+        let store = self
+            .settings_store()
+            .ok_or_else(|| DirectoryError::unsupported(self.protocol(), "set_domain_settings"))?;
+        let mut batch = BatchBuilder::new();
+        let key =
+            ValueClass::Directory(DirectoryClass::DomainSettings(domain.clone().into_bytes()));
+        if settings != DomainSettings::default() {
+            batch.set(key, Bincode::new(settings).serialize());
+        } else {
+            batch.clear(key);
+        }
+        store.write(batch.build()).await?;
+
+        // Accounts managed outside the directory are not tracked, so the
+        // usage counters are recalculated whenever the settings change
+        self.refresh_domain_usage(&domain).await?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn remove_domain_settings(&self, domain: &str) -> crate::Result<()> {
+        if let Some(store) = self.settings_store() {
+            let domain = domain.to_lowercase().into_bytes();
+            let mut batch = BatchBuilder::new();
+            batch
+                .clear(ValueClass::Directory(DirectoryClass::DomainSettings(
+                    domain.clone(),
+                )))
+                .clear(ValueClass::Directory(DirectoryClass::DomainAccounts(
+                    domain.clone(),
+                )))
+                .clear(ValueClass::Directory(DirectoryClass::DomainUsedQuota(
+                    domain,
+                )));
+            store.write(batch.build()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of individual accounts of a domain and the storage used by
+    /// all its principals, as tracked by the domain usage counters.
+    pub async fn domain_usage(&self, domain: &str) -> crate::Result<DomainUsage> {
+        let store = self
+            .settings_store()
+            .ok_or_else(|| DirectoryError::unsupported(self.protocol(), "domain_usage"))?;
+        let domain = domain.to_lowercase().into_bytes();
+
+        Ok(DomainUsage {
+            accounts: store
+                .get_counter(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::DomainAccounts(domain.clone()),
+                )))
+                .await?
+                .max(0) as u64,
+            used_quota: store
+                .get_counter(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::DomainUsedQuota(domain),
+                )))
+                .await?
+                .max(0) as u64,
+        })
+    }
+
+    /// Counts the individual accounts of a domain and adds up the storage used by all
+    /// its principals, updating the domain usage counters to match. Principals belong
+    /// to a domain when their name or any of their addresses is within it.
+    pub async fn refresh_domain_usage(&self, domain: &str) -> crate::Result<DomainUsage> {
+        let store = self
+            .settings_store()
+            .ok_or_else(|| DirectoryError::unsupported(self.protocol(), "domain_usage"))?;
+        let domain = domain.to_lowercase();
+        let suffix = format!("@{domain}");
+        let mut usage = DomainUsage::default();
+
+        for name in self.list_accounts(Some(&suffix), None).await? {
+            if let Some(principal) = self.query(QueryBy::Name(&name), false).await? {
+                let membership = principal.domain_membership();
+                if membership.domains.contains(&domain) {
+                    if membership.is_account {
+                        usage.accounts += 1;
+                    }
+                    usage.used_quota += self.used_quota(principal.id).await?.max(0) as u64;
+                }
+            }
+        }
+
+        let current = self.domain_usage(&domain).await?;
+        let mut batch = BatchBuilder::new();
+        batch
+            .add(
+                ValueClass::Directory(DirectoryClass::DomainAccounts(domain.clone().into_bytes())),
+                usage.accounts as i64 - current.accounts as i64,
+            )
+            .add(
+                ValueClass::Directory(DirectoryClass::DomainUsedQuota(domain.into_bytes())),
+                usage.used_quota as i64 - current.used_quota as i64,
+            );
+        store.write(batch.build()).await?;
+
+        Ok(usage)
+    }
+
+    /// Adds the changes to the used quota of accounts in a batch to the usage
+    /// counters of their domains. Must be called before writing any batch
+    /// that updates `DirectoryClass::UsedQuota`.
+    pub async fn track_domain_usage(&self, batch: &mut BatchBuilder) -> crate::Result<()> {
+        if self.settings_store().is_none() {
+            return Ok(());
+        }
+
+        let mut changes: Vec<(u32, i64)> = Vec::new();
+        for op in &batch.ops {
+            if let Operation::Value {
+                class: ValueClass::Directory(DirectoryClass::UsedQuota(account_id)),
+                op: ValueOp::AtomicAdd(value) | ValueOp::AddAndGet(value),
+            } = op
+            {
+                match changes.iter_mut().find(|(id, _)| id == account_id) {
+                    Some((_, total)) => *total += value,
+                    None => changes.push((*account_id, *value)),
+                }
+            }
+        }
+
+        for (account_id, value) in changes {
+            if value == 0 {
+                continue;
+            }
+            if let Some(principal) = self.query(QueryBy::Id(account_id), false).await? {
+                for domain in principal.domains() {
+                    batch.add(
+                        ValueClass::Directory(DirectoryClass::DomainUsedQuota(domain.into_bytes())),
+                        value,
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the account and its used quota between the usage counters of
+    /// its domains after it has been created, updated or deleted.
+    pub(crate) async fn update_domain_usage(
+        &self,
+        used_quota: i64,
+        before: Option<DomainMembership>,
+        after: Option<DomainMembership>,
+    ) -> crate::Result<()> {
+        let (before, after) = (before.unwrap_or_default(), after.unwrap_or_default());
+        let store = match self.settings_store() {
+            Some(store) if before != after => store,
+            _ => return Ok(()),
+        };
+
+        let mut batch = BatchBuilder::new();
+        let mut domains = before.domains.clone();
+        domains.extend(
+            after
+                .domains
+                .iter()
+                .filter(|domain| !before.domains.contains(domain))
+                .cloned(),
+        );
+        for domain in domains {
+            let was_member = before.domains.contains(&domain);
+            let is_member = after.domains.contains(&domain);
+            let accounts =
+                (is_member && after.is_account) as i64 - (was_member && before.is_account) as i64;
+            let used_quota = used_quota * (is_member as i64 - was_member as i64);
+            if accounts != 0 {
+                batch.add(
+                    ValueClass::Directory(DirectoryClass::DomainAccounts(
+                        domain.clone().into_bytes(),
+                    )),
+                    accounts,
+                );
+            }
+            if used_quota != 0 {
+                batch.add(
+                    ValueClass::Directory(DirectoryClass::DomainUsedQuota(domain.into_bytes())),
+                    used_quota,
+                );
+            }
+        }
+        if !batch.is_empty() {
+            store.write(batch.build()).await?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) async fn used_quota(&self, account_id: u32) -> crate::Result<i64> {
+        if let Some(store) = self.settings_store() {
+            store
+                .get_counter(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::UsedQuota(account_id),
+                )))
+                .await
+                .map_err(Into::into)
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Returns whether storing `size` additional bytes would exceed the quota of the domain.
+    pub async fn is_domain_over_quota(&self, domain: &str, size: u64) -> crate::Result<bool> {
+        match self.domain_settings(domain).await? {
+            Some(settings) if settings.quota > 0 => self
+                .domain_usage(domain)
+                .await
+                .map(|usage| usage.used_quota + size > settings.quota),
+            _ => Ok(false),
+        }
+    }
+
+    /// Applies the account limit and default quota of the principal's domains.
+    pub(crate) async fn apply_domain_settings(
+        &self,
+        principal: &mut Principal<String>,
+    ) -> crate::Result<()> {
+        for domain in principal.domains() {
+            let settings = if let Some(settings) = self.domain_settings(&domain).await? {
+                settings
+            } else {
+                continue;
+            };
+
+            if settings.max_accounts > 0
+                && principal.typ == Type::Individual
+                && self.domain_usage(&domain).await?.accounts >= settings.max_accounts
+            {
+                return Err(DirectoryError::Management(ManagementError::LimitExceeded {
+                    domain,
+                    limit: "maxAccounts",
+                }));
+            }
+            if principal.quota == 0 && settings.default_quota > 0 {
+                principal.quota = settings.default_quota;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn settings_store(&self) -> Option<&Store> {
+        match &self.store {
+            DirectoryInner::Internal(store) => Some(store),
+            DirectoryInner::Ldap(store) => Some(&store.data_store),
+            DirectoryInner::Sql(store) => Some(&store.data_store),
+            DirectoryInner::Memory(store) => Some(&store.data_store),
+            DirectoryInner::Imap(_) | DirectoryInner::Smtp(_) | DirectoryInner::Composite(_) => {
+                None
+            }
+        }
+    }
+
+    pub(crate) fn protocol(&self) -> &'static str {
+        match &self.store {
+            DirectoryInner::Internal(_) => "internal",
+            DirectoryInner::Ldap(_) => "ldap",
+            DirectoryInner::Sql(_) => "sql",
+            DirectoryInner::Imap(_) => "imap",
+            DirectoryInner::Smtp(_) => "smtp",
+            DirectoryInner::Memory(_) => "memory",
+            DirectoryInner::Composite(_) => "composite",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::retention::{RetentionAction, RetentionRule},
+        Principal,
+    };
+
+    use super::DomainSettings;
+
+    #[test]
+    fn domain_settings() {
+        let principal = Principal::<u32> {
+            name: "jane@Example.org".to_string(),
+            emails: vec![
+                "jane@example.org".to_string(),
+                "jane.doe@example.net".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(principal.domains(), vec!["example.org", "example.net"]);
+
+        let settings = DomainSettings {
+            send_rate: Some("100/1h".to_string()),
+            dkim_selector: Some("rsa-2024".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.send_rate().unwrap().requests, 100);
+
+        for settings in [
+            DomainSettings {
+                send_rate: Some("fast".to_string()),
+                ..Default::default()
+            },
+            DomainSettings {
+                dkim_selector: Some("bad selector".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(settings.validate().is_err(), "{settings:?}");
+        }
+
+        // Unlimited rates do not apply a limit
+        let settings = DomainSettings {
+            send_rate: Some("unlimited".to_string()),
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+        assert!(settings.send_rate().is_none());
+
+        // Retention rules
+        let rule = RetentionRule {
+            role: Some("trash".to_string()),
+            mailbox: None,
+            action: RetentionAction::Delete,
+            after: Some("30d".to_string()),
+            move_to: None,
+        };
+        assert_eq!(
+            rule.after(),
+            Some(std::time::Duration::from_secs(30 * 86400))
+        );
+        let settings = DomainSettings {
+            retention: vec![
+                rule.clone(),
+                RetentionRule {
+                    role: None,
+                    mailbox: Some("Newsletters".to_string()),
+                    action: RetentionAction::Move,
+                    move_to: Some("archive".to_string()),
+                    ..rule.clone()
+                },
+                RetentionRule {
+                    action: RetentionAction::Keep,
+                    after: None,
+                    ..rule.clone()
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(settings.validate().is_ok());
+
+        for invalid in [
+            RetentionRule {
+                role: None,
+                ..rule.clone()
+            },
+            RetentionRule {
+                mailbox: Some("Inbox".to_string()),
+                ..rule.clone()
+            },
+            RetentionRule {
+                after: None,
+                ..rule.clone()
+            },
+            RetentionRule {
+                after: Some("soon".to_string()),
+                ..rule.clone()
+            },
+            RetentionRule {
+                action: RetentionAction::Move,
+                ..rule.clone()
+            },
+        ] {
+            let settings = DomainSettings {
+                retention: vec![invalid],
+                ..Default::default()
+            };
+            assert!(settings.validate().is_err(), "{settings:?}");
+        }
+    }
+}
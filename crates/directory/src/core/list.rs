@@ -0,0 +1,150 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::{
+    backend::internal::lookup::DirectoryStore, Directory, DirectoryInner, Principal, QueryBy, Type,
+};
+
+/// Requests addressed to a mailing list using a subaddress of the list
+/// address, for example `list+subscribe@example.org`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListCommand {
+    Subscribe,
+    Unsubscribe,
+    Confirm(String),
+    Approve(String),
+    Reject(String),
+    Bounces,
+}
+
+impl ListCommand {
+    /// Splits a command address into the list address and the requested command.
+    pub fn parse(address: &str) -> Option<(String, ListCommand)> {
+        let (local_part, domain) = address.rsplit_once('@')?;
+        let (list, command) = local_part.split_once('+')?;
+        let command = match command.split_once('-') {
+            Some(("confirm", token)) if is_token(token) => ListCommand::Confirm(token.to_string()),
+            Some(("approve", token)) if is_token(token) => ListCommand::Approve(token.to_string()),
+            Some(("reject", token)) if is_token(token) => ListCommand::Reject(token.to_string()),
+            Some(_) => return None,
+            None => match command {
+                "subscribe" => ListCommand::Subscribe,
+                "unsubscribe" => ListCommand::Unsubscribe,
+                "bounces" => ListCommand::Bounces,
+                _ => return None,
+            },
+        };
+
+        if !list.is_empty() && !domain.is_empty() {
+            Some((format!("{list}@{domain}"), command))
+        } else {
+            None
+        }
+    }
+
+    /// Builds the address used to send this command to a list.
+    pub fn address(&self, list_address: &str) -> String {
+        let (local_part, domain) = list_address.rsplit_once('@').unwrap_or((list_address, ""));
+        let command = match self {
+            ListCommand::Subscribe => "subscribe".into(),
+            ListCommand::Unsubscribe => "unsubscribe".into(),
+            ListCommand::Confirm(token) => format!("confirm-{token}"),
+            ListCommand::Approve(token) => format!("approve-{token}"),
+            ListCommand::Reject(token) => format!("reject-{token}"),
+            ListCommand::Bounces => "bounces".into(),
+        };
+        format!("{local_part}+{command}@{domain}")
+    }
+}
+
+fn is_token(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|ch| ch.is_ascii_alphanumeric())
+}
+
+impl Directory {
+    /// Returns the mailing list that receives messages sent to an address.
+    pub async fn query_list(&self, address: &str) -> crate::Result<Option<Principal<u32>>> {
+        let principal = match &self.store {
+            DirectoryInner::Internal(store) => match store.email_to_list(address).await? {
+                Some(list_id) => self.query(QueryBy::Id(list_id), false).await?,
+                None => None,
+            },
+            _ => self.query(QueryBy::Name(address), false).await?,
+        };
+
+        Ok(principal.filter(|principal| principal.typ == Type::List))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ListCommand;
+
+    #[test]
+    fn parse_list_command() {
+        for (address, expected) in [
+            (
+                "team+subscribe@example.org",
+                Some(("team@example.org", ListCommand::Subscribe)),
+            ),
+            (
+                "team+unsubscribe@example.org",
+                Some(("team@example.org", ListCommand::Unsubscribe)),
+            ),
+            (
+                "team+confirm-a1b2c3@example.org",
+                Some((
+                    "team@example.org",
+                    ListCommand::Confirm("a1b2c3".to_string()),
+                )),
+            ),
+            (
+                "team+approve-x9@example.org",
+                Some(("team@example.org", ListCommand::Approve("x9".to_string()))),
+            ),
+            (
+                "team+reject-x9@example.org",
+                Some(("team@example.org", ListCommand::Reject("x9".to_string()))),
+            ),
+            (
+                "team+bounces@example.org",
+                Some(("team@example.org", ListCommand::Bounces)),
+            ),
+            ("team+confirm-@example.org", None),
+            ("team+confirm-a.b@example.org", None),
+            ("team+archive@example.org", None),
+            ("team@example.org", None),
+            ("+subscribe@example.org", None),
+        ] {
+            let result = ListCommand::parse(address);
+            assert_eq!(
+                result,
+                expected.map(|(list, command)| (list.to_string(), command)),
+                "{address}"
+            );
+            if let Some((list, command)) = result {
+                assert_eq!(command.address(&list), address);
+            }
+        }
+    }
+}
@@ -24,4 +24,10 @@
 pub mod cache;
 pub mod config;
 pub mod dispatch;
+pub mod domain;
+pub mod list;
+pub mod policy;
+pub mod retention;
+pub mod roles;
+pub mod scram;
 pub mod secret;
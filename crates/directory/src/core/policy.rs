@@ -0,0 +1,592 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{fmt::Display, time::Duration};
+
+use ahash::AHashSet;
+use password_hash::{PasswordHasher, SaltString};
+use pwhash::{bcrypt, sha512_crypt};
+use sha1::{Digest, Sha1};
+use store::{
+    rand::{thread_rng, RngCore},
+    write::{key::KeySerializer, now, BatchBuilder, DirectoryClass, ValueClass},
+    Deserialize, Serialize, Store, ValueKey, U64_LEN,
+};
+use tokio::sync::oneshot;
+use utils::{
+    codec::leb128::Leb128Iterator,
+    config::{
+        utils::{AsKey, ParseValue},
+        Config,
+    },
+};
+
+use crate::{DirectoryError, ManagementError};
+
+use super::{
+    scram::{ScramHash, ScramSecret},
+    secret::{
+        app_secret, is_password_secret, verify_secret_hash, APP_SECRET_PREFIX, OTP_SECRET_PREFIX,
+    },
+};
+
+pub struct PasswordPolicy {
+    pub rules: PasswordRules,
+    pub history: usize,
+    pub max_age: Option<Duration>,
+    pub allow_hashed: bool,
+    pub hash: HashAlgorithm,
+    store: Store,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct PasswordRules {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_special: bool,
+    pub breached: AHashSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Argon2,
+    Pbkdf2,
+    Scrypt,
+    Bcrypt,
+    Sha512Crypt,
+    ScramSha256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyViolation {
+    TooShort(usize),
+    TooLong(usize),
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSpecial,
+    Breached,
+    Reused(usize),
+    HashedSecret,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PasswordHistory {
+    pub changed_at: u64,
+    pub secrets: Vec<String>,
+}
+
+impl PasswordPolicy {
+    pub fn try_from_config(config: &mut Config, prefix: impl AsKey, store: Store) -> Option<Self> {
+        let prefix = prefix.as_key();
+        if !config.has_prefix((&prefix, "password-policy")) {
+            return None;
+        }
+
+        // Load breached password list
+        let mut breached = AHashSet::new();
+        if let Some(path) = config
+            .value((&prefix, "password-policy.breached-list"))
+            .map(|path| path.to_string())
+        {
+            match std::fs::read_to_string(&path) {
+                Ok(contents) => {
+                    for line in contents.lines() {
+                        let line = line.trim_end();
+                        if !line.is_empty() && !line.starts_with('#') {
+                            breached.insert(parse_breached_entry(line));
+                        }
+                    }
+                }
+                Err(err) => {
+                    config.new_build_error(
+                        (&prefix, "password-policy.breached-list"),
+                        format!("Failed to read breached password list {path:?}: {err}"),
+                    );
+                }
+            }
+        }
+
+        Some(PasswordPolicy {
+            rules: PasswordRules {
+                min_length: config
+                    .property_or_default_((&prefix, "password-policy.min-length"), "8")
+                    .unwrap_or(8),
+                max_length: config
+                    .property_or_default_((&prefix, "password-policy.max-length"), "256")
+                    .unwrap_or(256),
+                require_lowercase: config
+                    .property_or_default_((&prefix, "password-policy.require.lowercase"), "false")
+                    .unwrap_or(false),
+                require_uppercase: config
+                    .property_or_default_((&prefix, "password-policy.require.uppercase"), "false")
+                    .unwrap_or(false),
+                require_digit: config
+                    .property_or_default_((&prefix, "password-policy.require.digit"), "false")
+                    .unwrap_or(false),
+                require_special: config
+                    .property_or_default_((&prefix, "password-policy.require.special"), "false")
+                    .unwrap_or(false),
+                breached,
+            },
+            history: config
+                .property_or_default_((&prefix, "password-policy.history"), "0")
+                .unwrap_or(0),
+            max_age: config
+                .property_or_default_::<Option<Duration>>(
+                    (&prefix, "password-policy.max-age"),
+                    "false",
+                )
+                .unwrap_or_default(),
+            allow_hashed: config
+                .property_or_default_((&prefix, "password-policy.allow-hashed"), "true")
+                .unwrap_or(true),
+            hash: config
+                .property_or_default_((&prefix, "password-policy.hash"), "sha512-crypt")
+                .unwrap_or(HashAlgorithm::Sha512Crypt),
+            store,
+        })
+    }
+
+    /// Enforces the policy on a new set of secrets, hashing any plain text
+    /// password with the configured algorithm. Previously used passwords are
+    /// obtained from the password history and the secrets being replaced.
+    /// Hashed secrets that are already present in `current_secrets` and OTP
+    /// secrets are kept as they are, application passwords are hashed but
+    /// not subject to the password rules.
+    pub async fn prepare_secrets(
+        &self,
+        account_id: Option<u32>,
+        secrets: Vec<String>,
+        current_secrets: &[String],
+    ) -> crate::Result<Vec<String>> {
+        let mut previous_secrets = Vec::new();
+        if self.history > 0 {
+            if let Some(account_id) = account_id {
+                previous_secrets = self
+                    .password_history(account_id)
+                    .await?
+                    .map(|history| history.secrets)
+                    .unwrap_or_default();
+            }
+            for secret in current_secrets {
+                if is_password_secret(secret) && !previous_secrets.contains(secret) {
+                    previous_secrets.push(secret.clone());
+                }
+            }
+        }
+
+        let mut hashed_secrets = Vec::with_capacity(secrets.len());
+        for secret in secrets {
+            if secret.starts_with(OTP_SECRET_PREFIX)
+                || (current_secrets.contains(&secret) && plain_secret(&secret).is_none())
+            {
+                hashed_secrets.push(secret);
+            } else if let Some((name, app_secret)) = app_secret(&secret) {
+                if let Some(password) = plain_secret(app_secret) {
+                    hashed_secrets.push(format!(
+                        "{APP_SECRET_PREFIX}{name}${}",
+                        self.hash.hash_secret(password).await?
+                    ));
+                } else if self.allow_hashed {
+                    hashed_secrets.push(secret);
+                } else {
+                    return Err(violation(PolicyViolation::HashedSecret));
+                }
+            } else if let Some(password) = plain_secret(&secret) {
+                self.rules.validate(password).map_err(violation)?;
+                for previous_secret in &previous_secrets {
+                    if verify_secret_hash(previous_secret, password).await {
+                        return Err(violation(PolicyViolation::Reused(self.history)));
+                    }
+                }
+                hashed_secrets.push(self.hash.hash_secret(password).await?);
+            } else if self.allow_hashed {
+                if previous_secrets.contains(&secret) {
+                    return Err(violation(PolicyViolation::Reused(self.history)));
+                }
+                hashed_secrets.push(secret);
+            } else {
+                return Err(violation(PolicyViolation::HashedSecret));
+            }
+        }
+
+        Ok(hashed_secrets)
+    }
+
+    pub async fn password_history(
+        &self,
+        account_id: u32,
+    ) -> crate::Result<Option<PasswordHistory>> {
+        self.store
+            .get_value::<PasswordHistory>(ValueKey::from(ValueClass::Directory(
+                DirectoryClass::PasswordHistory(account_id),
+            )))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Records a password change, keeping up to `history` previous secrets.
+    pub async fn record_change(&self, account_id: u32, secrets: &[String]) -> crate::Result<()> {
+        let mut history = self.password_history(account_id).await?.unwrap_or_default();
+        history.changed_at = now();
+        if self.history > 0 {
+            for secret in secrets.iter().rev() {
+                history.secrets.retain(|s| s != secret);
+                history.secrets.insert(0, secret.clone());
+            }
+            history.secrets.truncate(self.history);
+        } else {
+            history.secrets.clear();
+        }
+
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::Directory(DirectoryClass::PasswordHistory(account_id)),
+            history.serialize(),
+        );
+        self.store.write(batch.build()).await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_history(&self, account_id: u32) -> crate::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.clear(ValueClass::Directory(DirectoryClass::PasswordHistory(
+            account_id,
+        )));
+        self.store.write(batch.build()).await?;
+
+        Ok(())
+    }
+
+    /// Returns true if the password of the account is older than the
+    /// maximum age. Accounts without a recorded change never expire.
+    pub async fn is_expired(&self, account_id: u32) -> crate::Result<bool> {
+        if let Some(max_age) = self.max_age {
+            Ok(self
+                .password_history(account_id)
+                .await?
+                .is_some_and(|history| history.changed_at + max_age.as_secs() < now()))
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+impl PasswordRules {
+    /// Validates a plain text password against the length, character class
+    /// and breached password rules.
+    pub fn validate(&self, password: &str) -> Result<(), PolicyViolation> {
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PolicyViolation::TooShort(self.min_length));
+        } else if self.max_length > 0 && length > self.max_length {
+            return Err(PolicyViolation::TooLong(self.max_length));
+        }
+
+        if self.require_lowercase && !password.chars().any(|ch| ch.is_lowercase()) {
+            return Err(PolicyViolation::MissingLowercase);
+        }
+        if self.require_uppercase && !password.chars().any(|ch| ch.is_uppercase()) {
+            return Err(PolicyViolation::MissingUppercase);
+        }
+        if self.require_digit && !password.chars().any(|ch| ch.is_numeric()) {
+            return Err(PolicyViolation::MissingDigit);
+        }
+        if self.require_special && password.chars().all(|ch| ch.is_alphanumeric()) {
+            return Err(PolicyViolation::MissingSpecial);
+        }
+
+        if !self.breached.is_empty()
+            && (self.breached.contains(password) || self.breached.contains(&sha1_hex(password)))
+        {
+            return Err(PolicyViolation::Breached);
+        }
+
+        Ok(())
+    }
+}
+
+impl HashAlgorithm {
+    pub async fn hash_secret(&self, secret: &str) -> crate::Result<String> {
+        let algorithm = *self;
+        let secret = secret.to_string();
+        let (tx, rx) = oneshot::channel();
+
+        tokio::task::spawn_blocking(move || {
+            tx.send(algorithm.hash_secret_sync(&secret)).ok();
+        });
+
+        rx.await
+            .map_err(|_| DirectoryError::Pool("Thread join error".to_string()))?
+            .ok_or_else(|| DirectoryError::Pool("Failed to hash secret".to_string()))
+    }
+
+    fn hash_secret_sync(&self, secret: &str) -> Option<String> {
+        match self {
+            HashAlgorithm::Argon2 | HashAlgorithm::Pbkdf2 | HashAlgorithm::Scrypt => {
+                let mut salt = [0u8; 16];
+                thread_rng().fill_bytes(&mut salt);
+                let salt = SaltString::encode_b64(&salt).ok()?;
+                match self {
+                    HashAlgorithm::Argon2 => argon2::Argon2::default()
+                        .hash_password(secret.as_bytes(), &salt)
+                        .ok()
+                        .map(|hash| hash.to_string()),
+                    HashAlgorithm::Pbkdf2 => pbkdf2::Pbkdf2
+                        .hash_password(secret.as_bytes(), &salt)
+                        .ok()
+                        .map(|hash| hash.to_string()),
+                    _ => scrypt::Scrypt
+                        .hash_password(secret.as_bytes(), &salt)
+                        .ok()
+                        .map(|hash| hash.to_string()),
+                }
+            }
+            HashAlgorithm::Bcrypt => bcrypt::hash(secret).ok(),
+            HashAlgorithm::Sha512Crypt => sha512_crypt::hash(secret).ok(),
+            HashAlgorithm::ScramSha256 => ScramSecret::new(ScramHash::Sha256, secret)
+                .to_string()
+                .into(),
+        }
+    }
+}
+
+/// Returns the password contained in a secret if it is not hashed.
+pub fn plain_secret(secret: &str) -> Option<&str> {
+    secret
+        .strip_prefix("{PLAIN}")
+        .or_else(|| secret.strip_prefix("{plain}"))
+        .or_else(|| secret.strip_prefix("{CLEAR}"))
+        .or_else(|| secret.strip_prefix("{clear}"))
+        .or_else(|| {
+            if !secret.starts_with(['$', '_', '{']) && !secret.starts_with("SCRAM-") {
+                Some(secret)
+            } else {
+                None
+            }
+        })
+}
+
+fn violation(violation: PolicyViolation) -> DirectoryError {
+    DirectoryError::Management(ManagementError::InvalidPassword(violation))
+}
+
+fn sha1_hex(password: &str) -> String {
+    Sha1::digest(password.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect()
+}
+
+// Entries are either plain text passwords or SHA-1 hashes as
+// published by breached password services, optionally followed
+// by an occurrence count.
+fn parse_breached_entry(line: &str) -> String {
+    let hash = line.split_once(':').map_or(line, |(hash, _)| hash);
+    if hash.len() == 40 && hash.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        hash.to_ascii_uppercase()
+    } else {
+        line.to_string()
+    }
+}
+
+impl PolicyViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyViolation::TooShort(_) => "tooShort",
+            PolicyViolation::TooLong(_) => "tooLong",
+            PolicyViolation::MissingLowercase => "missingLowercase",
+            PolicyViolation::MissingUppercase => "missingUppercase",
+            PolicyViolation::MissingDigit => "missingDigit",
+            PolicyViolation::MissingSpecial => "missingSpecial",
+            PolicyViolation::Breached => "breached",
+            PolicyViolation::Reused(_) => "reused",
+            PolicyViolation::HashedSecret => "hashedSecret",
+        }
+    }
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PolicyViolation::TooShort(min) => {
+                write!(f, "Password must be at least {min} characters long.")
+            }
+            PolicyViolation::TooLong(max) => {
+                write!(f, "Password must be at most {max} characters long.")
+            }
+            PolicyViolation::MissingLowercase => {
+                write!(f, "Password must contain a lowercase letter.")
+            }
+            PolicyViolation::MissingUppercase => {
+                write!(f, "Password must contain an uppercase letter.")
+            }
+            PolicyViolation::MissingDigit => write!(f, "Password must contain a digit."),
+            PolicyViolation::MissingSpecial => {
+                write!(f, "Password must contain a special character.")
+            }
+            PolicyViolation::Breached => {
+                write!(f, "Password has appeared in a data breach.")
+            }
+            PolicyViolation::Reused(_) => write!(f, "Password has been used recently."),
+            PolicyViolation::HashedSecret => {
+                write!(f, "Password must be provided in plain text.")
+            }
+        }
+    }
+}
+
+impl ParseValue for HashAlgorithm {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        match value {
+            "argon2" => Ok(HashAlgorithm::Argon2),
+            "pbkdf2" => Ok(HashAlgorithm::Pbkdf2),
+            "scrypt" => Ok(HashAlgorithm::Scrypt),
+            "bcrypt" => Ok(HashAlgorithm::Bcrypt),
+            "sha512-crypt" => Ok(HashAlgorithm::Sha512Crypt),
+            "scram-sha-256" => Ok(HashAlgorithm::ScramSha256),
+            _ => Err(format!(
+                "Invalid value for hash algorithm {:?}: {:?}",
+                key.as_key(),
+                value
+            )),
+        }
+    }
+}
+
+impl Serialize for PasswordHistory {
+    fn serialize(self) -> Vec<u8> {
+        (&self).serialize()
+    }
+}
+
+impl Serialize for &PasswordHistory {
+    fn serialize(self) -> Vec<u8> {
+        let mut serializer = KeySerializer::new(
+            U64_LEN + 2 + self.secrets.iter().map(|s| s.len() + 2).sum::<usize>(),
+        )
+        .write(self.changed_at)
+        .write_leb128(self.secrets.len());
+        for secret in &self.secrets {
+            serializer = serializer
+                .write_leb128(secret.len())
+                .write(secret.as_bytes());
+        }
+        serializer.finalize()
+    }
+}
+
+impl Deserialize for PasswordHistory {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        deserialize_history(bytes)
+            .ok_or_else(|| store::Error::InternalError("Failed to deserialize history".into()))
+    }
+}
+
+fn deserialize_history(bytes: &[u8]) -> Option<PasswordHistory> {
+    let changed_at = u64::from_be_bytes(bytes.get(..U64_LEN)?.try_into().ok()?);
+    let mut bytes = bytes.get(U64_LEN..)?.iter();
+    let len: usize = bytes.next_leb128()?;
+    let mut secrets = Vec::with_capacity(len);
+    for _ in 0..len {
+        let len: usize = bytes.next_leb128()?;
+        let mut secret = Vec::with_capacity(len);
+        for _ in 0..len {
+            secret.push(*bytes.next()?);
+        }
+        secrets.push(String::from_utf8(secret).ok()?);
+    }
+
+    Some(PasswordHistory {
+        changed_at,
+        secrets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use store::{Deserialize, Serialize};
+
+    use super::{
+        parse_breached_entry, plain_secret, PasswordHistory, PasswordRules, PolicyViolation,
+    };
+
+    #[test]
+    fn password_policy() {
+        let mut rules = PasswordRules {
+            min_length: 8,
+            max_length: 256,
+            require_uppercase: true,
+            require_digit: true,
+            require_special: true,
+            ..Default::default()
+        };
+        rules.breached.insert(parse_breached_entry("Password123!"));
+        rules.breached.insert(parse_breached_entry(
+            "b1b3773a05c0ed0176787a4f1574ff0075f7521e:42",
+        ));
+
+        for (password, expected) in [
+            ("short", Err(PolicyViolation::TooShort(8))),
+            ("lowercase only", Err(PolicyViolation::MissingUppercase)),
+            ("Uppercase only", Err(PolicyViolation::MissingDigit)),
+            ("Uppercase1only", Err(PolicyViolation::MissingSpecial)),
+            ("Password123!", Err(PolicyViolation::Breached)),
+            ("Correct-Horse-42", Ok(())),
+        ] {
+            assert_eq!(rules.validate(password), expected, "{password}");
+        }
+
+        // SHA-1 hashed breach entries
+        let rules = PasswordRules {
+            min_length: 6,
+            require_uppercase: false,
+            require_digit: false,
+            require_special: false,
+            ..rules
+        };
+        assert_eq!(rules.validate("qwerty"), Err(PolicyViolation::Breached));
+
+        // Plain text detection
+        assert_eq!(plain_secret("secret"), Some("secret"));
+        assert_eq!(plain_secret("{PLAIN}secret"), Some("secret"));
+        assert_eq!(plain_secret("$6$rounds=5000$abc$def"), None);
+        assert_eq!(plain_secret("{SSHA}abcdef"), None);
+        assert_eq!(plain_secret("SCRAM-SHA-256$4096:abc$def:ghi"), None);
+
+        // History serialization
+        let history = PasswordHistory {
+            changed_at: 1700000000,
+            secrets: vec!["$6$abc".to_string(), "{SHA}def".to_string()],
+        };
+        assert_eq!(
+            PasswordHistory::deserialize(&(&history).serialize()).unwrap(),
+            history
+        );
+    }
+}
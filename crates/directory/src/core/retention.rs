@@ -0,0 +1,141 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use store::{
+    write::{BatchBuilder, Bincode, DirectoryClass, ValueClass},
+    Serialize, ValueKey,
+};
+use utils::config::utils::ParseValue;
+
+use crate::{Directory, DirectoryError, ManagementError};
+
+/// Action taken on messages once they are older than the age of a retention rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetentionAction {
+    /// Messages are never removed automatically, overriding less specific rules.
+    Keep,
+    /// Messages are expunged from the mailbox.
+    Delete,
+    /// Messages are moved to the mailbox with the `moveTo` role.
+    Move,
+}
+
+/// Retention rule that applies to the mailboxes with a role or to a mailbox by name.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RetentionRule {
+    #[serde(default)]
+    pub role: Option<String>,
+    #[serde(default)]
+    pub mailbox: Option<String>,
+    pub action: RetentionAction,
+    #[serde(default)]
+    pub after: Option<String>,
+    #[serde(default)]
+    #[serde(rename = "moveTo")]
+    pub move_to: Option<String>,
+}
+
+impl RetentionRule {
+    /// Returns the age after which the rule applies to a message.
+    pub fn after(&self) -> Option<Duration> {
+        self.after
+            .as_deref()
+            .and_then(|after| Duration::parse_value("after", after).ok())
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        let invalid = |field: &'static str, value: &str| {
+            Err(DirectoryError::Management(ManagementError::InvalidValue {
+                field,
+                value: value.to_string(),
+            }))
+        };
+
+        match (&self.role, &self.mailbox) {
+            (Some(role), None) if !role.is_empty() => (),
+            (None, Some(mailbox)) if !mailbox.is_empty() => (),
+            (Some(role), _) => return invalid("role", role),
+            (None, mailbox) => return invalid("mailbox", mailbox.as_deref().unwrap_or_default()),
+        }
+        if self.action != RetentionAction::Keep {
+            match &self.after {
+                Some(after) if self.after().is_none() => return invalid("after", after),
+                Some(_) => (),
+                None => return invalid("after", ""),
+            }
+        }
+        match (&self.action, &self.move_to) {
+            (RetentionAction::Move, Some(move_to)) if !move_to.is_empty() => (),
+            (RetentionAction::Move, move_to) => {
+                return invalid("moveTo", move_to.as_deref().unwrap_or_default())
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+impl Directory {
+    /// Returns the retention rules of an account.
+    pub async fn retention_rules(&self, account_id: u32) -> crate::Result<Vec<RetentionRule>> {
+        if let Some(store) = self.settings_store() {
+            store
+                .get_value::<Bincode<Vec<RetentionRule>>>(ValueKey::from(ValueClass::Directory(
+                    DirectoryClass::Retention(account_id),
+                )))
+                .await
+                .map(|rules| rules.map(|rules| rules.inner).unwrap_or_default())
+                .map_err(Into::into)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    pub async fn set_retention_rules(
+        &self,
+        account_id: u32,
+        rules: Vec<RetentionRule>,
+    ) -> crate::Result<()> {
+        for rule in &rules {
+            rule.validate()?;
+        }
+
+        let store = self
+            .settings_store()
+            .ok_or_else(|| DirectoryError::unsupported(self.protocol(), "set_retention_rules"))?;
+        let mut batch = BatchBuilder::new();
+        let key = ValueClass::Directory(DirectoryClass::Retention(account_id));
+        if !rules.is_empty() {
+            batch.set(key, Bincode::new(rules).serialize());
+        } else {
+            batch.clear(key);
+        }
+        store.write(batch.build()).await?;
+
+        Ok(())
+    }
+}
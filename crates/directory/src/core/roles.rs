@@ -0,0 +1,380 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use ahash::{AHashMap, AHashSet};
+use utils::config::{
+    utils::{AsKey, ParseValue},
+    Config,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    PrincipalList,
+    PrincipalGet,
+    PrincipalCreate,
+    PrincipalUpdate,
+    PrincipalDelete,
+    DomainList,
+    DomainGet,
+    DomainCreate,
+    DomainUpdate,
+    DomainDelete,
+    ListGet,
+    ListUpdate,
+    ListModerate,
+    QueueList,
+    QueueUpdate,
+    QueueDelete,
+    ReportList,
+    ReportDelete,
+    SettingsList,
+    SettingsUpdate,
+    SettingsReload,
+    StoreMaintenance,
+    AuditList,
+    LegalHold,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    Domains(AHashSet<String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Authorization {
+    grants: AHashMap<Permission, Scope>,
+}
+
+#[derive(Debug, Default)]
+pub struct Roles {
+    roles: Vec<Role>,
+}
+
+#[derive(Debug)]
+struct Role {
+    permissions: Vec<Permission>,
+    domains: Vec<String>,
+    members: AHashSet<String>,
+}
+
+impl Roles {
+    pub fn parse(config: &Config) -> utils::config::Result<Self> {
+        let mut roles = Vec::new();
+
+        for role_id in config.sub_keys("authorization.role", "") {
+            let mut permissions = Vec::new();
+            for result in
+                config.properties::<Permission>(("authorization.role", role_id, "permissions"))
+            {
+                let (_, permission) = result?;
+                if !permissions.contains(&permission) {
+                    permissions.push(permission);
+                }
+            }
+            if permissions.is_empty() {
+                return Err(format!("Role {role_id:?} does not grant any permissions."));
+            }
+
+            roles.push(Role {
+                permissions,
+                domains: config
+                    .values(("authorization.role", role_id, "domains"))
+                    .map(|(_, domain)| domain.trim().to_lowercase())
+                    .collect(),
+                members: config
+                    .values(("authorization.role", role_id, "members"))
+                    .map(|(_, member)| member.trim().to_lowercase())
+                    .collect(),
+            });
+        }
+
+        Ok(Roles { roles })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roles.is_empty()
+    }
+
+    /// Builds the effective permissions of a principal from all the roles
+    /// granted to it, either directly or through any of its groups.
+    pub fn authorize<'x>(
+        &self,
+        name: &str,
+        groups: impl IntoIterator<Item = &'x str> + Clone,
+    ) -> Authorization {
+        let name = name.to_lowercase();
+        let mut authorization = Authorization::default();
+
+        for role in &self.roles {
+            if role.members.contains(&name)
+                || groups
+                    .clone()
+                    .into_iter()
+                    .any(|group| role.members.contains(&group.to_lowercase()))
+            {
+                for permission in &role.permissions {
+                    authorization.grant(*permission, &role.domains);
+                }
+            }
+        }
+
+        authorization
+    }
+
+    /// Returns true when a role is granted to the principal name, roles are bound
+    /// to names so only superusers may assign, rename or add members to them.
+    pub fn is_role_member(&self, name: &str) -> bool {
+        let name = name.trim().to_lowercase();
+        self.roles.iter().any(|role| role.members.contains(&name))
+    }
+}
+
+impl Authorization {
+    pub fn superuser() -> Self {
+        let mut authorization = Authorization::default();
+        for permission in Permission::all() {
+            authorization.grants.insert(*permission, Scope::All);
+        }
+        authorization
+    }
+
+    fn grant(&mut self, permission: Permission, domains: &[String]) {
+        if domains.is_empty() {
+            self.grants.insert(permission, Scope::All);
+        } else if let Scope::Domains(current) = self
+            .grants
+            .entry(permission)
+            .or_insert_with(|| Scope::Domains(AHashSet::new()))
+        {
+            current.extend(domains.iter().cloned());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.grants.is_empty()
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.grants.contains_key(&permission)
+    }
+
+    /// Returns true when the permission applies to all domains.
+    pub fn has_global_permission(&self, permission: Permission) -> bool {
+        matches!(self.grants.get(&permission), Some(Scope::All))
+    }
+
+    pub fn scope(&self, permission: Permission) -> Option<&Scope> {
+        self.grants.get(&permission)
+    }
+}
+
+impl Scope {
+    pub fn is_all(&self) -> bool {
+        matches!(self, Scope::All)
+    }
+
+    pub fn contains_domain(&self, domain: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Domains(domains) => domains.contains(&domain.to_lowercase()),
+        }
+    }
+
+    pub fn contains_address(&self, address: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Domains(_) => address
+                .rsplit_once('@')
+                .is_some_and(|(_, domain)| self.contains_domain(domain)),
+        }
+    }
+
+    /// A principal belongs to a domain scope when its name, if it is an address,
+    /// and all its e-mail addresses belong to the scoped domains.
+    pub fn contains_principal<'x>(
+        &self,
+        name: &str,
+        emails: impl IntoIterator<Item = &'x String>,
+    ) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Domains(_) => {
+                let mut addresses = emails
+                    .into_iter()
+                    .map(|email| email.as_str())
+                    .chain(name.contains('@').then_some(name))
+                    .peekable();
+                addresses.peek().is_some()
+                    && addresses.all(|address| self.contains_address(address))
+            }
+        }
+    }
+}
+
+impl Permission {
+    pub fn all() -> &'static [Permission] {
+        &[
+            Permission::PrincipalList,
+            Permission::PrincipalGet,
+            Permission::PrincipalCreate,
+            Permission::PrincipalUpdate,
+            Permission::PrincipalDelete,
+            Permission::DomainList,
+            Permission::DomainGet,
+            Permission::DomainCreate,
+            Permission::DomainUpdate,
+            Permission::DomainDelete,
+            Permission::ListGet,
+            Permission::ListUpdate,
+            Permission::ListModerate,
+            Permission::QueueList,
+            Permission::QueueUpdate,
+            Permission::QueueDelete,
+            Permission::ReportList,
+            Permission::ReportDelete,
+            Permission::SettingsList,
+            Permission::SettingsUpdate,
+            Permission::SettingsReload,
+            Permission::StoreMaintenance,
+            Permission::AuditList,
+            Permission::LegalHold,
+        ]
+    }
+}
+
+impl ParseValue for Permission {
+    fn parse_value(key: impl AsKey, value: &str) -> utils::config::Result<Self> {
+        match value {
+            "principal-list" => Ok(Permission::PrincipalList),
+            "principal-get" => Ok(Permission::PrincipalGet),
+            "principal-create" => Ok(Permission::PrincipalCreate),
+            "principal-update" => Ok(Permission::PrincipalUpdate),
+            "principal-delete" => Ok(Permission::PrincipalDelete),
+            "domain-list" => Ok(Permission::DomainList),
+            "domain-get" => Ok(Permission::DomainGet),
+            "domain-create" => Ok(Permission::DomainCreate),
+            "domain-update" => Ok(Permission::DomainUpdate),
+            "domain-delete" => Ok(Permission::DomainDelete),
+            "list-get" => Ok(Permission::ListGet),
+            "list-update" => Ok(Permission::ListUpdate),
+            "list-moderate" => Ok(Permission::ListModerate),
+            "queue-list" => Ok(Permission::QueueList),
+            "queue-update" => Ok(Permission::QueueUpdate),
+            "queue-delete" => Ok(Permission::QueueDelete),
+            "report-list" => Ok(Permission::ReportList),
+            "report-delete" => Ok(Permission::ReportDelete),
+            "settings-list" => Ok(Permission::SettingsList),
+            "settings-update" => Ok(Permission::SettingsUpdate),
+            "settings-reload" => Ok(Permission::SettingsReload),
+            "store-maintenance" => Ok(Permission::StoreMaintenance),
+            "audit-list" => Ok(Permission::AuditList),
+            "legal-hold" => Ok(Permission::LegalHold),
+            _ => Err(format!(
+                "Invalid value for permission {key:?}: {value:?}",
+                key = key.as_key(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use utils::config::Config;
+
+    use super::{Permission, Roles};
+
+    #[test]
+    fn role_authorization() {
+        let roles = Roles::parse(
+            &Config::new(
+                r#"
+[authorization.role.acme-admin]
+permissions = ["principal-list", "principal-update", "queue-list"]
+domains = ["acme.org"]
+members = ["it@acme.org", "acme-admins"]
+
+[authorization.role.beta-admin]
+permissions = ["principal-list"]
+domains = ["beta.org"]
+members = ["acme-admins"]
+
+[authorization.role.operator]
+permissions = ["queue-list", "settings-reload"]
+members = ["ops@example.org"]
+"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        // Roles granted directly
+        let authorization = roles.authorize("IT@acme.org", []);
+        assert!(authorization.has_permission(Permission::PrincipalUpdate));
+        assert!(!authorization.has_permission(Permission::PrincipalDelete));
+        assert!(!authorization.has_global_permission(Permission::QueueList));
+        let scope = authorization.scope(Permission::PrincipalList).unwrap();
+        assert!(scope.contains_address("john@ACME.org"));
+        assert!(!scope.contains_address("john@beta.org"));
+        assert!(scope.contains_principal("john", &["john@acme.org".to_string()]));
+        assert!(!scope.contains_principal(
+            "john",
+            &["john@acme.org".to_string(), "john@beta.org".to_string()]
+        ));
+        assert!(!scope.contains_principal("john", &[]));
+
+        // Roles granted through group membership are merged
+        let authorization = roles.authorize("jane@beta.org", ["acme-admins"]);
+        let scope = authorization.scope(Permission::PrincipalList).unwrap();
+        assert!(scope.contains_domain("acme.org") && scope.contains_domain("beta.org"));
+        assert!(!authorization
+            .scope(Permission::PrincipalUpdate)
+            .unwrap()
+            .contains_domain("beta.org"));
+
+        // Global roles
+        let authorization = roles.authorize("ops@example.org", []);
+        assert!(authorization.has_global_permission(Permission::SettingsReload));
+        assert!(authorization
+            .scope(Permission::QueueList)
+            .unwrap()
+            .contains_address("anyone@anywhere.org"));
+
+        // Principals without roles
+        assert!(roles.authorize("john@acme.org", ["staff"]).is_empty());
+        assert!(roles.is_role_member("ACME-admins"));
+        assert!(!roles.is_role_member("staff"));
+
+        // Invalid permissions are rejected
+        assert!(Roles::parse(
+            &Config::new(
+                r#"
+[authorization.role.invalid]
+permissions = ["principal-explode"]
+"#
+            )
+            .unwrap()
+        )
+        .is_err());
+    }
+}
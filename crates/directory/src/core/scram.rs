@@ -0,0 +1,681 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use hmac::{Hmac, Mac};
+use mail_builder::encoders::base64::base64_encode;
+use mail_parser::decoders::base64::base64_decode;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use store::rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
+
+use super::secret::is_password_secret;
+
+pub const SCRAM_ITERATIONS: u32 = 4096;
+const SCRAM_SALT_LEN: usize = 16;
+const SCRAM_NONCE_LEN: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramHash {
+    Sha1,
+    Sha256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramSecret {
+    pub hash: ScramHash,
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScramError {
+    InvalidEncoding,
+    ExtensionsNotSupported,
+    InvalidProof,
+    ChannelBindingsDontMatch,
+    ServerDoesSupportChannelBinding,
+    ChannelBindingNotSupported,
+    UnsupportedChannelBindingType,
+    InvalidUsernameEncoding,
+    OtherError,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScramState {
+    ClientFirst,
+    ClientFinal,
+    Done,
+}
+
+/// Server side of a SCRAM exchange (RFC 5802, RFC 7677) with optional
+/// `tls-exporter` channel binding (RFC 9266).
+#[derive(Debug, Clone)]
+pub struct ScramServer {
+    hash: ScramHash,
+    is_plus: bool,
+    channel_binding: Option<Vec<u8>>,
+    state: ScramState,
+    username: String,
+    gs2_header: String,
+    uses_channel_binding: bool,
+    client_first_bare: String,
+    server_first: String,
+    nonce: String,
+    secret: Option<ScramSecret>,
+}
+
+impl ScramHash {
+    pub fn mechanism(&self, is_plus: bool) -> &'static str {
+        match (self, is_plus) {
+            (ScramHash::Sha1, false) => "SCRAM-SHA-1",
+            (ScramHash::Sha1, true) => "SCRAM-SHA-1-PLUS",
+            (ScramHash::Sha256, false) => "SCRAM-SHA-256",
+            (ScramHash::Sha256, true) => "SCRAM-SHA-256-PLUS",
+        }
+    }
+
+    fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramHash::Sha1 => {
+                let mut mac = Hmac::<Sha1>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+            ScramHash::Sha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+                mac.update(data);
+                mac.finalize().into_bytes().to_vec()
+            }
+        }
+    }
+
+    fn hash(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            ScramHash::Sha1 => Sha1::digest(data).to_vec(),
+            ScramHash::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+
+    fn salted_password(&self, password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+        match self {
+            ScramHash::Sha1 => {
+                let mut output = vec![0u8; 20];
+                pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, iterations, &mut output);
+                output
+            }
+            ScramHash::Sha256 => {
+                let mut output = vec![0u8; 32];
+                pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut output);
+                output
+            }
+        }
+    }
+}
+
+impl ScramSecret {
+    pub fn new(hash: ScramHash, password: &str) -> Self {
+        let mut salt = vec![0u8; SCRAM_SALT_LEN];
+        thread_rng().fill_bytes(&mut salt);
+        Self::derive(hash, password, salt, SCRAM_ITERATIONS)
+    }
+
+    pub fn derive(hash: ScramHash, password: &str, salt: Vec<u8>, iterations: u32) -> Self {
+        let salted_password = hash.salted_password(password.as_bytes(), &salt, iterations);
+        let client_key = hash.hmac(&salted_password, b"Client Key");
+        ScramSecret {
+            hash,
+            iterations,
+            stored_key: hash.hash(&client_key),
+            server_key: hash.hmac(&salted_password, b"Server Key"),
+            salt,
+        }
+    }
+
+    /// Parses a secret in the RFC 5803 format:
+    /// `SCRAM-SHA-256$<iterations>:<salt>$<stored-key>:<server-key>`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (mechanism, value) = value.split_once('$')?;
+        let hash = if mechanism.eq_ignore_ascii_case("SCRAM-SHA-256") {
+            ScramHash::Sha256
+        } else if mechanism.eq_ignore_ascii_case("SCRAM-SHA-1") {
+            ScramHash::Sha1
+        } else {
+            return None;
+        };
+        let (params, keys) = value.split_once('$')?;
+        let (iterations, salt) = params.split_once(':')?;
+        let (stored_key, server_key) = keys.split_once(':')?;
+
+        Some(ScramSecret {
+            hash,
+            iterations: iterations.parse().ok().filter(|&i| i > 0)?,
+            salt: base64_decode(salt.as_bytes())?,
+            stored_key: base64_decode(stored_key.as_bytes())?,
+            server_key: base64_decode(server_key.as_bytes())?,
+        })
+    }
+
+    pub fn verify_password(&self, password: &str) -> bool {
+        let secret = ScramSecret::derive(self.hash, password, self.salt.clone(), self.iterations);
+        secret.stored_key == self.stored_key && secret.server_key == self.server_key
+    }
+
+    /// Obtains a SCRAM secret from the stored secrets of a principal, deriving
+    /// it from a plain text password using `salt` if no SCRAM secret is available.
+    pub fn from_secrets(hash: ScramHash, secrets: &[String], salt: &[u8]) -> Option<Self> {
+        secrets
+            .iter()
+            .filter(|secret| secret.starts_with("SCRAM-"))
+            .filter_map(|secret| ScramSecret::parse(secret))
+            .find(|secret| secret.hash == hash)
+            .or_else(|| {
+                secrets.iter().find_map(|secret| {
+                    if let Some(secret) = secret
+                        .strip_prefix("{PLAIN}")
+                        .or_else(|| secret.strip_prefix("{plain}"))
+                        .or_else(|| secret.strip_prefix("{CLEAR}"))
+                        .or_else(|| secret.strip_prefix("{clear}"))
+                    {
+                        Some(ScramSecret::derive(
+                            hash,
+                            secret,
+                            salt.to_vec(),
+                            SCRAM_ITERATIONS,
+                        ))
+                    } else if !secret.starts_with(['$', '_', '{'])
+                        && !secret.starts_with("SCRAM-")
+                        && is_password_secret(secret)
+                    {
+                        Some(ScramSecret::derive(
+                            hash,
+                            secret,
+                            salt.to_vec(),
+                            SCRAM_ITERATIONS,
+                        ))
+                    } else {
+                        None
+                    }
+                })
+            })
+    }
+}
+
+impl Display for ScramSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}${}:{}${}:{}",
+            self.hash.mechanism(false),
+            self.iterations,
+            encode(&self.salt),
+            encode(&self.stored_key),
+            encode(&self.server_key)
+        )
+    }
+}
+
+impl ScramServer {
+    /// Creates a new exchange, `channel_binding` contains the `tls-exporter`
+    /// data of the session or `None` when the session is not using TLS.
+    pub fn new(hash: ScramHash, is_plus: bool, channel_binding: Option<Vec<u8>>) -> Self {
+        ScramServer {
+            hash,
+            is_plus,
+            channel_binding,
+            state: ScramState::ClientFirst,
+            username: String::new(),
+            gs2_header: String::new(),
+            uses_channel_binding: false,
+            client_first_bare: String::new(),
+            server_first: String::new(),
+            nonce: String::new(),
+            secret: None,
+        }
+    }
+
+    pub fn mechanism(&self) -> &'static str {
+        self.hash.mechanism(self.is_plus)
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn is_client_first(&self) -> bool {
+        self.state == ScramState::ClientFirst
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == ScramState::Done
+    }
+
+    /// Parses the client-first message and returns the username to authenticate.
+    pub fn client_first(&mut self, message: &[u8]) -> Result<&str, ScramError> {
+        if self.state != ScramState::ClientFirst {
+            return Err(ScramError::OtherError);
+        }
+        let message = std::str::from_utf8(message).map_err(|_| ScramError::InvalidEncoding)?;
+
+        // Parse GS2 header
+        let mut parts = message.splitn(3, ',');
+        let cbind_flag = parts.next().ok_or(ScramError::InvalidEncoding)?;
+        let authzid = parts.next().ok_or(ScramError::InvalidEncoding)?;
+        let client_first_bare = parts.next().ok_or(ScramError::InvalidEncoding)?;
+        match cbind_flag {
+            "n" if !self.is_plus => (),
+            "y" if !self.is_plus => {
+                if self.channel_binding.is_some() {
+                    return Err(ScramError::ServerDoesSupportChannelBinding);
+                }
+            }
+            "n" | "y" => return Err(ScramError::OtherError),
+            _ => match cbind_flag.strip_prefix("p=") {
+                Some("tls-exporter") if self.is_plus => {
+                    if self.channel_binding.is_none() {
+                        return Err(ScramError::ChannelBindingNotSupported);
+                    }
+                    self.uses_channel_binding = true;
+                }
+                Some(_) if self.is_plus => {
+                    return Err(ScramError::UnsupportedChannelBindingType);
+                }
+                Some(_) => return Err(ScramError::ChannelBindingNotSupported),
+                None => return Err(ScramError::InvalidEncoding),
+            },
+        }
+
+        // Parse client-first-message-bare
+        let mut username = None;
+        let mut nonce = None;
+        for (pos, attribute) in client_first_bare.split(',').enumerate() {
+            match (pos, attribute.split_once('=')) {
+                (0, Some(("m", _))) => return Err(ScramError::ExtensionsNotSupported),
+                (0, Some(("n", value))) => {
+                    username = decode_saslname(value)
+                        .filter(|v| !v.is_empty())
+                        .ok_or(ScramError::InvalidUsernameEncoding)?
+                        .into();
+                }
+                (1, Some(("r", value))) => {
+                    if value.is_empty() || !value.bytes().all(|ch| (0x21..=0x7e).contains(&ch)) {
+                        return Err(ScramError::InvalidEncoding);
+                    }
+                    nonce = value.into();
+                }
+                (0 | 1, _) => return Err(ScramError::InvalidEncoding),
+                _ => (),
+            }
+        }
+        let (username, nonce) = match (username, nonce) {
+            (Some(username), Some(nonce)) => (username, nonce),
+            _ => return Err(ScramError::InvalidEncoding),
+        };
+
+        // Authorization identities other than the authenticated user are not supported
+        if let Some(authzid) = authzid.strip_prefix("a=") {
+            if decode_saslname(authzid).map_or(true, |authzid| authzid != username) {
+                return Err(ScramError::OtherError);
+            }
+        } else if !authzid.is_empty() {
+            return Err(ScramError::InvalidEncoding);
+        }
+
+        self.gs2_header = message[..message.len() - client_first_bare.len()].to_string();
+        self.client_first_bare = client_first_bare.to_string();
+        self.nonce = nonce.to_string();
+        self.username = username;
+
+        Ok(&self.username)
+    }
+
+    /// Builds the server-first message using the secrets of the principal
+    /// or, if the user does not exist, a fake secret that will never match.
+    ///
+    /// Secrets that are not stored in SCRAM format and fake secrets use a salt
+    /// derived from the username and `salt_key`, so that repeated attempts
+    /// always receive the same salt and iteration count whether the user exists
+    /// or not.
+    pub fn server_first(&mut self, secrets: Option<&[String]>, salt_key: &[u8]) -> String {
+        let salt = derive_salt(salt_key, &self.username);
+        let secret = secrets
+            .and_then(|secrets| ScramSecret::from_secrets(self.hash, secrets, &salt))
+            .unwrap_or_else(|| {
+                let mut secret = ScramSecret::derive(self.hash, "", salt, SCRAM_ITERATIONS);
+                thread_rng().fill_bytes(&mut secret.stored_key);
+                secret
+            });
+        let nonce = thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(SCRAM_NONCE_LEN)
+            .map(char::from)
+            .collect::<String>();
+        self.build_server_first(secret, &nonce)
+    }
+
+    fn build_server_first(&mut self, secret: ScramSecret, server_nonce: &str) -> String {
+        self.nonce.push_str(server_nonce);
+        self.server_first = format!(
+            "r={},s={},i={}",
+            self.nonce,
+            encode(&secret.salt),
+            secret.iterations
+        );
+        self.secret = secret.into();
+        self.state = ScramState::ClientFinal;
+        self.server_first.clone()
+    }
+
+    /// Verifies the client-final message and returns the server-final message.
+    pub fn client_final(&mut self, message: &[u8]) -> Result<String, ScramError> {
+        let secret = match (self.state, self.secret.take()) {
+            (ScramState::ClientFinal, Some(secret)) => secret,
+            _ => return Err(ScramError::OtherError),
+        };
+        self.state = ScramState::Done;
+        let message = std::str::from_utf8(message).map_err(|_| ScramError::InvalidEncoding)?;
+        let (without_proof, proof) = message
+            .rsplit_once(",p=")
+            .ok_or(ScramError::InvalidEncoding)?;
+        let proof = base64_decode(proof.as_bytes()).ok_or(ScramError::InvalidEncoding)?;
+
+        // Verify channel binding and nonce
+        let mut channel_binding = None;
+        let mut nonce = None;
+        for (pos, attribute) in without_proof.split(',').enumerate() {
+            match (pos, attribute.split_once('=')) {
+                (0, Some(("c", value))) => {
+                    channel_binding = base64_decode(value.as_bytes())
+                        .ok_or(ScramError::InvalidEncoding)?
+                        .into();
+                }
+                (1, Some(("r", value))) => {
+                    nonce = value.into();
+                }
+                (0 | 1, _) => return Err(ScramError::InvalidEncoding),
+                _ => (),
+            }
+        }
+        let mut expected_binding = self.gs2_header.as_bytes().to_vec();
+        if self.uses_channel_binding {
+            expected_binding.extend_from_slice(self.channel_binding.as_deref().unwrap_or_default());
+        }
+        if channel_binding.as_ref() != Some(&expected_binding) {
+            return Err(ScramError::ChannelBindingsDontMatch);
+        } else if nonce != Some(self.nonce.as_str()) {
+            return Err(ScramError::OtherError);
+        }
+
+        // Verify proof
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, self.server_first, without_proof
+        );
+        let client_signature = self.hash.hmac(&secret.stored_key, auth_message.as_bytes());
+        if proof.len() != client_signature.len() {
+            return Err(ScramError::InvalidProof);
+        }
+        let client_key = proof
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        if self
+            .hash
+            .hash(&client_key)
+            .iter()
+            .zip(secret.stored_key.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            != 0
+        {
+            return Err(ScramError::InvalidProof);
+        }
+
+        Ok(format!(
+            "v={}",
+            encode(&self.hash.hmac(&secret.server_key, auth_message.as_bytes()))
+        ))
+    }
+}
+
+impl Display for ScramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ScramError::InvalidEncoding => "invalid-encoding",
+            ScramError::ExtensionsNotSupported => "extensions-not-supported",
+            ScramError::InvalidProof => "invalid-proof",
+            ScramError::ChannelBindingsDontMatch => "channel-bindings-dont-match",
+            ScramError::ServerDoesSupportChannelBinding => "server-does-support-channel-binding",
+            ScramError::ChannelBindingNotSupported => "channel-binding-not-supported",
+            ScramError::UnsupportedChannelBindingType => "unsupported-channel-binding-type",
+            ScramError::InvalidUsernameEncoding => "invalid-username-encoding",
+            ScramError::OtherError => "other-error",
+        })
+    }
+}
+
+fn decode_saslname(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '=' => match (chars.next(), chars.next()) {
+                (Some('2'), Some('C')) => result.push(','),
+                (Some('3'), Some('D')) => result.push('='),
+                _ => return None,
+            },
+            ',' => return None,
+            _ => result.push(ch),
+        }
+    }
+    Some(result)
+}
+
+fn derive_salt(salt_key: &[u8], username: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(salt_key).unwrap();
+    mac.update(username.as_bytes());
+    mac.finalize().into_bytes()[..SCRAM_SALT_LEN].to_vec()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use mail_parser::decoders::base64::base64_decode;
+
+    use super::{ScramError, ScramHash, ScramSecret, ScramServer};
+
+    #[test]
+    fn scram_exchange() {
+        // Test vectors from RFC 5802 and RFC 7677
+        for (hash, client_first, salt, server_nonce, server_first, client_final, server_final) in [
+            (
+                ScramHash::Sha1,
+                "n,,n=user,r=fyko+d2lbbFgONRv9qkxdawL",
+                "QSXCR+Q6sek8bf92",
+                "3rfcNHYJY1ZVvWVs7j",
+                "r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,s=QSXCR+Q6sek8bf92,i=4096",
+                "c=biws,r=fyko+d2lbbFgONRv9qkxdawL3rfcNHYJY1ZVvWVs7j,p=v0X8v3Bz2T0CJGbJQyF0X+HI4Ts=",
+                "v=rmF9pqV8S7suAoZWja4dJRkFsKQ=",
+            ),
+            (
+                ScramHash::Sha256,
+                "n,,n=user,r=rOprNGfwEbeRWgbNEkqO",
+                "W22ZaJ0SNY7soEsUEjb6gQ==",
+                "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0",
+                "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+                concat!(
+                    "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,",
+                    "p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+                ),
+                "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+            ),
+        ] {
+            let secret = ScramSecret::derive(
+                hash,
+                "pencil",
+                base64_decode(salt.as_bytes()).unwrap(),
+                4096,
+            );
+
+            // Secrets round-trip and verify plain text passwords
+            let parsed = ScramSecret::parse(&secret.to_string()).unwrap();
+            assert_eq!(parsed, secret);
+            assert!(parsed.verify_password("pencil"));
+            assert!(!parsed.verify_password("pencil2"));
+
+            // Successful exchange
+            let mut server = ScramServer::new(hash, false, None);
+            assert_eq!(server.client_first(client_first.as_bytes()).unwrap(), "user");
+            assert_eq!(
+                server.build_server_first(secret.clone(), server_nonce),
+                server_first
+            );
+            assert_eq!(
+                server.client_final(client_final.as_bytes()).unwrap(),
+                server_final
+            );
+            assert!(server.is_done());
+
+            // Invalid proof
+            let mut server = ScramServer::new(hash, false, None);
+            server.client_first(client_first.as_bytes()).unwrap();
+            server.build_server_first(
+                ScramSecret::derive(hash, "pencil2", secret.salt.clone(), 4096),
+                server_nonce,
+            );
+            assert_eq!(
+                server.client_final(client_final.as_bytes()),
+                Err(ScramError::InvalidProof)
+            );
+
+            // Channel binding downgrade
+            let mut server = ScramServer::new(hash, false, Some(vec![0u8; 32]));
+            assert_eq!(
+                server
+                    .client_first(client_first.replacen("n,", "y,", 1).as_bytes())
+                    .err(),
+                Some(ScramError::ServerDoesSupportChannelBinding)
+            );
+            let mut server = ScramServer::new(hash, true, Some(vec![0u8; 32]));
+            assert_eq!(
+                server.client_first(client_first.as_bytes()).err(),
+                Some(ScramError::OtherError)
+            );
+        }
+
+        // Channel binding with tls-exporter
+        let channel_binding = vec![0xab; 32];
+        let secret = ScramSecret::new(ScramHash::Sha256, "secret");
+        let mut server = ScramServer::new(ScramHash::Sha256, true, Some(channel_binding.clone()));
+        assert_eq!(
+            server
+                .client_first(b"p=tls-exporter,a=jane=2Cdoe,n=jane=2Cdoe,r=abcdef")
+                .unwrap(),
+            "jane,doe"
+        );
+        server.build_server_first(secret.clone(), "ghijkl");
+        let mut cbind_input = b"p=tls-exporter,a=jane=2Cdoe,".to_vec();
+        cbind_input.extend_from_slice(&channel_binding);
+        let without_proof = format!("c={},r=abcdefghijkl", super::encode(&cbind_input));
+        let auth_message = format!(
+            "n=jane=2Cdoe,r=abcdef,r=abcdefghijkl,s={},i=4096,{}",
+            super::encode(&secret.salt),
+            without_proof
+        );
+        let salted_password =
+            ScramHash::Sha256.salted_password(b"secret", &secret.salt, secret.iterations);
+        let client_key = ScramHash::Sha256.hmac(&salted_password, b"Client Key");
+        let client_signature = ScramHash::Sha256.hmac(&secret.stored_key, auth_message.as_bytes());
+        let proof = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            server
+                .client_final(format!("{},p={}", without_proof, super::encode(&proof)).as_bytes())
+                .unwrap(),
+            format!(
+                "v={}",
+                super::encode(&ScramHash::Sha256.hmac(&secret.server_key, auth_message.as_bytes()))
+            )
+        );
+
+        // Mismatched channel binding
+        let mut server = ScramServer::new(ScramHash::Sha256, true, Some(vec![0xcd; 32]));
+        server
+            .client_first(b"p=tls-exporter,,n=jane,r=abcdef")
+            .unwrap();
+        server.build_server_first(secret, "ghijkl");
+        assert_eq!(
+            server.client_final(
+                format!(
+                    "c={},r=abcdefghijkl,p={}",
+                    super::encode(&cbind_input),
+                    super::encode(&proof)
+                )
+                .as_bytes()
+            ),
+            Err(ScramError::ChannelBindingsDontMatch)
+        );
+    }
+
+    #[test]
+    fn scram_fake_salt() {
+        let server_first = |secrets: Option<&[String]>, salt_key: &[u8], username: &str| {
+            let mut server = ScramServer::new(ScramHash::Sha256, false, None);
+            server
+                .client_first(format!("n,,n={username},r=abcdef").as_bytes())
+                .unwrap();
+            let server_first = server.server_first(secrets, salt_key);
+            server_first.split_once(",s=").unwrap().1.to_string()
+        };
+
+        // Unknown users always receive the same salt and iteration count
+        let unknown = server_first(None, b"key", "unknown");
+        assert_eq!(unknown, server_first(None, b"key", "unknown"));
+        assert!(unknown.ends_with(",i=4096"), "{unknown}");
+        assert_ne!(unknown, server_first(None, b"key", "unknown2"));
+        assert_ne!(unknown, server_first(None, b"key2", "unknown"));
+
+        // Plain text secrets are indistinguishable from unknown users
+        let secrets = vec!["secret".to_string()];
+        let known = server_first(Some(&secrets), b"key", "john");
+        assert_eq!(known, server_first(Some(&secrets), b"key", "john"));
+        assert_eq!(known, server_first(None, b"key", "john"));
+
+        // Stored SCRAM secrets keep their own salt
+        let secret = ScramSecret::new(ScramHash::Sha256, "secret");
+        let secrets = vec![secret.to_string()];
+        assert_eq!(
+            server_first(Some(&secrets), b"key", "jane"),
+            format!("{},i={}", super::encode(&secret.salt), secret.iterations)
+        );
+    }
+}
@@ -36,9 +36,16 @@ use tokio::sync::oneshot;
 
 use crate::Principal;
 
+use super::scram::ScramSecret;
+
 impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
     pub async fn verify_secret(&self, secret: &str) -> bool {
         for hashed_secret in &self.secrets {
+            let hashed_secret = match app_secret(hashed_secret) {
+                Some((_, hashed_secret)) => hashed_secret,
+                None if hashed_secret.starts_with(OTP_SECRET_PREFIX) => continue,
+                None => hashed_secret,
+            };
             if verify_secret_hash(hashed_secret, secret).await {
                 return true;
             }
@@ -47,6 +54,23 @@ impl<T: serde::Serialize + serde::de::DeserializeOwned> Principal<T> {
     }
 }
 
+pub const APP_SECRET_PREFIX: &str = "$app$";
+pub const OTP_SECRET_PREFIX: &str = "otpauth://";
+
+/// Returns the name and hash of an application password, which
+/// is stored as `$app$<name>$<hash>`.
+pub fn app_secret(secret: &str) -> Option<(&str, &str)> {
+    secret
+        .strip_prefix(APP_SECRET_PREFIX)
+        .and_then(|secret| secret.split_once('$'))
+}
+
+/// Returns `true` if the secret is the account password rather than
+/// an application password or an OTP secret.
+pub fn is_password_secret(secret: &str) -> bool {
+    !secret.starts_with(APP_SECRET_PREFIX) && !secret.starts_with(OTP_SECRET_PREFIX)
+}
+
 async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
     if hashed_secret.starts_with("$argon2")
         || hashed_secret.starts_with("$pbkdf2")
@@ -109,7 +133,7 @@ async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> bool {
     }
 }
 
-async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
+pub(crate) async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
     if hashed_secret.starts_with('$') {
         verify_hash_prefix(hashed_secret, secret).await
     } else if hashed_secret.starts_with('_') {
@@ -209,6 +233,20 @@ async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> bool {
             );
             false
         }
+    } else if hashed_secret.starts_with("SCRAM-") {
+        // SCRAM salted credentials
+        match ScramSecret::parse(hashed_secret) {
+            Some(scram_secret) => scram_secret.verify_password(secret),
+            None => {
+                tracing::warn!(
+                    context = "directory",
+                    event = "error",
+                    hash = hashed_secret,
+                    "Invalid SCRAM secret"
+                );
+                false
+            }
+        }
     } else {
         hashed_secret == secret
     }
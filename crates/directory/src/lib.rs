@@ -21,11 +21,15 @@
  * for more details.
 */
 
-use core::cache::CachedDirectory;
+use core::{
+    cache::CachedDirectory,
+    policy::{PasswordPolicy, PolicyViolation},
+};
 use std::{fmt::Debug, sync::Arc};
 
 use ahash::AHashMap;
 use backend::{
+    composite::CompositeDirectory,
     imap::{ImapDirectory, ImapError},
     internal::PrincipalField,
     ldap::LdapDirectory,
@@ -44,6 +48,8 @@ pub mod core;
 pub struct Directory {
     pub store: DirectoryInner,
     pub cache: Option<CachedDirectory>,
+    pub policy: Option<PasswordPolicy>,
+    pub salt_key: Vec<u8>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -105,6 +111,16 @@ pub enum ManagementError {
         value: String,
     },
     NotFound(String),
+    InvalidPassword(PolicyViolation),
+    InvalidValue {
+        field: &'static str,
+        value: String,
+    },
+    LimitExceeded {
+        domain: String,
+        limit: &'static str,
+    },
+    LegalHold(String),
 }
 
 pub enum DirectoryInner {
@@ -114,8 +130,10 @@ pub enum DirectoryInner {
     Imap(ImapDirectory),
     Smtp(SmtpDirectory),
     Memory(MemoryDirectory),
+    Composite(CompositeDirectory),
 }
 
+#[derive(Clone, Copy)]
 pub enum QueryBy<'x> {
     Name(&'x str),
     Id(u32),
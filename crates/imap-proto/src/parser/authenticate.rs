@@ -55,8 +55,12 @@ impl Mechanism {
             Ok(Self::DigestMd5)
         } else if value.eq_ignore_ascii_case(b"SCRAM-SHA-1") {
             Ok(Self::ScramSha1)
+        } else if value.eq_ignore_ascii_case(b"SCRAM-SHA-1-PLUS") {
+            Ok(Self::ScramSha1Plus)
         } else if value.eq_ignore_ascii_case(b"SCRAM-SHA-256") {
             Ok(Self::ScramSha256)
+        } else if value.eq_ignore_ascii_case(b"SCRAM-SHA-256-PLUS") {
+            Ok(Self::ScramSha256Plus)
         } else if value.eq_ignore_ascii_case(b"APOP") {
             Ok(Self::Apop)
         } else if value.eq_ignore_ascii_case(b"NTLM") {
@@ -109,6 +113,14 @@ mod tests {
                     params: vec![],
                 },
             ),
+            (
+                "A02 AUTHENTICATE SCRAM-SHA-256-PLUS biwsbj11c2VyLHI9ck9wck5HZndFYmVSV2diTkVrcU8=\r\n",
+                authenticate::Arguments {
+                    tag: "A02".to_string(),
+                    mechanism: Mechanism::ScramSha256Plus,
+                    params: vec!["biwsbj11c2VyLHI9ck9wck5HZndFYmVSV2diTkVrcU8=".to_string()],
+                },
+            ),
         ] {
             assert_eq!(
                 receiver
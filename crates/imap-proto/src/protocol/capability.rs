@@ -153,9 +153,18 @@ impl Capability {
             ]);
         } else {
             capabilties.extend([
+                Capability::Auth(Mechanism::ScramSha256),
+                Capability::Auth(Mechanism::ScramSha1),
                 Capability::Auth(Mechanism::OAuthBearer),
                 Capability::Auth(Mechanism::Plain),
             ]);
+            if is_tls {
+                capabilties.extend([
+                    Capability::Auth(Mechanism::ScramSha256Plus),
+                    Capability::Auth(Mechanism::ScramSha1Plus),
+                    Capability::Auth(Mechanism::External),
+                ]);
+            }
         }
         if !is_tls {
             capabilties.push(Capability::StartTLS);
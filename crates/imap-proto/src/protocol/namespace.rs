@@ -25,18 +25,73 @@ use super::{quoted_string, ImapResponse};
 
 pub struct Response {
     pub shared_prefix: Option<String>,
+    pub public_prefixes: Vec<String>,
 }
 
 impl ImapResponse for Response {
     fn serialize(self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(b"* NAMESPACE ((\"\" \"/\")) ");
         if let Some(shared_prefix) = &self.shared_prefix {
-            buf.extend_from_slice(b"* NAMESPACE ((\"\" \"/\")) ((");
+            buf.extend_from_slice(b"((");
             quoted_string(&mut buf, shared_prefix);
-            buf.extend_from_slice(b" \"/\")) NIL\r\n");
+            buf.extend_from_slice(b" \"/\")) ");
         } else {
-            buf.extend_from_slice(b"* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n");
+            buf.extend_from_slice(b"NIL ");
+        }
+        if !self.public_prefixes.is_empty() {
+            buf.push(b'(');
+            for public_prefix in &self.public_prefixes {
+                buf.push(b'(');
+                quoted_string(&mut buf, public_prefix);
+                buf.extend_from_slice(b" \"/\")");
+            }
+            buf.extend_from_slice(b")\r\n");
+        } else {
+            buf.extend_from_slice(b"NIL\r\n");
         }
         buf
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::ImapResponse;
+
+    #[test]
+    fn serialize_namespace() {
+        for (shared_prefix, public_prefixes, expected) in [
+            (None, vec![], "* NAMESPACE ((\"\" \"/\")) NIL NIL\r\n"),
+            (
+                Some("Shared Folders"),
+                vec![],
+                "* NAMESPACE ((\"\" \"/\")) ((\"Shared Folders\" \"/\")) NIL\r\n",
+            ),
+            (
+                Some("Shared Folders"),
+                vec!["Public", "Archive"],
+                concat!(
+                    "* NAMESPACE ((\"\" \"/\")) ((\"Shared Folders\" \"/\")) ",
+                    "((\"Public\" \"/\")(\"Archive\" \"/\"))\r\n"
+                ),
+            ),
+            (
+                None,
+                vec!["Public"],
+                "* NAMESPACE ((\"\" \"/\")) NIL ((\"Public\" \"/\"))\r\n",
+            ),
+        ] {
+            assert_eq!(
+                String::from_utf8(
+                    super::Response {
+                        shared_prefix: shared_prefix.map(String::from),
+                        public_prefixes: public_prefixes.into_iter().map(String::from).collect(),
+                    }
+                    .serialize()
+                )
+                .unwrap(),
+                expected
+            );
+        }
+    }
+}
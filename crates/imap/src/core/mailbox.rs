@@ -51,19 +51,7 @@ impl<T: SessionStream> SessionData<T> {
             match session
                 .fetch_account_mailboxes(
                     account_id,
-                    format!(
-                        "{}/{}",
-                        session.imap.name_shared,
-                        session
-                            .jmap
-                            .directory
-                            .query(QueryBy::Id(account_id), false)
-                            .await
-                            .unwrap_or_default()
-                            .map(|p| p.name)
-                            .unwrap_or_else(|| Id::from(account_id).to_string())
-                    )
-                    .into(),
+                    session.account_prefix(account_id).await.into(),
                     access_token,
                 )
                 .await
@@ -82,6 +70,27 @@ impl<T: SessionStream> SessionData<T> {
         Ok(session)
     }
 
+    /// Returns the prefix under which the mailboxes of a shared account are
+    /// listed, which is the namespace name for public folder accounts.
+    async fn account_prefix(&self, account_id: u32) -> String {
+        match self
+            .jmap
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .unwrap_or_default()
+        {
+            Some(principal) => {
+                if let Some(folder) = self.jmap.public_folder(&principal.name) {
+                    folder.namespace.clone()
+                } else {
+                    format!("{}/{}", self.imap.name_shared, principal.name)
+                }
+            }
+            None => format!("{}/{}", self.imap.name_shared, Id::from(account_id)),
+        }
+    }
+
     async fn fetch_account_mailboxes(
         &self,
         account_id: u32,
@@ -239,7 +248,12 @@ impl<T: SessionStream> SessionData<T> {
                                 .into(),
                             total_unseen: self
                                 .jmap
-                                .mailbox_unread_tags(account_id, *mailbox_id, &message_ids)
+                                .mailbox_unread_tags(
+                                    access_token,
+                                    account_id,
+                                    *mailbox_id,
+                                    &message_ids,
+                                )
                                 .await
                                 .map_err(|_| {})?
                                 .map(|v| v.len() as u32)
@@ -345,17 +359,7 @@ impl<T: SessionStream> SessionData<T> {
 
             // Fetch mailboxes for each new shared account
             for account_id in added_account_ids {
-                let prefix = format!(
-                    "{}/{}",
-                    self.imap.name_shared,
-                    self.jmap
-                        .directory
-                        .query(QueryBy::Id(account_id), false)
-                        .await
-                        .unwrap_or_default()
-                        .map(|p| p.name)
-                        .unwrap_or_else(|| Id::from(account_id).to_string())
-                );
+                let prefix = self.account_prefix(account_id).await;
                 match self
                     .fetch_account_mailboxes(account_id, prefix.into(), &access_token)
                     .await
@@ -457,18 +461,7 @@ impl<T: SessionStream> SessionData<T> {
                 } else {
                     // Refresh mailboxes for changed account
                     let mailbox_prefix = if !access_token.is_primary_id(account_id) {
-                        format!(
-                            "{}/{}",
-                            self.imap.name_shared,
-                            self.jmap
-                                .directory
-                                .query(QueryBy::Id(account_id), false)
-                                .await
-                                .unwrap_or_default()
-                                .map(|p| p.name)
-                                .unwrap_or_else(|| Id::from(account_id).to_string())
-                        )
-                        .into()
+                        self.account_prefix(account_id).await.into()
                     } else {
                         None
                     };
@@ -596,7 +589,11 @@ impl<T: SessionStream> SessionData<T> {
                     Property::Value,
                 )
                 .await?
-                .map(|mailbox| mailbox.effective_acl(&access_token).contains(item))
+                .map(|mailbox| {
+                    mailbox
+                        .effective_acl(&access_token, account_id)
+                        .contains(item)
+                })
                 .ok_or_else(|| StatusResponse::no("Mailbox no longer exists."))?)
     }
 }
@@ -36,7 +36,7 @@ use imap_proto::{
     Command, ResponseCode, StatusResponse,
 };
 use jmap::{
-    auth::{rate_limit::ConcurrencyLimiters, AccessToken},
+    auth::{rate_limit::ConcurrencyLimiters, sasl::ScramExchange, AccessToken},
     JMAP,
 };
 use tokio::{
@@ -102,6 +102,9 @@ pub struct Session<T: SessionStream> {
     pub in_flight: InFlight,
     pub remote_addr: IpAddr,
     pub span: tracing::Span,
+    pub tls_channel_binding: Option<Vec<u8>>,
+    pub tls_client_certificate: Option<Vec<u8>>,
+    pub sasl: Option<SaslExchange>,
 }
 
 pub struct SessionData<T: SessionStream> {
@@ -218,6 +221,12 @@ pub enum State<T: SessionStream> {
     },
 }
 
+pub enum SaslExchange {
+    Scram(Box<ScramExchange>),
+    External,
+    Success(AccessToken),
+}
+
 impl<T: SessionStream> State<T> {
     pub fn try_replace_stream_tx<U: SessionStream>(
         self,
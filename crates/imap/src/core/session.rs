@@ -127,6 +127,10 @@ impl<T: SessionStream> Session<T> {
         }
         let _ = session.stream.flush().await;
 
+        // Obtain TLS channel binding and client certificate before splitting the stream
+        let tls_channel_binding = session.stream.tls_channel_binding();
+        let tls_client_certificate = session.stream.tls_client_certificate();
+
         // Split stream into read and write halves
         let (stream_rx, stream_tx) = tokio::io::split(session.stream);
 
@@ -145,6 +149,9 @@ impl<T: SessionStream> Session<T> {
             remote_addr: session.remote_ip,
             stream_rx,
             stream_tx: Arc::new(tokio::sync::Mutex::new(stream_tx)),
+            tls_channel_binding,
+            tls_client_certificate,
+            sasl: None,
         })
     }
 
@@ -172,8 +179,10 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Upgrade to TLS
-        let (stream_rx, stream_tx) =
-            tokio::io::split(self.instance.tls_accept(stream, &self.span).await?);
+        let stream = self.instance.tls_accept(stream, &self.span).await?;
+        let tls_channel_binding = stream.tls_channel_binding();
+        let tls_client_certificate = stream.tls_client_certificate();
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
         let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
 
         Ok(Session {
@@ -191,6 +200,9 @@ impl<T: SessionStream> Session<T> {
             remote_addr: self.remote_addr,
             stream_rx,
             stream_tx,
+            tls_channel_binding,
+            tls_client_certificate,
+            sasl: None,
         })
     }
 }
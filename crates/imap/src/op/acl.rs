@@ -35,6 +35,7 @@ use imap_proto::{
 use jmap::{
     auth::{acl::EffectiveAcl, AccessToken},
     mailbox::set::SCHEMA,
+    sharing::AclChanges,
 };
 use jmap_proto::{
     error::method::MethodError,
@@ -165,7 +166,10 @@ impl<T: SessionStream> Session<T> {
                                         MyRightsResponse {
                                             mailbox_name: arguments.mailbox_name,
                                             rights: if access_token.is_shared(mailbox.account_id) {
-                                                let acl = values.inner.effective_acl(&access_token);
+                                                let acl = values.inner.effective_acl(
+                                                    &access_token,
+                                                    mailbox.account_id,
+                                                );
                                                 let mut rights = Vec::with_capacity(5);
                                                 if acl.contains(Acl::ReadItems) {
                                                     rights.push(Rights::Read);
@@ -232,14 +236,15 @@ impl<T: SessionStream> Session<T> {
 
                 tokio::spawn(async move {
                     // Validate mailbox
-                    let (mailbox, values, _) = match data.get_acl_mailbox(&arguments, true).await {
-                        Ok(result) => result,
-                        Err(response) => {
-                            data.write_bytes(response.with_tag(arguments.tag).into_bytes())
-                                .await;
-                            return;
-                        }
-                    };
+                    let (mailbox, values, access_token) =
+                        match data.get_acl_mailbox(&arguments, true).await {
+                            Ok(result) => result,
+                            Err(response) => {
+                                data.write_bytes(response.with_tag(arguments.tag).into_bytes())
+                                    .await;
+                                return;
+                            }
+                        };
 
                     // Obtain principal id
                     let acl_account_id = match data
@@ -340,16 +345,16 @@ impl<T: SessionStream> Session<T> {
 
                     // Write changes
                     let mailbox_id = mailbox.mailbox_id;
+                    let builder = ObjectIndexBuilder::new(SCHEMA)
+                        .with_changes(changes)
+                        .with_current(values);
+                    let acl_changes = AclChanges::new(&builder);
                     let mut batch = BatchBuilder::new();
                     batch
                         .with_account_id(mailbox.account_id)
                         .with_collection(Collection::Mailbox)
                         .update_document(mailbox_id)
-                        .custom(
-                            ObjectIndexBuilder::new(SCHEMA)
-                                .with_changes(changes)
-                                .with_current(values),
-                        );
+                        .custom(builder);
                     if !batch.is_empty() {
                         match data.jmap.write_batch(batch).await {
                             Ok(_) => {
@@ -363,6 +368,30 @@ impl<T: SessionStream> Session<T> {
                                                     .with_change(DataType::Mailbox, change_id),
                                             )
                                             .await;
+
+                                        // Notify sharees
+                                        if let Some(acl_changes) = acl_changes {
+                                            if data
+                                                .jmap
+                                                .share_notify(
+                                                    &access_token,
+                                                    Collection::Mailbox,
+                                                    mailbox.account_id,
+                                                    mailbox_id,
+                                                    acl_changes,
+                                                )
+                                                .await
+                                                .is_err()
+                                            {
+                                                data.write_bytes(
+                                                    StatusResponse::database_failure()
+                                                        .with_tag(arguments.tag)
+                                                        .into_bytes(),
+                                                )
+                                                .await;
+                                                return;
+                                            }
+                                        }
                                     }
                                     Err(_) => {
                                         data.write_bytes(
@@ -469,7 +498,7 @@ impl<T: SessionStream> SessionData<T> {
                         || access_token.is_member(mailbox.account_id)
                         || values
                             .inner
-                            .effective_acl(&access_token)
+                            .effective_acl(&access_token, mailbox.account_id)
                             .contains(Acl::Administer)
                     {
                         Ok((mailbox, values, access_token))
@@ -23,17 +23,22 @@
 
 use std::sync::Arc;
 
-use directory::AuthResult;
+use directory::{core::scram::ScramHash, AuthResult};
 use imap_proto::{
-    protocol::{authenticate::Mechanism, capability::Capability},
+    protocol::{
+        authenticate::{self, Mechanism},
+        capability::Capability,
+    },
     receiver::{self, Request},
     Command, ResponseCode, StatusResponse,
 };
+use jmap::auth::{sasl::ScramExchange, AccessToken};
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use utils::listener::SessionStream;
 
-use crate::core::{Session, SessionData, State};
+use crate::core::{SaslExchange, Session, SessionData, State};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::OpResult {
@@ -72,15 +77,14 @@ impl<T: SessionStream> Session<T> {
                             }
                         }
                     } else {
-                        self.receiver.request = receiver::Request {
-                            tag: args.tag,
-                            command: Command::Authenticate,
-                            tokens: vec![receiver::Token::Argument(args.mechanism.into_bytes())],
-                        };
-                        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
-                        self.write_bytes(b"+ \"\"\r\n".to_vec()).await
+                        self.sasl_challenge(args, b"").await
                     }
                 }
+                Mechanism::ScramSha1
+                | Mechanism::ScramSha1Plus
+                | Mechanism::ScramSha256
+                | Mechanism::ScramSha256Plus => self.handle_scram(args).await,
+                Mechanism::External => self.handle_external(args).await,
                 _ => {
                     self.write_bytes(
                         StatusResponse::no("Authentication mechanism not supported.")
@@ -95,29 +99,176 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
+    async fn handle_scram(&mut self, mut args: authenticate::Arguments) -> crate::OpResult {
+        let response = args.params.pop();
+        if response.as_deref() == Some("*") {
+            self.sasl = None;
+            return self
+                .write_bytes(
+                    StatusResponse::bad("Authentication exchange cancelled.")
+                        .with_tag(args.tag)
+                        .into_bytes(),
+                )
+                .await;
+        }
+
+        let mut exchange = match self.sasl.take() {
+            Some(SaslExchange::Scram(exchange)) => exchange,
+            Some(SaslExchange::Success(access_token)) => {
+                // The client has to acknowledge the server-final message with an empty response
+                return self
+                    .finish_authentication(
+                        response
+                            .map_or(true, |response| response.is_empty())
+                            .then_some(access_token),
+                        args.tag,
+                    )
+                    .await;
+            }
+            Some(SaslExchange::External) | None => {
+                self.is_auth_allowed().await?;
+                let (hash, is_plus) = match args.mechanism {
+                    Mechanism::ScramSha1 => (ScramHash::Sha1, false),
+                    Mechanism::ScramSha1Plus => (ScramHash::Sha1, true),
+                    Mechanism::ScramSha256Plus => (ScramHash::Sha256, true),
+                    _ => (ScramHash::Sha256, false),
+                };
+                let exchange = Box::new(ScramExchange::new(
+                    hash,
+                    is_plus,
+                    self.tls_channel_binding.clone(),
+                ));
+                if response.is_none() {
+                    self.sasl = SaslExchange::Scram(exchange).into();
+                    return self.sasl_challenge(args, b"").await;
+                }
+                exchange
+            }
+        };
+
+        let response = match response.and_then(|response| base64_decode(response.as_bytes())) {
+            Some(response) => response,
+            None => {
+                return self
+                    .write_bytes(
+                        StatusResponse::no("Failed to decode challenge.")
+                            .with_tag(args.tag)
+                            .with_code(ResponseCode::Parse)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+        };
+
+        let result = if exchange.is_client_first() {
+            self.jmap
+                .scram_client_first(&mut exchange, &response)
+                .await
+                .map(|server_first| (SaslExchange::Scram(exchange), server_first))
+        } else {
+            self.jmap
+                .scram_client_final(&mut exchange, &response, self.remote_addr)
+                .await
+                .map(|(access_token, server_final)| {
+                    (SaslExchange::Success(access_token), server_final)
+                })
+        };
+
+        match result {
+            Ok((exchange, challenge)) => {
+                self.sasl = exchange.into();
+                self.sasl_challenge(args, challenge.as_bytes()).await
+            }
+            Err(err) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "authenticate",
+                    mechanism = ?args.mechanism,
+                    reason = %err,
+                    "SCRAM authentication failed."
+                );
+                self.finish_authentication(None, args.tag).await
+            }
+        }
+    }
+
+    async fn handle_external(&mut self, mut args: authenticate::Arguments) -> crate::OpResult {
+        let authzid = match args.params.pop() {
+            Some(response) if response == "*" => {
+                return self
+                    .write_bytes(
+                        StatusResponse::bad("Authentication exchange cancelled.")
+                            .with_tag(args.tag)
+                            .into_bytes(),
+                    )
+                    .await;
+            }
+            Some(response) => match base64_decode(response.as_bytes())
+                .and_then(|authzid| String::from_utf8(authzid).ok())
+            {
+                Some(authzid) => authzid,
+                None if response.is_empty() || response == "=" => String::new(),
+                None => {
+                    return self
+                        .write_bytes(
+                            StatusResponse::no("Failed to decode challenge.")
+                                .with_tag(args.tag)
+                                .with_code(ResponseCode::Parse)
+                                .into_bytes(),
+                        )
+                        .await;
+                }
+            },
+            None if self.sasl.is_none() => {
+                // Request the authorization identity
+                self.sasl = SaslExchange::External.into();
+                return self.sasl_challenge(args, b"").await;
+            }
+            None => String::new(),
+        };
+        self.sasl = None;
+
+        self.is_auth_allowed().await?;
+        let access_token = self
+            .jmap
+            .authenticate_external(
+                self.tls_client_certificate.as_deref(),
+                &authzid,
+                self.remote_addr,
+            )
+            .await;
+        self.finish_authentication(access_token, args.tag).await
+    }
+
+    async fn sasl_challenge(
+        &mut self,
+        args: authenticate::Arguments,
+        challenge: &[u8],
+    ) -> crate::OpResult {
+        self.receiver.request = receiver::Request {
+            tag: args.tag,
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(args.mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        if !challenge.is_empty() {
+            let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 6);
+            buf.extend_from_slice(b"+ ");
+            buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+            buf.extend_from_slice(b"\r\n");
+            self.write_bytes(buf).await
+        } else {
+            self.write_bytes(b"+ \"\"\r\n".to_vec()).await
+        }
+    }
+
     pub async fn authenticate(
         &mut self,
         credentials: Credentials<String>,
         tag: String,
     ) -> crate::Result<()> {
         // Throttle authentication requests
-        if self
-            .jmap
-            .is_auth_allowed_soft(&self.remote_addr)
-            .await
-            .is_err()
-        {
-            self.write_bytes(
-                StatusResponse::bye("Too many authentication requests from this IP address.")
-                    .into_bytes(),
-            )
-            .await?;
-            tracing::debug!(parent: &self.span,
-                event = "disconnect",
-                "Too many authentication attempts, disconnecting.",
-            );
-            return Err(());
-        }
+        self.is_auth_allowed().await?;
 
         // Authenticate
         let access_token = match credentials {
@@ -152,6 +303,36 @@ impl<T: SessionStream> Session<T> {
             }
         };
 
+        self.finish_authentication(access_token, tag).await
+    }
+
+    async fn is_auth_allowed(&self) -> crate::Result<()> {
+        if self
+            .jmap
+            .is_auth_allowed_soft(&self.remote_addr)
+            .await
+            .is_err()
+        {
+            self.write_bytes(
+                StatusResponse::bye("Too many authentication requests from this IP address.")
+                    .into_bytes(),
+            )
+            .await?;
+            tracing::debug!(parent: &self.span,
+                event = "disconnect",
+                "Too many authentication attempts, disconnecting.",
+            );
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn finish_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        tag: String,
+    ) -> crate::Result<()> {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = self
@@ -340,10 +340,11 @@ impl<T: SessionStream> SessionData<T> {
                     if !mailboxes.current().contains(&src_mailbox_id) {
                         continue;
                     } else if mailboxes.current().len() == 1 {
-                        // Delete message if it is no longer in any mailbox
+                        // Delete message if it is no longer in any mailbox, the message
+                        // was moved so there is no need to keep a recoverable copy
                         if let Ok(changes) = self
                             .jmap
-                            .email_delete(src_account_id, id)
+                            .email_delete_with_hold(src_account_id, id, None)
                             .await
                             .map_err(|_| {
                                 StatusResponse::database_failure().with_tag(&arguments.tag)
@@ -136,8 +136,16 @@ impl<T: SessionStream> SessionData<T> {
         mailbox: Arc<SelectedMailbox>,
         sequence: Option<AHashMap<u32, ImapId>>,
     ) -> crate::op::Result<()> {
-        // Obtain message ids
+        // Messages of accounts under legal hold cannot be expunged
         let account_id = mailbox.id.account_id;
+        if self.jmap.is_legal_hold(account_id).await? {
+            return Err(StatusResponse::no(
+                "Account is under legal hold, messages cannot be expunged.",
+            )
+            .with_code(ResponseCode::NoPerm));
+        }
+
+        // Obtain message ids
         let deleted_ids = self
             .jmap
             .get_tag(
@@ -37,7 +37,7 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
-use jmap::email::metadata::MessageMetadata;
+use jmap::{email::metadata::MessageMetadata, sharing::seen::apply_shared_seen};
 use jmap_proto::{
     error::method::MethodError,
     types::{
@@ -250,12 +250,30 @@ impl<T: SessionStream> SessionData<T> {
             }
         }
 
+        // Sharees keep their own \Seen flags, which only require read access
+        let access_token = match self.get_access_token().await {
+            Ok(access_token) => access_token,
+            Err(response) => return response.with_tag(arguments.tag),
+        };
+        let shared_seen = if !access_token.is_member(account_id) {
+            match self.jmap.seen_messages(&access_token, account_id).await {
+                Ok(seen) => Some(seen.unwrap_or_default()),
+                Err(_) => return StatusResponse::database_failure().with_tag(arguments.tag),
+            }
+        } else {
+            None
+        };
+
         if set_seen_flags
             && !self
                 .check_mailbox_acl(
                     mailbox.id.account_id,
                     mailbox.id.mailbox_id,
-                    Acl::ModifyItems,
+                    if shared_seen.is_some() {
+                        Acl::ReadItems
+                    } else {
+                        Acl::ModifyItems
+                    },
                 )
                 .await
                 .unwrap_or(false)
@@ -281,7 +299,7 @@ impl<T: SessionStream> SessionData<T> {
         ids.sort_unstable_by_key(|(seqnum, _, _)| *seqnum);
         for (seqnum, uid, id) in ids {
             // Obtain attributes and keywords
-            let (email, keywords) = if let (Ok(Some(email)), Ok(Some(keywords))) = (
+            let (email, mut keywords) = if let (Ok(Some(email)), Ok(Some(keywords))) = (
                 self.jmap
                     .get_property::<Bincode<MessageMetadata>>(
                         account_id,
@@ -309,6 +327,9 @@ impl<T: SessionStream> SessionData<T> {
                     "Message metadata not found");
                 continue;
             };
+            if let Some(seen) = &shared_seen {
+                apply_shared_seen(&mut keywords.inner, seen.contains(id));
+            }
 
             // Fetch and parse blob
             let raw_message = if needs_blobs {
@@ -522,16 +543,34 @@ impl<T: SessionStream> SessionData<T> {
                 }
             };
             for (id, mut keywords) in set_seen_ids {
-                keywords.inner.push(Keyword::Seen);
                 let mut batch = BatchBuilder::new();
                 batch
                     .with_account_id(account_id)
                     .with_collection(Collection::Email)
-                    .update_document(id.document_id())
-                    .assert_value(Property::Keywords, &keywords)
-                    .value(Property::Keywords, keywords.inner, F_VALUE)
-                    .value(Property::Keywords, Keyword::Seen, F_BITMAP)
-                    .value(Property::Cid, changelog.change_id, F_VALUE);
+                    .update_document(id.document_id());
+                if shared_seen.is_some() {
+                    if self
+                        .jmap
+                        .shared_seen_batch(
+                            &mut batch,
+                            account_id,
+                            id.document_id(),
+                            access_token.primary_id(),
+                            true,
+                        )
+                        .await
+                        .is_err()
+                    {
+                        return StatusResponse::database_failure().with_tag(arguments.tag);
+                    }
+                } else {
+                    keywords.inner.push(Keyword::Seen);
+                    batch
+                        .assert_value(Property::Keywords, &keywords)
+                        .value(Property::Keywords, keywords.inner, F_VALUE)
+                        .value(Property::Keywords, Keyword::Seen, F_BITMAP);
+                }
+                batch.value(Property::Cid, changelog.change_id, F_VALUE);
                 match self.jmap.write_batch(batch).await {
                     Ok(_) => {
                         changelog.log_update(Collection::Email, id);
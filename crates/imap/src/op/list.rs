@@ -178,7 +178,7 @@ impl<T: SessionStream> SessionData<T> {
         let mut added_shared_folder = false;
         for account in self.mailboxes.lock().iter() {
             if let Some(prefix) = &account.prefix {
-                if !added_shared_folder {
+                if !added_shared_folder && prefix.starts_with(&self.imap.name_shared) {
                     if !filter_subscribed && matches_pattern(&patterns, &self.imap.name_shared) {
                         list_items.push(ListItem {
                             mailbox_name: self.imap.name_shared.clone(),
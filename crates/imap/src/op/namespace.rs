@@ -33,16 +33,36 @@ use crate::core::Session;
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_namespace(&mut self, request: Request<Command>) -> crate::OpResult {
+        let mut shared_prefix = None;
+        let mut public_prefixes = Vec::new();
+        for prefix in self
+            .state
+            .session_data()
+            .mailboxes
+            .lock()
+            .iter()
+            .filter_map(|account| account.prefix.as_ref())
+        {
+            if self
+                .jmap
+                .config
+                .public_folders
+                .iter()
+                .any(|folder| &folder.namespace == prefix)
+            {
+                public_prefixes.push(prefix.clone());
+            } else if shared_prefix.is_none() {
+                shared_prefix = self.imap.name_shared.clone().into();
+            }
+        }
+
         self.write_bytes(
             StatusResponse::completed(Command::Namespace)
                 .with_tag(request.tag)
                 .serialize(
                     Response {
-                        shared_prefix: if self.state.session_data().mailboxes.lock().len() > 1 {
-                            self.imap.name_shared.clone().into()
-                        } else {
-                            None
-                        },
+                        shared_prefix,
+                        public_prefixes,
                     }
                     .serialize(),
                 ),
@@ -32,6 +32,7 @@ use imap_proto::{
     Command, StatusResponse,
 };
 
+use jmap::sharing::seen::keyword_filter;
 use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
 use mail_parser::HeaderName;
 use nlp::language::Language;
@@ -253,6 +254,8 @@ impl<T: SessionStream> SessionData<T> {
         prev_saved_search: &Option<Option<Arc<Vec<ImapId>>>>,
     ) -> Result<(ResultSet, bool), StatusResponse> {
         // Obtain message ids
+        let access_token = self.get_access_token().await?;
+        let account_id = mailbox.id.account_id;
         let mut filters = Vec::with_capacity(imap_filter.len() + 1);
         let message_ids = self
             .jmap
@@ -468,8 +471,9 @@ impl<T: SessionStream> SessionData<T> {
                         ));
                     }
                     search::Filter::Keyword(keyword) => {
-                        filters.push(query::Filter::is_in_bitmap(
-                            Property::Keywords,
+                        filters.push(keyword_filter(
+                            &access_token,
+                            account_id,
                             Keyword::from(keyword),
                         ));
                     }
@@ -486,10 +490,7 @@ impl<T: SessionStream> SessionData<T> {
                         filters.push(query::Filter::End);
                     }
                     search::Filter::Seen => {
-                        filters.push(query::Filter::is_in_bitmap(
-                            Property::Keywords,
-                            Keyword::Seen,
-                        ));
+                        filters.push(keyword_filter(&access_token, account_id, Keyword::Seen));
                     }
                     search::Filter::SentBefore(date) => {
                         filters.push(query::Filter::lt(Property::SentAt, date as u64));
@@ -543,18 +544,16 @@ impl<T: SessionStream> SessionData<T> {
                     }
                     search::Filter::Unkeyword(keyword) => {
                         filters.push(query::Filter::Not);
-                        filters.push(query::Filter::is_in_bitmap(
-                            Property::Keywords,
+                        filters.push(keyword_filter(
+                            &access_token,
+                            account_id,
                             Keyword::from(keyword),
                         ));
                         filters.push(query::Filter::End);
                     }
                     search::Filter::Unseen => {
                         filters.push(query::Filter::Not);
-                        filters.push(query::Filter::is_in_bitmap(
-                            Property::Keywords,
-                            Keyword::Seen,
-                        ));
+                        filters.push(keyword_filter(&access_token, account_id, Keyword::Seen));
                         filters.push(query::Filter::End);
                     }
                     search::Filter::And => {
@@ -101,6 +101,12 @@ impl<T: SessionStream> SessionData<T> {
                     .map_or(false, |(base_name, path)| {
                         base_name == self.imap.name_shared && !path.contains('/')
                     })
+                || self
+                    .jmap
+                    .config
+                    .public_folders
+                    .iter()
+                    .any(|folder| folder.namespace == mailbox_name)
             {
                 Ok(StatusItem {
                     mailbox_name,
@@ -231,6 +237,7 @@ impl<T: SessionStream> SessionData<T> {
                 .jmap
                 .get_document_ids(mailbox.account_id, Collection::Email)
                 .await?;
+            let access_token = self.get_access_token().await?;
 
             for item in items_update {
                 let result = match item {
@@ -283,12 +290,7 @@ impl<T: SessionStream> SessionData<T> {
                         {
                             if let Some(mut seen) = self
                                 .jmap
-                                .get_tag(
-                                    mailbox.account_id,
-                                    Collection::Email,
-                                    Property::Keywords,
-                                    Keyword::Seen,
-                                )
+                                .seen_messages(&access_token, mailbox.account_id)
                                 .await?
                             {
                                 seen ^= message_ids;
@@ -33,7 +33,7 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, ResponseType, StatusResponse,
 };
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{email::set::TagManager, mailbox::UidMailbox, sharing::seen::apply_shared_seen};
 use jmap_proto::{
     error::method::MethodError,
     types::{
@@ -108,12 +108,41 @@ impl<T: SessionStream> SessionData<T> {
             }
         };
 
+        // Sharees keep their own \Seen flags, which only require read access
+        let access_token = self
+            .get_access_token()
+            .await
+            .map_err(|response| response.with_tag(&arguments.tag))?;
+        let shared_seen = if !access_token.is_member(account_id) {
+            Some(
+                self.jmap
+                    .seen_messages(&access_token, account_id)
+                    .await
+                    .map_err(|_| StatusResponse::database_failure().with_tag(&arguments.tag))?
+                    .unwrap_or_default(),
+            )
+        } else {
+            None
+        };
+        let set_keywords = arguments
+            .keywords
+            .into_iter()
+            .map(Keyword::from)
+            .collect::<Vec<_>>();
+        let is_seen_only = shared_seen.is_some()
+            && !matches!(arguments.operation, Operation::Set)
+            && set_keywords.iter().all(|keyword| keyword == &Keyword::Seen);
+
         // Verify that the user can modify messages in this mailbox.
         if !self
             .check_mailbox_acl(
                 mailbox.id.account_id,
                 mailbox.id.mailbox_id,
-                Acl::ModifyItems,
+                if is_seen_only {
+                    Acl::ReadItems
+                } else {
+                    Acl::ModifyItems
+                },
             )
             .await
             .map_err(|_| StatusResponse::database_failure().with_tag(&arguments.tag))?
@@ -190,11 +219,6 @@ impl<T: SessionStream> SessionData<T> {
         };
 
         // Process each change
-        let set_keywords = arguments
-            .keywords
-            .into_iter()
-            .map(Keyword::from)
-            .collect::<Vec<_>>();
         let mut changelog = ChangeLogBuilder::new();
         let mut changed_mailboxes = AHashSet::new();
         'outer: for (id, imap_id) in ids {
@@ -222,10 +246,14 @@ impl<T: SessionStream> SessionData<T> {
                                 .with_tag(response.tag.as_ref().unwrap())
                         })?,
                 ) {
-                    (TagManager::new(keywords), thread_id)
+                    (keywords, thread_id)
                 } else {
                     continue 'outer;
                 };
+                let owner_seen = shared_seen
+                    .as_ref()
+                    .map(|seen| apply_shared_seen(&mut keywords.inner, seen.contains(id)));
+                let mut keywords = TagManager::new(keywords);
 
                 // Apply changes
                 match arguments.operation {
@@ -266,7 +294,25 @@ impl<T: SessionStream> SessionData<T> {
                         .with_account_id(account_id)
                         .with_collection(Collection::Email)
                         .update_document(id);
-                    keywords.update_batch(&mut batch, Property::Keywords);
+                    if let (Some(owner_seen), true) = (owner_seen, seen_changed) {
+                        let is_seen = keywords.detach(&Keyword::Seen, owner_seen);
+                        self.jmap
+                            .shared_seen_batch(
+                                &mut batch,
+                                account_id,
+                                id,
+                                access_token.primary_id(),
+                                is_seen,
+                            )
+                            .await
+                            .map_err(|_| {
+                                StatusResponse::database_failure()
+                                    .with_tag(response.tag.as_ref().unwrap())
+                            })?;
+                    }
+                    if keywords.has_changes() {
+                        keywords.update_batch(&mut batch, Property::Keywords);
+                    }
                     if changelog.change_id == u64::MAX {
                         changelog.change_id =
                             self.jmap.assign_change_id(account_id).await.map_err(|_| {
@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    error::set::SetError,
+    parser::{json::Parser, JsonObjectParser, Token},
+    request::RequestProperty,
+    types::{date::UTCDate, id::Id, keyword::Keyword},
+};
+
+#[derive(Debug, Clone)]
+pub struct DeletedEmailGetRequest {
+    pub account_id: Id,
+    pub ids: Option<Vec<Id>>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeletedEmailGetResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "list")]
+    pub list: Vec<DeletedEmail>,
+
+    #[serde(rename = "notFound")]
+    pub not_found: Vec<Id>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeletedEmail {
+    #[serde(rename = "id")]
+    pub id: Id,
+
+    #[serde(rename = "mailboxIds")]
+    pub mailbox_ids: VecMap<Id, bool>,
+
+    #[serde(rename = "keywords")]
+    pub keywords: VecMap<Keyword, bool>,
+
+    #[serde(rename = "size")]
+    pub size: usize,
+
+    #[serde(rename = "subject")]
+    pub subject: Option<String>,
+
+    #[serde(rename = "from")]
+    pub from: Option<String>,
+
+    #[serde(rename = "receivedAt")]
+    pub received_at: UTCDate,
+
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: UTCDate,
+
+    #[serde(rename = "expiresAt")]
+    pub expires_at: UTCDate,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeletedEmailRestoreRequest {
+    pub account_id: Id,
+    pub ids: Vec<Id>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeletedEmailRestoreResponse {
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+
+    #[serde(rename = "restored")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub restored: VecMap<Id, Id>,
+
+    #[serde(rename = "notRestored")]
+    #[serde(skip_serializing_if = "VecMap::is_empty")]
+    pub not_restored: VecMap<Id, SetError>,
+}
+
+impl JsonObjectParser for DeletedEmailGetRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = DeletedEmailGetRequest {
+            account_id: Id::default(),
+            ids: None,
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_6469 if !key.is_ref => {
+                    request.ids = <Option<Vec<Id>>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
+
+impl JsonObjectParser for DeletedEmailRestoreRequest {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut request = DeletedEmailRestoreRequest {
+            account_id: Id::default(),
+            ids: Vec::new(),
+        };
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match &key.hash[0] {
+                0x0064_4974_6e75_6f63_6361 if !key.is_ref => {
+                    request.account_id = parser.next_token::<Id>()?.unwrap_string("accountId")?;
+                }
+                0x0073_6469 if !key.is_ref => {
+                    request.ids = <Vec<Id>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(request)
+    }
+}
@@ -25,9 +25,11 @@ use ahash::AHashMap;
 
 pub mod changes;
 pub mod copy;
+pub mod deleted;
 pub mod get;
 pub mod import;
 pub mod lookup;
+pub mod mdn;
 pub mod parse;
 pub mod query;
 pub mod query_changes;
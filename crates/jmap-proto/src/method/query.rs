@@ -117,6 +117,8 @@ pub enum Filter {
     IsActive(bool),
     Scope(String),
     ResourceType(String),
+    ObjectType(String),
+    ObjectAccountId(Id),
     _T(String),
 
     And,
@@ -154,6 +156,7 @@ pub enum SortProperty {
     AllInThreadHaveKeyword,
     SomeInThreadHaveKeyword,
     Used,
+    Created,
     _T(String),
 }
 
@@ -165,6 +168,7 @@ pub enum RequestArguments {
     SieveScript,
     Principal,
     Quota,
+    ShareNotification,
 }
 
 impl JsonObjectParser for QueryRequest<RequestArguments> {
@@ -180,6 +184,7 @@ impl JsonObjectParser for QueryRequest<RequestArguments> {
                 MethodObject::SieveScript => RequestArguments::SieveScript,
                 MethodObject::Principal => RequestArguments::Principal,
                 MethodObject::Quota => RequestArguments::Quota,
+                MethodObject::ShareNotification => RequestArguments::ShareNotification,
                 _ => {
                     return Err(Error::Method(MethodError::UnknownMethod(format!(
                         "{}/query",
@@ -443,6 +448,14 @@ pub fn parse_filter(parser: &mut Parser) -> crate::parser::Result<Vec<Filter>> {
                                 .next_token::<String>()?
                                 .unwrap_string("resourceType")?,
                         ),
+                        (0x6570_7954_7463_656a_626f, _) => Filter::ObjectType(
+                            parser.next_token::<String>()?.unwrap_string("objectType")?,
+                        ),
+                        (0x0064_4974_6e75_6f63_6341_7463_656a_626f, _) => Filter::ObjectAccountId(
+                            parser
+                                .next_token::<Id>()?
+                                .unwrap_string("objectAccountId")?,
+                        ),
                         _ => {
                             if parser.is_eof || parser.skip_string() {
                                 let filter = Filter::_T(
@@ -585,6 +598,7 @@ impl JsonObjectParser for SortProperty {
             0x4b65_7661_4864_6165_7268_546e_496c_6c61 => Ok(SortProperty::AllInThreadHaveKeyword),
             0x6576_6148_6461_6572_6854_6e49_656d_6f73 => Ok(SortProperty::SomeInThreadHaveKeyword),
             0x6465_7375 => Ok(SortProperty::Used),
+            0x0064_6574_6165_7263 => Ok(SortProperty::Created),
             _ => {
                 if parser.is_eof || parser.skip_string() {
                     Ok(SortProperty::_T(
@@ -647,6 +661,8 @@ impl Display for Filter {
             Filter::IsActive(_) => "isActive",
             Filter::ResourceType(_) => "resourceType",
             Filter::Scope(_) => "scope",
+            Filter::ObjectType(_) => "objectType",
+            Filter::ObjectAccountId(_) => "objectAccountId",
             Filter::_T(v) => v.as_str(),
             Filter::And => "and",
             Filter::Or => "or",
@@ -678,6 +694,7 @@ impl Display for SortProperty {
             SortProperty::AllInThreadHaveKeyword => "allInThreadHaveKeyword",
             SortProperty::SomeInThreadHaveKeyword => "someInThreadHaveKeyword",
             SortProperty::Used => "used",
+            SortProperty::Created => "created",
             SortProperty::_T(s) => s,
         })
     }
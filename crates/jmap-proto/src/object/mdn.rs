@@ -0,0 +1,328 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    error::method::MethodError,
+    parser::{json::Parser, Error, Ignore, JsonObjectParser, Token},
+    request::RequestProperty,
+    types::id::Id,
+};
+
+/// Message Disposition Notification object (RFC 9007).
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Mdn {
+    #[serde(rename = "forEmailId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub for_email_id: Option<Id>,
+
+    #[serde(rename = "subject")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+
+    #[serde(rename = "textBody")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_body: Option<String>,
+
+    #[serde(rename = "includeOriginalMessage")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_original_message: Option<bool>,
+
+    #[serde(rename = "reportingUA")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reporting_ua: Option<String>,
+
+    #[serde(rename = "disposition")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disposition: Option<Disposition>,
+
+    #[serde(rename = "mdnGateway")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mdn_gateway: Option<String>,
+
+    #[serde(rename = "originalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_recipient: Option<String>,
+
+    #[serde(rename = "finalRecipient")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub final_recipient: Option<String>,
+
+    #[serde(rename = "originalMessageId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_message_id: Option<String>,
+
+    #[serde(rename = "error")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<Vec<String>>,
+
+    #[serde(rename = "extensionFields")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extension_fields: Option<VecMap<String, String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Disposition {
+    #[serde(rename = "actionMode")]
+    pub action_mode: ActionMode,
+    #[serde(rename = "sendingMode")]
+    pub sending_mode: SendingMode,
+    #[serde(rename = "type")]
+    pub type_: DispositionType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum ActionMode {
+    #[serde(rename = "manual-action")]
+    Manual,
+    #[serde(rename = "automatic-action")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum SendingMode {
+    #[serde(rename = "mdn-sent-manually")]
+    Manual,
+    #[serde(rename = "mdn-sent-automatically")]
+    Automatic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum DispositionType {
+    #[serde(rename = "deleted")]
+    Deleted,
+    #[serde(rename = "dispatched")]
+    Dispatched,
+    #[serde(rename = "displayed")]
+    Displayed,
+    #[serde(rename = "processed")]
+    Processed,
+}
+
+impl JsonObjectParser for Mdn {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut mdn = Mdn::default();
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match (&key.hash[0], &key.hash[1]) {
+                (0x6449_6c69_616d_4572_6f66, _) => {
+                    mdn.for_email_id = parser
+                        .next_token::<Id>()?
+                        .unwrap_string_or_null("forEmailId")?;
+                }
+                (0x0074_6365_6a62_7573, _) => {
+                    mdn.subject = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("subject")?;
+                }
+                (0x7964_6f42_7478_6574, _) => {
+                    mdn.text_body = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("textBody")?;
+                }
+                (0x4d6c_616e_6967_6972_4f65_6475_6c63_6e69, 0x6567_6173_7365) => {
+                    mdn.include_original_message = parser
+                        .next_token::<Ignore>()?
+                        .unwrap_bool_or_null("includeOriginalMessage")?;
+                }
+                (0x0041_5567_6e69_7472_6f70_6572, _) => {
+                    mdn.reporting_ua = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("reportingUA")?;
+                }
+                (0x006e_6f69_7469_736f_7073_6964, _) => {
+                    mdn.disposition = Disposition::parse(parser)?.into();
+                }
+                (0x7961_7765_7461_476e_646d, _) => {
+                    mdn.mdn_gateway = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("mdnGateway")?;
+                }
+                (0x6e65_6970_6963_6552_6c61_6e69_6769_726f, 0x0074) => {
+                    mdn.original_recipient = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("originalRecipient")?;
+                }
+                (0x746e_6569_7069_6365_526c_616e_6966, _) => {
+                    mdn.final_recipient = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("finalRecipient")?;
+                }
+                (0x4965_6761_7373_654d_6c61_6e69_6769_726f, 0x0064) => {
+                    mdn.original_message_id = parser
+                        .next_token::<String>()?
+                        .unwrap_string_or_null("originalMessageId")?;
+                }
+                (0x0072_6f72_7265, _) => {
+                    mdn.error = <Option<Vec<String>>>::parse(parser)?;
+                }
+                (0x0073_646c_6569_466e_6f69_736e_6574_7865, _) => {
+                    mdn.extension_fields = <Option<VecMap<String, String>>>::parse(parser)?;
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        Ok(mdn)
+    }
+}
+
+impl JsonObjectParser for Disposition {
+    fn parse(parser: &mut Parser<'_>) -> crate::parser::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut action_mode = None;
+        let mut sending_mode = None;
+        let mut type_ = None;
+
+        parser
+            .next_token::<String>()?
+            .assert_jmap(Token::DictStart)?;
+
+        while let Some(key) = parser.next_dict_key::<RequestProperty>()? {
+            match (&key.hash[0], &key.hash[1]) {
+                (0x6564_6f4d_6e6f_6974_6361, _) => {
+                    action_mode = match parser
+                        .next_token::<String>()?
+                        .unwrap_string("actionMode")?
+                        .to_ascii_lowercase()
+                        .as_str()
+                    {
+                        "manual-action" => ActionMode::Manual,
+                        "automatic-action" => ActionMode::Automatic,
+                        _ => return Err(parser.error_value()),
+                    }
+                    .into();
+                }
+                (0x0065_646f_4d67_6e69_646e_6573, _) => {
+                    sending_mode = match parser
+                        .next_token::<String>()?
+                        .unwrap_string("sendingMode")?
+                        .to_ascii_lowercase()
+                        .as_str()
+                    {
+                        "mdn-sent-manually" => SendingMode::Manual,
+                        "mdn-sent-automatically" => SendingMode::Automatic,
+                        _ => return Err(parser.error_value()),
+                    }
+                    .into();
+                }
+                (0x6570_7974, _) => {
+                    type_ = DispositionType::parse(
+                        &parser.next_token::<String>()?.unwrap_string("type")?,
+                    )
+                    .ok_or_else(|| parser.error_value())?
+                    .into();
+                }
+                _ => {
+                    parser.skip_token(parser.depth_array, parser.depth_dict)?;
+                }
+            }
+        }
+
+        match (action_mode, sending_mode, type_) {
+            (Some(action_mode), Some(sending_mode), Some(type_)) => Ok(Disposition {
+                action_mode,
+                sending_mode,
+                type_,
+            }),
+            _ => Err(Error::Method(MethodError::InvalidArguments(
+                "Disposition requires actionMode, sendingMode and type.".to_string(),
+            ))),
+        }
+    }
+}
+
+impl Disposition {
+    /// Parses the value of an RFC 8098 `Disposition` field.
+    pub fn parse_field(value: &str) -> Option<Self> {
+        let (modes, type_) = value.split_once(';')?;
+        let (action_mode, sending_mode) = modes.split_once('/')?;
+
+        Some(Disposition {
+            action_mode: match action_mode.trim().to_ascii_lowercase().as_str() {
+                "manual-action" => ActionMode::Manual,
+                "automatic-action" => ActionMode::Automatic,
+                _ => return None,
+            },
+            sending_mode: match sending_mode.trim().to_ascii_lowercase().as_str() {
+                "mdn-sent-manually" => SendingMode::Manual,
+                "mdn-sent-automatically" => SendingMode::Automatic,
+                _ => return None,
+            },
+            type_: DispositionType::parse(type_.split('/').next().unwrap_or_default().trim())?,
+        })
+    }
+}
+
+impl DispositionType {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "deleted" => Some(DispositionType::Deleted),
+            "dispatched" => Some(DispositionType::Dispatched),
+            "displayed" => Some(DispositionType::Displayed),
+            "processed" => Some(DispositionType::Processed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DispositionType::Deleted => "deleted",
+            DispositionType::Dispatched => "dispatched",
+            DispositionType::Displayed => "displayed",
+            DispositionType::Processed => "processed",
+        }
+    }
+}
+
+impl Display for Disposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{}; {}",
+            match self.action_mode {
+                ActionMode::Manual => "manual-action",
+                ActionMode::Automatic => "automatic-action",
+            },
+            match self.sending_mode {
+                SendingMode::Manual => "MDN-sent-manually",
+                SendingMode::Automatic => "MDN-sent-automatically",
+            },
+            self.type_.as_str()
+        )
+    }
+}
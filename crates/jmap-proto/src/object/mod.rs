@@ -26,6 +26,7 @@ pub mod email;
 pub mod email_submission;
 pub mod index;
 pub mod mailbox;
+pub mod mdn;
 pub mod sieve;
 
 use std::slice::Iter;
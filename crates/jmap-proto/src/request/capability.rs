@@ -51,6 +51,14 @@ pub enum Capability {
     Blob = 1 << 8,
     #[serde(rename(serialize = "urn:ietf:params:jmap:quota"))]
     Quota = 1 << 9,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:mdn"))]
+    Mdn = 1 << 10,
+    #[serde(rename(serialize = "urn:stalwart:jmap:recovery"))]
+    Recovery = 1 << 11,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:principals"))]
+    Principals = 1 << 12,
+    #[serde(rename(serialize = "urn:ietf:params:jmap:principals:owner"))]
+    PrincipalsOwner = 1 << 13,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -171,7 +179,26 @@ impl JsonObjectParser for Capability {
     where
         Self: Sized,
     {
-        for ch in b"urn:ietf:params:jmap:" {
+        for ch in b"urn:" {
+            if parser
+                .next_unescaped()?
+                .ok_or_else(|| parser.error_capability())?
+                != *ch
+            {
+                return Err(parser.error_capability());
+            }
+        }
+
+        // Vendor capabilities are prefixed with "urn:stalwart:jmap:"
+        let (prefix, is_vendor): (&[u8], bool) = match parser
+            .next_unescaped()?
+            .ok_or_else(|| parser.error_capability())?
+        {
+            b'i' => (b"etf:params:jmap:", false),
+            b's' => (b"talwart:jmap:", true),
+            _ => return Err(parser.error_capability()),
+        };
+        for ch in prefix {
             if parser
                 .next_unescaped()?
                 .ok_or_else(|| parser.error_capability())?
@@ -182,6 +209,10 @@ impl JsonObjectParser for Capability {
         }
 
         match u128::parse(parser) {
+            Ok(key) if is_vendor => match key {
+                0x7972_6576_6f63_6572 => Ok(Capability::Recovery),
+                _ => Err(parser.error_capability()),
+            },
             Ok(key) => match key {
                 0x6572_6f63 => Ok(Capability::Core),
                 0x6c69_616d => Ok(Capability::Mail),
@@ -193,6 +224,9 @@ impl JsonObjectParser for Capability {
                 0x0065_7665_6973 => Ok(Capability::Sieve),
                 0x626f_6c62 => Ok(Capability::Blob),
                 0x0061_746f_7571 => Ok(Capability::Quota),
+                0x006e_646d => Ok(Capability::Mdn),
+                0x736c_6170_6963_6e69_7270 => Ok(Capability::Principals),
+                0x7265_6e77_6f3a_736c_6170_6963_6e69_7270 => Ok(Capability::PrincipalsOwner),
                 _ => Err(parser.error_capability()),
             },
             Err(Error::Method(_)) => Err(parser.error_capability()),
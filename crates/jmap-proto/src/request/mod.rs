@@ -38,9 +38,11 @@ use crate::{
     method::{
         changes::ChangesRequest,
         copy::{self, CopyBlobRequest, CopyRequest},
+        deleted::{DeletedEmailGetRequest, DeletedEmailRestoreRequest},
         get::{self, GetRequest},
         import::ImportEmailRequest,
         lookup::BlobLookupRequest,
+        mdn::{MdnParseRequest, MdnSendRequest},
         parse::ParseEmailRequest,
         query::{self, QueryRequest},
         query_changes::QueryChangesRequest,
@@ -90,6 +92,10 @@ pub enum RequestMethod {
     ValidateScript(ValidateSieveScriptRequest),
     LookupBlob(BlobLookupRequest),
     UploadBlob(BlobUploadRequest),
+    SendMdn(MdnSendRequest),
+    ParseMdn(MdnParseRequest),
+    GetDeletedEmail(DeletedEmailGetRequest),
+    RestoreDeletedEmail(DeletedEmailRestoreRequest),
     Echo(Echo),
     Error(MethodError),
 }
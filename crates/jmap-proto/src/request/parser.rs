@@ -31,9 +31,11 @@ use crate::{
     method::{
         changes::ChangesRequest,
         copy::{CopyBlobRequest, CopyRequest},
+        deleted::{DeletedEmailGetRequest, DeletedEmailRestoreRequest},
         get::GetRequest,
         import::ImportEmailRequest,
         lookup::BlobLookupRequest,
+        mdn::{MdnParseRequest, MdnSendRequest},
         parse::ParseEmailRequest,
         query::QueryRequest,
         query_changes::QueryChangesRequest,
@@ -143,7 +145,8 @@ impl Request {
                                 | MethodObject::SieveScript
                                 | MethodObject::Principal
                                 | MethodObject::Quota
-                                | MethodObject::Blob,
+                                | MethodObject::Blob
+                                | MethodObject::ShareNotification,
                             ) => GetRequest::parse(parser).map(RequestMethod::Get),
                             (MethodFunction::Get, MethodObject::SearchSnippet) => {
                                 GetSearchSnippetRequest::parse(parser)
@@ -183,6 +186,20 @@ impl Request {
                                 ValidateSieveScriptRequest::parse(parser)
                                     .map(RequestMethod::ValidateScript)
                             }
+                            (MethodFunction::Send, MethodObject::Mdn) => {
+                                MdnSendRequest::parse(parser).map(RequestMethod::SendMdn)
+                            }
+                            (MethodFunction::Parse, MethodObject::Mdn) => {
+                                MdnParseRequest::parse(parser).map(RequestMethod::ParseMdn)
+                            }
+                            (MethodFunction::Get, MethodObject::DeletedEmail) => {
+                                DeletedEmailGetRequest::parse(parser)
+                                    .map(RequestMethod::GetDeletedEmail)
+                            }
+                            (MethodFunction::Restore, MethodObject::DeletedEmail) => {
+                                DeletedEmailRestoreRequest::parse(parser)
+                                    .map(RequestMethod::RestoreDeletedEmail)
+                            }
                             (MethodFunction::Echo, MethodObject::Core) => {
                                 Echo::parse(parser).map(RequestMethod::Echo)
                             }
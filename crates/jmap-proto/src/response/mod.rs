@@ -31,9 +31,11 @@ use crate::{
     method::{
         changes::ChangesResponse,
         copy::{CopyBlobResponse, CopyResponse},
+        deleted::{DeletedEmailGetResponse, DeletedEmailRestoreResponse},
         get::GetResponse,
         import::ImportEmailResponse,
         lookup::BlobLookupResponse,
+        mdn::{MdnParseResponse, MdnSendResponse},
         parse::ParseEmailResponse,
         query::QueryResponse,
         query_changes::QueryChangesResponse,
@@ -64,6 +66,10 @@ pub enum ResponseMethod {
     ValidateScript(ValidateSieveScriptResponse),
     LookupBlob(BlobLookupResponse),
     UploadBlob(BlobUploadResponse),
+    SendMdn(MdnSendResponse),
+    ParseMdn(MdnParseResponse),
+    GetDeletedEmail(DeletedEmailGetResponse),
+    RestoreDeletedEmail(DeletedEmailRestoreResponse),
     Echo(Echo),
     Error(MethodError),
 }
@@ -165,6 +171,30 @@ impl From<ImportEmailResponse> for ResponseMethod {
     }
 }
 
+impl From<MdnSendResponse> for ResponseMethod {
+    fn from(send_mdn: MdnSendResponse) -> Self {
+        ResponseMethod::SendMdn(send_mdn)
+    }
+}
+
+impl From<MdnParseResponse> for ResponseMethod {
+    fn from(parse_mdn: MdnParseResponse) -> Self {
+        ResponseMethod::ParseMdn(parse_mdn)
+    }
+}
+
+impl From<DeletedEmailGetResponse> for ResponseMethod {
+    fn from(get_deleted: DeletedEmailGetResponse) -> Self {
+        ResponseMethod::GetDeletedEmail(get_deleted)
+    }
+}
+
+impl From<DeletedEmailRestoreResponse> for ResponseMethod {
+    fn from(restore_deleted: DeletedEmailRestoreResponse) -> Self {
+        ResponseMethod::RestoreDeletedEmail(restore_deleted)
+    }
+}
+
 impl From<ParseEmailResponse> for ResponseMethod {
     fn from(parse_email: ParseEmailResponse) -> Self {
         ResponseMethod::ParseEmail(parse_email)
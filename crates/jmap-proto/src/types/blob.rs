@@ -97,6 +97,19 @@ impl BlobId {
         self
     }
 
+    /// Returns a strong entity tag derived from the blob hash and section.
+    pub fn etag(&self) -> String {
+        let hash = Base32Writer::from_bytes(self.hash.as_slice()).finalize();
+        if let Some(section) = &self.section {
+            format!(
+                "\"{hash}-{:x}-{:x}-{:x}\"",
+                section.offset_start, section.size, section.encoding
+            )
+        } else {
+            format!("\"{hash}\"")
+        }
+    }
+
     pub fn from_base32(value: impl AsRef<[u8]>) -> Option<Self> {
         BlobId::from_iter(&mut Base32Reader::new(value.as_ref()))
     }
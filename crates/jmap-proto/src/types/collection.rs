@@ -38,7 +38,8 @@ pub enum Collection {
     SieveScript = 5,
     PushSubscription = 6,
     Principal = 7,
-    None = 8,
+    ShareNotification = 8,
+    None = 9,
 }
 
 impl From<u8> for Collection {
@@ -52,6 +53,7 @@ impl From<u8> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::ShareNotification,
             _ => Collection::None,
         }
     }
@@ -68,6 +70,7 @@ impl From<u64> for Collection {
             5 => Collection::SieveScript,
             6 => Collection::PushSubscription,
             7 => Collection::Principal,
+            8 => Collection::ShareNotification,
             _ => Collection::None,
         }
     }
@@ -97,6 +100,7 @@ impl TryFrom<Collection> for DataType {
             Collection::EmailSubmission => Ok(DataType::EmailSubmission),
             Collection::SieveScript => Ok(DataType::SieveScript),
             Collection::PushSubscription => Ok(DataType::PushSubscription),
+            Collection::ShareNotification => Ok(DataType::ShareNotification),
             _ => Err(()),
         }
     }
@@ -113,6 +117,7 @@ impl Display for Collection {
             Collection::EmailSubmission => write!(f, "emailSubmission"),
             Collection::SieveScript => write!(f, "sieveScript"),
             Collection::Principal => write!(f, "principal"),
+            Collection::ShareNotification => write!(f, "shareNotification"),
             Collection::None => write!(f, ""),
         }
     }
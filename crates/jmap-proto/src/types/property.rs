@@ -136,6 +136,15 @@ pub enum Property {
     WarnLimit,
     SoftLimit,
     Scope,
+    Created,
+    ChangedBy,
+    ObjectType,
+    ObjectAccountId,
+    ObjectId,
+    OldRights,
+    NewRights,
+    PrincipalId,
+    SeenBy,
     Digest(DigestProperty),
     Data(DataProperty),
     _T(String),
@@ -366,6 +375,8 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x63 => Property::Cc,
             0x7465_7372_6168 => Property::Charset,
             0x6469 => Property::Cid,
+            0x6465_7461_6572 => Property::Created,
+            0x7942_6465_676e_6168 => Property::ChangedBy,
             _ => return None,
         },
         b'd' => match hash {
@@ -427,6 +438,14 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
         },
         b'n' => match hash {
             0x0065_6d61 => Property::Name,
+            0x7374_6867_6952_7765 => Property::NewRights,
+            _ => return None,
+        },
+        b'o' => match hash {
+            0x0065_7079_5474_6365_6a62 => Property::ObjectType,
+            0x6449_746e_756f_6363_4174_6365_6a62 => Property::ObjectAccountId,
+            0x0064_4974_6365_6a62 => Property::ObjectId,
+            0x7374_6867_6952_646c => Property::OldRights,
             _ => return None,
         },
         b'p' => match hash {
@@ -434,6 +453,7 @@ fn parse_property(first_char: u8, hash: u128) -> Option<Property> {
             0x0064_4974_7261 => Property::PartId,
             0x6572_7574_6369 => Property::Picture,
             0x7765_6976_6572 => Property::Preview,
+            0x6449_6c61_7069_636e_6972 => Property::PrincipalId,
             _ => return None,
         },
         b'q' => match hash {
@@ -898,6 +918,15 @@ impl Display for Property {
             Property::Used => write!(f, "used"),
             Property::HardLimit => write!(f, "hardLimit"),
             Property::Scope => write!(f, "scope"),
+            Property::Created => write!(f, "created"),
+            Property::ChangedBy => write!(f, "changedBy"),
+            Property::ObjectType => write!(f, "objectType"),
+            Property::ObjectAccountId => write!(f, "objectAccountId"),
+            Property::ObjectId => write!(f, "objectId"),
+            Property::OldRights => write!(f, "oldRights"),
+            Property::NewRights => write!(f, "newRights"),
+            Property::PrincipalId => write!(f, "principalId"),
+            Property::SeenBy => write!(f, "seenBy"),
             Property::WarnLimit => write!(f, "warnLimit"),
             Property::SoftLimit => write!(f, "softLimit"),
             Property::_T(s) => write!(f, "{s}"),
@@ -1080,6 +1109,15 @@ impl From<&Property> for u8 {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Created => 104,
+            Property::ChangedBy => 105,
+            Property::ObjectType => 106,
+            Property::ObjectAccountId => 107,
+            Property::ObjectId => 108,
+            Property::OldRights => 109,
+            Property::NewRights => 110,
+            Property::PrincipalId => 111,
+            Property::SeenBy => 112,
             Property::Digest(_) | Property::Data(_) => unreachable!("invalid property"),
         }
     }
@@ -1222,6 +1260,15 @@ impl SerializeInto for Property {
             Property::WarnLimit => 101,
             Property::SoftLimit => 102,
             Property::Scope => 103,
+            Property::Created => 104,
+            Property::ChangedBy => 105,
+            Property::ObjectType => 106,
+            Property::ObjectAccountId => 107,
+            Property::ObjectId => 108,
+            Property::OldRights => 109,
+            Property::NewRights => 110,
+            Property::PrincipalId => 111,
+            Property::SeenBy => 112,
             Property::Digest(_) | Property::Data(_) => {
                 unreachable!("Property::Digest and Property::Data are not serializable")
             }
@@ -1340,6 +1387,15 @@ impl DeserializeFrom for Property {
             101 => Some(Property::WarnLimit),
             102 => Some(Property::SoftLimit),
             103 => Some(Property::Scope),
+            104 => Some(Property::Created),
+            105 => Some(Property::ChangedBy),
+            106 => Some(Property::ObjectType),
+            107 => Some(Property::ObjectAccountId),
+            108 => Some(Property::ObjectId),
+            109 => Some(Property::OldRights),
+            110 => Some(Property::NewRights),
+            111 => Some(Property::PrincipalId),
+            112 => Some(Property::SeenBy),
             _ => None,
         }
     }
@@ -21,22 +21,36 @@
  * for more details.
 */
 
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc};
 
 use directory::{
-    backend::internal::{lookup::DirectoryStore, manage::ManageDirectory, PrincipalUpdate},
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    core::{
+        domain::DomainSettings,
+        retention::RetentionRule,
+        roles::{Authorization, Permission, Scope},
+    },
     DirectoryError, ManagementError, Principal, QueryBy, Type,
 };
 use http_body_util::combinators::BoxBody;
 use hyper::{body::Bytes, Method, StatusCode};
-use jmap_proto::error::request::RequestError;
+use jmap_proto::{
+    error::request::RequestError,
+    method::deleted::DeletedEmail,
+    types::{collection::Collection, id::Id},
+};
 use serde_json::json;
-use store::ahash::AHashMap;
-use utils::{config::ConfigKey, url_params::UrlParams};
+use store::{ahash::AHashMap, BitmapKey};
+use utils::{config::ConfigKey, map::vec_map::VecMap, url_params::UrlParams};
 
 use crate::{
     auth::{oauth::OAuthCodeRequest, AccessToken},
-    services::housekeeper,
+    blob::{DownloadRange, DownloadResponse},
+    list::ListSettings,
+    services::{
+        audit::{redact_secrets, AuditAction, AuditEvent, AuditFilter},
+        housekeeper,
+    },
     JMAP,
 };
 
@@ -90,19 +104,114 @@ impl JMAP {
         req: &HttpRequest,
         body: Option<Vec<u8>>,
         access_token: Arc<AccessToken>,
+        authorization: &Authorization,
+        remote_addr: IpAddr,
+    ) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
+        let mut path = req.uri().path().split('/').skip(2);
+        let path_1 = path.next().unwrap_or("");
+        let path_2 = path.next();
+
+        if let Some(action) = audit_action(path_1, path_2, req.method()) {
+            // Record the change, along with the request that caused it, in the audit log
+            let target = if path_2.is_some() {
+                req.uri()
+                    .path()
+                    .split('/')
+                    .skip(3)
+                    .collect::<Vec<_>>()
+                    .join("/")
+            } else {
+                body.as_deref()
+                    .and_then(|body| serde_json::from_slice::<PrincipalResponse>(body).ok())
+                    .map(|principal| principal.name)
+                    .unwrap_or_default()
+            };
+            let diff = match (&body, req.uri().query()) {
+                (Some(body), _) => {
+                    serde_json::from_slice::<serde_json::Value>(body)
+                        .ok()
+                        .map(|mut value| {
+                            redact_secrets(&mut value);
+                            value.to_string()
+                        })
+                }
+                (None, Some(query)) => json!({ "query": query }).to_string().into(),
+                (None, None) => None,
+            };
+            let mut event = AuditEvent::new(action)
+                .with_actor(access_token.name.clone())
+                .with_remote_ip(remote_addr)
+                .with_diff(diff);
+            if !target.is_empty() {
+                event = event.with_target(target);
+            }
+
+            let response = self
+                .process_manage_request(req, body, access_token, authorization, remote_addr)
+                .await;
+            self.audit(event.with_success(response.status().is_success()))
+                .await;
+            response
+        } else {
+            self.process_manage_request(req, body, access_token, authorization, remote_addr)
+                .await
+        }
+    }
+
+    async fn process_manage_request(
+        &self,
+        req: &HttpRequest,
+        body: Option<Vec<u8>>,
+        access_token: Arc<AccessToken>,
+        authorization: &Authorization,
+        remote_addr: IpAddr,
     ) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
         let mut path = req.uri().path().split('/');
         path.next();
         path.next();
+        let path_1 = path.next().unwrap_or("");
+        let path_2 = path.next();
 
-        match (path.next().unwrap_or(""), path.next(), req.method()) {
+        // Make sure the principal has been granted the required permission
+        let scope = match required_permission(path_1, path_2, req.method()) {
+            Some((permission, requires_global)) => match authorization.scope(permission) {
+                Some(scope) if scope.is_all() || !requires_global => scope,
+                _ => {
+                    return RequestError::forbidden().into_http_response();
+                }
+            },
+            // OAuth requests are available to every authenticated principal
+            None if path_1 == "oauth" => &Scope::All,
+            None => {
+                return RequestError::forbidden().into_http_response();
+            }
+        };
+
+        match (path_1, path_2, req.method()) {
             ("principal", None, &Method::POST) => {
                 // Create principal
                 if let Some(principal) =
                     body.and_then(|body| serde_json::from_slice::<PrincipalResponse>(&body).ok())
                 {
+                    // Domain administrators can only create principals within their domains
+                    if (principal.typ == Type::Superuser && !access_token.is_super_user())
+                        || (!access_token.is_super_user()
+                            && principal
+                                .member_of
+                                .iter()
+                                .chain(std::iter::once(&principal.name))
+                                .any(|name| self.config.roles.is_role_member(name)))
+                        || !scope.contains_principal(&principal.name, &principal.emails)
+                        || !self
+                            .is_principal_in_scope(scope, &principal.member_of)
+                            .await
+                        || !self.is_principal_in_scope(scope, &principal.members).await
+                    {
+                        return RequestError::forbidden().into_http_response();
+                    }
+
                     match self
-                        .store
+                        .directory
                         .create_account(
                             Principal {
                                 id: principal.id,
@@ -141,8 +250,21 @@ impl JMAP {
                 let page: usize = params.parse("page").unwrap_or(0);
                 let limit: usize = params.parse("limit").unwrap_or(0);
 
-                match self.store.list_accounts(filter, typ).await {
-                    Ok(accounts) => {
+                match self.directory.list_accounts(filter, typ).await {
+                    Ok(mut accounts) => {
+                        if !scope.is_all() {
+                            let mut scoped_accounts = Vec::with_capacity(accounts.len());
+                            for account in accounts {
+                                if self
+                                    .is_principal_in_scope(scope, std::slice::from_ref(&account))
+                                    .await
+                                {
+                                    scoped_accounts.push(account);
+                                }
+                            }
+                            accounts = scoped_accounts;
+                        }
+
                         let (total, accounts) = if limit > 0 {
                             let offset = page.saturating_sub(1) * limit;
                             (
@@ -166,24 +288,35 @@ impl JMAP {
             }
             ("principal", Some(name), method) => {
                 // Fetch, update or delete principal
-                let account_id = match self.store.get_account_id(name).await {
-                    Ok(Some(account_id)) => account_id,
-                    Ok(None) => {
-                        return RequestError::blank(
-                            StatusCode::NOT_FOUND.as_u16(),
-                            "Not found",
-                            "Account not found.",
-                        )
-                        .into_http_response();
-                    }
-                    Err(err) => {
-                        return map_directory_error(err);
-                    }
-                };
+                let (account_id, account_name) =
+                    match self.directory.query(QueryBy::Name(name), false).await {
+                        Ok(Some(principal)) => {
+                            if !scope.contains_principal(&principal.name, &principal.emails)
+                                || (principal.typ == Type::Superuser
+                                    && *method != Method::GET
+                                    && !access_token.is_super_user())
+                            {
+                                return RequestError::forbidden().into_http_response();
+                            }
+                            (principal.id, principal.name)
+                        }
+                        Ok(None) => {
+                            return RequestError::blank(
+                                StatusCode::NOT_FOUND.as_u16(),
+                                "Not found",
+                                "Account not found.",
+                            )
+                            .into_http_response();
+                        }
+                        Err(err) => {
+                            return map_directory_error(err);
+                        }
+                    };
 
                 match *method {
                     Method::GET => {
-                        let result = match self.store.query(QueryBy::Id(account_id), true).await {
+                        let result = match self.directory.query(QueryBy::Id(account_id), true).await
+                        {
                             Ok(Some(principal)) => self.store.map_group_ids(principal).await,
                             Ok(None) => {
                                 return RequestError::blank(
@@ -209,7 +342,7 @@ impl JMAP {
                                     self.store.get_members(account_id).await.unwrap_or_default()
                                 {
                                     if let Ok(Some(member_principal)) =
-                                        self.store.query(QueryBy::Id(member_id), false).await
+                                        self.directory.query(QueryBy::Id(member_id), false).await
                                     {
                                         principal.members.push(member_principal.name);
                                     }
@@ -241,7 +374,7 @@ impl JMAP {
                         }
 
                         // Delete account
-                        match self.store.delete_account(QueryBy::Id(account_id)).await {
+                        match self.directory.delete_account(QueryBy::Id(account_id)).await {
                             Ok(_) => JsonResponse::new(json!({
                                 "data": (),
                             }))
@@ -253,8 +386,15 @@ impl JMAP {
                         if let Some(changes) = body.and_then(|body| {
                             serde_json::from_slice::<Vec<PrincipalUpdate>>(&body).ok()
                         }) {
+                            if !self
+                                .is_update_allowed(scope, &access_token, &account_name, &changes)
+                                .await
+                            {
+                                return RequestError::forbidden().into_http_response();
+                            }
+
                             match self
-                                .store
+                                .directory
                                 .update_account(QueryBy::Id(account_id), changes)
                                 .await
                             {
@@ -276,6 +416,193 @@ impl JMAP {
                     _ => RequestError::not_found().into_http_response(),
                 }
             }
+            ("retention", Some(name), method) => {
+                // Fetch or update per-account retention rules
+                let account_id = match self.directory.query(QueryBy::Name(name), false).await {
+                    Ok(Some(principal)) => {
+                        if !scope.contains_principal(&principal.name, &principal.emails) {
+                            return RequestError::forbidden().into_http_response();
+                        }
+                        principal.id
+                    }
+                    Ok(None) => {
+                        return RequestError::blank(
+                            StatusCode::NOT_FOUND.as_u16(),
+                            "Not found",
+                            "Account not found.",
+                        )
+                        .into_http_response();
+                    }
+                    Err(err) => {
+                        return map_directory_error(err);
+                    }
+                };
+
+                let result = match *method {
+                    Method::GET => match self.directory.retention_rules(account_id).await {
+                        Ok(rules) => {
+                            return JsonResponse::new(json!({
+                                "data": rules,
+                            }))
+                            .into_http_response()
+                        }
+                        Err(err) => Err(err),
+                    },
+                    Method::PUT => {
+                        if let Some(rules) = body.and_then(|body| {
+                            serde_json::from_slice::<Vec<RetentionRule>>(&body).ok()
+                        }) {
+                            self.directory.set_retention_rules(account_id, rules).await
+                        } else {
+                            return RequestError::blank(
+                                StatusCode::BAD_REQUEST.as_u16(),
+                                "Invalid parameters",
+                                "Failed to deserialize retention rules",
+                            )
+                            .into_http_response();
+                        }
+                    }
+                    Method::DELETE => {
+                        self.directory
+                            .set_retention_rules(account_id, Vec::new())
+                            .await
+                    }
+                    _ => return RequestError::not_found().into_http_response(),
+                };
+
+                match result {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => map_directory_error(err),
+                }
+            }
+            ("legal-hold", Some(name), method) => {
+                // Fetch, place or release the legal hold of an account
+                let account_id = match self.directory.query(QueryBy::Name(name), false).await {
+                    Ok(Some(principal)) => {
+                        if !scope.contains_principal(&principal.name, &principal.emails) {
+                            return RequestError::forbidden().into_http_response();
+                        }
+                        principal.id
+                    }
+                    Ok(None) => {
+                        return RequestError::blank(
+                            StatusCode::NOT_FOUND.as_u16(),
+                            "Not found",
+                            "Account not found.",
+                        )
+                        .into_http_response();
+                    }
+                    Err(err) => {
+                        return map_directory_error(err);
+                    }
+                };
+
+                let result = match *method {
+                    Method::GET => match self.store.legal_hold(account_id).await {
+                        Ok(since) => {
+                            return JsonResponse::new(json!({
+                                "data": {
+                                    "enabled": since.is_some(),
+                                    "since": since,
+                                },
+                            }))
+                            .into_http_response()
+                        }
+                        Err(err) => Err(err),
+                    },
+                    Method::PUT => self.store.set_legal_hold(account_id, true).await,
+                    Method::DELETE => self.store.set_legal_hold(account_id, false).await,
+                    _ => return RequestError::not_found().into_http_response(),
+                };
+
+                match result {
+                    Ok(_) => JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response(),
+                    Err(err) => map_store_error(err),
+                }
+            }
+            ("recovery", Some(name), method) => {
+                // List or restore the deleted items of an account
+                let (account_id, account_quota) =
+                    match self.directory.query(QueryBy::Name(name), false).await {
+                        Ok(Some(principal)) => {
+                            if !scope.contains_principal(&principal.name, &principal.emails) {
+                                return RequestError::forbidden().into_http_response();
+                            }
+                            (principal.id, principal.quota as i64)
+                        }
+                        Ok(None) => {
+                            return RequestError::blank(
+                                StatusCode::NOT_FOUND.as_u16(),
+                                "Not found",
+                                "Account not found.",
+                            )
+                            .into_http_response();
+                        }
+                        Err(err) => {
+                            return map_directory_error(err);
+                        }
+                    };
+
+                match *method {
+                    Method::GET => match self.deleted_items(account_id).await {
+                        Ok(items) => JsonResponse::new(json!({
+                            "data": items
+                                .into_iter()
+                                .map(|item| DeletedEmail::from(item.inner))
+                                .collect::<Vec<_>>(),
+                        }))
+                        .into_http_response(),
+                        Err(_) => RequestError::internal_server_error().into_http_response(),
+                    },
+                    Method::POST => {
+                        if let Some(ids) =
+                            body.and_then(|body| serde_json::from_slice::<Vec<Id>>(&body).ok())
+                        {
+                            let mut restored = VecMap::new();
+                            let mut not_restored = VecMap::new();
+                            for id in ids {
+                                match self
+                                    .deleted_item_restore(account_id, account_quota, id.into())
+                                    .await
+                                {
+                                    Ok(Ok(email_id)) => {
+                                        restored.append(id, email_id);
+                                    }
+                                    Ok(Err(err)) => {
+                                        not_restored.append(id, err);
+                                    }
+                                    Err(_) => {
+                                        return RequestError::internal_server_error()
+                                            .into_http_response();
+                                    }
+                                }
+                            }
+
+                            JsonResponse::new(json!({
+                                "data": {
+                                    "restored": restored,
+                                    "notRestored": not_restored,
+                                },
+                            }))
+                            .into_http_response()
+                        } else {
+                            RequestError::blank(
+                                StatusCode::BAD_REQUEST.as_u16(),
+                                "Invalid parameters",
+                                "Failed to deserialize item ids",
+                            )
+                            .into_http_response()
+                        }
+                    }
+                    _ => RequestError::not_found().into_http_response(),
+                }
+            }
             ("domain", None, &Method::GET) => {
                 // List domains
                 let params = UrlParams::new(req.uri().query());
@@ -283,8 +610,10 @@ impl JMAP {
                 let page: usize = params.parse("page").unwrap_or(0);
                 let limit: usize = params.parse("limit").unwrap_or(0);
 
-                match self.store.list_domains(filter).await {
-                    Ok(domains) => {
+                match self.directory.list_domains(filter).await {
+                    Ok(mut domains) => {
+                        domains.retain(|domain| scope.contains_domain(domain));
+
                         let (total, domains) = if limit > 0 {
                             let offset = page.saturating_sub(1) * limit;
                             (
@@ -306,9 +635,57 @@ impl JMAP {
                     Err(err) => map_directory_error(err),
                 }
             }
+            ("domain", Some(domain), _) if !scope.contains_domain(domain) => {
+                RequestError::forbidden().into_http_response()
+            }
+            ("domain", Some(domain), &Method::GET) => {
+                // Obtain domain settings and usage
+                match self.directory.is_local_domain(domain).await {
+                    Ok(true) => {}
+                    Ok(false) => return RequestError::not_found().into_http_response(),
+                    Err(err) => return map_directory_error(err),
+                }
+                let settings = match self.directory.domain_settings(domain).await {
+                    Ok(settings) => settings.unwrap_or_default(),
+                    Err(err) => return map_directory_error(err),
+                };
+                match self.directory.domain_usage(domain).await {
+                    Ok(usage) => JsonResponse::new(json!({
+                        "data": {
+                            "name": domain,
+                            "settings": settings,
+                            "accounts": usage.accounts,
+                            "usedQuota": usage.used_quota,
+                        },
+                    }))
+                    .into_http_response(),
+                    Err(err) => map_directory_error(err),
+                }
+            }
+            ("domain", Some(domain), &Method::PATCH) => {
+                // Update domain settings
+                if let Some(settings) =
+                    body.and_then(|body| serde_json::from_slice::<DomainSettings>(&body).ok())
+                {
+                    match self.directory.set_domain_settings(domain, settings).await {
+                        Ok(_) => JsonResponse::new(json!({
+                            "data": (),
+                        }))
+                        .into_http_response(),
+                        Err(err) => map_directory_error(err),
+                    }
+                } else {
+                    RequestError::blank(
+                        StatusCode::BAD_REQUEST.as_u16(),
+                        "Invalid parameters",
+                        "Failed to deserialize domain settings",
+                    )
+                    .into_http_response()
+                }
+            }
             ("domain", Some(domain), &Method::POST) => {
                 // Create domain
-                match self.store.create_domain(domain).await {
+                match self.directory.create_domain(domain).await {
                     Ok(_) => JsonResponse::new(json!({
                         "data": (),
                     }))
@@ -318,7 +695,7 @@ impl JMAP {
             }
             ("domain", Some(domain), &Method::DELETE) => {
                 // Delete domain
-                match self.store.delete_domain(domain).await {
+                match self.directory.delete_domain(domain).await {
                     Ok(_) => JsonResponse::new(json!({
                         "data": (),
                     }))
@@ -326,6 +703,116 @@ impl JMAP {
                     Err(err) => map_directory_error(err),
                 }
             }
+            ("list", Some(list_address), method) => {
+                // Mailing list management
+                let list = match self.directory.query_list(list_address).await {
+                    Ok(Some(list)) => list,
+                    Ok(None) => return RequestError::not_found().into_http_response(),
+                    Err(err) => return map_directory_error(err),
+                };
+                if !scope.contains_principal(&list.name, &list.emails) {
+                    return RequestError::forbidden().into_http_response();
+                }
+                let list_address = list_address.to_lowercase();
+
+                match (path.next(), path.next(), method) {
+                    (None, None, &Method::GET) => {
+                        let settings = match self.list_settings(list.id).await {
+                            Ok(settings) => settings,
+                            Err(err) => return map_store_error(err),
+                        };
+                        match self.list_subscribers(&list).await {
+                            Ok(subscribers) => JsonResponse::new(json!({
+                                "data": {
+                                    "name": list_address,
+                                    "settings": settings,
+                                    "subscribers": subscribers,
+                                },
+                            }))
+                            .into_http_response(),
+                            Err(err) => map_store_error(err),
+                        }
+                    }
+                    (None, None, &Method::PATCH) => {
+                        // Update list settings
+                        if let Some(settings) =
+                            body.and_then(|body| serde_json::from_slice::<ListSettings>(&body).ok())
+                        {
+                            match self.set_list_settings(list.id, settings).await {
+                                Ok(_) => JsonResponse::new(json!({
+                                    "data": (),
+                                }))
+                                .into_http_response(),
+                                Err(err) => map_store_error(err),
+                            }
+                        } else {
+                            RequestError::blank(
+                                StatusCode::BAD_REQUEST.as_u16(),
+                                "Invalid parameters",
+                                "Failed to deserialize list settings",
+                            )
+                            .into_http_response()
+                        }
+                    }
+                    (Some("subscribers"), Some(address), &Method::PUT | &Method::DELETE) => {
+                        // Add or remove a subscriber
+                        let result = if method == Method::PUT {
+                            self.add_list_subscriber(list.id, address).await
+                        } else {
+                            self.remove_list_subscriber(list.id, address).await
+                        };
+                        match result {
+                            Ok(_) => JsonResponse::new(json!({
+                                "data": (),
+                            }))
+                            .into_http_response(),
+                            Err(err) => map_store_error(err),
+                        }
+                    }
+                    (Some("held"), None, &Method::GET) => {
+                        // List messages awaiting moderation
+                        match self.list_held_messages(list.id).await {
+                            Ok(messages) => JsonResponse::new(json!({
+                                "data": messages
+                                    .into_iter()
+                                    .map(|held| json!({
+                                        "id": held.id.to_string(),
+                                        "sender": held.sender,
+                                        "subject": held.subject,
+                                        "size": held.size,
+                                        "receivedAt": held.received_at,
+                                        "expires": held.expires,
+                                    }))
+                                    .collect::<Vec<_>>(),
+                            }))
+                            .into_http_response(),
+                            Err(err) => map_store_error(err),
+                        }
+                    }
+                    (Some(action @ ("approve" | "reject")), Some(id), &Method::POST) => {
+                        // Approve or reject a held message
+                        let held = match id.parse::<u64>() {
+                            Ok(id) => match self.list_held_message(list.id, id).await {
+                                Ok(Some(held)) => held,
+                                Ok(None) => return RequestError::not_found().into_http_response(),
+                                Err(err) => return map_store_error(err),
+                            },
+                            Err(_) => return RequestError::not_found().into_http_response(),
+                        };
+                        match self
+                            .list_moderate(&list, &list_address, &held, action == "approve")
+                            .await
+                        {
+                            Ok(_) => JsonResponse::new(json!({
+                                "data": (),
+                            }))
+                            .into_http_response(),
+                            Err(err) => map_store_error(err),
+                        }
+                    }
+                    _ => RequestError::not_found().into_http_response(),
+                }
+            }
             ("store", Some("maintenance"), &Method::GET) => {
                 match self.store.purge_blobs(self.blob_store.clone()).await {
                     Ok(_) => match self.store.purge_store().await {
@@ -348,6 +835,152 @@ impl JMAP {
                     .into_http_response(),
                 }
             }
+            ("store", Some("fsck"), &Method::GET) => {
+                // Report the progress of the last consistency check
+                JsonResponse::new(json!({
+                    "data": self.fsck_status.lock().clone(),
+                }))
+                .into_http_response()
+            }
+            ("store", Some("fsck"), &Method::POST) => {
+                let params = UrlParams::new(req.uri().query());
+                let repair = params.parse::<bool>("repair").unwrap_or(false);
+
+                // Obtain the accounts to check
+                let account_ids: Vec<u32> = if let Some(account) = params.get("account") {
+                    match self.store.get_account_id(account).await {
+                        Ok(Some(account_id)) => vec![account_id],
+                        Ok(None) => {
+                            return RequestError::blank(
+                                StatusCode::NOT_FOUND.as_u16(),
+                                "Not found",
+                                "Account not found.",
+                            )
+                            .into_http_response();
+                        }
+                        Err(err) => return map_directory_error(err),
+                    }
+                } else {
+                    match self
+                        .store
+                        .get_bitmap(BitmapKey::document_ids(u32::MAX, Collection::Principal))
+                        .await
+                    {
+                        Ok(account_ids) => account_ids.unwrap_or_default().into_iter().collect(),
+                        Err(err) => {
+                            return RequestError::blank(
+                                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                                "Store check failed",
+                                err.to_string(),
+                            )
+                            .into_http_response();
+                        }
+                    }
+                };
+
+                // Run the check in the background
+                if !self.fsck_start(account_ids.len(), repair) {
+                    return RequestError::blank(
+                        StatusCode::CONFLICT.as_u16(),
+                        "Check in progress",
+                        "A store consistency check is already running.",
+                    )
+                    .into_http_response();
+                }
+                if self
+                    .housekeeper_tx
+                    .send(housekeeper::Event::Fsck {
+                        account_ids,
+                        repair,
+                    })
+                    .await
+                    .is_err()
+                {
+                    self.fsck_status.lock().is_running = false;
+                    return RequestError::blank(
+                        StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        "Store check failed",
+                        "Failed to start the store consistency check.",
+                    )
+                    .into_http_response();
+                }
+
+                JsonResponse::new(json!({
+                    "data": self.fsck_status.lock().clone(),
+                }))
+                .into_http_response()
+            }
+            ("store", Some("reindex"), &Method::GET) => {
+                // Report the progress of the last reindex
+                JsonResponse::new(json!({
+                    "data": self.reindex_status.lock().clone(),
+                }))
+                .into_http_response()
+            }
+            ("store", Some("reindex"), &Method::POST) => {
+                let params = UrlParams::new(req.uri().query());
+
+                // Obtain the accounts to reindex
+                let account_ids: Vec<u32> = if let Some(account) = params.get("account") {
+                    match self.store.get_account_id(account).await {
+                        Ok(Some(account_id)) => vec![account_id],
+                        Ok(None) => {
+                            return RequestError::blank(
+                                StatusCode::NOT_FOUND.as_u16(),
+                                "Not found",
+                                "Account not found.",
+                            )
+                            .into_http_response();
+                        }
+                        Err(err) => return map_directory_error(err),
+                    }
+                } else {
+                    match self
+                        .store
+                        .get_bitmap(BitmapKey::document_ids(u32::MAX, Collection::Principal))
+                        .await
+                    {
+                        Ok(account_ids) => account_ids.unwrap_or_default().into_iter().collect(),
+                        Err(err) => {
+                            return RequestError::blank(
+                                StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                                "Reindex failed",
+                                err.to_string(),
+                            )
+                            .into_http_response();
+                        }
+                    }
+                };
+
+                // Queue the accounts for reindexing in the background
+                if !self.fts_reindex_start(account_ids.len()) {
+                    return RequestError::blank(
+                        StatusCode::CONFLICT.as_u16(),
+                        "Reindex in progress",
+                        "A full-text index rebuild is already running.",
+                    )
+                    .into_http_response();
+                }
+                if self
+                    .housekeeper_tx
+                    .send(housekeeper::Event::Reindex { account_ids })
+                    .await
+                    .is_err()
+                {
+                    self.reindex_status.lock().is_running = false;
+                    return RequestError::blank(
+                        StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        "Reindex failed",
+                        "Failed to start the full-text index rebuild.",
+                    )
+                    .into_http_response();
+                }
+
+                JsonResponse::new(json!({
+                    "data": self.reindex_status.lock().clone(),
+                }))
+                .into_http_response()
+            }
             ("reload", Some("settings"), &Method::GET) => {
                 let _ = self
                     .housekeeper_tx
@@ -707,10 +1340,69 @@ impl JMAP {
                     .into_http_response()
                 }
             }
-            ("oauth", _, _) => self.handle_api_request(req, body, access_token).await,
-            (path_1 @ ("queue" | "reports"), Some(path_2), &Method::GET) => {
+            ("audit", None, &Method::GET) => {
+                // List audit log entries, newest first
+                let params = UrlParams::new(req.uri().query());
+                let page: usize = params.parse("page").unwrap_or(0);
+                let limit: usize = params.parse("limit").unwrap_or(0);
+
+                match self
+                    .audit_query(
+                        &AuditFilter::from(&params),
+                        page.saturating_sub(1) * limit,
+                        limit,
+                    )
+                    .await
+                {
+                    Ok((total, entries)) => JsonResponse::new(json!({
+                            "data": {
+                                "items": entries.iter().map(|entry| entry.to_json()).collect::<Vec<_>>(),
+                                "total": total,
+                            },
+                    }))
+                    .into_http_response(),
+                    Err(err) => map_store_error(err),
+                }
+            }
+            ("audit", Some("export"), &Method::GET) => {
+                // Export audit log entries in chronological order, one JSON object per line
+                match self
+                    .audit_export(&AuditFilter::from(&UrlParams::new(req.uri().query())))
+                    .await
+                {
+                    Ok(export) => DownloadResponse {
+                        filename: "audit.jsonl".to_string(),
+                        content_type: "application/x-ndjson".to_string(),
+                        size: export.len(),
+                        blob: export,
+                        etag: None,
+                        range: DownloadRange::Full,
+                    }
+                    .into_http_response(),
+                    Err(err) => map_store_error(err),
+                }
+            }
+            ("audit", Some("verify"), &Method::GET) => match self.audit_verify().await {
+                Ok(result) => JsonResponse::new(json!({
+                    "data": result,
+                }))
+                .into_http_response(),
+                Err(err) => map_store_error(err),
+            },
+            ("oauth", _, _) => {
+                self.handle_api_request(req, body, access_token, remote_addr)
+                    .await
+            }
+            (path_1 @ ("queue" | "reports"), Some(path_2), _) => {
                 self.smtp
-                    .handle_manage_request(req.uri(), req.method(), path_1, path_2, path.next())
+                    .handle_manage_request(
+                        req.uri(),
+                        req.method(),
+                        path_1,
+                        path_2,
+                        path.next(),
+                        authorization,
+                    )
                     .await
             }
             _ => RequestError::not_found().into_http_response(),
@@ -722,6 +1414,7 @@ impl JMAP {
         req: &HttpRequest,
         body: Option<Vec<u8>>,
         access_token: Arc<AccessToken>,
+        remote_addr: IpAddr,
     ) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
         let mut path = req.uri().path().split('/');
         path.next();
@@ -732,6 +1425,14 @@ impl JMAP {
                 if let Some(request) =
                     body.and_then(|body| serde_json::from_slice::<OAuthCodeRequest>(&body).ok())
                 {
+                    self.audit(
+                        AuditEvent::new(AuditAction::OAuthGrant)
+                            .with_actor(access_token.name.clone())
+                            .with_remote_ip(remote_addr)
+                            .with_target(request.client_id.clone()),
+                    )
+                    .await;
+
                     JsonResponse::new(json!({
                         "data": self.issue_client_code(&access_token, request.client_id, request.redirect_uri),
                     }))
@@ -750,7 +1451,175 @@ impl JMAP {
     }
 }
 
-fn map_directory_error(err: DirectoryError) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
+impl JMAP {
+    async fn is_principal_in_scope(&self, scope: &Scope, names: &[String]) -> bool {
+        if scope.is_all() {
+            return true;
+        }
+
+        for name in names {
+            match self.directory.query(QueryBy::Name(name), false).await {
+                Ok(Some(principal))
+                    if scope.contains_principal(&principal.name, &principal.emails) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+
+    async fn is_update_allowed(
+        &self,
+        scope: &Scope,
+        access_token: &AccessToken,
+        name: &str,
+        changes: &[PrincipalUpdate],
+    ) -> bool {
+        for change in changes {
+            // Roles are granted by name, only superusers can change who holds them
+            if !access_token.is_super_user() {
+                let is_role_change = match change.field {
+                    PrincipalField::Name | PrincipalField::MemberOf => values_of(&change.value)
+                        .iter()
+                        .any(|name| self.config.roles.is_role_member(name)),
+                    PrincipalField::Members => {
+                        self.config.roles.is_role_member(name)
+                            || values_of(&change.value)
+                                .iter()
+                                .any(|name| self.config.roles.is_role_member(name))
+                    }
+                    _ => false,
+                };
+                if is_role_change {
+                    return false;
+                }
+            }
+
+            let values = values_of(&change.value);
+
+            let is_allowed = match change.field {
+                PrincipalField::Type => {
+                    access_token.is_super_user()
+                        || !values
+                            .iter()
+                            .any(|typ| Type::parse(typ) == Some(Type::Superuser))
+                }
+                PrincipalField::Name => values
+                    .iter()
+                    .all(|name| !name.contains('@') || scope.contains_address(name)),
+                PrincipalField::Emails => values.iter().all(|email| scope.contains_address(email)),
+                PrincipalField::MemberOf | PrincipalField::Members => {
+                    self.is_principal_in_scope(scope, values).await
+                }
+                PrincipalField::Quota | PrincipalField::Description | PrincipalField::Secrets => {
+                    true
+                }
+            };
+
+            if !is_allowed {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn values_of(value: &PrincipalValue) -> &[String] {
+    match value {
+        PrincipalValue::String(value) => std::slice::from_ref(value),
+        PrincipalValue::StringList(values) => values.as_slice(),
+        PrincipalValue::Integer(_) => &[],
+    }
+}
+
+fn required_permission(
+    path_1: &str,
+    path_2: Option<&str>,
+    method: &Method,
+) -> Option<(Permission, bool)> {
+    let permission = match (path_1, path_2, method) {
+        ("principal", None, &Method::POST) => Permission::PrincipalCreate,
+        ("principal", None, &Method::GET) => Permission::PrincipalList,
+        ("principal", Some(_), &Method::GET) => Permission::PrincipalGet,
+        ("principal", Some(_), &Method::PATCH) => Permission::PrincipalUpdate,
+        ("principal", Some(_), &Method::DELETE) => Permission::PrincipalDelete,
+        ("retention", Some(_), &Method::GET) => Permission::PrincipalGet,
+        ("retention", Some(_), _) => Permission::PrincipalUpdate,
+        ("legal-hold", Some(_), &Method::GET) => Permission::PrincipalGet,
+        ("legal-hold", Some(_), _) => return (Permission::LegalHold, true).into(),
+        ("recovery", Some(_), &Method::GET) => Permission::PrincipalGet,
+        ("recovery", Some(_), _) => Permission::PrincipalUpdate,
+        ("domain", None, &Method::GET) => Permission::DomainList,
+        ("domain", Some(_), &Method::GET) => Permission::DomainGet,
+        ("domain", Some(_), &Method::POST) => Permission::DomainCreate,
+        ("domain", Some(_), &Method::PATCH) => Permission::DomainUpdate,
+        ("domain", Some(_), &Method::DELETE) => Permission::DomainDelete,
+        ("list", Some(_), &Method::GET) => Permission::ListGet,
+        ("list", Some(_), &Method::POST) => Permission::ListModerate,
+        ("list", Some(_), _) => Permission::ListUpdate,
+        ("queue", Some("reports"), &Method::DELETE) | ("reports", _, &Method::DELETE) => {
+            Permission::ReportDelete
+        }
+        ("queue", Some("reports"), _) | ("reports", _, _) => Permission::ReportList,
+        ("queue", _, &Method::PATCH) => Permission::QueueUpdate,
+        ("queue", _, &Method::DELETE) => Permission::QueueDelete,
+        ("queue", _, _) => Permission::QueueList,
+        ("store", _, _) => return (Permission::StoreMaintenance, true).into(),
+        ("audit", _, _) => return (Permission::AuditList, true).into(),
+        ("reload", _, _) => return (Permission::SettingsReload, true).into(),
+        ("settings", _, &Method::GET) => return (Permission::SettingsList, true).into(),
+        ("settings", _, _) => return (Permission::SettingsUpdate, true).into(),
+        _ => return None,
+    };
+
+    Some((permission, false))
+}
+
+fn audit_action(path_1: &str, path_2: Option<&str>, method: &Method) -> Option<AuditAction> {
+    match (path_1, path_2, method) {
+        ("principal", None, &Method::POST) => AuditAction::PrincipalCreate.into(),
+        ("principal", Some(_), &Method::PATCH) => AuditAction::PrincipalUpdate.into(),
+        ("principal", Some(_), &Method::DELETE) => AuditAction::PrincipalDelete.into(),
+        ("retention", Some(_), &Method::PUT | &Method::DELETE) => {
+            AuditAction::PrincipalUpdate.into()
+        }
+        ("legal-hold", Some(_), &Method::PUT | &Method::DELETE) => {
+            AuditAction::PrincipalUpdate.into()
+        }
+        ("recovery", Some(_), &Method::POST) => AuditAction::PrincipalUpdate.into(),
+        ("domain", Some(_), &Method::POST) => AuditAction::DomainCreate.into(),
+        ("domain", Some(_), &Method::PATCH) => AuditAction::DomainUpdate.into(),
+        ("domain", Some(_), &Method::DELETE) => AuditAction::DomainDelete.into(),
+        ("list", Some(_), &Method::POST) => AuditAction::ListModerate.into(),
+        ("list", Some(_), &Method::PATCH | &Method::PUT | &Method::DELETE) => {
+            AuditAction::ListUpdate.into()
+        }
+        ("settings", None, &Method::POST) => AuditAction::SettingsUpdate.into(),
+        ("settings", Some(_), &Method::DELETE) => AuditAction::SettingsDelete.into(),
+        ("reload", Some(_), &Method::GET) => AuditAction::SettingsReload.into(),
+        ("store", Some(_), &Method::GET | &Method::POST) => AuditAction::StoreMaintenance.into(),
+        ("queue", Some("messages"), &Method::PATCH) => AuditAction::QueueUpdate.into(),
+        ("queue", Some("messages"), &Method::DELETE) => AuditAction::QueueDelete.into(),
+        ("queue", Some("reports"), &Method::DELETE) | ("reports", Some(_), &Method::DELETE) => {
+            AuditAction::ReportDelete.into()
+        }
+        _ => None,
+    }
+}
+
+fn map_store_error(err: store::Error) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
+    RequestError::blank(
+        StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+        "Store error",
+        err.to_string(),
+    )
+    .into_http_response()
+}
+
+pub(crate) fn map_directory_error(
+    err: DirectoryError,
+) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
     match err {
         DirectoryError::Management(err) => {
             let response = match err {
@@ -770,6 +1639,28 @@ fn map_directory_error(err: DirectoryError) -> hyper::Response<BoxBody<Bytes, hy
                     "item": details,
                     "details": format!("'{details}' does not exist."),
                 }),
+                ManagementError::InvalidPassword(violation) => json!({
+                    "error": "invalidPassword",
+                    "reason": violation.as_str(),
+                    "details": violation.to_string(),
+                }),
+                ManagementError::InvalidValue { field, value } => json!({
+                    "error": "invalidValue",
+                    "field": field,
+                    "value": value,
+                    "details": format!("Invalid value '{value}' for field '{field}'."),
+                }),
+                ManagementError::LimitExceeded { domain, limit } => json!({
+                    "error": "limitExceeded",
+                    "item": domain,
+                    "limit": limit,
+                    "details": format!("Domain '{domain}' has reached its '{limit}' limit."),
+                }),
+                ManagementError::LegalHold(name) => json!({
+                    "error": "legalHold",
+                    "item": name,
+                    "details": format!("Account '{name}' is under legal hold."),
+                }),
             };
             JsonResponse::new(response).into_http_response()
         }
@@ -23,13 +23,24 @@
 
 use std::{str::FromStr, time::Duration};
 
+use directory::core::roles::Roles;
 use nlp::language::Language;
-use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
+use store::{
+    fts::office::ExtractLimits,
+    rand::{distributions::Alphanumeric, thread_rng, Rng},
+};
+
+use crate::{
+    services::{audit::parse_audit_key, retention::parse_retention_rules},
+    sharing::public::parse_public_folders,
+    sieve::notify::PUSH_NOTIFY_URI,
+};
 
 use super::session::BaseCapabilities;
 
 impl crate::Config {
     pub fn new(settings: &utils::config::Config) -> Result<Self, String> {
+        let audit_enable = settings.property_or_default("audit.enable", "true")?;
         let mut config = Self {
             default_language: Language::from_iso_639(
                 settings
@@ -89,12 +100,36 @@ impl crate::Config {
             mail_parse_max_items: settings
                 .property("jmap.email.parse.max-items")?
                 .unwrap_or(10),
+            fts_extract_limits: ExtractLimits {
+                max_size: settings
+                    .property("storage.full-text.attachments.max-size")?
+                    .unwrap_or(20000000),
+                max_text: settings
+                    .property("storage.full-text.attachments.max-text")?
+                    .unwrap_or(1000000),
+                timeout: settings.property_or_default::<Duration>(
+                    "storage.full-text.attachments.timeout",
+                    "5s",
+                )?,
+            },
             sieve_max_script_name: settings
                 .property("sieve.untrusted.limits.name-length")?
                 .unwrap_or(512),
             sieve_max_scripts: settings
                 .property("sieve.untrusted.limits.max-scripts")?
                 .unwrap_or(256),
+            sieve_notify_push_uri: settings
+                .value("sieve.untrusted.notify.push-uri")
+                .unwrap_or(PUSH_NOTIFY_URI)
+                .to_string(),
+            sieve_notify_rate: settings
+                .property_or_default("sieve.untrusted.notify.rate", "10/1h")?,
+            sieve_addrbook_expiry: settings
+                .property_or_default::<Duration>(
+                    "sieve.untrusted.extlists.address-book-expiry",
+                    "90d",
+                )?
+                .as_secs(),
             capabilities: BaseCapabilities::default(),
             session_cache_ttl: settings
                 .property("cache.session.ttl")?
@@ -144,6 +179,20 @@ impl crate::Config {
             principal_allow_lookups: settings
                 .property("jmap.principal.allow-lookups")?
                 .unwrap_or(true),
+            scim_token: settings.value("scim.token").map(|s| s.to_string()),
+            roles: Roles::parse(settings)?,
+            audit_enable,
+            audit_key: parse_audit_key(settings, audit_enable)?,
+            audit_retention: settings
+                .property_or_default::<Option<Duration>>("audit.retention", "365d")?,
+            list_url: settings.value("list.url").map(|s| s.to_string()),
+            list_confirm_expiry: settings.property_or_default("list.expiry.confirm", "2d")?,
+            list_held_expiry: settings.property_or_default("list.expiry.held", "7d")?,
+            list_outbox_expiry: settings.property_or_default("list.expiry.outbox", "2d")?,
+            retention_rules: parse_retention_rules(settings)?,
+            deleted_items_hold: settings
+                .property_or_default::<Option<Duration>>("jmap.retention.deleted-items", "14d")?,
+            public_folders: parse_public_folders(settings)?,
             encrypt: settings.property_or_default("storage.encryption.enable", "true")?,
             encrypt_append: settings.property_or_default("storage.encryption.append", "false")?,
             spam_header: settings.value("spam.header.is-spam").and_then(|v| {
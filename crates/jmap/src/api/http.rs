@@ -43,8 +43,14 @@ use utils::listener::{ServerInstance, SessionData, SessionManager, SessionStream
 
 use crate::{
     auth::{oauth::OAuthMetadata, AccessToken},
-    blob::{DownloadResponse, UploadResponse},
-    services::state,
+    blob::{
+        resumable::ResumableUploadResponse, DownloadRange, DownloadResponse, RangeRequest,
+        UploadResponse,
+    },
+    services::{
+        audit::{redact_secrets, AuditAction, AuditEvent},
+        state,
+    },
     websocket::upgrade::upgrade_websocket_connection,
     JMAP,
 };
@@ -107,8 +113,32 @@ pub async fn parse_jmap_request(
                         path.next().and_then(BlobId::from_base32),
                         path.next(),
                     ) {
-                        return match jmap.blob_download(&blob_id, &access_token).await {
-                            Ok(Some(blob)) => DownloadResponse {
+                        let etag = blob_id.etag();
+                        let range = RangeRequest::parse(
+                            req.headers()
+                                .get(header::RANGE)
+                                .and_then(|h| h.to_str().ok()),
+                            req.headers()
+                                .get(header::IF_RANGE)
+                                .and_then(|h| h.to_str().ok()),
+                            &etag,
+                        );
+                        let result = if let Some(range) = range {
+                            jmap.blob_download_range(&blob_id, &access_token, range)
+                                .await
+                        } else {
+                            jmap.blob_download(&blob_id, &access_token)
+                                .await
+                                .map(|blob| {
+                                    blob.map(|blob| {
+                                        let size = blob.len();
+                                        (blob, size, DownloadRange::Full)
+                                    })
+                                })
+                        };
+
+                        return match result {
+                            Ok(Some((blob, size, range))) => DownloadResponse {
                                 filename: name.to_string(),
                                 content_type: req
                                     .uri()
@@ -120,6 +150,9 @@ pub async fn parse_jmap_request(
                                     })
                                     .unwrap_or("application/octet-stream".to_string()),
                                 blob,
+                                size,
+                                etag: etag.into(),
+                                range,
                             }
                             .into_http_response(),
                             Ok(None) => RequestError::not_found().into_http_response(),
@@ -130,6 +163,28 @@ pub async fn parse_jmap_request(
                 ("upload", &Method::POST) => {
                     if let Some(account_id) = path.next().and_then(|p| Id::from_bytes(p.as_bytes()))
                     {
+                        // Start a resumable upload
+                        if let Some(size) = req.headers().get("Upload-Length") {
+                            return match size.to_str().ok().and_then(|s| s.trim().parse().ok()) {
+                                Some(size) => match jmap
+                                    .upload_session_create(
+                                        account_id,
+                                        req.headers()
+                                            .get(CONTENT_TYPE)
+                                            .and_then(|h| h.to_str().ok())
+                                            .unwrap_or("application/octet-stream"),
+                                        size,
+                                        &access_token,
+                                    )
+                                    .await
+                                {
+                                    Ok(response) => response.into_http_response(),
+                                    Err(err) => err.into_http_response(),
+                                },
+                                None => RequestError::invalid_parameters().into_http_response(),
+                            };
+                        }
+
                         return match fetch_body(
                             &mut req,
                             jmap.config.upload_max_size,
@@ -159,6 +214,61 @@ pub async fn parse_jmap_request(
                         };
                     }
                 }
+                ("upload", &Method::PATCH | &Method::HEAD | &Method::DELETE) => {
+                    if let (Some(account_id), Some(upload_id)) = (
+                        path.next().and_then(|p| Id::from_bytes(p.as_bytes())),
+                        path.next().and_then(|p| Id::from_bytes(p.as_bytes())),
+                    ) {
+                        let result = match *req.method() {
+                            Method::PATCH => {
+                                let offset = match req
+                                    .headers()
+                                    .get("Upload-Offset")
+                                    .and_then(|h| h.to_str().ok())
+                                    .and_then(|h| h.trim().parse().ok())
+                                {
+                                    Some(offset) => offset,
+                                    None => {
+                                        return RequestError::invalid_parameters()
+                                            .into_http_response()
+                                    }
+                                };
+                                match fetch_body(
+                                    &mut req,
+                                    jmap.config.upload_max_size,
+                                    &access_token,
+                                )
+                                .await
+                                {
+                                    Some(bytes) => {
+                                        jmap.upload_session_append(
+                                            account_id,
+                                            upload_id,
+                                            offset,
+                                            &bytes,
+                                            access_token,
+                                        )
+                                        .await
+                                    }
+                                    None => Err(RequestError::limit(RequestLimitError::SizeUpload)),
+                                }
+                            }
+                            Method::HEAD => {
+                                jmap.upload_session_status(account_id, upload_id, &access_token)
+                                    .await
+                            }
+                            _ => {
+                                jmap.upload_session_cancel(account_id, upload_id, &access_token)
+                                    .await
+                            }
+                        };
+
+                        return match result {
+                            Ok(response) => response.into_http_response(),
+                            Err(err) => err.into_http_response(),
+                        };
+                    }
+                }
                 ("eventsource", &Method::GET) => {
                     return jmap.handle_event_source(req, access_token).await
                 }
@@ -273,21 +383,107 @@ pub async fn parse_jmap_request(
                 return ().into_http_response();
             }
 
-            // Make sure the user is a superuser
+            match (path.next(), req.method()) {
+                (Some("password"), &Method::POST) => {
+                    // Self-service password changes are authenticated with the current password
+                    return jmap.handle_password_change(&mut req, remote_ip).await;
+                }
+                (Some("unsubscribe"), &Method::POST) => {
+                    // One-click list unsubscription is authorized by the token in the URL
+                    return jmap.handle_list_unsubscribe(&req).await;
+                }
+                _ => (),
+            }
+
+            // Make sure the user is a superuser or has been granted an administrative role
             return match jmap.authenticate_headers(&req, remote_ip).await {
                 Ok(Some((_, access_token))) => {
                     let body = fetch_body(&mut req, 8192, &access_token).await;
-                    if access_token.is_super_user() {
-                        jmap.handle_api_manage_request(&req, body, access_token)
-                            .await
+                    let authorization = jmap.get_authorization(&access_token).await;
+                    let remote_addr = jmap.build_remote_addr(&req, remote_ip);
+                    if !authorization.is_empty() {
+                        jmap.handle_api_manage_request(
+                            &req,
+                            body,
+                            access_token,
+                            &authorization,
+                            remote_addr,
+                        )
+                        .await
                     } else {
-                        jmap.handle_api_request(&req, body, access_token).await
+                        jmap.handle_api_request(&req, body, access_token, remote_addr)
+                            .await
                     }
                 }
                 Ok(None) => RequestError::unauthorized().into_http_response(),
                 Err(err) => err.into_http_response(),
             };
         }
+        "scim" if jmap.config.scim_token.is_some() => {
+            // Allow CORS preflight requests
+            if req.method() == Method::OPTIONS {
+                return ().into_http_response();
+            }
+
+            // Provisioning clients authenticate with a dedicated bearer token
+            let remote_addr = jmap.build_remote_addr(&req, remote_ip);
+            if let Err(err) = jmap.is_auth_allowed_soft(&remote_addr).await {
+                return err.into_http_response();
+            }
+            if !jmap.is_scim_authorized(&req) {
+                jmap.audit(
+                    AuditEvent::new(AuditAction::AuthFailure)
+                        .with_actor("scim")
+                        .with_remote_ip(remote_addr)
+                        .with_success(false),
+                )
+                .await;
+                return match jmap.is_auth_allowed_hard(&remote_addr).await {
+                    Ok(_) => RequestError::unauthorized().into_http_response(),
+                    Err(err) => err.into_http_response(),
+                };
+            }
+
+            let body = fetch_body(
+                &mut req,
+                jmap.config.request_max_size,
+                &AccessToken::default(),
+            )
+            .await;
+            let action = match *req.method() {
+                Method::POST => AuditAction::PrincipalCreate.into(),
+                Method::PUT | Method::PATCH => AuditAction::PrincipalUpdate.into(),
+                Method::DELETE => AuditAction::PrincipalDelete.into(),
+                _ => None,
+            };
+            return if let Some(action) = action {
+                let event = AuditEvent::new(action)
+                    .with_actor("scim")
+                    .with_remote_ip(remote_addr)
+                    .with_target(
+                        req.uri()
+                            .path()
+                            .split('/')
+                            .skip(3)
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                    )
+                    .with_diff(
+                        body.as_deref()
+                            .and_then(|body| serde_json::from_slice::<serde_json::Value>(body).ok())
+                            .map(|mut value| {
+                                redact_secrets(&mut value);
+                                value.to_string()
+                            }),
+                    );
+                let response = jmap.handle_scim_request(&req, body, &instance.data).await;
+                jmap.audit(event.with_success(response.status().is_success()))
+                    .await;
+                response
+            } else {
+                jmap.handle_scim_request(&req, body, &instance.data).await
+            };
+        }
         _ => (),
     }
     RequestError::not_found().into_http_response()
@@ -441,8 +637,8 @@ impl ToHttpResponse for Session {
 
 impl ToHttpResponse for DownloadResponse {
     fn into_http_response(self) -> HttpResponse {
-        hyper::Response::builder()
-            .status(StatusCode::OK)
+        let size = self.size;
+        let mut builder = hyper::Response::builder()
             .header(header::CONTENT_TYPE, self.content_type)
             .header(
                 header::CONTENT_DISPOSITION,
@@ -454,9 +650,32 @@ impl ToHttpResponse for DownloadResponse {
             .header(
                 header::CACHE_CONTROL,
                 "private, immutable, max-age=31536000",
-            )
+            );
+        if let Some(etag) = self.etag {
+            builder = builder
+                .header(header::ETAG, etag)
+                .header(header::ACCEPT_RANGES, "bytes");
+        }
+
+        let (status, body) = match self.range {
+            DownloadRange::Full => (StatusCode::OK, self.blob),
+            DownloadRange::Partial(range) => {
+                builder = builder.header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{size}", range.start, range.end - 1),
+                );
+                (StatusCode::PARTIAL_CONTENT, self.blob)
+            }
+            DownloadRange::Unsatisfiable => {
+                builder = builder.header(header::CONTENT_RANGE, format!("bytes */{size}"));
+                (StatusCode::RANGE_NOT_SATISFIABLE, Vec::new())
+            }
+        };
+
+        builder
+            .status(status)
             .body(
-                Full::new(Bytes::from(self.blob))
+                Full::new(Bytes::from(body))
                     .map_err(|never| match never {})
                     .boxed(),
             )
@@ -464,6 +683,44 @@ impl ToHttpResponse for DownloadResponse {
     }
 }
 
+impl ToHttpResponse for ResumableUploadResponse {
+    fn into_http_response(self) -> HttpResponse {
+        match self {
+            ResumableUploadResponse::Created(session) => {
+                let location = format!("/jmap/upload/{}/{}", session.account_id, session.upload_id);
+                let mut response =
+                    JsonResponse::with_status(StatusCode::CREATED, &session).into_http_response();
+                set_upload_headers(&mut response, session.offset, session.size);
+                if let Ok(location) = header::HeaderValue::from_str(&location) {
+                    response.headers_mut().insert(header::LOCATION, location);
+                }
+                response
+            }
+            ResumableUploadResponse::Progress(session) => {
+                let mut response = JsonResponse::new(&session).into_http_response();
+                set_upload_headers(&mut response, session.offset, session.size);
+                response
+            }
+            ResumableUploadResponse::Completed(upload) => {
+                let mut response = JsonResponse::new(&upload).into_http_response();
+                set_upload_headers(&mut response, upload.size(), upload.size());
+                response
+            }
+            ResumableUploadResponse::Cancelled => ().into_http_response(),
+        }
+    }
+}
+
+fn set_upload_headers(response: &mut HttpResponse, offset: usize, size: usize) {
+    let headers = response.headers_mut();
+    headers.insert("Upload-Offset", header::HeaderValue::from(offset));
+    headers.insert("Upload-Length", header::HeaderValue::from(size));
+    headers.insert(
+        header::CACHE_CONTROL,
+        header::HeaderValue::from_static("no-store"),
+    );
+}
+
 impl ToHttpResponse for UploadResponse {
     fn into_http_response(self) -> HttpResponse {
         JsonResponse::new(self).into_http_response()
@@ -35,6 +35,7 @@ pub mod config;
 pub mod event_source;
 pub mod http;
 pub mod request;
+pub mod scim;
 pub mod session;
 
 #[derive(Clone)]
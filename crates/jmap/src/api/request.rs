@@ -193,6 +193,11 @@ impl JMAP {
                         .await?
                         .into()
                 }
+                get::RequestArguments::ShareNotification => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.share_notification_get(req).await?.into()
+                }
             },
             RequestMethod::Query(mut req) => match req.take_arguments() {
                 query::RequestArguments::Email(arguments) => {
@@ -233,6 +238,11 @@ impl JMAP {
 
                     self.quota_query(req, access_token).await?.into()
                 }
+                query::RequestArguments::ShareNotification => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.share_notification_query(req).await?.into()
+                }
             },
             RequestMethod::Set(mut req) => match req.take_arguments() {
                 set::RequestArguments::Email => {
@@ -274,6 +284,11 @@ impl JMAP {
 
                     self.vacation_response_set(req).await?.into()
                 }
+                set::RequestArguments::ShareNotification => {
+                    access_token.assert_is_member(req.account_id)?;
+
+                    self.share_notification_set(req).await?.into()
+                }
             },
             RequestMethod::Changes(req) => self.changes(req, access_token).await?.into(),
             RequestMethod::Copy(req) => {
@@ -319,6 +334,26 @@ impl JMAP {
 
                 self.blob_upload_many(req, access_token).await?.into()
             }
+            RequestMethod::SendMdn(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.mdn_send(req, instance, next_call).await?.into()
+            }
+            RequestMethod::ParseMdn(req) => {
+                access_token.assert_has_access(req.account_id, Collection::Email)?;
+
+                self.mdn_parse(req, access_token).await?.into()
+            }
+            RequestMethod::GetDeletedEmail(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.deleted_email_get(req).await?.into()
+            }
+            RequestMethod::RestoreDeletedEmail(req) => {
+                access_token.assert_is_member(req.account_id)?;
+
+                self.deleted_email_restore(req, access_token).await?.into()
+            }
             RequestMethod::Echo(req) => req.into(),
             RequestMethod::Error(error) => return Err(error),
         })
@@ -0,0 +1,561 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use serde_json::Value;
+
+const MAX_FILTER_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare {
+        attr: String,
+        op: CompareOp,
+        value: Value,
+    },
+    Present(String),
+    ValuePath {
+        attr: String,
+        filter: Box<Filter>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttrPath {
+    pub attr: String,
+    pub filter: Option<Filter>,
+    pub sub_attr: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Open,
+    Close,
+    OpenBracket,
+    CloseBracket,
+}
+
+struct Tokenizer<'x> {
+    iter: Peekable<Chars<'x>>,
+    peeked: Option<Option<Token>>,
+    depth: usize,
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self, String> {
+        let mut tokens = Tokenizer::new(filter);
+        let filter = tokens.parse_or()?;
+        if let Some(token) = tokens.next_token()? {
+            Err(format!("Unexpected token {token:?}"))
+        } else {
+            Ok(filter)
+        }
+    }
+
+    /// Evaluates the filter against a SCIM resource in its JSON representation.
+    pub fn matches(&self, resource: &Value) -> bool {
+        match self {
+            Filter::And(left, right) => left.matches(resource) && right.matches(resource),
+            Filter::Or(left, right) => left.matches(resource) || right.matches(resource),
+            Filter::Not(filter) => !filter.matches(resource),
+            Filter::Compare { attr, op, value } => resolve(resource, attr)
+                .into_iter()
+                .any(|item| compare(item, *op, value)),
+            Filter::Present(attr) => resolve(resource, attr).into_iter().any(|item| match item {
+                Value::Null => false,
+                Value::String(value) => !value.is_empty(),
+                Value::Array(value) => !value.is_empty(),
+                Value::Object(value) => !value.is_empty(),
+                _ => true,
+            }),
+            Filter::ValuePath { attr, filter } => resolve(resource, attr)
+                .into_iter()
+                .any(|item| filter.matches(item)),
+        }
+    }
+
+    /// Returns the value of an equality comparison on the given attribute, used to
+    /// avoid listing the whole directory for the lookups issued by identity providers.
+    pub fn as_equals(&self, attr: &str) -> Option<&str> {
+        match self {
+            Filter::Compare {
+                attr: attr_,
+                op: CompareOp::Eq,
+                value: Value::String(value),
+            } if attr_.eq_ignore_ascii_case(attr) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl AttrPath {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let mut tokens = Tokenizer::new(path);
+        let attr = match tokens.next_token()? {
+            Some(Token::Word(attr)) => attr,
+            token => return Err(format!("Invalid attribute path {token:?}")),
+        };
+        let (attr, filter) = if tokens.peek_token()? == Some(&Token::OpenBracket) {
+            tokens.next_token()?;
+            let filter = tokens.parse_or()?;
+            if tokens.next_token()? != Some(Token::CloseBracket) {
+                return Err("Expected ']'".to_string());
+            }
+            (attr, Some(filter))
+        } else {
+            (attr, None)
+        };
+        let sub_attr = match tokens.next_token()? {
+            Some(Token::Word(sub_attr)) if filter.is_some() && sub_attr.starts_with('.') => {
+                Some(sub_attr[1..].to_string())
+            }
+            None => None,
+            token => return Err(format!("Unexpected token {token:?}")),
+        };
+
+        Ok(AttrPath {
+            attr,
+            filter,
+            sub_attr,
+        })
+    }
+}
+
+impl<'x> Tokenizer<'x> {
+    fn new(text: &'x str) -> Self {
+        Tokenizer {
+            iter: text.chars().peekable(),
+            peeked: None,
+            depth: 0,
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_and()?;
+        while self.next_if_keyword("or")? {
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.parse_unary()?;
+        while self.next_if_keyword("and")? {
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, String> {
+        match self.next_token()? {
+            Some(Token::Open) => self.parse_group(),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("not") => {
+                if self.next_token()? == Some(Token::Open) {
+                    Ok(Filter::Not(Box::new(self.parse_group()?)))
+                } else {
+                    Err("Expected '(' after 'not'".to_string())
+                }
+            }
+            Some(Token::Word(attr)) => {
+                if self.peek_token()? == Some(&Token::OpenBracket) {
+                    self.next_token()?;
+                    let filter = self.parse_nested()?;
+                    if self.next_token()? == Some(Token::CloseBracket) {
+                        Ok(Filter::ValuePath {
+                            attr,
+                            filter: Box::new(filter),
+                        })
+                    } else {
+                        Err("Expected ']'".to_string())
+                    }
+                } else {
+                    self.parse_comparison(attr)
+                }
+            }
+            token => Err(format!("Unexpected token {token:?}")),
+        }
+    }
+
+    fn parse_group(&mut self) -> Result<Filter, String> {
+        let filter = self.parse_nested()?;
+        if self.next_token()? == Some(Token::Close) {
+            Ok(filter)
+        } else {
+            Err("Expected ')'".to_string())
+        }
+    }
+
+    fn parse_nested(&mut self) -> Result<Filter, String> {
+        if self.depth >= MAX_FILTER_DEPTH {
+            return Err(format!(
+                "Filter exceeds the maximum nesting depth of {MAX_FILTER_DEPTH}"
+            ));
+        }
+        self.depth += 1;
+        let filter = self.parse_or();
+        self.depth -= 1;
+        filter
+    }
+
+    fn parse_comparison(&mut self, attr: String) -> Result<Filter, String> {
+        let op = match self.next_token()? {
+            Some(Token::Word(op)) => op.to_ascii_lowercase(),
+            token => return Err(format!("Expected operator, found {token:?}")),
+        };
+        let op = match op.as_str() {
+            "pr" => return Ok(Filter::Present(attr)),
+            "eq" => CompareOp::Eq,
+            "ne" => CompareOp::Ne,
+            "co" => CompareOp::Co,
+            "sw" => CompareOp::Sw,
+            "ew" => CompareOp::Ew,
+            "gt" => CompareOp::Gt,
+            "ge" => CompareOp::Ge,
+            "lt" => CompareOp::Lt,
+            "le" => CompareOp::Le,
+            _ => return Err(format!("Unsupported operator {op:?}")),
+        };
+        let value = match self.next_token()? {
+            Some(Token::String(value)) => Value::String(value),
+            Some(Token::Word(value)) => match value.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                _ => serde_json::from_str::<serde_json::Number>(&value)
+                    .map(Value::Number)
+                    .map_err(|_| format!("Invalid comparison value {value:?}"))?,
+            },
+            token => return Err(format!("Expected comparison value, found {token:?}")),
+        };
+
+        Ok(Filter::Compare { attr, op, value })
+    }
+
+    fn next_if_keyword(&mut self, keyword: &str) -> Result<bool, String> {
+        if matches!(self.peek_token()?, Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+        {
+            self.next_token()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&Token>, String> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.read_token()?);
+        }
+        Ok(self.peeked.as_ref().unwrap().as_ref())
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, String> {
+        if let Some(token) = self.peeked.take() {
+            Ok(token)
+        } else {
+            self.read_token()
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token>, String> {
+        while self.iter.next_if(|ch| ch.is_whitespace()).is_some() {}
+
+        match self.iter.next() {
+            Some('(') => Ok(Some(Token::Open)),
+            Some(')') => Ok(Some(Token::Close)),
+            Some('[') => Ok(Some(Token::OpenBracket)),
+            Some(']') => Ok(Some(Token::CloseBracket)),
+            Some('"') => {
+                let mut value = String::new();
+                loop {
+                    match self.iter.next() {
+                        Some('"') => return Ok(Some(Token::String(value))),
+                        Some('\\') => match self.iter.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(ch) => value.push(ch),
+                            None => break,
+                        },
+                        Some(ch) => value.push(ch),
+                        None => break,
+                    }
+                }
+                Err("Unterminated string".to_string())
+            }
+            Some(ch) => {
+                let mut word = String::from(ch);
+                while let Some(ch) = self
+                    .iter
+                    .next_if(|ch| !ch.is_whitespace() && !matches!(ch, '(' | ')' | '[' | ']' | '"'))
+                {
+                    word.push(ch);
+                }
+                Ok(Some(Token::Word(word)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Resolves a possibly dotted or schema qualified attribute path, attribute
+/// names are case insensitive and multi-valued attributes are flattened.
+fn resolve<'x>(resource: &'x Value, attr: &str) -> Vec<&'x Value> {
+    let (resource, attr) = match attr.rsplit_once(':') {
+        Some((schema, attr)) => match get_attribute(resource, schema) {
+            Some(resource) => (resource, attr),
+            None => return Vec::new(),
+        },
+        None => (resource, attr),
+    };
+
+    let mut items = vec![resource];
+    for name in attr.split('.') {
+        let mut next = Vec::new();
+        for item in items {
+            match item {
+                Value::Array(values) => {
+                    for value in values {
+                        if let Some(value) = get_attribute(value, name) {
+                            next.push(value);
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(value) = get_attribute(item, name) {
+                        next.push(value);
+                    }
+                }
+            }
+        }
+        items = next;
+    }
+
+    items
+        .into_iter()
+        .flat_map(|item| match item {
+            Value::Array(values) => values.iter().collect::<Vec<_>>(),
+            _ => vec![item],
+        })
+        .collect()
+}
+
+fn get_attribute<'x>(resource: &'x Value, name: &str) -> Option<&'x Value> {
+    resource
+        .as_object()?
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+fn compare(item: &Value, op: CompareOp, value: &Value) -> bool {
+    match (item, value) {
+        (Value::String(item), Value::String(value)) => {
+            let item = item.to_lowercase();
+            let value = value.to_lowercase();
+            match op {
+                CompareOp::Eq => item == value,
+                CompareOp::Ne => item != value,
+                CompareOp::Co => item.contains(&value),
+                CompareOp::Sw => item.starts_with(&value),
+                CompareOp::Ew => item.ends_with(&value),
+                CompareOp::Gt => item > value,
+                CompareOp::Ge => item >= value,
+                CompareOp::Lt => item < value,
+                CompareOp::Le => item <= value,
+            }
+        }
+        (Value::Number(item), Value::Number(value)) => {
+            match item
+                .as_f64()
+                .unwrap_or_default()
+                .partial_cmp(&value.as_f64().unwrap_or_default())
+            {
+                Some(ordering) => match op {
+                    CompareOp::Eq => ordering == Ordering::Equal,
+                    CompareOp::Ne => ordering != Ordering::Equal,
+                    CompareOp::Gt => ordering == Ordering::Greater,
+                    CompareOp::Ge => ordering != Ordering::Less,
+                    CompareOp::Lt => ordering == Ordering::Less,
+                    CompareOp::Le => ordering != Ordering::Greater,
+                    CompareOp::Co | CompareOp::Sw | CompareOp::Ew => false,
+                },
+                None => false,
+            }
+        }
+        (item, value) => match op {
+            CompareOp::Eq => item == value,
+            CompareOp::Ne => item != value,
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{AttrPath, CompareOp, Filter};
+
+    #[test]
+    fn parse_filter() {
+        assert_eq!(
+            Filter::parse("userName eq \"john\"").unwrap(),
+            Filter::Compare {
+                attr: "userName".to_string(),
+                op: CompareOp::Eq,
+                value: json!("john"),
+            }
+        );
+        assert_eq!(
+            Filter::parse("emails[type eq \"work\" and value co \"@example.org\"]").unwrap(),
+            Filter::ValuePath {
+                attr: "emails".to_string(),
+                filter: Box::new(Filter::And(
+                    Box::new(Filter::Compare {
+                        attr: "type".to_string(),
+                        op: CompareOp::Eq,
+                        value: json!("work"),
+                    }),
+                    Box::new(Filter::Compare {
+                        attr: "value".to_string(),
+                        op: CompareOp::Co,
+                        value: json!("@example.org"),
+                    })
+                )),
+            }
+        );
+        assert!(Filter::parse(&format!(
+            "{}userName eq \"john\"{}",
+            "(".repeat(32),
+            ")".repeat(32)
+        ))
+        .is_ok());
+        assert_eq!(
+            AttrPath::parse("emails[type eq \"work\"].value").unwrap(),
+            AttrPath {
+                attr: "emails".to_string(),
+                filter: Filter::parse("type eq \"work\"").unwrap().into(),
+                sub_attr: "value".to_string().into(),
+            }
+        );
+        assert_eq!(
+            AttrPath::parse("urn:ietf:params:scim:schemas:extension:stalwart:2.0:User:quota")
+                .unwrap(),
+            AttrPath {
+                attr: "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User:quota".to_string(),
+                filter: None,
+                sub_attr: None,
+            }
+        );
+
+        for invalid in [
+            "userName",
+            "userName eq",
+            "userName xx \"john\"",
+            "(userName eq \"john\"",
+            "userName eq \"john",
+            "emails[type eq \"work\"",
+        ]
+        .into_iter()
+        .map(String::from)
+        .chain([
+            format!("{}userName eq \"john\"{}", "(".repeat(33), ")".repeat(33)),
+            format!(
+                "{}userName eq \"john\"{}",
+                "not (".repeat(33),
+                ")".repeat(33)
+            ),
+            format!("{}type eq \"work\"{}", "emails[".repeat(33), "]".repeat(33)),
+        ]) {
+            assert!(Filter::parse(&invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn match_filter() {
+        let user = json!({
+            "id": "1",
+            "userName": "John",
+            "displayName": "John Doe",
+            "active": true,
+            "emails": [
+                {"value": "john@example.org", "type": "work", "primary": true},
+                {"value": "jdoe@example.net", "type": "work", "primary": false}
+            ],
+            "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User": {
+                "quota": 1024
+            }
+        });
+
+        for (filter, expected) in [
+            ("userName eq \"john\"", true),
+            ("username EQ \"JOHN\"", true),
+            ("userName ne \"john\"", false),
+            ("displayName sw \"john\" and displayName ew \"doe\"", true),
+            ("emails.value co \"example.net\"", true),
+            (
+                "emails[value ew \"example.net\" and primary eq true]",
+                false,
+            ),
+            (
+                "emails[value ew \"example.net\" and primary eq false]",
+                true,
+            ),
+            ("not (active eq true) or userName eq \"jane\"", false),
+            ("title pr", false),
+            ("emails pr and active eq true", true),
+            (
+                "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User:quota gt 1000",
+                true,
+            ),
+            (
+                "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User:quota le 1000",
+                false,
+            ),
+        ] {
+            assert_eq!(
+                Filter::parse(filter).unwrap().matches(&user),
+                expected,
+                "{filter}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1223 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+pub mod filter;
+
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Bytes, header, Method, StatusCode};
+use serde_json::{json, Value};
+use utils::url_params::UrlParams;
+
+use crate::JMAP;
+
+use self::filter::{AttrPath, Filter};
+
+use super::{http::ToHttpResponse, HttpRequest, HttpResponse};
+
+const SCHEMA_USER: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+const SCHEMA_GROUP: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const SCHEMA_USER_EXT: &str = "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User";
+const SCHEMA_LIST: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const SCHEMA_ERROR: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const SCHEMA_SERVICE_PROVIDER: &str = "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+const SCHEMA_RESOURCE_TYPE: &str = "urn:ietf:params:scim:schemas:core:2.0:ResourceType";
+
+pub struct ScimResponse {
+    status: StatusCode,
+    body: Value,
+}
+
+pub struct ScimError {
+    status: StatusCode,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+type ScimResult<T> = Result<T, ScimError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResourceType {
+    User,
+    Group,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ScimResource {
+    #[serde(rename = "userName")]
+    #[serde(default)]
+    user_name: Option<String>,
+    #[serde(rename = "displayName")]
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    name: Option<ScimName>,
+    #[serde(default)]
+    emails: Vec<ScimValue>,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    active: Option<Value>,
+    #[serde(default)]
+    members: Vec<ScimValue>,
+    #[serde(rename = "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User")]
+    #[serde(default)]
+    extension: Option<ScimUserExtension>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ScimName {
+    #[serde(default)]
+    formatted: Option<String>,
+    #[serde(rename = "givenName")]
+    #[serde(default)]
+    given_name: Option<String>,
+    #[serde(rename = "familyName")]
+    #[serde(default)]
+    family_name: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ScimValue {
+    value: String,
+    #[serde(default)]
+    primary: bool,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+struct ScimUserExtension {
+    #[serde(default)]
+    quota: Option<u64>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ScimPatch {
+    #[serde(rename = "Operations")]
+    operations: Vec<ScimPatchOperation>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ScimPatchOperation {
+    op: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    value: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+impl JMAP {
+    pub fn is_scim_authorized(&self, req: &HttpRequest) -> bool {
+        let token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.split_once(' '))
+            .and_then(|(mechanism, token)| {
+                mechanism
+                    .eq_ignore_ascii_case("bearer")
+                    .then_some(token.trim())
+            });
+
+        match (token, &self.config.scim_token) {
+            (Some(token), Some(expected)) if token.len() == expected.len() => {
+                token
+                    .bytes()
+                    .zip(expected.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+            }
+            _ => false,
+        }
+    }
+
+    pub async fn handle_scim_request(
+        &self,
+        req: &HttpRequest,
+        body: Option<Vec<u8>>,
+        base_url: &str,
+    ) -> HttpResponse {
+        let mut path = req.uri().path().split('/');
+        path.next();
+        path.next();
+
+        let result = match (
+            path.next().unwrap_or(""),
+            path.next().unwrap_or(""),
+            path.next(),
+            req.method(),
+        ) {
+            ("v2", "ServiceProviderConfig", None, &Method::GET) => {
+                Ok(self.scim_service_provider_config(base_url))
+            }
+            ("v2", "ResourceTypes", None, &Method::GET) => Ok(scim_resource_types(base_url)),
+            ("v2", resource @ ("Users" | "Groups"), id, method) => {
+                let typ = if resource == "Users" {
+                    ResourceType::User
+                } else {
+                    ResourceType::Group
+                };
+                match (id, method) {
+                    (None, &Method::GET) => {
+                        self.scim_list(typ, UrlParams::new(req.uri().query()), base_url)
+                            .await
+                    }
+                    (None, &Method::POST) => self.scim_create(typ, body, base_url).await,
+                    (Some(id), &Method::GET) => {
+                        let principal = self.scim_principal(typ, id).await;
+                        match principal {
+                            Ok(principal) => self
+                                .scim_resource(typ, principal, base_url)
+                                .await
+                                .map(ScimResponse::ok),
+                            Err(err) => Err(err),
+                        }
+                    }
+                    (Some(id), &Method::PUT) => self.scim_replace(typ, id, body, base_url).await,
+                    (Some(id), &Method::PATCH) => self.scim_patch(typ, id, body, base_url).await,
+                    (Some(id), &Method::DELETE) => self.scim_delete(typ, id).await,
+                    _ => Err(ScimError::not_found()),
+                }
+            }
+            _ => Err(ScimError::not_found()),
+        };
+
+        match result {
+            Ok(response) => response.into_http_response(),
+            Err(err) => err.into_http_response(),
+        }
+    }
+
+    async fn scim_list(
+        &self,
+        typ: ResourceType,
+        params: UrlParams<'_>,
+        base_url: &str,
+    ) -> ScimResult<ScimResponse> {
+        let filter = params
+            .get("filter")
+            .map(Filter::parse)
+            .transpose()
+            .map_err(|err| ScimError::new(StatusCode::BAD_REQUEST, "invalidFilter", err))?;
+        let start_index = params.parse::<usize>("startIndex").unwrap_or(1).max(1);
+        let count = params
+            .parse::<usize>("count")
+            .unwrap_or(self.config.query_max_results)
+            .min(self.config.query_max_results);
+
+        // Identity providers look up resources by name before provisioning them
+        let names = if let Some(name) = filter.as_ref().and_then(|f| f.as_equals(typ.name_attr())) {
+            vec![name.to_string()]
+        } else {
+            self.directory
+                .list_accounts(None, Some(typ.principal_type()))
+                .await?
+        };
+
+        let mut total = 0;
+        let mut resources = Vec::new();
+        if let Some(filter) = &filter {
+            // Filters are evaluated on the resource, every principal has to be fetched
+            for name in names {
+                if let Some(principal) = self.scim_principal_by_name(typ, &name).await? {
+                    let resource = self.scim_resource(typ, principal, base_url).await?;
+                    if filter.matches(&resource) {
+                        total += 1;
+                        if total >= start_index && resources.len() < count {
+                            resources.push(resource);
+                        }
+                    }
+                }
+            }
+        } else {
+            // Only fetch the principals on the requested page
+            total = names.len();
+            for name in names.iter().skip(start_index - 1).take(count) {
+                if let Some(principal) = self.scim_principal_by_name(typ, name).await? {
+                    resources.push(self.scim_resource(typ, principal, base_url).await?);
+                }
+            }
+        }
+
+        Ok(ScimResponse::ok(json!({
+            "schemas": [SCHEMA_LIST],
+            "totalResults": total,
+            "startIndex": start_index,
+            "itemsPerPage": resources.len(),
+            "Resources": resources,
+        })))
+    }
+
+    async fn scim_create(
+        &self,
+        typ: ResourceType,
+        body: Option<Vec<u8>>,
+        base_url: &str,
+    ) -> ScimResult<ScimResponse> {
+        let resource = parse_body::<ScimResource>(body)?;
+        let (principal, members) = match typ {
+            ResourceType::User => {
+                let name = resource
+                    .user_name
+                    .as_deref()
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| ScimError::invalid_value("Missing required field 'userName'."))?
+                    .to_string();
+                (
+                    Principal {
+                        typ: Type::Individual,
+                        quota: resource
+                            .extension
+                            .as_ref()
+                            .and_then(|ext| ext.quota)
+                            .unwrap_or_default(),
+                        description: resource.description(),
+                        emails: resource.emails(),
+                        secrets: resource.secrets(),
+                        name,
+                        ..Default::default()
+                    },
+                    Vec::new(),
+                )
+            }
+            ResourceType::Group => {
+                let name = resource
+                    .display_name
+                    .as_deref()
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| {
+                        ScimError::invalid_value("Missing required field 'displayName'.")
+                    })?
+                    .to_string();
+                (
+                    Principal {
+                        typ: Type::Group,
+                        name,
+                        ..Default::default()
+                    },
+                    self.scim_member_names(resource.members.iter().map(|m| m.value.as_str()))
+                        .await?,
+                )
+            }
+        };
+
+        let account_id = self.directory.create_account(principal, members).await?;
+        if typ == ResourceType::User && !resource.is_active() {
+            self.directory.set_disabled(account_id, true).await?;
+        }
+        let principal = self.scim_principal(typ, &account_id.to_string()).await?;
+        self.scim_resource(typ, principal, base_url)
+            .await
+            .map(|resource| ScimResponse::new(StatusCode::CREATED, resource))
+    }
+
+    async fn scim_replace(
+        &self,
+        typ: ResourceType,
+        id: &str,
+        body: Option<Vec<u8>>,
+        base_url: &str,
+    ) -> ScimResult<ScimResponse> {
+        let principal = self.scim_principal(typ, id).await?;
+        let resource = parse_body::<ScimResource>(body)?;
+        let mut changes = Vec::new();
+        let mut active = None;
+
+        match typ {
+            ResourceType::User => {
+                if let Some(name) = resource.user_name.as_ref().filter(|n| !n.is_empty()) {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Name,
+                        PrincipalValue::String(name.to_string()),
+                    ));
+                }
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Description,
+                    PrincipalValue::String(resource.description().unwrap_or_default()),
+                ));
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Emails,
+                    PrincipalValue::StringList(resource.emails()),
+                ));
+                if resource.password.is_some() {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Secrets,
+                        PrincipalValue::StringList(resource.secrets()),
+                    ));
+                }
+                active = Some(resource.is_active());
+                if let Some(quota) = resource.extension.as_ref().and_then(|ext| ext.quota) {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Quota,
+                        PrincipalValue::Integer(quota),
+                    ));
+                }
+            }
+            ResourceType::Group => {
+                if let Some(name) = resource.display_name.as_ref().filter(|n| !n.is_empty()) {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Name,
+                        PrincipalValue::String(name.to_string()),
+                    ));
+                }
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Members,
+                    PrincipalValue::StringList(
+                        self.scim_member_names(resource.members.iter().map(|m| m.value.as_str()))
+                            .await?,
+                    ),
+                ));
+            }
+        }
+
+        self.scim_update(typ, principal.id, changes, active, base_url)
+            .await
+    }
+
+    async fn scim_patch(
+        &self,
+        typ: ResourceType,
+        id: &str,
+        body: Option<Vec<u8>>,
+        base_url: &str,
+    ) -> ScimResult<ScimResponse> {
+        let principal = self.scim_principal(typ, id).await?;
+        let account_id = principal.id;
+        let resource = self.scim_resource(typ, principal, base_url).await?;
+        let patch = parse_body::<ScimPatch>(body)?;
+        let mut changes = Vec::new();
+        let mut active = None;
+
+        for operation in patch.operations {
+            let op = match operation.op.to_ascii_lowercase().as_str() {
+                "add" => PatchOp::Add,
+                "replace" => PatchOp::Replace,
+                "remove" => PatchOp::Remove,
+                _ => {
+                    return Err(ScimError::new(
+                        StatusCode::BAD_REQUEST,
+                        "invalidSyntax",
+                        format!("Unsupported patch operation {:?}.", operation.op),
+                    ))
+                }
+            };
+
+            if let Some(path) = &operation.path {
+                let path = AttrPath::parse(path)
+                    .map_err(|err| ScimError::new(StatusCode::BAD_REQUEST, "invalidPath", err))?;
+                self.scim_patch_attribute(
+                    typ,
+                    op,
+                    path,
+                    operation.value,
+                    &resource,
+                    &mut changes,
+                    &mut active,
+                )
+                .await?;
+            } else if let (PatchOp::Add | PatchOp::Replace, Value::Object(values)) =
+                (op, operation.value)
+            {
+                for (attr, value) in values {
+                    // Extension attributes are nested under the schema URN
+                    if let (true, Value::Object(ext_values)) =
+                        (attr.eq_ignore_ascii_case(SCHEMA_USER_EXT), &value)
+                    {
+                        for (ext_attr, ext_value) in ext_values {
+                            self.scim_patch_attribute(
+                                typ,
+                                op,
+                                AttrPath::new(format!("{SCHEMA_USER_EXT}:{ext_attr}")),
+                                ext_value.clone(),
+                                &resource,
+                                &mut changes,
+                                &mut active,
+                            )
+                            .await?;
+                        }
+                    } else {
+                        self.scim_patch_attribute(
+                            typ,
+                            op,
+                            AttrPath::new(attr),
+                            value,
+                            &resource,
+                            &mut changes,
+                            &mut active,
+                        )
+                        .await?;
+                    }
+                }
+            } else {
+                return Err(ScimError::new(
+                    StatusCode::BAD_REQUEST,
+                    "noTarget",
+                    "A path is required for this operation.",
+                ));
+            }
+        }
+
+        self.scim_update(typ, account_id, changes, active, base_url)
+            .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn scim_patch_attribute(
+        &self,
+        typ: ResourceType,
+        op: PatchOp,
+        path: AttrPath,
+        value: Value,
+        resource: &Value,
+        changes: &mut Vec<PrincipalUpdate>,
+        active: &mut Option<bool>,
+    ) -> ScimResult<()> {
+        let attr = path.attr.to_ascii_lowercase();
+        match (typ, attr.as_str()) {
+            (ResourceType::User, "username") | (ResourceType::Group, "displayname") => {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Name,
+                    PrincipalValue::String(
+                        value
+                            .as_str()
+                            .filter(|name| op != PatchOp::Remove && !name.is_empty())
+                            .ok_or_else(|| {
+                                ScimError::new(
+                                    StatusCode::BAD_REQUEST,
+                                    "mutability",
+                                    format!("Attribute {:?} cannot be removed.", path.attr),
+                                )
+                            })?
+                            .to_string(),
+                    ),
+                ));
+            }
+            (ResourceType::User, "displayname" | "name.formatted") => {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Description,
+                    PrincipalValue::String(
+                        value
+                            .as_str()
+                            .filter(|_| op != PatchOp::Remove)
+                            .unwrap_or_default()
+                            .to_string(),
+                    ),
+                ));
+            }
+            (ResourceType::User, "password") => {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Secrets,
+                    PrincipalValue::StringList(
+                        value
+                            .as_str()
+                            .filter(|_| op != PatchOp::Remove)
+                            .map(|secret| vec![secret.to_string()])
+                            .unwrap_or_default(),
+                    ),
+                ));
+            }
+            (ResourceType::User, "active") => {
+                *active = Some(op != PatchOp::Remove && is_true(&value));
+            }
+            (ResourceType::User, "emails") => {
+                let current = current_values(resource, "emails", None);
+                match (op, &path.filter) {
+                    (PatchOp::Remove, Some(_)) => {
+                        for email in current_values(resource, "emails", path.filter.as_ref()) {
+                            changes.push(PrincipalUpdate::remove_item(
+                                PrincipalField::Emails,
+                                PrincipalValue::String(email),
+                            ));
+                        }
+                    }
+                    (PatchOp::Remove, None) if value.is_array() => {
+                        for email in patch_values(&value) {
+                            changes.push(PrincipalUpdate::remove_item(
+                                PrincipalField::Emails,
+                                PrincipalValue::String(email),
+                            ));
+                        }
+                    }
+                    (PatchOp::Remove, None) => {
+                        changes.push(PrincipalUpdate::set(
+                            PrincipalField::Emails,
+                            PrincipalValue::StringList(Vec::new()),
+                        ));
+                    }
+                    (PatchOp::Add, None) => {
+                        for email in patch_values(&value) {
+                            if !current.contains(&email) {
+                                changes.push(PrincipalUpdate::add_item(
+                                    PrincipalField::Emails,
+                                    PrincipalValue::String(email),
+                                ));
+                            }
+                        }
+                    }
+                    (PatchOp::Replace, None) => {
+                        changes.push(PrincipalUpdate::set(
+                            PrincipalField::Emails,
+                            PrincipalValue::StringList(patch_values(&value)),
+                        ));
+                    }
+                    (PatchOp::Add | PatchOp::Replace, Some(filter)) => {
+                        // Replace the matching addresses, or add the address when none matches
+                        let new_email = if path.sub_attr.is_some() {
+                            value.as_str().map(|v| v.to_string())
+                        } else {
+                            patch_values(&value).into_iter().next()
+                        }
+                        .ok_or_else(|| ScimError::invalid_value("Invalid email address."))?;
+                        let matched = current_values(resource, "emails", Some(filter));
+                        let mut emails = Vec::with_capacity(current.len() + 1);
+                        for email in current {
+                            if !matched.contains(&email) {
+                                emails.push(email);
+                            } else if !emails.contains(&new_email) {
+                                emails.push(new_email.clone());
+                            }
+                        }
+                        if !emails.contains(&new_email) {
+                            emails.push(new_email);
+                        }
+                        changes.push(PrincipalUpdate::set(
+                            PrincipalField::Emails,
+                            PrincipalValue::StringList(emails),
+                        ));
+                    }
+                }
+            }
+            (ResourceType::Group, "members") => match (op, &path.filter) {
+                (PatchOp::Remove, Some(filter)) => {
+                    for member in self
+                        .scim_member_names(
+                            current_values(resource, "members", Some(filter))
+                                .iter()
+                                .map(|m| m.as_str()),
+                        )
+                        .await?
+                    {
+                        changes.push(PrincipalUpdate::remove_item(
+                            PrincipalField::Members,
+                            PrincipalValue::String(member),
+                        ));
+                    }
+                }
+                (PatchOp::Remove, None) if value.is_array() => {
+                    for member in self
+                        .scim_member_names(patch_values(&value).iter().map(|m| m.as_str()))
+                        .await?
+                    {
+                        changes.push(PrincipalUpdate::remove_item(
+                            PrincipalField::Members,
+                            PrincipalValue::String(member),
+                        ));
+                    }
+                }
+                (PatchOp::Remove, None) => {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Members,
+                        PrincipalValue::StringList(Vec::new()),
+                    ));
+                }
+                (PatchOp::Add, None) => {
+                    let current = current_values(resource, "members", None);
+                    for member in self
+                        .scim_member_names(
+                            patch_values(&value)
+                                .iter()
+                                .filter(|id| !current.contains(id))
+                                .map(|m| m.as_str()),
+                        )
+                        .await?
+                    {
+                        changes.push(PrincipalUpdate::add_item(
+                            PrincipalField::Members,
+                            PrincipalValue::String(member),
+                        ));
+                    }
+                }
+                (PatchOp::Replace, None) => {
+                    changes.push(PrincipalUpdate::set(
+                        PrincipalField::Members,
+                        PrincipalValue::StringList(
+                            self.scim_member_names(patch_values(&value).iter().map(|m| m.as_str()))
+                                .await?,
+                        ),
+                    ));
+                }
+                (PatchOp::Add | PatchOp::Replace, Some(_)) => {
+                    return Err(ScimError::new(
+                        StatusCode::BAD_REQUEST,
+                        "invalidPath",
+                        "Group members cannot be modified through a value filter.",
+                    ));
+                }
+            },
+            (ResourceType::User, _)
+                if attr.eq_ignore_ascii_case(&format!("{SCHEMA_USER_EXT}:quota")) =>
+            {
+                changes.push(PrincipalUpdate::set(
+                    PrincipalField::Quota,
+                    PrincipalValue::Integer(if op != PatchOp::Remove {
+                        value
+                            .as_u64()
+                            .or_else(|| value.as_str().and_then(|v| v.parse().ok()))
+                            .ok_or_else(|| ScimError::invalid_value("Invalid quota."))?
+                    } else {
+                        0
+                    }),
+                ));
+            }
+            _ => {
+                // Attributes that have no equivalent in the directory are ignored
+                tracing::debug!(
+                    context = "scim",
+                    event = "patch",
+                    attribute = path.attr,
+                    "Ignoring unsupported attribute"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn scim_update(
+        &self,
+        typ: ResourceType,
+        account_id: u32,
+        changes: Vec<PrincipalUpdate>,
+        active: Option<bool>,
+        base_url: &str,
+    ) -> ScimResult<ScimResponse> {
+        if !changes.is_empty() {
+            self.directory
+                .update_account(QueryBy::Id(account_id), changes)
+                .await?;
+        }
+        if let Some(active) = active {
+            self.directory.set_disabled(account_id, !active).await?;
+        }
+
+        let principal = self.scim_principal(typ, &account_id.to_string()).await?;
+        self.scim_resource(typ, principal, base_url)
+            .await
+            .map(ScimResponse::ok)
+    }
+
+    async fn scim_delete(&self, typ: ResourceType, id: &str) -> ScimResult<ScimResponse> {
+        let account_id = self.scim_principal(typ, id).await?.id;
+
+        // Remove FTS index
+        if let Err(err) = self.fts_store.remove_all(account_id).await {
+            tracing::warn!(
+                context = "fts",
+                event = "error",
+                reason = ?err,
+                "Failed to remove FTS index"
+            );
+            return Err(ScimError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                "Failed to remove FTS index",
+            ));
+        }
+
+        self.directory
+            .delete_account(QueryBy::Id(account_id))
+            .await?;
+
+        Ok(ScimResponse::new(StatusCode::NO_CONTENT, Value::Null))
+    }
+
+    async fn scim_principal(&self, typ: ResourceType, id: &str) -> ScimResult<Principal<u32>> {
+        if let Ok(account_id) = id.parse::<u32>() {
+            if let Some(principal) = self
+                .directory
+                .query(QueryBy::Id(account_id), true)
+                .await?
+                .filter(|p| typ.is_type(p.typ))
+            {
+                return Ok(principal);
+            }
+        }
+
+        Err(ScimError::not_found())
+    }
+
+    async fn scim_principal_by_name(
+        &self,
+        typ: ResourceType,
+        name: &str,
+    ) -> ScimResult<Option<Principal<u32>>> {
+        Ok(self
+            .directory
+            .query(QueryBy::Name(name), true)
+            .await?
+            .filter(|p| typ.is_type(p.typ)))
+    }
+
+    async fn scim_resource(
+        &self,
+        typ: ResourceType,
+        principal: Principal<u32>,
+        base_url: &str,
+    ) -> ScimResult<Value> {
+        let location = format!("{base_url}/scim/v2/{}/{}", typ.endpoint(), principal.id);
+
+        match typ {
+            ResourceType::User => {
+                let mut groups = Vec::with_capacity(principal.member_of.len());
+                for group_id in &principal.member_of {
+                    if let Some(group) = self.directory.query(QueryBy::Id(*group_id), false).await?
+                    {
+                        groups.push(json!({
+                            "value": group_id.to_string(),
+                            "display": group.name,
+                            "$ref": format!("{base_url}/scim/v2/Groups/{group_id}"),
+                        }));
+                    }
+                }
+
+                let mut resource = json!({
+                    "schemas": [SCHEMA_USER, SCHEMA_USER_EXT],
+                    "id": principal.id.to_string(),
+                    "userName": principal.name,
+                    "active": !self.directory.is_disabled(principal.id).await?,
+                    "emails": principal
+                        .emails
+                        .iter()
+                        .enumerate()
+                        .map(|(pos, email)| {
+                            json!({
+                                "value": email,
+                                "type": "work",
+                                "primary": pos == 0,
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                    "groups": groups,
+                    SCHEMA_USER_EXT: {
+                        "quota": principal.quota,
+                    },
+                    "meta": {
+                        "resourceType": "User",
+                        "location": location,
+                    },
+                });
+                if let Some(description) = principal.description {
+                    resource["displayName"] = json!(description);
+                    resource["name"] = json!({ "formatted": description });
+                }
+
+                Ok(resource)
+            }
+            ResourceType::Group => {
+                let mut members = Vec::new();
+                for member_id in self.store.get_members(principal.id).await? {
+                    if let Some(member) =
+                        self.directory.query(QueryBy::Id(member_id), false).await?
+                    {
+                        let typ = if member.typ == Type::Group {
+                            ResourceType::Group
+                        } else {
+                            ResourceType::User
+                        };
+                        members.push(json!({
+                            "value": member_id.to_string(),
+                            "display": member.name,
+                            "type": typ.name(),
+                            "$ref": format!("{base_url}/scim/v2/{}/{member_id}", typ.endpoint()),
+                        }));
+                    }
+                }
+
+                Ok(json!({
+                    "schemas": [SCHEMA_GROUP],
+                    "id": principal.id.to_string(),
+                    "displayName": principal.name,
+                    "members": members,
+                    "meta": {
+                        "resourceType": "Group",
+                        "location": location,
+                    },
+                }))
+            }
+        }
+    }
+
+    async fn scim_member_names<'x>(
+        &self,
+        ids: impl Iterator<Item = &'x str>,
+    ) -> ScimResult<Vec<String>> {
+        let mut names = Vec::new();
+        for id in ids {
+            if let Ok(account_id) = id.parse::<u32>() {
+                if let Some(member) = self.directory.query(QueryBy::Id(account_id), false).await? {
+                    names.push(member.name);
+                    continue;
+                }
+            }
+            return Err(ScimError::invalid_value(format!(
+                "Member {id:?} does not exist."
+            )));
+        }
+
+        Ok(names)
+    }
+
+    fn scim_service_provider_config(&self, base_url: &str) -> ScimResponse {
+        ScimResponse::ok(json!({
+            "schemas": [SCHEMA_SERVICE_PROVIDER],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": self.config.query_max_results },
+            "changePassword": { "supported": true },
+            "sort": { "supported": false },
+            "etag": { "supported": false },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Bearer Token",
+                "description": "Authentication using the bearer token configured in 'scim.token'",
+                "primary": true,
+            }],
+            "meta": {
+                "resourceType": "ServiceProviderConfig",
+                "location": format!("{base_url}/scim/v2/ServiceProviderConfig"),
+            },
+        }))
+    }
+}
+
+fn scim_resource_types(base_url: &str) -> ScimResponse {
+    let resources = [ResourceType::User, ResourceType::Group]
+        .into_iter()
+        .map(|typ| {
+            let mut resource = json!({
+                "schemas": [SCHEMA_RESOURCE_TYPE],
+                "id": typ.name(),
+                "name": typ.name(),
+                "endpoint": format!("/{}", typ.endpoint()),
+                "schema": typ.schema(),
+                "meta": {
+                    "resourceType": "ResourceType",
+                    "location": format!("{base_url}/scim/v2/ResourceTypes/{}", typ.name()),
+                },
+            });
+            if typ == ResourceType::User {
+                resource["schemaExtensions"] = json!([{
+                    "schema": SCHEMA_USER_EXT,
+                    "required": false,
+                }]);
+            }
+            resource
+        })
+        .collect::<Vec<_>>();
+
+    ScimResponse::ok(json!({
+        "schemas": [SCHEMA_LIST],
+        "totalResults": resources.len(),
+        "startIndex": 1,
+        "itemsPerPage": resources.len(),
+        "Resources": resources,
+    }))
+}
+
+impl ResourceType {
+    fn name(&self) -> &'static str {
+        match self {
+            ResourceType::User => "User",
+            ResourceType::Group => "Group",
+        }
+    }
+
+    fn endpoint(&self) -> &'static str {
+        match self {
+            ResourceType::User => "Users",
+            ResourceType::Group => "Groups",
+        }
+    }
+
+    fn schema(&self) -> &'static str {
+        match self {
+            ResourceType::User => SCHEMA_USER,
+            ResourceType::Group => SCHEMA_GROUP,
+        }
+    }
+
+    fn name_attr(&self) -> &'static str {
+        match self {
+            ResourceType::User => "userName",
+            ResourceType::Group => "displayName",
+        }
+    }
+
+    fn principal_type(&self) -> Type {
+        match self {
+            ResourceType::User => Type::Individual,
+            ResourceType::Group => Type::Group,
+        }
+    }
+
+    fn is_type(&self, typ: Type) -> bool {
+        match self {
+            ResourceType::User => matches!(typ, Type::Individual | Type::Superuser),
+            ResourceType::Group => typ == Type::Group,
+        }
+    }
+}
+
+impl ScimResource {
+    fn description(&self) -> Option<String> {
+        self.display_name
+            .clone()
+            .or_else(|| {
+                self.name.as_ref().and_then(|name| {
+                    name.formatted.clone().or_else(|| {
+                        let name = [name.given_name.as_deref(), name.family_name.as_deref()]
+                            .into_iter()
+                            .flatten()
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        (!name.is_empty()).then_some(name)
+                    })
+                })
+            })
+            .filter(|description| !description.is_empty())
+    }
+
+    fn emails(&self) -> Vec<String> {
+        // The primary address is always listed first
+        let mut emails = Vec::with_capacity(self.emails.len());
+        for email in self.emails.iter().filter(|e| e.primary) {
+            emails.push(email.value.to_lowercase());
+        }
+        for email in self.emails.iter().filter(|e| !e.primary) {
+            let email = email.value.to_lowercase();
+            if !emails.contains(&email) {
+                emails.push(email);
+            }
+        }
+        emails
+    }
+
+    fn secrets(&self) -> Vec<String> {
+        self.password
+            .as_ref()
+            .map(|password| vec![password.to_string()])
+            .unwrap_or_default()
+    }
+
+    fn is_active(&self) -> bool {
+        self.active.as_ref().is_none_or(is_true)
+    }
+}
+
+impl AttrPath {
+    fn new(attr: String) -> Self {
+        AttrPath {
+            attr,
+            filter: None,
+            sub_attr: None,
+        }
+    }
+}
+
+/// Returns the "value" of the elements of a multi-valued attribute, optionally
+/// restricted to the elements matching a value filter.
+fn current_values(resource: &Value, attr: &str, filter: Option<&Filter>) -> Vec<String> {
+    resource
+        .get(attr)
+        .and_then(|values| values.as_array())
+        .map(|values| {
+            values
+                .iter()
+                .filter(|value| filter.is_none_or(|f| f.matches(value)))
+                .filter_map(|value| value.get("value").and_then(|v| v.as_str()))
+                .map(|value| value.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Extracts the values of a patch operation, which can be either a list of
+/// complex values, a single complex value or a plain string.
+fn patch_values(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values.iter().flat_map(patch_values).collect(),
+        Value::Object(_) => value
+            .get("value")
+            .and_then(|v| v.as_str())
+            .map(|v| vec![v.to_string()])
+            .unwrap_or_default(),
+        Value::String(value) => vec![value.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn is_true(value: &Value) -> bool {
+    match value {
+        Value::Bool(value) => *value,
+        Value::String(value) => value.eq_ignore_ascii_case("true"),
+        _ => false,
+    }
+}
+
+fn parse_body<T: serde::de::DeserializeOwned>(body: Option<Vec<u8>>) -> ScimResult<T> {
+    body.and_then(|body| serde_json::from_slice::<T>(&body).ok())
+        .ok_or_else(|| {
+            ScimError::new(
+                StatusCode::BAD_REQUEST,
+                "invalidSyntax",
+                "Failed to deserialize request.",
+            )
+        })
+}
+
+impl ScimResponse {
+    fn new(status: StatusCode, body: Value) -> Self {
+        ScimResponse { status, body }
+    }
+
+    fn ok(body: Value) -> Self {
+        ScimResponse {
+            status: StatusCode::OK,
+            body,
+        }
+    }
+}
+
+impl ScimError {
+    fn new(
+        status: StatusCode,
+        scim_type: impl Into<Option<&'static str>>,
+        detail: impl Into<String>,
+    ) -> Self {
+        ScimError {
+            status,
+            scim_type: scim_type.into(),
+            detail: detail.into(),
+        }
+    }
+
+    fn not_found() -> Self {
+        ScimError::new(StatusCode::NOT_FOUND, None, "Resource not found.")
+    }
+
+    fn invalid_value(detail: impl Into<String>) -> Self {
+        ScimError::new(StatusCode::BAD_REQUEST, "invalidValue", detail)
+    }
+}
+
+impl From<DirectoryError> for ScimError {
+    fn from(err: DirectoryError) -> Self {
+        match err {
+            DirectoryError::Management(ManagementError::AlreadyExists { field, value }) => {
+                ScimError::new(
+                    StatusCode::CONFLICT,
+                    "uniqueness",
+                    format!("Another record exists containing '{value}' in the '{field}' field."),
+                )
+            }
+            DirectoryError::Management(ManagementError::MissingField(field)) => {
+                ScimError::invalid_value(format!("Missing required field '{field}'."))
+            }
+            DirectoryError::Management(ManagementError::NotFound(details)) => {
+                ScimError::invalid_value(format!("'{details}' does not exist."))
+            }
+            DirectoryError::Management(ManagementError::InvalidPassword(violation)) => {
+                ScimError::invalid_value(violation.to_string())
+            }
+            DirectoryError::Management(ManagementError::LimitExceeded { domain, limit }) => {
+                ScimError::invalid_value(format!(
+                    "Domain '{domain}' has reached its '{limit}' limit."
+                ))
+            }
+            DirectoryError::Management(ManagementError::LegalHold(name)) => ScimError::new(
+                StatusCode::FORBIDDEN,
+                None,
+                format!("Account '{name}' is under legal hold."),
+            ),
+            DirectoryError::Unsupported => ScimError::new(
+                StatusCode::NOT_IMPLEMENTED,
+                None,
+                "Requested action is unsupported by the directory.",
+            ),
+            err => {
+                tracing::warn!(
+                    context = "scim",
+                    event = "error",
+                    reason = ?err,
+                    "Directory error"
+                );
+
+                ScimError::new(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    None,
+                    "Contact the administrator if this problem persists.",
+                )
+            }
+        }
+    }
+}
+
+impl ToHttpResponse for ScimResponse {
+    fn into_http_response(self) -> HttpResponse {
+        let body = if self.status != StatusCode::NO_CONTENT {
+            Bytes::from(serde_json::to_string(&self.body).unwrap())
+        } else {
+            Bytes::new()
+        };
+
+        hyper::Response::builder()
+            .status(self.status)
+            .header(header::CONTENT_TYPE, "application/scim+json")
+            .body(Full::new(body).map_err(|never| match never {}).boxed())
+            .unwrap()
+    }
+}
+
+impl ToHttpResponse for ScimError {
+    fn into_http_response(self) -> HttpResponse {
+        let mut body = json!({
+            "schemas": [SCHEMA_ERROR],
+            "status": self.status.as_u16().to_string(),
+            "detail": self.detail,
+        });
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = json!(scim_type);
+        }
+
+        ScimResponse::new(self.status, body).into_http_response()
+    }
+}
@@ -33,7 +33,11 @@ use jmap_proto::{
 use store::ahash::AHashSet;
 use utils::{listener::ServerInstance, map::vec_map::VecMap, UnwrapFailure};
 
-use crate::{auth::AccessToken, JMAP};
+use crate::{
+    auth::AccessToken,
+    sieve::extlists::{ADDRBOOK_PERSONAL, DIRECTORY_LIST_PREFIX, LOOKUP_LIST_PREFIX},
+    JMAP,
+};
 
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct Session {
@@ -83,6 +87,8 @@ pub enum Capabilities {
     SieveAccount(SieveAccountCapabilities),
     SieveSession(SieveSessionCapabilities),
     Blob(BlobCapabilities),
+    Principals(PrincipalCapabilities),
+    PrincipalsOwner(PrincipalOwnerCapabilities),
     Empty(EmptyCapabilities),
 }
 
@@ -174,6 +180,20 @@ pub struct BlobCapabilities {
     supported_digest_algorithms: Vec<&'static str>,
 }
 
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PrincipalCapabilities {
+    #[serde(rename(serialize = "currentUserPrincipalId"))]
+    current_user_principal_id: Option<Id>,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PrincipalOwnerCapabilities {
+    #[serde(rename(serialize = "accountIdForPrincipal"))]
+    account_id_for_principal: Id,
+    #[serde(rename(serialize = "principalId"))]
+    principal_id: Id,
+}
+
 #[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct EmptyCapabilities {}
 
@@ -201,6 +221,10 @@ impl JMAP {
             None,
             &self.config.capabilities.account,
         );
+        session.set_principal_capabilities(
+            access_token.primary_id().into(),
+            access_token.primary_id().into(),
+        );
 
         // Add secondary accounts
         for id in access_token.secondary_ids() {
@@ -224,6 +248,7 @@ impl JMAP {
                 Some(&[Capability::Mail, Capability::Quota, Capability::Blob]),
                 &self.config.capabilities.account,
             );
+            session.set_principal_capabilities((*id).into(), access_token.primary_id().into());
         }
 
         Ok(session)
@@ -298,6 +323,16 @@ impl crate::Config {
             Capabilities::Blob(BlobCapabilities::new(self)),
         );
 
+        // Add MDN capabilities
+        self.capabilities.session.append(
+            Capability::Mdn,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+        self.capabilities.account.append(
+            Capability::Mdn,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+
         // Add Quota capabilities
         self.capabilities.session.append(
             Capability::Quota,
@@ -307,6 +342,24 @@ impl crate::Config {
             Capability::Quota,
             Capabilities::Empty(EmptyCapabilities::default()),
         );
+
+        // Add principal and sharing capabilities
+        self.capabilities.session.append(
+            Capability::Principals,
+            Capabilities::Empty(EmptyCapabilities::default()),
+        );
+
+        // Add deleted items recovery capabilities
+        if self.deleted_items_hold.is_some() {
+            self.capabilities.session.append(
+                Capability::Recovery,
+                Capabilities::Empty(EmptyCapabilities::default()),
+            );
+            self.capabilities.account.append(
+                Capability::Recovery,
+                Capabilities::Empty(EmptyCapabilities::default()),
+            );
+        }
     }
 }
 
@@ -380,6 +433,24 @@ impl Session {
         );
     }
 
+    pub fn set_principal_capabilities(&mut self, account_id: Id, primary_account_id: Id) {
+        if let Some(account) = self.accounts.get_mut(&account_id) {
+            account.account_capabilities.append(
+                Capability::Principals,
+                Capabilities::Principals(PrincipalCapabilities {
+                    current_user_principal_id: primary_account_id.into(),
+                }),
+            );
+            account.account_capabilities.append(
+                Capability::PrincipalsOwner,
+                Capabilities::PrincipalsOwner(PrincipalOwnerCapabilities {
+                    account_id_for_principal: primary_account_id,
+                    principal_id: account_id,
+                }),
+            );
+        }
+    }
+
     pub fn set_state(&mut self, state: u32) {
         self.state = state;
     }
@@ -467,6 +538,16 @@ impl SieveAccountCapabilities {
             capabilities.remove(&sieve::compiler::grammar::Capability::parse(capability));
         }
 
+        let ext_lists = if capabilities.contains(&sieve::compiler::grammar::Capability::ExtLists) {
+            Some(vec![
+                ADDRBOOK_PERSONAL.to_string(),
+                DIRECTORY_LIST_PREFIX.to_string(),
+                LOOKUP_LIST_PREFIX.to_string(),
+            ])
+        } else {
+            None
+        };
+
         let mut extensions = capabilities
             .into_iter()
             .map(|c| c.to_string())
@@ -490,7 +571,7 @@ impl SieveAccountCapabilities {
             } else {
                 None
             },
-            ext_lists: None,
+            ext_lists,
         }
     }
 }
@@ -102,6 +102,7 @@ impl JMAP {
                 }
             }
         }
+        self.add_public_access(&mut access_token).await?;
         access_token.into()
     }
 
@@ -113,7 +114,18 @@ impl JMAP {
         check_acls: impl Into<Bitmap<Acl>>,
     ) -> Result<RoaringBitmap, MethodError> {
         let check_acls = check_acls.into();
+
+        // Public folders grant their default rights on mailboxes without an explicit ACL
         let mut document_ids = RoaringBitmap::new();
+        if to_collection == Collection::Mailbox {
+            if let Some(mut acls) = access_token.public_acl(to_account_id) {
+                acls.intersection(&check_acls);
+                if !acls.is_empty() {
+                    document_ids = self.public_mailboxes(to_account_id).await?;
+                }
+            }
+        }
+
         let to_collection = u8::from(to_collection);
         for &grant_account_id in [access_token.primary_id]
             .iter()
@@ -228,6 +240,24 @@ impl JMAP {
     ) -> Result<bool, MethodError> {
         let to_collection = to_collection.into();
         let check_acls = check_acls.into();
+        if to_collection == u8::from(Collection::Mailbox) {
+            if let Some(mut acls) = access_token.public_acl(to_account_id) {
+                acls.intersection(&check_acls);
+                if !acls.is_empty()
+                    && self
+                        .get_property::<Object<Value>>(
+                            to_account_id,
+                            Collection::Mailbox,
+                            to_document_id,
+                            Property::Value,
+                        )
+                        .await?
+                        .is_some_and(|mailbox| !mailbox.has_explicit_acl())
+                {
+                    return Ok(true);
+                }
+            }
+        }
         for &grant_account_id in [access_token.primary_id]
             .iter()
             .chain(access_token.member_of.clone().iter())
@@ -264,6 +294,30 @@ impl JMAP {
         Ok(false)
     }
 
+    /// Returns the mailboxes of a public folder account that have no explicit
+    /// ACL, setting an ACL on a mailbox replaces the public folder's default rights.
+    async fn public_mailboxes(&self, account_id: u32) -> Result<RoaringBitmap, MethodError> {
+        let mut document_ids = self
+            .get_document_ids(account_id, Collection::Mailbox)
+            .await?
+            .unwrap_or_default();
+        for (document_id, mailbox) in self
+            .get_properties::<Object<Value>, _, _>(
+                account_id,
+                Collection::Mailbox,
+                &document_ids,
+                Property::Value,
+            )
+            .await?
+        {
+            if mailbox.has_explicit_acl() {
+                document_ids.remove(document_id);
+            }
+        }
+
+        Ok(document_ids)
+    }
+
     pub async fn acl_set(
         &self,
         changes: &mut Object<Value>,
@@ -485,12 +539,17 @@ impl JMAP {
 }
 
 pub trait EffectiveAcl {
-    fn effective_acl(&self, access_token: &AccessToken) -> Bitmap<Acl>;
+    fn effective_acl(&self, access_token: &AccessToken, account_id: u32) -> Bitmap<Acl>;
+    fn has_explicit_acl(&self) -> bool;
 }
 
 impl EffectiveAcl for Object<Value> {
-    fn effective_acl(&self, access_token: &AccessToken) -> Bitmap<Acl> {
-        let mut acl = Bitmap::<Acl>::new();
+    fn effective_acl(&self, access_token: &AccessToken, account_id: u32) -> Bitmap<Acl> {
+        let mut acl = if !self.has_explicit_acl() {
+            access_token.public_acl(account_id).unwrap_or_default()
+        } else {
+            Bitmap::new()
+        };
         if let Some(Value::Acl(permissions)) = self.properties.get(&Property::Acl) {
             for item in permissions {
                 if access_token.is_member(item.account_id) {
@@ -501,4 +560,8 @@ impl EffectiveAcl for Object<Value> {
 
         acl
     }
+
+    fn has_explicit_acl(&self) -> bool {
+        matches!(self.properties.get(&Property::Acl), Some(Value::Acl(acl)) if !acl.is_empty())
+    }
 }
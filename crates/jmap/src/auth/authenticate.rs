@@ -30,7 +30,10 @@ use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use utils::{listener::limiter::InFlight, map::ttl_dashmap::TtlMap};
 
-use crate::JMAP;
+use crate::{
+    services::audit::{AuditAction, AuditEvent},
+    JMAP,
+};
 
 use super::AccessToken;
 
@@ -189,8 +192,20 @@ impl JMAP {
             )
             .await
         {
-            Ok(AuthResult::Success(principal)) => AuthResult::Success(AccessToken::new(principal)),
+            Ok(AuthResult::Success(principal)) => {
+                match self.update_access_token(AccessToken::new(principal)).await {
+                    Some(access_token) => AuthResult::Success(access_token),
+                    None => AuthResult::Failure,
+                }
+            }
             Ok(AuthResult::Failure) => {
+                self.audit(
+                    AuditEvent::new(AuditAction::AuthFailure)
+                        .with_actor(username)
+                        .with_remote_ip(remote_ip)
+                        .with_success(false),
+                )
+                .await;
                 let _ = self.is_auth_allowed_hard(&remote_ip).await;
                 AuthResult::Failure
             }
@@ -34,7 +34,7 @@ use aes_gcm_siv::{
 use directory::{Principal, Type};
 use jmap_proto::{
     error::method::MethodError,
-    types::{collection::Collection, id::Id},
+    types::{acl::Acl, collection::Collection, id::Id},
 };
 use store::blake3;
 use utils::map::bitmap::Bitmap;
@@ -42,13 +42,17 @@ use utils::map::bitmap::Bitmap;
 pub mod acl;
 pub mod authenticate;
 pub mod oauth;
+pub mod password;
 pub mod rate_limit;
+pub mod roles;
+pub mod sasl;
 
 #[derive(Debug, Clone, Default)]
 pub struct AccessToken {
     pub primary_id: u32,
     pub member_of: Vec<u32>,
     pub access_to: Vec<(u32, Bitmap<Collection>)>,
+    pub public_acl: Vec<(u32, Bitmap<Acl>)>,
     pub name: String,
     pub description: Option<String>,
     pub quota: u64,
@@ -61,6 +65,7 @@ impl AccessToken {
             primary_id: principal.id,
             member_of: principal.member_of,
             access_to: Vec::new(),
+            public_acl: Vec::new(),
             name: principal.name,
             description: principal.description,
             quota: principal.quota,
@@ -119,6 +124,12 @@ impl AccessToken {
             }))
     }
 
+    pub fn public_acl(&self, account_id: u32) -> Option<Bitmap<Acl>> {
+        self.public_acl
+            .iter()
+            .find_map(|(id, acl)| if *id == account_id { Some(*acl) } else { None })
+    }
+
     pub fn has_access(&self, to_account_id: u32, to_collection: impl Into<Collection>) -> bool {
         let to_collection = to_collection.into();
         self.is_member(to_account_id)
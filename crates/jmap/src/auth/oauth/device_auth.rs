@@ -40,6 +40,7 @@ use crate::{
         MAX_POST_LEN, OAUTH_HTML_ERROR, OAUTH_HTML_LOGIN_HEADER_FAILED, OAUTH_HTML_LOGIN_SUCCESS,
         STATUS_AUTHORIZED,
     },
+    services::audit::{AuditAction, AuditEvent},
     JMAP,
 };
 
@@ -178,6 +179,13 @@ impl JMAP {
                     if let AuthResult::Success(id) =
                         self.authenticate_plain(email, password, remote_addr).await
                     {
+                        self.audit(
+                            AuditEvent::new(AuditAction::OAuthGrant)
+                                .with_actor(id.name.clone())
+                                .with_remote_ip(remote_addr)
+                                .with_target(oauth.client_id.clone()),
+                        )
+                        .await;
                         oauth
                             .account_id
                             .store(id.primary_id(), atomic::Ordering::Relaxed);
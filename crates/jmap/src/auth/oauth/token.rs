@@ -185,10 +185,10 @@ impl JMAP {
     ) -> Result<OAuthResponse, &'static str> {
         let password_hash = self
             .directory
-            .query(QueryBy::Id(account_id), false)
+            .query_login(QueryBy::Id(account_id), false)
             .await
             .map_err(|_| "Temporary lookup error")?
-            .ok_or("Account no longer exists")?
+            .ok_or("Account no longer exists or is disabled")?
             .secrets
             .into_iter()
             .next()
@@ -303,10 +303,10 @@ impl JMAP {
         // Obtain password hash
         let password_hash = self
             .directory
-            .query(QueryBy::Id(account_id), false)
+            .query_login(QueryBy::Id(account_id), false)
             .await
             .map_err(|_| "Temporary lookup error")?
-            .ok_or("Account no longer exists")?
+            .ok_or("Account no longer exists or is disabled")?
             .secrets
             .into_iter()
             .next()
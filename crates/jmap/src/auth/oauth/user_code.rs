@@ -39,6 +39,7 @@ use utils::map::ttl_dashmap::TtlMap;
 use crate::{
     api::{http::ToHttpResponse, HtmlResponse, HttpRequest, HttpResponse},
     auth::AccessToken,
+    services::audit::{AuditAction, AuditEvent},
     JMAP,
 };
 
@@ -168,6 +169,18 @@ impl JMAP {
             if let AuthResult::Success(access_token) =
                 self.authenticate_plain(email, password, remote_addr).await
             {
+                self.audit(
+                    AuditEvent::new(AuditAction::OAuthGrant)
+                        .with_actor(access_token.name.clone())
+                        .with_remote_ip(remote_addr)
+                        .with_target(
+                            code_req
+                                .get("client_id")
+                                .map(|s| s.as_str())
+                                .unwrap_or_default(),
+                        ),
+                )
+                .await;
                 auth_code = self
                     .issue_client_code(
                         &access_token,
@@ -0,0 +1,142 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use hyper::StatusCode;
+use jmap_proto::error::request::RequestError;
+use serde_json::json;
+
+use crate::{
+    api::{
+        admin::map_directory_error,
+        http::{fetch_body, ToHttpResponse},
+        HttpRequest, HttpResponse, JsonResponse,
+    },
+    services::audit::{AuditAction, AuditEvent},
+    JMAP,
+};
+
+use super::AccessToken;
+
+#[derive(Debug, serde::Deserialize)]
+struct PasswordChangeRequest {
+    username: String,
+    #[serde(rename = "currentPassword")]
+    current_password: String,
+    #[serde(rename = "newPassword")]
+    new_password: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordChangeResult {
+    Success,
+    Failure,
+    Banned,
+}
+
+impl JMAP {
+    /// Verifies the current password of an account and replaces it with a
+    /// new one, enforcing the password policy of the directory.
+    pub async fn change_password(
+        &self,
+        username: &str,
+        current_password: &str,
+        new_password: &str,
+        remote_ip: IpAddr,
+    ) -> directory::Result<PasswordChangeResult> {
+        if self.is_auth_allowed_soft(&remote_ip).await.is_err() {
+            return Ok(PasswordChangeResult::Banned);
+        }
+
+        let result = self
+            .directory
+            .change_password(username, current_password, new_password)
+            .await;
+        let success = matches!(result, Ok(true));
+        self.audit(
+            AuditEvent::new(if matches!(result, Ok(false)) {
+                AuditAction::AuthFailure
+            } else {
+                AuditAction::PasswordChange
+            })
+            .with_actor(username)
+            .with_remote_ip(remote_ip)
+            .with_target(username)
+            .with_success(success),
+        )
+        .await;
+
+        match result {
+            Ok(true) => Ok(PasswordChangeResult::Success),
+            Ok(false) => Ok(if self.is_auth_allowed_hard(&remote_ip).await.is_ok() {
+                PasswordChangeResult::Failure
+            } else {
+                PasswordChangeResult::Banned
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn handle_password_change(
+        &self,
+        req: &mut HttpRequest,
+        remote_ip: IpAddr,
+    ) -> HttpResponse {
+        let remote_addr = self.build_remote_addr(req, remote_ip);
+        let request = match fetch_body(req, 8192, &AccessToken::default())
+            .await
+            .and_then(|body| serde_json::from_slice::<PasswordChangeRequest>(&body).ok())
+        {
+            Some(request) => request,
+            None => {
+                return RequestError::blank(
+                    StatusCode::BAD_REQUEST.as_u16(),
+                    "Invalid parameters",
+                    "Expected username, currentPassword and newPassword",
+                )
+                .into_http_response();
+            }
+        };
+
+        match self
+            .change_password(
+                &request.username,
+                &request.current_password,
+                &request.new_password,
+                remote_addr,
+            )
+            .await
+        {
+            Ok(PasswordChangeResult::Success) => JsonResponse::new(json!({
+                "data": (),
+            }))
+            .into_http_response(),
+            Ok(PasswordChangeResult::Failure) => RequestError::unauthorized().into_http_response(),
+            Ok(PasswordChangeResult::Banned) => {
+                RequestError::too_many_auth_attempts().into_http_response()
+            }
+            Err(err) => map_directory_error(err),
+        }
+    }
+}
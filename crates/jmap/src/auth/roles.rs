@@ -0,0 +1,63 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{core::roles::Authorization, QueryBy};
+
+use crate::JMAP;
+
+use super::AccessToken;
+
+impl JMAP {
+    pub async fn get_authorization(&self, access_token: &AccessToken) -> Authorization {
+        if access_token.is_super_user() {
+            return Authorization::superuser();
+        } else if self.config.roles.is_empty() {
+            return Authorization::default();
+        }
+
+        // Roles can be granted to any of the groups the principal is a member of
+        let mut groups = Vec::with_capacity(access_token.member_of.len());
+        for &group_id in &access_token.member_of {
+            match self.directory.query(QueryBy::Id(group_id), false).await {
+                Ok(Some(group)) => {
+                    groups.push(group.name);
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    tracing::warn!(
+                        context = "authorization",
+                        event = "error",
+                        account_id = group_id,
+                        reason = ?err,
+                        "Failed to obtain group name"
+                    );
+                }
+            }
+        }
+
+        self.config.roles.authorize(
+            &access_token.name,
+            groups.iter().map(|group| group.as_str()),
+        )
+    }
+}
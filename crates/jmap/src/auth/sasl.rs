@@ -0,0 +1,154 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use directory::{
+    core::scram::{ScramError, ScramHash, ScramServer},
+    Principal, QueryBy,
+};
+use utils::listener::tls::certificate_identities;
+
+use crate::JMAP;
+
+use super::AccessToken;
+
+pub struct ScramExchange {
+    server: ScramServer,
+    principal: Option<Principal<u32>>,
+}
+
+impl ScramExchange {
+    pub fn new(hash: ScramHash, is_plus: bool, channel_binding: Option<Vec<u8>>) -> Self {
+        ScramExchange {
+            server: ScramServer::new(hash, is_plus, channel_binding),
+            principal: None,
+        }
+    }
+
+    pub fn is_client_first(&self) -> bool {
+        self.server.is_client_first()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.server.is_done()
+    }
+}
+
+impl JMAP {
+    pub async fn scram_client_first(
+        &self,
+        exchange: &mut ScramExchange,
+        message: &[u8],
+    ) -> Result<String, ScramError> {
+        let username = exchange.server.client_first(message)?.to_string();
+
+        // Unknown users receive a fake challenge that will always fail
+        exchange.principal = match self
+            .directory
+            .query_login(QueryBy::Name(&username), true)
+            .await
+        {
+            Ok(principal) => principal,
+            Err(err) => {
+                tracing::debug!(
+                    context = "sasl",
+                    event = "error",
+                    mechanism = exchange.server.mechanism(),
+                    reason = ?err,
+                    "Failed to obtain SCRAM credentials."
+                );
+                None
+            }
+        };
+
+        Ok(exchange.server.server_first(
+            exchange
+                .principal
+                .as_ref()
+                .map(|principal| principal.secrets.as_slice()),
+            &self.directory.salt_key,
+        ))
+    }
+
+    pub async fn scram_client_final(
+        &self,
+        exchange: &mut ScramExchange,
+        message: &[u8],
+        remote_ip: IpAddr,
+    ) -> Result<(AccessToken, String), ScramError> {
+        match (
+            exchange.server.client_final(message),
+            exchange.principal.take(),
+        ) {
+            (Ok(server_final), Some(principal)) => self
+                .update_access_token(AccessToken::new(principal))
+                .await
+                .map(|access_token| (access_token, server_final))
+                .ok_or(ScramError::OtherError),
+            (result, _) => {
+                let _ = self.is_auth_allowed_hard(&remote_ip).await;
+                Err(result.err().unwrap_or(ScramError::InvalidProof))
+            }
+        }
+    }
+
+    pub async fn authenticate_external(
+        &self,
+        certificate: Option<&[u8]>,
+        authzid: &str,
+        remote_ip: IpAddr,
+    ) -> Option<AccessToken> {
+        let identities = certificate.map(certificate_identities).unwrap_or_default();
+        match self
+            .directory
+            .query_by_certificate(&identities, authzid, true)
+            .await
+        {
+            Ok(Some(principal)) => {
+                return self.update_access_token(AccessToken::new(principal)).await;
+            }
+            Ok(None) => {
+                tracing::debug!(
+                    context = "sasl",
+                    event = "failed",
+                    mechanism = "EXTERNAL",
+                    identities = ?identities,
+                    "No principal found for client certificate."
+                );
+            }
+            Err(err) => {
+                tracing::debug!(
+                    context = "sasl",
+                    event = "error",
+                    mechanism = "EXTERNAL",
+                    reason = ?err,
+                    "Failed to map client certificate to a principal."
+                );
+            }
+        }
+        let _ = self.is_auth_allowed_hard(&remote_ip).await;
+
+        None
+    }
+}
@@ -26,6 +26,7 @@ use jmap_proto::types::{blob::BlobId, id::Id};
 pub mod copy;
 pub mod download;
 pub mod get;
+pub mod resumable;
 pub mod upload;
 
 #[derive(Debug, serde::Serialize)]
@@ -39,8 +40,30 @@ pub struct UploadResponse {
     size: usize,
 }
 
+impl UploadResponse {
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
 pub struct DownloadResponse {
     pub filename: String,
     pub content_type: String,
     pub blob: Vec<u8>,
+    pub size: usize,
+    pub etag: Option<String>,
+    pub range: DownloadRange,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadRange {
+    Full,
+    Partial(std::ops::Range<usize>),
+    Unsatisfiable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeRequest {
+    Bounded { start: usize, end: Option<usize> },
+    Suffix(usize),
 }
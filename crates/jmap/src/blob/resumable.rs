@@ -0,0 +1,404 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use jmap_proto::{
+    error::request::{RequestError, RequestLimitError},
+    types::{date::UTCDate, id::Id},
+};
+use store::{
+    write::{assert::HashedValue, now, BatchBuilder, Bincode, BlobOp, ValueClass},
+    BlobClass, Deserialize, IterateParams, Serialize, ValueKey,
+};
+use utils::BlobHash;
+
+use crate::{auth::AccessToken, JMAP};
+
+use super::UploadResponse;
+
+/// Partially uploaded blob, its chunks are stored in the blob store as
+/// temporary blobs and count against the account's upload quota.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UploadSession {
+    pub id: u64,
+    pub expires: u64,
+    pub size: usize,
+    pub content_type: String,
+    pub chunks: Vec<UploadChunk>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct UploadChunk {
+    pub hash: BlobHash,
+    pub size: usize,
+    pub until: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct UploadSessionResponse {
+    #[serde(rename(serialize = "accountId"))]
+    pub account_id: Id,
+    #[serde(rename(serialize = "uploadId"))]
+    pub upload_id: Id,
+    #[serde(rename(serialize = "type"))]
+    pub c_type: String,
+    pub offset: usize,
+    pub size: usize,
+    pub expires: UTCDate,
+}
+
+pub enum ResumableUploadResponse {
+    Created(UploadSessionResponse),
+    Progress(UploadSessionResponse),
+    Completed(UploadResponse),
+    Cancelled,
+}
+
+impl JMAP {
+    pub async fn upload_session_create(
+        &self,
+        account_id: Id,
+        content_type: &str,
+        size: usize,
+        access_token: &AccessToken,
+    ) -> Result<ResumableUploadResponse, RequestError> {
+        if !access_token.is_member(account_id.document_id()) {
+            return Err(RequestError::forbidden());
+        } else if size == 0 {
+            return Err(RequestError::blank(
+                400,
+                "Invalid Parameters",
+                "Upload-Length must be greater than zero.",
+            ));
+        } else if size > self.config.upload_max_size {
+            return Err(RequestError::limit(RequestLimitError::SizeUpload));
+        }
+
+        // Enforce quota
+        self.check_upload_quota(account_id.document_id(), size, access_token)
+            .await?;
+
+        let session = UploadSession {
+            id: self
+                .generate_snowflake_id()
+                .map_err(|_| RequestError::internal_server_error())?,
+            expires: now() + self.config.upload_tmp_ttl,
+            size,
+            content_type: content_type.to_string(),
+            chunks: Vec::new(),
+        };
+        let mut batch = BatchBuilder::new();
+        batch.with_account_id(account_id.document_id()).set(
+            ValueClass::UploadSession {
+                expires: session.expires,
+                id: session.id,
+            },
+            Bincode::new(session.clone()).serialize(),
+        );
+        self.write_batch(batch)
+            .await
+            .map_err(|_| RequestError::internal_server_error())?;
+
+        Ok(ResumableUploadResponse::Created(
+            session.into_response(account_id),
+        ))
+    }
+
+    pub async fn upload_session_append(
+        &self,
+        account_id: Id,
+        upload_id: Id,
+        offset: usize,
+        data: &[u8],
+        access_token: Arc<AccessToken>,
+    ) -> Result<ResumableUploadResponse, RequestError> {
+        // Limit concurrent uploads
+        let _in_flight = self.is_upload_allowed(&access_token)?;
+
+        let session = self
+            .upload_session(account_id, upload_id, &access_token)
+            .await?;
+        let current_offset = session.inner.offset();
+        if offset != current_offset {
+            return Err(RequestError::blank(
+                409,
+                "Conflict",
+                format!("Upload-Offset does not match the current offset {current_offset}."),
+            ));
+        } else if data.is_empty() {
+            return Ok(ResumableUploadResponse::Progress(
+                session.inner.into_response(account_id),
+            ));
+        } else if current_offset + data.len() > session.inner.size {
+            return Err(RequestError::blank(
+                400,
+                "Invalid Parameters",
+                "Chunk exceeds the declared Upload-Length.",
+            ));
+        }
+
+        // Enforce quota
+        let document_id = account_id.document_id();
+        self.check_upload_quota(document_id, data.len(), &access_token)
+            .await?;
+
+        // Store chunk as a temporary blob
+        let chunk_id = self
+            .put_blob(document_id, data, true)
+            .await
+            .map_err(|_| RequestError::internal_server_error())?;
+        let mut updated = session.inner.clone();
+        updated.chunks.push(UploadChunk {
+            until: match chunk_id.class {
+                BlobClass::Reserved { expires, .. } => expires,
+                BlobClass::Linked { .. } => 0,
+            },
+            hash: chunk_id.hash,
+            size: data.len(),
+        });
+        let class = ValueClass::UploadSession {
+            expires: updated.expires,
+            id: updated.id,
+        };
+
+        // Save progress
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(document_id)
+            .assert_value(class.clone(), &session);
+        if updated.offset() < updated.size {
+            batch.set(class, Bincode::new(updated.clone()).serialize());
+        } else {
+            batch.clear(class);
+        }
+        match self.store.write(batch.build()).await {
+            Ok(_) => (),
+            Err(store::Error::AssertValueFailed) => {
+                return Err(RequestError::blank(
+                    409,
+                    "Conflict",
+                    "Upload session was modified by another request.",
+                ));
+            }
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "upload_session_append",
+                    account_id = document_id,
+                    error = ?err,
+                    "Failed to write batch.");
+                return Err(RequestError::internal_server_error());
+            }
+        }
+        if updated.offset() < updated.size {
+            return Ok(ResumableUploadResponse::Progress(
+                updated.into_response(account_id),
+            ));
+        }
+
+        // Assemble chunks into a single blob
+        let mut blob = Vec::with_capacity(updated.size);
+        for chunk in &updated.chunks {
+            blob.extend(
+                self.get_blob(&chunk.hash, 0..usize::MAX)
+                    .await
+                    .map_err(|_| RequestError::internal_server_error())?
+                    .ok_or_else(RequestError::internal_server_error)?,
+            );
+        }
+        let blob_id = self
+            .put_blob(document_id, &blob, true)
+            .await
+            .map_err(|_| RequestError::internal_server_error())?;
+        self.upload_chunks_release(document_id, &updated.chunks, Some(&blob_id.hash))
+            .await?;
+
+        Ok(ResumableUploadResponse::Completed(UploadResponse {
+            account_id,
+            blob_id,
+            c_type: updated.content_type,
+            size: blob.len(),
+        }))
+    }
+
+    pub async fn upload_session_status(
+        &self,
+        account_id: Id,
+        upload_id: Id,
+        access_token: &AccessToken,
+    ) -> Result<ResumableUploadResponse, RequestError> {
+        self.upload_session(account_id, upload_id, access_token)
+            .await
+            .map(|session| {
+                ResumableUploadResponse::Progress(session.inner.into_response(account_id))
+            })
+    }
+
+    pub async fn upload_session_cancel(
+        &self,
+        account_id: Id,
+        upload_id: Id,
+        access_token: &AccessToken,
+    ) -> Result<ResumableUploadResponse, RequestError> {
+        let session = self
+            .upload_session(account_id, upload_id, access_token)
+            .await?;
+        let class = ValueClass::UploadSession {
+            expires: session.inner.expires,
+            id: session.inner.id,
+        };
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id.document_id())
+            .assert_value(class.clone(), &session)
+            .clear(class);
+        match self.store.write(batch.build()).await {
+            Ok(_) => (),
+            Err(store::Error::AssertValueFailed) => return Err(RequestError::not_found()),
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "upload_session_cancel",
+                    account_id = account_id.document_id(),
+                    error = ?err,
+                    "Failed to write batch.");
+                return Err(RequestError::internal_server_error());
+            }
+        }
+        self.upload_chunks_release(account_id.document_id(), &session.inner.chunks, None)
+            .await?;
+
+        Ok(ResumableUploadResponse::Cancelled)
+    }
+
+    async fn upload_session(
+        &self,
+        account_id: Id,
+        upload_id: Id,
+        access_token: &AccessToken,
+    ) -> Result<HashedValue<UploadSession>, RequestError> {
+        let document_id = account_id.document_id();
+        if !access_token.is_member(document_id) {
+            return Err(RequestError::forbidden());
+        }
+
+        let now = now();
+        let mut result = None;
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey {
+                        account_id: document_id,
+                        collection: 0,
+                        document_id: 0,
+                        class: ValueClass::UploadSession {
+                            expires: now,
+                            id: 0,
+                        },
+                    },
+                    ValueKey {
+                        account_id: document_id,
+                        collection: 0,
+                        document_id: 0,
+                        class: ValueClass::UploadSession {
+                            expires: u64::MAX,
+                            id: u64::MAX,
+                        },
+                    },
+                )
+                .ascending(),
+                |_, value| {
+                    let session = HashedValue::<UploadSession>::deserialize(value)?;
+                    if session.inner.id == upload_id.id() && session.inner.expires > now {
+                        result = Some(session);
+                        Ok(false)
+                    } else {
+                        Ok(true)
+                    }
+                },
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "upload_session",
+                    account_id = document_id,
+                    error = ?err,
+                    "Failed to obtain upload session.");
+                RequestError::internal_server_error()
+            })?;
+
+        result.ok_or_else(RequestError::not_found)
+    }
+
+    /// Releases the quota reserved by the chunks of an upload session, the
+    /// chunks are then removed by the blob purge.
+    async fn upload_chunks_release(
+        &self,
+        account_id: u32,
+        chunks: &[UploadChunk],
+        keep: Option<&BlobHash>,
+    ) -> Result<(), RequestError> {
+        let mut batch = BatchBuilder::new();
+        batch.with_account_id(account_id);
+        for chunk in chunks {
+            if keep != Some(&chunk.hash) {
+                batch.clear(BlobOp::Reserve {
+                    hash: chunk.hash.clone(),
+                    until: chunk.until,
+                });
+            }
+        }
+        if !batch.is_empty() {
+            self.write_batch(batch)
+                .await
+                .map_err(|_| RequestError::internal_server_error())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UploadSession {
+    pub fn offset(&self) -> usize {
+        self.chunks.iter().map(|chunk| chunk.size).sum()
+    }
+
+    fn into_response(self, account_id: Id) -> UploadSessionResponse {
+        UploadSessionResponse {
+            account_id,
+            upload_id: Id::from(self.id),
+            offset: self.offset(),
+            size: self.size,
+            expires: UTCDate::from_timestamp(self.expires as i64),
+            c_type: self.content_type,
+        }
+    }
+}
+
+impl Deserialize for UploadSession {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        Bincode::<UploadSession>::deserialize(bytes).map(|session| session.inner)
+    }
+}
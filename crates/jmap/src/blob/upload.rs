@@ -211,21 +211,37 @@ impl JMAP {
         }
 
         // Enforce quota
-        let used = self
-            .store
-            .blob_quota(account_id.document_id())
-            .await
-            .map_err(|err| {
-                tracing::error!(event = "error",
+        self.check_upload_quota(account_id.document_id(), data.len(), &access_token)
+            .await?;
+
+        Ok(UploadResponse {
+            account_id,
+            blob_id: self
+                .put_blob(account_id.document_id(), data, true)
+                .await
+                .map_err(|_| RequestError::internal_server_error())?,
+            c_type: content_type.to_string(),
+            size: data.len(),
+        })
+    }
+
+    pub async fn check_upload_quota(
+        &self,
+        account_id: u32,
+        size: usize,
+        access_token: &AccessToken,
+    ) -> Result<(), RequestError> {
+        let used = self.store.blob_quota(account_id).await.map_err(|err| {
+            tracing::error!(event = "error",
                     context = "blob_store",
-                    account_id = account_id.document_id(),
+                    account_id = account_id,
                     error = ?err,
                     "Failed to obtain blob quota");
-                RequestError::internal_server_error()
-            })?;
+            RequestError::internal_server_error()
+        })?;
 
         if ((self.config.upload_tmp_quota_size > 0
-            && used.bytes + data.len() > self.config.upload_tmp_quota_size)
+            && used.bytes + size > self.config.upload_tmp_quota_size)
             || (self.config.upload_tmp_quota_amount > 0
                 && used.count + 1 > self.config.upload_tmp_quota_amount))
             && !access_token.is_super_user()
@@ -244,15 +260,7 @@ impl JMAP {
             return err;
         }
 
-        Ok(UploadResponse {
-            account_id,
-            blob_id: self
-                .put_blob(account_id.document_id(), data, true)
-                .await
-                .map_err(|_| RequestError::internal_server_error())?,
-            c_type: content_type.to_string(),
-            size: data.len(),
-        })
+        Ok(())
     }
 
     #[allow(clippy::blocks_in_conditions)]
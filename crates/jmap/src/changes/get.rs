@@ -67,6 +67,11 @@ impl JMAP {
 
                 return Err(MethodError::CannotCalculateChanges);
             }
+            RequestArguments::ShareNotification => {
+                access_token.assert_is_member(request.account_id)?;
+
+                Collection::ShareNotification
+            }
         };
 
         let max_changes = if self.config.changes_max_results > 0
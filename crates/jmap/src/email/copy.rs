@@ -435,6 +435,7 @@ impl JMAP {
             .custom(EmailIndexBuilder::set(metadata))
             .custom(changes);
 
+        self.track_domain_usage(&mut batch).await?;
         self.store.write(batch.build()).await.map_err(|err| {
             tracing::error!(
                     event = "error",
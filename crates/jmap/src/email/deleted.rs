@@ -0,0 +1,402 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::deleted::{
+        DeletedEmail, DeletedEmailGetRequest, DeletedEmailGetResponse, DeletedEmailRestoreRequest,
+        DeletedEmailRestoreResponse,
+    },
+    types::{date::UTCDate, id::Id, keyword::Keyword, state::StateChange, type_state::DataType},
+};
+use mail_parser::{HeaderName, MessageParser};
+use store::{
+    write::{assert::HashedValue, now, BatchBuilder, Bincode, BlobOp, ValueClass},
+    Deserialize, IterateParams, Serialize, ValueKey,
+};
+use utils::{map::vec_map::VecMap, BlobHash};
+
+use crate::{auth::AccessToken, mailbox::INBOX_ID, IngestError, JMAP};
+
+use super::{ingest::IngestEmail, metadata::MessageMetadata};
+
+/// Message that was expunged or destroyed and that can be restored
+/// until its hold period expires.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct DeletedItem {
+    pub id: u64,
+    pub expires: u64,
+    pub deleted_at: u64,
+    pub blob_hash: BlobHash,
+    pub size: usize,
+    pub received_at: u64,
+    pub mailbox_ids: Vec<u32>,
+    pub keywords: Vec<String>,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+}
+
+impl DeletedItem {
+    pub fn new(
+        id: u64,
+        hold: Duration,
+        metadata: &MessageMetadata,
+        mailbox_ids: Vec<u32>,
+        keywords: Vec<String>,
+    ) -> Self {
+        let mut subject = None;
+        let mut from = None;
+        if let Some(part) = metadata.contents.parts.first() {
+            for header in &part.headers {
+                match &header.name {
+                    HeaderName::Subject if subject.is_none() => {
+                        subject = header.value.as_text().map(|s| s.to_string());
+                    }
+                    HeaderName::From if from.is_none() => {
+                        from = header
+                            .value
+                            .as_address()
+                            .and_then(|addr| addr.first())
+                            .and_then(|addr| addr.address.as_ref())
+                            .map(|addr| addr.to_string());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let deleted_at = now();
+
+        DeletedItem {
+            id,
+            expires: deleted_at + hold.as_secs(),
+            deleted_at,
+            blob_hash: metadata.blob_hash.clone(),
+            size: metadata.size,
+            received_at: metadata.received_at,
+            mailbox_ids,
+            keywords,
+            subject,
+            from,
+        }
+    }
+
+    /// Adds the deleted item to a batch, reserving its blob until the item expires.
+    pub fn write(self, batch: &mut BatchBuilder) {
+        batch
+            .set(
+                BlobOp::Reserve {
+                    hash: self.blob_hash.clone(),
+                    until: self.expires,
+                },
+                0u32.serialize(),
+            )
+            .set(
+                ValueClass::DeletedItem {
+                    expires: self.expires,
+                    id: self.id,
+                },
+                Bincode::new(self).serialize(),
+            );
+    }
+}
+
+impl JMAP {
+    pub async fn deleted_email_get(
+        &self,
+        request: DeletedEmailGetRequest,
+    ) -> Result<DeletedEmailGetResponse, MethodError> {
+        let account_id = request.account_id.document_id();
+        let items = self.deleted_items(account_id).await?;
+        let mut response = DeletedEmailGetResponse {
+            account_id: request.account_id,
+            list: Vec::with_capacity(items.len()),
+            not_found: vec![],
+        };
+
+        if let Some(ids) = request.ids {
+            if ids.len() > self.config.get_max_objects {
+                return Err(MethodError::RequestTooLarge);
+            }
+            for id in ids {
+                if let Some(item) = items.iter().find(|item| item.inner.id == u64::from(id)) {
+                    response.list.push(item.inner.clone().into());
+                } else {
+                    response.not_found.push(id);
+                }
+            }
+        } else {
+            response
+                .list
+                .extend(items.into_iter().map(|item| item.inner.into()));
+        }
+
+        Ok(response)
+    }
+
+    pub async fn deleted_email_restore(
+        &self,
+        request: DeletedEmailRestoreRequest,
+        access_token: &AccessToken,
+    ) -> Result<DeletedEmailRestoreResponse, MethodError> {
+        if request.ids.len() > self.config.set_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+
+        let account_id = request.account_id.document_id();
+        let account_quota = self.get_quota(access_token, account_id).await?;
+        let mut response = DeletedEmailRestoreResponse {
+            account_id: request.account_id,
+            restored: VecMap::new(),
+            not_restored: VecMap::new(),
+        };
+
+        for id in request.ids {
+            match self
+                .deleted_item_restore(account_id, account_quota, id.into())
+                .await?
+            {
+                Ok(email_id) => {
+                    response.restored.append(id, email_id);
+                }
+                Err(err) => {
+                    response.not_restored.append(id, err);
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Returns the deleted items of an account that have not expired yet.
+    pub async fn deleted_items(
+        &self,
+        account_id: u32,
+    ) -> Result<Vec<HashedValue<DeletedItem>>, MethodError> {
+        let now = now();
+        let mut items = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey {
+                        account_id,
+                        collection: 0,
+                        document_id: 0,
+                        class: ValueClass::DeletedItem {
+                            expires: now,
+                            id: 0,
+                        },
+                    },
+                    ValueKey {
+                        account_id,
+                        collection: 0,
+                        document_id: 0,
+                        class: ValueClass::DeletedItem {
+                            expires: u64::MAX,
+                            id: u64::MAX,
+                        },
+                    },
+                )
+                .ascending(),
+                |_, value| {
+                    let item = HashedValue::<DeletedItem>::deserialize(value)?;
+                    if item.inner.expires > now {
+                        items.push(item);
+                    }
+                    Ok(true)
+                },
+            )
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                    event = "error",
+                    context = "deleted_items",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to obtain deleted items.");
+                MethodError::ServerPartialFail
+            })?;
+
+        Ok(items)
+    }
+
+    /// Restores a deleted item to the mailboxes it was removed from, or to the
+    /// Inbox when none of them exist anymore.
+    pub async fn deleted_item_restore(
+        &self,
+        account_id: u32,
+        account_quota: i64,
+        id: u64,
+    ) -> Result<Result<Id, SetError>, MethodError> {
+        let item = if let Some(item) = self
+            .deleted_items(account_id)
+            .await?
+            .into_iter()
+            .find(|item| item.inner.id == id)
+        {
+            item
+        } else {
+            return Ok(Err(SetError::not_found()));
+        };
+        let item_class = ValueClass::DeletedItem {
+            expires: item.inner.expires,
+            id,
+        };
+
+        // Remove the entry first to avoid restoring the same message twice
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .assert_value(item_class.clone(), &item)
+            .clear(item_class.clone());
+        match self.store.write(batch.build()).await {
+            Ok(_) => (),
+            Err(store::Error::AssertValueFailed) => {
+                return Ok(Err(SetError::not_found()));
+            }
+            Err(err) => {
+                tracing::error!(
+                    event = "error",
+                    context = "deleted_item_restore",
+                    account_id = account_id,
+                    error = ?err,
+                    "Failed to write batch.");
+                return Err(MethodError::ServerPartialFail);
+            }
+        }
+        let item = item.inner;
+
+        let result = match self.get_blob(&item.blob_hash, 0..usize::MAX).await? {
+            Some(raw_message) => {
+                let valid_mailbox_ids = self.mailbox_get_or_create(account_id).await?;
+                let mut mailbox_ids = item
+                    .mailbox_ids
+                    .iter()
+                    .copied()
+                    .filter(|mailbox_id| valid_mailbox_ids.contains(*mailbox_id))
+                    .collect::<Vec<_>>();
+                if mailbox_ids.is_empty() {
+                    mailbox_ids.push(INBOX_ID);
+                }
+
+                match self
+                    .email_ingest(IngestEmail {
+                        raw_message: &raw_message,
+                        message: MessageParser::new().parse(&raw_message),
+                        account_id,
+                        account_quota,
+                        mailbox_ids,
+                        keywords: item
+                            .keywords
+                            .iter()
+                            .map(|keyword| Keyword::from(keyword.clone()))
+                            .collect(),
+                        received_at: item.received_at.into(),
+                        skip_duplicates: false,
+                        encrypt: false,
+                    })
+                    .await
+                {
+                    Ok(email) => Ok(email),
+                    Err(IngestError::OverQuota) => Err(Some(
+                        SetError::new(SetErrorType::OverQuota)
+                            .with_description("You have exceeded your disk quota."),
+                    )),
+                    Err(IngestError::Permanent { reason, .. }) => Err(Some(
+                        SetError::new(SetErrorType::InvalidEmail).with_description(reason),
+                    )),
+                    Err(IngestError::Temporary) => Err(None),
+                }
+            }
+            None => Err(Some(
+                SetError::new(SetErrorType::BlobNotFound)
+                    .with_description("Message contents are no longer available."),
+            )),
+        };
+
+        match result {
+            Ok(email) => {
+                // The message is linked again, release the reserved blob
+                let mut batch = BatchBuilder::new();
+                batch.with_account_id(account_id).clear(BlobOp::Reserve {
+                    hash: item.blob_hash,
+                    until: item.expires,
+                });
+                self.write_batch(batch).await?;
+
+                self.broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, email.change_id)
+                        .with_change(DataType::Mailbox, email.change_id)
+                        .with_change(DataType::Thread, email.change_id),
+                )
+                .await;
+
+                Ok(Ok(email.id))
+            }
+            Err(err) => {
+                // Put the entry back so that it can be restored later
+                let mut batch = BatchBuilder::new();
+                batch.with_account_id(account_id);
+                item.write(&mut batch);
+                self.write_batch(batch).await?;
+
+                err.map(Err).ok_or(MethodError::ServerPartialFail)
+            }
+        }
+    }
+}
+
+impl From<DeletedItem> for DeletedEmail {
+    fn from(item: DeletedItem) -> Self {
+        DeletedEmail {
+            id: Id::new(item.id),
+            mailbox_ids: item
+                .mailbox_ids
+                .into_iter()
+                .map(|mailbox_id| (Id::from(mailbox_id), true))
+                .collect(),
+            keywords: item
+                .keywords
+                .into_iter()
+                .map(|keyword| (Keyword::from(keyword), true))
+                .collect(),
+            size: item.size,
+            subject: item.subject,
+            from: item.from,
+            received_at: UTCDate::from_timestamp(item.received_at as i64),
+            deleted_at: UTCDate::from_timestamp(item.deleted_at as i64),
+            expires_at: UTCDate::from_timestamp(item.expires as i64),
+        }
+    }
+}
+
+impl Deserialize for DeletedItem {
+    fn deserialize(bytes: &[u8]) -> store::Result<Self> {
+        Bincode::<DeletedItem>::deserialize(bytes).map(|item| item.inner)
+    }
+}
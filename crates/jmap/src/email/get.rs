@@ -39,7 +39,10 @@ use jmap_proto::{
 use mail_parser::HeaderName;
 use store::{write::Bincode, BlobClass};
 
-use crate::{auth::AccessToken, email::headers::HeaderToValue, mailbox::UidMailbox, JMAP};
+use crate::{
+    auth::AccessToken, email::headers::HeaderToValue, mailbox::UidMailbox,
+    sharing::seen::apply_shared_seen, JMAP,
+};
 
 use super::{
     body::{ToBodyPart, TruncateBody},
@@ -126,6 +129,15 @@ impl JMAP {
                 })
                 .collect()
         };
+        let shared_seen =
+            if !access_token.is_member(account_id) && properties.contains(&Property::Keywords) {
+                self.seen_messages(access_token, account_id)
+                    .await?
+                    .unwrap_or_default()
+                    .into()
+            } else {
+                None
+            };
         let mut response = GetResponse {
             account_id: request.account_id.into(),
             state: self.get_state(account_id, Collection::Email).await?.into(),
@@ -253,7 +265,13 @@ impl JMAP {
                                 &Property::Keywords,
                             )
                             .await?
-                            .map(|keywords| {
+                            .map(|mut keywords| {
+                                if let Some(seen) = &shared_seen {
+                                    apply_shared_seen(
+                                        &mut keywords,
+                                        seen.contains(id.document_id()),
+                                    );
+                                }
                                 let mut obj = Object::with_capacity(keywords.len());
                                 for keyword in keywords {
                                     obj.append(Property::_T(keyword.to_string()), true);
@@ -21,19 +21,23 @@
  * for more details.
 */
 
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Instant};
 
 use jmap_proto::types::{keyword::Keyword, property::Property};
 use mail_parser::{
     decoders::html::html_to_text,
     parsers::{fields::thread::thread_name, preview::preview_text},
     Addr, Address, GetHeader, Group, Header, HeaderName, HeaderValue, Message, MessagePart,
-    PartType,
+    MimeHeaders, PartType,
 };
 use nlp::language::Language;
 use store::{
     backend::MAX_TOKEN_LENGTH,
-    fts::{index::FtsDocument, Field},
+    fts::{
+        index::FtsDocument,
+        office::{extract_text, DocumentType, ExtractLimits},
+        Field,
+    },
     write::{
         BatchBuilder, Bincode, BlobOp, DirectoryClass, IntoOperations, F_BITMAP, F_CLEAR, F_INDEX,
         F_VALUE,
@@ -71,7 +75,119 @@ pub(super) trait IndexMessage {
 }
 
 pub trait IndexMessageText<'x>: Sized {
-    fn index_message(self, message: &'x Message<'x>) -> Self;
+    fn index_message(self, message: &'x Message<'x>, documents: Vec<ExtractedText>) -> Self;
+}
+
+/// Office document or RTF part of a message, copied so its text can be
+/// extracted on a blocking thread.
+pub struct ExtractDocument {
+    bytes: Vec<u8>,
+    typ: DocumentType,
+    language: Language,
+    is_body: bool,
+}
+
+pub struct ExtractedText {
+    text: String,
+    language: Language,
+    is_body: bool,
+}
+
+impl ExtractDocument {
+    /// Returns the parts of a message, including those of attached messages,
+    /// with text to extract.
+    pub fn collect(message: &Message<'_>, limits: &ExtractLimits) -> Vec<ExtractDocument> {
+        let mut documents = Vec::new();
+        let mut language = Language::Unknown;
+
+        for (part_id, part) in message.parts.iter().take(MAX_MESSAGE_PARTS).enumerate() {
+            let part_language = part.language().unwrap_or(language);
+            if part_id == 0 {
+                language = part_language;
+            }
+
+            if let PartType::Message(nested_message) = &part.body {
+                let nested_message_language = nested_message
+                    .root_part()
+                    .language()
+                    .unwrap_or(Language::Unknown);
+                for sub_part in nested_message.parts.iter().take(MAX_MESSAGE_PARTS) {
+                    documents.extend(ExtractDocument::new(
+                        sub_part,
+                        sub_part.language().unwrap_or(nested_message_language),
+                        false,
+                        limits,
+                    ));
+                }
+            } else {
+                documents.extend(ExtractDocument::new(
+                    part,
+                    part_language,
+                    message.text_body.contains(&part_id) || message.html_body.contains(&part_id),
+                    limits,
+                ));
+            }
+        }
+
+        documents
+    }
+
+    fn new(
+        part: &MessagePart<'_>,
+        language: Language,
+        is_body: bool,
+        limits: &ExtractLimits,
+    ) -> Option<Self> {
+        let bytes = match &part.body {
+            PartType::Text(text) if is_rtf(part) => text.as_bytes(),
+            PartType::Binary(bytes) | PartType::InlineBinary(bytes) => bytes.as_ref(),
+            _ => return None,
+        };
+        if bytes.len() > limits.max_size {
+            return None;
+        }
+
+        DocumentType::detect(
+            part.content_type()
+                .map(|ct| format!("{}/{}", ct.ctype(), ct.subtype().unwrap_or_default()))
+                .as_deref(),
+            part.attachment_name(),
+        )
+        .map(|typ| ExtractDocument {
+            bytes: bytes.to_vec(),
+            typ,
+            language,
+            is_body,
+        })
+    }
+
+    /// Extracts the text of all the documents of a message within a single
+    /// `timeout` budget. This is CPU bound and should not run on async workers.
+    pub fn extract(documents: Vec<ExtractDocument>, limits: &ExtractLimits) -> Vec<ExtractedText> {
+        let deadline = Instant::now() + limits.timeout;
+        documents
+            .into_iter()
+            .filter_map(|document| {
+                extract_text(&document.bytes, document.typ, limits, deadline).map(|text| {
+                    ExtractedText {
+                        text,
+                        language: document.language,
+                        is_body: document.is_body,
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+// mail-parser returns text/rtf as a text part, its markup must not be indexed
+fn is_rtf(part: &MessagePart<'_>) -> bool {
+    part.content_type().is_some_and(|ct| {
+        ct.ctype().eq_ignore_ascii_case("text")
+            && ct
+                .subtype()
+                .is_some_and(|subtype| subtype.eq_ignore_ascii_case("rtf"))
+    })
 }
 
 impl IndexMessage for BatchBuilder {
@@ -286,7 +402,7 @@ impl IndexMessage for BatchBuilder {
 }
 
 impl<'x> IndexMessageText<'x> for FtsDocument<'x, HeaderName<'x>> {
-    fn index_message(mut self, message: &'x Message<'x>) -> Self {
+    fn index_message(mut self, message: &'x Message<'x>, documents: Vec<ExtractedText>) -> Self {
         let mut language = Language::Unknown;
 
         for (part_id, part) in message.parts.iter().take(MAX_MESSAGE_PARTS).enumerate() {
@@ -346,6 +462,7 @@ impl<'x> IndexMessageText<'x> for FtsDocument<'x, HeaderName<'x>> {
             }
 
             match &part.body {
+                PartType::Text(_) if is_rtf(part) => {}
                 PartType::Text(text) => {
                     if message.text_body.contains(&part_id) || message.html_body.contains(&part_id)
                     {
@@ -378,6 +495,7 @@ impl<'x> IndexMessageText<'x> for FtsDocument<'x, HeaderName<'x>> {
                     for sub_part in nested_message.parts.iter().take(MAX_MESSAGE_PARTS) {
                         let language = sub_part.language().unwrap_or(nested_message_language);
                         match &sub_part.body {
+                            PartType::Text(_) if is_rtf(sub_part) => {}
                             PartType::Text(text) => {
                                 self.index(Field::Attachment, text.as_ref(), language);
                             }
@@ -391,6 +509,20 @@ impl<'x> IndexMessageText<'x> for FtsDocument<'x, HeaderName<'x>> {
                 _ => {}
             }
         }
+
+        // Text extracted from office documents and RTF parts
+        for document in documents {
+            self.index(
+                if document.is_body {
+                    Field::Body
+                } else {
+                    Field::Attachment
+                },
+                document.text,
+                document.language,
+            );
+        }
+
         self
     }
 }
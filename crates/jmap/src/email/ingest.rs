@@ -23,6 +23,7 @@
 
 use std::{borrow::Cow, time::Duration};
 
+use directory::QueryBy;
 use jmap_proto::{
     object::Object,
     types::{
@@ -100,6 +101,25 @@ impl JMAP {
             return Err(IngestError::OverQuota);
         }
 
+        // Check domain quotas
+        if let Some(principal) = self
+            .directory
+            .query(QueryBy::Id(params.account_id), false)
+            .await
+            .map_err(|_| IngestError::Temporary)?
+        {
+            for domain in principal.domains() {
+                if self
+                    .directory
+                    .is_domain_over_quota(&domain, raw_message_len as u64)
+                    .await
+                    .map_err(|_| IngestError::Temporary)?
+                {
+                    return Err(IngestError::OverQuota);
+                }
+            }
+        }
+
         // Parse message
         let mut raw_message = Cow::from(params.raw_message);
         let mut message = params.message.ok_or_else(|| IngestError::Permanent {
@@ -371,6 +391,9 @@ impl JMAP {
                 ),
                 blob_id.hash.clone(),
             );
+        self.track_domain_usage(&mut batch)
+            .await
+            .map_err(|_| IngestError::Temporary)?;
         self.store.write(batch.build()).await.map_err(|err| {
             tracing::error!(
                 event = "error",
@@ -25,6 +25,7 @@ pub mod body;
 pub mod cache;
 pub mod copy;
 pub mod crypto;
+pub mod deleted;
 pub mod get;
 pub mod headers;
 pub mod import;
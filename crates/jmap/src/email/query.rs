@@ -37,7 +37,7 @@ use store::{
     ValueKey,
 };
 
-use crate::{auth::AccessToken, JMAP};
+use crate::{auth::AccessToken, sharing::seen::keyword_filter, JMAP};
 
 impl JMAP {
     pub async fn email_query(
@@ -226,11 +226,11 @@ impl JMAP {
                             filters.push(query::Filter::End);
                         }
                         Filter::HasKeyword(keyword) => {
-                            filters.push(query::Filter::is_in_bitmap(Property::Keywords, keyword))
+                            filters.push(keyword_filter(access_token, account_id, keyword))
                         }
                         Filter::NotKeyword(keyword) => {
                             filters.push(query::Filter::Not);
-                            filters.push(query::Filter::is_in_bitmap(Property::Keywords, keyword));
+                            filters.push(keyword_filter(access_token, account_id, keyword));
                             filters.push(query::Filter::End);
                         }
                         Filter::HasAttachment(has_attach) => {
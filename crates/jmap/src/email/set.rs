@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use std::{borrow::Cow, collections::HashMap, slice::IterMut};
+use std::{borrow::Cow, collections::HashMap, slice::IterMut, time::Duration};
 
 use jmap_proto::{
     error::{
@@ -60,10 +60,12 @@ use store::{
 };
 
 use crate::{
-    auth::AccessToken, mailbox::UidMailbox, services::housekeeper::Event, IngestError, JMAP,
+    auth::AccessToken, mailbox::UidMailbox, services::housekeeper::Event,
+    sharing::seen::apply_shared_seen, IngestError, JMAP,
 };
 
 use super::{
+    deleted::DeletedItem,
     headers::{BuildHeader, ValueToHeader},
     index::EmailIndexBuilder,
     ingest::IngestEmail,
@@ -106,6 +108,18 @@ impl JMAP {
         } else {
             (None, None, None)
         };
+        let shared_seen = if !access_token.is_member(account_id) {
+            (
+                self.seen_messages(access_token, account_id)
+                    .await?
+                    .unwrap_or_default(),
+                self.shared_messages(access_token, account_id, Acl::ReadItems)
+                    .await?,
+            )
+                .into()
+        } else {
+            None
+        };
 
         let will_destroy = request.unwrap_destroy();
 
@@ -770,7 +784,7 @@ impl JMAP {
 
             // Obtain current keywords and mailboxes
             let document_id = id.document_id();
-            let (mut mailboxes, mut keywords) = if let (Some(mailboxes), Some(keywords)) = (
+            let (mailboxes, mut keywords) = if let (Some(mailboxes), Some(keywords)) = (
                 self.get_property::<HashedValue<Vec<UidMailbox>>>(
                     account_id,
                     Collection::Email,
@@ -786,12 +800,19 @@ impl JMAP {
                 )
                 .await?,
             ) {
-                (TagManager::new(mailboxes), TagManager::new(keywords))
+                (mailboxes, keywords)
             } else {
                 response.not_updated.append(id, SetError::not_found());
                 continue 'update;
             };
 
+            // Sharees see their own seen state rather than the owner's
+            let owner_seen = shared_seen.as_ref().map(|(seen, _)| {
+                apply_shared_seen(&mut keywords.inner, seen.contains(document_id))
+            });
+            let (mut mailboxes, mut keywords) =
+                (TagManager::new(mailboxes), TagManager::new(keywords));
+
             // Prepare write batch
             let mut batch = BatchBuilder::new();
             batch
@@ -850,7 +871,18 @@ impl JMAP {
                 }
             }
 
-            if !mailboxes.has_changes() && !keywords.has_changes() {
+            // Changes to the seen state of sharees are stored separately
+            let mut shared_seen_change = None;
+            if let Some(owner_seen) = owner_seen {
+                if keywords
+                    .changed_tags()
+                    .any(|keyword| keyword == &Keyword::Seen)
+                {
+                    shared_seen_change = keywords.detach(&Keyword::Seen, owner_seen).into();
+                }
+            }
+
+            if !mailboxes.has_changes() && !keywords.has_changes() && shared_seen_change.is_none() {
                 response.not_updated.append(
                     id,
                     SetError::invalid_properties()
@@ -896,6 +928,34 @@ impl JMAP {
                 batch.value(Property::Cid, changes.change_id, F_VALUE);
             }
 
+            // Process seen state of sharees
+            if let Some(is_seen) = shared_seen_change {
+                if matches!(&shared_seen, Some((_, can_read_message_ids)) if !can_read_message_ids.contains(document_id))
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::forbidden()
+                            .with_description("You are not allowed to read this message."),
+                    );
+                    continue 'update;
+                }
+                for mailbox_id in mailboxes.current() {
+                    changed_mailboxes.insert(mailbox_id.mailbox_id);
+                }
+                self.shared_seen_batch(
+                    &mut batch,
+                    account_id,
+                    document_id,
+                    access_token.primary_id(),
+                    is_seen,
+                )
+                .await?;
+                if changes.change_id == u64::MAX {
+                    changes.change_id = self.assign_change_id(account_id).await?;
+                }
+                batch.value(Property::Cid, changes.change_id, F_VALUE);
+            }
+
             // Process mailboxes
             if mailboxes.has_changes() {
                 // Make sure the message is at least in one mailbox
@@ -1080,6 +1140,25 @@ impl JMAP {
         account_id: u32,
         document_id: u32,
     ) -> Result<Result<ChangeLogBuilder, SetError>, MethodError> {
+        self.email_delete_with_hold(account_id, document_id, self.config.deleted_items_hold)
+            .await
+    }
+
+    /// Deletes a message, keeping it as a recoverable deleted item for the
+    /// duration of the hold period, if any.
+    pub async fn email_delete_with_hold(
+        &self,
+        account_id: u32,
+        document_id: u32,
+        hold: Option<Duration>,
+    ) -> Result<Result<ChangeLogBuilder, SetError>, MethodError> {
+        // Messages of accounts under legal hold cannot be expunged
+        if self.is_legal_hold(account_id).await? {
+            return Ok(Err(SetError::forbidden().with_description(
+                "Account is under legal hold, messages cannot be deleted.",
+            )));
+        }
+
         // Create batch
         let mut batch = BatchBuilder::new();
         let mut changes = ChangeLogBuilder::with_change_id(0);
@@ -1122,6 +1201,11 @@ impl JMAP {
             debug_assert!(mailbox_id.uid != 0);
             changes.log_child_update(Collection::Mailbox, mailbox_id.mailbox_id);
         }
+        let deleted_mailbox_ids = mailboxes
+            .inner
+            .iter()
+            .map(|mailbox_id| mailbox_id.mailbox_id)
+            .collect::<Vec<_>>();
         batch.assert_value(Property::MailboxIds, &mailboxes).value(
             Property::MailboxIds,
             mailboxes.inner,
@@ -1129,6 +1213,7 @@ impl JMAP {
         );
 
         // Remove keywords
+        let deleted_keywords;
         if let Some(keywords) = self
             .get_property::<HashedValue<Vec<Keyword>>>(
                 account_id,
@@ -1138,6 +1223,11 @@ impl JMAP {
             )
             .await?
         {
+            deleted_keywords = keywords
+                .inner
+                .iter()
+                .map(|keyword| keyword.to_string())
+                .collect();
             batch.assert_value(Property::Keywords, &keywords).value(
                 Property::Keywords,
                 keywords.inner,
@@ -1154,6 +1244,23 @@ impl JMAP {
             return Ok(Err(SetError::not_found()));
         };
 
+        // Remove seen state of sharees
+        if let Some(seen_by) = self
+            .get_property::<HashedValue<Vec<u32>>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::SeenBy,
+            )
+            .await?
+        {
+            batch.assert_value(Property::SeenBy, &seen_by).value(
+                Property::SeenBy,
+                seen_by.inner,
+                F_VALUE | F_BITMAP | F_CLEAR,
+            );
+        }
+
         // Remove threadIds
         let mut delete_thread_id = None;
         if let Some(thread_id) = self
@@ -1220,6 +1327,16 @@ impl JMAP {
             )
             .await?
         {
+            if let Some(hold) = hold {
+                DeletedItem::new(
+                    self.generate_snowflake_id()?,
+                    hold,
+                    &metadata.inner,
+                    deleted_mailbox_ids,
+                    deleted_keywords,
+                )
+                .write(&mut batch);
+            }
             batch.custom(EmailIndexBuilder::clear(metadata.inner));
         } else {
             tracing::debug!(
@@ -1240,6 +1357,7 @@ impl JMAP {
         }
 
         // Commit batch
+        self.track_domain_usage(&mut batch).await?;
         match self.store.write(batch.build()).await {
             Ok(_) => (),
             Err(store::Error::AssertValueFailed) => {
@@ -1357,6 +1475,20 @@ impl<
         !self.added.is_empty() || !self.removed.is_empty()
     }
 
+    /// Drops any pending change to a tag and restores its stored state,
+    /// returning whether the tag was set after applying the changes.
+    pub fn detach(&mut self, tag: &T, is_stored: bool) -> bool {
+        let is_set = self.current.inner.contains(tag);
+        self.added.retain(|t| t != tag);
+        self.removed.retain(|t| t != tag);
+        if is_set && !is_stored {
+            self.current.inner.retain(|t| t != tag);
+        } else if !is_set && is_stored {
+            self.current.inner.push(tag.clone());
+        }
+        is_set
+    }
+
     pub fn update_batch(self, batch: &mut BatchBuilder, property: Property) {
         let property = u8::from(property);
 
@@ -23,11 +23,19 @@
 
 use std::{collections::hash_map::RandomState, fmt::Display, sync::Arc, time::Duration};
 
+use crate::sieve::{
+    extlists::{ADDRBOOK_PERSONAL, LOOKUP_LIST_PREFIX},
+    global::GlobalScripts,
+    notify::PUSH_NOTIFY_URI,
+};
 use ::sieve::{Compiler, Runtime};
 use api::session::BaseCapabilities;
 use auth::{oauth::OAuthCode, rate_limit::ConcurrencyLimiters, AccessToken};
 use dashmap::DashMap;
-use directory::{Directories, Directory, QueryBy};
+use directory::{
+    core::{retention::RetentionRule, roles::Roles},
+    Directories, Directory, QueryBy,
+};
 use email::cache::Threads;
 use jmap_proto::{
     error::method::MethodError,
@@ -41,12 +49,16 @@ use mail_parser::HeaderName;
 use nlp::language::Language;
 use services::{
     delivery::spawn_delivery_manager,
+    fsck::FsckStatus,
     housekeeper::{self, init_housekeeper, spawn_housekeeper},
+    index::ReindexStatus,
     state::{self, init_state_manager, spawn_state_manager},
+    webhook::spawn_webhook_manager,
 };
+use sharing::public::PublicFolder;
 use smtp::core::SMTP;
 use store::{
-    fts::FtsFilter,
+    fts::{office::ExtractLimits, FtsFilter},
     query::{sort::Pagination, Comparator, Filter, ResultSet, SortedResultSet},
     roaring::RoaringBitmap,
     write::{
@@ -62,6 +74,7 @@ use utils::{
     lru_cache::{LruCache, LruCached},
     map::ttl_dashmap::{TtlDashMap, TtlMap},
     snowflake::SnowflakeIdGenerator,
+    webhooks::WebhookEvent,
     UnwrapFailure,
 };
 
@@ -71,11 +84,14 @@ pub mod blob;
 pub mod changes;
 pub mod email;
 pub mod identity;
+pub mod list;
 pub mod mailbox;
+pub mod mdn;
 pub mod principal;
 pub mod push;
 pub mod quota;
 pub mod services;
+pub mod sharing;
 pub mod sieve;
 pub mod submission;
 pub mod thread;
@@ -106,7 +122,12 @@ pub struct JMAP {
     pub cache_threads: LruCache<u32, Arc<Threads>>,
 
     pub sieve_compiler: Compiler,
+    pub sieve_global_scripts: GlobalScripts,
     pub sieve_runtime: Runtime<()>,
+
+    pub audit_head: tokio::sync::Mutex<Option<(u64, [u8; 32])>>,
+    pub fsck_status: store::parking_lot::Mutex<FsckStatus>,
+    pub reindex_status: store::parking_lot::Mutex<ReindexStatus>,
 }
 
 pub struct Config {
@@ -135,8 +156,13 @@ pub struct Config {
     pub mail_parse_max_items: usize,
     pub mail_max_size: usize,
 
+    pub fts_extract_limits: ExtractLimits,
+
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
+    pub sieve_notify_push_uri: String,
+    pub sieve_notify_rate: Rate,
+    pub sieve_addrbook_expiry: u64,
 
     pub session_cache_ttl: Duration,
     pub rate_authenticated: Rate,
@@ -168,6 +194,23 @@ pub struct Config {
 
     pub principal_allow_lookups: bool,
 
+    pub scim_token: Option<String>,
+    pub roles: Roles,
+
+    pub audit_enable: bool,
+    pub audit_key: [u8; 32],
+    pub audit_retention: Option<Duration>,
+
+    pub list_url: Option<String>,
+    pub list_confirm_expiry: Duration,
+    pub list_held_expiry: Duration,
+    pub list_outbox_expiry: Duration,
+
+    pub retention_rules: Vec<RetentionRule>,
+    pub deleted_items_hold: Option<Duration>,
+
+    pub public_folders: Vec<PublicFolder>,
+
     pub capabilities: BaseCapabilities,
 }
 
@@ -185,6 +228,7 @@ impl JMAP {
         directories: &Directories,
         servers: &mut Servers,
         delivery_rx: mpsc::Receiver<DeliveryEvent>,
+        webhook_rx: mpsc::Receiver<WebhookEvent>,
         smtp: Arc<SMTP>,
     ) -> Result<Arc<Self>, String> {
         // Init state manager and housekeeper
@@ -195,6 +239,67 @@ impl JMAP {
             .unwrap_or(32)
             .next_power_of_two() as usize;
         let capacity = config.property("cache.capacity")?.unwrap_or(100);
+        let ext_lists = [ADDRBOOK_PERSONAL.to_string()]
+            .into_iter()
+            .chain(
+                smtp.shared
+                    .lookup_stores
+                    .keys()
+                    .map(|name| format!("{LOOKUP_LIST_PREFIX}{name}")),
+            )
+            .collect::<Vec<_>>();
+        let sieve_compiler = Compiler::new()
+            .with_max_script_size(
+                config
+                    .property("sieve.untrusted.limits.script-size")?
+                    .unwrap_or(1024 * 1024),
+            )
+            .with_max_string_size(
+                config
+                    .property("sieve.untrusted.limits.string-length")?
+                    .unwrap_or(4096),
+            )
+            .with_max_variable_name_size(
+                config
+                    .property("sieve.untrusted.limits.variable-name-length")?
+                    .unwrap_or(32),
+            )
+            .with_max_nested_blocks(
+                config
+                    .property("sieve.untrusted.limits.nested-blocks")?
+                    .unwrap_or(15),
+            )
+            .with_max_nested_tests(
+                config
+                    .property("sieve.untrusted.limits.nested-tests")?
+                    .unwrap_or(15),
+            )
+            .with_max_nested_foreverypart(
+                config
+                    .property("sieve.untrusted.limits.nested-foreverypart")?
+                    .unwrap_or(3),
+            )
+            .with_max_match_variables(
+                config
+                    .property("sieve.untrusted.limits.match-variables")?
+                    .unwrap_or(30),
+            )
+            .with_max_local_variables(
+                config
+                    .property("sieve.untrusted.limits.local-variables")?
+                    .unwrap_or(128),
+            )
+            .with_max_header_size(
+                config
+                    .property("sieve.untrusted.limits.header-size")?
+                    .unwrap_or(1024),
+            )
+            .with_max_includes(
+                config
+                    .property("sieve.untrusted.limits.includes")?
+                    .unwrap_or(3),
+            );
+        let sieve_global_scripts = GlobalScripts::parse(config, &sieve_compiler)?;
 
         let jmap_server = Arc::new(JMAP {
             directory: directories
@@ -228,57 +333,8 @@ impl JMAP {
             state_tx,
             housekeeper_tx,
             smtp,
-            sieve_compiler: Compiler::new()
-                .with_max_script_size(
-                    config
-                        .property("sieve.untrusted.limits.script-size")?
-                        .unwrap_or(1024 * 1024),
-                )
-                .with_max_string_size(
-                    config
-                        .property("sieve.untrusted.limits.string-length")?
-                        .unwrap_or(4096),
-                )
-                .with_max_variable_name_size(
-                    config
-                        .property("sieve.untrusted.limits.variable-name-length")?
-                        .unwrap_or(32),
-                )
-                .with_max_nested_blocks(
-                    config
-                        .property("sieve.untrusted.limits.nested-blocks")?
-                        .unwrap_or(15),
-                )
-                .with_max_nested_tests(
-                    config
-                        .property("sieve.untrusted.limits.nested-tests")?
-                        .unwrap_or(15),
-                )
-                .with_max_nested_foreverypart(
-                    config
-                        .property("sieve.untrusted.limits.nested-foreverypart")?
-                        .unwrap_or(3),
-                )
-                .with_max_match_variables(
-                    config
-                        .property("sieve.untrusted.limits.match-variables")?
-                        .unwrap_or(30),
-                )
-                .with_max_local_variables(
-                    config
-                        .property("sieve.untrusted.limits.local-variables")?
-                        .unwrap_or(128),
-                )
-                .with_max_header_size(
-                    config
-                        .property("sieve.untrusted.limits.header-size")?
-                        .unwrap_or(1024),
-                )
-                .with_max_includes(
-                    config
-                        .property("sieve.untrusted.limits.includes")?
-                        .unwrap_or(3),
-                ),
+            sieve_compiler,
+            sieve_global_scripts,
             sieve_runtime: Runtime::new()
                 .with_max_nested_includes(
                     config
@@ -340,9 +396,16 @@ impl JMAP {
                     if !values.is_empty() {
                         values
                     } else {
-                        vec!["mailto".to_string()]
+                        vec![
+                            "mailto".to_string(),
+                            config
+                                .value("sieve.untrusted.notify.push-uri")
+                                .unwrap_or(PUSH_NOTIFY_URI)
+                                .to_string(),
+                        ]
                     }
                 })
+                .with_valid_ext_lists(ext_lists)
                 .with_protected_headers({
                     let values = config
                         .values("sieve.untrusted.protected-headers")
@@ -375,11 +438,17 @@ impl JMAP {
                 .with_env_variable("version", env!("CARGO_PKG_VERSION"))
                 .with_env_variable("location", "MS")
                 .with_env_variable("phase", "during"),
+            audit_head: tokio::sync::Mutex::new(None),
+            fsck_status: store::parking_lot::Mutex::new(FsckStatus::default()),
+            reindex_status: store::parking_lot::Mutex::new(ReindexStatus::default()),
         });
 
         // Spawn delivery manager
         spawn_delivery_manager(jmap_server.clone(), delivery_rx);
 
+        // Spawn webhook manager
+        spawn_webhook_manager(jmap_server.smtp.webhooks.hooks.clone(), webhook_rx);
+
         // Spawn state manager
         spawn_state_manager(jmap_server.clone(), config, state_rx);
 
@@ -601,6 +670,38 @@ impl JMAP {
         })
     }
 
+    pub async fn is_legal_hold(&self, account_id: u32) -> Result<bool, MethodError> {
+        self.store
+            .legal_hold(account_id)
+            .await
+            .map(|since| since.is_some())
+            .map_err(|err| {
+                tracing::error!(
+                event = "error",
+                context = "is_legal_hold",
+                account_id = account_id,
+                error = ?err,
+                "Failed to obtain legal hold status for account.");
+                MethodError::ServerPartialFail
+            })
+    }
+
+    /// Adds the changes to the used quota of accounts in a batch to the usage counters
+    /// of their domains, must be called before writing batches that update the quota.
+    pub async fn track_domain_usage(&self, batch: &mut BatchBuilder) -> Result<(), MethodError> {
+        self.directory
+            .track_domain_usage(batch)
+            .await
+            .map_err(|err| {
+                tracing::error!(
+                event = "error",
+                context = "track_domain_usage",
+                error = ?err,
+                "Failed to update domain usage.");
+                MethodError::ServerPartialFail
+            })
+    }
+
     pub async fn get_used_quota(&self, account_id: u32) -> Result<i64, MethodError> {
         self.store
             .get_counter(DirectoryClass::UsedQuota(account_id))
@@ -735,7 +836,8 @@ impl JMAP {
         Ok(response)
     }
 
-    pub async fn write_batch(&self, batch: BatchBuilder) -> Result<(), MethodError> {
+    pub async fn write_batch(&self, mut batch: BatchBuilder) -> Result<(), MethodError> {
+        self.track_domain_usage(&mut batch).await?;
         self.store
             .write(batch.build())
             .await
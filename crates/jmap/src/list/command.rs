@@ -0,0 +1,327 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{core::list::ListCommand, Principal};
+use mail_builder::{headers::HeaderType, MessageBuilder};
+use mail_parser::MessageParser;
+use store::write::now;
+use utils::ipc::DeliveryResult;
+
+use crate::JMAP;
+
+use super::{HeldMessage, PendingAction, SubscriptionPolicy};
+
+impl JMAP {
+    /// Processes a message sent to one of the list's command addresses.
+    pub(super) async fn list_command(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        command: ListCommand,
+        sender: &str,
+        raw_message: &[u8],
+    ) -> DeliveryResult {
+        // Ignore commands sent by automated senders, such as vacation responders
+        let sender = sender.to_lowercase();
+        if command != ListCommand::Bounces
+            && (sender.is_empty()
+                || MessageParser::new()
+                    .parse_headers(raw_message)
+                    .and_then(|message| {
+                        message
+                            .header_raw("Auto-Submitted")
+                            .map(|value| !value.trim().eq_ignore_ascii_case("no"))
+                    })
+                    .unwrap_or(false))
+        {
+            return DeliveryResult::Success;
+        }
+
+        let result = match command {
+            ListCommand::Subscribe => self.list_request(list, list_address, &sender, true).await,
+            ListCommand::Unsubscribe => self.list_request(list, list_address, &sender, false).await,
+            ListCommand::Confirm(token) => {
+                self.list_confirm(list, list_address, &sender, &token).await
+            }
+            ListCommand::Approve(token) => {
+                self.list_moderate_token(list, list_address, &sender, &token, true)
+                    .await
+            }
+            ListCommand::Reject(token) => {
+                self.list_moderate_token(list, list_address, &sender, &token, false)
+                    .await
+            }
+            ListCommand::Bounces => {
+                tracing::debug!(
+                    context = "list",
+                    event = "bounce",
+                    list = list_address,
+                    from = sender,
+                    "Received bounce for list message."
+                );
+                Ok(())
+            }
+        };
+
+        match result {
+            Ok(_) => DeliveryResult::Success,
+            Err(err) => {
+                tracing::error!(
+                    context = "list",
+                    event = "error",
+                    list = list_address,
+                    reason = ?err,
+                    "Failed to process list command."
+                );
+                DeliveryResult::TemporaryFailure {
+                    reason: "Transient server failure.".into(),
+                }
+            }
+        }
+    }
+
+    async fn list_request(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        sender: &str,
+        subscribe: bool,
+    ) -> store::Result<()> {
+        let settings = self.list_settings(list.id).await?;
+        if subscribe && settings.subscription == SubscriptionPolicy::Closed {
+            self.list_notify(
+                list,
+                list_address,
+                sender,
+                format!("Subscription to {list_address} rejected"),
+                format!("The list {list_address} does not accept subscription requests.\r\n"),
+                None,
+            )
+            .await;
+            return Ok(());
+        }
+
+        let token = self
+            .list_create_pending(
+                list.id,
+                sender,
+                if subscribe {
+                    PendingAction::Subscribe
+                } else {
+                    PendingAction::Unsubscribe
+                },
+                now() + self.config.list_confirm_expiry.as_secs(),
+            )
+            .await?;
+        let confirm = ListCommand::Confirm(token).address(list_address);
+        let action = if subscribe {
+            "subscribe to"
+        } else {
+            "unsubscribe from"
+        };
+        self.list_notify(
+            list,
+            list_address,
+            sender,
+            format!("Confirm request to {action} {list_address}"),
+            format!(
+                concat!(
+                    "A request was received to {} the list {} for the address {}.\r\n\r\n",
+                    "To confirm it, reply to this message or send a message to:\r\n    {}\r\n\r\n",
+                    "If you did not make this request, please ignore this message.\r\n"
+                ),
+                action, list_address, sender, confirm
+            ),
+            Some(&confirm),
+        )
+        .await;
+
+        Ok(())
+    }
+
+    async fn list_confirm(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        sender: &str,
+        token: &str,
+    ) -> store::Result<()> {
+        let request = if let Some(request) = self.list_take_pending(list.id, token).await? {
+            request
+        } else {
+            tracing::debug!(
+                context = "list",
+                event = "invalid-token",
+                list = list_address,
+                from = sender,
+                "Confirmation token not found or expired."
+            );
+            return Ok(());
+        };
+
+        let (subject, body) = match request.action {
+            PendingAction::Subscribe => {
+                self.add_list_subscriber(list.id, &request.address).await?;
+                (
+                    format!("Welcome to {list_address}"),
+                    format!(
+                        concat!(
+                            "The address {} is now subscribed to the list {}.\r\n\r\n",
+                            "To unsubscribe, send a message to:\r\n    {}\r\n"
+                        ),
+                        request.address,
+                        list_address,
+                        ListCommand::Unsubscribe.address(list_address)
+                    ),
+                )
+            }
+            PendingAction::Unsubscribe => {
+                self.remove_list_subscriber(list.id, &request.address)
+                    .await?;
+                (
+                    format!("Unsubscribed from {list_address}"),
+                    format!(
+                        "The address {} has been removed from the list {}.\r\n",
+                        request.address, list_address
+                    ),
+                )
+            }
+            PendingAction::Moderate { .. } => return Ok(()),
+        };
+        self.list_notify(list, list_address, &request.address, subject, body, None)
+            .await;
+
+        Ok(())
+    }
+
+    async fn list_moderate_token(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        sender: &str,
+        token: &str,
+        approve: bool,
+    ) -> store::Result<()> {
+        if !self.list_settings(list.id).await?.is_moderator(sender) {
+            tracing::debug!(
+                context = "list",
+                event = "unauthorized",
+                list = list_address,
+                from = sender,
+                "Moderation request from a non-moderator."
+            );
+            return Ok(());
+        }
+
+        if let Some(PendingAction::Moderate { id }) = self
+            .list_take_pending(list.id, token)
+            .await?
+            .map(|request| request.action)
+        {
+            if let Some(held) = self.list_held_message(list.id, id).await? {
+                self.list_moderate(list, list_address, &held, approve)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Approves or rejects a held message, releasing it from the moderation queue.
+    pub async fn list_moderate(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        held: &HeldMessage,
+        approve: bool,
+    ) -> store::Result<()> {
+        if approve {
+            if let Some(raw_message) = self
+                .blob_store
+                .get_blob(held.blob_hash.as_ref(), 0..usize::MAX)
+                .await?
+            {
+                if let Some(message) = MessageParser::new().parse(&raw_message) {
+                    let settings = self.list_settings(list.id).await?;
+                    let subscribers = self.list_subscribers(list).await?;
+                    self.list_distribute(list, list_address, &settings, &subscribers, &message)
+                        .await;
+                }
+            }
+        } else {
+            self.list_notify(
+                list,
+                list_address,
+                &held.sender,
+                format!("Message to {list_address} rejected"),
+                format!(
+                    "Your message \"{}\" to the list {} was rejected by a moderator.\r\n",
+                    held.subject, list_address
+                ),
+                None,
+            )
+            .await;
+        }
+
+        tracing::debug!(
+            context = "list",
+            event = if approve { "approved" } else { "rejected" },
+            list = list_address,
+            id = held.id,
+            "Held message moderated."
+        );
+
+        self.list_remove_held(list.id, held).await
+    }
+
+    /// Sends an automatic notice from the list to an address.
+    pub(super) async fn list_notify(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        rcpt: &str,
+        subject: String,
+        body: String,
+        reply_to: Option<&str>,
+    ) -> bool {
+        let list_name = list.description.as_deref().unwrap_or(list.name.as_str());
+        let mut builder = MessageBuilder::new()
+            .from((list_name, list_address))
+            .to(rcpt)
+            .header("Auto-Submitted", HeaderType::Text("auto-replied".into()))
+            .header("Precedence", HeaderType::Text("list".into()))
+            .subject(subject)
+            .text_body(body);
+        if let Some(reply_to) = reply_to {
+            builder = builder.reply_to(reply_to);
+        }
+
+        match builder.write_to_vec() {
+            Ok(message) => {
+                self.list_send(&ListCommand::Bounces.address(list_address), rcpt, message)
+                    .await
+            }
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,577 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::Principal;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use smtp::core::{Session, SessionAddress};
+use store::{
+    write::{key::DeserializeBigEndian, now, BatchBuilder, Bincode, BlobOp, ListClass, ValueClass},
+    Deserialize, IterateParams, Serialize, ValueKey, U32_LEN,
+};
+use utils::{listener::stream::NullIo, BlobHash};
+
+use crate::JMAP;
+
+pub mod command;
+pub mod post;
+pub mod unsubscribe;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PostingPolicy {
+    #[serde(rename = "open")]
+    Open,
+    #[serde(rename = "members")]
+    #[default]
+    Members,
+    #[serde(rename = "moderated")]
+    Moderated,
+    #[serde(rename = "announce")]
+    Announce,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum SubscriptionPolicy {
+    #[serde(rename = "confirm")]
+    #[default]
+    Confirm,
+    #[serde(rename = "closed")]
+    Closed,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ReplyTo {
+    #[serde(rename = "sender")]
+    #[default]
+    Sender,
+    #[serde(rename = "list")]
+    List,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum FromRewrite {
+    #[serde(rename = "dmarc")]
+    #[default]
+    Dmarc,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "never")]
+    Never,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ListSettings {
+    #[serde(default)]
+    pub posting: PostingPolicy,
+    #[serde(default)]
+    pub subscription: SubscriptionPolicy,
+    #[serde(default)]
+    pub moderators: Vec<String>,
+    #[serde(default)]
+    #[serde(rename = "replyTo")]
+    pub reply_to: ReplyTo,
+    #[serde(default)]
+    #[serde(rename = "fromRewrite")]
+    pub from_rewrite: FromRewrite,
+    #[serde(default)]
+    #[serde(rename = "subjectPrefix")]
+    pub subject_prefix: Option<String>,
+}
+
+/// A request that has to be confirmed by replying to the address containing its token.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PendingRequest {
+    pub list_id: u32,
+    pub address: String,
+    pub action: PendingAction,
+    pub expires: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum PendingAction {
+    Subscribe,
+    Unsubscribe,
+    Moderate { id: u64 },
+}
+
+/// A post awaiting approval by a list moderator.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct HeldMessage {
+    pub id: u64,
+    pub token: String,
+    pub sender: String,
+    pub subject: String,
+    pub size: usize,
+    pub received_at: u64,
+    pub expires: u64,
+    pub blob_hash: BlobHash,
+}
+
+/// A list message that could not be queued for some of its subscribers.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ListOutbox {
+    pub id: u64,
+    pub list_id: u32,
+    pub list_address: String,
+    pub subscribers: Vec<String>,
+    pub rewrite_from: bool,
+    pub attempts: u32,
+    pub expires: u64,
+    pub blob_hash: BlobHash,
+}
+
+impl ListSettings {
+    pub fn is_moderator(&self, address: &str) -> bool {
+        self.moderators
+            .iter()
+            .any(|moderator| moderator.eq_ignore_ascii_case(address))
+    }
+}
+
+impl JMAP {
+    pub async fn list_settings(&self, list_id: u32) -> store::Result<ListSettings> {
+        self.store
+            .get_value::<Bincode<ListSettings>>(ValueKey::from(ValueClass::List(
+                ListClass::Settings(list_id),
+            )))
+            .await
+            .map(|settings| settings.map(|settings| settings.inner).unwrap_or_default())
+    }
+
+    pub async fn set_list_settings(
+        &self,
+        list_id: u32,
+        settings: ListSettings,
+    ) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::List(ListClass::Settings(list_id)),
+            Bincode::new(settings).serialize(),
+        );
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    /// Returns the addresses of the list members in the directory followed by
+    /// the addresses that subscribed by e-mail.
+    pub async fn list_subscribers(&self, list: &Principal<u32>) -> store::Result<Vec<String>> {
+        let mut subscribers = Vec::new();
+        for address in &list.emails {
+            for member in self.directory.expn(address).await.unwrap_or_default() {
+                let member = member.to_lowercase();
+                if !subscribers.contains(&member) {
+                    subscribers.push(member);
+                }
+            }
+        }
+
+        for address in self.list_stored_subscribers(list.id).await? {
+            if !subscribers.contains(&address) {
+                subscribers.push(address);
+            }
+        }
+
+        Ok(subscribers)
+    }
+
+    pub async fn list_stored_subscribers(&self, list_id: u32) -> store::Result<Vec<String>> {
+        let mut subscribers = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::List(ListClass::Subscriber {
+                        list_id,
+                        address: vec![],
+                    })),
+                    ValueKey::from(ValueClass::List(ListClass::Subscriber {
+                        list_id,
+                        address: vec![u8::MAX; 10],
+                    })),
+                )
+                .no_values(),
+                |key, _| {
+                    subscribers.push(
+                        String::from_utf8_lossy(key.get(U32_LEN + 1..).unwrap_or_default())
+                            .into_owned(),
+                    );
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(subscribers)
+    }
+
+    pub async fn is_list_subscriber(&self, list_id: u32, address: &str) -> store::Result<bool> {
+        self.store
+            .get_value::<u64>(ValueKey::from(ValueClass::List(ListClass::Subscriber {
+                list_id,
+                address: address.to_lowercase().into_bytes(),
+            })))
+            .await
+            .map(|subscribed_at| subscribed_at.is_some())
+    }
+
+    pub async fn add_list_subscriber(&self, list_id: u32, address: &str) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::List(ListClass::Subscriber {
+                list_id,
+                address: address.to_lowercase().into_bytes(),
+            }),
+            now().serialize(),
+        );
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    pub async fn remove_list_subscriber(&self, list_id: u32, address: &str) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.clear(ValueClass::List(ListClass::Subscriber {
+            list_id,
+            address: address.to_lowercase().into_bytes(),
+        }));
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    /// Stores a request that is completed once its token is sent back.
+    pub async fn list_create_pending(
+        &self,
+        list_id: u32,
+        address: &str,
+        action: PendingAction,
+        expires: u64,
+    ) -> store::Result<String> {
+        let token = thread_rng()
+            .sample_iter(Alphanumeric)
+            .take(24)
+            .map(|ch| char::from(ch.to_ascii_lowercase()))
+            .collect::<String>();
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::List(ListClass::Pending(token.as_bytes().to_vec())),
+            Bincode::new(PendingRequest {
+                list_id,
+                address: address.to_lowercase(),
+                action,
+                expires,
+            })
+            .serialize(),
+        );
+        self.store.write(batch.build()).await?;
+
+        Ok(token)
+    }
+
+    /// Removes and returns a pending request, provided that it has not expired.
+    pub async fn list_take_pending(
+        &self,
+        list_id: u32,
+        token: &str,
+    ) -> store::Result<Option<PendingRequest>> {
+        let key = ValueClass::List(ListClass::Pending(token.as_bytes().to_vec()));
+        match self
+            .store
+            .get_value::<Bincode<PendingRequest>>(ValueKey::from(key.clone()))
+            .await?
+        {
+            Some(request) if request.inner.list_id == list_id => {
+                let mut batch = BatchBuilder::new();
+                batch.clear(key);
+                self.store.write(batch.build()).await?;
+
+                Ok(Some(request.inner).filter(|request| request.expires > now()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub async fn list_held_message(
+        &self,
+        list_id: u32,
+        id: u64,
+    ) -> store::Result<Option<HeldMessage>> {
+        self.store
+            .get_value::<Bincode<HeldMessage>>(ValueKey::from(ValueClass::List(ListClass::Held {
+                list_id,
+                id,
+            })))
+            .await
+            .map(|held| held.map(|held| held.inner))
+    }
+
+    pub async fn list_held_messages(&self, list_id: u32) -> store::Result<Vec<HeldMessage>> {
+        let mut messages = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::List(ListClass::Held { list_id, id: 0 })),
+                    ValueKey::from(ValueClass::List(ListClass::Held {
+                        list_id,
+                        id: u64::MAX,
+                    })),
+                ),
+                |_, value| {
+                    messages.push(Bincode::<HeldMessage>::deserialize(value)?.inner);
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(messages)
+    }
+
+    /// Removes a held message, releasing its blob.
+    pub async fn list_remove_held(&self, list_id: u32, held: &HeldMessage) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(list_id)
+            .clear(BlobOp::Reserve {
+                hash: held.blob_hash.clone(),
+                until: held.expires,
+            })
+            .clear(ValueClass::List(ListClass::Held {
+                list_id,
+                id: held.id,
+            }))
+            .clear(ValueClass::List(ListClass::Pending(
+                held.token.as_bytes().to_vec(),
+            )));
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    /// Keeps a copy of a list message along with the subscribers it has to be
+    /// queued for, so that their delivery can be retried.
+    pub async fn list_create_outbox(
+        &self,
+        list_id: u32,
+        list_address: &str,
+        raw_message: &[u8],
+        subscribers: Vec<String>,
+        rewrite_from: bool,
+    ) -> store::Result<()> {
+        let outbox = ListOutbox {
+            id: self.snowflake_id.generate().unwrap_or_else(now),
+            list_id,
+            list_address: list_address.to_string(),
+            subscribers,
+            rewrite_from,
+            attempts: 0,
+            expires: now() + self.config.list_outbox_expiry.as_secs(),
+            blob_hash: BlobHash::from(raw_message),
+        };
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(list_id)
+            .set(
+                BlobOp::Reserve {
+                    hash: outbox.blob_hash.clone(),
+                    until: outbox.expires,
+                },
+                0u32.serialize(),
+            )
+            .set(
+                ValueClass::List(ListClass::Outbox {
+                    list_id,
+                    id: outbox.id,
+                }),
+                Bincode::new(outbox.clone()).serialize(),
+            );
+        self.store.write(batch.build()).await?;
+        self.blob_store
+            .put_blob(outbox.blob_hash.as_ref(), raw_message)
+            .await
+    }
+
+    pub async fn list_outbox(&self) -> store::Result<Vec<ListOutbox>> {
+        let mut outbox = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::List(ListClass::Outbox { list_id: 0, id: 0 })),
+                    ValueKey::from(ValueClass::List(ListClass::Outbox {
+                        list_id: u32::MAX,
+                        id: u64::MAX,
+                    })),
+                ),
+                |_, value| {
+                    outbox.push(Bincode::<ListOutbox>::deserialize(value)?.inner);
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(outbox)
+    }
+
+    pub async fn list_update_outbox(&self, outbox: &ListOutbox) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            ValueClass::List(ListClass::Outbox {
+                list_id: outbox.list_id,
+                id: outbox.id,
+            }),
+            Bincode::new(outbox.clone()).serialize(),
+        );
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    /// Removes a message from the outbox, releasing its blob.
+    pub async fn list_remove_outbox(&self, outbox: &ListOutbox) -> store::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(outbox.list_id)
+            .clear(BlobOp::Reserve {
+                hash: outbox.blob_hash.clone(),
+                until: outbox.expires,
+            })
+            .clear(ValueClass::List(ListClass::Outbox {
+                list_id: outbox.list_id,
+                id: outbox.id,
+            }));
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    /// Removes expired confirmation requests and held messages.
+    pub async fn purge_lists(&self) {
+        let result = match self.list_expired_keys().await {
+            Ok(expired) => {
+                let mut result = Ok(());
+                for chunk in expired.chunks(100) {
+                    let mut batch = BatchBuilder::new();
+                    for key in chunk {
+                        batch.clear(key.clone());
+                    }
+                    result = self.store.write(batch.build()).await.map(|_| ());
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result
+            }
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            tracing::error!(
+                context = "list",
+                event = "error",
+                reason = ?err,
+                "Failed to purge expired list requests"
+            );
+        }
+    }
+
+    async fn list_expired_keys(&self) -> store::Result<Vec<ValueClass>> {
+        let now = now();
+        let mut expired = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::List(ListClass::Pending(vec![]))),
+                    ValueKey::from(ValueClass::List(ListClass::Pending(vec![u8::MAX; 32]))),
+                ),
+                |key, value| {
+                    if Bincode::<PendingRequest>::deserialize(value)?.inner.expires <= now {
+                        expired.push(ValueClass::List(ListClass::Pending(
+                            key.get(1..).unwrap_or_default().to_vec(),
+                        )));
+                    }
+                    Ok(true)
+                },
+            )
+            .await?;
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::List(ListClass::Held { list_id: 0, id: 0 })),
+                    ValueKey::from(ValueClass::List(ListClass::Held {
+                        list_id: u32::MAX,
+                        id: u64::MAX,
+                    })),
+                ),
+                |key, value| {
+                    let held = Bincode::<HeldMessage>::deserialize(value)?.inner;
+                    if held.expires <= now {
+                        expired.push(ValueClass::List(ListClass::Held {
+                            list_id: key.deserialize_be_u32(1)?,
+                            id: held.id,
+                        }));
+                    }
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(expired)
+    }
+
+    /// Derives the token that authorizes one-click unsubscription of an address.
+    pub fn list_unsubscribe_token(&self, list_id: u32, address: &str) -> String {
+        let key = blake3::derive_key("list unsubscribe", self.config.oauth_key.as_bytes());
+        let mut hasher = blake3::Hasher::new_keyed(&key);
+        hasher.update(&list_id.to_be_bytes());
+        hasher.update(address.to_lowercase().as_bytes());
+        hasher
+            .finalize()
+            .as_bytes()
+            .iter()
+            .take(16)
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Verifies a one-click unsubscription token in constant time.
+    pub fn list_verify_unsubscribe_token(&self, list_id: u32, address: &str, token: &str) -> bool {
+        let expected = self.list_unsubscribe_token(list_id, address);
+        expected.len() == token.len()
+            && expected
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    /// Queues a message for delivery through the SMTP queue.
+    pub async fn list_send(&self, return_path: &str, rcpt: &str, message: Vec<u8>) -> bool {
+        let response = Session::<NullIo>::sieve(
+            self.smtp.clone(),
+            SessionAddress::new(return_path.to_string()),
+            vec![SessionAddress::new(rcpt.to_string())],
+            message,
+        )
+        .queue_message()
+        .await;
+
+        if response.first() == Some(&b'2') {
+            true
+        } else {
+            tracing::warn!(
+                context = "list",
+                event = "queue-failed",
+                from = return_path,
+                to = rcpt,
+                smtp_response = std::str::from_utf8(&response).unwrap_or_default().trim(),
+                "Failed to queue list message"
+            );
+            false
+        }
+    }
+}
@@ -0,0 +1,629 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{core::list::ListCommand, Principal};
+use mail_builder::headers::{address::Address, text::Text, Header};
+use mail_parser::{Message, MessageParser};
+use store::{
+    write::{now, BatchBuilder, Bincode, BlobOp, ListClass, ValueClass},
+    Serialize,
+};
+use utils::{
+    ipc::{DeliveryResult, IngestMessage},
+    BlobHash,
+};
+
+use crate::JMAP;
+
+use super::{
+    FromRewrite, HeldMessage, ListOutbox, ListSettings, PendingAction, PostingPolicy, ReplyTo,
+};
+
+/// Headers replaced by the list when distributing a message.
+const LIST_HEADERS: [&str; 10] = [
+    "List-Id",
+    "List-Post",
+    "List-Help",
+    "List-Subscribe",
+    "List-Unsubscribe",
+    "List-Unsubscribe-Post",
+    "List-Archive",
+    "List-Owner",
+    "Precedence",
+    "Return-Path",
+];
+
+impl JMAP {
+    /// Handles messages addressed to a mailing list or to one of its command
+    /// addresses, returns `None` when the recipient is not a list.
+    pub async fn list_deliver(
+        &self,
+        rcpt: &str,
+        message: &IngestMessage,
+        raw_message: &[u8],
+    ) -> Option<DeliveryResult> {
+        let (list_address, command) = match ListCommand::parse(rcpt) {
+            Some((list_address, command)) => (list_address, Some(command)),
+            None => (rcpt.to_lowercase(), None),
+        };
+        let list = match self.directory.query_list(&list_address).await {
+            Ok(Some(list)) => list,
+            Ok(None) => return None,
+            Err(_) => {
+                return DeliveryResult::TemporaryFailure {
+                    reason: "Address lookup failed.".into(),
+                }
+                .into()
+            }
+        };
+
+        Some(if let Some(command) = command {
+            self.list_command(
+                &list,
+                &list_address,
+                command,
+                &message.sender_address,
+                raw_message,
+            )
+            .await
+        } else {
+            self.list_post(&list, &list_address, message, raw_message)
+                .await
+        })
+    }
+
+    async fn list_post(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        envelope: &IngestMessage,
+        raw_message: &[u8],
+    ) -> DeliveryResult {
+        let message = if let Some(message) = MessageParser::new().parse(raw_message) {
+            message
+        } else {
+            return DeliveryResult::PermanentFailure {
+                code: [5, 5, 0],
+                reason: "Failed to parse e-mail message.".into(),
+            };
+        };
+        let settings = if let Ok(settings) = self.list_settings(list.id).await {
+            settings
+        } else {
+            return DeliveryResult::TemporaryFailure {
+                reason: "Transient server failure.".into(),
+            };
+        };
+
+        // Discard messages that have already been distributed by this list
+        let list_id = format!("<{}>", list_id(list_address));
+        if message
+            .header_raw("List-Id")
+            .is_some_and(|header| header.contains(&list_id))
+        {
+            tracing::debug!(
+                context = "list",
+                event = "loop-detected",
+                list = list_address,
+                "Discarding message already distributed by the list."
+            );
+            return DeliveryResult::Success;
+        }
+
+        // Enforce the posting policy. As both the envelope sender and the From header
+        // can be forged, posting rights are only granted to authenticated submitters
+        // and to authors whose domain passed DMARC.
+        let sender = envelope.sender_address.as_str();
+        let from = message
+            .from()
+            .and_then(|from| from.first())
+            .and_then(|addr| addr.address())
+            .map(|address| address.to_lowercase());
+        let author = from.clone().unwrap_or_else(|| sender.to_lowercase());
+        let poster = if envelope.sender_authenticated {
+            Some(sender.to_lowercase())
+        } else if envelope.author_authenticated {
+            from
+        } else {
+            None
+        };
+        let is_moderator = poster
+            .as_deref()
+            .is_some_and(|poster| settings.is_moderator(poster));
+        let subscribers = if let Ok(subscribers) = self.list_subscribers(list).await {
+            subscribers
+        } else {
+            return DeliveryResult::TemporaryFailure {
+                reason: "Transient server failure.".into(),
+            };
+        };
+        let is_member = poster
+            .as_deref()
+            .is_some_and(|poster| subscribers.iter().any(|address| address == poster));
+
+        match settings.posting {
+            PostingPolicy::Open => {}
+            PostingPolicy::Members if is_member || is_moderator => {}
+            PostingPolicy::Moderated | PostingPolicy::Announce if is_moderator => {}
+            PostingPolicy::Members => {
+                return DeliveryResult::PermanentFailure {
+                    code: [5, 7, 1],
+                    reason: "Only list members may post to this list.".into(),
+                };
+            }
+            PostingPolicy::Announce => {
+                return DeliveryResult::PermanentFailure {
+                    code: [5, 7, 1],
+                    reason: "Only moderators may post to this list.".into(),
+                };
+            }
+            PostingPolicy::Moderated => {
+                return self
+                    .list_hold(list, list_address, &settings, &author, &message)
+                    .await;
+            }
+        }
+
+        self.list_distribute(list, list_address, &settings, &subscribers, &message)
+            .await
+    }
+
+    /// Sends a copy of the message to every subscriber through the SMTP queue,
+    /// keeping those that could not be queued in the outbox to retry them later.
+    pub async fn list_distribute(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        settings: &ListSettings,
+        subscribers: &[String],
+        message: &Message<'_>,
+    ) -> DeliveryResult {
+        let rewrite_from = match settings.from_rewrite {
+            FromRewrite::Always => true,
+            FromRewrite::Never => false,
+            FromRewrite::Dmarc => {
+                if let Some(domain) = message
+                    .from()
+                    .and_then(|from| from.first())
+                    .and_then(|addr| addr.address())
+                    .and_then(|address| address.rsplit_once('@'))
+                    .map(|(_, domain)| domain.to_lowercase())
+                {
+                    self.has_strict_dmarc_policy(&domain).await
+                } else {
+                    false
+                }
+            }
+        };
+
+        let failed = self
+            .list_queue(
+                list,
+                list_address,
+                settings,
+                message,
+                subscribers,
+                rewrite_from,
+            )
+            .await;
+
+        tracing::debug!(
+            context = "list",
+            event = "distribute",
+            list = list_address,
+            subscribers = subscribers.len(),
+            failed = failed.len(),
+            "Distributed list message."
+        );
+
+        if failed.is_empty() {
+            return DeliveryResult::Success;
+        }
+
+        // Retry the failed subscribers only, as the others already have their copy
+        match self
+            .list_create_outbox(
+                list.id,
+                list_address,
+                message.raw_message(),
+                failed,
+                rewrite_from,
+            )
+            .await
+        {
+            Ok(_) => DeliveryResult::Success,
+            Err(err) => {
+                tracing::error!(
+                    context = "list",
+                    event = "error",
+                    list = list_address,
+                    reason = ?err,
+                    "Failed to store list message for retry."
+                );
+                DeliveryResult::TemporaryFailure {
+                    reason: "Failed to queue list message.".into(),
+                }
+            }
+        }
+    }
+
+    /// Retries the distribution of list messages that could not be queued
+    /// for some of their subscribers.
+    pub async fn list_retry_outbox(&self) {
+        let outbox = match self.list_outbox().await {
+            Ok(outbox) => outbox,
+            Err(err) => {
+                tracing::error!(
+                    context = "list",
+                    event = "error",
+                    reason = ?err,
+                    "Failed to read list outbox."
+                );
+                return;
+            }
+        };
+
+        for outbox in outbox {
+            if let Err(err) = self.list_retry(outbox).await {
+                tracing::error!(
+                    context = "list",
+                    event = "error",
+                    reason = ?err,
+                    "Failed to retry list message."
+                );
+            }
+        }
+    }
+
+    async fn list_retry(&self, mut outbox: ListOutbox) -> store::Result<()> {
+        let list = self
+            .directory
+            .query_list(&outbox.list_address)
+            .await
+            .ok()
+            .flatten()
+            .filter(|list| list.id == outbox.list_id);
+        let raw_message = self
+            .blob_store
+            .get_blob(outbox.blob_hash.as_ref(), 0..usize::MAX)
+            .await?;
+        let message = raw_message
+            .as_deref()
+            .and_then(|raw_message| MessageParser::new().parse(raw_message));
+        let subscribers = std::mem::take(&mut outbox.subscribers);
+        if let (Some(list), Some(message)) = (list, message) {
+            let settings = self.list_settings(list.id).await?;
+            outbox.subscribers = self
+                .list_queue(
+                    &list,
+                    &outbox.list_address,
+                    &settings,
+                    &message,
+                    &subscribers,
+                    outbox.rewrite_from,
+                )
+                .await;
+        }
+        outbox.attempts += 1;
+
+        if outbox.subscribers.is_empty() || outbox.expires <= now() {
+            if !outbox.subscribers.is_empty() {
+                tracing::warn!(
+                    context = "list",
+                    event = "retry-expired",
+                    list = outbox.list_address,
+                    subscribers = outbox.subscribers.len(),
+                    attempts = outbox.attempts,
+                    "Giving up on list message after repeated failures."
+                );
+            }
+            self.list_remove_outbox(&outbox).await
+        } else {
+            self.list_update_outbox(&outbox).await
+        }
+    }
+
+    /// Queues a copy of the message for each subscriber, returning those that failed.
+    async fn list_queue(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        settings: &ListSettings,
+        message: &Message<'_>,
+        subscribers: &[String],
+        rewrite_from: bool,
+    ) -> Vec<String> {
+        let return_path = ListCommand::Bounces.address(list_address);
+        let mut failed = Vec::new();
+        for subscriber in subscribers {
+            let raw_message = self.list_build_message(
+                list,
+                list_address,
+                settings,
+                message,
+                subscriber,
+                rewrite_from,
+            );
+            if !self.list_send(&return_path, subscriber, raw_message).await {
+                failed.push(subscriber.clone());
+            }
+        }
+
+        failed
+    }
+
+    async fn list_hold(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        settings: &ListSettings,
+        author: &str,
+        message: &Message<'_>,
+    ) -> DeliveryResult {
+        let raw_message = message.raw_message();
+        let blob_hash = BlobHash::from(raw_message);
+        let expires = now() + self.config.list_held_expiry.as_secs();
+        let id = self.snowflake_id.generate().unwrap_or_else(now);
+
+        // Keep the message until it is moderated or the hold expires
+        let token = match self
+            .list_create_pending(list.id, author, PendingAction::Moderate { id }, expires)
+            .await
+        {
+            Ok(token) => token,
+            Err(_) => {
+                return DeliveryResult::TemporaryFailure {
+                    reason: "Transient server failure.".into(),
+                }
+            }
+        };
+        let held = HeldMessage {
+            id,
+            token: token.clone(),
+            sender: author.to_string(),
+            subject: message.subject().unwrap_or_default().to_string(),
+            size: raw_message.len(),
+            received_at: now(),
+            expires,
+            blob_hash: blob_hash.clone(),
+        };
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(list.id)
+            .set(
+                BlobOp::Reserve {
+                    hash: blob_hash.clone(),
+                    until: expires,
+                },
+                0u32.serialize(),
+            )
+            .set(
+                ValueClass::List(ListClass::Held {
+                    list_id: list.id,
+                    id,
+                }),
+                Bincode::new(held.clone()).serialize(),
+            );
+        if self.store.write(batch.build()).await.is_err()
+            || self
+                .blob_store
+                .put_blob(blob_hash.as_ref(), raw_message)
+                .await
+                .is_err()
+        {
+            return DeliveryResult::TemporaryFailure {
+                reason: "Transient server failure.".into(),
+            };
+        }
+
+        // Notify moderators
+        let approve = ListCommand::Approve(token.clone()).address(list_address);
+        let reject = ListCommand::Reject(token).address(list_address);
+        for moderator in &settings.moderators {
+            self.list_notify(
+                list,
+                list_address,
+                moderator,
+                format!("Message held for moderation: {}", held.subject),
+                format!(
+                    concat!(
+                        "A message sent by {} to the list {} requires approval.\r\n\r\n",
+                        "Subject: {}\r\n\r\n",
+                        "To approve it, send a message to:\r\n    {}\r\n\r\n",
+                        "To reject it, send a message to:\r\n    {}\r\n"
+                    ),
+                    held.sender, list_address, held.subject, approve, reject
+                ),
+                Some(&approve),
+            )
+            .await;
+        }
+
+        tracing::debug!(
+            context = "list",
+            event = "held",
+            list = list_address,
+            sender = author,
+            id = id,
+            "Message held for moderation."
+        );
+
+        DeliveryResult::Success
+    }
+
+    fn list_build_message(
+        &self,
+        list: &Principal<u32>,
+        list_address: &str,
+        settings: &ListSettings,
+        message: &Message<'_>,
+        subscriber: &str,
+        rewrite_from: bool,
+    ) -> Vec<u8> {
+        let raw_message = message.raw_message();
+        let mut output = Vec::with_capacity(raw_message.len() + 1024);
+        let list_name = list.description.as_deref().unwrap_or(list.name.as_str());
+        let author = message.from().and_then(|from| from.first());
+
+        // RFC 2919 and RFC 2369 list headers
+        output.extend_from_slice(b"List-Id: ");
+        if list_name.is_ascii() && !list_name.contains(['<', '>', '"', '\\']) {
+            output.extend_from_slice(format!("\"{list_name}\" ").as_bytes());
+        }
+        output.extend_from_slice(format!("<{}>\r\n", list_id(list_address)).as_bytes());
+        if settings.posting != PostingPolicy::Announce {
+            output.extend_from_slice(format!("List-Post: <mailto:{list_address}>\r\n").as_bytes());
+        } else {
+            output.extend_from_slice(b"List-Post: NO\r\n");
+        }
+        output.extend_from_slice(
+            format!(
+                "List-Subscribe: <mailto:{}>\r\n",
+                ListCommand::Subscribe.address(list_address)
+            )
+            .as_bytes(),
+        );
+        output.extend_from_slice(
+            format!(
+                "List-Unsubscribe: <mailto:{}>",
+                ListCommand::Unsubscribe.address(list_address)
+            )
+            .as_bytes(),
+        );
+
+        // RFC 8058 one-click unsubscription
+        if let Some(url) = &self.config.list_url {
+            output.extend_from_slice(
+                format!(
+                    ",\r\n\t<{}/api/unsubscribe?list={}&address={}&token={}>\r\n",
+                    url.trim_end_matches('/'),
+                    list.id,
+                    form_urlencoded::byte_serialize(subscriber.as_bytes()).collect::<String>(),
+                    self.list_unsubscribe_token(list.id, subscriber)
+                )
+                .as_bytes(),
+            );
+            output.extend_from_slice(b"List-Unsubscribe-Post: List-Unsubscribe=One-Click\r\n");
+        } else {
+            output.extend_from_slice(b"\r\n");
+        }
+        output.extend_from_slice(b"Precedence: list\r\n");
+
+        // Rewrite the author address for domains that publish a strict DMARC policy
+        let rewrite_from = rewrite_from && author.is_some();
+        if rewrite_from {
+            let author_name = author
+                .and_then(|addr| addr.name().or(addr.address()))
+                .unwrap_or_default();
+            output.extend_from_slice(b"From: ");
+            let _ =
+                Address::new_address(Some(format!("{author_name} via {list_name}")), list_address)
+                    .write_header(&mut output, 6);
+        }
+
+        // Reply-To munging
+        let set_reply_to = match settings.reply_to {
+            ReplyTo::List => {
+                output.extend_from_slice(b"Reply-To: ");
+                let _ = Address::new_address(Some(list_name), list_address)
+                    .write_header(&mut output, 10);
+                true
+            }
+            ReplyTo::Sender if rewrite_from && message.reply_to().is_none() => {
+                let author = author.unwrap();
+                output.extend_from_slice(b"Reply-To: ");
+                let _ = Address::new_address(author.name(), author.address().unwrap_or_default())
+                    .write_header(&mut output, 10);
+                true
+            }
+            ReplyTo::Sender => false,
+        };
+
+        // Subject prefix
+        let set_subject = match &settings.subject_prefix {
+            Some(prefix) if !prefix.is_empty() => {
+                let subject = message.subject().unwrap_or_default();
+                if !subject.contains(prefix.as_str()) {
+                    output.extend_from_slice(b"Subject: ");
+                    let _ = Text::new(format!("{prefix} {subject}")).write_header(&mut output, 9);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        };
+
+        // Copy the remaining headers and the body
+        for header in message.headers() {
+            let name = header.name.as_str();
+            if LIST_HEADERS.iter().any(|h| name.eq_ignore_ascii_case(h))
+                || (rewrite_from && name.eq_ignore_ascii_case("From"))
+                || (set_reply_to && name.eq_ignore_ascii_case("Reply-To"))
+                || (set_subject && name.eq_ignore_ascii_case("Subject"))
+            {
+                continue;
+            }
+            output.extend_from_slice(name.as_bytes());
+            output.push(b':');
+            output.extend_from_slice(
+                raw_message
+                    .get(header.offset_start..header.offset_end)
+                    .unwrap_or_default(),
+            );
+        }
+        output.extend_from_slice(b"\r\n");
+        output.extend_from_slice(
+            raw_message
+                .get(message.root_part().offset_body..)
+                .unwrap_or_default(),
+        );
+
+        output
+    }
+
+    /// Returns whether a domain publishes a DMARC policy of `quarantine` or `reject`.
+    async fn has_strict_dmarc_policy(&self, domain: &str) -> bool {
+        match self
+            .smtp
+            .resolvers
+            .dns
+            .txt_raw_lookup(format!("_dmarc.{domain}."))
+            .await
+        {
+            Ok(record) => std::str::from_utf8(&record).is_ok_and(|record| {
+                record.split(';').any(|tag| {
+                    tag.trim()
+                        .strip_prefix("p=")
+                        .is_some_and(|policy| matches!(policy.trim(), "reject" | "quarantine"))
+                })
+            }),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Builds the RFC 2919 list identifier from the list address.
+pub fn list_id(list_address: &str) -> String {
+    list_address.replacen('@', ".", 1)
+}
@@ -0,0 +1,74 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{QueryBy, Type};
+use jmap_proto::error::request::RequestError;
+use serde_json::json;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+impl JMAP {
+    /// Handles RFC 8058 one-click unsubscription requests, which are
+    /// authorized by the token included in the `List-Unsubscribe` header.
+    pub async fn handle_list_unsubscribe(&self, req: &HttpRequest) -> HttpResponse {
+        let params = UrlParams::new(req.uri().query());
+        let (list_id, address) = match (
+            params.parse::<u32>("list"),
+            params.get("address"),
+            params.get("token"),
+        ) {
+            (Some(list_id), Some(address), Some(token))
+                if self.list_verify_unsubscribe_token(list_id, address, token) =>
+            {
+                (list_id, address)
+            }
+            _ => return RequestError::not_found().into_http_response(),
+        };
+
+        match self.directory.query(QueryBy::Id(list_id), false).await {
+            Ok(Some(list)) if list.typ == Type::List => {}
+            _ => return RequestError::not_found().into_http_response(),
+        }
+
+        match self.remove_list_subscriber(list_id, address).await {
+            Ok(_) => {
+                tracing::debug!(
+                    context = "list",
+                    event = "unsubscribe",
+                    list_id = list_id,
+                    address = address,
+                    "One-click unsubscription."
+                );
+                JsonResponse::new(json!({
+                    "data": (),
+                }))
+                .into_http_response()
+            }
+            Err(_) => RequestError::internal_server_error().into_http_response(),
+        }
+    }
+}
@@ -25,9 +25,10 @@ use jmap_proto::{
     error::method::MethodError,
     method::get::{GetRequest, GetResponse, RequestArguments},
     object::Object,
-    types::{acl::Acl, collection::Collection, keyword::Keyword, property::Property, value::Value},
+    types::{acl::Acl, collection::Collection, property::Property, value::Value},
 };
 use store::{ahash::AHashSet, query::Filter, roaring::RoaringBitmap};
+use utils::map::bitmap::Bitmap;
 
 use crate::{
     auth::{acl::EffectiveAcl, AccessToken},
@@ -152,10 +153,15 @@ impl JMAP {
                         .unwrap_or(0),
                     ),
                     Property::UnreadEmails => Value::UnsignedInt(
-                        self.mailbox_unread_tags(account_id, document_id, &message_ids)
-                            .await?
-                            .map(|v| v.len())
-                            .unwrap_or(0),
+                        self.mailbox_unread_tags(
+                            access_token,
+                            account_id,
+                            document_id,
+                            &message_ids,
+                        )
+                        .await?
+                        .map(|v| v.len())
+                        .unwrap_or(0),
                     ),
                     Property::TotalThreads => Value::UnsignedInt(
                         self.mailbox_count_threads(
@@ -173,34 +179,19 @@ impl JMAP {
                     Property::UnreadThreads => Value::UnsignedInt(
                         self.mailbox_count_threads(
                             account_id,
-                            self.mailbox_unread_tags(account_id, document_id, &message_ids)
-                                .await?,
+                            self.mailbox_unread_tags(
+                                access_token,
+                                account_id,
+                                document_id,
+                                &message_ids,
+                            )
+                            .await?,
                         )
                         .await? as u64,
                     ),
                     Property::MyRights => {
                         if access_token.is_shared(account_id) {
-                            let acl = values.effective_acl(access_token);
-                            Object::with_capacity(9)
-                                .with_property(Property::MayReadItems, acl.contains(Acl::ReadItems))
-                                .with_property(Property::MayAddItems, acl.contains(Acl::AddItems))
-                                .with_property(
-                                    Property::MayRemoveItems,
-                                    acl.contains(Acl::RemoveItems),
-                                )
-                                .with_property(Property::MaySetSeen, acl.contains(Acl::ModifyItems))
-                                .with_property(
-                                    Property::MaySetKeywords,
-                                    acl.contains(Acl::ModifyItems),
-                                )
-                                .with_property(
-                                    Property::MayCreateChild,
-                                    acl.contains(Acl::CreateChild),
-                                )
-                                .with_property(Property::MayRename, acl.contains(Acl::Modify))
-                                .with_property(Property::MayDelete, acl.contains(Acl::Delete))
-                                .with_property(Property::MaySubmit, acl.contains(Acl::Submit))
-                                .into()
+                            mailbox_rights(&values.effective_acl(access_token, account_id)).into()
                         } else {
                             Object::with_capacity(9)
                                 .with_property(Property::MayReadItems, true)
@@ -283,6 +274,7 @@ impl JMAP {
 
     pub async fn mailbox_unread_tags(
         &self,
+        access_token: &AccessToken,
         account_id: u32,
         document_id: u32,
         message_ids: &Option<RoaringBitmap>,
@@ -297,15 +289,7 @@ impl JMAP {
             )
             .await?,
         ) {
-            if let Some(mut seen) = self
-                .get_tag(
-                    account_id,
-                    Collection::Email,
-                    Property::Keywords,
-                    Keyword::Seen,
-                )
-                .await?
-            {
+            if let Some(mut seen) = self.seen_messages(access_token, account_id).await? {
                 seen ^= message_ids;
                 seen &= &mailbox_message_ids;
                 if !seen.is_empty() {
@@ -432,3 +416,16 @@ pub struct ExpandPath<'x> {
     pub path: Vec<&'x str>,
     pub found_names: Vec<(String, u32, u32)>,
 }
+
+pub fn mailbox_rights(acl: &Bitmap<Acl>) -> Object<Value> {
+    Object::with_capacity(9)
+        .with_property(Property::MayReadItems, acl.contains(Acl::ReadItems))
+        .with_property(Property::MayAddItems, acl.contains(Acl::AddItems))
+        .with_property(Property::MayRemoveItems, acl.contains(Acl::RemoveItems))
+        .with_property(Property::MaySetSeen, acl.contains(Acl::ModifyItems))
+        .with_property(Property::MaySetKeywords, acl.contains(Acl::ModifyItems))
+        .with_property(Property::MayCreateChild, acl.contains(Acl::CreateChild))
+        .with_property(Property::MayRename, acl.contains(Acl::Modify))
+        .with_property(Property::MayDelete, acl.contains(Acl::Delete))
+        .with_property(Property::MaySubmit, acl.contains(Acl::Submit))
+}
@@ -55,6 +55,7 @@ use store::{
 
 use crate::{
     auth::{acl::EffectiveAcl, AccessToken},
+    sharing::AclChanges,
     JMAP,
 };
 
@@ -111,6 +112,7 @@ impl JMAP {
 
         // Process creates
         let mut changes = ChangeLogBuilder::new();
+        let mut share_changes = Vec::new();
         'create: for (id, object) in request.unwrap_create() {
             match self.mailbox_set_item(object, None, &ctx).await? {
                 Ok(builder) => {
@@ -133,12 +135,16 @@ impl JMAP {
                         }
                     }
 
+                    let acl_changes = AclChanges::new(&builder);
                     batch.create_document(document_id).custom(builder);
                     changes.log_insert(Collection::Mailbox, document_id);
                     ctx.mailbox_ids.insert(document_id);
                     match self.store.write(batch.build()).await {
                         Ok(_) => {
                             ctx.response.created(id, document_id);
+                            if let Some(acl_changes) = acl_changes {
+                                share_changes.push((document_id, acl_changes));
+                            }
                         }
                         Err(store::Error::AssertValueFailed) => {
                             ctx.response.not_created.append(
@@ -190,7 +196,7 @@ impl JMAP {
             {
                 // Validate ACL
                 if ctx.is_shared {
-                    let acl = mailbox.inner.effective_acl(access_token);
+                    let acl = mailbox.inner.effective_acl(access_token, account_id);
                     if !acl.contains(Acl::Modify) {
                         ctx.response.not_updated.append(
                             id,
@@ -232,12 +238,16 @@ impl JMAP {
                             }
                         }
 
+                        let acl_changes = AclChanges::new(&builder);
                         batch.update_document(document_id).custom(builder);
 
                         if !batch.is_empty() {
                             match self.store.write(batch.build()).await {
                                 Ok(_) => {
                                     changes.log_update(Collection::Mailbox, document_id);
+                                    if let Some(acl_changes) = acl_changes {
+                                        share_changes.push((document_id, acl_changes));
+                                    }
                                 }
                                 Err(store::Error::AssertValueFailed) => {
                                     ctx.response.not_updated.append(id, SetError::forbidden().with_description(
@@ -306,6 +316,18 @@ impl JMAP {
             ctx.response.new_state = Some(self.commit_changes(account_id, changes).await?.into());
         }
 
+        // Notify sharees
+        for (document_id, acl_changes) in share_changes {
+            self.share_notify(
+                access_token,
+                Collection::Mailbox,
+                account_id,
+                document_id,
+                acl_changes,
+            )
+            .await?;
+        }
+
         Ok(ctx.response)
     }
 
@@ -359,6 +381,13 @@ impl JMAP {
             .await?
         {
             if remove_emails {
+                // Messages of accounts under legal hold cannot be expunged
+                if self.is_legal_hold(account_id).await? {
+                    return Ok(Err(SetError::forbidden().with_description(
+                        "Account is under legal hold, messages cannot be deleted.",
+                    )));
+                }
+
                 // Flag removal for state change notification
                 did_remove_emails = true;
 
@@ -476,7 +505,7 @@ impl JMAP {
         {
             // Validate ACLs
             if access_token.is_shared(account_id) {
-                let acl = mailbox.inner.effective_acl(access_token);
+                let acl = mailbox.inner.effective_acl(access_token, account_id);
                 if !acl.contains(Acl::Administer) {
                     if !acl.contains(Acl::Delete) {
                         return Ok(Err(SetError::forbidden()
@@ -660,7 +689,7 @@ impl JMAP {
                     if depth == 0
                         && ctx.is_shared
                         && !fields
-                            .effective_acl(ctx.access_token)
+                            .effective_acl(ctx.access_token, ctx.account_id)
                             .contains_any([Acl::CreateChild, Acl::Administer].into_iter())
                     {
                         return Ok(Err(SetError::forbidden().with_description(
@@ -0,0 +1,262 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::mdn::{MdnParseRequest, MdnParseResponse},
+    object::mdn::{Disposition, Mdn},
+    types::{collection::Collection, id::Id, property::Property},
+};
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+use store::query::Filter;
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    auth::AccessToken,
+    submission::report::{field, parse_fields},
+    JMAP,
+};
+
+impl JMAP {
+    pub async fn mdn_parse(
+        &self,
+        request: MdnParseRequest,
+        access_token: &AccessToken,
+    ) -> Result<MdnParseResponse, MethodError> {
+        if request.blob_ids.len() > self.config.mail_parse_max_items {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = MdnParseResponse {
+            account_id: request.account_id,
+            parsed: VecMap::with_capacity(request.blob_ids.len()),
+            not_parsable: vec![],
+            not_found: vec![],
+        };
+
+        for blob_id in request.blob_ids {
+            // Fetch raw message to parse
+            let raw_message = match self.blob_download(&blob_id, access_token).await? {
+                Some(raw_message) => raw_message,
+                None => {
+                    response.not_found.push(blob_id);
+                    continue;
+                }
+            };
+            let mut mdn = if let Some(mdn) = MessageParser::new()
+                .parse(&raw_message)
+                .and_then(|message| parse_mdn(&message))
+            {
+                mdn
+            } else {
+                response.not_parsable.push(blob_id);
+                continue;
+            };
+
+            // Look up the original message
+            if let Some(message_id) = mdn
+                .original_message_id
+                .as_deref()
+                .map(|id| id.trim().trim_matches(['<', '>']))
+                .filter(|id| !id.is_empty())
+            {
+                if let Some(document_id) = self
+                    .filter(
+                        account_id,
+                        Collection::Email,
+                        vec![Filter::eq(Property::MessageId, message_id)],
+                    )
+                    .await?
+                    .results
+                    .max()
+                {
+                    if let Some(thread_id) = self
+                        .get_property::<u32>(
+                            account_id,
+                            Collection::Email,
+                            document_id,
+                            Property::ThreadId,
+                        )
+                        .await?
+                    {
+                        mdn.for_email_id = Id::from_parts(thread_id, document_id).into();
+                    }
+                }
+            }
+
+            response.parsed.append(blob_id, mdn);
+        }
+
+        Ok(response)
+    }
+}
+
+pub(crate) fn parse_mdn(message: &Message<'_>) -> Option<Mdn> {
+    let mut mdn = Mdn {
+        subject: message.subject().map(|subject| subject.to_string()),
+        text_body: message.body_text(0).map(|body| body.into_owned()),
+        include_original_message: false.into(),
+        ..Default::default()
+    };
+
+    for part in message.parts.iter().skip(1) {
+        let (ctype, subtype) = if let Some(content_type) = part.content_type() {
+            (
+                content_type.ctype().to_ascii_lowercase(),
+                content_type
+                    .subtype()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase(),
+            )
+        } else {
+            continue;
+        };
+
+        match (ctype.as_str(), subtype.as_str(), &part.body) {
+            ("message", "disposition-notification" | "global-disposition-notification", _)
+                if mdn.disposition.is_none() =>
+            {
+                let fields = parse_fields(part.contents())
+                    .into_iter()
+                    .flatten()
+                    .collect::<Vec<_>>();
+                mdn.disposition = Disposition::parse_field(field(&fields, "disposition")?)?.into();
+                mdn.reporting_ua = field(&fields, "reporting-ua").map(|v| v.to_string());
+                mdn.mdn_gateway = field(&fields, "mdn-gateway").map(|v| v.to_string());
+                mdn.original_recipient =
+                    field(&fields, "original-recipient").map(|v| v.to_string());
+                mdn.final_recipient = field(&fields, "final-recipient").map(|v| v.to_string());
+                mdn.original_message_id =
+                    field(&fields, "original-message-id").map(|v| v.to_string());
+
+                let mut errors = Vec::new();
+                let mut extension_fields = VecMap::new();
+                for (name, value) in fields {
+                    match name.as_str() {
+                        "error" => errors.push(value),
+                        "disposition"
+                        | "reporting-ua"
+                        | "mdn-gateway"
+                        | "original-recipient"
+                        | "final-recipient"
+                        | "original-message-id" => (),
+                        _ => extension_fields.append(name, value),
+                    }
+                }
+                if !errors.is_empty() {
+                    mdn.error = errors.into();
+                }
+                if !extension_fields.is_empty() {
+                    mdn.extension_fields = extension_fields.into();
+                }
+            }
+            ("message", "rfc822" | "global", _) | (_, _, PartType::Message(_)) => {
+                mdn.include_original_message = true.into();
+            }
+            _ => (),
+        }
+    }
+
+    mdn.disposition.is_some().then_some(mdn)
+}
+
+#[cfg(test)]
+mod tests {
+    use jmap_proto::object::mdn::{ActionMode, Disposition, DispositionType, SendingMode};
+    use mail_parser::MessageParser;
+
+    use super::parse_mdn;
+
+    #[test]
+    fn parse_mdns() {
+        let message = MessageParser::new()
+            .parse(
+                concat!(
+                    "From: jane@example.org\r\n",
+                    "To: john@example.org\r\n",
+                    "Subject: Read: Meeting notes\r\n",
+                    "MIME-Version: 1.0\r\n",
+                    "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+                    "\tboundary=\"b1\"\r\n",
+                    "\r\n",
+                    "--b1\r\n",
+                    "Content-Type: text/plain\r\n",
+                    "\r\n",
+                    "The message was displayed.\r\n",
+                    "--b1\r\n",
+                    "Content-Type: message/disposition-notification\r\n",
+                    "\r\n",
+                    "Reporting-UA: mail.example.org; Example Mail\r\n",
+                    "Original-Recipient: rfc822; jane@example.org\r\n",
+                    "Final-Recipient: rfc822; jane@example.org\r\n",
+                    "Original-Message-ID: <abc@example.org>\r\n",
+                    "Disposition: manual-action/MDN-sent-manually; displayed\r\n",
+                    "X-Custom: value\r\n",
+                    "--b1--\r\n",
+                )
+                .as_bytes(),
+            )
+            .unwrap();
+        let mdn = parse_mdn(&message).unwrap();
+
+        assert_eq!(mdn.subject.as_deref(), Some("Read: Meeting notes"));
+        assert_eq!(
+            mdn.text_body.as_deref().map(|body| body.trim()),
+            Some("The message was displayed.")
+        );
+        assert_eq!(
+            mdn.disposition,
+            Some(Disposition {
+                action_mode: ActionMode::Manual,
+                sending_mode: SendingMode::Manual,
+                type_: DispositionType::Displayed,
+            })
+        );
+        assert_eq!(
+            mdn.reporting_ua.as_deref(),
+            Some("mail.example.org; Example Mail")
+        );
+        assert_eq!(
+            mdn.final_recipient.as_deref(),
+            Some("rfc822; jane@example.org")
+        );
+        assert_eq!(
+            mdn.original_message_id.as_deref(),
+            Some("<abc@example.org>")
+        );
+        assert_eq!(mdn.include_original_message, Some(false));
+        assert_eq!(
+            mdn.extension_fields
+                .as_ref()
+                .and_then(|fields| fields.get("x-custom"))
+                .map(|value| value.as_str()),
+            Some("value")
+        );
+
+        // Messages without a disposition are not MDNs
+        let message = MessageParser::new()
+            .parse(b"Subject: hello\r\n\r\nworld\r\n".as_slice())
+            .unwrap();
+        assert!(parse_mdn(&message).is_none());
+    }
+}
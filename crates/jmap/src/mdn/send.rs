@@ -0,0 +1,430 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{fmt::Write, sync::Arc};
+
+use jmap_proto::{
+    error::{
+        method::MethodError,
+        set::{SetError, SetErrorType},
+    },
+    method::{
+        mdn::{MdnSendRequest, MdnSendResponse},
+        set::{self, SetRequest},
+    },
+    object::{mdn::Mdn, Object},
+    request::{
+        method::{MethodFunction, MethodName, MethodObject},
+        reference::MaybeReference,
+        Call, RequestMethod,
+    },
+    types::{
+        collection::Collection,
+        id::Id,
+        keyword::Keyword,
+        property::Property,
+        value::{SetValue, Value},
+    },
+};
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::{parsers::MessageStream, Header, HeaderName, HeaderValue};
+use smtp::core::{Session, SessionData, State};
+use smtp_proto::{MailFrom, RcptTo};
+use store::write::Bincode;
+use utils::{
+    listener::{stream::NullIo, ServerInstance},
+    map::vec_map::VecMap,
+};
+
+use crate::{email::metadata::MessageMetadata, identity::set::sanitize_email, JMAP};
+
+impl JMAP {
+    pub async fn mdn_send(
+        &self,
+        request: MdnSendRequest,
+        instance: &Arc<ServerInstance>,
+        next_call: &mut Option<Call<RequestMethod>>,
+    ) -> Result<MdnSendResponse, MethodError> {
+        if request.send.len() > self.config.set_max_objects {
+            return Err(MethodError::RequestTooLarge);
+        }
+        let account_id = request.account_id.document_id();
+        let mut response = MdnSendResponse {
+            account_id: request.account_id,
+            sent: VecMap::with_capacity(request.send.len()),
+            not_sent: VecMap::new(),
+        };
+
+        // Obtain the identity the MDNs are sent from
+        let mut identity = self
+            .get_property::<Object<Value>>(
+                account_id,
+                Collection::Identity,
+                request.identity_id.document_id(),
+                Property::Value,
+            )
+            .await?
+            .ok_or_else(|| MethodError::InvalidArguments("Identity not found.".to_string()))?;
+        let identity_email = identity
+            .properties
+            .remove(&Property::Email)
+            .and_then(|value| value.try_unwrap_string())
+            .ok_or_else(|| {
+                MethodError::InvalidArguments("Identity has no email address.".to_string())
+            })?;
+        let identity_name = identity
+            .properties
+            .remove(&Property::Name)
+            .and_then(|value| value.try_unwrap_string())
+            .unwrap_or_default();
+
+        // Send MDNs
+        let mut sent_email_ids = VecMap::new();
+        for (id, mdn) in request.send {
+            match self
+                .send_mdn(account_id, &identity_name, &identity_email, instance, mdn)
+                .await?
+            {
+                Ok((email_id, mdn)) => {
+                    sent_email_ids.append(id.clone(), email_id);
+                    response.sent.append(id, mdn);
+                }
+                Err(err) => {
+                    response.not_sent.append(id, err);
+                }
+            }
+        }
+
+        // Update the original messages, adding the $MDNSent keyword
+        if !sent_email_ids.is_empty() {
+            let mut update: VecMap<_, Object<SetValue>> =
+                VecMap::with_capacity(sent_email_ids.len());
+            for (id, object) in request.on_success_update_email.unwrap_or_default() {
+                let email_id = match id {
+                    MaybeReference::Value(id) => id,
+                    MaybeReference::Reference(id_ref) => {
+                        if let Some(email_id) = sent_email_ids.get(&id_ref) {
+                            *email_id
+                        } else {
+                            continue;
+                        }
+                    }
+                };
+                let current = update.get_mut_or_insert_with(email_id, || Object {
+                    properties: VecMap::with_capacity(object.properties.len() + 1),
+                });
+                for (property, value) in object.properties {
+                    current.properties.append(property, value);
+                }
+            }
+            for email_id in sent_email_ids.values() {
+                update
+                    .get_mut_or_insert_with(*email_id, || Object {
+                        properties: VecMap::with_capacity(1),
+                    })
+                    .properties
+                    .append(
+                        Property::Keywords,
+                        SetValue::Patch(vec![Value::Keyword(Keyword::MdnSent), Value::Bool(true)]),
+                    );
+            }
+
+            *next_call = Call {
+                id: String::new(),
+                name: MethodName::new(MethodObject::Email, MethodFunction::Set),
+                method: RequestMethod::Set(SetRequest {
+                    account_id: request.account_id,
+                    if_in_state: None,
+                    create: None,
+                    update: update.into(),
+                    destroy: None,
+                    arguments: set::RequestArguments::Email,
+                }),
+            }
+            .into();
+        }
+
+        Ok(response)
+    }
+
+    async fn send_mdn(
+        &self,
+        account_id: u32,
+        identity_name: &str,
+        identity_email: &str,
+        instance: &Arc<ServerInstance>,
+        mdn: Mdn,
+    ) -> Result<Result<(Id, Mdn), SetError>, MethodError> {
+        // Validate request
+        let (email_id, disposition) = match (mdn.for_email_id, mdn.disposition) {
+            (Some(email_id), Some(disposition)) => (email_id, disposition),
+            _ => {
+                return Ok(Err(SetError::invalid_properties().with_description(
+                    "forEmailId and disposition properties are required.",
+                )));
+            }
+        };
+
+        // Obtain message metadata
+        let document_id = email_id.document_id();
+        let metadata = if let Some(metadata) = self
+            .get_property::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::BodyStructure,
+            )
+            .await?
+        {
+            metadata.inner
+        } else {
+            return Ok(Err(
+                SetError::not_found().with_description("Email not found.")
+            ));
+        };
+
+        // Make sure an MDN was not already sent for this message
+        if self
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::Keywords,
+                Keyword::MdnSent,
+            )
+            .await?
+            .is_some_and(|ids| ids.contains(document_id))
+        {
+            return Ok(Err(SetError::new(SetErrorType::MdnAlreadySent)
+                .with_description("An MDN was already sent for this message.")));
+        }
+
+        // Obtain the address the MDN is to be sent to
+        let headers = &metadata.contents.root_part().headers;
+        let raw_headers = &metadata.raw_headers;
+        let rcpt_to = if let Some(rcpt_to) =
+            raw_header(headers, raw_headers, "Disposition-Notification-To").and_then(|value| {
+                match MessageStream::new(value).parse_address() {
+                    HeaderValue::Address(addr) => addr
+                        .iter()
+                        .find_map(|addr| addr.address().and_then(sanitize_email)),
+                    _ => None,
+                }
+            }) {
+            rcpt_to
+        } else {
+            return Ok(Err(SetError::invalid_properties().with_description(
+                "Email does not contain a Disposition-Notification-To header.",
+            )));
+        };
+        let original_subject =
+            headers
+                .iter()
+                .find_map(|header| match (&header.name, &header.value) {
+                    (HeaderName::Subject, HeaderValue::Text(subject)) => Some(subject.as_ref()),
+                    _ => None,
+                });
+        let message_id = headers
+            .iter()
+            .find_map(|header| match (&header.name, &header.value) {
+                (HeaderName::MessageId, HeaderValue::Text(id)) => Some(id.to_string()),
+                (HeaderName::MessageId, HeaderValue::TextList(ids)) => {
+                    ids.first().map(|id| id.to_string())
+                }
+                _ => None,
+            });
+        let original_recipient = raw_header(headers, raw_headers, "Original-Recipient")
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|value| !value.is_empty());
+
+        // Build the disposition notification
+        let sent = Mdn {
+            include_original_message: mdn.include_original_message.unwrap_or(false).into(),
+            reporting_ua: mdn
+                .reporting_ua
+                .unwrap_or_else(|| format!("{}; Stalwart JMAP", instance.hostname))
+                .into(),
+            mdn_gateway: mdn.mdn_gateway,
+            original_recipient,
+            final_recipient: format!("rfc822; {identity_email}").into(),
+            original_message_id: message_id.as_ref().map(|id| format!("<{id}>")),
+            ..Default::default()
+        };
+        let mut report = String::with_capacity(128);
+        let _ = write!(
+            &mut report,
+            "Reporting-UA: {}\r\n",
+            sent.reporting_ua.as_deref().unwrap_or_default()
+        );
+        if let Some(mdn_gateway) = &sent.mdn_gateway {
+            let _ = write!(&mut report, "MDN-Gateway: {}\r\n", clean_value(mdn_gateway));
+        }
+        if let Some(original_recipient) = &sent.original_recipient {
+            let _ = write!(&mut report, "Original-Recipient: {original_recipient}\r\n");
+        }
+        let _ = write!(
+            &mut report,
+            "Final-Recipient: {}\r\n",
+            sent.final_recipient.as_deref().unwrap_or_default()
+        );
+        if let Some(original_message_id) = &sent.original_message_id {
+            let _ = write!(
+                &mut report,
+                "Original-Message-ID: {original_message_id}\r\n"
+            );
+        }
+        let _ = write!(&mut report, "Disposition: {disposition}\r\n");
+        for error in mdn.error.iter().flatten() {
+            let _ = write!(&mut report, "Error: {}\r\n", clean_value(error));
+        }
+        for (name, value) in mdn.extension_fields.iter().flat_map(|fields| fields.iter()) {
+            if !name.is_empty() && name.bytes().all(|ch| ch.is_ascii_graphic() && ch != b':') {
+                let _ = write!(&mut report, "{name}: {}\r\n", clean_value(value));
+            }
+        }
+
+        let subject = mdn.subject.unwrap_or_else(|| {
+            format!(
+                "Disposition notification: {}",
+                original_subject.unwrap_or("(no subject)")
+            )
+        });
+        let text_body = mdn.text_body.unwrap_or_else(|| {
+            format!(
+                "This is a disposition notification for the message \"{}\".\r\n\r\nDisposition: {}\r\n",
+                original_subject.unwrap_or("(no subject)"),
+                disposition.type_.as_str()
+            )
+        });
+        let mut parts = vec![
+            MimePart::new(
+                ContentType::new("text/plain"),
+                BodyPart::Text(text_body.into()),
+            ),
+            MimePart::new(
+                ContentType::new("message/disposition-notification"),
+                BodyPart::Text(report.into()),
+            ),
+        ];
+        if sent.include_original_message == Some(true) {
+            match self.get_blob(&metadata.blob_hash, 0..usize::MAX).await? {
+                Some(original) if original.len() < self.config.mail_max_size => {
+                    parts.push(MimePart::new(
+                        ContentType::new("message/rfc822"),
+                        BodyPart::Binary(original.into()),
+                    ));
+                }
+                Some(_) => {
+                    return Ok(Err(SetError::too_large().with_description(
+                        "Original message is too large to be included.",
+                    )));
+                }
+                None => {
+                    return Ok(Err(
+                        SetError::not_found().with_description("Blob for email not found.")
+                    ));
+                }
+            }
+        }
+        let mut builder = MessageBuilder::new()
+            .from((identity_name, identity_email))
+            .to(rcpt_to.as_str())
+            .header("Auto-Submitted", HeaderType::Text("auto-replied".into()))
+            .message_id(format!("{}@{}", make_boundary("."), instance.hostname))
+            .subject(subject.as_str());
+        if let Some(message_id) = &message_id {
+            builder = builder
+                .in_reply_to(message_id.as_str())
+                .references(message_id.as_str());
+        }
+        let message = builder
+            .body(MimePart::new(
+                ContentType::new("multipart/report")
+                    .attribute("report-type", "disposition-notification"),
+                BodyPart::Multipart(parts),
+            ))
+            .write_to_vec()
+            .unwrap_or_default();
+
+        // Begin local SMTP session
+        let mut session =
+            Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
+
+        // MAIL FROM
+        let _ = session
+            .handle_mail_from(MailFrom {
+                address: identity_email.to_string(),
+                ..Default::default()
+            })
+            .await;
+        if let Some(error) = session.has_failed() {
+            return Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
+                .with_description(format!(
+                    "Server rejected MAIL-FROM: {}",
+                    error.trim()
+                ))));
+        }
+
+        // RCPT TO
+        let _ = session
+            .handle_rcpt_to(RcptTo {
+                address: rcpt_to,
+                ..Default::default()
+            })
+            .await;
+        if let Some(error) = session.has_failed() {
+            return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                .with_description(format!(
+                    "Server rejected RCPT-TO: {}",
+                    error.trim()
+                ))));
+        }
+
+        // DATA
+        session.data.message = message;
+        let response = session.queue_message().await;
+        if !matches!(session.state, State::Accepted(_)) {
+            return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                .with_description(format!(
+                    "Server rejected DATA: {}",
+                    std::str::from_utf8(&response).unwrap_or_default().trim()
+                ))));
+        }
+
+        Ok(Ok((email_id, sent)))
+    }
+}
+
+fn raw_header<'x>(headers: &[Header<'_>], raw_headers: &'x [u8], name: &str) -> Option<&'x [u8]> {
+    headers
+        .iter()
+        .find(|header| header.name.as_str().eq_ignore_ascii_case(name))
+        .and_then(|header| raw_headers.get(header.offset_start..header.offset_end))
+}
+
+fn clean_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
@@ -27,7 +27,14 @@ use store::ahash::{AHashMap, AHashSet};
 use tokio::sync::mpsc;
 use utils::{config::Config, UnwrapFailure};
 
-use crate::{api::StateChangeResponse, services::IPC_CHANNEL_BUFFER, LONG_SLUMBER};
+use crate::{
+    api::StateChangeResponse,
+    services::{
+        retry::{Attempt, RetryPolicy, RetryState},
+        IPC_CHANNEL_BUFFER,
+    },
+    LONG_SLUMBER,
+};
 
 use super::{ece::ece_encrypt, EncryptionKeys, Event, PushServer, PushUpdate};
 
@@ -59,6 +66,11 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
     let push_throttle: Duration = settings
         .property_or_default("jmap.push.throttle", "1s")
         .failed("Invalid configuration");
+    let push_policy = RetryPolicy {
+        throttle: push_throttle,
+        attempts_interval: push_attempt_interval,
+        attempts_max: push_attempts_max,
+    };
 
     tokio::spawn(async move {
         let mut subscriptions = AHashMap::default();
@@ -133,11 +145,8 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
                                         entry.insert(PushServer {
                                             url,
                                             keys,
-                                            num_attempts: 0,
-                                            last_request: Instant::now()
-                                                - (push_throttle + Duration::from_millis(1)),
+                                            retry: RetryState::new(&push_policy),
                                             state_changes: Vec::new(),
-                                            in_flight: false,
                                         });
                                     }
                                 }
@@ -151,15 +160,8 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
                         for id in ids {
                             if let Some(subscription) = subscriptions.get_mut(&id) {
                                 subscription.state_changes.push(state_change.clone());
-                                let last_request = subscription.last_request.elapsed();
 
-                                if !subscription.in_flight
-                                    && ((subscription.num_attempts == 0
-                                        && last_request > push_throttle)
-                                        || ((1..push_attempts_max)
-                                            .contains(&subscription.num_attempts)
-                                            && last_request > push_attempt_interval))
-                                {
+                                if subscription.retry.next_attempt(&push_policy) == Attempt::Send {
                                     subscription.send(id, push_tx.clone(), push_timeout);
                                     retry_ids.remove(&id);
                                 } else {
@@ -170,22 +172,36 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
                             }
                         }
                     }
+                    Event::Notify { ids, notification } => {
+                        let body = serde_json::to_string(&notification).unwrap_or_default();
+                        for id in ids {
+                            if let Some(subscription) = subscriptions.get(&id) {
+                                let url = subscription.url.clone();
+                                let keys = subscription.keys.clone();
+                                let body = body.clone();
+
+                                // Notifications are delivered once, without retries
+                                tokio::spawn(async move {
+                                    http_request(url, body, keys, push_timeout).await;
+                                });
+                            } else {
+                                tracing::debug!("No push subscription found for id: {}", id);
+                            }
+                        }
+                    }
                     Event::Reset => {
                         subscriptions.clear();
                     }
                     Event::DeliverySuccess { id } => {
                         if let Some(subscription) = subscriptions.get_mut(&id) {
-                            subscription.num_attempts = 0;
-                            subscription.in_flight = false;
+                            subscription.retry.succeeded();
                             retry_ids.remove(&id);
                         }
                     }
                     Event::DeliveryFailure { id, state_changes } => {
                         if let Some(subscription) = subscriptions.get_mut(&id) {
-                            subscription.last_request = Instant::now();
-                            subscription.num_attempts += 1;
+                            subscription.retry.failed();
                             subscription.state_changes.extend(state_changes);
-                            subscription.in_flight = false;
                             retry_ids.insert(id);
                         }
                     }
@@ -204,17 +220,12 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
 
                     for retry_id in &retry_ids {
                         if let Some(subscription) = subscriptions.get_mut(retry_id) {
-                            let last_request = subscription.last_request.elapsed();
-
-                            if !subscription.in_flight
-                                && ((subscription.num_attempts == 0
-                                    && last_request >= push_throttle)
-                                    || (subscription.num_attempts > 0
-                                        && last_request >= push_attempt_interval))
-                            {
-                                if subscription.num_attempts < push_attempts_max {
+                            match subscription.retry.next_attempt(&push_policy) {
+                                Attempt::Send => {
                                     subscription.send(*retry_id, push_tx.clone(), push_timeout);
-                                } else {
+                                    remove_ids.push(*retry_id);
+                                }
+                                Attempt::Exhausted => {
                                     tracing::debug!(
                                         concat!(
                                             "Failed to deliver push subscription: ",
@@ -223,9 +234,10 @@ pub fn spawn_push_manager(settings: &Config) -> mpsc::Sender<Event> {
                                         subscription.url
                                     );
                                     subscription.state_changes.clear();
-                                    subscription.num_attempts = 0;
+                                    subscription.retry.reset();
+                                    remove_ids.push(*retry_id);
                                 }
-                                remove_ids.push(*retry_id);
+                                Attempt::Wait(_) | Attempt::InFlight => (),
                             }
                         } else {
                             remove_ids.push(*retry_id);
@@ -260,8 +272,7 @@ impl PushServer {
         let keys = self.keys.clone();
         let state_changes = std::mem::take(&mut self.state_changes);
 
-        self.in_flight = true;
-        self.last_request = Instant::now();
+        self.retry.sent();
 
         tokio::spawn(async move {
             let mut response = StateChangeResponse::new();
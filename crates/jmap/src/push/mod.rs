@@ -26,11 +26,11 @@ pub mod get;
 pub mod manager;
 pub mod set;
 
-use std::time::Instant;
-
 use jmap_proto::types::{id::Id, state::StateChange, type_state::DataType};
 use utils::map::bitmap::Bitmap;
 
+use crate::services::retry::RetryState;
+
 #[derive(Debug)]
 pub enum UpdateSubscription {
     Unverified {
@@ -73,9 +73,34 @@ pub enum Event {
         id: Id,
         state_changes: Vec<StateChange>,
     },
+    Notify {
+        ids: Vec<Id>,
+        notification: PushNotification,
+    },
     Reset,
 }
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy)]
+pub enum PushNotificationType {
+    SieveNotification,
+}
+
+/// Notification requested by a Sieve `notify` action.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct PushNotification {
+    #[serde(rename = "@type")]
+    pub type_: PushNotificationType,
+    #[serde(rename = "accountId")]
+    pub account_id: Id,
+    #[serde(rename = "emailId")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_id: Option<Id>,
+    pub importance: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub message: String,
+}
+
 #[derive(Debug)]
 pub enum PushUpdate {
     Verify {
@@ -99,8 +124,6 @@ pub enum PushUpdate {
 pub struct PushServer {
     url: String,
     keys: Option<EncryptionKeys>,
-    num_attempts: u32,
-    last_request: Instant,
+    retry: RetryState,
     state_changes: Vec<StateChange>,
-    in_flight: bool,
 }
@@ -0,0 +1,713 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::net::IpAddr;
+
+use serde_json::json;
+use store::{
+    blake3,
+    write::{now, BatchBuilder, Bincode, ValueClass},
+    Deserialize, IterateParams, Serialize, ValueKey,
+};
+
+use utils::{
+    url_params::UrlParams,
+    webhooks::{WebhookPayload, WebhookType},
+};
+
+use crate::JMAP;
+
+const MAX_APPEND_ATTEMPTS: usize = 10;
+
+// Entries are numbered from 1, the first and last ids of the
+// range hold the purge checkpoint and the chain head respectively.
+const AUDIT_CHECKPOINT_ID: u64 = 0;
+const AUDIT_HEAD_ID: u64 = u64::MAX;
+const AUDIT_FIRST_ID: u64 = AUDIT_CHECKPOINT_ID + 1;
+const AUDIT_LAST_ID: u64 = AUDIT_HEAD_ID - 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    AuthFailure,
+    OAuthGrant,
+    PasswordChange,
+    PrincipalCreate,
+    PrincipalUpdate,
+    PrincipalDelete,
+    DomainCreate,
+    DomainUpdate,
+    DomainDelete,
+    ListUpdate,
+    ListModerate,
+    SettingsUpdate,
+    SettingsDelete,
+    SettingsReload,
+    StoreMaintenance,
+    QueueUpdate,
+    QueueDelete,
+    ReportDelete,
+}
+
+pub struct AuditEvent {
+    action: AuditAction,
+    actor: Option<String>,
+    remote_ip: Option<IpAddr>,
+    target: Option<String>,
+    diff: Option<String>,
+    success: bool,
+}
+
+/// Audit log entries are stored sequentially, each one including the
+/// MAC of the previous entry so that altering or removing any entry
+/// breaks the chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub action: String,
+    pub actor: Option<String>,
+    pub remote_ip: Option<IpAddr>,
+    pub target: Option<String>,
+    pub diff: Option<String>,
+    pub success: bool,
+    pub prev_hash: [u8; 32],
+    pub hash: [u8; 32],
+}
+
+/// Authenticated pointer into the chain, used to record both the last
+/// purged entry and the most recently appended one.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct AuditMarker {
+    pub id: u64,
+    pub hash: [u8; 32],
+    pub mac: [u8; 32],
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target: Option<String>,
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+}
+
+#[derive(Debug, Default, serde::Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub total: u64,
+    #[serde(rename = "checkpointId")]
+    pub checkpoint_id: Option<u64>,
+    #[serde(rename = "firstId")]
+    pub first_id: Option<u64>,
+    #[serde(rename = "lastId")]
+    pub last_id: Option<u64>,
+    #[serde(rename = "lastHash")]
+    pub last_hash: Option<String>,
+    #[serde(rename = "failedId")]
+    pub failed_id: Option<u64>,
+}
+
+impl JMAP {
+    pub async fn audit(&self, event: AuditEvent) {
+        // Notify webhooks about authentication failures and principal changes
+        let typ = match event.action {
+            AuditAction::AuthFailure => Some(WebhookType::AuthFailure),
+            AuditAction::PrincipalCreate if event.success => Some(WebhookType::PrincipalCreated),
+            AuditAction::PrincipalUpdate if event.success => Some(WebhookType::PrincipalUpdated),
+            AuditAction::PrincipalDelete if event.success => Some(WebhookType::PrincipalDeleted),
+            _ => None,
+        };
+        if let Some(typ) = typ {
+            self.smtp.webhooks.send(typ, || match typ {
+                WebhookType::AuthFailure => WebhookPayload::AuthFailure {
+                    login: event.actor.clone(),
+                    remote_ip: event.remote_ip,
+                },
+                _ => WebhookPayload::Principal {
+                    name: event.target.clone(),
+                    actor: event.actor.clone(),
+                    remote_ip: event.remote_ip,
+                    changes: event
+                        .diff
+                        .as_deref()
+                        .and_then(|diff| serde_json::from_str(diff).ok()),
+                },
+            });
+        }
+
+        if !self.config.audit_enable {
+            return;
+        }
+
+        let action = event.action;
+        if let Err(err) = self.audit_append(event).await {
+            tracing::error!(
+                context = "audit",
+                event = "error",
+                action = action.as_str(),
+                reason = ?err,
+                "Failed to write audit log entry"
+            );
+        }
+    }
+
+    async fn audit_append(&self, event: AuditEvent) -> store::Result<()> {
+        // Appends are serialized so each entry links to its predecessor
+        let mut head = self.audit_head.lock().await;
+        let mut attempts = 0;
+
+        loop {
+            let (last_id, last_hash) = match *head {
+                Some(head) => head,
+                None => self
+                    .audit_last_entry()
+                    .await?
+                    .map(|entry| (entry.id, entry.hash))
+                    .unwrap_or((0, [0u8; 32])),
+            };
+
+            let mut entry = AuditEntry {
+                id: last_id + 1,
+                timestamp: now(),
+                action: event.action.as_str().to_string(),
+                actor: event.actor.clone(),
+                remote_ip: event.remote_ip,
+                target: event.target.clone(),
+                diff: event.diff.clone(),
+                success: event.success,
+                prev_hash: last_hash,
+                hash: [0u8; 32],
+            };
+            entry.hash = entry.digest(&self.config.audit_key);
+
+            // Make sure no other node has appended an entry with the same id
+            let mut batch = BatchBuilder::new();
+            batch
+                .assert_value(ValueClass::Audit(entry.id), ())
+                .set(
+                    ValueClass::Audit(entry.id),
+                    Bincode::new(entry.clone()).serialize(),
+                )
+                .set(
+                    ValueClass::Audit(AUDIT_HEAD_ID),
+                    Bincode::new(AuditMarker::new(
+                        &self.config.audit_key,
+                        entry.id,
+                        entry.hash,
+                    ))
+                    .serialize(),
+                );
+
+            match self.store.write(batch.build()).await {
+                Ok(_) => {
+                    *head = Some((entry.id, entry.hash));
+                    return Ok(());
+                }
+                Err(store::Error::AssertValueFailed) if attempts < MAX_APPEND_ATTEMPTS => {
+                    *head = None;
+                    attempts += 1;
+                }
+                Err(err) => {
+                    *head = None;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    async fn audit_last_entry(&self) -> store::Result<Option<AuditEntry>> {
+        let mut last_entry = None;
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(AUDIT_LAST_ID)),
+                )
+                .descending(),
+                |_, value| {
+                    last_entry = Bincode::<AuditEntry>::deserialize(value)?.inner.into();
+                    Ok(false)
+                },
+            )
+            .await?;
+
+        // The log might be empty after a purge, continue from the checkpoint
+        if last_entry.is_none() {
+            if let Some(checkpoint) = self.audit_marker(AUDIT_CHECKPOINT_ID).await? {
+                return Ok(Some(AuditEntry {
+                    id: checkpoint.id,
+                    hash: checkpoint.hash,
+                    ..Default::default()
+                }));
+            }
+        }
+
+        Ok(last_entry)
+    }
+
+    async fn audit_marker(&self, id: u64) -> store::Result<Option<AuditMarker>> {
+        self.store
+            .get_value::<Bincode<AuditMarker>>(ValueKey::from(ValueClass::Audit(id)))
+            .await
+            .map(|marker| marker.map(|marker| marker.inner))
+    }
+
+    pub async fn audit_query(
+        &self,
+        filter: &AuditFilter,
+        offset: usize,
+        limit: usize,
+    ) -> store::Result<(usize, Vec<AuditEntry>)> {
+        let mut offset = offset;
+        let mut total = 0;
+        let mut results = Vec::new();
+
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(AUDIT_LAST_ID)),
+                )
+                .descending(),
+                |_, value| {
+                    let entry = Bincode::<AuditEntry>::deserialize(value)?.inner;
+                    if filter.matches(&entry) {
+                        if offset == 0 {
+                            if limit == 0 || results.len() < limit {
+                                results.push(entry);
+                            }
+                        } else {
+                            offset -= 1;
+                        }
+                        total += 1;
+                    }
+
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok((total, results))
+    }
+
+    pub async fn audit_export(&self, filter: &AuditFilter) -> store::Result<Vec<u8>> {
+        let mut export = Vec::new();
+
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(AUDIT_LAST_ID)),
+                )
+                .ascending(),
+                |_, value| {
+                    let entry = Bincode::<AuditEntry>::deserialize(value)?.inner;
+                    if filter.matches(&entry) {
+                        export.extend_from_slice(entry.to_json().to_string().as_bytes());
+                        export.push(b'\n');
+                    }
+
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(export)
+    }
+
+    pub async fn audit_verify(&self) -> store::Result<AuditVerification> {
+        // Block local appends so the head marker matches the last entry
+        let _head = self.audit_head.lock().await;
+        let key = &self.config.audit_key;
+        let mut result = AuditVerification {
+            valid: true,
+            ..Default::default()
+        };
+
+        // The chain starts at the last purged entry, or at the genesis entry
+        let checkpoint = self.audit_marker(AUDIT_CHECKPOINT_ID).await?;
+        let head = self.audit_marker(AUDIT_HEAD_ID).await?;
+        for marker in [&checkpoint, &head].into_iter().flatten() {
+            if !marker.is_valid(key) {
+                result.valid = false;
+                result.failed_id = marker.id.into();
+                return Ok(result);
+            }
+        }
+        result.checkpoint_id = checkpoint.as_ref().map(|checkpoint| checkpoint.id);
+        let mut last = checkpoint
+            .map(|checkpoint| (checkpoint.id, checkpoint.hash))
+            .unwrap_or((0, [0u8; 32]));
+
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(AUDIT_LAST_ID)),
+                )
+                .ascending(),
+                |_, value| {
+                    let entry = Bincode::<AuditEntry>::deserialize(value)?.inner;
+
+                    // Skip entries left behind by an interrupted purge
+                    if entry.id <= last.0 && result.first_id.is_none() {
+                        return Ok(true);
+                    }
+
+                    if !entry.is_linked(last) || entry.hash != entry.digest(key) {
+                        result.valid = false;
+                        result.failed_id = (last.0 + 1).min(entry.id).into();
+                        return Ok(false);
+                    }
+
+                    if result.first_id.is_none() {
+                        result.first_id = entry.id.into();
+                    }
+                    result.total += 1;
+                    last = (entry.id, entry.hash);
+
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        // Entries removed from the end of the chain no longer match the head
+        if result.valid {
+            let head = head
+                .map(|head| (head.id, head.hash))
+                .unwrap_or((0, [0u8; 32]));
+            if head != last {
+                result.valid = false;
+                result.failed_id = (last.0.min(head.0) + 1).into();
+            }
+        }
+
+        if result.total > 0 {
+            result.last_id = last.0.into();
+            result.last_hash = blake3::Hash::from(last.1).to_hex().to_string().into();
+        }
+
+        Ok(result)
+    }
+
+    pub async fn purge_audit_log(&self) {
+        if let Some(retention) = self.config.audit_retention {
+            if let Err(err) = self
+                .audit_purge(now().saturating_sub(retention.as_secs()))
+                .await
+            {
+                tracing::error!(
+                    context = "audit",
+                    event = "error",
+                    reason = ?err,
+                    "Failed to purge audit log"
+                );
+            }
+        }
+    }
+
+    /// Removes all entries older than `expires`, recording the last removed
+    /// entry as a checkpoint from which the chain can still be verified.
+    pub async fn audit_purge(&self, expires: u64) -> store::Result<Option<u64>> {
+        let _head = self.audit_head.lock().await;
+        let key = &self.config.audit_key;
+        let mut last = match self.audit_marker(AUDIT_CHECKPOINT_ID).await? {
+            Some(checkpoint) if checkpoint.is_valid(key) => (checkpoint.id, checkpoint.hash),
+            Some(checkpoint) => {
+                tracing::error!(
+                    context = "audit",
+                    event = "error",
+                    id = checkpoint.id,
+                    "Audit log checkpoint failed verification, skipping purge"
+                );
+                return Ok(None);
+            }
+            None => (0, [0u8; 32]),
+        };
+
+        // Entries are sorted chronologically, only expired entries that
+        // are part of a valid chain are purged
+        let mut last_expired = None;
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(AUDIT_LAST_ID)),
+                )
+                .ascending(),
+                |_, value| {
+                    let entry = Bincode::<AuditEntry>::deserialize(value)?.inner;
+                    if entry.timestamp >= expires {
+                        Ok(false)
+                    } else if entry.id <= last.0 && last_expired.is_none() {
+                        Ok(true)
+                    } else if entry.is_linked(last) && entry.hash == entry.digest(key) {
+                        last = (entry.id, entry.hash);
+                        last_expired = entry.id.into();
+                        Ok(true)
+                    } else {
+                        tracing::error!(
+                            context = "audit",
+                            event = "error",
+                            id = entry.id,
+                            "Audit log entry failed verification, stopping purge"
+                        );
+                        Ok(false)
+                    }
+                },
+            )
+            .await?;
+
+        if let Some(last_expired) = last_expired {
+            // Store the checkpoint first, leftover entries are ignored on verification
+            let mut batch = BatchBuilder::new();
+            batch.set(
+                ValueClass::Audit(AUDIT_CHECKPOINT_ID),
+                Bincode::new(AuditMarker::new(key, last.0, last.1)).serialize(),
+            );
+            self.store.write(batch.build()).await?;
+            self.store
+                .delete_range(
+                    ValueKey::from(ValueClass::Audit(AUDIT_FIRST_ID)),
+                    ValueKey::from(ValueClass::Audit(last_expired + 1)),
+                )
+                .await?;
+        }
+
+        Ok(last_expired)
+    }
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        AuditEvent {
+            action,
+            actor: None,
+            remote_ip: None,
+            target: None,
+            diff: None,
+            success: true,
+        }
+    }
+
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    pub fn with_remote_ip(mut self, remote_ip: IpAddr) -> Self {
+        self.remote_ip = Some(remote_ip);
+        self
+    }
+
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn with_diff(mut self, diff: Option<String>) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    pub fn with_success(mut self, success: bool) -> Self {
+        self.success = success;
+        self
+    }
+}
+
+impl AuditEntry {
+    pub fn digest(&self, key: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(key);
+        hasher.update(&self.prev_hash);
+        hasher.update(&self.id.to_be_bytes());
+        hasher.update(&self.timestamp.to_be_bytes());
+        hasher.update(&[self.success as u8]);
+
+        let remote_ip = self.remote_ip.map(|ip| ip.to_string());
+        for field in [
+            Some(self.action.as_str()),
+            self.actor.as_deref(),
+            remote_ip.as_deref(),
+            self.target.as_deref(),
+            self.diff.as_deref(),
+        ] {
+            if let Some(field) = field {
+                hasher.update(&[1u8]);
+                hasher.update(&(field.len() as u64).to_be_bytes());
+                hasher.update(field.as_bytes());
+            } else {
+                hasher.update(&[0u8]);
+            }
+        }
+
+        *hasher.finalize().as_bytes()
+    }
+
+    pub fn is_linked(&self, (prev_id, prev_hash): (u64, [u8; 32])) -> bool {
+        self.id == prev_id + 1 && self.prev_hash == prev_hash
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "timestamp": self.timestamp,
+            "action": self.action,
+            "actor": self.actor,
+            "remoteIp": self.remote_ip,
+            "target": self.target,
+            "diff": self.diff.as_deref().map(|diff| {
+                serde_json::from_str::<serde_json::Value>(diff)
+                    .unwrap_or_else(|_| serde_json::Value::String(diff.to_string()))
+            }),
+            "success": self.success,
+            "prevHash": blake3::Hash::from(self.prev_hash).to_hex().as_str(),
+            "hash": blake3::Hash::from(self.hash).to_hex().as_str(),
+        })
+    }
+}
+
+impl AuditMarker {
+    pub fn new(key: &[u8; 32], id: u64, hash: [u8; 32]) -> Self {
+        AuditMarker {
+            id,
+            hash,
+            mac: Self::digest(key, id, &hash),
+        }
+    }
+
+    pub fn is_valid(&self, key: &[u8; 32]) -> bool {
+        self.mac == Self::digest(key, self.id, &self.hash)
+    }
+
+    fn digest(key: &[u8; 32], id: u64, hash: &[u8; 32]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new_keyed(key);
+        hasher.update(b"marker");
+        hasher.update(&id.to_be_bytes());
+        hasher.update(hash);
+        *hasher.finalize().as_bytes()
+    }
+}
+
+impl From<&UrlParams<'_>> for AuditFilter {
+    fn from(params: &UrlParams<'_>) -> Self {
+        AuditFilter {
+            actor: params.get("actor").map(|actor| actor.to_string()),
+            action: params.get("action").map(|action| action.to_string()),
+            target: params.get("target").map(|target| target.to_string()),
+            after: params.parse("after"),
+            before: params.parse("before"),
+        }
+    }
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| entry.actor.as_ref() == Some(actor))
+            && self
+                .action
+                .as_ref()
+                .is_none_or(|action| entry.action.starts_with(action.as_str()))
+            && self
+                .target
+                .as_ref()
+                .is_none_or(|target| entry.target.as_ref() == Some(target))
+            && self.after.is_none_or(|after| entry.timestamp >= after)
+            && self.before.is_none_or(|before| entry.timestamp < before)
+    }
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::AuthFailure => "auth.failure",
+            AuditAction::OAuthGrant => "oauth.grant",
+            AuditAction::PasswordChange => "password.change",
+            AuditAction::PrincipalCreate => "principal.create",
+            AuditAction::PrincipalUpdate => "principal.update",
+            AuditAction::PrincipalDelete => "principal.delete",
+            AuditAction::DomainCreate => "domain.create",
+            AuditAction::DomainUpdate => "domain.update",
+            AuditAction::DomainDelete => "domain.delete",
+            AuditAction::ListUpdate => "list.update",
+            AuditAction::ListModerate => "list.moderate",
+            AuditAction::SettingsUpdate => "settings.update",
+            AuditAction::SettingsDelete => "settings.delete",
+            AuditAction::SettingsReload => "settings.reload",
+            AuditAction::StoreMaintenance => "store.maintenance",
+            AuditAction::QueueUpdate => "queue.update",
+            AuditAction::QueueDelete => "queue.delete",
+            AuditAction::ReportDelete => "report.delete",
+        }
+    }
+}
+
+/// Derives the key used to authenticate the audit chain, falling back to the
+/// OAuth key so that entries remain verifiable across restarts.
+pub fn parse_audit_key(
+    settings: &utils::config::Config,
+    is_enabled: bool,
+) -> Result<[u8; 32], String> {
+    match settings
+        .value("audit.key")
+        .or_else(|| settings.value("oauth.key"))
+    {
+        Some(key) => Ok(blake3::derive_key(
+            "Stalwart Mail Server audit log",
+            key.as_bytes(),
+        )),
+        None if is_enabled => Err(
+            "Audit log is enabled but neither 'audit.key' nor 'oauth.key' are configured"
+                .to_string(),
+        ),
+        None => Ok([0; 32]),
+    }
+}
+
+/// Removes any secrets from a request body before it is recorded.
+pub fn redact_secrets(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let is_secret_update = map
+                .get("field")
+                .and_then(|field| field.as_str())
+                .is_some_and(|field| field == "secrets" || field == "password");
+            for (key, value) in map.iter_mut() {
+                if key == "secrets" || key == "password" || (is_secret_update && key == "value") {
+                    *value = serde_json::Value::String("[redacted]".to_string());
+                } else {
+                    redact_secrets(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                redact_secrets(value);
+            }
+        }
+        _ => (),
+    }
+}
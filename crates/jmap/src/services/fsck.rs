@@ -0,0 +1,818 @@
+/*
+ * Copyright (c) 2023, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    object::Object,
+    types::{
+        blob::BlobId, collection::Collection, id::Id, keyword::Keyword, property::Property,
+        value::Value,
+    },
+};
+use store::{
+    ahash::{AHashMap, AHashSet},
+    roaring::RoaringBitmap,
+    write::{
+        key::DeserializeBigEndian, log::ChangeLogBuilder, now, BatchBuilder, Bincode, BitmapClass,
+        BlobOp, DirectoryClass, Operation, TagValue, ValueClass, F_VALUE,
+    },
+    BitmapKey, BlobClass, Deserialize, IndexKeyPrefix, IterateParams, Serialize, ValueKey, U32_LEN,
+};
+
+use crate::{
+    email::metadata::MessageMetadata, mailbox::UidMailbox, sieve::set::ObjectBlobId, JMAP,
+};
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct FsckReport {
+    #[serde(rename = "accountId")]
+    pub account_id: u32,
+    pub issues: Vec<FsckIssue>,
+    pub repaired: bool,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum FsckIssue {
+    OrphanedDocument {
+        id: u32,
+    },
+    MissingValue {
+        id: u32,
+        property: Property,
+    },
+    MailboxBitmap {
+        id: u32,
+        mailbox: u32,
+        expected: bool,
+    },
+    KeywordBitmap {
+        id: u32,
+        keyword: Keyword,
+        expected: bool,
+    },
+    ThreadBitmap {
+        id: u32,
+        thread: u32,
+        expected: bool,
+    },
+    MissingMailbox {
+        mailbox: u32,
+    },
+    MissingThread {
+        thread: u32,
+    },
+    OrphanedThread {
+        thread: u32,
+    },
+    MissingIndex {
+        id: u32,
+        property: Property,
+    },
+    OrphanedIndex {
+        id: u32,
+        property: Property,
+    },
+    DuplicateUid {
+        id: u32,
+        mailbox: u32,
+        uid: u32,
+    },
+    UidNext {
+        mailbox: u32,
+        expected: u32,
+        found: u32,
+    },
+    MissingBlobLink {
+        id: u32,
+        #[serde(rename = "blobId")]
+        blob_id: BlobId,
+    },
+    MissingBlobCommit {
+        id: u32,
+        #[serde(rename = "blobId")]
+        blob_id: BlobId,
+    },
+    MissingBlob {
+        id: u32,
+        #[serde(rename = "blobId")]
+        blob_id: BlobId,
+    },
+    QuotaMismatch {
+        expected: i64,
+        found: i64,
+    },
+}
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct FsckStatus {
+    #[serde(rename = "isRunning")]
+    pub is_running: bool,
+    pub repair: bool,
+    pub total: usize,
+    pub checked: usize,
+    #[serde(rename = "startedAt")]
+    pub started_at: u64,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: u64,
+    pub error: Option<String>,
+    pub reports: Vec<FsckReport>,
+}
+
+struct RepairBatch {
+    account_id: u32,
+    batch: BatchBuilder,
+}
+
+impl JMAP {
+    pub fn fsck_start(&self, total: usize, repair: bool) -> bool {
+        let mut status = self.fsck_status.lock();
+        if !status.is_running {
+            *status = FsckStatus {
+                is_running: true,
+                repair,
+                total,
+                started_at: now(),
+                ..Default::default()
+            };
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn fsck_run(&self, account_ids: Vec<u32>, repair: bool) {
+        tracing::info!(
+            context = "fsck",
+            event = "start",
+            accounts = account_ids.len(),
+            repair = repair,
+            "Store consistency check started."
+        );
+
+        let mut error = None;
+        for account_id in account_ids {
+            match self.fsck_account(account_id, repair).await {
+                Ok(report) => {
+                    let mut status = self.fsck_status.lock();
+                    status.checked += 1;
+                    if !report.issues.is_empty() {
+                        status.reports.push(report);
+                    }
+                }
+                Err(err) => {
+                    tracing::error!(
+                        context = "fsck",
+                        event = "error",
+                        account_id = account_id,
+                        error = ?err,
+                        "Store consistency check failed."
+                    );
+                    error = err.to_string().into();
+                    break;
+                }
+            }
+        }
+
+        let mut status = self.fsck_status.lock();
+        status.is_running = false;
+        status.finished_at = now();
+        status.error = error;
+
+        tracing::info!(
+            context = "fsck",
+            event = "finish",
+            checked = status.checked,
+            issues = status.reports.iter().map(|r| r.issues.len()).sum::<usize>(),
+            "Store consistency check finished."
+        );
+    }
+
+    pub async fn fsck_account(&self, account_id: u32, repair: bool) -> store::Result<FsckReport> {
+        let mut report = FsckReport {
+            account_id,
+            ..Default::default()
+        };
+        let mut repairs = RepairBatch {
+            account_id,
+            batch: BatchBuilder::new(),
+        };
+        let mut changes = ChangeLogBuilder::new();
+        let mut changed_emails = AHashSet::new();
+        let mut changed_mailboxes = AHashSet::new();
+
+        // Obtain document ids and email records
+        let mut email_ids = self
+            .fsck_document_ids(account_id, Collection::Email)
+            .await?;
+        let mailbox_ids = self
+            .fsck_document_ids(account_id, Collection::Mailbox)
+            .await?;
+        let thread_ids = self
+            .fsck_document_ids(account_id, Collection::Thread)
+            .await?;
+        let mut email_mailboxes = self
+            .fsck_values::<Vec<UidMailbox>>(account_id, Collection::Email, Property::MailboxIds)
+            .await?;
+        let email_keywords = self
+            .fsck_values::<Vec<Keyword>>(account_id, Collection::Email, Property::Keywords)
+            .await?;
+        let email_threads = self
+            .fsck_values::<u32>(account_id, Collection::Email, Property::ThreadId)
+            .await?;
+        let email_metadata = self
+            .fsck_values::<Bincode<MessageMetadata>>(
+                account_id,
+                Collection::Email,
+                Property::BodyStructure,
+            )
+            .await?;
+
+        // Validate that every message has all its mandatory values and
+        // that no complete records are missing from the document ids bitmap.
+        let mut valid_ids = RoaringBitmap::new();
+        for document_id in email_ids
+            .iter()
+            .chain(email_mailboxes.keys().copied())
+            .chain(email_metadata.keys().copied())
+            .chain(email_threads.keys().copied())
+            .collect::<AHashSet<_>>()
+        {
+            let mut is_valid = true;
+            for (property, has_value) in [
+                (
+                    Property::MailboxIds,
+                    email_mailboxes.contains_key(&document_id),
+                ),
+                (
+                    Property::BodyStructure,
+                    email_metadata.contains_key(&document_id),
+                ),
+                (Property::ThreadId, email_threads.contains_key(&document_id)),
+            ] {
+                if !has_value {
+                    is_valid = false;
+                    if email_ids.contains(document_id) {
+                        report.issues.push(FsckIssue::MissingValue {
+                            id: document_id,
+                            property,
+                        });
+                    }
+                }
+            }
+
+            if !email_ids.contains(document_id) {
+                report
+                    .issues
+                    .push(FsckIssue::OrphanedDocument { id: document_id });
+                if is_valid && repair {
+                    repairs
+                        .document(Collection::Email, document_id)
+                        .create_document(document_id);
+                    changes.log_insert(
+                        Collection::Email,
+                        Id::from_parts(email_threads[&document_id], document_id),
+                    );
+                    email_ids.insert(document_id);
+                }
+            }
+
+            if is_valid && email_ids.contains(document_id) {
+                valid_ids.insert(document_id);
+            }
+        }
+
+        // Validate mailbox bitmaps
+        let mut expected_mailboxes: AHashMap<u32, RoaringBitmap> = AHashMap::new();
+        for document_id in &valid_ids {
+            for mailbox in &email_mailboxes[&document_id] {
+                expected_mailboxes
+                    .entry(mailbox.mailbox_id)
+                    .or_default()
+                    .insert(document_id);
+            }
+        }
+        for mailbox_id in &mailbox_ids {
+            expected_mailboxes.entry(mailbox_id).or_default();
+        }
+        for (mailbox_id, expected) in &expected_mailboxes {
+            if !mailbox_ids.contains(*mailbox_id) {
+                report.issues.push(FsckIssue::MissingMailbox {
+                    mailbox: *mailbox_id,
+                });
+            }
+            let found = self
+                .fsck_tag(account_id, Property::MailboxIds, TagValue::Id(*mailbox_id))
+                .await?;
+            for (document_ids, set) in [(expected - &found, true), (&found - expected, false)] {
+                for document_id in document_ids {
+                    report.issues.push(FsckIssue::MailboxBitmap {
+                        id: document_id,
+                        mailbox: *mailbox_id,
+                        expected: set,
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).ops.push(
+                            Operation::Bitmap {
+                                class: BitmapClass::Tag {
+                                    field: Property::MailboxIds.into(),
+                                    value: TagValue::Id(*mailbox_id),
+                                },
+                                set,
+                            },
+                        );
+                        changed_emails.insert(document_id);
+                        changed_mailboxes.insert(*mailbox_id);
+                    }
+                }
+            }
+        }
+
+        // Validate keyword bitmaps
+        let mut expected_keywords: AHashMap<Keyword, RoaringBitmap> = [
+            Keyword::Seen,
+            Keyword::Draft,
+            Keyword::Flagged,
+            Keyword::Answered,
+            Keyword::Deleted,
+        ]
+        .into_iter()
+        .map(|keyword| (keyword, RoaringBitmap::new()))
+        .collect();
+        for document_id in &valid_ids {
+            for keyword in email_keywords
+                .get(&document_id)
+                .map(|keywords| keywords.as_slice())
+                .unwrap_or_default()
+            {
+                expected_keywords
+                    .entry(keyword.clone())
+                    .or_default()
+                    .insert(document_id);
+            }
+        }
+        for (keyword, expected) in &expected_keywords {
+            let found = self
+                .fsck_tag(account_id, Property::Keywords, keyword.into())
+                .await?;
+            for (document_ids, set) in [(expected - &found, true), (&found - expected, false)] {
+                for document_id in document_ids {
+                    report.issues.push(FsckIssue::KeywordBitmap {
+                        id: document_id,
+                        keyword: keyword.clone(),
+                        expected: set,
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).tag(
+                            Property::Keywords,
+                            keyword,
+                            if set { 0 } else { store::write::F_CLEAR },
+                        );
+                        changed_emails.insert(document_id);
+                    }
+                }
+            }
+        }
+
+        // Validate threads
+        let mut expected_threads: AHashMap<u32, RoaringBitmap> = AHashMap::new();
+        for document_id in &valid_ids {
+            expected_threads
+                .entry(email_threads[&document_id])
+                .or_default()
+                .insert(document_id);
+        }
+        for thread_id in &thread_ids {
+            expected_threads.entry(thread_id).or_default();
+        }
+        for (thread_id, expected) in &expected_threads {
+            if expected.is_empty() {
+                report
+                    .issues
+                    .push(FsckIssue::OrphanedThread { thread: *thread_id });
+                if repair {
+                    repairs
+                        .document(Collection::Thread, *thread_id)
+                        .delete_document(*thread_id);
+                    changes.log_delete(Collection::Thread, *thread_id);
+                }
+            } else if !thread_ids.contains(*thread_id) {
+                report
+                    .issues
+                    .push(FsckIssue::MissingThread { thread: *thread_id });
+                if repair {
+                    repairs
+                        .document(Collection::Thread, *thread_id)
+                        .create_document(*thread_id);
+                    changes.log_insert(Collection::Thread, *thread_id);
+                }
+            }
+            let found = self
+                .fsck_tag(account_id, Property::ThreadId, TagValue::Id(*thread_id))
+                .await?;
+            for (document_ids, set) in [(expected - &found, true), (&found - expected, false)] {
+                for document_id in document_ids {
+                    report.issues.push(FsckIssue::ThreadBitmap {
+                        id: document_id,
+                        thread: *thread_id,
+                        expected: set,
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).tag(
+                            Property::ThreadId,
+                            *thread_id,
+                            if set { 0 } else { store::write::F_CLEAR },
+                        );
+                        changed_emails.insert(document_id);
+                    }
+                }
+            }
+        }
+
+        // Validate sort indexes
+        for property in [Property::Size, Property::ReceivedAt] {
+            let mut found = self.fsck_index(account_id, property.clone()).await?;
+            for document_id in &valid_ids {
+                let metadata = &email_metadata[&document_id].inner;
+                let expected = if property == Property::Size {
+                    (metadata.size as u32).serialize()
+                } else {
+                    metadata.received_at.serialize()
+                };
+                let keys = found.remove(&document_id).unwrap_or_default();
+                if !keys.contains(&expected) {
+                    report.issues.push(FsckIssue::MissingIndex {
+                        id: document_id,
+                        property: property.clone(),
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).ops.push(
+                            Operation::Index {
+                                field: property.clone().into(),
+                                key: expected.clone(),
+                                set: true,
+                            },
+                        );
+                    }
+                }
+                for key in keys.into_iter().filter(|key| key != &expected) {
+                    report.issues.push(FsckIssue::OrphanedIndex {
+                        id: document_id,
+                        property: property.clone(),
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).ops.push(
+                            Operation::Index {
+                                field: property.clone().into(),
+                                key,
+                                set: false,
+                            },
+                        );
+                    }
+                }
+            }
+            for (document_id, keys) in found {
+                for key in keys {
+                    report.issues.push(FsckIssue::OrphanedIndex {
+                        id: document_id,
+                        property: property.clone(),
+                    });
+                    if repair {
+                        repairs.document(Collection::Email, document_id).ops.push(
+                            Operation::Index {
+                                field: property.clone().into(),
+                                key,
+                                set: false,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        // Validate IMAP UIDs
+        let mut duplicate_uids = Vec::new();
+        for mailbox_id in &mailbox_ids {
+            let mut uids = AHashSet::new();
+            let mut max_uid = 0;
+            for document_id in &valid_ids {
+                if let Some(mailbox) = email_mailboxes[&document_id]
+                    .iter()
+                    .find(|m| m.mailbox_id == mailbox_id)
+                {
+                    if mailbox.uid == 0 || !uids.insert(mailbox.uid) {
+                        report.issues.push(FsckIssue::DuplicateUid {
+                            id: document_id,
+                            mailbox: mailbox_id,
+                            uid: mailbox.uid,
+                        });
+                        duplicate_uids.push((document_id, mailbox_id));
+                    }
+                    max_uid = std::cmp::max(max_uid, mailbox.uid);
+                }
+            }
+
+            let uid_next = self
+                .store
+                .get_counter(ValueKey {
+                    account_id,
+                    collection: Collection::Mailbox.into(),
+                    document_id: mailbox_id,
+                    class: ValueClass::Property(Property::EmailIds.into()),
+                })
+                .await? as u32;
+            if max_uid > uid_next {
+                report.issues.push(FsckIssue::UidNext {
+                    mailbox: mailbox_id,
+                    expected: max_uid,
+                    found: uid_next,
+                });
+                if repair {
+                    repairs
+                        .document(Collection::Mailbox, mailbox_id)
+                        .add(Property::EmailIds, (max_uid - uid_next) as i64);
+                }
+            }
+        }
+
+        // Validate blobs
+        let mut used_quota = 0;
+        for document_id in &valid_ids {
+            let metadata = &email_metadata[&document_id].inner;
+            let hash = &metadata.blob_hash;
+            let blob_id = BlobId::new(
+                hash.clone(),
+                BlobClass::Linked {
+                    account_id,
+                    collection: Collection::Email.into(),
+                    document_id,
+                },
+            );
+            used_quota += metadata.size as i64;
+
+            if !self.store.blob_has_access(hash, &blob_id.class).await? {
+                report.issues.push(FsckIssue::MissingBlobLink {
+                    id: document_id,
+                    blob_id: blob_id.clone(),
+                });
+                if repair {
+                    repairs
+                        .document(Collection::Email, document_id)
+                        .set(BlobOp::Link { hash: hash.clone() }, Vec::new());
+                }
+            }
+            if !self.store.blob_exists(hash).await? {
+                if self
+                    .blob_store
+                    .get_blob(hash.as_ref(), 0..1)
+                    .await?
+                    .is_some()
+                {
+                    report.issues.push(FsckIssue::MissingBlobCommit {
+                        id: document_id,
+                        blob_id: blob_id.clone(),
+                    });
+                    if repair {
+                        repairs
+                            .batch
+                            .set(BlobOp::Commit { hash: hash.clone() }, Vec::new());
+                    }
+                } else {
+                    report.issues.push(FsckIssue::MissingBlob {
+                        id: document_id,
+                        blob_id: blob_id.clone(),
+                    });
+                }
+            }
+            repairs.flush(self, false).await?;
+        }
+
+        // Validate quota, which includes the size of Sieve scripts
+        for (_, script) in self
+            .fsck_values::<Object<Value>>(account_id, Collection::SieveScript, Property::Value)
+            .await?
+        {
+            used_quota += script
+                .blob_id()
+                .and_then(|blob_id| blob_id.section.as_ref())
+                .map_or(0, |section| section.size as i64);
+        }
+        let found_quota = self
+            .store
+            .get_counter(DirectoryClass::UsedQuota(account_id))
+            .await?;
+        if used_quota != found_quota {
+            report.issues.push(FsckIssue::QuotaMismatch {
+                expected: used_quota,
+                found: found_quota,
+            });
+            if repair {
+                repairs.batch.add(
+                    DirectoryClass::UsedQuota(account_id),
+                    used_quota - found_quota,
+                );
+            }
+        }
+
+        if !repair || report.issues.is_empty() {
+            return Ok(report);
+        }
+
+        // Write repairs, keeping the domain usage counters in sync with the quota
+        self.track_domain_usage(&mut repairs.batch)
+            .await
+            .map_err(|_| store::Error::InternalError("Failed to update domain usage.".into()))?;
+        repairs.flush(self, true).await?;
+
+        // Assign new UIDs to messages with duplicate or missing UIDs
+        for (document_id, mailbox_id) in duplicate_uids {
+            let uid = self.assign_imap_uid(account_id, mailbox_id).await?;
+            let mailboxes = email_mailboxes.get_mut(&document_id).unwrap();
+            for mailbox in mailboxes.iter_mut() {
+                if mailbox.mailbox_id == mailbox_id {
+                    mailbox.uid = uid;
+                }
+            }
+            repairs.document(Collection::Email, document_id).value(
+                Property::MailboxIds,
+                mailboxes.clone(),
+                F_VALUE,
+            );
+            changed_emails.insert(document_id);
+            changed_mailboxes.insert(mailbox_id);
+            repairs.flush(self, false).await?;
+        }
+
+        // Log changes so clients resynchronize
+        for document_id in changed_emails {
+            if let Some(thread_id) = email_threads.get(&document_id) {
+                changes.log_update(Collection::Email, Id::from_parts(*thread_id, document_id));
+            }
+        }
+        for mailbox_id in changed_mailboxes {
+            changes.log_child_update(Collection::Mailbox, mailbox_id);
+        }
+        if !changes.changes.is_empty() {
+            changes.change_id = self.assign_change_id(account_id).await.map_err(|_| {
+                store::Error::InternalError("Failed to assign changeId.".to_string())
+            })?;
+            repairs.batch.with_account_id(account_id).custom(changes);
+        }
+        repairs.flush(self, true).await?;
+
+        report.repaired = true;
+
+        Ok(report)
+    }
+
+    async fn fsck_document_ids(
+        &self,
+        account_id: u32,
+        collection: Collection,
+    ) -> store::Result<RoaringBitmap> {
+        self.store
+            .get_bitmap(BitmapKey::document_ids(account_id, collection))
+            .await
+            .map(|bitmap| bitmap.unwrap_or_default())
+    }
+
+    async fn fsck_tag(
+        &self,
+        account_id: u32,
+        property: Property,
+        value: TagValue,
+    ) -> store::Result<RoaringBitmap> {
+        self.store
+            .get_bitmap(BitmapKey {
+                account_id,
+                collection: Collection::Email.into(),
+                class: BitmapClass::Tag {
+                    field: property.into(),
+                    value,
+                },
+                block_num: 0,
+            })
+            .await
+            .map(|bitmap| bitmap.unwrap_or_default())
+    }
+
+    async fn fsck_values<U>(
+        &self,
+        account_id: u32,
+        collection: Collection,
+        property: Property,
+    ) -> store::Result<AHashMap<u32, U>>
+    where
+        U: Deserialize + 'static,
+    {
+        let collection: u8 = collection.into();
+        let property: u8 = property.into();
+        let mut values = AHashMap::new();
+
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey {
+                        account_id,
+                        collection,
+                        document_id: 0,
+                        class: ValueClass::Property(property),
+                    },
+                    ValueKey {
+                        account_id,
+                        collection,
+                        document_id: u32::MAX,
+                        class: ValueClass::Property(property),
+                    },
+                ),
+                |key, value| {
+                    values.insert(
+                        key.deserialize_be_u32(key.len() - U32_LEN)?,
+                        U::deserialize(value)?,
+                    );
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(values)
+    }
+
+    async fn fsck_index(
+        &self,
+        account_id: u32,
+        property: Property,
+    ) -> store::Result<AHashMap<u32, Vec<Vec<u8>>>> {
+        let field: u8 = property.into();
+        let mut entries: AHashMap<u32, Vec<Vec<u8>>> = AHashMap::new();
+
+        self.store
+            .iterate(
+                IterateParams::new(
+                    IndexKeyPrefix {
+                        account_id,
+                        collection: Collection::Email.into(),
+                        field,
+                    },
+                    IndexKeyPrefix {
+                        account_id,
+                        collection: Collection::Email.into(),
+                        field: field + 1,
+                    },
+                )
+                .no_values()
+                .ascending(),
+                |key, _| {
+                    let id_pos = key.len() - U32_LEN;
+                    entries
+                        .entry(key.deserialize_be_u32(id_pos)?)
+                        .or_default()
+                        .push(
+                            key.get(IndexKeyPrefix::len()..id_pos)
+                                .unwrap_or_default()
+                                .to_vec(),
+                        );
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        Ok(entries)
+    }
+}
+
+impl RepairBatch {
+    fn document(&mut self, collection: Collection, document_id: u32) -> &mut BatchBuilder {
+        self.batch
+            .with_account_id(self.account_id)
+            .with_collection(collection)
+            .update_document(document_id);
+        &mut self.batch
+    }
+
+    async fn flush(&mut self, core: &JMAP, force: bool) -> store::Result<()> {
+        if !self.batch.is_empty() && (force || self.batch.ops.len() >= 1000) {
+            core.store.write(self.batch.build_batch()).await?;
+        }
+        Ok(())
+    }
+}
@@ -41,6 +41,13 @@ pub enum Event {
     ReloadConfig,
     IndexStart,
     IndexDone,
+    Fsck {
+        account_ids: Vec<u32>,
+        repair: bool,
+    },
+    Reindex {
+        account_ids: Vec<u32>,
+    },
     #[cfg(feature = "test_mode")]
     IndexIsActive(tokio::sync::oneshot::Sender<bool>),
     Exit,
@@ -55,6 +62,9 @@ pub fn spawn_housekeeper(
     let purge_cache = settings
         .property_or_default::<SimpleCron>("jmap.session.purge.frequency", "15 * *")
         .failed("Initialize housekeeper");
+    let retention = settings
+        .property_or_default::<SimpleCron>("jmap.retention.frequency", "30 3 *")
+        .failed("Initialize housekeeper");
 
     let certificates = std::mem::take(&mut servers.certificates);
 
@@ -71,8 +81,11 @@ pub fn spawn_housekeeper(
         });
 
         loop {
-            let time_to_next = purge_cache.time_to_next();
+            let purge_next = purge_cache.time_to_next();
+            let retention_next = retention.time_to_next();
+            let time_to_next = purge_next.min(retention_next);
             let mut do_purge = false;
+            let mut do_retention = false;
 
             match tokio::time::timeout(time_to_next, rx.recv()).await {
                 Ok(Some(event)) => match event {
@@ -159,6 +172,21 @@ pub fn spawn_housekeeper(
                             index_busy = false;
                         }
                     }
+                    Event::Fsck {
+                        account_ids,
+                        repair,
+                    } => {
+                        let core = core.clone();
+                        tokio::spawn(async move {
+                            core.fsck_run(account_ids, repair).await;
+                        });
+                    }
+                    Event::Reindex { account_ids } => {
+                        let core = core.clone();
+                        tokio::spawn(async move {
+                            core.fts_reindex_run(account_ids).await;
+                        });
+                    }
                     #[cfg(feature = "test_mode")]
                     Event::IndexIsActive(tx) => {
                         tx.send(index_busy).ok();
@@ -173,7 +201,8 @@ pub fn spawn_housekeeper(
                     return;
                 }
                 Err(_) => {
-                    do_purge = true;
+                    do_purge = purge_next <= retention_next;
+                    do_retention = retention_next <= purge_next;
                 }
             }
 
@@ -186,6 +215,17 @@ pub fn spawn_housekeeper(
                     core.oauth_codes.cleanup();
                     core.concurrency_limiter
                         .retain(|_, limiter| limiter.is_active());
+                    core.purge_audit_log().await;
+                    core.purge_lists().await;
+                    core.list_retry_outbox().await;
+                });
+            }
+
+            if do_retention {
+                let core = core.clone();
+                tokio::spawn(async move {
+                    tracing::info!("Applying retention rules.");
+                    core.apply_retention_rules().await;
                 });
             }
         }
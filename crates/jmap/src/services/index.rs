@@ -24,17 +24,33 @@
 use jmap_proto::types::{collection::Collection, property::Property};
 use store::{
     fts::index::FtsDocument,
-    write::{key::DeserializeBigEndian, BatchBuilder, Bincode, ValueClass},
+    write::{key::DeserializeBigEndian, now, BatchBuilder, Bincode, ValueClass},
     Deserialize, IterateParams, ValueKey, U32_LEN, U64_LEN,
 };
 
 use crate::{
-    email::{index::IndexMessageText, metadata::MessageMetadata},
+    email::{
+        index::{ExtractDocument, IndexMessageText},
+        metadata::MessageMetadata,
+    },
     JMAP,
 };
 
 use super::housekeeper::Event;
 
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct ReindexStatus {
+    #[serde(rename = "isRunning")]
+    pub is_running: bool,
+    pub total: usize,
+    pub queued: usize,
+    #[serde(rename = "startedAt")]
+    pub started_at: u64,
+    #[serde(rename = "finishedAt")]
+    pub finished_at: u64,
+    pub error: Option<String>,
+}
+
 #[derive(Debug)]
 struct IndexEmail {
     account_id: u32,
@@ -113,13 +129,27 @@ impl JMAP {
                         };
                         let message = metadata.inner.contents.into_message(&raw_message);
 
+                        // Extract text from attachments on a blocking thread
+                        let documents =
+                            ExtractDocument::collect(&message, &self.config.fts_extract_limits);
+                        let documents = if !documents.is_empty() {
+                            let limits = self.config.fts_extract_limits.clone();
+                            tokio::task::spawn_blocking(move || {
+                                ExtractDocument::extract(documents, &limits)
+                            })
+                            .await
+                            .unwrap_or_default()
+                        } else {
+                            Vec::new()
+                        };
+
                         // Index message
                         let document =
                             FtsDocument::with_default_language(self.config.default_language)
                                 .with_account_id(key.account_id)
                                 .with_collection(Collection::Email)
                                 .with_document_id(key.document_id)
-                                .index_message(&message);
+                                .index_message(&message, documents);
                         if let Err(err) = self.fts_store.index(document).await {
                             tracing::error!(
                                 context = "fts_index_queued",
@@ -217,6 +247,121 @@ impl JMAP {
     }
 }
 
+impl JMAP {
+    pub fn fts_reindex_start(&self, total: usize) -> bool {
+        let mut status = self.reindex_status.lock();
+        if !status.is_running {
+            *status = ReindexStatus {
+                is_running: true,
+                total,
+                started_at: now(),
+                ..Default::default()
+            };
+            true
+        } else {
+            false
+        }
+    }
+
+    pub async fn fts_reindex_run(&self, account_ids: Vec<u32>) {
+        tracing::info!(
+            context = "fts_reindex",
+            event = "start",
+            accounts = account_ids.len(),
+            "Full-text index rebuild started."
+        );
+
+        let mut error = None;
+        for account_id in account_ids {
+            if let Err(err) = self.fts_reindex(account_id).await {
+                tracing::error!(
+                    context = "fts_reindex",
+                    event = "error",
+                    account_id = account_id,
+                    error = ?err,
+                    "Full-text index rebuild failed."
+                );
+                error = err.to_string().into();
+                break;
+            }
+            self.reindex_status.lock().queued += 1;
+        }
+
+        let mut status = self.reindex_status.lock();
+        status.is_running = false;
+        status.finished_at = now();
+        status.error = error;
+
+        tracing::info!(
+            context = "fts_reindex",
+            event = "finish",
+            queued = status.queued,
+            "Full-text index rebuild queued."
+        );
+    }
+
+    pub async fn fts_reindex(&self, account_id: u32) -> store::Result<()> {
+        // Remove all documents from the FTS index
+        self.fts_store.remove_all(account_id).await?;
+
+        // Obtain the blob hashes of all messages in the account
+        let mut blob_hashes = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey {
+                        account_id,
+                        collection: Collection::Email.into(),
+                        document_id: 0,
+                        class: ValueClass::Property(Property::BodyStructure.into()),
+                    },
+                    ValueKey {
+                        account_id,
+                        collection: Collection::Email.into(),
+                        document_id: u32::MAX,
+                        class: ValueClass::Property(Property::BodyStructure.into()),
+                    },
+                ),
+                |key, value| {
+                    blob_hashes.push((
+                        key.deserialize_be_u32(key.len() - U32_LEN)?,
+                        Bincode::<MessageMetadata>::deserialize(value)?
+                            .inner
+                            .blob_hash,
+                    ));
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        // Queue messages for indexing
+        let mut batch = BatchBuilder::new();
+        batch.with_account_id(account_id);
+        for (document_id, blob_hash) in blob_hashes {
+            if batch.ops.len() >= 1000 {
+                self.store.write(batch.build()).await?;
+                batch = BatchBuilder::new();
+                batch.with_account_id(account_id);
+            }
+
+            batch.update_document(document_id).set(
+                ValueClass::IndexEmail(self.generate_snowflake_id().map_err(|_| {
+                    store::Error::InternalError("Failed to generate snowflake id.".to_string())
+                })?),
+                blob_hash,
+            );
+        }
+        if !batch.is_empty() {
+            self.store.write(batch.build()).await?;
+        }
+
+        // Start indexing
+        let _ = self.housekeeper_tx.send(Event::IndexStart).await;
+
+        Ok(())
+    }
+}
+
 impl Deserialize for IndexEmail {
     fn deserialize(bytes: &[u8]) -> store::Result<Self> {
         let len = bytes.len();
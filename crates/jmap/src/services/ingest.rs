@@ -25,9 +25,15 @@ use directory::QueryBy;
 use jmap_proto::types::{state::StateChange, type_state::DataType};
 use mail_parser::MessageParser;
 use store::ahash::AHashMap;
-use utils::ipc::{DeliveryResult, IngestMessage};
+use utils::{
+    ipc::{DeliveryResult, IngestMessage},
+    webhooks::{WebhookPayload, WebhookType},
+};
 
-use crate::{email::ingest::IngestEmail, mailbox::INBOX_ID, IngestError, JMAP};
+use crate::{
+    email::ingest::IngestEmail, mailbox::INBOX_ID, submission::report::DeliveryReport, IngestError,
+    JMAP,
+};
 
 impl JMAP {
     pub async fn deliver_message(&self, message: IngestMessage) -> Vec<DeliveryResult> {
@@ -50,7 +56,17 @@ impl JMAP {
         // Obtain the UIDs for each recipient
         let mut recipients = Vec::with_capacity(message.recipients.len());
         let mut deliver_names = AHashMap::with_capacity(message.recipients.len());
+        let mut list_results = Vec::with_capacity(message.recipients.len());
         for rcpt in &message.recipients {
+            // Messages addressed to mailing lists are handled by the list manager
+            let list_result = self.list_deliver(rcpt, &message, &raw_message).await;
+            if list_result.is_some() {
+                list_results.push(list_result);
+                recipients.push(vec![]);
+                continue;
+            }
+            list_results.push(None);
+
             let uids = self.directory.email_to_ids(rcpt).await.unwrap_or_default();
             for uid in &uids {
                 deliver_names.insert(*uid, (DeliveryResult::Success, rcpt));
@@ -58,21 +74,30 @@ impl JMAP {
             recipients.push(uids);
         }
 
+        // Delivery and disposition notifications are correlated with their submissions
+        let report = MessageParser::new()
+            .parse_headers(&raw_message)
+            .is_some_and(|message| DeliveryReport::is_report(&message))
+            .then(|| MessageParser::new().parse(&raw_message))
+            .flatten();
+
         // Deliver to each recipient
         for (uid, (status, rcpt)) in &mut deliver_names {
             // Check if there is an active sieve script
+            let mandatory_scripts = self.sieve_mandatory_scripts(rcpt).await;
             let result = match self.sieve_script_get_active(*uid).await {
-                Ok(Some(active_script)) => {
+                Ok(active_script) if active_script.is_some() || !mandatory_scripts.is_empty() => {
                     self.sieve_script_ingest(
                         &raw_message,
                         &message.sender_address,
                         rcpt,
                         *uid,
                         active_script,
+                        mandatory_scripts,
                     )
                     .await
                 }
-                Ok(None) => {
+                Ok(_) => {
                     let account_quota = match self.directory.query(QueryBy::Id(*uid), false).await {
                         Ok(Some(p)) => p.quota as i64,
                         Ok(None) => 0,
@@ -107,6 +132,49 @@ impl JMAP {
 
             match result {
                 Ok(ingested_message) => {
+                    if let Some(report) = report
+                        .as_ref()
+                        .filter(|_| ingested_message.change_id != u64::MAX)
+                    {
+                        if let Err(err) = self
+                            .email_submission_correlate(*uid, report, &ingested_message.blob_id)
+                            .await
+                        {
+                            tracing::warn!(
+                                context = "email_submission",
+                                event = "error",
+                                account_id = *uid,
+                                reason = ?err,
+                                "Failed to correlate delivery report."
+                            );
+                        }
+                    }
+
+                    // Notify webhooks
+                    if ingested_message.change_id != u64::MAX {
+                        self.smtp.webhooks.send(WebhookType::MessageIngested, || {
+                            WebhookPayload::MessageIngested {
+                                account_id: *uid,
+                                email_id: ingested_message.id.to_string(),
+                                blob_id: ingested_message.blob_id.to_string(),
+                                size: ingested_message.size,
+                                sender: message.sender_address.clone(),
+                                recipient: rcpt.to_string(),
+                                headers: MessageParser::new()
+                                    .parse_headers(&raw_message)
+                                    .map(|message| {
+                                        message
+                                            .headers_raw()
+                                            .map(|(name, value)| {
+                                                (name.to_string(), value.trim().to_string())
+                                            })
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                            }
+                        });
+                    }
+
                     // Notify state change
                     if ingested_message.change_id != u64::MAX {
                         self.broadcast_state_change(
@@ -143,7 +211,12 @@ impl JMAP {
         // Build result
         recipients
             .into_iter()
-            .map(|names| {
+            .zip(list_results)
+            .map(|(names, list_result)| {
+                if let Some(list_result) = list_result {
+                    return list_result;
+                }
+
                 match names.len() {
                     1 => {
                         // Delivery to single recipient
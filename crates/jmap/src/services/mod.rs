@@ -21,10 +21,15 @@
  * for more details.
 */
 
+pub mod audit;
 pub mod delivery;
+pub mod fsck;
 pub mod housekeeper;
 pub mod index;
 pub mod ingest;
+pub mod retention;
+pub mod retry;
 pub mod state;
+pub mod webhook;
 
 pub const IPC_CHANNEL_BUFFER: usize = 1024;
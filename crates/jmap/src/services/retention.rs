@@ -0,0 +1,331 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    core::retention::{RetentionAction, RetentionRule},
+    QueryBy,
+};
+use jmap_proto::{
+    error::method::MethodError,
+    types::{
+        collection::Collection, date::UTCDate, id::Id, property::Property, state::StateChange,
+        type_state::DataType,
+    },
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, F_VALUE},
+    BitmapKey,
+};
+
+use crate::{email::set::TagManager, mailbox::UidMailbox, JMAP};
+
+/// Parses the retention rules that apply to all accounts, i.e.
+/// `jmap.retention.roles.trash.action = "delete"`.
+pub fn parse_retention_rules(
+    settings: &utils::config::Config,
+) -> Result<Vec<RetentionRule>, String> {
+    let mut rules = Vec::new();
+    for role in settings.sub_keys("jmap.retention.roles", ".action") {
+        let rule = RetentionRule {
+            role: role.to_string().into(),
+            mailbox: None,
+            action: match settings.value_require(("jmap.retention.roles", role, "action"))? {
+                "keep" => RetentionAction::Keep,
+                "delete" => RetentionAction::Delete,
+                "move" => RetentionAction::Move,
+                action => {
+                    return Err(format!(
+                        "Invalid retention action {action:?} for role {role:?}."
+                    ))
+                }
+            },
+            after: settings
+                .value(("jmap.retention.roles", role, "after"))
+                .map(|after| after.to_string()),
+            move_to: settings
+                .value(("jmap.retention.roles", role, "move-to"))
+                .map(|move_to| move_to.to_string()),
+        };
+        if rule.validate().is_err() {
+            return Err(format!("Invalid retention rule for role {role:?}."));
+        }
+        rules.push(rule);
+    }
+
+    Ok(rules)
+}
+
+impl JMAP {
+    /// Expunges or moves the messages that are older than the retention rules
+    /// of each account.
+    pub async fn apply_retention_rules(&self) {
+        let account_ids = match self
+            .store
+            .get_bitmap(BitmapKey::document_ids(u32::MAX, Collection::Principal))
+            .await
+        {
+            Ok(account_ids) => account_ids.unwrap_or_default(),
+            Err(err) => {
+                tracing::error!(
+                    context = "retention",
+                    event = "error",
+                    reason = ?err,
+                    "Failed to obtain account ids."
+                );
+                return;
+            }
+        };
+
+        for account_id in account_ids {
+            if let Err(err) = self.apply_account_retention_rules(account_id).await {
+                tracing::error!(
+                    context = "retention",
+                    event = "error",
+                    account_id = account_id,
+                    reason = ?err,
+                    "Failed to apply retention rules."
+                );
+            }
+        }
+    }
+
+    pub async fn apply_account_retention_rules(&self, account_id: u32) -> Result<(), MethodError> {
+        let rules = self.account_retention_rules(account_id).await?;
+        if rules.is_empty() {
+            return Ok(());
+        }
+        if self.is_legal_hold(account_id).await? {
+            tracing::debug!(
+                context = "retention",
+                event = "skip",
+                account_id = account_id,
+                "Account is under legal hold, skipping retention rules."
+            );
+            return Ok(());
+        }
+
+        let mut changes = ChangeLogBuilder::new();
+        let now = now();
+        for (mailbox_id, rule) in rules {
+            let after = match (rule.action, rule.after()) {
+                (RetentionAction::Delete | RetentionAction::Move, Some(after)) => after.as_secs(),
+                _ => continue,
+            };
+            let move_to = if let Some(move_to) = rule
+                .move_to
+                .as_deref()
+                .filter(|_| rule.action == RetentionAction::Move)
+            {
+                match self.mailbox_get_by_role(account_id, move_to).await? {
+                    Some(move_to_id) if move_to_id != mailbox_id => Some(move_to_id),
+                    Some(_) => continue,
+                    None => {
+                        tracing::debug!(
+                            context = "retention",
+                            event = "skip",
+                            account_id = account_id,
+                            role = move_to,
+                            "Destination mailbox not found."
+                        );
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+
+            let document_ids = self
+                .filter(
+                    account_id,
+                    Collection::Email,
+                    vec![
+                        Filter::is_in_bitmap(Property::MailboxIds, mailbox_id),
+                        Filter::lt(
+                            Property::ReceivedAt,
+                            UTCDate::from_timestamp(now.saturating_sub(after) as i64),
+                        ),
+                    ],
+                )
+                .await?
+                .results;
+
+            self.retention_expire(account_id, mailbox_id, move_to, document_ids, &mut changes)
+                .await?;
+        }
+
+        if !changes.is_empty() {
+            let change_id = self.commit_changes(account_id, changes).await?;
+            self.broadcast_state_change(
+                StateChange::new(account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Mailbox, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+
+    async fn retention_expire(
+        &self,
+        account_id: u32,
+        mailbox_id: u32,
+        move_to: Option<u32>,
+        document_ids: RoaringBitmap,
+        changes: &mut ChangeLogBuilder,
+    ) -> Result<(), MethodError> {
+        let src_mailbox_id = UidMailbox::new_unassigned(mailbox_id);
+        for document_id in document_ids {
+            let (mailboxes, thread_id) = match (
+                self.get_property::<HashedValue<Vec<UidMailbox>>>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::MailboxIds,
+                )
+                .await?,
+                self.get_property::<u32>(
+                    account_id,
+                    Collection::Email,
+                    document_id,
+                    Property::ThreadId,
+                )
+                .await?,
+            ) {
+                (Some(mailboxes), Some(thread_id)) => (mailboxes, thread_id),
+                _ => continue,
+            };
+            if !mailboxes.inner.contains(&src_mailbox_id) {
+                continue;
+            }
+
+            if move_to.is_none() && mailboxes.inner.len() == 1 {
+                // Expunge message
+                if let Ok(email_changes) = self.email_delete(account_id, document_id).await? {
+                    changes.merge(email_changes);
+                }
+                continue;
+            }
+
+            // Untag message from this mailbox and add it to the destination mailbox
+            let mut mailboxes = TagManager::new(mailboxes);
+            mailboxes.update(src_mailbox_id, false);
+            if let Some(move_to) = move_to {
+                let dest_mailbox_id = UidMailbox::new_unassigned(move_to);
+                if !mailboxes.current().contains(&dest_mailbox_id) {
+                    mailboxes.update(
+                        UidMailbox::new(
+                            move_to,
+                            self.assign_imap_uid(account_id, move_to)
+                                .await
+                                .map_err(|_| MethodError::ServerPartialFail)?,
+                        ),
+                        true,
+                    );
+                }
+                changes.log_child_update(Collection::Mailbox, move_to);
+            }
+
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Email)
+                .update_document(document_id);
+            mailboxes.update_batch(&mut batch, Property::MailboxIds);
+            if changes.change_id == u64::MAX {
+                changes.change_id = self.assign_change_id(account_id).await?;
+            }
+            batch.value(Property::Cid, changes.change_id, F_VALUE);
+            match self.write_batch(batch).await {
+                Ok(_) => {
+                    changes.log_update(Collection::Email, Id::from_parts(thread_id, document_id));
+                    changes.log_child_update(Collection::Mailbox, mailbox_id);
+                }
+                Err(MethodError::ServerUnavailable) => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the rule that applies to each mailbox of an account. Rules of the
+    /// account take precedence over the ones of its domain, which take precedence
+    /// over the global rules. Within each level, rules for a mailbox name take
+    /// precedence over rules for a mailbox role.
+    async fn account_retention_rules(
+        &self,
+        account_id: u32,
+    ) -> Result<Vec<(u32, RetentionRule)>, MethodError> {
+        let mut levels = vec![self
+            .directory
+            .retention_rules(account_id)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?];
+        if let Some(principal) = self
+            .directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .map_err(|_| MethodError::ServerPartialFail)?
+        {
+            for domain in principal.domains() {
+                if let Some(settings) = self
+                    .directory
+                    .domain_settings(&domain)
+                    .await
+                    .map_err(|_| MethodError::ServerPartialFail)?
+                {
+                    levels.push(settings.retention);
+                }
+            }
+        }
+        levels.push(self.config.retention_rules.clone());
+
+        let mut rules: Vec<(u32, RetentionRule)> = Vec::new();
+        for level in levels {
+            for by_name in [true, false] {
+                for rule in &level {
+                    let mailbox_id = match (&rule.mailbox, &rule.role) {
+                        (Some(name), _) if by_name => {
+                            self.mailbox_get_by_name(account_id, name).await?
+                        }
+                        (None, Some(role)) if !by_name => {
+                            self.mailbox_get_by_role(account_id, role).await?
+                        }
+                        _ => None,
+                    };
+                    if let Some(mailbox_id) = mailbox_id {
+                        if !rules.iter().any(|(id, _)| *id == mailbox_id) {
+                            rules.push((mailbox_id, rule.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(rules)
+    }
+}
@@ -0,0 +1,100 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::{Duration, Instant};
+
+/// Throttling and retry schedule of an outbound HTTP endpoint, shared by
+/// the push subscription and webhook managers.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub throttle: Duration,
+    pub attempts_interval: Duration,
+    pub attempts_max: u32,
+}
+
+#[derive(Debug)]
+pub struct RetryState {
+    num_attempts: u32,
+    last_request: Instant,
+    in_flight: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    Send,
+    Wait(Duration),
+    InFlight,
+    Exhausted,
+}
+
+impl RetryState {
+    pub fn new(policy: &RetryPolicy) -> Self {
+        RetryState {
+            num_attempts: 0,
+            last_request: Instant::now() - (policy.throttle + Duration::from_millis(1)),
+            in_flight: false,
+        }
+    }
+
+    /// Returns whether a request can be sent now, waiting for the throttle
+    /// period on the first attempt and for the attempts interval after a failure.
+    pub fn next_attempt(&self, policy: &RetryPolicy) -> Attempt {
+        if self.in_flight {
+            return Attempt::InFlight;
+        }
+
+        let wait = if self.num_attempts == 0 {
+            policy.throttle
+        } else {
+            policy.attempts_interval
+        };
+        let elapsed = self.last_request.elapsed();
+        if elapsed < wait {
+            Attempt::Wait(wait - elapsed)
+        } else if self.num_attempts < policy.attempts_max {
+            Attempt::Send
+        } else {
+            Attempt::Exhausted
+        }
+    }
+
+    pub fn sent(&mut self) {
+        self.in_flight = true;
+        self.last_request = Instant::now();
+    }
+
+    pub fn succeeded(&mut self) {
+        self.num_attempts = 0;
+        self.in_flight = false;
+    }
+
+    pub fn failed(&mut self) {
+        self.last_request = Instant::now();
+        self.num_attempts += 1;
+        self.in_flight = false;
+    }
+
+    pub fn reset(&mut self) {
+        self.num_attempts = 0;
+    }
+}
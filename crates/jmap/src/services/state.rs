@@ -32,7 +32,7 @@ use tokio::sync::mpsc;
 use utils::{config::Config, map::bitmap::Bitmap};
 
 use crate::{
-    push::{manager::spawn_push_manager, UpdateSubscription},
+    push::{manager::spawn_push_manager, PushNotification, UpdateSubscription},
     JMAP,
 };
 
@@ -55,6 +55,10 @@ pub enum Event {
         account_id: u32,
         subscriptions: Vec<UpdateSubscription>,
     },
+    Notify {
+        account_id: u32,
+        notification: PushNotification,
+    },
     Stop,
 }
 
@@ -272,6 +276,49 @@ pub fn spawn_state_manager(
                         }
                     }
                 }
+                Event::Notify {
+                    account_id,
+                    notification,
+                } => {
+                    let current_time = SystemTime::now()
+                        .duration_since(SystemTime::UNIX_EPOCH)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0);
+                    let push_ids = subscribers
+                        .get(&account_id)
+                        .map(|subscribers| {
+                            subscribers
+                                .iter()
+                                .filter_map(|(subscriber_id, subscriber)| {
+                                    match &subscriber.subscription {
+                                        SubscriberType::Push { expires }
+                                            if expires > &current_time
+                                                && subscriber.types.contains(DataType::Email) =>
+                                        {
+                                            Some(Id::from_parts(
+                                                account_id,
+                                                (*subscriber_id).into(),
+                                            ))
+                                        }
+                                        _ => None,
+                                    }
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .unwrap_or_default();
+
+                    if !push_ids.is_empty() {
+                        if let Err(err) = push_tx
+                            .send(crate::push::Event::Notify {
+                                ids: push_ids,
+                                notification,
+                            })
+                            .await
+                        {
+                            tracing::debug!("Error sending push notification: {}", err);
+                        }
+                    }
+                }
                 Event::UpdateSubscriptions {
                     account_id,
                     subscriptions,
@@ -434,6 +481,28 @@ impl JMAP {
         }
     }
 
+    pub async fn notify_push_subscriptions(
+        &self,
+        account_id: u32,
+        notification: PushNotification,
+    ) -> bool {
+        match self
+            .state_tx
+            .clone()
+            .send(Event::Notify {
+                account_id,
+                notification,
+            })
+            .await
+        {
+            Ok(_) => true,
+            Err(err) => {
+                tracing::error!("Channel failure while publishing notification: {}", err);
+                false
+            }
+        }
+    }
+
     pub async fn update_push_subscriptions(&self, account_id: u32) -> bool {
         let push_subs = match self.fetch_push_subscriptions(account_id).await {
             Ok(push_subs) => push_subs,
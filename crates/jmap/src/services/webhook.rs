@@ -0,0 +1,238 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{collections::VecDeque, sync::Arc};
+
+use reqwest::header::CONTENT_TYPE;
+use store::{ahash::AHashSet, write::now};
+use tokio::sync::mpsc;
+use utils::webhooks::{Webhook, WebhookEvent};
+
+use crate::LONG_SLUMBER;
+
+use super::{
+    retry::{Attempt, RetryPolicy, RetryState},
+    IPC_CHANNEL_BUFFER,
+};
+
+enum Event {
+    DeliverySuccess {
+        id: usize,
+    },
+    DeliveryFailure {
+        id: usize,
+        events: Vec<WebhookEvent>,
+    },
+}
+
+struct WebhookServer {
+    hook: Arc<Webhook>,
+    policy: RetryPolicy,
+    retry: RetryState,
+    events: VecDeque<WebhookEvent>,
+    failed: Vec<WebhookEvent>,
+}
+
+#[derive(serde::Serialize)]
+struct WebhookRequest<'x> {
+    events: &'x [WebhookEvent],
+}
+
+pub fn spawn_webhook_manager(webhooks: Vec<Webhook>, mut webhook_rx: mpsc::Receiver<WebhookEvent>) {
+    let (result_tx, mut result_rx) = mpsc::channel::<Event>(IPC_CHANNEL_BUFFER);
+
+    tokio::spawn(async move {
+        let mut servers = webhooks
+            .into_iter()
+            .map(|hook| {
+                let policy = RetryPolicy {
+                    throttle: hook.throttle,
+                    attempts_interval: hook.attempts_interval,
+                    attempts_max: hook.attempts_max,
+                };
+                WebhookServer {
+                    hook: Arc::new(hook),
+                    retry: RetryState::new(&policy),
+                    policy,
+                    events: VecDeque::new(),
+                    failed: Vec::new(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut retry_ids = AHashSet::default();
+        let mut retry_timeout = LONG_SLUMBER;
+
+        loop {
+            tokio::select! {
+                event = webhook_rx.recv() => match event {
+                    Some(event) => {
+                        for (id, server) in servers.iter_mut().enumerate() {
+                            if server.hook.events.contains(&event.typ) {
+                                server.push(event.clone());
+                                retry_ids.insert(id);
+                            }
+                        }
+                    }
+                    None => break,
+                },
+                Some(event) = result_rx.recv() => match event {
+                    Event::DeliverySuccess { id } => {
+                        servers[id].retry.succeeded();
+                        retry_ids.insert(id);
+                    }
+                    Event::DeliveryFailure { id, events } => {
+                        let server = &mut servers[id];
+                        server.retry.failed();
+                        server.failed = events;
+                        retry_ids.insert(id);
+                    }
+                },
+                _ = tokio::time::sleep(retry_timeout) => (),
+            }
+
+            // Deliver pending events, a failed batch is retried on its own
+            // until it is delivered or the maximum number of attempts is reached.
+            retry_timeout = LONG_SLUMBER;
+            retry_ids.retain(|id| {
+                let server = &mut servers[*id];
+                let mut attempt = server.retry.next_attempt(&server.policy);
+                if attempt == Attempt::Exhausted {
+                    tracing::warn!(
+                        context = "webhook",
+                        event = "error",
+                        id = server.hook.id,
+                        url = server.hook.url,
+                        discarded = server.failed.len(),
+                        "Too many failed attempts, discarding webhook events."
+                    );
+                    server.failed.clear();
+                    server.retry.reset();
+                    attempt = server.retry.next_attempt(&server.policy);
+                }
+                if server.failed.is_empty() && server.events.is_empty() {
+                    return false;
+                }
+
+                match attempt {
+                    Attempt::Send => {
+                        server.send(*id, result_tx.clone());
+                        false
+                    }
+                    Attempt::Wait(wait) => {
+                        retry_timeout = std::cmp::min(retry_timeout, wait);
+                        true
+                    }
+                    Attempt::InFlight | Attempt::Exhausted => false,
+                }
+            });
+        }
+    });
+}
+
+impl WebhookServer {
+    fn push(&mut self, event: WebhookEvent) {
+        if self.events.len() >= self.hook.buffer_max {
+            self.events.pop_front();
+            tracing::warn!(
+                context = "webhook",
+                event = "error",
+                id = self.hook.id,
+                url = self.hook.url,
+                "Webhook buffer is full, discarding oldest event."
+            );
+        }
+        self.events.push_back(event);
+    }
+
+    fn send(&mut self, id: usize, result_tx: mpsc::Sender<Event>) {
+        let hook = self.hook.clone();
+        let events = if !self.failed.is_empty() {
+            std::mem::take(&mut self.failed)
+        } else {
+            std::mem::take(&mut self.events).into()
+        };
+
+        self.retry.sent();
+
+        tokio::spawn(async move {
+            result_tx
+                .send(if post_events(&hook, &events).await {
+                    Event::DeliverySuccess { id }
+                } else {
+                    Event::DeliveryFailure { id, events }
+                })
+                .await
+                .ok();
+        });
+    }
+}
+
+async fn post_events(hook: &Webhook, events: &[WebhookEvent]) -> bool {
+    let body = serde_json::to_string(&WebhookRequest { events }).unwrap_or_default();
+    let mut request = reqwest::Client::builder()
+        .timeout(hook.timeout)
+        .danger_accept_invalid_certs(hook.tls_allow_invalid_certs)
+        .build()
+        .unwrap_or_default()
+        .post(&hook.url)
+        .header(CONTENT_TYPE, "application/json");
+
+    for (name, value) in &hook.headers {
+        request = request.header(name, value);
+    }
+    if let Some((username, secret)) = &hook.auth {
+        request = request.basic_auth(username, Some(secret));
+    }
+    let timestamp = now();
+    if let Some(signature) = hook.sign(timestamp, body.as_bytes()) {
+        request = request
+            .header("X-Signature", signature)
+            .header("X-Signature-Timestamp", timestamp.to_string());
+    }
+
+    match request.body(body).send().await {
+        Ok(response) if response.status().is_success() => true,
+        Ok(response) => {
+            tracing::debug!(
+                context = "webhook",
+                event = "error",
+                id = hook.id,
+                url = hook.url,
+                status = response.status().as_u16(),
+                "Webhook endpoint rejected the request."
+            );
+            false
+        }
+        Err(err) => {
+            tracing::debug!(
+                context = "webhook",
+                event = "error",
+                id = hook.id,
+                url = hook.url,
+                reason = %err,
+                "Failed to deliver webhook events."
+            );
+            false
+        }
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    method::get::{GetRequest, GetResponse, RequestArguments},
+    object::Object,
+    types::{collection::Collection, date::UTCDate, property::Property, value::Value},
+};
+
+use crate::JMAP;
+
+impl JMAP {
+    pub async fn share_notification_get(
+        &self,
+        mut request: GetRequest<RequestArguments>,
+    ) -> Result<GetResponse, MethodError> {
+        let ids = request.unwrap_ids(self.config.get_max_objects)?;
+        let properties = request.unwrap_properties(&[
+            Property::Id,
+            Property::Created,
+            Property::ChangedBy,
+            Property::ObjectType,
+            Property::ObjectAccountId,
+            Property::ObjectId,
+            Property::OldRights,
+            Property::NewRights,
+            Property::Name,
+        ]);
+        let account_id = request.account_id.document_id();
+        let notification_ids = self
+            .get_document_ids(account_id, Collection::ShareNotification)
+            .await?
+            .unwrap_or_default();
+        let ids = if let Some(ids) = ids {
+            ids
+        } else {
+            notification_ids
+                .iter()
+                .take(self.config.get_max_objects)
+                .map(Into::into)
+                .collect::<Vec<_>>()
+        };
+        let mut response = GetResponse {
+            account_id: request.account_id.into(),
+            state: self
+                .get_state(account_id, Collection::ShareNotification)
+                .await?
+                .into(),
+            list: Vec::with_capacity(ids.len()),
+            not_found: vec![],
+        };
+
+        for id in ids {
+            // Obtain the notification object
+            let document_id = id.document_id();
+            if !notification_ids.contains(document_id) {
+                response.not_found.push(id.into());
+                continue;
+            }
+            let mut notification = if let Some(notification) = self
+                .get_property::<Object<Value>>(
+                    account_id,
+                    Collection::ShareNotification,
+                    document_id,
+                    Property::Value,
+                )
+                .await?
+            {
+                notification
+            } else {
+                response.not_found.push(id.into());
+                continue;
+            };
+
+            let mut result = Object::with_capacity(properties.len());
+            for property in &properties {
+                let value = match property {
+                    Property::Id => Value::Id(id),
+                    Property::Created => notification
+                        .get(property)
+                        .as_uint()
+                        .map(|created| UTCDate::from_timestamp(created as i64).into())
+                        .unwrap_or(Value::Null),
+                    Property::ChangedBy
+                    | Property::ObjectType
+                    | Property::ObjectAccountId
+                    | Property::ObjectId
+                    | Property::OldRights
+                    | Property::NewRights
+                    | Property::Name => notification.remove(property),
+                    _ => Value::Null,
+                };
+
+                result.append(property.clone(), value);
+            }
+            response.list.push(result);
+        }
+
+        Ok(response)
+    }
+}
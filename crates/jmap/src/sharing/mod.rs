@@ -0,0 +1,187 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::QueryBy;
+use jmap_proto::{
+    error::method::MethodError,
+    object::{index::ObjectIndexBuilder, Object},
+    types::{
+        acl::Acl,
+        collection::Collection,
+        id::Id,
+        property::Property,
+        state::StateChange,
+        type_state::DataType,
+        value::{AclGrant, Value},
+    },
+};
+use store::write::{log::ChangeLogBuilder, now, BatchBuilder};
+use utils::map::bitmap::Bitmap;
+
+use crate::{auth::AccessToken, mailbox::get::mailbox_rights, JMAP};
+
+use self::set::SCHEMA;
+
+pub mod get;
+pub mod public;
+pub mod query;
+pub mod seen;
+pub mod set;
+
+#[derive(Debug, Default)]
+pub struct AclChanges {
+    pub name: String,
+    pub current: Vec<AclGrant>,
+    pub changes: Vec<AclGrant>,
+}
+
+impl AclChanges {
+    pub fn new(builder: &ObjectIndexBuilder) -> Option<Self> {
+        let changes = builder.changes()?;
+        let acl = changes.get(&Property::Acl).as_acl()?;
+        let current = builder.current().map(|current| &current.inner);
+
+        Some(AclChanges {
+            name: changes
+                .get(&Property::Name)
+                .as_string()
+                .or_else(|| current?.get(&Property::Name).as_string())
+                .unwrap_or_default()
+                .to_string(),
+            current: current
+                .and_then(|current| current.get(&Property::Acl).as_acl())
+                .cloned()
+                .unwrap_or_default(),
+            changes: acl.clone(),
+        })
+    }
+}
+
+impl JMAP {
+    pub async fn share_notify(
+        &self,
+        changed_by: &AccessToken,
+        collection: Collection,
+        account_id: u32,
+        document_id: u32,
+        acl_changes: AclChanges,
+    ) -> Result<(), MethodError> {
+        // Obtain the principals whose rights changed
+        let mut sharees = Vec::new();
+        for item in &acl_changes.current {
+            let new_grants = acl_changes
+                .changes
+                .iter()
+                .find(|change| change.account_id == item.account_id)
+                .map(|change| change.grants);
+            if new_grants != Some(item.grants) {
+                sharees.push((item.account_id, Some(item.grants), new_grants));
+            }
+        }
+        for item in &acl_changes.changes {
+            if !acl_changes
+                .current
+                .iter()
+                .any(|current| current.account_id == item.account_id)
+            {
+                sharees.push((item.account_id, None, Some(item.grants)));
+            }
+        }
+        sharees.retain(|(account_id, _, _)| *account_id != changed_by.primary_id());
+        if sharees.is_empty() {
+            return Ok(());
+        }
+
+        // Build the notification
+        let changed_by = Object::with_capacity(3)
+            .with_property(
+                Property::Name,
+                changed_by
+                    .description
+                    .clone()
+                    .unwrap_or_else(|| changed_by.name.clone()),
+            )
+            .with_property(
+                Property::Email,
+                self.directory
+                    .query(QueryBy::Id(changed_by.primary_id()), false)
+                    .await
+                    .unwrap_or_default()
+                    .and_then(|principal| principal.emails.into_iter().next())
+                    .map(Value::Text)
+                    .unwrap_or(Value::Null),
+            )
+            .with_property(Property::PrincipalId, Id::from(changed_by.primary_id()));
+        let object_type = DataType::try_from(collection).unwrap_or(DataType::None);
+        let created = now();
+
+        for (sharee_id, old_grants, new_grants) in sharees {
+            let notification = Object::with_capacity(9)
+                .with_property(Property::Created, created)
+                .with_property(Property::ChangedBy, changed_by.clone())
+                .with_property(Property::ObjectType, object_type.as_str().to_string())
+                .with_property(Property::ObjectAccountId, Id::from(account_id))
+                .with_property(Property::ObjectId, Id::from(document_id))
+                .with_property(Property::OldRights, share_rights(collection, old_grants))
+                .with_property(Property::NewRights, share_rights(collection, new_grants))
+                .with_property(Property::Name, acl_changes.name.clone());
+
+            // Insert record
+            let notification_id = self
+                .assign_document_id(sharee_id, Collection::ShareNotification)
+                .await?;
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(sharee_id)
+                .with_collection(Collection::ShareNotification)
+                .create_document(notification_id)
+                .custom(ObjectIndexBuilder::new(SCHEMA).with_changes(notification));
+            self.write_batch(batch).await?;
+            let mut changes = ChangeLogBuilder::new();
+            changes.log_insert(Collection::ShareNotification, notification_id);
+            let change_id = self.commit_changes(sharee_id, changes).await?;
+
+            // Notify the sharee
+            self.broadcast_state_change(
+                StateChange::new(sharee_id).with_change(DataType::ShareNotification, change_id),
+            )
+            .await;
+        }
+
+        Ok(())
+    }
+}
+
+fn share_rights(collection: Collection, grants: Option<Bitmap<Acl>>) -> Value {
+    match (collection, grants) {
+        (Collection::Mailbox, Some(grants)) => mailbox_rights(&grants).into(),
+        (_, Some(grants)) => {
+            let mut rights = Object::with_capacity(grants.bitmap.count_ones() as usize);
+            for grant in grants {
+                rights.append(Property::_T(grant.to_string()), true);
+            }
+            rights.into()
+        }
+        (_, None) => Value::Null,
+    }
+}
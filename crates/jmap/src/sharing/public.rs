@@ -0,0 +1,137 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::QueryBy;
+use jmap_proto::types::{acl::Acl, collection::Collection};
+use utils::map::bitmap::Bitmap;
+
+use crate::{auth::AccessToken, JMAP};
+
+#[derive(Debug, Clone)]
+pub struct PublicFolder {
+    pub namespace: String,
+    pub principal: String,
+    pub rights: Bitmap<Acl>,
+}
+
+/// Parses the public folder namespaces, each one backed by a dedicated
+/// principal whose mailboxes are visible to all users, i.e.
+/// `sharing.public."Public".principal = "public"`.
+pub fn parse_public_folders(settings: &utils::config::Config) -> Result<Vec<PublicFolder>, String> {
+    let mut folders = Vec::new();
+    for namespace in settings.sub_keys("sharing.public", ".principal") {
+        if namespace.is_empty() || namespace.contains('/') {
+            return Err(format!("Invalid public folder namespace {namespace:?}."));
+        }
+
+        let mut rights = Bitmap::new();
+        for (_, right) in settings.values(("sharing.public", namespace, "rights")) {
+            rights.insert(
+                (0..Acl::None as u64)
+                    .map(Acl::from)
+                    .find(|acl| acl.to_string() == right)
+                    .ok_or_else(|| {
+                        format!("Invalid right {right:?} for public folder {namespace:?}.")
+                    })?,
+            );
+        }
+        if rights.is_empty() {
+            rights.insert(Acl::Read);
+            rights.insert(Acl::ReadItems);
+        }
+
+        folders.push(PublicFolder {
+            namespace: namespace.to_string(),
+            principal: settings
+                .value_require(("sharing.public", namespace, "principal"))?
+                .to_string(),
+            rights,
+        });
+    }
+
+    Ok(folders)
+}
+
+impl JMAP {
+    /// Grants the default rights of all public folders to a user, unless
+    /// the user is the owner of the principal backing the namespace.
+    pub async fn add_public_access(&self, access_token: &mut AccessToken) -> Option<()> {
+        for folder in &self.config.public_folders {
+            let account_id = match self
+                .directory
+                .query(QueryBy::Name(&folder.principal), false)
+                .await
+            {
+                Ok(Some(principal)) => principal.id,
+                Ok(None) => {
+                    tracing::debug!(
+                        context = "public_folders",
+                        event = "not-found",
+                        principal = folder.principal.as_str(),
+                        "Public folder principal not found."
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    tracing::error!(
+                        event = "error",
+                        context = "public_folders",
+                        error = ?err,
+                        "Failed to query public folder principal.");
+                    return None;
+                }
+            };
+
+            if access_token.is_member(account_id) {
+                continue;
+            }
+
+            let mut collections: Bitmap<Collection> = Bitmap::new();
+            if folder.rights.contains(Acl::Read) || folder.rights.contains(Acl::Administer) {
+                collections.insert(Collection::Mailbox);
+            }
+            if folder.rights.contains(Acl::ReadItems) || folder.rights.contains(Acl::Administer) {
+                collections.insert(Collection::Email);
+            }
+            if let Some((_, sharing)) = access_token
+                .access_to
+                .iter_mut()
+                .find(|(to_account_id, _)| *to_account_id == account_id)
+            {
+                sharing.union(&collections);
+            } else if !collections.is_empty() {
+                access_token.access_to.push((account_id, collections));
+            }
+            access_token.public_acl.push((account_id, folder.rights));
+        }
+
+        Some(())
+    }
+
+    pub fn public_folder(&self, name: &str) -> Option<&PublicFolder> {
+        self.config
+            .public_folders
+            .iter()
+            .find(|folder| folder.principal == name)
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    types::{collection::Collection, keyword::Keyword, property::Property},
+};
+use store::{
+    query::Filter,
+    roaring::RoaringBitmap,
+    write::{
+        assert::{AssertValue, HashedValue},
+        BatchBuilder, F_BITMAP, F_CLEAR, F_VALUE,
+    },
+};
+
+use crate::{auth::AccessToken, JMAP};
+
+impl JMAP {
+    /// Returns the messages seen by the user. On accounts the user does not
+    /// own, the seen state is kept per user rather than in the owner's keywords.
+    pub async fn seen_messages(
+        &self,
+        access_token: &AccessToken,
+        account_id: u32,
+    ) -> Result<Option<RoaringBitmap>, MethodError> {
+        if access_token.is_member(account_id) {
+            self.get_tag(
+                account_id,
+                Collection::Email,
+                Property::Keywords,
+                Keyword::Seen,
+            )
+            .await
+        } else {
+            self.get_tag(
+                account_id,
+                Collection::Email,
+                Property::SeenBy,
+                access_token.primary_id(),
+            )
+            .await
+        }
+    }
+
+    /// Adds to the batch the operations needed to mark a message as seen or
+    /// unseen by a sharee, leaving the owner's keywords untouched.
+    pub async fn shared_seen_batch(
+        &self,
+        batch: &mut BatchBuilder,
+        account_id: u32,
+        document_id: u32,
+        seen_by: u32,
+        seen: bool,
+    ) -> Result<(), MethodError> {
+        let (assert_value, mut seen_by_ids) = match self
+            .get_property::<HashedValue<Vec<u32>>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::SeenBy,
+            )
+            .await?
+        {
+            Some(current) => (AssertValue::Hash(current.hash), current.inner),
+            None => (AssertValue::None, Vec::new()),
+        };
+
+        if seen_by_ids.contains(&seen_by) != seen {
+            if seen {
+                seen_by_ids.push(seen_by);
+                batch.value(Property::SeenBy, seen_by, F_BITMAP);
+            } else {
+                seen_by_ids.retain(|id| *id != seen_by);
+                batch.value(Property::SeenBy, seen_by, F_BITMAP | F_CLEAR);
+            }
+            batch.assert_value(Property::SeenBy, assert_value);
+            if !seen_by_ids.is_empty() {
+                batch.value(Property::SeenBy, seen_by_ids, F_VALUE);
+            } else {
+                batch.value(Property::SeenBy, (), F_VALUE | F_CLEAR);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a filter for messages with a keyword, matching `$seen` against the
+/// user's own seen state on accounts they do not own.
+pub fn keyword_filter(access_token: &AccessToken, account_id: u32, keyword: Keyword) -> Filter {
+    if keyword == Keyword::Seen && !access_token.is_member(account_id) {
+        Filter::is_in_bitmap(Property::SeenBy, access_token.primary_id())
+    } else {
+        Filter::is_in_bitmap(Property::Keywords, keyword)
+    }
+}
+
+/// Replaces the owner's `$seen` keyword with the sharee's seen state,
+/// returning whether the owner had seen the message.
+pub fn apply_shared_seen(keywords: &mut Vec<Keyword>, is_seen: bool) -> bool {
+    let owner_seen = keywords.contains(&Keyword::Seen);
+    if is_seen && !owner_seen {
+        keywords.push(Keyword::Seen);
+    } else if !is_seen && owner_seen {
+        keywords.retain(|keyword| keyword != &Keyword::Seen);
+    }
+    owner_seen
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use sieve::MatchAs;
+
+use crate::JMAP;
+
+/// Personal address book, populated with the addresses the account has sent mail to.
+pub const ADDRBOOK_PERSONAL: &str = ":addrbook:personal";
+/// Members of a directory group or mailing list, i.e. `:directory:sales@example.org`.
+pub const DIRECTORY_LIST_PREFIX: &str = ":directory:";
+/// Keys of an admin-defined lookup list, i.e. `:lookup:blocked-domains`.
+pub const LOOKUP_LIST_PREFIX: &str = ":lookup:";
+
+impl JMAP {
+    pub async fn add_to_address_book(&self, account_id: u32, addresses: &[&str]) {
+        for address in addresses {
+            if let Err(err) = self
+                .lookup_store
+                .key_set(
+                    addrbook_key(account_id, address),
+                    vec![],
+                    self.config.sieve_addrbook_expiry.into(),
+                )
+                .await
+            {
+                tracing::error!(
+                    context = "sieve",
+                    event = "error",
+                    account_id = account_id,
+                    reason = %err,
+                    "Failed to update personal address book."
+                );
+                break;
+            }
+        }
+    }
+
+    pub(crate) async fn sieve_list_contains(
+        &self,
+        account_id: u32,
+        lists: &[String],
+        values: &[String],
+        match_as: MatchAs,
+    ) -> bool {
+        for list in lists {
+            let result = if list.eq_ignore_ascii_case(ADDRBOOK_PERSONAL) {
+                self.addrbook_contains(account_id, values).await
+            } else if let Some(address) = strip_prefix_ignore_case(list, DIRECTORY_LIST_PREFIX) {
+                self.directory.expn(address).await.map(|members| {
+                    values.iter().any(|value| {
+                        let value = list_address(value);
+                        members
+                            .iter()
+                            .any(|member| member.eq_ignore_ascii_case(value))
+                    })
+                })
+            } else if let Some(store) = strip_prefix_ignore_case(list, LOOKUP_LIST_PREFIX)
+                .and_then(|name| self.smtp.shared.lookup_stores.get(name))
+            {
+                let mut result = Ok(false);
+                for value in values {
+                    result = store
+                        .key_exists(
+                            if !matches!(match_as, MatchAs::Lowercase) {
+                                value.clone()
+                            } else {
+                                value.to_lowercase()
+                            }
+                            .into_bytes(),
+                        )
+                        .await
+                        .map_err(Into::into);
+                    if !matches!(result, Ok(false)) {
+                        break;
+                    }
+                }
+                result
+            } else {
+                tracing::debug!(
+                    context = "sieve",
+                    event = "list-not-found",
+                    account_id = account_id,
+                    list = list,
+                );
+                continue;
+            };
+
+            match result {
+                Ok(true) => return true,
+                Ok(false) => (),
+                Err(err) => {
+                    tracing::error!(
+                        context = "sieve",
+                        event = "error",
+                        account_id = account_id,
+                        list = list,
+                        reason = ?err,
+                        "Failed to query list."
+                    );
+                }
+            }
+        }
+
+        false
+    }
+
+    async fn addrbook_contains(
+        &self,
+        account_id: u32,
+        values: &[String],
+    ) -> directory::Result<bool> {
+        for value in values {
+            if self
+                .lookup_store
+                .key_exists(addrbook_key(account_id, list_address(value)))
+                .await?
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+fn addrbook_key(account_id: u32, address: &str) -> Vec<u8> {
+    format!("abk:{account_id}:{}", address.trim().to_lowercase()).into_bytes()
+}
+
+fn strip_prefix_ignore_case<'x>(value: &'x str, prefix: &str) -> Option<&'x str> {
+    value
+        .get(..prefix.len())
+        .filter(|value_prefix| value_prefix.eq_ignore_ascii_case(prefix))
+        .map(|_| &value[prefix.len()..])
+        .filter(|value| !value.is_empty())
+}
+
+/// Returns the bare address from values such as `"John Doe <jdoe@example.org>"`.
+fn list_address(value: &str) -> &str {
+    value
+        .rsplit_once('<')
+        .and_then(|(_, address)| address.split_once('>'))
+        .map_or(value, |(address, _)| address)
+        .trim()
+}
@@ -0,0 +1,216 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::sync::Arc;
+
+use sieve::{Compiler, Sieve};
+use store::ahash::AHashMap;
+
+use crate::JMAP;
+
+/// Prefix used by ManageSieve to list global scripts, which are read-only.
+pub const GLOBAL_SCRIPT_PREFIX: &str = "global/";
+
+/// Name of the script that includes the mandatory scripts around the active script.
+pub const MANDATORY_SCRIPT_NAME: &str = "@mandatory";
+
+/// Scripts published by the administrator, which users can include with
+/// `include :global` and which can be run before and after the user's active script.
+#[derive(Default)]
+pub struct GlobalScripts {
+    pub scripts: AHashMap<String, GlobalScript>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+pub struct GlobalScript {
+    pub source: Vec<u8>,
+    pub script: Arc<Sieve>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MandatoryScripts {
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl GlobalScripts {
+    pub fn parse(config: &utils::config::Config, compiler: &Compiler) -> Result<Self, String> {
+        let mut scripts = AHashMap::new();
+
+        for id in config.sub_keys("sieve.untrusted.global.scripts", "") {
+            let key = ("sieve.untrusted.global.scripts", id);
+
+            let script = if !config.contains_key(key) {
+                let mut script = String::new();
+                for sub_key in config.sub_keys(key, "") {
+                    script.push_str(config.value_require((
+                        "sieve.untrusted.global.scripts",
+                        id,
+                        sub_key,
+                    ))?);
+                }
+                script
+            } else {
+                config.value_require(key)?.to_string()
+            };
+
+            let compiled = compiler
+                .compile(script.as_bytes())
+                .map_err(|err| format!("Failed to compile global Sieve script {id:?}: {err}"))?;
+            scripts.insert(
+                id.to_string(),
+                GlobalScript {
+                    source: script.into_bytes(),
+                    script: Arc::new(compiled),
+                },
+            );
+        }
+
+        let mut global = GlobalScripts {
+            scripts,
+            before: None,
+            after: None,
+        };
+        for (key, value) in [
+            ("sieve.untrusted.global.before", &mut global.before),
+            ("sieve.untrusted.global.after", &mut global.after),
+        ] {
+            if let Some(name) = config.value(key).filter(|name| !name.is_empty()) {
+                if !global.scripts.contains_key(name) {
+                    return Err(format!(
+                        "Global Sieve script {name:?} referenced by {key:?} does not exist."
+                    ));
+                }
+                *value = Some(name.to_string());
+            }
+        }
+
+        Ok(global)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&GlobalScript> {
+        self.scripts.get(name)
+    }
+}
+
+impl MandatoryScripts {
+    pub fn is_empty(&self) -> bool {
+        self.before.is_none() && self.after.is_none()
+    }
+}
+
+impl JMAP {
+    /// Returns the scripts to run before and after the active script of a recipient,
+    /// using the settings of the recipient's domain when available.
+    pub async fn sieve_mandatory_scripts(&self, rcpt: &str) -> MandatoryScripts {
+        let mut scripts = MandatoryScripts {
+            before: self.sieve_global_scripts.before.clone(),
+            after: self.sieve_global_scripts.after.clone(),
+        };
+
+        if let Some((_, domain)) = rcpt.rsplit_once('@') {
+            match self.directory.domain_settings(domain).await {
+                Ok(Some(settings)) => {
+                    if let Some(before) = settings.sieve_before {
+                        scripts.before = Some(before);
+                    }
+                    if let Some(after) = settings.sieve_after {
+                        scripts.after = Some(after);
+                    }
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    tracing::warn!(
+                        context = "sieve",
+                        event = "error",
+                        domain = domain,
+                        reason = ?err,
+                        "Failed to obtain domain settings."
+                    );
+                }
+            }
+        }
+
+        for name in [&mut scripts.before, &mut scripts.after] {
+            let is_valid = match name.as_deref() {
+                Some("") => false,
+                Some(script_name) if self.sieve_global_scripts.get(script_name).is_none() => {
+                    tracing::warn!(
+                        context = "sieve",
+                        event = "script-not-found",
+                        script = script_name,
+                        "Mandatory global Sieve script not found."
+                    );
+                    false
+                }
+                _ => true,
+            };
+            if !is_valid {
+                *name = None;
+            }
+        }
+
+        scripts
+    }
+
+    /// Builds a script that includes the mandatory scripts around the active script.
+    pub(crate) fn sieve_mandatory_script(
+        &self,
+        mandatory: &MandatoryScripts,
+        active_script: Option<&str>,
+    ) -> Option<Arc<Sieve>> {
+        let mut script = String::from("require \"include\";\r\n");
+        for (location, name) in [
+            (":global", mandatory.before.as_deref()),
+            (":personal", active_script),
+            (":global", mandatory.after.as_deref()),
+        ] {
+            if let Some(name) = name {
+                script.push_str("include ");
+                script.push_str(location);
+                script.push_str(" \"");
+                for ch in name.chars() {
+                    if ['\\', '\"'].contains(&ch) {
+                        script.push('\\');
+                    }
+                    script.push(ch);
+                }
+                script.push_str("\";\r\n");
+            }
+        }
+
+        match self.sieve_compiler.compile(script.as_bytes()) {
+            Ok(script) => Some(Arc::new(script)),
+            Err(err) => {
+                tracing::warn!(
+                    context = "sieve",
+                    event = "error",
+                    reason = %err,
+                    "Failed to compile mandatory Sieve script."
+                );
+                None
+            }
+        }
+    }
+}
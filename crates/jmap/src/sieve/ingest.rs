@@ -21,12 +21,12 @@
  * for more details.
 */
 
-use std::borrow::Cow;
+use std::{borrow::Cow, sync::Arc};
 
 use directory::QueryBy;
 use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
 use mail_parser::MessageParser;
-use sieve::{Envelope, Event, Input, Mailbox, Recipient};
+use sieve::{Envelope, Event, Importance, Input, Mailbox, Recipient, Script};
 use smtp::core::{Session, SessionAddress};
 use store::{
     ahash::AHashSet,
@@ -37,7 +37,11 @@ use utils::listener::stream::NullIo;
 use crate::{
     email::ingest::{IngestEmail, IngestedEmail},
     mailbox::{INBOX_ID, TRASH_ID},
-    sieve::SeenIdHash,
+    push::{PushNotification, PushNotificationType},
+    sieve::{
+        global::{MandatoryScripts, MANDATORY_SCRIPT_NAME},
+        SeenIdHash,
+    },
     IngestError, JMAP,
 };
 
@@ -47,6 +51,7 @@ struct SieveMessage<'x> {
     pub raw_message: Cow<'x, [u8]>,
     pub file_into: Vec<u32>,
     pub flags: Vec<Keyword>,
+    pub is_notification: bool,
 }
 
 impl JMAP {
@@ -57,7 +62,8 @@ impl JMAP {
         envelope_from: &str,
         envelope_to: &str,
         account_id: u32,
-        mut active_script: ActiveScript,
+        mut active_script: Option<ActiveScript>,
+        mandatory_scripts: MandatoryScripts,
     ) -> Result<IngestedEmail, IngestError> {
         // Parse message
         let message = if let Some(message) = MessageParser::new().parse(raw_message) {
@@ -79,37 +85,58 @@ impl JMAP {
         let mut instance = self.sieve_runtime.filter_parsed(message);
 
         // Set account name and obtain quota
-        let (account_quota, mail_from) =
+        let (account_quota, account_emails) =
             match self.directory.query(QueryBy::Id(account_id), false).await {
                 Ok(Some(p)) => {
                     instance.set_user_full_name(p.description().unwrap_or_else(|| p.name()));
-                    (p.quota as i64, p.emails.into_iter().next())
+                    (p.quota as i64, p.emails)
                 }
-                Ok(None) => (0, None),
+                Ok(None) => (0, Vec::new()),
                 Err(_) => {
                     return Err(IngestError::Temporary);
                 }
             };
 
         // Set account address
-        let mail_from = mail_from.unwrap_or_else(|| envelope_to.to_string());
+        let mail_from = account_emails
+            .first()
+            .cloned()
+            .unwrap_or_else(|| envelope_to.to_string());
         instance.set_user_address(&mail_from);
 
         // Set envelope
         instance.set_envelope(Envelope::From, envelope_from);
         instance.set_envelope(Envelope::To, envelope_to);
 
-        let mut input = Input::script(active_script.script_name, active_script.script.clone());
+        let mut input = if !mandatory_scripts.is_empty() {
+            self.sieve_mandatory_script(
+                &mandatory_scripts,
+                active_script
+                    .as_ref()
+                    .map(|script| script.script_name.as_str()),
+            )
+            .map(|script| Input::script(Script::Global(MANDATORY_SCRIPT_NAME.to_string()), script))
+        } else {
+            None
+        }
+        .or_else(|| {
+            active_script
+                .as_ref()
+                .map(|script| Input::script(script.script_name.clone(), script.script.clone()))
+        })
+        .ok_or(IngestError::Temporary)?;
 
         let mut do_discard = false;
         let mut do_deliver = false;
 
         let mut new_ids = AHashSet::new();
         let mut reject_reason = None;
+        let mut push_notifications = Vec::new();
         let mut messages: Vec<SieveMessage> = vec![SieveMessage {
             raw_message: raw_message.into(),
             file_into: Vec::new(),
             flags: Vec::new(),
+            is_notification: false,
         }];
         let now = now();
         let mut ingested_message = IngestedEmail {
@@ -124,9 +151,28 @@ impl JMAP {
             match event {
                 Ok(event) => match event {
                     Event::IncludeScript { name, .. } => {
-                        if let Ok(Some(script)) =
-                            self.sieve_script_get_by_name(account_id, &name).await
-                        {
+                        let script = match &name {
+                            Script::Global(global_name) => self
+                                .sieve_global_scripts
+                                .get(global_name)
+                                .map(|script| script.script.clone()),
+                            Script::Personal(personal_name) => {
+                                if let Some(active_script) = active_script
+                                    .as_ref()
+                                    .filter(|script| &script.script_name == personal_name)
+                                {
+                                    Some(active_script.script.clone())
+                                } else {
+                                    self.sieve_script_get_by_name(account_id, personal_name)
+                                        .await
+                                        .ok()
+                                        .flatten()
+                                        .map(Arc::new)
+                                }
+                            }
+                        };
+
+                        if let Some(script) = script {
                             input = Input::script(name, script);
                         } else {
                             input = false.into();
@@ -209,7 +255,9 @@ impl JMAP {
                     }
                     Event::DuplicateId { id, expiry, last } => {
                         let id_hash = SeenIdHash::new(&id, expiry + now);
-                        let seen_id = active_script.seen_ids.ids.contains(&id_hash);
+                        let seen_id = active_script
+                            .as_ref()
+                            .is_some_and(|script| script.seen_ids.ids.contains(&id_hash));
                         if !seen_id || last {
                             new_ids.insert(id_hash);
                         }
@@ -328,20 +376,35 @@ impl JMAP {
                     } => {
                         input = true.into();
                         if let Some(message) = messages.get(message_id) {
+                            let mut recipients = match recipient {
+                                Recipient::Address(rcpt) => vec![rcpt],
+                                Recipient::Group(rcpts) => rcpts,
+                                Recipient::List(_) => {
+                                    // Not yet implemented
+                                    continue;
+                                }
+                            };
+
+                            if message.is_notification {
+                                // Do not notify the account's own addresses, avoiding loops
+                                recipients.retain(|rcpt| {
+                                    !rcpt.eq_ignore_ascii_case(envelope_to)
+                                        && !account_emails
+                                            .iter()
+                                            .any(|email| rcpt.eq_ignore_ascii_case(email))
+                                });
+                                if recipients.is_empty()
+                                    || !self.sieve_notify_allowed(account_id).await
+                                {
+                                    continue;
+                                }
+                            }
+
                             if message.raw_message.len() <= self.config.mail_max_size {
                                 let result = Session::<NullIo>::sieve(
                                     self.smtp.clone(),
                                     SessionAddress::new(mail_from.clone()),
-                                    match recipient {
-                                        Recipient::Address(rcpt) => vec![SessionAddress::new(rcpt)],
-                                        Recipient::Group(rcpts) => {
-                                            rcpts.into_iter().map(SessionAddress::new).collect()
-                                        }
-                                        Recipient::List(_) => {
-                                            // Not yet implemented
-                                            continue;
-                                        }
-                                    },
+                                    recipients.into_iter().map(SessionAddress::new).collect(),
                                     message.raw_message.to_vec(),
                                 )
                                 .queue_message()
@@ -371,18 +434,68 @@ impl JMAP {
                             continue;
                         }
                     }
-                    Event::ListContains { .. }
-                    | Event::Function { .. }
-                    | Event::Notify { .. }
-                    | Event::SetEnvelope { .. } => {
+                    Event::Notify {
+                        from,
+                        importance,
+                        message,
+                        method,
+                        ..
+                    } => {
+                        if method.eq_ignore_ascii_case(&self.config.sieve_notify_push_uri)
+                            && self.sieve_notify_allowed(account_id).await
+                        {
+                            push_notifications.push(PushNotification {
+                                type_: PushNotificationType::SieveNotification,
+                                account_id: Id::from(account_id),
+                                email_id: None,
+                                importance: match importance {
+                                    Importance::High => "high",
+                                    Importance::Normal => "normal",
+                                    Importance::Low => "low",
+                                }
+                                .to_string(),
+                                from,
+                                message,
+                            });
+                            input = true.into();
+                        } else {
+                            tracing::debug!(
+                                context = "sieve_script_ingest",
+                                event = "notify",
+                                method = method.as_str(),
+                                "Notification not sent."
+                            );
+                            input = false.into();
+                        }
+                    }
+                    Event::ListContains {
+                        lists,
+                        values,
+                        match_as,
+                    } => {
+                        input = self
+                            .sieve_list_contains(account_id, &lists, &values, match_as)
+                            .await
+                            .into();
+                    }
+                    Event::Function { .. } | Event::SetEnvelope { .. } => {
                         // Not allowed
                         input = false.into();
                     }
                     Event::CreatedMessage { message, .. } => {
+                        let is_notification = MessageParser::new()
+                            .parse_headers(&message)
+                            .and_then(|message| {
+                                message
+                                    .header_raw("Auto-Submitted")
+                                    .map(|value| value.trim().eq_ignore_ascii_case("auto-notified"))
+                            })
+                            .unwrap_or(false);
                         messages.push(SieveMessage {
                             raw_message: message.into(),
                             file_into: Vec::new(),
                             flags: Vec::new(),
+                            is_notification,
                         });
                         input = true.into();
                     }
@@ -457,8 +570,20 @@ impl JMAP {
             }
         }
 
+        // Notify push subscriptions
+        for mut notification in push_notifications {
+            if has_delivered {
+                notification.email_id = ingested_message.id.into();
+            }
+            self.notify_push_subscriptions(account_id, notification)
+                .await;
+        }
+
         // Save new ids script changes
-        if !new_ids.is_empty() || active_script.seen_ids.has_changes {
+        if let Some(active_script) = active_script
+            .as_mut()
+            .filter(|script| !new_ids.is_empty() || script.seen_ids.has_changes)
+        {
             active_script.seen_ids.ids.extend(new_ids);
             let mut batch = BatchBuilder::new();
             batch
@@ -467,7 +592,7 @@ impl JMAP {
                 .update_document(active_script.document_id)
                 .value(
                     Property::EmailIds,
-                    Bincode::new(active_script.seen_ids),
+                    Bincode::new(std::mem::take(&mut active_script.seen_ids)),
                     F_VALUE,
                 );
             let _ = self.write_batch(batch).await;
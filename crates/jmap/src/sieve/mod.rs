@@ -27,8 +27,11 @@ use serde::ser::SerializeSeq;
 use sieve::Sieve;
 use store::{ahash::AHashSet, blake3, write::now};
 
+pub mod extlists;
 pub mod get;
+pub mod global;
 pub mod ingest;
+pub mod notify;
 pub mod query;
 pub mod set;
 pub mod validate;
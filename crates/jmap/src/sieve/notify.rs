@@ -0,0 +1,61 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use crate::JMAP;
+
+/// Default URI used by Sieve scripts to notify the account's push subscriptions.
+pub const PUSH_NOTIFY_URI: &str = "https://jmap/push";
+
+impl JMAP {
+    pub(crate) async fn sieve_notify_allowed(&self, account_id: u32) -> bool {
+        match self
+            .lookup_store
+            .is_rate_allowed(
+                format!("sntfy:{account_id}").as_bytes(),
+                &self.config.sieve_notify_rate,
+                false,
+            )
+            .await
+        {
+            Ok(None) => true,
+            Ok(Some(_)) => {
+                tracing::debug!(
+                    context = "sieve_script_ingest",
+                    event = "rate-limited",
+                    account_id = account_id,
+                    "Too many Sieve notifications."
+                );
+                false
+            }
+            Err(err) => {
+                tracing::error!(
+                    context = "sieve_script_ingest",
+                    event = "error",
+                    reason = %err,
+                    "Failed to check Sieve notification rate."
+                );
+                false
+            }
+        }
+    }
+}
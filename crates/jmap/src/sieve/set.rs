@@ -248,6 +248,7 @@ impl JMAP {
 
                         if !batch.is_empty() {
                             changes.log_update(Collection::SieveScript, document_id);
+                            self.track_domain_usage(&mut batch).await?;
                             match self.store.write(batch.build()).await {
                                 Ok(_) => (),
                                 Err(store::Error::AssertValueFailed) => {
@@ -109,6 +109,25 @@ impl JMAP {
                         match (queued_message.as_ref(), push.remove(property)) {
                             (Some(message), Value::Object(mut status)) => {
                                 for rcpt in &message.recipients {
+                                    // Notifications received for this recipient take precedence
+                                    let current = status.get(&Property::_T(rcpt.address.clone()));
+                                    if matches!(
+                                        current,
+                                        Value::Object(current)
+                                            if matches!(
+                                                current.get(&Property::Delivered),
+                                                Value::Text(delivered)
+                                                    if delivered == "yes" || delivered == "no"
+                                            )
+                                    ) {
+                                        continue;
+                                    }
+                                    let displayed = match current {
+                                        Value::Object(current) => {
+                                            current.get(&Property::Displayed).clone()
+                                        }
+                                        _ => Value::Text("unknown".to_string()),
+                                    };
                                     status.set(
                                         Property::_T(rcpt.address.clone()),
                                         Object::with_capacity(3)
@@ -142,7 +161,7 @@ impl JMAP {
                                                     }
                                                 },
                                             )
-                                            .with_property(Property::Displayed, "unknown"),
+                                            .with_property(Property::Displayed, displayed),
                                     );
                                 }
 
@@ -163,7 +182,10 @@ impl JMAP {
                     | Property::ThreadId
                     | Property::Envelope
                     | Property::SendAt => push.remove(property),
-                    Property::MdnBlobIds | Property::DsnBlobIds => Value::List(vec![]),
+                    Property::MdnBlobIds | Property::DsnBlobIds => match push.remove(property) {
+                        Value::Null => Value::List(vec![]),
+                        value => value,
+                    },
                     _ => Value::Null,
                 };
 
@@ -23,4 +23,5 @@
 
 pub mod get;
 pub mod query;
+pub mod report;
 pub mod set;
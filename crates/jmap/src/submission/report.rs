@@ -0,0 +1,480 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap_proto::{
+    error::method::MethodError,
+    object::{index::ObjectIndexBuilder, Object},
+    types::{
+        blob::BlobId, collection::Collection, property::Property, state::StateChange,
+        type_state::DataType, value::Value,
+    },
+};
+use mail_parser::{Message, MessageParser, MimeHeaders, PartType};
+use store::{
+    query::Filter,
+    write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder},
+};
+
+use crate::JMAP;
+
+use super::set::SCHEMA;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportType {
+    Dsn,
+    Mdn,
+}
+
+/// A delivery status notification (RFC 3464) or message disposition
+/// notification (RFC 8098) received for a previously submitted message.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DeliveryReport {
+    pub envelope_id: Option<String>,
+    pub message_ids: Vec<String>,
+    pub recipients: Vec<RecipientReport>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RecipientReport {
+    pub addresses: Vec<String>,
+    pub delivered: Option<&'static str>,
+    pub smtp_reply: Option<String>,
+    pub displayed: bool,
+}
+
+impl DeliveryReport {
+    pub fn is_report(message: &Message<'_>) -> bool {
+        message.content_type().is_some_and(|content_type| {
+            content_type.ctype().eq_ignore_ascii_case("multipart")
+                && content_type
+                    .subtype()
+                    .is_some_and(|subtype| subtype.eq_ignore_ascii_case("report"))
+        })
+    }
+
+    pub fn parse(message: &Message<'_>) -> Option<(ReportType, Self)> {
+        if !Self::is_report(message) {
+            return None;
+        }
+
+        let mut report_type = None;
+        let mut report = DeliveryReport::default();
+        for part in message.parts.iter().skip(1) {
+            let (ctype, subtype) = if let Some(content_type) = part.content_type() {
+                (
+                    content_type.ctype().to_ascii_lowercase(),
+                    content_type
+                        .subtype()
+                        .unwrap_or_default()
+                        .to_ascii_lowercase(),
+                )
+            } else {
+                continue;
+            };
+
+            match (ctype.as_str(), subtype.as_str(), &part.body) {
+                ("message", "delivery-status" | "global-delivery-status", _) => {
+                    report_type = ReportType::Dsn.into();
+                    report.parse_delivery_status(part.contents());
+                }
+                ("message", "disposition-notification" | "global-disposition-notification", _) => {
+                    report_type = ReportType::Mdn.into();
+                    report.parse_disposition_notification(part.contents());
+                }
+                (_, _, PartType::Message(original)) => {
+                    report.add_message_id(original.message_id());
+                }
+                ("text", "rfc822-headers" | "global-headers", _) => {
+                    report.add_message_id(
+                        MessageParser::new()
+                            .parse_headers(part.contents())
+                            .as_ref()
+                            .and_then(|headers| headers.message_id()),
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        report_type.map(|report_type| (report_type, report))
+    }
+
+    fn parse_delivery_status(&mut self, contents: &[u8]) {
+        let mut groups = parse_fields(contents).into_iter();
+
+        // Per-message fields
+        if let Some(fields) = groups.next() {
+            self.envelope_id = field(&fields, "original-envelope-id").map(|v| v.to_string());
+        }
+
+        // Per-recipient fields
+        for fields in groups {
+            let status = field(&fields, "status").unwrap_or_default();
+            let delivered = match field(&fields, "action")
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+                .as_str()
+            {
+                "failed" => "no",
+                "delayed" => "queued",
+                "delivered" | "relayed" | "expanded" => "yes",
+                _ => continue,
+            };
+            let smtp_reply = field(&fields, "diagnostic-code")
+                .and_then(|code| {
+                    code.split_once(';')
+                        .filter(|(typ, _)| typ.trim().eq_ignore_ascii_case("smtp"))
+                        .map(|(_, reply)| reply.trim().to_string())
+                })
+                .or_else(|| {
+                    let code = match status.chars().next() {
+                        Some('2') => "250",
+                        Some('4') => "451",
+                        Some('5') => "550",
+                        _ => return None,
+                    };
+                    format!("{code} {status}").into()
+                });
+
+            self.recipients.push(RecipientReport {
+                addresses: recipient_addresses(&fields),
+                delivered: delivered.into(),
+                smtp_reply,
+                displayed: false,
+            });
+        }
+    }
+
+    fn parse_disposition_notification(&mut self, contents: &[u8]) {
+        for fields in parse_fields(contents) {
+            if let Some(message_id) = field(&fields, "original-message-id") {
+                self.add_message_id(message_id.trim_matches(['<', '>']).into());
+            }
+            if let Some(disposition) = field(&fields, "disposition") {
+                let displayed = disposition
+                    .split_once(';')
+                    .map(|(_, typ)| typ)
+                    .unwrap_or_default()
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .eq_ignore_ascii_case("displayed");
+                self.recipients.push(RecipientReport {
+                    addresses: recipient_addresses(&fields),
+                    delivered: None,
+                    smtp_reply: None,
+                    displayed,
+                });
+            }
+        }
+    }
+
+    fn add_message_id(&mut self, message_id: Option<&str>) {
+        if let Some(message_id) = message_id.filter(|id| !id.is_empty()) {
+            if !self.message_ids.iter().any(|id| id == message_id) {
+                self.message_ids.push(message_id.to_string());
+            }
+        }
+    }
+}
+
+impl JMAP {
+    /// Attaches a delivered DSN or MDN to the submission it refers to, updating
+    /// the delivery status of the reported recipients.
+    pub async fn email_submission_correlate(
+        &self,
+        account_id: u32,
+        message: &Message<'_>,
+        blob_id: &BlobId,
+    ) -> Result<(), MethodError> {
+        let (report_type, report) = if let Some(report) = DeliveryReport::parse(message) {
+            report
+        } else {
+            return Ok(());
+        };
+
+        // Find the originating submission using the envelope id or the Message-ID
+        let mut document_id = None;
+        for id in report.envelope_id.iter().chain(report.message_ids.iter()) {
+            if let Some(id) = self
+                .filter(
+                    account_id,
+                    Collection::EmailSubmission,
+                    vec![Filter::eq(Property::References, id.as_str())],
+                )
+                .await?
+                .results
+                .max()
+            {
+                document_id = id.into();
+                break;
+            }
+        }
+        let document_id = if let Some(document_id) = document_id {
+            document_id
+        } else {
+            return Ok(());
+        };
+        let submission = if let Some(submission) = self
+            .get_property::<HashedValue<Object<Value>>>(
+                account_id,
+                Collection::EmailSubmission,
+                document_id,
+                Property::Value,
+            )
+            .await?
+        {
+            submission
+        } else {
+            return Ok(());
+        };
+
+        // Update the status of the reported recipients
+        let mut delivery_status = submission.inner.get(&Property::DeliveryStatus).clone();
+        if let Value::Object(delivery_status) = &mut delivery_status {
+            for (rcpt, status) in delivery_status.properties.iter_mut() {
+                let (rcpt, status) = match (rcpt, status) {
+                    (Property::_T(rcpt), Value::Object(status)) => (rcpt, status),
+                    _ => continue,
+                };
+                if let Some(report) = report.recipients.iter().find(|report| {
+                    report
+                        .addresses
+                        .iter()
+                        .any(|address| address.eq_ignore_ascii_case(rcpt))
+                }) {
+                    if let Some(delivered) = report.delivered {
+                        status.set(Property::Delivered, delivered);
+                    }
+                    if let Some(smtp_reply) = &report.smtp_reply {
+                        status.set(Property::SmtpReply, smtp_reply.as_str());
+                    }
+                    if report.displayed {
+                        status.set(Property::Displayed, "yes");
+                    }
+                }
+            }
+        }
+        let property = match report_type {
+            ReportType::Dsn => Property::DsnBlobIds,
+            ReportType::Mdn => Property::MdnBlobIds,
+        };
+        let mut blob_ids = submission.inner.get(&property).clone();
+        if let Value::List(blob_ids) = &mut blob_ids {
+            blob_ids.push(Value::BlobId(blob_id.clone()));
+        } else {
+            blob_ids = Value::List(vec![Value::BlobId(blob_id.clone())]);
+        }
+
+        // Write changes
+        let mut batch = BatchBuilder::new();
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::EmailSubmission)
+            .update_document(document_id)
+            .custom(
+                ObjectIndexBuilder::new(SCHEMA)
+                    .with_current(submission)
+                    .with_changes(
+                        Object::with_capacity(2)
+                            .with_property(Property::DeliveryStatus, delivery_status)
+                            .with_property(property, blob_ids),
+                    ),
+            );
+        self.write_batch(batch).await?;
+        let mut changes = ChangeLogBuilder::new();
+        changes.log_update(Collection::EmailSubmission, document_id);
+        let change_id = self.commit_changes(account_id, changes).await?;
+        self.broadcast_state_change(
+            StateChange::new(account_id).with_change(DataType::EmailSubmission, change_id),
+        )
+        .await;
+
+        Ok(())
+    }
+}
+
+/// Parses the groups of header-style fields in a report body, which are separated by blank lines.
+pub(crate) fn parse_fields(contents: &[u8]) -> Vec<Vec<(String, String)>> {
+    let mut groups = Vec::new();
+    let mut fields: Vec<(String, String)> = Vec::new();
+
+    for line in String::from_utf8_lossy(contents).lines() {
+        if line.trim().is_empty() {
+            if !fields.is_empty() {
+                groups.push(std::mem::take(&mut fields));
+            }
+        } else if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = fields.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            fields.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+    if !fields.is_empty() {
+        groups.push(fields);
+    }
+
+    groups
+}
+
+pub(crate) fn field<'x>(fields: &'x [(String, String)], name: &str) -> Option<&'x str> {
+    fields
+        .iter()
+        .find(|(field, _)| field == name)
+        .map(|(_, value)| value.as_str())
+}
+
+fn recipient_addresses(fields: &[(String, String)]) -> Vec<String> {
+    ["final-recipient", "original-recipient"]
+        .into_iter()
+        .filter_map(|name| field(fields, name))
+        .map(|value| {
+            value
+                .split_once(';')
+                .map_or(value, |(_, address)| address)
+                .trim()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use mail_parser::MessageParser;
+
+    use super::{DeliveryReport, RecipientReport, ReportType};
+
+    #[test]
+    fn parse_reports() {
+        let dsn = concat!(
+            "From: MAILER-DAEMON@example.org\r\n",
+            "To: john@example.org\r\n",
+            "Subject: Delivery Status Notification\r\n",
+            "Content-Type: multipart/report; report-type=\"delivery-status\";\r\n",
+            "\tboundary=\"bnd\"\r\n",
+            "\r\n",
+            "--bnd\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Your message could not be delivered.\r\n",
+            "--bnd\r\n",
+            "Content-Type: message/delivery-status\r\n",
+            "\r\n",
+            "Reporting-MTA: dns;mx.example.org\r\n",
+            "Original-Envelope-Id: abc123\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822;jane@example.com\r\n",
+            "Action: failed\r\n",
+            "Status: 5.1.1\r\n",
+            "Diagnostic-Code: smtp;550 5.1.1 Mailbox does not\r\n",
+            " exist\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822;bill@example.com\r\n",
+            "Action: delayed\r\n",
+            "Status: 4.4.1\r\n",
+            "\r\n",
+            "--bnd\r\n",
+            "Content-Type: message/rfc822\r\n",
+            "\r\n",
+            "Message-ID: <original@example.org>\r\n",
+            "Subject: Hello\r\n",
+            "\r\n",
+            "--bnd--\r\n"
+        );
+        assert_eq!(
+            DeliveryReport::parse(&MessageParser::new().parse(dsn).unwrap()),
+            Some((
+                ReportType::Dsn,
+                DeliveryReport {
+                    envelope_id: Some("abc123".to_string()),
+                    message_ids: vec!["original@example.org".to_string()],
+                    recipients: vec![
+                        RecipientReport {
+                            addresses: vec!["jane@example.com".to_string()],
+                            delivered: Some("no"),
+                            smtp_reply: Some("550 5.1.1 Mailbox does not exist".to_string()),
+                            displayed: false,
+                        },
+                        RecipientReport {
+                            addresses: vec!["bill@example.com".to_string()],
+                            delivered: Some("queued"),
+                            smtp_reply: Some("451 4.4.1".to_string()),
+                            displayed: false,
+                        }
+                    ],
+                }
+            ))
+        );
+
+        let mdn = concat!(
+            "From: jane@example.com\r\n",
+            "To: john@example.org\r\n",
+            "Subject: Read: Hello\r\n",
+            "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+            "\tboundary=\"bnd\"\r\n",
+            "\r\n",
+            "--bnd\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Your message was displayed.\r\n",
+            "--bnd\r\n",
+            "Content-Type: message/disposition-notification\r\n",
+            "\r\n",
+            "Reporting-UA: mail.example.com; Mail Client\r\n",
+            "Final-Recipient: rfc822;jane@example.com\r\n",
+            "Original-Message-ID: <original@example.org>\r\n",
+            "Disposition: manual-action/MDN-sent-manually; displayed\r\n",
+            "\r\n",
+            "--bnd--\r\n"
+        );
+        assert_eq!(
+            DeliveryReport::parse(&MessageParser::new().parse(mdn).unwrap()),
+            Some((
+                ReportType::Mdn,
+                DeliveryReport {
+                    envelope_id: None,
+                    message_ids: vec!["original@example.org".to_string()],
+                    recipients: vec![RecipientReport {
+                        addresses: vec!["jane@example.com".to_string()],
+                        delivered: None,
+                        smtp_reply: None,
+                        displayed: true,
+                    }],
+                }
+            ))
+        );
+
+        assert_eq!(
+            DeliveryReport::parse(
+                &MessageParser::new()
+                    .parse("Subject: Hello\r\n\r\nHi!\r\n")
+                    .unwrap()
+            ),
+            None
+        );
+    }
+}
@@ -48,6 +48,7 @@ use jmap_proto::{
     },
 };
 use mail_parser::{HeaderName, HeaderValue};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use smtp::core::{Session, SessionData, State};
 use smtp_proto::{request::parser::Rfc5321Parser, MailFrom, RcptTo};
 use store::write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, Bincode};
@@ -67,6 +68,10 @@ pub static SCHEMA: &[IndexProperty] = &[
     IndexProperty::new(Property::IdentityId).index_as(IndexAs::Integer),
     IndexProperty::new(Property::ThreadId).index_as(IndexAs::Integer),
     IndexProperty::new(Property::SendAt).index_as(IndexAs::LongInteger),
+    IndexProperty::new(Property::References).index_as(IndexAs::TextList {
+        tokenize: false,
+        index: true,
+    }),
 ];
 
 impl JMAP {
@@ -460,7 +465,7 @@ impl JMAP {
         };
 
         // Make sure the envelope address matches the identity email address
-        let mail_from = if let Some(mail_from) = mail_from {
+        let mut mail_from = if let Some(mail_from) = mail_from {
             if !mail_from.address.eq_ignore_ascii_case(&identity_mail_from) {
                 return Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
                     .with_description(
@@ -546,6 +551,32 @@ impl JMAP {
             }
         }
 
+        // Keep the envelope id and Message-ID, which are used to correlate
+        // delivery and disposition notifications with this submission
+        let env_id = mail_from
+            .env_id
+            .get_or_insert_with(|| {
+                thread_rng()
+                    .sample_iter(Alphanumeric)
+                    .take(24)
+                    .map(char::from)
+                    .collect::<String>()
+            })
+            .clone();
+        let mut references = vec![Value::Text(env_id)];
+        for header in &metadata.contents.parts[0].headers {
+            if header.name == HeaderName::MessageId {
+                match &header.value {
+                    HeaderValue::Text(id) => references.push(Value::Text(id.to_string())),
+                    HeaderValue::TextList(ids) => {
+                        references.extend(ids.iter().map(|id| Value::Text(id.to_string())))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        submission.append(Property::References, Value::List(references));
+
         // Update sendAt
         submission.append(
             Property::SendAt,
@@ -580,6 +611,9 @@ impl JMAP {
         let mut session =
             Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
 
+        // The envelope sender was verified against the identity's address
+        session.data.authenticated_emails = vec![mail_from.address.to_lowercase()];
+
         // MAIL FROM
         let _ = session.handle_mail_from(mail_from).await;
         if let Some(error) = session.has_failed() {
@@ -609,6 +643,14 @@ impl JMAP {
             let response = session.queue_message().await;
             if let State::Accepted(queue_id) = session.state {
                 submission.append(Property::MessageId, queue_id);
+
+                // Add recipients to the personal address book
+                let recipients = responses
+                    .iter()
+                    .filter(|(_, response)| response.is_none())
+                    .map(|(addr, _)| addr.as_str())
+                    .collect::<Vec<_>>();
+                self.add_to_address_book(account_id, &recipients).await;
             } else {
                 return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
                     .with_description(format!(
@@ -90,15 +90,24 @@ async fn main() -> std::io::Result<()> {
 
     // Init servers
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &stores, &directory, delivery_tx)
-        .await
-        .failed("Invalid configuration file");
+    let (webhook_tx, webhook_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+    let smtp = SMTP::init(
+        &config,
+        &servers,
+        &stores,
+        &directory,
+        delivery_tx,
+        webhook_tx,
+    )
+    .await
+    .failed("Invalid configuration file");
     let jmap = JMAP::init(
         &config,
         &stores,
         &directory,
         &mut servers,
         delivery_rx,
+        webhook_rx,
         smtp.clone(),
     )
     .await
@@ -149,6 +158,9 @@ async fn main() -> std::io::Result<()> {
     // Stop services
     let _ = shutdown_tx.send(true);
 
+    // Flush buffered full-text index changes
+    let _ = jmap.fts_store.commit().await;
+
     // Wait for services to finish
     tokio::time::sleep(Duration::from_secs(1)).await;
 
@@ -74,6 +74,7 @@ impl<T: SessionStream> Session<T> {
                 Command::DeleteScript => self.handle_deletescript(request).await,
                 Command::RenameScript => self.handle_renamescript(request).await,
                 Command::CheckScript => self.handle_checkscript(request).await,
+                Command::Password => self.handle_password(request).await,
                 Command::HaveSpace => self.handle_havespace(request).await,
                 Command::Capability => self.handle_capability("").await,
                 Command::Authenticate => self.handle_authenticate(request).await,
@@ -124,6 +125,16 @@ impl<T: SessionStream> Session<T> {
                     Err(StatusResponse::no("Already authenticated."))
                 }
             }
+            Command::Password => {
+                if self.stream.is_tls() || self.imap.allow_plain_auth {
+                    Ok(command)
+                } else {
+                    Err(
+                        StatusResponse::no("Cannot change password over plain-text.")
+                            .with_code(ResponseCode::EncryptNeeded),
+                    )
+                }
+            }
             Command::StartTls => {
                 if !self.stream.is_tls() {
                     Ok(command)
@@ -26,7 +26,7 @@ pub mod session;
 
 use std::{borrow::Cow, net::IpAddr, sync::Arc};
 
-use imap::core::IMAP;
+use imap::core::{SaslExchange, IMAP};
 use imap_proto::receiver::{CommandParser, Receiver};
 use jmap::{auth::AccessToken, JMAP};
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -42,6 +42,7 @@ pub struct Session<T: AsyncRead + AsyncWrite> {
     pub stream: T,
     pub span: tracing::Span,
     pub in_flight: InFlight,
+    pub sasl: Option<SaslExchange>,
 }
 
 pub enum State {
@@ -89,6 +90,7 @@ pub enum Command {
     DeleteScript,
     RenameScript,
     CheckScript,
+    Password,
     #[default]
     Noop,
     Unauthenticate,
@@ -109,6 +111,7 @@ impl CommandParser for Command {
             b"DELETESCRIPT" => Some(Command::DeleteScript),
             b"RENAMESCRIPT" => Some(Command::RenameScript),
             b"CHECKSCRIPT" => Some(Command::CheckScript),
+            b"PASSWORD" => Some(Command::Password),
             b"NOOP" => Some(Command::Noop),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             _ => None,
@@ -142,7 +145,7 @@ pub enum ResponseCode {
     QuotaMaxScripts,
     QuotaMaxSize,
     Referral,
-    Sasl,
+    Sasl(String),
     TransitionNeeded,
     TryLater,
     Active,
@@ -161,7 +164,12 @@ impl ResponseCode {
             ResponseCode::QuotaMaxScripts => b"QUOTA/MAXSCRIPTS",
             ResponseCode::QuotaMaxSize => b"QUOTA/MAXSIZE",
             ResponseCode::Referral => b"REFERRAL",
-            ResponseCode::Sasl => b"SASL",
+            ResponseCode::Sasl(data) => {
+                buf.extend_from_slice(b"SASL \"");
+                buf.extend_from_slice(data.as_bytes());
+                buf.push(b'"');
+                return;
+            }
             ResponseCode::TransitionNeeded => b"TRANSITION-NEEDED",
             ResponseCode::TryLater => b"TRYLATER",
             ResponseCode::Active => b"ACTIVE",
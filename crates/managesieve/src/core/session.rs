@@ -48,6 +48,7 @@ impl SessionManager for ManageSieveSessionManager {
                 stream: session.stream,
                 in_flight: session.in_flight,
                 remote_addr: session.remote_ip,
+                sasl: None,
             };
 
             if session
@@ -156,6 +157,7 @@ impl<T: SessionStream> Session<T> {
             imap: self.imap,
             receiver: self.receiver,
             remote_addr: self.remote_addr,
+            sasl: None,
         })
     }
 }
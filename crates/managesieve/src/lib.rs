@@ -137,6 +137,17 @@ mod tests {
                     ],
                 }],
             ),
+            (
+                vec!["Password \"old secret\" \"new secret\"\r\n"],
+                vec![Request {
+                    tag: "".to_string(),
+                    command: Command::Password,
+                    tokens: vec![
+                        Token::Argument(b"old secret".to_vec()),
+                        Token::Argument(b"new secret".to_vec()),
+                    ],
+                }],
+            ),
             (
                 vec!["NOOP \"STARTTLS-SYNC-42\"\r\n"],
                 vec![Request {
@@ -23,17 +23,20 @@
 
 use std::sync::Arc;
 
-use directory::AuthResult;
+use directory::{core::scram::ScramHash, AuthResult};
+use imap::core::SaslExchange;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
 use imap_proto::{
     protocol::authenticate::Mechanism,
     receiver::{self, Request},
 };
+use jmap::auth::{sasl::ScramExchange, AccessToken};
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use utils::listener::SessionStream;
 
-use crate::core::{Command, Session, State, StatusResponse};
+use crate::core::{Command, ResponseCode, Session, State, StatusResponse};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> crate::op::OpResult {
@@ -60,15 +63,16 @@ impl<T: SessionStream> Session<T> {
                     }
                     .map_err(StatusResponse::no))?
                 } else {
-                    self.receiver.request = receiver::Request {
-                        tag: String::new(),
-                        command: Command::Authenticate,
-                        tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
-                    };
-                    self.receiver.state = receiver::State::Argument { last_ch: b' ' };
-                    return Ok(b"{0}\r\n".to_vec());
+                    return Ok(self.sasl_challenge(mechanism, b""));
                 }
             }
+            Mechanism::ScramSha1
+            | Mechanism::ScramSha1Plus
+            | Mechanism::ScramSha256
+            | Mechanism::ScramSha256Plus => {
+                return self.handle_scram(mechanism, params.pop()).await
+            }
+            Mechanism::External => return self.handle_external(mechanism, params.pop()).await,
             _ => {
                 return Err(StatusResponse::no(
                     "Authentication mechanism not supported.",
@@ -77,20 +81,7 @@ impl<T: SessionStream> Session<T> {
         };
 
         // Throttle authentication requests
-        if self
-            .jmap
-            .is_auth_allowed_soft(&self.remote_addr)
-            .await
-            .is_err()
-        {
-            tracing::debug!(parent: &self.span,
-                event = "disconnect",
-                "Too many authentication attempts, disconnecting.",
-            );
-            return Err(StatusResponse::bye(
-                "Too many authentication requests from this IP address.",
-            ));
-        }
+        self.is_auth_allowed().await?;
 
         // Authenticate
         let access_token = match credentials {
@@ -129,6 +120,169 @@ impl<T: SessionStream> Session<T> {
             }
         };
 
+        self.finish_authentication(access_token, None).await
+    }
+
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> crate::op::OpResult {
+        if response.as_deref() == Some("*") {
+            self.sasl = None;
+            return Err(StatusResponse::no("Authentication exchange cancelled."));
+        }
+
+        let mut exchange = match self.sasl.take() {
+            Some(SaslExchange::Scram(exchange)) => exchange,
+            _ => {
+                self.is_auth_allowed().await?;
+                let (hash, is_plus) = match mechanism {
+                    Mechanism::ScramSha1 => (ScramHash::Sha1, false),
+                    Mechanism::ScramSha1Plus => (ScramHash::Sha1, true),
+                    Mechanism::ScramSha256Plus => (ScramHash::Sha256, true),
+                    _ => (ScramHash::Sha256, false),
+                };
+                let exchange = Box::new(ScramExchange::new(
+                    hash,
+                    is_plus,
+                    self.stream.tls_channel_binding(),
+                ));
+                if response.is_none() {
+                    self.sasl = SaslExchange::Scram(exchange).into();
+                    return Ok(self.sasl_challenge(mechanism, b""));
+                }
+                exchange
+            }
+        };
+
+        let response = response
+            .and_then(|response| base64_decode(response.as_bytes()))
+            .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?;
+        if exchange.is_client_first() {
+            match self.jmap.scram_client_first(&mut exchange, &response).await {
+                Ok(server_first) => {
+                    self.sasl = SaslExchange::Scram(exchange).into();
+                    Ok(self.sasl_challenge(mechanism, server_first.as_bytes()))
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        parent: &self.span,
+                        context = "authenticate",
+                        mechanism = ?mechanism,
+                        reason = %err,
+                        "SCRAM authentication failed."
+                    );
+                    self.finish_authentication(None, None).await
+                }
+            }
+        } else {
+            match self
+                .jmap
+                .scram_client_final(&mut exchange, &response, self.remote_addr)
+                .await
+            {
+                Ok((access_token, server_final)) => {
+                    self.finish_authentication(
+                        Some(access_token),
+                        String::from_utf8(
+                            base64_encode(server_final.as_bytes()).unwrap_or_default(),
+                        )
+                        .unwrap_or_default()
+                        .into(),
+                    )
+                    .await
+                }
+                Err(err) => {
+                    tracing::debug!(
+                        parent: &self.span,
+                        context = "authenticate",
+                        mechanism = ?mechanism,
+                        reason = %err,
+                        "SCRAM authentication failed."
+                    );
+                    self.finish_authentication(None, None).await
+                }
+            }
+        }
+    }
+
+    async fn handle_external(
+        &mut self,
+        mechanism: Mechanism,
+        response: Option<String>,
+    ) -> crate::op::OpResult {
+        let authzid = match response {
+            Some(response) if response == "*" => {
+                self.sasl = None;
+                return Err(StatusResponse::no("Authentication exchange cancelled."));
+            }
+            Some(response) => base64_decode(response.as_bytes())
+                .and_then(|authzid| String::from_utf8(authzid).ok())
+                .ok_or_else(|| StatusResponse::no("Failed to decode challenge."))?,
+            None if self.sasl.is_none() => {
+                // Request the authorization identity
+                self.sasl = SaslExchange::External.into();
+                return Ok(self.sasl_challenge(mechanism, b""));
+            }
+            None => String::new(),
+        };
+        self.sasl = None;
+
+        self.is_auth_allowed().await?;
+        let access_token = self
+            .jmap
+            .authenticate_external(
+                self.stream.tls_client_certificate().as_deref(),
+                &authzid,
+                self.remote_addr,
+            )
+            .await;
+        self.finish_authentication(access_token, None).await
+    }
+
+    fn sasl_challenge(&mut self, mechanism: Mechanism, challenge: &[u8]) -> Vec<u8> {
+        self.receiver.request = receiver::Request {
+            tag: String::new(),
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+        if !challenge.is_empty() {
+            let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 6);
+            buf.push(b'"');
+            buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+            buf.extend_from_slice(b"\"\r\n");
+            buf
+        } else {
+            b"{0}\r\n".to_vec()
+        }
+    }
+
+    async fn is_auth_allowed(&self) -> Result<(), StatusResponse> {
+        if self
+            .jmap
+            .is_auth_allowed_soft(&self.remote_addr)
+            .await
+            .is_err()
+        {
+            tracing::debug!(parent: &self.span,
+                event = "disconnect",
+                "Too many authentication attempts, disconnecting.",
+            );
+            Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn finish_authentication(
+        &mut self,
+        access_token: Option<AccessToken>,
+        server_final: Option<String>,
+    ) -> crate::op::OpResult {
         if let Some(access_token) = access_token {
             // Enforce concurrency limits
             let in_flight = self
@@ -147,7 +301,13 @@ impl<T: SessionStream> Session<T> {
                     in_flight,
                 };
 
-                self.handle_capability("Authentication successful").await
+                if let Some(server_final) = server_final {
+                    Ok(StatusResponse::ok("Authentication successful")
+                        .with_code(ResponseCode::Sasl(server_final))
+                        .into_bytes())
+                } else {
+                    self.handle_capability("Authentication successful").await
+                }
             } else {
                 tracing::debug!(parent: &self.span,
                     event = "disconnect",
@@ -22,7 +22,7 @@
 */
 
 use imap_proto::receiver::Request;
-use jmap::sieve::set::ObjectBlobId;
+use jmap::sieve::{global::GLOBAL_SCRIPT_PREFIX, set::ObjectBlobId};
 use jmap_proto::{
     object::Object,
     types::{collection::Collection, property::Property, value::Value},
@@ -40,7 +40,26 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             .and_then(|s| s.unwrap_string().ok())
             .ok_or_else(|| StatusResponse::no("Expected script name as a parameter."))?;
         let account_id = self.state.access_token().primary_id();
-        let document_id = self.get_script_id(account_id, &name).await?;
+        let document_id = match self.get_script_id(account_id, &name).await {
+            Ok(document_id) => document_id,
+            Err(err) => {
+                // Global scripts are listed with a prefix
+                if let Some(script) = name
+                    .strip_prefix(GLOBAL_SCRIPT_PREFIX)
+                    .and_then(|name| self.jmap.sieve_global_scripts.get(name))
+                {
+                    let mut response = Vec::with_capacity(script.source.len() + 30);
+                    response.push(b'{');
+                    response.extend_from_slice(script.source.len().to_string().as_bytes());
+                    response.extend_from_slice(b"}\r\n");
+                    response.extend_from_slice(&script.source);
+
+                    return Ok(StatusResponse::ok("").serialize(response));
+                } else {
+                    return Err(err);
+                }
+            }
+        };
         let (blob_section, blob_hash) = self
             .jmap
             .get_property::<Object<Value>>(
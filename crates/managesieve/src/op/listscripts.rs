@@ -21,6 +21,7 @@
  * for more details.
 */
 
+use jmap::sieve::global::GLOBAL_SCRIPT_PREFIX;
 use jmap_proto::{
     object::Object,
     types::{collection::Collection, property::Property, value::Value},
@@ -38,7 +39,7 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             .await?
             .unwrap_or_default();
 
-        if document_ids.is_empty() {
+        if document_ids.is_empty() && self.jmap.sieve_global_scripts.scripts.is_empty() {
             return Ok(StatusResponse::ok("").into_bytes());
         }
 
@@ -57,12 +58,7 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             {
                 response.push(b'\"');
                 if let Some(name) = script.get(&Property::Name).as_string() {
-                    for ch in name.as_bytes() {
-                        if [b'\\', b'\"'].contains(ch) {
-                            response.push(b'\\');
-                        }
-                        response.push(*ch);
-                    }
+                    push_name(&mut response, name);
                 }
 
                 if script.get(&Property::IsActive).as_bool() == Some(true) {
@@ -73,6 +69,23 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             }
         }
 
+        // Global scripts are listed read-only
+        for name in self.jmap.sieve_global_scripts.scripts.keys() {
+            response.push(b'\"');
+            push_name(&mut response, GLOBAL_SCRIPT_PREFIX);
+            push_name(&mut response, name);
+            response.extend_from_slice(b"\"\r\n");
+        }
+
         Ok(StatusResponse::ok("").serialize(response))
     }
 }
+
+fn push_name(response: &mut Vec<u8>, name: &str) {
+    for ch in name.as_bytes() {
+        if [b'\\', b'\"'].contains(ch) {
+            response.push(b'\\');
+        }
+        response.push(*ch);
+    }
+}
@@ -34,6 +34,7 @@ pub mod havespace;
 pub mod listscripts;
 pub mod logout;
 pub mod noop;
+pub mod password;
 pub mod putscript;
 pub mod renamescript;
 pub mod setactive;
@@ -0,0 +1,91 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{DirectoryError, ManagementError};
+use imap_proto::receiver::Request;
+use jmap::auth::password::PasswordChangeResult;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::core::{Command, Session, State, StatusResponse};
+
+impl<T: AsyncRead + AsyncWrite> Session<T> {
+    pub async fn handle_password(&mut self, request: Request<Command>) -> super::OpResult {
+        let mut tokens = request
+            .tokens
+            .into_iter()
+            .filter_map(|token| token.unwrap_string().ok())
+            .collect::<Vec<_>>();
+
+        // Unauthenticated clients have to provide the account name, which
+        // allows renewing expired passwords
+        let (username, current_password, new_password) = match (&self.state, tokens.len()) {
+            (State::Authenticated { access_token, .. }, 2) => {
+                let new_password = tokens.pop().unwrap();
+                let current_password = tokens.pop().unwrap();
+                (access_token.name.clone(), current_password, new_password)
+            }
+            (State::NotAuthenticated { .. }, 3) => {
+                let new_password = tokens.pop().unwrap();
+                let current_password = tokens.pop().unwrap();
+                let username = tokens.pop().unwrap();
+                (username, current_password, new_password)
+            }
+            (State::Authenticated { .. }, _) => {
+                return Err(StatusResponse::no(
+                    "Expected current and new password as parameters.",
+                ));
+            }
+            (State::NotAuthenticated { .. }, _) => {
+                return Err(StatusResponse::no(
+                    "Expected account name, current and new password as parameters.",
+                ));
+            }
+        };
+
+        match self
+            .jmap
+            .change_password(
+                &username,
+                &current_password,
+                &new_password,
+                self.remote_addr,
+            )
+            .await
+        {
+            Ok(PasswordChangeResult::Success) => {
+                Ok(StatusResponse::ok("Password changed.").into_bytes())
+            }
+            Ok(PasswordChangeResult::Failure) => Err(StatusResponse::no("Authentication failed")),
+            Ok(PasswordChangeResult::Banned) => Err(StatusResponse::bye(
+                "Too many authentication requests from this IP address.",
+            )),
+            Err(DirectoryError::Management(ManagementError::InvalidPassword(violation))) => {
+                Err(StatusResponse::no(violation.to_string()))
+            }
+            Err(DirectoryError::Unsupported) => Err(StatusResponse::no(
+                "Password changes are not supported by the directory.",
+            )),
+            Err(_) => Err(StatusResponse::database_failure()),
+        }
+    }
+}
@@ -22,7 +22,10 @@
 */
 
 use imap_proto::receiver::Request;
-use jmap::sieve::set::{ObjectBlobId, SCHEMA};
+use jmap::sieve::{
+    global::GLOBAL_SCRIPT_PREFIX,
+    set::{ObjectBlobId, SCHEMA},
+};
 use jmap_proto::{
     object::{index::ObjectIndexBuilder, Object},
     types::{blob::BlobId, collection::Collection, property::Property, value::Value},
@@ -222,6 +225,8 @@ impl<T: AsyncRead + AsyncWrite> Session<T> {
             Err(StatusResponse::no(
                 "The 'vacation' name is reserved, please use a different name.",
             ))
+        } else if name.starts_with(GLOBAL_SCRIPT_PREFIX) {
+            Err(StatusResponse::no("Global scripts are read-only."))
         } else {
             Ok(self
                 .jmap
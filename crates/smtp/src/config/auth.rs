@@ -200,7 +200,20 @@ impl ConfigAuth for Config {
                         ))
                     }
                 };
-            ctx.signers.insert(id.to_string(), Arc::new(signer));
+            let signer = Arc::new(signer);
+
+            // Signatures can also be looked up by their DKIM record name, which is
+            // used to honour the selector configured for a domain
+            if let (Some(domain), Some(selector)) = (
+                self.value(("signature", id, "domain")),
+                self.value(("signature", id, "selector")),
+            ) {
+                ctx.signers.insert(
+                    format!("{selector}._domainkey.{}", domain.to_lowercase()),
+                    signer.clone(),
+                );
+            }
+            ctx.signers.insert(id.to_string(), signer);
             ctx.sealers.insert(id.to_string(), Arc::new(sealer));
         }
 
@@ -218,6 +218,7 @@ pub struct QueueConfig {
     pub source_ip: QueueOutboundSourceIp,
     pub tls: QueueOutboundTls,
     pub dsn: Dsn,
+    pub journal: Journal,
 
     // Timeouts
     pub timeout: QueueOutboundTimeout,
@@ -262,6 +263,13 @@ pub struct Dsn {
     pub sign: IfBlock,
 }
 
+pub struct Journal {
+    pub address: IfBlock,
+    pub name: IfBlock,
+    pub from_address: IfBlock,
+    pub sign: IfBlock,
+}
+
 pub struct AggregateReport {
     pub name: IfBlock,
     pub address: IfBlock,
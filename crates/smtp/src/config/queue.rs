@@ -30,9 +30,10 @@ use crate::core::eval::*;
 use super::{
     map_expr_token,
     throttle::{ConfigThrottle, ParseTrottleKey},
-    Dsn, QueueConfig, QueueOutboundSourceIp, QueueOutboundTimeout, QueueOutboundTls, QueueQuota,
-    QueueQuotas, QueueThrottle, RequireOptional, THROTTLE_LOCAL_IP, THROTTLE_MX, THROTTLE_RCPT,
-    THROTTLE_RCPT_DOMAIN, THROTTLE_REMOTE_IP, THROTTLE_SENDER, THROTTLE_SENDER_DOMAIN,
+    Dsn, Journal, QueueConfig, QueueOutboundSourceIp, QueueOutboundTimeout, QueueOutboundTls,
+    QueueQuota, QueueQuotas, QueueThrottle, RequireOptional, THROTTLE_LOCAL_IP, THROTTLE_MX,
+    THROTTLE_RCPT, THROTTLE_RCPT_DOMAIN, THROTTLE_REMOTE_IP, THROTTLE_SENDER,
+    THROTTLE_SENDER_DOMAIN,
 };
 use utils::{
     config::{
@@ -54,6 +55,13 @@ impl ConfigQueue for Config {
     fn parse_queue(&self) -> super::Result<QueueConfig> {
         let rcpt_envelope_keys = &[V_RECIPIENT_DOMAIN, V_SENDER, V_SENDER_DOMAIN, V_PRIORITY];
         let sender_envelope_keys = &[V_SENDER, V_SENDER_DOMAIN, V_PRIORITY];
+        let journal_envelope_keys = &[
+            V_RECIPIENT,
+            V_RECIPIENT_DOMAIN,
+            V_SENDER,
+            V_SENDER_DOMAIN,
+            V_PRIORITY,
+        ];
         let mx_envelope_keys = &[
             V_RECIPIENT_DOMAIN,
             V_SENDER,
@@ -209,6 +217,28 @@ impl ConfigQueue for Config {
                     })?
                     .unwrap_or_default(),
             },
+            journal: Journal {
+                address: self
+                    .parse_if_block("report.journal.address", |name| {
+                        map_expr_token::<NoConstants>(name, journal_envelope_keys)
+                    })?
+                    .unwrap_or_default(),
+                name: self
+                    .parse_if_block("report.journal.from-name", |name| {
+                        map_expr_token::<NoConstants>(name, sender_envelope_keys)
+                    })?
+                    .unwrap_or_else(|| IfBlock::new("Journal Service".to_string())),
+                from_address: self
+                    .parse_if_block("report.journal.from-address", |name| {
+                        map_expr_token::<NoConstants>(name, sender_envelope_keys)
+                    })?
+                    .unwrap_or_else(|| IfBlock::new(format!("MAILER-DAEMON@{default_hostname}"))),
+                sign: self
+                    .parse_if_block("report.journal.sign", |name| {
+                        map_expr_token::<NoConstants>(name, sender_envelope_keys)
+                    })?
+                    .unwrap_or_default(),
+            },
         };
 
         Ok(config)
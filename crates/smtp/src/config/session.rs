@@ -540,11 +540,12 @@ impl ParseValue for Mechanism {
             "PLAIN" => AUTH_PLAIN,
             "XOAUTH2" => AUTH_XOAUTH2,
             "OAUTHBEARER" => AUTH_OAUTHBEARER,
-            /*"SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
+            "SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
             "SCRAM-SHA-256" => AUTH_SCRAM_SHA_256,
             "SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
             "SCRAM-SHA-1" => AUTH_SCRAM_SHA_1,
-            "XOAUTH" => AUTH_XOAUTH,
+            "EXTERNAL" => AUTH_EXTERNAL,
+            /*"XOAUTH" => AUTH_XOAUTH,
             "9798-M-DSA-SHA1" => AUTH_9798_M_DSA_SHA1,
             "9798-M-ECDSA-SHA1" => AUTH_9798_M_ECDSA_SHA1,
             "9798-M-RSA-SHA1-ENC" => AUTH_9798_M_RSA_SHA1_ENC,
@@ -555,7 +556,6 @@ impl ParseValue for Mechanism {
             "EAP-AES128-PLUS" => AUTH_EAP_AES128_PLUS,
             "ECDH-X25519-CHALLENGE" => AUTH_ECDH_X25519_CHALLENGE,
             "ECDSA-NIST256P-CHALLENGE" => AUTH_ECDSA_NIST256P_CHALLENGE,
-            "EXTERNAL" => AUTH_EXTERNAL,
             "GS2-KRB5" => AUTH_GS2_KRB5,
             "GS2-KRB5-PLUS" => AUTH_GS2_KRB5_PLUS,
             "GSS-SPNEGO" => AUTH_GSS_SPNEGO,
@@ -23,6 +23,7 @@
 
 use std::{net::IpAddr, str::FromStr, sync::Arc};
 
+use directory::core::roles::{Authorization, Permission, Scope};
 use directory::Type;
 use http_body_util::{combinators::BoxBody, BodyExt, Empty, Full};
 use hyper::{
@@ -333,6 +334,7 @@ impl SMTP {
                 path.next().unwrap_or_default(),
                 path.next().unwrap_or_default(),
                 path.next(),
+                &Authorization::superuser(),
             )
             .await)
     }
@@ -344,11 +346,25 @@ impl SMTP {
         path_1: &str,
         path_2: &str,
         path_3: Option<&str>,
+        authorization: &Authorization,
     ) -> hyper::Response<BoxBody<Bytes, hyper::Error>> {
         let params = UrlParams::new(uri.query());
 
-        let (status, response) = match (method, path_1, path_2, path_3) {
-            (&Method::GET, "queue", "messages", None) => {
+        // Obtain the domains the principal is allowed to manage
+        let scope = match (method, path_1, path_2) {
+            (&Method::GET, "queue", "messages") => authorization.scope(Permission::QueueList),
+            (&Method::PATCH, "queue", "messages") => authorization.scope(Permission::QueueUpdate),
+            (&Method::DELETE, "queue", "messages") => authorization.scope(Permission::QueueDelete),
+            (&Method::GET, "queue" | "reports", _) => authorization.scope(Permission::ReportList),
+            (&Method::DELETE, "queue" | "reports", _) => {
+                authorization.scope(Permission::ReportDelete)
+            }
+            _ => None,
+        };
+
+        let (status, response) = match (scope, method, path_1, path_2, path_3) {
+            (None, _, _, _, _) => forbidden(),
+            (Some(scope), &Method::GET, "queue", "messages", None) => {
                 let text = params.get("text");
                 let from = params.get("from");
                 let to = params.get("to");
@@ -377,38 +393,41 @@ impl SMTP {
                         IterateParams::new(from_key, to_key).ascending(),
                         |key, value| {
                             let message = Bincode::<queue::Message>::deserialize(value)?.inner;
-                            let matches = !has_filters
-                                || (text
-                                    .as_ref()
-                                    .map(|text| {
-                                        message.return_path.contains(text)
-                                            || message
-                                                .recipients
-                                                .iter()
-                                                .any(|r| r.address_lcase.contains(text))
-                                    })
-                                    .unwrap_or_else(|| {
-                                        from.as_ref()
-                                            .map_or(true, |from| message.return_path.contains(from))
-                                            && to.as_ref().map_or(true, |to| {
-                                                message
-                                                    .recipients
-                                                    .iter()
-                                                    .any(|r| r.address_lcase.contains(to))
+                            let rcpt_in_scope = rcpt_scope(&message, scope);
+                            let matches = message.is_in_scope(scope)
+                                && (!has_filters
+                                    || (text
+                                        .as_ref()
+                                        .map(|text| {
+                                            message.return_path.contains(text)
+                                                || message.recipients.iter().any(|r| {
+                                                    rcpt_in_scope(&r.address_lcase)
+                                                        && r.address_lcase.contains(text)
+                                                })
+                                        })
+                                        .unwrap_or_else(|| {
+                                            from.as_ref().map_or(true, |from| {
+                                                message.return_path.contains(from)
+                                            }) && to.as_ref().map_or(true, |to| {
+                                                message.recipients.iter().any(|r| {
+                                                    rcpt_in_scope(&r.address_lcase)
+                                                        && r.address_lcase.contains(to)
+                                                })
                                             })
-                                    })
-                                    && before.as_ref().map_or(true, |before| {
-                                        message.next_delivery_event() < *before
-                                    })
-                                    && after.as_ref().map_or(true, |after| {
-                                        message.next_delivery_event() > *after
-                                    }));
+                                        })
+                                        && before.as_ref().map_or(true, |before| {
+                                            message.next_delivery_event() < *before
+                                        })
+                                        && after.as_ref().map_or(true, |after| {
+                                            message.next_delivery_event() > *after
+                                        })));
 
                             if matches {
                                 if offset == 0 {
                                     if limit == 0 || total_returned < limit {
                                         if values {
-                                            result_values.push(Message::from(&message));
+                                            result_values
+                                                .push(Message::from(&message).scoped(scope));
                                         } else {
                                             result_ids.push(key.deserialize_be_u64(1)?);
                                         }
@@ -446,15 +465,16 @@ impl SMTP {
                     .unwrap_or_default(),
                 )
             }
-            (&Method::GET, "queue", "messages", Some(queue_id)) => {
+            (Some(scope), &Method::GET, "queue", "messages", Some(queue_id)) => {
                 if let Some(message) = self
                     .read_message(queue_id.parse().unwrap_or_default())
                     .await
+                    .filter(|message| message.is_in_scope(scope))
                 {
                     (
                         StatusCode::OK,
                         serde_json::to_string(&Response {
-                            data: Message::from(&message),
+                            data: Message::from(&message).scoped(scope),
                         })
                         .unwrap_or_default(),
                     )
@@ -462,7 +482,7 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::PATCH, "queue", "messages", Some(queue_id)) => {
+            (Some(scope), &Method::PATCH, "queue", "messages", Some(queue_id)) => {
                 let time = params
                     .parse::<Timestamp>("at")
                     .map(|t| t.into_inner())
@@ -472,17 +492,30 @@ impl SMTP {
                 if let Some(mut message) = self
                     .read_message(queue_id.parse().unwrap_or_default())
                     .await
+                    .filter(|message| message.is_in_scope(scope))
                 {
                     let prev_event = message.next_event().unwrap_or_default();
                     let mut found = false;
 
-                    for domain in &mut message.domains {
-                        if matches!(
-                            domain.status,
-                            Status::Scheduled | Status::TemporaryFailure(_)
-                        ) && item
-                            .as_ref()
-                            .map_or(true, |item| domain.domain.contains(item))
+                    // Only reschedule domains with recipients the principal can manage
+                    let rcpt_in_scope = rcpt_scope(&message, scope);
+                    let domains_in_scope = (0..message.domains.len())
+                        .map(|domain_idx| {
+                            message.recipients.iter().any(|rcpt| {
+                                rcpt.domain_idx == domain_idx && rcpt_in_scope(&rcpt.address_lcase)
+                            })
+                        })
+                        .collect::<Vec<_>>();
+
+                    for (domain, in_scope) in message.domains.iter_mut().zip(domains_in_scope) {
+                        if in_scope
+                            && matches!(
+                                domain.status,
+                                Status::Scheduled | Status::TemporaryFailure(_)
+                            )
+                            && item
+                                .as_ref()
+                                .map_or(true, |item| domain.domain.contains(item))
                         {
                             domain.retry.due = time;
                             if domain.expires > time {
@@ -508,18 +541,28 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::DELETE, "queue", "messages", Some(queue_id)) => {
+            (Some(scope), &Method::DELETE, "queue", "messages", Some(queue_id)) => {
                 if let Some(mut message) = self
                     .read_message(queue_id.parse().unwrap_or_default())
                     .await
+                    .filter(|message| message.is_in_scope(scope))
                 {
                     let mut found = false;
                     let prev_event = message.next_event().unwrap_or_default();
-
-                    if let Some(item) = params.get("filter") {
-                        // Cancel delivery for all recipients that match
+                    let item = params.get("filter");
+                    let rcpt_in_scope = rcpt_scope(&message, scope);
+
+                    if item.is_some()
+                        || !message
+                            .recipients
+                            .iter()
+                            .all(|rcpt| rcpt_in_scope(&rcpt.address_lcase))
+                    {
+                        // Cancel delivery for all recipients in scope that match
                         for rcpt in &mut message.recipients {
-                            if rcpt.address_lcase.contains(item) {
+                            if rcpt_in_scope(&rcpt.address_lcase)
+                                && item.map_or(true, |item| rcpt.address_lcase.contains(item))
+                            {
                                 rcpt.status = Status::PermanentFailure(HostResponse {
                                     hostname: ErrorDetails::default(),
                                     response: smtp_proto::Response {
@@ -587,7 +630,7 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::GET, "queue", "reports", None) => {
+            (Some(scope), &Method::GET, "queue", "reports", None) => {
                 let domain = params.get("domain").map(|d| d.to_lowercase());
                 let type_ = params.get("type").and_then(|t| match t {
                     "dmarc" => 0u8.into(),
@@ -626,6 +669,7 @@ impl SMTP {
                             if type_.map_or(true, |t| t == *key.last().unwrap()) {
                                 let event = ReportEvent::deserialize(key)?;
                                 if event.seq_id != 0
+                                    && scope.contains_domain(&event.domain)
                                     && domain.as_ref().map_or(true, |d| event.domain.contains(d))
                                 {
                                     if offset == 0 {
@@ -664,9 +708,11 @@ impl SMTP {
                     .unwrap_or_default(),
                 )
             }
-            (&Method::GET, "queue", "reports", Some(report_id)) => {
+            (Some(scope), &Method::GET, "queue", "reports", Some(report_id)) => {
                 let mut result = None;
-                if let Some(report_id) = parse_queued_report_id(report_id) {
+                if let Some(report_id) = parse_queued_report_id(report_id)
+                    .filter(|report_id| report_id.is_in_scope(scope))
+                {
                     match report_id {
                         QueueClass::DmarcReportHeader(event) => {
                             let mut rua = Vec::new();
@@ -699,8 +745,10 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::DELETE, "queue", "reports", Some(report_id)) => {
-                if let Some(report_id) = parse_queued_report_id(report_id) {
+            (Some(scope), &Method::DELETE, "queue", "reports", Some(report_id)) => {
+                if let Some(report_id) = parse_queued_report_id(report_id)
+                    .filter(|report_id| report_id.is_in_scope(scope))
+                {
                     match report_id {
                         QueueClass::DmarcReportHeader(event) => {
                             self.delete_dmarc_report(event).await;
@@ -719,7 +767,7 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::GET, "reports", class @ ("dmarc" | "tls" | "arf"), None) => {
+            (Some(scope), &Method::GET, "reports", class @ ("dmarc" | "tls" | "arf"), None) => {
                 let filter = params.get("text");
                 let page: usize = params.parse::<usize>("page").unwrap_or_default();
                 let limit: usize = params.parse::<usize>("limit").unwrap_or_default();
@@ -764,7 +812,7 @@ impl SMTP {
                     .default_data_store
                     .iterate(
                         IterateParams::new(from_key, to_key)
-                            .set_values(filter.is_some())
+                            .set_values(filter.is_some() || !scope.is_all())
                             .descending(),
                         |key, value| {
                             // Skip chunked records
@@ -775,22 +823,27 @@ impl SMTP {
                             last_id = id;
 
                             // TODO: Support filtering chunked records (over 10MB) on FDB
-                            let matches = filter.map_or(true, |filter| match typ {
-                                ReportType::Dmarc => Bincode::<
-                                    IncomingReport<mail_auth::report::Report>,
-                                >::deserialize(
-                                    value
-                                )
-                                .map_or(false, |v| v.inner.contains(filter)),
-                                ReportType::Tls => {
-                                    Bincode::<IncomingReport<TlsReport>>::deserialize(value)
-                                        .map_or(false, |v| v.inner.contains(filter))
-                                }
-                                ReportType::Arf => {
-                                    Bincode::<IncomingReport<Feedback>>::deserialize(value)
-                                        .map_or(false, |v| v.inner.contains(filter))
-                                }
-                            });
+                            let matches = (filter.is_none() && scope.is_all())
+                                || match typ {
+                                    ReportType::Dmarc => Bincode::<
+                                        IncomingReport<mail_auth::report::Report>,
+                                    >::deserialize(
+                                        value
+                                    )
+                                    .map_or(false, |v| report_matches(&v.inner, filter, scope)),
+                                    ReportType::Tls => {
+                                        Bincode::<IncomingReport<TlsReport>>::deserialize(value)
+                                            .map_or(false, |v| {
+                                                report_matches(&v.inner, filter, scope)
+                                            })
+                                    }
+                                    ReportType::Arf => {
+                                        Bincode::<IncomingReport<Feedback>>::deserialize(value)
+                                            .map_or(false, |v| {
+                                                report_matches(&v.inner, filter, scope)
+                                            })
+                                    }
+                                };
                             if matches {
                                 if offset == 0 {
                                     if limit == 0 || results.len() < limit {
@@ -825,7 +878,13 @@ impl SMTP {
                     Err(err) => err.into_bad_request(),
                 }
             }
-            (&Method::GET, "reports", class @ ("dmarc" | "tls" | "arf"), Some(report_id)) => {
+            (
+                Some(scope),
+                &Method::GET,
+                "reports",
+                class @ ("dmarc" | "tls" | "arf"),
+                Some(report_id),
+            ) => {
                 if let Some(report_id) = parse_incoming_report_id(class, report_id) {
                     match &report_id {
                         ReportClass::Tls { .. } => match self
@@ -836,14 +895,14 @@ impl SMTP {
                             ))
                             .await
                         {
-                            Ok(Some(report)) => (
+                            Ok(Some(report)) if report.inner.is_in_scope(scope) => (
                                 StatusCode::OK,
                                 serde_json::to_string(&json!({
                                     "data": report.inner,
                                 }))
                                 .unwrap_or_default(),
                             ),
-                            Ok(None) => not_found(),
+                            Ok(_) => not_found(),
                             Err(err) => err.into_bad_request(),
                         },
                         ReportClass::Dmarc { .. } => match self
@@ -854,14 +913,14 @@ impl SMTP {
                             )
                             .await
                         {
-                            Ok(Some(report)) => (
+                            Ok(Some(report)) if report.inner.is_in_scope(scope) => (
                                 StatusCode::OK,
                                 serde_json::to_string(&json!({
                                     "data": report.inner,
                                 }))
                                 .unwrap_or_default(),
                             ),
-                            Ok(None) => not_found(),
+                            Ok(_) => not_found(),
                             Err(err) => err.into_bad_request(),
                         },
                         ReportClass::Arf { .. } => match self
@@ -872,14 +931,14 @@ impl SMTP {
                             ))
                             .await
                         {
-                            Ok(Some(report)) => (
+                            Ok(Some(report)) if report.inner.is_in_scope(scope) => (
                                 StatusCode::OK,
                                 serde_json::to_string(&json!({
                                     "data": report.inner,
                                 }))
                                 .unwrap_or_default(),
                             ),
-                            Ok(None) => not_found(),
+                            Ok(_) => not_found(),
                             Err(err) => err.into_bad_request(),
                         },
                     }
@@ -887,8 +946,23 @@ impl SMTP {
                     not_found()
                 }
             }
-            (&Method::DELETE, "reports", class @ ("dmarc" | "tls" | "arf"), Some(report_id)) => {
-                if let Some(report_id) = parse_incoming_report_id(class, report_id) {
+            (
+                Some(scope),
+                &Method::DELETE,
+                "reports",
+                class @ ("dmarc" | "tls" | "arf"),
+                Some(report_id),
+            ) => {
+                let report_id = parse_incoming_report_id(class, report_id);
+                if let Some(report_id) = match report_id {
+                    Some(report_id)
+                        if scope.is_all()
+                            || self.is_incoming_report_in_scope(&report_id, scope).await =>
+                    {
+                        Some(report_id)
+                    }
+                    _ => None,
+                } {
                     let mut batch = BatchBuilder::new();
                     batch.clear(ValueClass::Report(report_id));
                     let result = self
@@ -927,6 +1001,40 @@ fn not_found() -> (StatusCode, String) {
     )
 }
 
+fn forbidden() -> (StatusCode, String) {
+    (
+        StatusCode::FORBIDDEN,
+        "{\"error\": \"forbidden\", \"details\": \"Insufficient permissions.\"}".to_string(),
+    )
+}
+
+impl SMTP {
+    async fn is_incoming_report_in_scope(&self, report_id: &ReportClass, scope: &Scope) -> bool {
+        let key = ValueKey::from(ValueClass::Report(report_id.clone()));
+        let store = &self.shared.default_data_store;
+        match report_id {
+            ReportClass::Tls { .. } => store
+                .get_value::<Bincode<IncomingReport<TlsReport>>>(key)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|report| report.inner.is_in_scope(scope)),
+            ReportClass::Dmarc { .. } => store
+                .get_value::<Bincode<IncomingReport<mail_auth::report::Report>>>(key)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|report| report.inner.is_in_scope(scope)),
+            ReportClass::Arf { .. } => store
+                .get_value::<Bincode<IncomingReport<Feedback>>>(key)
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|report| report.inner.is_in_scope(scope)),
+        }
+    }
+}
+
 enum ReportType {
     Dmarc,
     Tls,
@@ -1083,6 +1191,91 @@ impl<T: Contains> Contains for IncomingReport<T> {
     }
 }
 
+trait InScope {
+    fn is_in_scope(&self, scope: &Scope) -> bool;
+}
+
+impl InScope for queue::Message {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        let rcpt_in_scope = rcpt_scope(self, scope);
+        scope.is_all()
+            || self
+                .recipients
+                .iter()
+                .any(|rcpt| rcpt_in_scope(&rcpt.address_lcase))
+    }
+}
+
+// Messages sent from a domain are managed in full by its administrators,
+// otherwise only the recipients within their domains are visible.
+fn rcpt_scope<'x>(message: &queue::Message, scope: &'x Scope) -> impl Fn(&str) -> bool + 'x {
+    let sender_in_scope = scope.contains_address(&message.return_path_lcase);
+    move |address| sender_in_scope || scope.contains_address(address)
+}
+
+impl Message {
+    fn scoped(mut self, scope: &Scope) -> Self {
+        if !scope.is_all() && !scope.contains_address(&self.return_path) {
+            for domain in &mut self.domains {
+                domain
+                    .recipients
+                    .retain(|rcpt| scope.contains_address(&rcpt.address));
+            }
+            self.domains.retain(|domain| !domain.recipients.is_empty());
+        }
+        self
+    }
+}
+
+impl InScope for QueueClass {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        match self {
+            QueueClass::DmarcReportHeader(event) | QueueClass::TlsReportHeader(event) => {
+                scope.contains_domain(&event.domain)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl InScope for mail_auth::report::Report {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        scope.contains_domain(self.domain())
+    }
+}
+
+impl InScope for TlsReport {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        self.policies
+            .iter()
+            .any(|p| scope.contains_domain(&p.policy.policy_domain))
+    }
+}
+
+impl<'x> InScope for Feedback<'x> {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        self.reported_domain()
+            .iter()
+            .any(|domain| scope.contains_domain(domain))
+    }
+}
+
+impl<T: InScope> InScope for IncomingReport<T> {
+    fn is_in_scope(&self, scope: &Scope) -> bool {
+        scope.is_all()
+            || self.to.iter().any(|to| scope.contains_address(to))
+            || self.report.is_in_scope(scope)
+    }
+}
+
+fn report_matches<T: InScope + Contains>(
+    report: &IncomingReport<T>,
+    filter: Option<&str>,
+    scope: &Scope,
+) -> bool {
+    report.is_in_scope(scope) && filter.is_none_or(|filter| report.contains(filter))
+}
+
 impl From<&queue::Message> for Message {
     fn from(message: &queue::Message) -> Self {
         let now = now();
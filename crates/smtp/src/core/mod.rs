@@ -56,6 +56,7 @@ use utils::{
         ServerInstance, TcpAcceptor,
     },
     snowflake::SnowflakeIdGenerator,
+    webhooks::Webhooks,
 };
 
 use crate::{
@@ -111,6 +112,7 @@ pub struct SMTP {
     pub report: ReportCore,
     pub sieve: SieveCore,
     pub shared: Shared,
+    pub webhooks: Webhooks,
     #[cfg(feature = "local_delivery")]
     pub delivery_tx: mpsc::Sender<DeliveryEvent>,
 }
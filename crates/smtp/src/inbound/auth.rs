@@ -21,55 +21,98 @@
  * for more details.
 */
 
+use directory::{
+    core::scram::{ScramError, ScramHash, ScramServer},
+    Principal, QueryBy,
+};
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
-use tokio::io::{AsyncRead, AsyncWrite};
+use smtp_proto::{
+    IntoString, AUTH_EXTERNAL, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_SCRAM_SHA_1,
+    AUTH_SCRAM_SHA_1_PLUS, AUTH_SCRAM_SHA_256, AUTH_SCRAM_SHA_256_PLUS, AUTH_XOAUTH2,
+};
+use utils::{
+    listener::{tls::certificate_identities, SessionStream},
+    webhooks::{WebhookPayload, WebhookType},
+};
 
 use crate::core::Session;
 
 pub struct SaslToken {
     mechanism: u64,
     credentials: Credentials<String>,
+    scram: Option<Box<ScramServer>>,
+    principal: Option<Principal<u32>>,
+    is_prompted: bool,
 }
 
 impl SaslToken {
     pub fn from_mechanism(mechanism: u64) -> Option<SaslToken> {
         match mechanism {
-            AUTH_PLAIN | AUTH_LOGIN => SaslToken {
+            AUTH_PLAIN
+            | AUTH_LOGIN
+            | AUTH_SCRAM_SHA_256_PLUS
+            | AUTH_SCRAM_SHA_256
+            | AUTH_SCRAM_SHA_1_PLUS
+            | AUTH_SCRAM_SHA_1
+            | AUTH_EXTERNAL => SaslToken::new(
                 mechanism,
-                credentials: Credentials::Plain {
+                Credentials::Plain {
                     username: String::new(),
                     secret: String::new(),
                 },
-            }
+            )
             .into(),
-            AUTH_OAUTHBEARER => SaslToken {
+            AUTH_OAUTHBEARER => SaslToken::new(
                 mechanism,
-                credentials: Credentials::OAuthBearer {
+                Credentials::OAuthBearer {
                     token: String::new(),
                 },
-            }
+            )
             .into(),
-            AUTH_XOAUTH2 => SaslToken {
+            AUTH_XOAUTH2 => SaslToken::new(
                 mechanism,
-                credentials: Credentials::XOauth2 {
+                Credentials::XOauth2 {
                     username: String::new(),
                     secret: String::new(),
                 },
-            }
+            )
             .into(),
             _ => None,
         }
     }
+
+    fn new(mechanism: u64, credentials: Credentials<String>) -> Self {
+        SaslToken {
+            mechanism,
+            credentials,
+            scram: None,
+            principal: None,
+            is_prompted: false,
+        }
+    }
 }
 
-impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
+impl<T: SessionStream> Session<T> {
     pub async fn handle_sasl_response(
         &mut self,
         token: &mut SaslToken,
         response: &[u8],
     ) -> Result<bool, ()> {
+        match token.mechanism {
+            AUTH_SCRAM_SHA_256_PLUS
+            | AUTH_SCRAM_SHA_256
+            | AUTH_SCRAM_SHA_1_PLUS
+            | AUTH_SCRAM_SHA_1 => {
+                return self.handle_sasl_scram(token, response).await;
+            }
+            AUTH_EXTERNAL => {
+                return self.handle_sasl_external(token, response).await;
+            }
+            _ => (),
+        }
+
         if response.is_empty() {
             match (token.mechanism, &token.credentials) {
                 (AUTH_PLAIN | AUTH_XOAUTH2 | AUTH_OAUTHBEARER, _) => {
@@ -173,6 +216,197 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
+    async fn handle_sasl_scram(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        if response == b"*" {
+            self.write(b"501 5.7.0 Authentication cancelled.\r\n")
+                .await?;
+            return Ok(false);
+        }
+        let lookup = match &self.params.auth_directory {
+            Some(lookup) => lookup.clone(),
+            None => {
+                return self
+                    .authenticate(std::mem::take(&mut token.credentials))
+                    .await
+            }
+        };
+        let channel_binding = self.stream.tls_channel_binding();
+        let scram = token.scram.get_or_insert_with(|| {
+            let (hash, is_plus) = match token.mechanism {
+                AUTH_SCRAM_SHA_1 => (ScramHash::Sha1, false),
+                AUTH_SCRAM_SHA_1_PLUS => (ScramHash::Sha1, true),
+                AUTH_SCRAM_SHA_256_PLUS => (ScramHash::Sha256, true),
+                _ => (ScramHash::Sha256, false),
+            };
+            Box::new(ScramServer::new(hash, is_plus, channel_binding))
+        });
+
+        if scram.is_done() {
+            // The client acknowledged the server-final message
+            return match token.principal.take() {
+                Some(principal) if response.is_empty() => {
+                    self.authenticated(principal.name, principal.emails).await
+                }
+                _ => self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await,
+            };
+        } else if response.is_empty() {
+            if scram.is_client_first() && !token.is_prompted {
+                token.is_prompted = true;
+                self.write(b"334 \r\n").await?;
+                return Ok(true);
+            }
+            return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+        }
+
+        let response = match base64_decode(response) {
+            Some(response) => response,
+            None => return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await,
+        };
+        let result = if scram.is_client_first() {
+            match scram.client_first(&response) {
+                Ok(username) => {
+                    // Unknown users receive a fake challenge that will always fail
+                    token.principal = lookup
+                        .query_login(QueryBy::Name(username), false)
+                        .await
+                        .unwrap_or_default();
+                    Ok(scram.server_first(
+                        token
+                            .principal
+                            .as_ref()
+                            .map(|principal| principal.secrets.as_slice()),
+                        &lookup.salt_key,
+                    ))
+                }
+                Err(err) => Err(err),
+            }
+        } else {
+            scram.client_final(&response).and_then(|server_final| {
+                if token.principal.is_some() {
+                    Ok(server_final)
+                } else {
+                    Err(ScramError::InvalidProof)
+                }
+            })
+        };
+
+        match result {
+            Ok(challenge) => {
+                let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 8);
+                buf.extend_from_slice(b"334 ");
+                buf.extend_from_slice(&base64_encode(challenge.as_bytes()).unwrap_or_default());
+                buf.extend_from_slice(b"\r\n");
+                self.write(&buf).await?;
+                Ok(true)
+            }
+            Err(err) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    mechanism = scram.mechanism(),
+                    result = "failed",
+                    reason = %err
+                );
+                self.auth_failure_webhook(token.principal.as_ref().map(|p| p.name.clone()));
+
+                self.auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
+                    .await
+            }
+        }
+    }
+
+    async fn handle_sasl_external(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        if response.is_empty() && !token.is_prompted {
+            token.is_prompted = true;
+            self.write(b"334 \r\n").await?;
+            return Ok(true);
+        } else if response == b"*" {
+            self.write(b"501 5.7.0 Authentication cancelled.\r\n")
+                .await?;
+            return Ok(false);
+        }
+        let authzid = if response.is_empty() || response == b"=" {
+            String::new()
+        } else if let Some(authzid) =
+            base64_decode(response).and_then(|authzid| String::from_utf8(authzid).ok())
+        {
+            authzid
+        } else {
+            return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+        };
+        let lookup = match &self.params.auth_directory {
+            Some(lookup) => lookup.clone(),
+            None => {
+                return self
+                    .authenticate(std::mem::take(&mut token.credentials))
+                    .await
+            }
+        };
+
+        let identities = self
+            .stream
+            .tls_client_certificate()
+            .map(|certificate| certificate_identities(&certificate))
+            .unwrap_or_default();
+        match lookup
+            .query_by_certificate(&identities, &authzid, false)
+            .await
+        {
+            Ok(Some(principal)) => self.authenticated(principal.name, principal.emails).await,
+            Ok(None) => {
+                tracing::debug!(
+                    parent: &self.span,
+                    context = "auth",
+                    event = "authenticate",
+                    mechanism = "EXTERNAL",
+                    result = "failed",
+                    identities = ?identities
+                );
+                self.auth_failure_webhook((!authzid.is_empty()).then_some(authzid));
+
+                self.auth_error(b"535 5.7.8 Authentication credentials invalid.\r\n")
+                    .await
+            }
+            Err(_) => {
+                self.write(b"454 4.7.0 Temporary authentication failure\r\n")
+                    .await?;
+                Ok(false)
+            }
+        }
+    }
+
+    async fn authenticated(
+        &mut self,
+        authenticated_as: String,
+        emails: Vec<String>,
+    ) -> Result<bool, ()> {
+        tracing::debug!(
+            parent: &self.span,
+            context = "auth",
+            event = "authenticate",
+            result = "success"
+        );
+
+        self.data.authenticated_as = authenticated_as.to_lowercase();
+        self.data.authenticated_emails = emails
+            .into_iter()
+            .map(|e| e.trim().to_lowercase())
+            .collect();
+        self.eval_post_auth_params().await;
+        self.write(b"235 2.7.0 Authentication succeeded.\r\n")
+            .await?;
+        Ok(false)
+    }
+
     pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
         if let Some(lookup) = &self.params.auth_directory {
             let authenticated_as = match &credentials {
@@ -242,6 +476,15 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         Ok(false)
     }
 
+    fn auth_failure_webhook(&self, login: Option<String>) {
+        self.core
+            .webhooks
+            .send(WebhookType::AuthFailure, || WebhookPayload::AuthFailure {
+                login,
+                remote_ip: self.data.remote_ip.into(),
+            });
+    }
+
     pub async fn auth_error(&mut self, response: &[u8]) -> Result<bool, ()> {
         tokio::time::sleep(self.params.auth_errors_wait).await;
         self.data.auth_errors += 1;
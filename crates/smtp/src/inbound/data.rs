@@ -44,7 +44,7 @@ use utils::{config::Rate, listener::SessionStream};
 use crate::{
     config::VerifyStrategy,
     core::{Session, SessionAddress, State},
-    queue::{self, Message, SimpleEnvelope},
+    queue::{self, Message, SimpleEnvelope, MAIL_AUTHOR_AUTHENTICATED, MAIL_SENDER_AUTHENTICATED},
     reporting::analysis::AnalyzeReport,
     scripts::{ScriptModification, ScriptResult},
 };
@@ -534,6 +534,21 @@ impl<T: SessionStream> Session<T> {
         let rcpt_to = std::mem::take(&mut self.data.rcpt_to);
         let mut message = self.build_message(mail_from, rcpt_to).await;
 
+        // Record whether the envelope sender belongs to the authenticated user
+        // and whether the author's domain passed DMARC
+        if !message.return_path_lcase.is_empty()
+            && (self.data.authenticated_as == message.return_path_lcase
+                || self
+                    .data
+                    .authenticated_emails
+                    .contains(&message.return_path_lcase))
+        {
+            message.flags |= MAIL_SENDER_AUTHENTICATED;
+        }
+        if matches!(dmarc_result, Some(DmarcResult::Pass)) {
+            message.flags |= MAIL_AUTHOR_AUTHENTICATED;
+        }
+
         // Add Received header
         if self
             .core
@@ -626,12 +641,18 @@ impl<T: SessionStream> Session<T> {
 
         // DKIM sign
         let raw_message = edited_message.unwrap_or(raw_message);
-        for signer in self
+        let mut signers = self
             .core
             .eval_if::<Vec<String>, _>(&ac.dkim.sign, self)
             .await
-            .unwrap_or_default()
-        {
+            .unwrap_or_default();
+        if !signers.is_empty() {
+            // Use the selector configured for the sender's domain, if any
+            if let Some(signer) = self.domain_dkim_signer().await {
+                signers = vec![signer];
+            }
+        }
+        for signer in signers {
             if let Some(signer) = self.core.get_dkim_signer(&signer) {
                 match signer.sign_chained(&[headers.as_ref(), &raw_message]) {
                     Ok(signature) => {
@@ -653,11 +674,26 @@ impl<T: SessionStream> Session<T> {
 
         // Verify queue quota
         if self.core.has_quota(&mut message).await {
+            // Build journal report
+            let journal = self
+                .core
+                .build_journal_report(
+                    &message,
+                    &headers,
+                    &raw_message,
+                    &self.data.authenticated_as,
+                    self.data.remote_ip,
+                )
+                .await;
+
             let queue_id = message.id;
             if message
                 .queue(Some(&headers), &raw_message, &self.core, &self.span)
                 .await
             {
+                if let Some(journal) = journal {
+                    self.core.send_journal_report(journal, &self.span).await;
+                }
                 self.state = State::Accepted(queue_id);
                 self.data.messages_sent += 1;
                 (b"250 2.0.0 Message queued for delivery.\r\n"[..]).into()
@@ -676,6 +712,33 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
+    /// Returns the DKIM signature matching the selector configured for the sender's domain.
+    async fn domain_dkim_signer(&self) -> Option<String> {
+        let domain = &self.data.mail_from.as_ref()?.domain;
+        let selector = self
+            .core
+            .eval_if::<String, _>(&self.core.session.config.rcpt.directory, self)
+            .await
+            .and_then(|name| self.core.get_directory(&name))?
+            .domain_settings(domain)
+            .await
+            .ok()??
+            .dkim_selector?;
+        let signer = format!("{selector}._domainkey.{domain}");
+
+        if self.core.shared.signers.contains_key(&signer) {
+            Some(signer)
+        } else {
+            tracing::debug!(parent: &self.span,
+                context = "dkim",
+                event = "not-found",
+                domain = domain,
+                selector = selector,
+                "No DKIM signature found for the domain selector.");
+            None
+        }
+    }
+
     pub async fn build_message(
         &self,
         mail_from: SessionAddress,
@@ -693,7 +756,7 @@ impl<T: SessionStream> Session<T> {
             return_path_domain: mail_from.domain,
             recipients: Vec::with_capacity(rcpt_to.len()),
             domains: Vec::with_capacity(3),
-            flags: mail_from.flags,
+            flags: mail_from.flags & !(MAIL_SENDER_AUTHENTICATED | MAIL_AUTHOR_AUTHENTICATED),
             priority: self.data.priority,
             size: 0,
             env_id: mail_from.dsn_info,
@@ -180,8 +180,12 @@ impl<T: SessionStream> Session<T> {
                 .unwrap_or_default()
                 .into();
             if response.auth_mechanisms != 0 {
-                if !self.stream.is_tls() && !self.params.auth_plain_text {
-                    response.auth_mechanisms &= !(AUTH_PLAIN | AUTH_LOGIN);
+                if !self.stream.is_tls() {
+                    response.auth_mechanisms &=
+                        !(AUTH_SCRAM_SHA_256_PLUS | AUTH_SCRAM_SHA_1_PLUS | AUTH_EXTERNAL);
+                    if !self.params.auth_plain_text {
+                        response.auth_mechanisms &= !(AUTH_PLAIN | AUTH_LOGIN);
+                    }
                 }
                 if response.auth_mechanisms != 0 {
                     response.capabilities |= EXT_AUTH;
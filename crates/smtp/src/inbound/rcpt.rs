@@ -21,6 +21,7 @@
  * for more details.
 */
 
+use directory::core::list::ListCommand;
 use smtp_proto::{
     RcptTo, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
 };
@@ -150,9 +151,57 @@ impl<T: SessionStream> Session<T> {
             .await
             .and_then(|name| self.core.get_directory(&name))
         {
+            // Enforce the sending rate of the authenticated principal's domain, which
+            // is taken from its login name or its primary address
+            if let Some(sender_domain) = self
+                .data
+                .authenticated_as
+                .rsplit_once('@')
+                .or_else(|| {
+                    self.data
+                        .authenticated_emails
+                        .first()
+                        .and_then(|email| email.rsplit_once('@'))
+                })
+                .map(|(_, domain)| domain.to_lowercase())
+            {
+                if let Ok(Some(rate)) = directory
+                    .domain_settings(&sender_domain)
+                    .await
+                    .map(|settings| settings.and_then(|settings| settings.send_rate()))
+                {
+                    if !self
+                        .throttle_rcpt(&sender_domain, &rate, "domain-rate")
+                        .await
+                    {
+                        tracing::debug!(parent: &self.span,
+                            context = "rcpt",
+                            event = "rate-limit",
+                            domain = sender_domain,
+                            "Domain sending rate exceeded.");
+
+                        self.data.rcpt_to.pop();
+                        return self
+                            .write(b"451 4.4.5 Rate limit exceeded, try again later.\r\n")
+                            .await;
+                    }
+                }
+            }
+
             if let Ok(is_local_domain) = directory.is_local_domain(&rcpt.domain).await {
                 if is_local_domain {
-                    if let Ok(is_local_address) = directory.rcpt(&rcpt.address_lcase).await {
+                    // Requests to mailing lists are sent to subaddresses of the list
+                    let is_local_address = match directory.rcpt(&rcpt.address_lcase).await {
+                        Ok(false) => match ListCommand::parse(&rcpt.address_lcase) {
+                            Some((list, _)) => {
+                                directory.query_list(&list).await.map(|list| list.is_some())
+                            }
+                            None => Ok(false),
+                        },
+                        result => result,
+                    };
+
+                    if let Ok(is_local_address) = is_local_address {
                         if !is_local_address {
                             tracing::debug!(parent: &self.span,
                                             context = "rcpt", 
@@ -164,6 +213,21 @@ impl<T: SessionStream> Session<T> {
                             return self
                                 .rcpt_error(b"550 5.1.2 Mailbox does not exist.\r\n")
                                 .await;
+                        } else if directory
+                            .is_domain_over_quota(&rcpt.domain, 0)
+                            .await
+                            .unwrap_or(false)
+                        {
+                            tracing::debug!(parent: &self.span,
+                                            context = "rcpt",
+                                            event = "error",
+                                            address = &rcpt.address_lcase,
+                                            "Domain quota exceeded.");
+
+                            self.data.rcpt_to.pop();
+                            return self
+                                .write(b"452 4.2.2 Domain storage quota exceeded.\r\n")
+                                .await;
                         }
                     } else {
                         tracing::debug!(parent: &self.span,
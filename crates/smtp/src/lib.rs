@@ -43,6 +43,7 @@ use tokio::sync::mpsc;
 use utils::{
     config::{Config, ServerProtocol, Servers},
     snowflake::SnowflakeIdGenerator,
+    webhooks::{WebhookEvent, Webhooks},
     UnwrapFailure,
 };
 
@@ -64,6 +65,7 @@ impl SMTP {
         stores: &Stores,
         directory: &Directories,
         #[cfg(feature = "local_delivery")] delivery_tx: mpsc::Sender<utils::ipc::DeliveryEvent>,
+        webhook_tx: mpsc::Sender<WebhookEvent>,
     ) -> Result<Arc<Self>, String> {
         // Read configuration parameters
         let mut config_ctx = ConfigContext::new();
@@ -78,6 +80,7 @@ impl SMTP {
         let mail_auth_config = config.parse_mail_auth()?;
         let report_config = config.parse_reports()?;
         let mut shared = config.parse_shared(&config_ctx)?;
+        let webhooks = Webhooks::parse(config, webhook_tx)?;
 
         // Add local delivery host
         #[cfg(feature = "local_delivery")]
@@ -146,6 +149,7 @@ impl SMTP {
             mail_auth: mail_auth_config,
             sieve: sieve_config,
             shared,
+            webhooks,
             #[cfg(feature = "local_delivery")]
             delivery_tx,
         });
@@ -90,7 +90,15 @@ impl DeliveryAttempt {
             );
 
             // Check that the message still has recipients to be delivered
+            let pending_rcpts = message.pending_recipients();
             let has_pending_delivery = message.has_pending_delivery(&span);
+            for &idx in &pending_rcpts {
+                let rcpt = &message.recipients[idx];
+                let domain = &message.domains[rcpt.domain_idx];
+                if matches!(domain.status, Status::PermanentFailure(_)) {
+                    core.delivery_webhook(&message, domain, rcpt);
+                }
+            }
 
             // Send any due Delivery Status Notifications
             core.send_dsn(&mut message, &span).await;
@@ -171,6 +179,20 @@ impl DeliveryAttempt {
                     continue;
                 }
 
+                // Recipients resolved in a previous attempt are not reported again
+                let pending_rcpts = recipients
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, rcpt)| {
+                        rcpt.domain_idx == domain_idx
+                            && matches!(
+                                rcpt.status,
+                                Status::Scheduled | Status::TemporaryFailure(_)
+                            )
+                    })
+                    .map(|(idx, _)| idx)
+                    .collect::<Vec<_>>();
+
                 // Create new span for domain
                 let span = tracing::info_span!(
                     parent: &span,
@@ -225,6 +247,9 @@ impl DeliveryAttempt {
                                 .await
                                 .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                         );
+                        for &idx in &pending_rcpts {
+                            core.delivery_webhook(&message, domain, &recipients[idx]);
+                        }
                         continue 'next_domain;
                     }
                     Some(next_hop) => (
@@ -357,6 +382,9 @@ impl DeliveryAttempt {
                                         .await
                                         .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                                 );
+                                for &idx in &pending_rcpts {
+                                    core.delivery_webhook(&message, domain, &recipients[idx]);
+                                }
                                 continue 'next_domain;
                             } else {
                                 tracing::debug!(
@@ -395,6 +423,9 @@ impl DeliveryAttempt {
                                     .await
                                     .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                             );
+                            for &idx in &pending_rcpts {
+                                core.delivery_webhook(&message, domain, &recipients[idx]);
+                            }
                             continue 'next_domain;
                         }
                     };
@@ -422,6 +453,9 @@ impl DeliveryAttempt {
                                 .await
                                 .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                         );
+                        for &idx in &pending_rcpts {
+                            core.delivery_webhook(&message, domain, &recipients[idx]);
+                        }
                         continue 'next_domain;
                     }
                 }
@@ -1038,6 +1072,9 @@ impl DeliveryAttempt {
                                 .await
                                 .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                         );
+                        for &idx in &pending_rcpts {
+                            core.delivery_webhook(&message, domain, &recipients[idx]);
+                        }
                         continue 'next_domain;
                     }
                 }
@@ -1051,6 +1088,9 @@ impl DeliveryAttempt {
                         .await
                         .unwrap_or_else(|| vec![Duration::from_secs(60)]),
                 );
+                for &idx in &pending_rcpts {
+                    core.delivery_webhook(&message, domain, &recipients[idx]);
+                }
             }
             message.domains = domains;
             message.recipients = recipients;
@@ -1117,6 +1157,22 @@ impl DeliveryAttempt {
 }
 
 impl Message {
+    /// Returns the indexes of all recipients that are still pending delivery
+    pub fn pending_recipients(&self) -> Vec<usize> {
+        self.recipients
+            .iter()
+            .enumerate()
+            .filter(|(_, rcpt)| {
+                matches!(rcpt.status, Status::Scheduled | Status::TemporaryFailure(_))
+                    && matches!(
+                        self.domains[rcpt.domain_idx].status,
+                        Status::Scheduled | Status::TemporaryFailure(_)
+                    )
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
     /// Marks as failed all domains that reached their expiration time
     pub fn has_pending_delivery(&mut self, span: &tracing::Span) -> bool {
         let now = now();
@@ -26,7 +26,8 @@ use tokio::sync::{mpsc, oneshot};
 use utils::ipc::{DeliveryEvent, DeliveryResult, IngestMessage};
 
 use crate::queue::{
-    Error, ErrorDetails, HostResponse, Message, Recipient, Status, RCPT_STATUS_CHANGED,
+    Error, ErrorDetails, HostResponse, Message, Recipient, Status, MAIL_AUTHOR_AUTHENTICATED,
+    MAIL_SENDER_AUTHENTICATED, RCPT_STATUS_CHANGED,
 };
 
 impl Message {
@@ -65,6 +66,8 @@ impl Message {
                     recipients: recipient_addresses,
                     message_blob: self.blob_hash.clone(),
                     message_size: self.size,
+                    sender_authenticated: self.has_flag(MAIL_SENDER_AUTHENTICATED),
+                    author_authenticated: self.has_flag(MAIL_AUTHOR_AUTHENTICATED),
                 },
                 result_tx,
             })
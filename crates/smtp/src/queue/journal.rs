@@ -0,0 +1,191 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{fmt::Write, net::IpAddr};
+
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::{DateTime, MessageParser};
+
+use crate::core::SMTP;
+
+use super::{DomainPart, Message, SimpleEnvelope};
+
+pub struct JournalReport {
+    pub addresses: Vec<String>,
+    pub report: Vec<u8>,
+}
+
+impl SMTP {
+    /// Builds an envelope-wrapped copy of a message for each journal address
+    /// matching its sender or recipients.
+    pub async fn build_journal_report(
+        &self,
+        message: &Message,
+        raw_headers: &[u8],
+        raw_message: &[u8],
+        authenticated_as: &str,
+        remote_ip: IpAddr,
+    ) -> Option<JournalReport> {
+        let config = &self.queue.config.journal;
+        if config.address.is_empty() {
+            return None;
+        }
+
+        // Obtain the journal addresses for each recipient
+        let mut addresses: Vec<String> = Vec::new();
+        for rcpt in &message.recipients {
+            if let Some(address) = self
+                .eval_if::<String, _>(
+                    &config.address,
+                    &SimpleEnvelope::new_rcpt(
+                        message,
+                        rcpt.address_lcase.domain_part(),
+                        &rcpt.address_lcase,
+                    ),
+                )
+                .await
+                .map(|address| address.trim().to_lowercase())
+                .filter(|address| !address.is_empty())
+            {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        if addresses.is_empty() {
+            return None;
+        }
+
+        // Build envelope metadata
+        let mut raw = Vec::with_capacity(raw_headers.len() + raw_message.len());
+        raw.extend_from_slice(raw_headers);
+        raw.extend_from_slice(raw_message);
+        let parsed = MessageParser::new().parse_headers(&raw);
+        let mut envelope = String::with_capacity(128);
+        let _ = write!(
+            envelope,
+            "Sender: {}\r\n",
+            if !message.return_path.is_empty() {
+                message.return_path.as_str()
+            } else {
+                "<>"
+            }
+        );
+        for rcpt in &message.recipients {
+            let _ = write!(envelope, "Recipient: {}\r\n", rcpt.address);
+        }
+        if !authenticated_as.is_empty() {
+            let _ = write!(envelope, "Authenticated-As: {authenticated_as}\r\n");
+        }
+        let _ = write!(envelope, "Remote-IP: {remote_ip}\r\n");
+        if let Some(message_id) = parsed.as_ref().and_then(|m| m.message_id()) {
+            let _ = write!(envelope, "Message-ID: <{message_id}>\r\n");
+        }
+        let _ = write!(envelope, "Queue-ID: {:x}\r\n", message.id);
+        let _ = write!(
+            envelope,
+            "Arrival-Date: {}\r\n",
+            DateTime::from_timestamp(message.created as i64).to_rfc822()
+        );
+        let subject = parsed
+            .as_ref()
+            .and_then(|m| m.subject())
+            .map(|subject| format!("Journal Report: {subject}"))
+            .unwrap_or_else(|| "Journal Report".to_string());
+
+        // Obtain sender name and address
+        let from_name = self
+            .eval_if(&config.name, message)
+            .await
+            .unwrap_or_else(|| String::from("Journal Service"));
+        let from_addr = self
+            .eval_if(&config.from_address, message)
+            .await
+            .unwrap_or_else(|| String::from("MAILER-DAEMON@localhost"));
+        let hostname = self
+            .eval_if(&self.queue.config.hostname, message)
+            .await
+            .unwrap_or_else(|| String::from("localhost"));
+
+        // Build message
+        let report = MessageBuilder::new()
+            .from((from_name.as_str(), from_addr.as_str()))
+            .header("To", HeaderType::Text(addresses.join(", ").into()))
+            .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+            .header("X-Journal-Report", HeaderType::Text("1".into()))
+            .message_id(format!("<{}@{}>", make_boundary("."), hostname))
+            .subject(subject)
+            .body(MimePart::new(
+                ContentType::new("multipart/mixed"),
+                BodyPart::Multipart(vec![
+                    MimePart::new(
+                        ContentType::new("text/plain").attribute("charset", "utf-8"),
+                        BodyPart::Text(envelope.into()),
+                    ),
+                    MimePart::new(
+                        ContentType::new("message/rfc822"),
+                        BodyPart::Binary(raw.into()),
+                    )
+                    .attachment("message.eml")
+                    .transfer_encoding("8bit"),
+                ]),
+            ))
+            .write_to_vec()
+            .unwrap_or_default();
+
+        Some(JournalReport { addresses, report })
+    }
+
+    pub async fn send_journal_report(&self, journal: JournalReport, span: &tracing::Span) {
+        let mut message = self.queue.new_message("", "", "");
+        for address in &journal.addresses {
+            message.add_recipient(address, self).await;
+        }
+
+        // Sign message
+        let signature = self
+            .sign_message(
+                &mut message,
+                &self.queue.config.journal.sign,
+                &journal.report,
+                span,
+            )
+            .await;
+
+        tracing::debug!(
+            parent: span,
+            context = "journal",
+            event = "queue",
+            addresses = ?journal.addresses,
+            "Queueing journal report."
+        );
+
+        message
+            .queue(signature.as_deref(), &journal.report, self, span)
+            .await;
+    }
+}
@@ -40,10 +40,12 @@ use crate::core::{eval::*, ResolveVariable};
 use self::spool::QueueEventLock;
 
 pub mod dsn;
+pub mod journal;
 pub mod manager;
 pub mod quota;
 pub mod spool;
 pub mod throttle;
+pub mod webhook;
 
 pub type QueueId = u64;
 
@@ -116,6 +118,9 @@ pub struct Recipient {
 pub const RCPT_DSN_SENT: u64 = 1 << 32;
 pub const RCPT_STATUS_CHANGED: u64 = 2 << 32;
 
+pub const MAIL_SENDER_AUTHENTICATED: u64 = 1 << 32;
+pub const MAIL_AUTHOR_AUTHENTICATED: u64 = 2 << 32;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status<T, E> {
     #[serde(rename = "scheduled")]
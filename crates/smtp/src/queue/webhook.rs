@@ -0,0 +1,55 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use utils::webhooks::{WebhookPayload, WebhookType};
+
+use crate::core::SMTP;
+
+use super::{Domain, Message, Recipient, Status};
+
+impl SMTP {
+    /// Notifies webhooks about the outcome of a delivery attempt to a recipient.
+    pub fn delivery_webhook(&self, message: &Message, domain: &Domain, rcpt: &Recipient) {
+        let (typ, status): (_, &dyn Display) = match &rcpt.status {
+            Status::Completed(_) => (WebhookType::DeliverySuccess, &rcpt.status),
+            Status::TemporaryFailure(_) => (WebhookType::DeliveryDelayed, &rcpt.status),
+            Status::PermanentFailure(_) => (WebhookType::DeliveryFailure, &rcpt.status),
+            Status::Scheduled => match &domain.status {
+                Status::Completed(_) => (WebhookType::DeliverySuccess, &domain.status),
+                Status::PermanentFailure(_) => (WebhookType::DeliveryFailure, &domain.status),
+                Status::TemporaryFailure(_) | Status::Scheduled => {
+                    (WebhookType::DeliveryDelayed, &domain.status)
+                }
+            },
+        };
+
+        self.webhooks.send(typ, || WebhookPayload::Delivery {
+            queue_id: message.id,
+            sender: message.return_path.clone(),
+            recipient: rcpt.address.clone(),
+            status: status.to_string(),
+        });
+    }
+}
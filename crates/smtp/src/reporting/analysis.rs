@@ -42,6 +42,7 @@ use store::{
     Serialize,
 };
 use tokio::runtime::Handle;
+use utils::webhooks::{WebhookPayload, WebhookType};
 
 use crate::core::SMTP;
 
@@ -283,6 +284,24 @@ impl AnalyzeReport for Arc<SMTP> {
                     },
                 };
 
+                // Notify webhooks
+                let typ = match &report {
+                    Format::Dmarc(_) => WebhookType::ReportDmarc,
+                    Format::Tls(_) => WebhookType::ReportTls,
+                    Format::Arf(_) => WebhookType::ReportArf,
+                };
+                core.webhooks.send(typ, || WebhookPayload::Report {
+                    from: from.clone(),
+                    to: to.clone(),
+                    subject: subject.clone(),
+                    report: match &report {
+                        Format::Dmarc(report) => serde_json::to_value(report),
+                        Format::Tls(report) => serde_json::to_value(report),
+                        Format::Arf(report) => serde_json::to_value(report),
+                    }
+                    .unwrap_or_default(),
+                });
+
                 // Store report
                 if let Some(expires_in) = &core.report.config.analysis.store {
                     let expires = now() + expires_in.as_secs();
@@ -0,0 +1,184 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use nlp::{
+    language::{
+        detect::{LanguageDetector, MIN_LANGUAGE_SCORE},
+        stemmer::Stemmer,
+        Language,
+    },
+    tokenizers::word::WordTokenizer,
+};
+use tantivy::{
+    tokenizer::{PreTokenizedString, Token},
+    TantivyDocument, Term,
+};
+
+use crate::{
+    backend::MAX_TOKEN_LENGTH,
+    fts::index::{FtsDocument, Type},
+};
+
+use super::{token_text, TantivyStore, WriterOp};
+
+#[derive(Default)]
+struct Tokens {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl TantivyStore {
+    pub async fn fts_index<T: Into<u8> + Display + Clone + std::fmt::Debug>(
+        &self,
+        document: FtsDocument<'_, T>,
+    ) -> crate::Result<()> {
+        let mut detect = LanguageDetector::new();
+        let mut words = Tokens::default();
+        let mut stemmed = Tokens::default();
+        let mut keywords = Vec::new();
+        let mut parts = Vec::new();
+
+        for text in document.parts {
+            match text.typ {
+                Type::Text(language) => {
+                    let language = if language == Language::Unknown {
+                        detect.detect(&text.text, MIN_LANGUAGE_SCORE)
+                    } else {
+                        language
+                    };
+                    parts.push((text.field, language, text.text));
+                }
+                Type::Tokenize => {
+                    let field = u8::from(text.field);
+                    for token in WordTokenizer::new(text.text.as_ref(), MAX_TOKEN_LENGTH) {
+                        words.push(field, token.word.as_ref(), token.from, token.to);
+                    }
+                    words.next_part();
+                }
+                Type::Keyword => {
+                    keywords.push(token_text(u8::from(text.field), text.text.as_ref()));
+                }
+            }
+        }
+
+        let default_language = detect
+            .most_frequent_language()
+            .unwrap_or(document.default_language);
+
+        for (field, language, text) in parts.into_iter() {
+            let language = if language != Language::Unknown {
+                language
+            } else {
+                default_language
+            };
+            let field: u8 = field.into();
+
+            for token in Stemmer::new(&text, language, MAX_TOKEN_LENGTH) {
+                let position = words.push(field, token.word.as_ref(), token.from, token.to);
+                if let Some(stemmed_word) = token.stemmed_word {
+                    stemmed.position = position;
+                    stemmed.push(field, stemmed_word.as_ref(), token.from, token.to);
+                }
+            }
+            words.next_part();
+        }
+
+        // Build document
+        let id = document_key(
+            document.account_id,
+            document.collection,
+            document.document_id,
+        );
+        let mut doc = TantivyDocument::default();
+        doc.add_text(self.fields.id, &id);
+        doc.add_u64(self.fields.account_id, document.account_id as u64);
+        doc.add_u64(self.fields.collection, document.collection as u64);
+        doc.add_u64(self.fields.document_id, document.document_id as u64);
+        doc.add_pre_tokenized_text(self.fields.word, words.into());
+        doc.add_pre_tokenized_text(self.fields.stemmed, stemmed.into());
+        for keyword in keywords {
+            doc.add_text(self.fields.keyword, keyword);
+        }
+
+        // Replace any previous version of the document
+        self.send(WriterOp::Index {
+            id: Term::from_field_text(self.fields.id, &id),
+            document: doc,
+        })
+    }
+
+    pub async fn fts_remove(
+        &self,
+        account_id: u32,
+        collection: u8,
+        document_id: u32,
+    ) -> crate::Result<bool> {
+        self.send(WriterOp::Remove(Term::from_field_text(
+            self.fields.id,
+            &document_key(account_id, collection, document_id),
+        )))
+        .map(|_| true)
+    }
+
+    pub async fn fts_remove_all(&self, account_id: u32) -> crate::Result<()> {
+        self.send(WriterOp::Remove(Term::from_field_u64(
+            self.fields.account_id,
+            account_id as u64,
+        )))
+    }
+}
+
+impl Tokens {
+    fn push(&mut self, field: u8, word: &str, from: usize, to: usize) -> usize {
+        let position = self.position;
+        self.tokens.push(Token {
+            offset_from: from,
+            offset_to: to,
+            position,
+            text: token_text(field, word),
+            position_length: 1,
+        });
+        self.position += 1;
+        position
+    }
+
+    // Leave a gap between parts so phrases do not match across them
+    fn next_part(&mut self) {
+        self.position += 1;
+    }
+}
+
+impl From<Tokens> for PreTokenizedString {
+    fn from(tokens: Tokens) -> Self {
+        PreTokenizedString {
+            text: String::new(),
+            tokens: tokens.tokens,
+        }
+    }
+}
+
+fn document_key(account_id: u32, collection: u8, document_id: u32) -> String {
+    format!("{account_id}:{collection}:{document_id}")
+}
@@ -0,0 +1,288 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use tantivy::{
+    directory::MmapDirectory,
+    schema::{
+        Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED, STRING,
+    },
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, TantivyError, Term,
+};
+use tokio::sync::oneshot;
+use utils::config::{utils::AsKey, Config};
+
+pub mod index;
+pub mod query;
+
+pub struct TantivyStore {
+    reader: IndexReader,
+    writer: mpsc::Sender<WriterOp>,
+    has_changes: Arc<AtomicBool>,
+    fields: Fields,
+}
+
+// The index writer is owned by a dedicated thread, changes are batched and
+// committed every `writer.commit.interval` or `writer.commit.max-changes`.
+enum WriterOp {
+    Index { id: Term, document: TantivyDocument },
+    Remove(Term),
+    Commit(oneshot::Sender<crate::Result<()>>),
+}
+
+struct Writer {
+    writer: IndexWriter,
+    reader: IndexReader,
+    commit_interval: Duration,
+    commit_max_changes: usize,
+}
+
+struct Fields {
+    id: Field,
+    account_id: Field,
+    collection: Field,
+    document_id: Field,
+    word: Field,
+    stemmed: Field,
+    keyword: Field,
+}
+
+impl TantivyStore {
+    pub async fn open(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
+        let prefix = prefix.as_key();
+        // Create the index directory if it doesn't exist
+        let idx_path: PathBuf = PathBuf::from(config.value_require_((&prefix, "path"))?);
+        std::fs::create_dir_all(&idx_path)
+            .map_err(|err| {
+                config.new_build_error(
+                    (&prefix, "path"),
+                    format!(
+                        "Failed to create index directory {}: {:?}",
+                        idx_path.display(),
+                        err
+                    ),
+                )
+            })
+            .ok()?;
+
+        // Tokens are generated by the nlp crate, which takes care of
+        // language detection and stemming, so no tokenizer is configured.
+        let mut schema = Schema::builder();
+        let text_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer("raw")
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let fields = Fields {
+            id: schema.add_text_field("id", STRING),
+            account_id: schema.add_u64_field("account_id", INDEXED),
+            collection: schema.add_u64_field("collection", INDEXED),
+            document_id: schema.add_u64_field("document_id", FAST),
+            word: schema.add_text_field("word", text_options.clone()),
+            stemmed: schema.add_text_field("stemmed", text_options),
+            keyword: schema.add_text_field("keyword", STRING),
+        };
+
+        let index = MmapDirectory::open(&idx_path)
+            .map_err(TantivyError::from)
+            .and_then(|dir| Index::open_or_create(dir, schema.build()))
+            .map_err(|err| {
+                config.new_build_error(prefix.as_str(), format!("Failed to open index: {:?}", err))
+            })
+            .ok()?;
+        let writer = index
+            .writer(
+                config
+                    .property_or_default_((&prefix, "writer.heap-size"), "52428800")
+                    .unwrap_or(52428800),
+            )
+            .map_err(|err| {
+                config.new_build_error(
+                    prefix.as_str(),
+                    format!("Failed to create index writer: {:?}", err),
+                )
+            })
+            .ok()?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(|err| {
+                config.new_build_error(
+                    prefix.as_str(),
+                    format!("Failed to create index reader: {:?}", err),
+                )
+            })
+            .ok()?;
+
+        let writer = Writer {
+            writer,
+            reader: reader.clone(),
+            commit_interval: config
+                .property_or_default_::<Duration>((&prefix, "writer.commit.interval"), "1s")
+                .unwrap_or(Duration::from_secs(1)),
+            commit_max_changes: config
+                .property_or_default_((&prefix, "writer.commit.max-changes"), "1000")
+                .unwrap_or(1000),
+        };
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("tantivy-writer".to_string())
+            .spawn(move || writer.run(rx))
+            .map_err(|err| {
+                config.new_build_error(
+                    prefix.as_str(),
+                    format!("Failed to spawn index writer thread: {:?}", err),
+                )
+            })
+            .ok()?;
+
+        Some(TantivyStore {
+            reader,
+            writer: tx,
+            has_changes: Arc::new(AtomicBool::new(false)),
+            fields,
+        })
+    }
+
+    fn send(&self, op: WriterOp) -> crate::Result<()> {
+        self.writer.send(op).map_err(|_| writer_stopped())?;
+        self.has_changes.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Commits any pending changes so they become visible to searches.
+    pub async fn commit(&self) -> crate::Result<()> {
+        if self.has_changes.swap(false, Ordering::AcqRel) {
+            let (tx, rx) = oneshot::channel();
+            self.writer
+                .send(WriterOp::Commit(tx))
+                .map_err(|_| writer_stopped())?;
+            rx.await.map_err(|_| writer_stopped())?
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Writer {
+    fn run(mut self, rx: mpsc::Receiver<WriterOp>) {
+        let mut changes = 0;
+        let mut last_commit = Instant::now();
+
+        loop {
+            let op = if changes > 0 {
+                match rx.recv_timeout(self.commit_interval.saturating_sub(last_commit.elapsed())) {
+                    Ok(op) => Some(op),
+                    Err(mpsc::RecvTimeoutError::Timeout) => None,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        // Flush pending changes on shutdown
+                        let _ = self.commit();
+                        return;
+                    }
+                }
+            } else {
+                match rx.recv() {
+                    Ok(op) => Some(op),
+                    Err(_) => return,
+                }
+            };
+
+            let mut reply = None;
+            match op {
+                Some(WriterOp::Index { id, document }) => {
+                    self.writer.delete_term(id);
+                    if let Err(err) = self.writer.add_document(document) {
+                        tracing::error!(
+                            context = "tantivy",
+                            event = "error",
+                            reason = ?err,
+                            "Failed to add document to index."
+                        );
+                    }
+                    changes += 1;
+                }
+                Some(WriterOp::Remove(term)) => {
+                    self.writer.delete_term(term);
+                    changes += 1;
+                }
+                Some(WriterOp::Commit(tx)) => {
+                    reply = tx.into();
+                }
+                None => (),
+            }
+
+            if reply.is_some()
+                || changes >= self.commit_max_changes
+                || (changes > 0 && last_commit.elapsed() >= self.commit_interval)
+            {
+                let result = if changes > 0 { self.commit() } else { Ok(()) };
+                changes = 0;
+                last_commit = Instant::now();
+                if let Some(reply) = reply {
+                    let _ = reply.send(result);
+                }
+            }
+        }
+    }
+
+    fn commit(&mut self) -> crate::Result<()> {
+        let result = self
+            .writer
+            .commit()
+            .map(|_| ())
+            .and_then(|_| self.reader.reload())
+            .map_err(crate::Error::from);
+        if let Err(err) = &result {
+            tracing::error!(
+                context = "tantivy",
+                event = "error",
+                reason = ?err,
+                "Failed to commit index changes."
+            );
+        }
+        result
+    }
+}
+
+impl From<TantivyError> for crate::Error {
+    fn from(value: TantivyError) -> Self {
+        crate::Error::InternalError(format!("Tantivy error: {}", value))
+    }
+}
+
+fn writer_stopped() -> crate::Error {
+    crate::Error::InternalError("Tantivy index writer is not running.".to_string())
+}
+
+fn token_text(field: u8, word: &str) -> String {
+    format!("{field}:{word}")
+}
@@ -0,0 +1,175 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::fmt::Display;
+
+use nlp::language::stemmer::Stemmer;
+use roaring::RoaringBitmap;
+use tantivy::{
+    collector::DocSetCollector,
+    query::{AllQuery, BooleanQuery, EmptyQuery, Occur, PhraseQuery, Query, TermQuery},
+    schema::IndexRecordOption,
+    Term,
+};
+
+use crate::{backend::MAX_TOKEN_LENGTH, fts::FtsFilter};
+
+use super::{token_text, TantivyStore};
+
+impl TantivyStore {
+    pub async fn fts_query<T: Into<u8> + Display + Clone + std::fmt::Debug>(
+        &self,
+        account_id: u32,
+        collection: impl Into<u8>,
+        filters: Vec<FtsFilter<T>>,
+    ) -> crate::Result<RoaringBitmap> {
+        // Make pending changes visible before searching
+        self.commit().await?;
+
+        let mut stack: Vec<(FtsFilter<T>, Vec<Box<dyn Query>>)> = vec![];
+        let mut conditions: Vec<Box<dyn Query>> = vec![
+            term_query(Term::from_field_u64(
+                self.fields.account_id,
+                account_id as u64,
+            )),
+            term_query(Term::from_field_u64(
+                self.fields.collection,
+                collection.into() as u64,
+            )),
+        ];
+        let mut logical_op = FtsFilter::And;
+
+        for filter in filters {
+            match filter {
+                FtsFilter::Exact {
+                    field,
+                    text,
+                    language,
+                } => {
+                    let field: u8 = field.into();
+                    let mut terms = language
+                        .tokenize_text(text.as_ref(), MAX_TOKEN_LENGTH)
+                        .map(|token| {
+                            Term::from_field_text(
+                                self.fields.word,
+                                &token_text(field, token.word.as_ref()),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+
+                    conditions.push(match terms.len() {
+                        0 => Box::new(EmptyQuery),
+                        1 => term_query(terms.pop().unwrap()),
+                        _ => Box::new(PhraseQuery::new(terms)),
+                    });
+                }
+                FtsFilter::Contains {
+                    field,
+                    text,
+                    language,
+                } => {
+                    let field: u8 = field.into();
+                    let mut tokens = Vec::new();
+
+                    for token in Stemmer::new(text.as_ref(), language, MAX_TOKEN_LENGTH) {
+                        let stemmed_word = token.stemmed_word.as_ref().unwrap_or(&token.word);
+                        tokens.push(Box::new(BooleanQuery::union(vec![
+                            term_query(Term::from_field_text(
+                                self.fields.word,
+                                &token_text(field, token.word.as_ref()),
+                            )),
+                            term_query(Term::from_field_text(
+                                self.fields.stemmed,
+                                &token_text(field, stemmed_word.as_ref()),
+                            )),
+                        ])) as Box<dyn Query>);
+                    }
+
+                    conditions.push(if !tokens.is_empty() {
+                        Box::new(BooleanQuery::intersection(tokens))
+                    } else {
+                        Box::new(EmptyQuery)
+                    });
+                }
+                FtsFilter::Keyword { field, text } => {
+                    conditions.push(term_query(Term::from_field_text(
+                        self.fields.keyword,
+                        &token_text(field.into(), &text),
+                    )));
+                }
+                FtsFilter::And | FtsFilter::Or | FtsFilter::Not => {
+                    stack.push((logical_op, conditions));
+                    logical_op = filter;
+                    conditions = Vec::new();
+                }
+                FtsFilter::End => {
+                    if let Some((prev_logical_op, mut prev_conditions)) = stack.pop() {
+                        if !conditions.is_empty() {
+                            prev_conditions.push(match logical_op {
+                                FtsFilter::And => Box::new(BooleanQuery::intersection(conditions)),
+                                FtsFilter::Or => Box::new(BooleanQuery::union(conditions)),
+                                FtsFilter::Not => Box::new(BooleanQuery::new(
+                                    std::iter::once((
+                                        Occur::Must,
+                                        Box::new(AllQuery) as Box<dyn Query>,
+                                    ))
+                                    .chain(
+                                        conditions
+                                            .into_iter()
+                                            .map(|condition| (Occur::MustNot, condition)),
+                                    )
+                                    .collect(),
+                                )),
+                                _ => unreachable!(),
+                            });
+                        }
+                        logical_op = prev_logical_op;
+                        conditions = prev_conditions;
+                    }
+                }
+            }
+        }
+
+        let searcher = self.reader.searcher();
+        let mut results = RoaringBitmap::new();
+        let mut columns = Vec::with_capacity(searcher.segment_readers().len());
+        for segment_reader in searcher.segment_readers() {
+            columns.push(segment_reader.fast_fields().u64("document_id")?);
+        }
+
+        for address in searcher.search(&BooleanQuery::intersection(conditions), &DocSetCollector)? {
+            if let Some(document_id) = columns
+                .get(address.segment_ord as usize)
+                .and_then(|column| column.first(address.doc_id))
+            {
+                results.insert(document_id as u32);
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+fn term_query(term: Term) -> Box<dyn Query> {
+    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+}
@@ -0,0 +1,308 @@
+/*
+ * Copyright (c) 2023, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{ops::Range, time::Duration};
+
+use utils::{
+    config::{utils::AsKey, Config},
+    BlobHash, BLOB_HASH_LEN,
+};
+
+use crate::{
+    write::{
+        assert::{AssertValue, HashedValue},
+        key::DeserializeBigEndian,
+        now, BatchBuilder, BlobOp, ValueClass,
+    },
+    BlobBackend, Deserialize, IterateParams, Serialize, Store, Stores, ValueKey, U64_LEN,
+};
+
+pub struct TieredStore {
+    pub(crate) store: Store,
+    pub(crate) hot: BlobBackend,
+    pub(crate) cold: BlobBackend,
+    pub(crate) migrate_after: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobTier {
+    Hot,
+    Cold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobPlacement {
+    pub tier: BlobTier,
+    pub since: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationResult {
+    pub migrated: usize,
+    pub missing: usize,
+}
+
+impl TieredStore {
+    pub fn open(config: &mut Config, prefix: impl AsKey, stores: &Stores) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let mut tiers = Vec::with_capacity(2);
+        for tier in ["hot", "cold"] {
+            let store_id = config.value_require_((&prefix, tier))?.to_string();
+            if let Some(blob_store) = stores.blob_stores.get(&store_id) {
+                if matches!(blob_store.backend, BlobBackend::Tiered(_)) {
+                    config.new_build_error(
+                        (&prefix, tier),
+                        format!("Blob store {store_id:?} cannot be nested in a tiered store"),
+                    );
+                    return None;
+                }
+                tiers.push(blob_store.backend.clone());
+            } else {
+                config.new_build_error(
+                    (&prefix, tier),
+                    format!("Blob store {store_id:?} not found"),
+                );
+                return None;
+            }
+        }
+
+        // Tier placement is tracked in the data store, next to the blob links,
+        // so that migrations can assert that a blob is still linked.
+        let store_id = config.value_require_("storage.data")?.to_string();
+        let store = if let Some(store) = stores.stores.get(&store_id) {
+            store.clone()
+        } else {
+            config.new_build_error("storage.data", format!("Data store {store_id:?} not found"));
+            return None;
+        };
+
+        let migrate_after = config
+            .property_or_default_::<Duration>((&prefix, "migrate.after"), "30d")
+            .unwrap_or_else(|| Duration::from_secs(30 * 86400))
+            .as_secs();
+        let cold = tiers.pop()?;
+        let hot = tiers.pop()?;
+
+        Some(TieredStore {
+            store,
+            hot,
+            cold,
+            migrate_after,
+        })
+    }
+
+    pub(crate) async fn get_blob(
+        &self,
+        key: &[u8],
+        range: Range<usize>,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        // Blobs are read from the hot tier first, falling back to the cold tier
+        // in case the blob was already migrated.
+        match self.hot.get_blob(key, range.clone()).await? {
+            Some(data) => Ok(Some(data)),
+            None => self.cold.get_blob(key, range).await,
+        }
+    }
+
+    pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
+        let hash = Self::blob_hash(key)?;
+
+        // Blobs are content addressed, there is no need to write them again
+        // if they already live in the cold tier.
+        if let Some(BlobPlacement {
+            tier: BlobTier::Cold,
+            ..
+        }) = self.placement(&hash).await?
+        {
+            return Ok(());
+        }
+
+        self.hot.put_blob(key, data).await?;
+        self.set_placement(hash, BlobTier::Hot).await
+    }
+
+    pub(crate) async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        let hash = Self::blob_hash(key)?;
+        let deleted_hot = self.hot.delete_blob(key).await?;
+        let deleted_cold = self.cold.delete_blob(key).await?;
+
+        let mut batch = BatchBuilder::new();
+        batch.clear(BlobOp::Tier { hash });
+        self.store.write(batch.build()).await?;
+
+        Ok(deleted_hot || deleted_cold)
+    }
+
+    pub async fn placement(&self, hash: &BlobHash) -> crate::Result<Option<BlobPlacement>> {
+        self.store
+            .get_value::<BlobPlacement>(ValueKey::from(ValueClass::Blob(BlobOp::Tier {
+                hash: hash.clone(),
+            })))
+            .await
+    }
+
+    async fn set_placement(&self, hash: BlobHash, tier: BlobTier) -> crate::Result<()> {
+        let mut batch = BatchBuilder::new();
+        batch.set(
+            BlobOp::Tier { hash },
+            BlobPlacement { tier, since: now() }.serialize(),
+        );
+        self.store.write(batch.build()).await.map(|_| ())
+    }
+
+    pub async fn migrate_blobs(&self) -> crate::Result<MigrationResult> {
+        // Obtain hot blobs that are past the migration threshold
+        let cutoff = now().saturating_sub(self.migrate_after);
+        let mut candidates = Vec::new();
+        self.store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Blob(BlobOp::Tier {
+                        hash: BlobHash::default(),
+                    })),
+                    ValueKey::from(ValueClass::Blob(BlobOp::Tier {
+                        hash: BlobHash::new_max(),
+                    })),
+                )
+                .ascending(),
+                |key, value| {
+                    let placement = HashedValue::<BlobPlacement>::deserialize(value)?;
+                    if placement.inner.tier == BlobTier::Hot && placement.inner.since <= cutoff {
+                        candidates.push((
+                            BlobHash::try_from_hash_slice(
+                                key.get(1..1 + BLOB_HASH_LEN).ok_or_else(|| {
+                                    crate::Error::InternalError(format!(
+                                        "Invalid key {key:?} in blob tier tables"
+                                    ))
+                                })?,
+                            )
+                            .unwrap(),
+                            placement,
+                        ));
+                    }
+                    Ok(true)
+                },
+            )
+            .await?;
+
+        // Copy blobs to the cold tier before removing them from the hot tier,
+        // so that readers always find the blob in one of them.
+        let mut result = MigrationResult::default();
+        for (hash, placement) in candidates {
+            if !self.store.blob_is_linked(&hash).await? {
+                // Unlinked blobs are left for the purge task
+                continue;
+            }
+
+            if let Some(data) = self.hot.get_blob(hash.as_ref(), 0..usize::MAX).await? {
+                self.cold.put_blob(hash.as_ref(), &data).await?;
+                if self.promote_placement(&hash, &placement).await? {
+                    self.hot.delete_blob(hash.as_ref()).await?;
+                    result.migrated += 1;
+                } else {
+                    // The blob was purged while it was being copied
+                    self.cold.delete_blob(hash.as_ref()).await?;
+                }
+            } else if self.cold.get_blob(hash.as_ref(), 0..1).await?.is_some() {
+                self.promote_placement(&hash, &placement).await?;
+            } else {
+                tracing::debug!(
+                    context = "blob_migrate",
+                    event = "error",
+                    hash = ?hash,
+                    "Blob not found in any tier."
+                );
+                result.missing += 1;
+            }
+        }
+
+        Ok(result)
+    }
+
+    async fn promote_placement(
+        &self,
+        hash: &BlobHash,
+        placement: &HashedValue<BlobPlacement>,
+    ) -> crate::Result<bool> {
+        // Move the blob to the cold tier only if it is still committed and its
+        // placement was not cleared or changed by a concurrent purge.
+        let mut batch = BatchBuilder::new();
+        batch
+            .assert_value(
+                ValueClass::Blob(BlobOp::Commit { hash: hash.clone() }),
+                AssertValue::Some,
+            )
+            .assert_value(
+                ValueClass::Blob(BlobOp::Tier { hash: hash.clone() }),
+                placement,
+            )
+            .set(
+                BlobOp::Tier { hash: hash.clone() },
+                BlobPlacement {
+                    tier: BlobTier::Cold,
+                    since: now(),
+                }
+                .serialize(),
+            );
+        match self.store.write(batch.build()).await {
+            Ok(_) => Ok(true),
+            Err(crate::Error::AssertValueFailed) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn blob_hash(key: &[u8]) -> crate::Result<BlobHash> {
+        BlobHash::try_from_hash_slice(key).map_err(|_| {
+            crate::Error::InternalError(format!("Invalid blob key {key:?} for tiered store"))
+        })
+    }
+}
+
+impl Serialize for BlobPlacement {
+    fn serialize(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(U64_LEN + 1);
+        bytes.push(match self.tier {
+            BlobTier::Hot => 0,
+            BlobTier::Cold => 1,
+        });
+        bytes.extend_from_slice(&self.since.to_be_bytes());
+        bytes
+    }
+}
+
+impl Deserialize for BlobPlacement {
+    fn deserialize(bytes: &[u8]) -> crate::Result<Self> {
+        Ok(BlobPlacement {
+            tier: match bytes.first() {
+                Some(0) => BlobTier::Hot,
+                Some(1) => BlobTier::Cold,
+                _ => {
+                    return Err(crate::Error::InternalError(
+                        "Failed to deserialize blob placement".to_string(),
+                    ))
+                }
+            },
+            since: bytes.deserialize_be_u64(1)?,
+        })
+    }
+}
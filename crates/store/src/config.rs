@@ -26,9 +26,9 @@ use std::sync::Arc;
 use utils::config::{cron::SimpleCron, Config};
 
 use crate::{
-    backend::fs::FsStore,
+    backend::{fs::FsStore, tiered::TieredStore},
     write::purge::{PurgeSchedule, PurgeStore},
-    BlobStore, CompressionAlgo, FtsStore, LookupStore, QueryStore, Store, Stores,
+    BlobBackend, BlobStore, CompressionAlgo, FtsStore, LookupStore, QueryStore, Store, Stores,
 };
 
 #[cfg(feature = "s3")]
@@ -52,6 +52,9 @@ use crate::backend::rocksdb::RocksDbStore;
 #[cfg(feature = "elastic")]
 use crate::backend::elastic::ElasticSearchStore;
 
+#[cfg(feature = "tantivy")]
+use crate::backend::tantivy::TantivyStore;
+
 #[cfg(feature = "redis")]
 use crate::backend::redis::RedisStore;
 
@@ -62,6 +65,7 @@ impl Stores {
             .sub_keys("store", ".type")
             .map(|id| id.to_string())
             .collect::<Vec<_>>();
+        let mut tiered_ids = Vec::new();
         for id in ids {
             let id = id.as_str();
             // Parse store
@@ -173,6 +177,10 @@ impl Stores {
                     }
                     continue;
                 }
+                "tiered" => {
+                    tiered_ids.push((store_id, compression_algo));
+                    continue;
+                }
                 #[cfg(feature = "s3")]
                 "s3" => {
                     if let Some(db) = S3Store::open(config, prefix).await.map(BlobStore::from) {
@@ -192,6 +200,13 @@ impl Stores {
                     }
                     continue;
                 }
+                #[cfg(feature = "tantivy")]
+                "tantivy" => {
+                    if let Some(db) = TantivyStore::open(config, prefix).await.map(FtsStore::from) {
+                        stores.fts_stores.insert(store_id, db);
+                    }
+                    continue;
+                }
                 #[cfg(feature = "redis")]
                 "redis" => {
                     if let Some(db) = RedisStore::open(config, prefix)
@@ -238,6 +253,17 @@ impl Stores {
             }
         }
 
+        // Tiered blob stores are built once all other blob stores are available
+        for (store_id, compression_algo) in tiered_ids {
+            if let Some(db) = TieredStore::open(config, ("store", store_id.as_str()), &stores)
+                .map(BlobStore::from)
+            {
+                stores
+                    .blob_stores
+                    .insert(store_id, db.with_compression(compression_algo));
+            }
+        }
+
         // Parse purge schedules
         if let Some(store) = config
             .value("storage.data")
@@ -284,6 +310,21 @@ impl Stores {
                 });
             }
         }
+        for (store_id, blob_store) in &stores.blob_stores {
+            if let BlobBackend::Tiered(store) = &blob_store.backend {
+                if let Some(cron) = config.property_::<SimpleCron>((
+                    "store",
+                    store_id.as_str(),
+                    "migrate.frequency",
+                )) {
+                    stores.purge_schedules.push(PurgeSchedule {
+                        cron,
+                        store_id: store_id.clone(),
+                        store: PurgeStore::Migrate(store.clone()),
+                    });
+                }
+            }
+        }
 
         stores
     }
@@ -310,6 +351,7 @@ impl ConfigStore for Config {
             .sub_keys("store", ".type")
             .map(|id| id.to_string())
             .collect::<Vec<_>>();
+        let mut tiered_ids = Vec::new();
         for id in ids {
             let id = id.as_str();
             // Parse store
@@ -401,6 +443,10 @@ impl ConfigStore for Config {
                     );
                     continue;
                 }
+                "tiered" => {
+                    tiered_ids.push((store_id, compression_algo));
+                    continue;
+                }
                 #[cfg(feature = "s3")]
                 "s3" => {
                     config.blob_stores.insert(
@@ -418,6 +464,14 @@ impl ConfigStore for Config {
                     );
                     continue;
                 }
+                #[cfg(feature = "tantivy")]
+                "tantivy" => {
+                    config.fts_stores.insert(
+                        store_id,
+                        TantivyStore::open(self, prefix).await.unwrap().into(),
+                    );
+                    continue;
+                }
                 #[cfg(feature = "redis")]
                 "redis" => {
                     config.lookup_stores.insert(
@@ -453,6 +507,16 @@ impl ConfigStore for Config {
             }
         }
 
+        // Tiered blob stores are built once all other blob stores are available
+        for (store_id, compression_algo) in tiered_ids {
+            let store = TieredStore::open(self, ("store", store_id.as_str()), &config)
+                .ok_or_else(|| format!("Failed to build tiered blob store {store_id:?}."))?;
+            config.blob_stores.insert(
+                store_id,
+                BlobStore::from(store).with_compression(compression_algo),
+            );
+        }
+
         Ok(config)
     }
 
@@ -508,6 +572,20 @@ impl ConfigStore for Config {
             }
         }
 
+        for (store_id, blob_store) in &stores.blob_stores {
+            if let BlobBackend::Tiered(store) = &blob_store.backend {
+                if let Some(cron) =
+                    self.property::<SimpleCron>(("store", store_id.as_str(), "migrate.frequency"))?
+                {
+                    schedules.push(PurgeSchedule {
+                        cron,
+                        store_id: store_id.clone(),
+                        store: PurgeStore::Migrate(store.clone()),
+                    });
+                }
+            }
+        }
+
         Ok(schedules)
     }
 }
@@ -38,23 +38,7 @@ impl BlobStore {
             CompressionAlgo::Lz4 => 0..usize::MAX,
         };
 
-        let result = match &self.backend {
-            BlobBackend::Store(store) => match store {
-                #[cfg(feature = "sqlite")]
-                Store::SQLite(store) => store.get_blob(key, read_range).await,
-                #[cfg(feature = "foundation")]
-                Store::FoundationDb(store) => store.get_blob(key, read_range).await,
-                #[cfg(feature = "postgres")]
-                Store::PostgreSQL(store) => store.get_blob(key, read_range).await,
-                #[cfg(feature = "mysql")]
-                Store::MySQL(store) => store.get_blob(key, read_range).await,
-                #[cfg(feature = "rocks")]
-                Store::RocksDb(store) => store.get_blob(key, read_range).await,
-            },
-            BlobBackend::Fs(store) => store.get_blob(key, read_range).await,
-            #[cfg(feature = "s3")]
-            BlobBackend::S3(store) => store.get_blob(key, read_range).await,
-        };
+        let result = self.backend.get_blob(key, read_range).await;
 
         let decompressed = match self.compression {
             CompressionAlgo::Lz4 => match result? {
@@ -103,27 +87,70 @@ impl BlobStore {
             }
         };
 
-        match &self.backend {
+        self.backend.put_blob(key, data.as_ref()).await
+    }
+
+    pub async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        self.backend.delete_blob(key).await
+    }
+
+    pub fn with_compression(self, compression: CompressionAlgo) -> Self {
+        Self {
+            backend: self.backend,
+            compression,
+        }
+    }
+}
+
+impl BlobBackend {
+    pub(crate) async fn get_blob(
+        &self,
+        key: &[u8],
+        range: Range<usize>,
+    ) -> crate::Result<Option<Vec<u8>>> {
+        match self {
+            BlobBackend::Store(store) => match store {
+                #[cfg(feature = "sqlite")]
+                Store::SQLite(store) => store.get_blob(key, range).await,
+                #[cfg(feature = "foundation")]
+                Store::FoundationDb(store) => store.get_blob(key, range).await,
+                #[cfg(feature = "postgres")]
+                Store::PostgreSQL(store) => store.get_blob(key, range).await,
+                #[cfg(feature = "mysql")]
+                Store::MySQL(store) => store.get_blob(key, range).await,
+                #[cfg(feature = "rocks")]
+                Store::RocksDb(store) => store.get_blob(key, range).await,
+            },
+            BlobBackend::Fs(store) => store.get_blob(key, range).await,
+            #[cfg(feature = "s3")]
+            BlobBackend::S3(store) => store.get_blob(key, range).await,
+            BlobBackend::Tiered(store) => Box::pin(store.get_blob(key, range)).await,
+        }
+    }
+
+    pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> crate::Result<()> {
+        match self {
             BlobBackend::Store(store) => match store {
                 #[cfg(feature = "sqlite")]
-                Store::SQLite(store) => store.put_blob(key, data.as_ref()).await,
+                Store::SQLite(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "foundation")]
-                Store::FoundationDb(store) => store.put_blob(key, data.as_ref()).await,
+                Store::FoundationDb(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "postgres")]
-                Store::PostgreSQL(store) => store.put_blob(key, data.as_ref()).await,
+                Store::PostgreSQL(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "mysql")]
-                Store::MySQL(store) => store.put_blob(key, data.as_ref()).await,
+                Store::MySQL(store) => store.put_blob(key, data).await,
                 #[cfg(feature = "rocks")]
-                Store::RocksDb(store) => store.put_blob(key, data.as_ref()).await,
+                Store::RocksDb(store) => store.put_blob(key, data).await,
             },
-            BlobBackend::Fs(store) => store.put_blob(key, data.as_ref()).await,
+            BlobBackend::Fs(store) => store.put_blob(key, data).await,
             #[cfg(feature = "s3")]
-            BlobBackend::S3(store) => store.put_blob(key, data.as_ref()).await,
+            BlobBackend::S3(store) => store.put_blob(key, data).await,
+            BlobBackend::Tiered(store) => Box::pin(store.put_blob(key, data)).await,
         }
     }
 
-    pub async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
-        match &self.backend {
+    pub(crate) async fn delete_blob(&self, key: &[u8]) -> crate::Result<bool> {
+        match self {
             BlobBackend::Store(store) => match store {
                 #[cfg(feature = "sqlite")]
                 Store::SQLite(store) => store.delete_blob(key).await,
@@ -139,13 +166,7 @@ impl BlobStore {
             BlobBackend::Fs(store) => store.delete_blob(key).await,
             #[cfg(feature = "s3")]
             BlobBackend::S3(store) => store.delete_blob(key).await,
-        }
-    }
-
-    pub fn with_compression(self, compression: CompressionAlgo) -> Self {
-        Self {
-            backend: self.backend,
-            compression,
+            BlobBackend::Tiered(store) => Box::pin(store.delete_blob(key)).await,
         }
     }
 }
@@ -39,6 +39,8 @@ impl FtsStore {
             FtsStore::Store(store) => store.fts_index(document).await,
             #[cfg(feature = "elastic")]
             FtsStore::ElasticSearch(store) => store.fts_index(document).await,
+            #[cfg(feature = "tantivy")]
+            FtsStore::Tantivy(store) => store.fts_index(document).await,
         }
     }
 
@@ -54,6 +56,8 @@ impl FtsStore {
             FtsStore::ElasticSearch(store) => {
                 store.fts_query(account_id, collection, filters).await
             }
+            #[cfg(feature = "tantivy")]
+            FtsStore::Tantivy(store) => store.fts_query(account_id, collection, filters).await,
         }
     }
 
@@ -69,6 +73,17 @@ impl FtsStore {
             FtsStore::ElasticSearch(store) => {
                 store.fts_remove(account_id, collection, document_id).await
             }
+            #[cfg(feature = "tantivy")]
+            FtsStore::Tantivy(store) => store.fts_remove(account_id, collection, document_id).await,
+        }
+    }
+
+    /// Flushes any changes buffered by the backend.
+    pub async fn commit(&self) -> crate::Result<()> {
+        match self {
+            #[cfg(feature = "tantivy")]
+            FtsStore::Tantivy(store) => store.commit().await,
+            _ => Ok(()),
         }
     }
 
@@ -77,6 +92,8 @@ impl FtsStore {
             FtsStore::Store(store) => store.fts_remove_all(account_id).await,
             #[cfg(feature = "elastic")]
             FtsStore::ElasticSearch(store) => store.fts_remove_all(account_id).await,
+            #[cfg(feature = "tantivy")]
+            FtsStore::Tantivy(store) => store.fts_remove_all(account_id).await,
         }
     }
 }
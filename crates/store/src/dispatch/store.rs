@@ -23,12 +23,16 @@
 
 use std::ops::{BitAndAssign, Range};
 
+use ahash::AHashSet;
 use roaring::RoaringBitmap;
 
 use crate::{
-    write::{key::KeySerializer, now, AnyKey, Batch, BitmapClass, ReportClass, ValueClass},
-    BitmapKey, Deserialize, IterateParams, Key, Store, ValueKey, SUBSPACE_BITMAPS,
-    SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN,
+    write::{
+        key::{DeserializeBigEndian, KeySerializer},
+        now, AnyKey, Batch, BatchBuilder, BitmapClass, DirectoryClass, ReportClass, ValueClass,
+    },
+    BitmapKey, Deserialize, IterateParams, Key, Serialize, Store, ValueKey, SUBSPACE_BITMAPS,
+    SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN, U64_LEN,
 };
 
 #[cfg(feature = "test_mode")]
@@ -256,6 +260,63 @@ impl Store {
         )
         .await?;
 
+        // Delete expired deleted items and upload sessions, their blobs are released by the blob purge
+        for is_upload in [false, true] {
+            let class = |expires, id| {
+                if is_upload {
+                    ValueClass::UploadSession { expires, id }
+                } else {
+                    ValueClass::DeletedItem { expires, id }
+                }
+            };
+            let mut expired = Vec::new();
+            self.iterate(
+                IterateParams::new(
+                    ValueKey::from(class(0, 0)),
+                    ValueKey {
+                        account_id: u32::MAX,
+                        collection: 0,
+                        document_id: 0,
+                        class: class(u64::MAX, u64::MAX),
+                    },
+                )
+                .ascending()
+                .no_values(),
+                |key, _| {
+                    let expires = key.deserialize_be_u64(1 + U32_LEN)?;
+                    if expires <= now {
+                        expired.push((
+                            key.deserialize_be_u32(1)?,
+                            class(expires, key.deserialize_be_u64(1 + U32_LEN + U64_LEN)?),
+                        ));
+                    }
+                    Ok(true)
+                },
+            )
+            .await?;
+
+            // Recoverable items of accounts under legal hold are kept until the hold is lifted
+            if !is_upload {
+                let held = self
+                    .legal_holds(expired.iter().map(|(account_id, _)| *account_id).collect())
+                    .await?;
+                expired.retain(|(account_id, _)| !held.contains(account_id));
+            }
+
+            for chunk in expired.chunks(1000) {
+                let mut batch = BatchBuilder::new();
+                let mut last_account_id = u32::MAX;
+                for (account_id, class) in chunk {
+                    if *account_id != last_account_id {
+                        batch.with_account_id(*account_id);
+                        last_account_id = *account_id;
+                    }
+                    batch.clear(class.clone());
+                }
+                self.write(batch.build()).await?;
+            }
+        }
+
         match self {
             #[cfg(feature = "sqlite")]
             Self::SQLite(store) => store.purge_store().await,
@@ -286,6 +347,13 @@ impl Store {
     }
 
     pub async fn purge_account(&self, account_id: u32) -> crate::Result<()> {
+        // Accounts under legal hold cannot be purged
+        if self.legal_hold(account_id).await?.is_some() {
+            return Err(crate::Error::InternalError(format!(
+                "Account {account_id} is under legal hold and cannot be purged."
+            )));
+        }
+
         for subspace in [SUBSPACE_BITMAPS, SUBSPACE_LOGS, SUBSPACE_INDEXES] {
             self.delete_range(
                 AnyKey {
@@ -305,6 +373,14 @@ impl Store {
             (ValueClass::ReservedId, ValueClass::ReservedId),
             (ValueClass::Property(0), ValueClass::Property(0)),
             (ValueClass::TermIndex, ValueClass::TermIndex),
+            (
+                ValueClass::DeletedItem { expires: 0, id: 0 },
+                ValueClass::DeletedItem { expires: 0, id: 0 },
+            ),
+            (
+                ValueClass::UploadSession { expires: 0, id: 0 },
+                ValueClass::UploadSession { expires: 0, id: 0 },
+            ),
         ] {
             self.delete_range(
                 ValueKey {
@@ -326,6 +402,39 @@ impl Store {
         Ok(())
     }
 
+    /// Returns the time at which the account was placed under legal hold, if any.
+    pub async fn legal_hold(&self, account_id: u32) -> crate::Result<Option<u64>> {
+        self.get_value::<u64>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::LegalHold(account_id),
+        )))
+        .await
+    }
+
+    /// Returns which of the given accounts are under legal hold.
+    pub async fn legal_holds(&self, account_ids: AHashSet<u32>) -> crate::Result<AHashSet<u32>> {
+        let mut held = AHashSet::new();
+        for account_id in account_ids {
+            if self.legal_hold(account_id).await?.is_some() {
+                held.insert(account_id);
+            }
+        }
+        Ok(held)
+    }
+
+    pub async fn set_legal_hold(&self, account_id: u32, enable: bool) -> crate::Result<()> {
+        let mut batch = BatchBuilder::new();
+        let key = ValueClass::Directory(DirectoryClass::LegalHold(account_id));
+        if enable {
+            if self.legal_hold(account_id).await?.is_some() {
+                return Ok(());
+            }
+            batch.set(key, now().serialize());
+        } else {
+            batch.clear(key);
+        }
+        self.write(batch.build()).await.map(|_| ())
+    }
+
     pub async fn get_blob(
         &self,
         key: &[u8],
@@ -479,6 +588,20 @@ impl Store {
         use crate::{SUBSPACE_BLOBS, SUBSPACE_COUNTERS, SUBSPACE_VALUES};
 
         self.blob_expire_all().await;
+        self.delete_range(
+            ValueKey::from(ValueClass::DeletedItem { expires: 0, id: 0 }),
+            ValueKey {
+                account_id: u32::MAX,
+                collection: 0,
+                document_id: 0,
+                class: ValueClass::DeletedItem {
+                    expires: u64::MAX,
+                    id: u64::MAX,
+                },
+            },
+        )
+        .await
+        .unwrap();
         self.purge_blobs(blob_store).await.unwrap();
         self.purge_store().await.unwrap();
 
@@ -579,6 +702,10 @@ impl Store {
                             // Ignore named keys
                             return Ok(true);
                         }
+                        SUBSPACE_COUNTERS if [32, 33].contains(&key[0]) => {
+                            // Ignore domain usage counters
+                            return Ok(true);
+                        }
                         SUBSPACE_INDEXES => {
                             println!(
                                 concat!(
@@ -0,0 +1,528 @@
+/*
+ * Copyright (c) 2023, Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    io::{Cursor, Read},
+    time::{Duration, Instant},
+};
+
+use quick_xml::{events::Event, Reader};
+use zip::ZipArchive;
+
+#[derive(Debug, Clone)]
+pub struct ExtractLimits {
+    pub max_size: usize,
+    pub max_text: usize,
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentType {
+    Docx,
+    Xlsx,
+    Pptx,
+    OpenDocument,
+    Rtf,
+}
+
+struct Extractor {
+    text: String,
+    max_text: usize,
+    deadline: Instant,
+}
+
+impl DocumentType {
+    pub fn detect(content_type: Option<&str>, file_name: Option<&str>) -> Option<Self> {
+        if let Some(content_type) = content_type {
+            let typ = match content_type.to_ascii_lowercase().as_str() {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+                | "application/vnd.openxmlformats-officedocument.wordprocessingml.template"
+                | "application/vnd.ms-word.document.macroenabled.12" => Some(DocumentType::Docx),
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+                | "application/vnd.openxmlformats-officedocument.spreadsheetml.template"
+                | "application/vnd.ms-excel.sheet.macroenabled.12" => Some(DocumentType::Xlsx),
+                "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+                | "application/vnd.openxmlformats-officedocument.presentationml.slideshow"
+                | "application/vnd.ms-powerpoint.presentation.macroenabled.12" => {
+                    Some(DocumentType::Pptx)
+                }
+                "application/vnd.oasis.opendocument.text"
+                | "application/vnd.oasis.opendocument.spreadsheet"
+                | "application/vnd.oasis.opendocument.presentation" => {
+                    Some(DocumentType::OpenDocument)
+                }
+                "application/rtf" | "text/rtf" => Some(DocumentType::Rtf),
+                _ => None,
+            };
+            if typ.is_some() {
+                return typ;
+            }
+        }
+
+        // Many clients send attachments as application/octet-stream
+        match file_name?.rsplit_once('.')?.1.to_ascii_lowercase().as_str() {
+            "docx" | "docm" | "dotx" => Some(DocumentType::Docx),
+            "xlsx" | "xlsm" | "xltx" => Some(DocumentType::Xlsx),
+            "pptx" | "pptm" | "ppsx" => Some(DocumentType::Pptx),
+            "odt" | "ods" | "odp" => Some(DocumentType::OpenDocument),
+            "rtf" => Some(DocumentType::Rtf),
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the text of a document, stopping at the deadline so all the
+/// documents of a message share the same `timeout` budget.
+pub fn extract_text(
+    bytes: &[u8],
+    typ: DocumentType,
+    limits: &ExtractLimits,
+    deadline: Instant,
+) -> Option<String> {
+    if bytes.len() > limits.max_size || Instant::now() > deadline {
+        return None;
+    }
+
+    let mut extractor = Extractor {
+        text: String::new(),
+        max_text: limits.max_text,
+        deadline,
+    };
+
+    match typ {
+        DocumentType::Rtf => extractor.rtf(bytes),
+        _ => {
+            let mut archive = ZipArchive::new(Cursor::new(bytes)).ok()?;
+            let mut budget = limits.max_size;
+
+            for name in typ.entries(&archive) {
+                let mut file = archive.by_name(&name).ok()?;
+                if file.size() > budget as u64 {
+                    break;
+                }
+
+                // Never trust the declared size, compressed data can expand beyond it
+                let mut xml = Vec::with_capacity(file.size() as usize);
+                (&mut file).take(budget as u64).read_to_end(&mut xml).ok()?;
+                budget -= xml.len();
+
+                let has_more = match typ {
+                    DocumentType::Docx => extractor.xml(&xml, Some(b"t"), &[b"p"]),
+                    DocumentType::Xlsx => extractor.xml(&xml, Some(b"t"), &[b"si", b"c"]),
+                    DocumentType::Pptx => extractor.xml(&xml, Some(b"t"), &[b"p"]),
+                    _ => extractor.xml(&xml, None, &[b"p", b"h"]),
+                };
+                if !has_more {
+                    break;
+                }
+            }
+        }
+    }
+
+    let text = extractor.text.trim();
+    if !text.is_empty() {
+        Some(text.to_string())
+    } else {
+        None
+    }
+}
+
+impl DocumentType {
+    fn entries(&self, archive: &ZipArchive<Cursor<&[u8]>>) -> Vec<String> {
+        let (prefix, fixed): (&str, &[&str]) = match self {
+            DocumentType::Docx => (
+                "word/header",
+                &[
+                    "word/document.xml",
+                    "word/footnotes.xml",
+                    "word/endnotes.xml",
+                ],
+            ),
+            DocumentType::Xlsx => ("xl/worksheets/sheet", &["xl/sharedStrings.xml"]),
+            DocumentType::Pptx => ("ppt/slides/slide", &[]),
+            DocumentType::OpenDocument => ("", &["content.xml"]),
+            DocumentType::Rtf => ("", &[]),
+        };
+
+        let mut entries = fixed
+            .iter()
+            .filter(|name| archive.file_names().any(|n| n == **name))
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        if !prefix.is_empty() {
+            // Sort numbered parts (i.e. slide2.xml before slide10.xml)
+            let mut numbered = archive
+                .file_names()
+                .filter_map(|name| {
+                    name.strip_prefix(prefix)?
+                        .strip_suffix(".xml")?
+                        .parse::<u32>()
+                        .ok()
+                        .map(|num| (num, name.to_string()))
+                })
+                .collect::<Vec<_>>();
+            numbered.sort_unstable();
+            entries.extend(numbered.into_iter().map(|(_, name)| name));
+        }
+
+        entries
+    }
+}
+
+impl Extractor {
+    // Returns false when a limit has been reached
+    fn xml(&mut self, xml: &[u8], text_tag: Option<&[u8]>, break_tags: &[&[u8]]) -> bool {
+        let mut reader = Reader::from_reader(xml);
+        let mut buf = Vec::new();
+        let mut in_text = text_tag.is_none();
+
+        loop {
+            if Instant::now() > self.deadline {
+                return false;
+            }
+
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(e)) => {
+                    if text_tag.is_some_and(|tag| e.local_name().as_ref() == tag) {
+                        in_text = true;
+                    }
+                }
+                Ok(Event::End(e)) => {
+                    let name = e.local_name();
+                    if text_tag.is_some_and(|tag| name.as_ref() == tag) {
+                        in_text = false;
+                    } else if break_tags.contains(&name.as_ref()) && !self.push("\n") {
+                        return false;
+                    }
+                }
+                Ok(Event::Empty(e)) => {
+                    let separator = match e.local_name().as_ref() {
+                        b"tab" | b"s" => " ",
+                        b"br" | b"cr" | b"line-break" => "\n",
+                        _ => "",
+                    };
+                    if !separator.is_empty() && !self.push(separator) {
+                        return false;
+                    }
+                }
+                Ok(Event::Text(e)) if in_text => {
+                    if let Ok(text) = e.unescape() {
+                        if !self.push(text.as_ref()) {
+                            return false;
+                        }
+                    }
+                }
+                Ok(Event::Eof) => return true,
+                Err(_) => return true,
+                _ => (),
+            }
+
+            buf.clear();
+        }
+    }
+
+    fn rtf(&mut self, bytes: &[u8]) {
+        if !bytes.starts_with(b"{\\rtf") {
+            return;
+        }
+
+        let mut stack = Vec::new();
+        let mut skip = false;
+        let mut uc = 1;
+        let mut skip_chars = 0;
+        let mut iter = bytes.iter().peekable();
+
+        while let Some(&ch) = iter.next() {
+            if Instant::now() > self.deadline {
+                return;
+            }
+
+            let text = match ch {
+                b'{' => {
+                    stack.push((skip, uc));
+                    continue;
+                }
+                b'}' => {
+                    (skip, uc) = stack.pop().unwrap_or((false, 1));
+                    continue;
+                }
+                b'\r' | b'\n' => continue,
+                b'\\' => match iter.next() {
+                    Some(b'*') => {
+                        skip = true;
+                        continue;
+                    }
+                    Some(b'\'') => {
+                        let hex = [
+                            iter.next().copied().unwrap_or_default(),
+                            iter.next().copied().unwrap_or_default(),
+                        ];
+                        if skip_chars > 0 {
+                            skip_chars -= 1;
+                            continue;
+                        }
+                        match std::str::from_utf8(&hex)
+                            .ok()
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        {
+                            Some(byte) => char::from(byte),
+                            None => continue,
+                        }
+                    }
+                    Some(b'\r' | b'\n') => '\n',
+                    Some(b'~') => ' ',
+                    Some(&ch @ (b'\\' | b'{' | b'}')) => char::from(ch),
+                    Some(ch) if ch.is_ascii_alphabetic() => {
+                        let mut word = vec![*ch];
+                        while let Some(ch) = iter.next_if(|ch| ch.is_ascii_alphabetic()) {
+                            word.push(*ch);
+                        }
+                        let mut param = Vec::new();
+                        if let Some(ch) = iter.next_if(|ch| **ch == b'-') {
+                            param.push(*ch);
+                        }
+                        while let Some(ch) = iter.next_if(|ch| ch.is_ascii_digit()) {
+                            param.push(*ch);
+                        }
+                        iter.next_if(|ch| **ch == b' ');
+                        let param = std::str::from_utf8(&param)
+                            .ok()
+                            .and_then(|param| param.parse::<i32>().ok());
+
+                        match word.as_slice() {
+                            b"par" | b"line" | b"row" | b"sect" | b"page" => '\n',
+                            b"tab" | b"cell" => ' ',
+                            b"uc" => {
+                                uc = param.unwrap_or(1).max(0);
+                                continue;
+                            }
+                            b"u" => {
+                                skip_chars = uc;
+                                match param
+                                    .map(|param| if param < 0 { param + 65536 } else { param })
+                                    .and_then(|param| char::from_u32(param as u32))
+                                {
+                                    Some(ch) => ch,
+                                    None => continue,
+                                }
+                            }
+                            b"fonttbl" | b"colortbl" | b"stylesheet" | b"info" | b"pict"
+                            | b"object" | b"themedata" | b"datastore" | b"listtable"
+                            | b"listoverridetable" | b"rsidtbl" | b"generator"
+                            | b"latentstyles" | b"filetbl" | b"revtbl" | b"xmlnstbl" => {
+                                skip = true;
+                                continue;
+                            }
+                            _ => continue,
+                        }
+                    }
+                    _ => continue,
+                },
+                _ => {
+                    if skip_chars > 0 {
+                        skip_chars -= 1;
+                        continue;
+                    }
+                    char::from(ch)
+                }
+            };
+
+            if !skip && !self.push(text.encode_utf8(&mut [0; 4])) {
+                return;
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str) -> bool {
+        let remaining = self.max_text.saturating_sub(self.text.len());
+        if text.len() <= remaining {
+            self.text.push_str(text);
+            true
+        } else {
+            let mut end = remaining;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.text.push_str(&text[..end]);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    fn limits() -> ExtractLimits {
+        ExtractLimits {
+            max_size: 1024 * 1024,
+            max_text: 1024,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    fn deadline() -> Instant {
+        Instant::now() + limits().timeout
+    }
+
+    fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn extract_office_text() {
+        for (typ, file, expected) in [
+            (
+                DocumentType::Docx,
+                zip(&[(
+                    "word/document.xml",
+                    concat!(
+                        "<w:document><w:body><w:p><w:r><w:t>Hello</w:t></w:r>",
+                        "<w:r><w:tab/><w:t>world &amp; friends</w:t></w:r></w:p>",
+                        "<w:p><w:r><w:t>Second</w:t></w:r></w:p></w:body></w:document>"
+                    ),
+                )]),
+                "Hello world & friends\nSecond",
+            ),
+            (
+                DocumentType::Xlsx,
+                zip(&[
+                    (
+                        "xl/sharedStrings.xml",
+                        "<sst><si><t>Revenue</t></si><si><t>Costs</t></si></sst>",
+                    ),
+                    (
+                        "xl/worksheets/sheet1.xml",
+                        "<worksheet><c><v>0</v></c><c t=\"inlineStr\"><is><t>Inline</t></is></c></worksheet>",
+                    ),
+                ]),
+                "Revenue\nCosts\n\nInline",
+            ),
+            (
+                DocumentType::Pptx,
+                zip(&[
+                    ("ppt/slides/slide10.xml", "<p:sld><a:p><a:t>Last</a:t></a:p></p:sld>"),
+                    ("ppt/slides/slide2.xml", "<p:sld><a:p><a:t>First</a:t></a:p></p:sld>"),
+                ]),
+                "First\nLast",
+            ),
+            (
+                DocumentType::OpenDocument,
+                zip(&[(
+                    "content.xml",
+                    concat!(
+                        "<office:document-content><office:body><office:text>",
+                        "<text:h>Title</text:h><text:p>Some<text:s/>text</text:p>",
+                        "</office:text></office:body></office:document-content>"
+                    ),
+                )]),
+                "Title\nSome text",
+            ),
+            (
+                DocumentType::Rtf,
+                concat!(
+                    "{\\rtf1\\ansi{\\fonttbl{\\f0 Times;}}{\\*\\generator Writer;}",
+                    "\\f0 Caf\\'e9 con leche\\par \\uc1\\u8364? 10 {\\b bold}}"
+                )
+                .as_bytes()
+                .to_vec(),
+                "Café con leche\n€ 10 bold",
+            ),
+        ] {
+            assert_eq!(
+                extract_text(&file, typ, &limits(), deadline()).unwrap(),
+                expected,
+                "failed for {typ:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn extract_limits() {
+        let file = zip(&[(
+            "word/document.xml",
+            "<w:document><w:p><w:t>0123456789</w:t></w:p></w:document>",
+        )]);
+
+        // Text is truncated
+        assert_eq!(
+            extract_text(
+                &file,
+                DocumentType::Docx,
+                &ExtractLimits {
+                    max_text: 4,
+                    ..limits()
+                },
+                deadline()
+            )
+            .unwrap(),
+            "0123"
+        );
+
+        // Oversized files are skipped
+        assert_eq!(
+            extract_text(
+                &file,
+                DocumentType::Docx,
+                &ExtractLimits {
+                    max_size: 10,
+                    ..limits()
+                },
+                deadline()
+            ),
+            None
+        );
+
+        // Nothing is extracted once the deadline has passed
+        assert_eq!(
+            extract_text(
+                &file,
+                DocumentType::Docx,
+                &limits(),
+                Instant::now() - Duration::from_secs(1)
+            ),
+            None
+        );
+
+        // Detection
+        assert_eq!(
+            DocumentType::detect(Some("application/octet-stream"), Some("Report.DOCX")),
+            Some(DocumentType::Docx)
+        );
+        assert_eq!(
+            DocumentType::detect(Some("application/vnd.oasis.opendocument.text"), None),
+            Some(DocumentType::OpenDocument)
+        );
+        assert_eq!(DocumentType::detect(Some("image/png"), Some("a.png")), None);
+    }
+}
@@ -32,7 +32,7 @@ pub mod write;
 
 pub use ahash;
 use ahash::AHashMap;
-use backend::fs::FsStore;
+use backend::{fs::FsStore, tiered::TieredStore};
 pub use blake3;
 pub use parking_lot;
 pub use rand;
@@ -60,6 +60,9 @@ use backend::rocksdb::RocksDbStore;
 #[cfg(feature = "elastic")]
 use backend::elastic::ElasticSearchStore;
 
+#[cfg(feature = "tantivy")]
+use backend::tantivy::TantivyStore;
+
 #[cfg(feature = "redis")]
 use backend::redis::RedisStore;
 
@@ -225,6 +228,7 @@ pub enum BlobBackend {
     Fs(Arc<FsStore>),
     #[cfg(feature = "s3")]
     S3(Arc<S3Store>),
+    Tiered(Arc<TieredStore>),
 }
 
 #[derive(Clone)]
@@ -232,6 +236,8 @@ pub enum FtsStore {
     Store(Store),
     #[cfg(feature = "elastic")]
     ElasticSearch(Arc<ElasticSearchStore>),
+    #[cfg(feature = "tantivy")]
+    Tantivy(Arc<TantivyStore>),
 }
 
 #[derive(Clone)]
@@ -301,6 +307,15 @@ impl From<S3Store> for BlobStore {
     }
 }
 
+impl From<TieredStore> for BlobStore {
+    fn from(store: TieredStore) -> Self {
+        BlobStore {
+            backend: BlobBackend::Tiered(Arc::new(store)),
+            compression: CompressionAlgo::None,
+        }
+    }
+}
+
 #[cfg(feature = "elastic")]
 impl From<ElasticSearchStore> for FtsStore {
     fn from(store: ElasticSearchStore) -> Self {
@@ -308,6 +323,13 @@ impl From<ElasticSearchStore> for FtsStore {
     }
 }
 
+#[cfg(feature = "tantivy")]
+impl From<TantivyStore> for FtsStore {
+    fn from(store: TantivyStore) -> Self {
+        Self::Tantivy(Arc::new(store))
+    }
+}
+
 #[cfg(feature = "redis")]
 impl From<RedisStore> for LookupStore {
     fn from(store: RedisStore) -> Self {
@@ -54,6 +54,40 @@ impl Store {
         .map(|v| v.is_some())
     }
 
+    pub async fn blob_is_linked(
+        &self,
+        hash: impl AsRef<BlobHash> + Sync + Send,
+    ) -> crate::Result<bool> {
+        let from_key = ValueKey {
+            account_id: 0,
+            collection: 0,
+            document_id: 0,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: hash.as_ref().clone(),
+            }),
+        };
+        let to_key = ValueKey {
+            account_id: u32::MAX,
+            collection: u8::MAX,
+            document_id: u32::MAX,
+            class: ValueClass::Blob(BlobOp::Link {
+                hash: hash.as_ref().clone(),
+            }),
+        };
+        let mut is_linked = false;
+        self.iterate(
+            IterateParams::new(from_key, to_key).ascending().no_values(),
+            |key, _| {
+                // The commit marker is stored under the same prefix, skip it
+                is_linked = key.deserialize_be_u32(key.len() - U32_LEN)? != u32::MAX;
+                Ok(!is_linked)
+            },
+        )
+        .await?;
+
+        Ok(is_linked)
+    }
+
     pub async fn blob_quota(&self, account_id: u32) -> crate::Result<BlobQuota> {
         let from_key = ValueKey {
             account_id,
@@ -183,6 +217,18 @@ impl Store {
         )
         .await?;
 
+        // Blobs reserved by accounts under legal hold are kept until the hold is lifted
+        let held = self
+            .legal_holds(delete_keys.iter().map(|key| key.account_id).collect())
+            .await?;
+        delete_keys.retain(|key| match &key.class {
+            ValueClass::Blob(BlobOp::Reserve { hash, .. }) if held.contains(&key.account_id) => {
+                active_hashes.insert(hash.clone());
+                false
+            }
+            _ => true,
+        });
+
         // Validate linked blobs
         let from_key = ValueKey {
             account_id: 0,
@@ -31,8 +31,8 @@ use crate::{
 };
 
 use super::{
-    AnyKey, BitmapClass, BlobOp, DirectoryClass, LookupClass, QueueClass, ReportClass, ReportEvent,
-    TagValue, ValueClass,
+    AnyKey, BitmapClass, BlobOp, DirectoryClass, ListClass, LookupClass, QueueClass, ReportClass,
+    ReportEvent, TagValue, ValueClass,
 };
 
 pub struct KeySerializer {
@@ -172,7 +172,11 @@ impl<T: AsRef<ValueClass>> ValueKey<T> {
 
     pub fn is_counter(&self) -> bool {
         match self.class.as_ref() {
-            ValueClass::Directory(DirectoryClass::UsedQuota(_))
+            ValueClass::Directory(
+                DirectoryClass::UsedQuota(_)
+                | DirectoryClass::DomainUsedQuota(_)
+                | DirectoryClass::DomainAccounts(_),
+            )
             | ValueClass::Lookup(LookupClass::Counter(_))
             | ValueClass::Queue(QueueClass::QuotaCount(_) | QueueClass::QuotaSize(_)) => true,
             ValueClass::Property(84) if self.collection == 1 => true, // TODO: Find a more elegant way to do this
@@ -290,6 +294,7 @@ impl<T: AsRef<ValueClass> + Sync + Send> Key for ValueKey<T> {
                     .write(self.account_id)
                     .write(self.collection)
                     .write(self.document_id),
+                BlobOp::Tier { hash } => serializer.write(11u8).write::<&[u8]>(hash.as_ref()),
             },
             ValueClass::Config(key) => serializer.write(8u8).write(key.as_slice()),
             ValueClass::Lookup(lookup) => match lookup {
@@ -303,6 +308,19 @@ impl<T: AsRef<ValueClass> + Sync + Send> Key for ValueKey<T> {
                 DirectoryClass::Principal(uid) => serializer.write(22u8).write_leb128(*uid),
                 DirectoryClass::Domain(name) => serializer.write(23u8).write(name.as_slice()),
                 DirectoryClass::UsedQuota(uid) => serializer.write(24u8).write_leb128(*uid),
+                DirectoryClass::PasswordHistory(uid) => serializer.write(27u8).write_leb128(*uid),
+                DirectoryClass::DomainSettings(name) => {
+                    serializer.write(28u8).write(name.as_slice())
+                }
+                DirectoryClass::Retention(uid) => serializer.write(29u8).write_leb128(*uid),
+                DirectoryClass::LegalHold(uid) => serializer.write(30u8).write_leb128(*uid),
+                DirectoryClass::Disabled(uid) => serializer.write(31u8).write_leb128(*uid),
+                DirectoryClass::DomainUsedQuota(name) => {
+                    serializer.write(32u8).write(name.as_slice())
+                }
+                DirectoryClass::DomainAccounts(name) => {
+                    serializer.write(33u8).write(name.as_slice())
+                }
                 DirectoryClass::MemberOf {
                     principal_id,
                     member_of,
@@ -364,6 +382,31 @@ impl<T: AsRef<ValueClass> + Sync + Send> Key for ValueKey<T> {
                     serializer.write(62u8).write(*expires).write(*id)
                 }
             },
+            ValueClass::Audit(id) => serializer.write(70u8).write(*id),
+            ValueClass::List(list) => match list {
+                ListClass::Settings(list_id) => serializer.write(80u8).write(*list_id),
+                ListClass::Subscriber { list_id, address } => serializer
+                    .write(81u8)
+                    .write(*list_id)
+                    .write(address.as_slice()),
+                ListClass::Pending(token) => serializer.write(82u8).write(token.as_slice()),
+                ListClass::Held { list_id, id } => {
+                    serializer.write(83u8).write(*list_id).write(*id)
+                }
+                ListClass::Outbox { list_id, id } => {
+                    serializer.write(84u8).write(*list_id).write(*id)
+                }
+            },
+            ValueClass::DeletedItem { expires, id } => serializer
+                .write(90u8)
+                .write(self.account_id)
+                .write(*expires)
+                .write(*id),
+            ValueClass::UploadSession { expires, id } => serializer
+                .write(91u8)
+                .write(self.account_id)
+                .write(*expires)
+                .write(*id),
         }
         .finalize()
     }
@@ -499,13 +542,22 @@ impl ValueClass {
             ValueClass::Directory(d) => match d {
                 DirectoryClass::NameToId(v)
                 | DirectoryClass::EmailToId(v)
-                | DirectoryClass::Domain(v) => v.len(),
-                DirectoryClass::Principal(_) | DirectoryClass::UsedQuota(_) => U32_LEN,
+                | DirectoryClass::Domain(v)
+                | DirectoryClass::DomainSettings(v)
+                | DirectoryClass::DomainUsedQuota(v)
+                | DirectoryClass::DomainAccounts(v) => v.len(),
+                DirectoryClass::Principal(_)
+                | DirectoryClass::UsedQuota(_)
+                | DirectoryClass::PasswordHistory(_)
+                | DirectoryClass::Retention(_)
+                | DirectoryClass::LegalHold(_)
+                | DirectoryClass::Disabled(_) => U32_LEN,
                 DirectoryClass::Members { .. } | DirectoryClass::MemberOf { .. } => U32_LEN * 2,
             },
             ValueClass::Blob(op) => match op {
                 BlobOp::Reserve { .. } => BLOB_HASH_LEN + U64_LEN + U32_LEN + 1,
                 BlobOp::Commit { .. } | BlobOp::Link { .. } => BLOB_HASH_LEN + U32_LEN * 2 + 2,
+                BlobOp::Tier { .. } => BLOB_HASH_LEN + 1,
             },
             ValueClass::IndexEmail { .. } => U64_LEN * 2,
             ValueClass::Queue(q) => match q {
@@ -520,6 +572,16 @@ impl ValueClass {
                 QueueClass::QuotaCount(v) | QueueClass::QuotaSize(v) => v.len(),
             },
             ValueClass::Report(_) => U64_LEN * 2 + 1,
+            ValueClass::Audit(_) => U64_LEN + 1,
+            ValueClass::List(list) => match list {
+                ListClass::Settings(_) => U32_LEN + 1,
+                ListClass::Subscriber { address, .. } => U32_LEN + address.len() + 1,
+                ListClass::Pending(token) => token.len() + 1,
+                ListClass::Held { .. } | ListClass::Outbox { .. } => U32_LEN + U64_LEN + 1,
+            },
+            ValueClass::DeletedItem { .. } | ValueClass::UploadSession { .. } => {
+                U32_LEN + U64_LEN * 2 + 1
+            }
         }
     }
 }
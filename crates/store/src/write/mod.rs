@@ -141,6 +141,10 @@ pub enum ValueClass {
     Config(Vec<u8>),
     Queue(QueueClass),
     Report(ReportClass),
+    Audit(u64),
+    List(ListClass),
+    DeletedItem { expires: u64, id: u64 },
+    UploadSession { expires: u64, id: u64 },
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -150,6 +154,15 @@ pub enum LookupClass {
     CounterExpiry(Vec<u8>),
 }
 
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub enum ListClass {
+    Settings(u32),
+    Subscriber { list_id: u32, address: Vec<u8> },
+    Pending(Vec<u8>),
+    Held { list_id: u32, id: u64 },
+    Outbox { list_id: u32, id: u64 },
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub enum DirectoryClass {
     NameToId(Vec<u8>),
@@ -159,6 +172,13 @@ pub enum DirectoryClass {
     Domain(Vec<u8>),
     Principal(u32),
     UsedQuota(u32),
+    PasswordHistory(u32),
+    DomainSettings(Vec<u8>),
+    Retention(u32),
+    LegalHold(u32),
+    Disabled(u32),
+    DomainUsedQuota(Vec<u8>),
+    DomainAccounts(Vec<u8>),
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -208,6 +228,7 @@ pub enum BlobOp {
     Reserve { hash: BlobHash, until: u64 },
     Commit { hash: BlobHash },
     Link { hash: BlobHash },
+    Tier { hash: BlobHash },
 }
 
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
@@ -21,18 +21,19 @@
  * for more details.
 */
 
-use std::fmt::Display;
+use std::{fmt::Display, sync::Arc};
 
 use tokio::sync::watch;
 use utils::config::cron::SimpleCron;
 
-use crate::{BlobStore, LookupStore, Store};
+use crate::{backend::tiered::TieredStore, BlobStore, LookupStore, Store};
 
 #[derive(Clone)]
 pub enum PurgeStore {
     Data(Store),
     Blobs { store: Store, blob_store: BlobStore },
     Lookup(LookupStore),
+    Migrate(Arc<TieredStore>),
 }
 
 #[derive(Clone)]
@@ -69,6 +70,14 @@ impl PurgeSchedule {
                         store.purge_blobs(blob_store.clone()).await
                     }
                     PurgeStore::Lookup(store) => store.purge_lookup_store().await,
+                    PurgeStore::Migrate(store) => store.migrate_blobs().await.map(|result| {
+                        tracing::debug!(
+                            "Migrated {} blobs to cold storage for store {:?} ({} missing).",
+                            result.migrated,
+                            self.store_id,
+                            result.missing
+                        );
+                    }),
                 };
 
                 if let Err(err) = result {
@@ -90,6 +99,7 @@ impl Display for PurgeStore {
             PurgeStore::Data(_) => write!(f, "bitmaps"),
             PurgeStore::Blobs { .. } => write!(f, "blobs"),
             PurgeStore::Lookup(_) => write!(f, "expired keys"),
+            PurgeStore::Migrate(_) => write!(f, "blob migration"),
         }
     }
 }
@@ -50,7 +50,7 @@ use crate::{
 };
 
 use super::{
-    tls::{TLS12_VERSION, TLS13_VERSION},
+    tls::{build_client_verifier, TLS12_VERSION, TLS13_VERSION},
     utils::{AsKey, ParseKey, ParseValue},
     Config, Listener, Server, ServerProtocol, Servers,
 };
@@ -280,8 +280,10 @@ impl Config {
                 provider.cipher_suites = ciphers;
             }
 
+            let provider = Arc::new(provider);
+
             // Build server config
-            let mut config = ServerConfig::builder_with_provider(provider.into())
+            let config = ServerConfig::builder_with_provider(provider.clone())
                 .with_protocol_versions(if tls_v3 == tls_v2 {
                     ALL_VERSIONS
                 } else if tls_v3 {
@@ -289,9 +291,26 @@ impl Config {
                 } else {
                     TLS12_VERSION
                 })
-                .map_err(|err| format!("Failed to build TLS config: {err}"))?
-                .with_no_client_auth()
-                .with_cert_resolver(resolver.clone());
+                .map_err(|err| format!("Failed to build TLS config: {err}"))?;
+            let mut config = if let Some(ca) = self.value_or_else(
+                ("server.listener", id, "tls.client-auth.ca"),
+                "server.tls.client-auth.ca",
+            ) {
+                // Verify client certificates issued by the configured CAs
+                config.with_client_cert_verifier(build_client_verifier(
+                    ca.as_bytes(),
+                    self.property_or_else(
+                        ("server.listener", id, "tls.client-auth.required"),
+                        "server.tls.client-auth.required",
+                    )?
+                    .unwrap_or(false),
+                    provider,
+                    id,
+                )?)
+            } else {
+                config.with_no_client_auth()
+            }
+            .with_cert_resolver(resolver.clone());
             config.ignore_client_order = self
                 .property_or_else(
                     ("server.listener", id, "tls.ignore-client-order"),
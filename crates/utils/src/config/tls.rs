@@ -27,10 +27,11 @@ use ahash::AHashMap;
 use arc_swap::ArcSwap;
 use rcgen::generate_simple_self_signed;
 use rustls::{
-    crypto::ring::sign::any_supported_type,
+    crypto::{ring::sign::any_supported_type, CryptoProvider},
+    server::{danger::ClientCertVerifier, WebPkiClientVerifier},
     sign::CertifiedKey,
     version::{TLS12, TLS13},
-    SupportedProtocolVersion,
+    RootCertStore, SupportedProtocolVersion,
 };
 use rustls_pemfile::{certs, read_one, Item};
 use rustls_pki_types::PrivateKeyDer;
@@ -169,6 +170,36 @@ pub(crate) fn build_certified_key(
     })
 }
 
+pub(crate) fn build_client_verifier(
+    ca: &[u8],
+    is_required: bool,
+    provider: Arc<CryptoProvider>,
+    id: &str,
+) -> super::Result<Arc<dyn ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in certs(&mut Cursor::new(ca)) {
+        roots
+            .add(cert.map_err(|err| {
+                format!("Failed to read client CA certificates for listener {id:?}: {err}")
+            })?)
+            .map_err(|err| format!("Invalid client CA certificate for listener {id:?}: {err}"))?;
+    }
+    if roots.is_empty() {
+        return Err(format!(
+            "No client CA certificates found for listener {id:?}."
+        ));
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    if is_required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    }
+    .build()
+    .map_err(|err| format!("Failed to build client verifier for listener {id:?}: {err}"))
+}
+
 pub(crate) fn build_self_signed_cert(domains: &[String]) -> super::Result<CertifiedKey> {
     let cert = generate_simple_self_signed(domains).map_err(|err| {
         format!(
@@ -42,6 +42,8 @@ pub struct IngestMessage {
     pub recipients: Vec<String>,
     pub message_blob: BlobHash,
     pub message_size: usize,
+    pub sender_authenticated: bool,
+    pub author_authenticated: bool,
 }
 
 #[derive(Debug, Clone)]
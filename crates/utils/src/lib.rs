@@ -36,6 +36,7 @@ pub mod map;
 pub mod snowflake;
 pub mod suffixlist;
 pub mod url_params;
+pub mod webhooks;
 
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
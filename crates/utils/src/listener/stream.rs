@@ -40,6 +40,14 @@ impl SessionStream for TcpStream {
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
         (Cow::Borrowed(""), Cow::Borrowed(""))
     }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn tls_client_certificate(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl<T: SessionStream> SessionStream for TlsStream<T> {
@@ -79,6 +87,26 @@ impl<T: SessionStream> SessionStream for TlsStream<T> {
             .into(),
         )
     }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        let (_, conn) = self.get_ref();
+
+        // The tls-exporter channel binding (RFC 9266) is only safe to use with TLS 1.3
+        if conn.protocol_version() == Some(rustls::ProtocolVersion::TLSv1_3) {
+            conn.export_keying_material(vec![0u8; 32], b"EXPORTER-Channel-Binding", Some(b""))
+                .ok()
+        } else {
+            None
+        }
+    }
+
+    fn tls_client_certificate(&self) -> Option<Vec<u8>> {
+        self.get_ref()
+            .1
+            .peer_certificates()
+            .and_then(|certs| certs.first())
+            .map(|cert| cert.as_ref().to_vec())
+    }
 }
 
 impl SessionStream for ProxiedStream<TcpStream> {
@@ -99,6 +127,14 @@ impl SessionStream for ProxiedStream<TcpStream> {
             })
             .unwrap_or((Cow::Borrowed("unknown"), Cow::Borrowed("unknown")))
     }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn tls_client_certificate(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[derive(Default)]
@@ -157,4 +193,12 @@ impl SessionStream for NullIo {
             std::borrow::Cow::Borrowed(""),
         )
     }
+
+    fn tls_channel_binding(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn tls_client_certificate(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
@@ -39,6 +39,7 @@ use rustls::{
 use rustls_pki_types::{DnsName, ServerName};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio_rustls::{Accept, LazyConfigAcceptor, TlsAcceptor};
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
 
 use crate::{acme::resolver::IsTlsAlpnChallenge, config::tls::build_certified_key};
 
@@ -190,6 +191,34 @@ impl Certificate {
     }
 }
 
+/// Returns the identities of a verified client certificate, the e-mail
+/// addresses in its subject alternative names followed by its subject.
+pub fn certificate_identities(der: &[u8]) -> Vec<String> {
+    let mut identities = Vec::new();
+    if let Ok((_, cert)) = parse_x509_certificate(der) {
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            for name in &san.value.general_names {
+                if let GeneralName::RFC822Name(email) = name {
+                    identities.push(email.to_lowercase());
+                }
+            }
+        }
+        for attr in cert
+            .subject()
+            .iter_email()
+            .chain(cert.subject().iter_common_name())
+        {
+            if let Ok(value) = attr.as_str() {
+                let value = value.to_lowercase();
+                if !identities.contains(&value) {
+                    identities.push(value);
+                }
+            }
+        }
+    }
+    identities
+}
+
 impl std::fmt::Debug for CertificateResolver {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("CertificateResolver")
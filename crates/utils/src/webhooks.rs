@@ -0,0 +1,286 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{net::IpAddr, time::Duration};
+
+use ahash::AHashSet;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::hmac;
+use serde::{Serialize, Serializer};
+use tokio::sync::mpsc;
+
+use crate::{
+    config::{
+        utils::{AsKey, ParseValue},
+        Config,
+    },
+    snowflake::SnowflakeIdGenerator,
+};
+
+/// Outbound webhooks configured by the administrator. Events are handed over
+/// to the webhook manager, which batches, signs and delivers them.
+pub struct Webhooks {
+    pub hooks: Vec<Webhook>,
+    pub events: AHashSet<WebhookType>,
+    pub tx: mpsc::Sender<WebhookEvent>,
+    pub id_generator: SnowflakeIdGenerator,
+}
+
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub events: AHashSet<WebhookType>,
+    pub signature_key: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub auth: Option<(String, String)>,
+    pub tls_allow_invalid_certs: bool,
+    pub timeout: Duration,
+    pub throttle: Duration,
+    pub attempts_max: u32,
+    pub attempts_interval: Duration,
+    pub buffer_max: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookType {
+    MessageIngested,
+    DeliverySuccess,
+    DeliveryDelayed,
+    DeliveryFailure,
+    ReportDmarc,
+    ReportTls,
+    ReportArf,
+    AuthFailure,
+    PrincipalCreated,
+    PrincipalUpdated,
+    PrincipalDeleted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookEvent {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub typ: WebhookType,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    pub data: WebhookPayload,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum WebhookPayload {
+    MessageIngested {
+        #[serde(rename = "accountId")]
+        account_id: u32,
+        #[serde(rename = "emailId")]
+        email_id: String,
+        #[serde(rename = "blobId")]
+        blob_id: String,
+        size: usize,
+        sender: String,
+        recipient: String,
+        headers: Vec<(String, String)>,
+    },
+    Delivery {
+        #[serde(rename = "queueId")]
+        queue_id: u64,
+        sender: String,
+        recipient: String,
+        status: String,
+    },
+    Report {
+        from: String,
+        to: Vec<String>,
+        subject: String,
+        report: serde_json::Value,
+    },
+    AuthFailure {
+        login: Option<String>,
+        #[serde(rename = "remoteIp")]
+        remote_ip: Option<IpAddr>,
+    },
+    Principal {
+        name: Option<String>,
+        actor: Option<String>,
+        #[serde(rename = "remoteIp")]
+        remote_ip: Option<IpAddr>,
+        changes: Option<serde_json::Value>,
+    },
+}
+
+impl Webhooks {
+    pub fn parse(config: &Config, tx: mpsc::Sender<WebhookEvent>) -> crate::config::Result<Self> {
+        let mut hooks = Vec::new();
+        let mut events = AHashSet::new();
+
+        for id in config.sub_keys("webhook", ".url") {
+            let hook_events = config
+                .properties::<WebhookType>(("webhook", id, "events"))
+                .map(|result| result.map(|(_, typ)| typ))
+                .collect::<crate::config::Result<AHashSet<_>>>()?;
+            if hook_events.is_empty() {
+                return Err(format!("No events configured for webhook {id:?}."));
+            }
+            events.extend(hook_events.iter().copied());
+
+            hooks.push(Webhook {
+                id: id.to_string(),
+                url: config.value_require(("webhook", id, "url"))?.to_string(),
+                events: hook_events,
+                signature_key: config
+                    .value(("webhook", id, "signature-key"))
+                    .map(|key| key.to_string()),
+                headers: config
+                    .values(("webhook", id, "headers"))
+                    .map(|(key, header)| {
+                        header
+                            .split_once(':')
+                            .map(|(name, value)| {
+                                (name.trim().to_string(), value.trim().to_string())
+                            })
+                            .ok_or_else(|| {
+                                format!("Invalid HTTP header {header:?} for key {key:?}.")
+                            })
+                    })
+                    .collect::<crate::config::Result<Vec<_>>>()?,
+                auth: config
+                    .value(("webhook", id, "auth.username"))
+                    .map(|username| {
+                        (
+                            username.to_string(),
+                            config
+                                .value(("webhook", id, "auth.secret"))
+                                .unwrap_or_default()
+                                .to_string(),
+                        )
+                    }),
+                tls_allow_invalid_certs: config
+                    .property_or_default(("webhook", id, "allow-invalid-certs"), "false")?,
+                timeout: config.property_or_default(("webhook", id, "timeout"), "30s")?,
+                throttle: config.property_or_default(("webhook", id, "throttle"), "1s")?,
+                attempts_max: config.property_or_default(("webhook", id, "attempts.max"), "5")?,
+                attempts_interval: config
+                    .property_or_default(("webhook", id, "attempts.interval"), "1m")?,
+                buffer_max: config.property_or_default(("webhook", id, "buffer.max"), "1000")?,
+            });
+        }
+
+        Ok(Webhooks {
+            hooks,
+            events,
+            tx,
+            id_generator: SnowflakeIdGenerator::new(),
+        })
+    }
+
+    pub fn is_enabled(&self, typ: WebhookType) -> bool {
+        self.events.contains(&typ)
+    }
+
+    /// Queues an event for delivery. The payload is only built when at least
+    /// one webhook is subscribed to the event type.
+    pub fn send(&self, typ: WebhookType, payload: impl FnOnce() -> WebhookPayload) {
+        if !self.is_enabled(typ) {
+            return;
+        }
+
+        let event = WebhookEvent {
+            id: self.id_generator.generate().unwrap_or_default(),
+            typ,
+            created_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            data: payload(),
+        };
+        if let Err(err) = self.tx.try_send(event) {
+            tracing::warn!(
+                context = "webhook",
+                event = "error",
+                typ = typ.as_str(),
+                reason = %err,
+                "Failed to queue webhook event."
+            );
+        }
+    }
+}
+
+impl Webhook {
+    /// Returns the base64 encoded HMAC-SHA256 signature of the request timestamp
+    /// followed by a dot and the request body, so that receivers can reject replays.
+    pub fn sign(&self, timestamp: u64, body: &[u8]) -> Option<String> {
+        self.signature_key.as_ref().map(|key| {
+            let mut ctx =
+                hmac::Context::with_key(&hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes()));
+            ctx.update(timestamp.to_string().as_bytes());
+            ctx.update(b".");
+            ctx.update(body);
+            STANDARD.encode(ctx.sign())
+        })
+    }
+}
+
+impl WebhookType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookType::MessageIngested => "message.ingested",
+            WebhookType::DeliverySuccess => "delivery.success",
+            WebhookType::DeliveryDelayed => "delivery.delayed",
+            WebhookType::DeliveryFailure => "delivery.failure",
+            WebhookType::ReportDmarc => "report.dmarc",
+            WebhookType::ReportTls => "report.tls",
+            WebhookType::ReportArf => "report.arf",
+            WebhookType::AuthFailure => "auth.failure",
+            WebhookType::PrincipalCreated => "principal.created",
+            WebhookType::PrincipalUpdated => "principal.updated",
+            WebhookType::PrincipalDeleted => "principal.deleted",
+        }
+    }
+}
+
+impl ParseValue for WebhookType {
+    fn parse_value(key: impl AsKey, value: &str) -> crate::config::Result<Self> {
+        match value {
+            "message.ingested" => Ok(WebhookType::MessageIngested),
+            "delivery.success" => Ok(WebhookType::DeliverySuccess),
+            "delivery.delayed" => Ok(WebhookType::DeliveryDelayed),
+            "delivery.failure" => Ok(WebhookType::DeliveryFailure),
+            "report.dmarc" => Ok(WebhookType::ReportDmarc),
+            "report.tls" => Ok(WebhookType::ReportTls),
+            "report.arf" => Ok(WebhookType::ReportArf),
+            "auth.failure" => Ok(WebhookType::AuthFailure),
+            "principal.created" => Ok(WebhookType::PrincipalCreated),
+            "principal.updated" => Ok(WebhookType::PrincipalUpdated),
+            "principal.deleted" => Ok(WebhookType::PrincipalDeleted),
+            _ => Err(format!(
+                "Invalid webhook event type {:?} for property {:?}.",
+                value,
+                key.as_key()
+            )),
+        }
+    }
+}
+
+impl Serialize for WebhookType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{backend::internal::manage::ManageDirectory, QueryBy};
+use mail_send::Credentials;
+
+use crate::directory::{map_account_ids, DirectoryTest};
+
+use super::DirectoryStore;
+
+#[tokio::test]
+async fn composite_directory() {
+    // Enable logging
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::TRACE)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let mut config = DirectoryTest::new("sqlite".into()).await;
+    let handle = config.directories.directories.remove("composite").unwrap();
+    let handle_merge = config
+        .directories
+        .directories
+        .remove("composite-merge")
+        .unwrap();
+    let handle_reject = config
+        .directories
+        .directories
+        .remove("composite-reject")
+        .unwrap();
+    let store = DirectoryStore {
+        store: config.stores.lookup_stores.remove("sqlite").unwrap(),
+    };
+    let base_store = config.stores.stores.get("sqlite").unwrap();
+
+    // The SQL directory takes precedence and shares the account "john" with the
+    // in-memory directory, "svc" only exists in SQL and "jane" only in memory.
+    store.create_test_directory().await;
+    store.create_test_user("john", "sqlpass", "John Doe").await;
+    store.create_test_user("svc", "svcpass", "Service").await;
+    store.create_test_group("support", "Support Team").await;
+    store.add_to_group("john", "support").await;
+    store
+        .link_test_address("john", "john@example.org", "primary")
+        .await;
+    store
+        .link_test_address("svc", "svc@service.org", "primary")
+        .await;
+    store
+        .link_test_address("svc", "info@example.org", "list")
+        .await;
+
+    // Accounts are authenticated by the first directory that knows them
+    let john = handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "john".to_string(),
+                secret: "sqlpass".to_string(),
+            }),
+            true,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        john.id,
+        base_store.get_account_id("john").await.unwrap().unwrap()
+    );
+    assert_eq!(
+        john.member_of,
+        map_account_ids(base_store, vec!["support"]).await
+    );
+    assert!(handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "svc".to_string(),
+                secret: "svcpass".to_string(),
+            }),
+            true,
+        )
+        .await
+        .unwrap()
+        .is_some());
+
+    // Credentials rejected by the owning directory are not retried
+    assert!(handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "john".to_string(),
+                secret: "12345".to_string(),
+            }),
+            true,
+        )
+        .await
+        .unwrap()
+        .is_none());
+
+    // Accounts unknown to the first directory fall back to the next one
+    let jane = handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "jane".to_string(),
+                secret: "abcde".to_string(),
+            }),
+            true,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(jane.name, "jane");
+    assert_eq!(
+        handle
+            .query(QueryBy::Id(jane.id), false)
+            .await
+            .unwrap()
+            .unwrap()
+            .name,
+        "jane"
+    );
+
+    // Group memberships are merged across directories when enabled
+    let mut member_of = handle_merge
+        .query(QueryBy::Name("john"), true)
+        .await
+        .unwrap()
+        .unwrap()
+        .member_of;
+    member_of.sort_unstable();
+    let mut expected = map_account_ids(base_store, vec!["support", "sales"]).await;
+    expected.sort_unstable();
+    assert_eq!(member_of, expected);
+
+    // Accounts present in more than one directory are rejected when configured
+    assert!(handle_reject
+        .query(QueryBy::Name("john"), true)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(handle_reject
+        .query(QueryBy::Name("jane"), true)
+        .await
+        .unwrap()
+        .is_some());
+
+    // Recipients, domains and lists are resolved across all directories
+    assert!(handle.rcpt("svc@service.org").await.unwrap());
+    assert!(handle.rcpt("jane@example.org").await.unwrap());
+    assert!(!handle.rcpt("unknown@example.org").await.unwrap());
+    assert!(handle.is_local_domain("service.org").await.unwrap());
+    assert!(handle.is_local_domain("example.org").await.unwrap());
+    assert!(!handle.is_local_domain("other.org").await.unwrap());
+    let mut ids = handle.email_to_ids("info@example.org").await.unwrap();
+    ids.sort_unstable();
+    let mut expected = map_account_ids(base_store, vec!["svc", "john", "jane", "bill"]).await;
+    expected.sort_unstable();
+    assert_eq!(ids, expected);
+}
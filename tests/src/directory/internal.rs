@@ -21,18 +21,21 @@
  * for more details.
 */
 
+use std::time::Duration;
+
 use directory::{
     backend::internal::{
         lookup::DirectoryStore, manage::ManageDirectory, PrincipalField, PrincipalUpdate,
         PrincipalValue,
     },
-    DirectoryError, ManagementError, Principal, QueryBy, Type,
+    core::{domain::DomainSettings, policy::PolicyViolation},
+    Directory, DirectoryError, ManagementError, Principal, QueryBy, Type,
 };
 use jmap_proto::types::collection::Collection;
 use mail_send::Credentials;
 use store::{
     roaring::RoaringBitmap,
-    write::{BatchBuilder, BitmapClass, ValueClass},
+    write::{BatchBuilder, BitmapClass, DirectoryClass, ValueClass},
     BitmapKey, ValueKey,
 };
 
@@ -651,3 +654,303 @@ async fn internal_directory() {
         );
     }
 }
+
+#[tokio::test]
+async fn password_policy() {
+    let config = DirectoryTest::new(None).await;
+    let directory = config
+        .directories
+        .directories
+        .get("policy")
+        .unwrap()
+        .clone();
+
+    // Passwords are validated on creation
+    assert_eq!(
+        directory
+            .create_account(
+                Principal {
+                    name: "pat".to_string(),
+                    secrets: vec!["short".to_string()],
+                    ..Default::default()
+                },
+                vec![]
+            )
+            .await,
+        invalid(PolicyViolation::TooShort(8))
+    );
+    directory
+        .create_account(
+            Principal {
+                name: "pat".to_string(),
+                secrets: vec!["Secret-123".to_string()],
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert!(login(&directory, "pat", "Secret-123").await);
+
+    // Any change to the secrets is validated
+    for change in [
+        PrincipalUpdate::set(
+            PrincipalField::Secrets,
+            PrincipalValue::StringList(vec!["no-digits-here".to_string()]),
+        ),
+        PrincipalUpdate::set(
+            PrincipalField::Secrets,
+            PrincipalValue::String("no-digits-here".to_string()),
+        ),
+        PrincipalUpdate::add_item(
+            PrincipalField::Secrets,
+            PrincipalValue::String("no-digits-here".to_string()),
+        ),
+    ] {
+        assert_eq!(
+            directory
+                .update_account(QueryBy::Name("pat"), vec![change])
+                .await,
+            invalid(PolicyViolation::MissingDigit)
+        );
+    }
+
+    // Add an application password and an OTP secret
+    let otp_secret = "otpauth://totp/Stalwart:pat?secret=JBSWY3DPEHPK3PXP".to_string();
+    let mut new_secrets = secrets(&directory, "pat").await;
+    new_secrets.push("$app$imap$app-token".to_string());
+    new_secrets.push(otp_secret.clone());
+    directory
+        .update_account(
+            QueryBy::Name("pat"),
+            vec![PrincipalUpdate::set(
+                PrincipalField::Secrets,
+                PrincipalValue::StringList(new_secrets),
+            )],
+        )
+        .await
+        .unwrap();
+    let new_secrets = secrets(&directory, "pat").await;
+    assert_eq!(new_secrets.len(), 3, "{new_secrets:?}");
+    assert!(!new_secrets.contains(&"$app$imap$app-token".to_string()));
+    assert!(login(&directory, "pat", "Secret-123").await);
+    assert!(login(&directory, "pat", "app-token").await);
+    assert!(!login(&directory, "pat", &otp_secret).await);
+
+    // Changing the password only replaces the password entry
+    assert!(!directory
+        .change_password("pat", "app-token", "Secret-456")
+        .await
+        .unwrap());
+    assert!(directory
+        .change_password("pat", "Secret-123", "Secret-456")
+        .await
+        .unwrap());
+    let new_secrets = secrets(&directory, "pat").await;
+    assert_eq!(new_secrets.len(), 3, "{new_secrets:?}");
+    assert!(new_secrets.contains(&otp_secret));
+    assert!(new_secrets
+        .iter()
+        .any(|secret| secret.starts_with("$app$imap$")));
+    assert!(!login(&directory, "pat", "Secret-123").await);
+    assert!(login(&directory, "pat", "Secret-456").await);
+    assert!(login(&directory, "pat", "app-token").await);
+
+    // Recently used passwords are rejected
+    assert_eq!(
+        directory
+            .change_password("pat", "Secret-456", "Secret-123")
+            .await,
+        invalid(PolicyViolation::Reused(2))
+    );
+
+    // Expired passwords are rejected on every login path
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    assert!(!login(&directory, "pat", "Secret-456").await);
+    assert_eq!(
+        directory
+            .query_login(QueryBy::Name("pat"), false)
+            .await
+            .unwrap(),
+        None
+    );
+    assert!(directory
+        .query(QueryBy::Name("pat"), false)
+        .await
+        .unwrap()
+        .is_some());
+
+    // But can still be renewed
+    assert!(directory
+        .change_password("pat", "Secret-456", "Secret-789")
+        .await
+        .unwrap());
+    assert!(login(&directory, "pat", "Secret-789").await);
+}
+
+async fn login(directory: &Directory, username: &str, secret: &str) -> bool {
+    directory
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: username.to_string(),
+                secret: secret.to_string(),
+            }),
+            false,
+        )
+        .await
+        .unwrap()
+        .is_some()
+}
+
+async fn secrets(directory: &Directory, name: &str) -> Vec<String> {
+    directory
+        .query(QueryBy::Name(name), false)
+        .await
+        .unwrap()
+        .unwrap()
+        .secrets
+}
+
+fn invalid<T>(violation: PolicyViolation) -> directory::Result<T> {
+    Err(DirectoryError::Management(
+        ManagementError::InvalidPassword(violation),
+    ))
+}
+
+#[tokio::test]
+async fn domain_limits() {
+    let config = DirectoryTest::new(None).await;
+    let directory = config
+        .directories
+        .directories
+        .get("rocksdb")
+        .unwrap()
+        .clone();
+    let store = config.stores.stores.get("rocksdb").unwrap().clone();
+    store.destroy().await;
+
+    for domain in ["limits.org", "other.org"] {
+        directory.create_domain(domain).await.unwrap();
+    }
+    directory
+        .set_domain_settings(
+            "limits.org",
+            DomainSettings {
+                quota: 1000,
+                max_accounts: 1,
+                default_quota: 500,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    // Accounts are counted and the domain defaults applied
+    let account_id = directory
+        .create_account(account("first@limits.org", Type::Individual), vec![])
+        .await
+        .unwrap();
+    assert_eq!(
+        directory
+            .query(QueryBy::Id(account_id), false)
+            .await
+            .unwrap()
+            .unwrap()
+            .quota,
+        500
+    );
+    assert_eq!(usage(&directory, "limits.org").await, (1, 0));
+
+    // The account limit only applies to individual accounts
+    assert_eq!(
+        directory
+            .create_account(account("second@limits.org", Type::Individual), vec![])
+            .await,
+        Err(DirectoryError::Management(ManagementError::LimitExceeded {
+            domain: "limits.org".to_string(),
+            limit: "maxAccounts",
+        }))
+    );
+    let group_id = directory
+        .create_account(account("group@limits.org", Type::Group), vec![])
+        .await
+        .unwrap();
+    assert_eq!(usage(&directory, "limits.org").await, (1, 0));
+
+    // Quota changes are added to the domain usage
+    for (account_id, used) in [(account_id, 600), (group_id, 300), (account_id, -200)] {
+        let mut batch = BatchBuilder::new();
+        batch.add(
+            ValueClass::Directory(DirectoryClass::UsedQuota(account_id)),
+            used,
+        );
+        directory.track_domain_usage(&mut batch).await.unwrap();
+        store.write(batch.build()).await.unwrap();
+    }
+    assert_eq!(usage(&directory, "limits.org").await, (1, 700));
+    assert!(!directory
+        .is_domain_over_quota("limits.org", 300)
+        .await
+        .unwrap());
+    assert!(directory
+        .is_domain_over_quota("limits.org", 301)
+        .await
+        .unwrap());
+
+    // Renaming an account moves its usage to the new domain
+    directory
+        .update_account(
+            QueryBy::Id(account_id),
+            vec![
+                PrincipalUpdate::set(
+                    PrincipalField::Name,
+                    PrincipalValue::String("first@other.org".to_string()),
+                ),
+                PrincipalUpdate::set(
+                    PrincipalField::Emails,
+                    PrincipalValue::StringList(vec!["first@other.org".to_string()]),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(usage(&directory, "limits.org").await, (0, 300));
+    assert_eq!(usage(&directory, "other.org").await, (1, 400));
+    directory
+        .create_account(account("second@limits.org", Type::Individual), vec![])
+        .await
+        .unwrap();
+    assert_eq!(usage(&directory, "limits.org").await, (1, 300));
+
+    // Deleted accounts are no longer counted
+    directory
+        .delete_account(QueryBy::Id(account_id))
+        .await
+        .unwrap();
+    directory
+        .delete_account(QueryBy::Id(group_id))
+        .await
+        .unwrap();
+    assert_eq!(usage(&directory, "other.org").await, (0, 0));
+    assert_eq!(usage(&directory, "limits.org").await, (1, 0));
+
+    // A full recount matches the counters
+    for domain in ["limits.org", "other.org"] {
+        let usage = directory.domain_usage(domain).await.unwrap();
+        assert_eq!(directory.refresh_domain_usage(domain).await.unwrap(), usage);
+    }
+}
+
+fn account(name: &str, typ: Type) -> Principal<String> {
+    Principal {
+        name: name.to_string(),
+        typ,
+        emails: vec![name.to_string()],
+        ..Default::default()
+    }
+}
+
+async fn usage(directory: &Directory, domain: &str) -> (u64, u64) {
+    let usage = directory.domain_usage(domain).await.unwrap();
+    (usage.accounts, usage.used_quota)
+}
@@ -23,7 +23,10 @@
 
 use std::fmt::Debug;
 
-use directory::{backend::internal::manage::ManageDirectory, Principal, QueryBy, Type};
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
 use mail_send::Credentials;
 
 use crate::directory::{map_account_ids, DirectoryTest, IntoSortedPrincipal};
@@ -217,6 +220,208 @@ async fn ldap_directory() {
     );
 }
 
+#[tokio::test]
+async fn ldap_directory_manage() {
+    // Obtain directory handle
+    let mut config = DirectoryTest::new("sqlite".into()).await;
+    let handle = config
+        .directories
+        .directories
+        .remove("ldap-manage")
+        .unwrap();
+    let base_store = config.stores.stores.get("sqlite").unwrap();
+
+    // Remove data from previous runs
+    for name in ["staff", "jane.doe", "doe, jane"] {
+        let _ = handle.delete_account(QueryBy::Name(name)).await;
+    }
+
+    // Create an account whose RDN requires escaping
+    let account_id = handle
+        .create_account(
+            Principal {
+                name: "Doe, Jane".to_string(),
+                description: "Jane Doe".to_string().into(),
+                secrets: vec!["secret".to_string()],
+                typ: Type::Individual,
+                emails: vec!["jane.doe@example.org".to_string()],
+                ..Default::default()
+            },
+            vec![],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        handle
+            .query(QueryBy::Name("doe, jane"), true)
+            .await
+            .unwrap()
+            .unwrap(),
+        Principal {
+            id: account_id,
+            name: "doe, jane".to_string(),
+            description: "Jane Doe".to_string().into(),
+            secrets: vec!["secret".to_string()],
+            typ: Type::Individual,
+            emails: vec!["jane.doe@example.org".to_string()],
+            ..Default::default()
+        }
+    );
+    assert!(handle
+        .query(
+            QueryBy::Credentials(&Credentials::Plain {
+                username: "doe, jane".to_string(),
+                secret: "secret".to_string()
+            }),
+            true
+        )
+        .await
+        .unwrap()
+        .is_some());
+
+    // Duplicate names and addresses are not allowed
+    assert_eq!(
+        handle
+            .create_account(
+                Principal {
+                    name: "doe, jane".to_string(),
+                    secrets: vec!["secret".to_string()],
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await,
+        Err(DirectoryError::Management(ManagementError::AlreadyExists {
+            field: PrincipalField::Name,
+            value: "doe, jane".to_string()
+        }))
+    );
+    assert_eq!(
+        handle
+            .create_account(
+                Principal {
+                    name: "other".to_string(),
+                    secrets: vec!["secret".to_string()],
+                    emails: vec!["jane.doe@example.org".to_string()],
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await,
+        Err(DirectoryError::Management(ManagementError::AlreadyExists {
+            field: PrincipalField::Emails,
+            value: "jane.doe@example.org".to_string()
+        }))
+    );
+
+    // Create a group with the account as its first member
+    let group_id = handle
+        .create_account(
+            Principal {
+                name: "staff".to_string(),
+                description: "Staff".to_string().into(),
+                typ: Type::Group,
+                ..Default::default()
+            },
+            vec!["doe, jane".to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        handle
+            .query(QueryBy::Name("staff"), true)
+            .await
+            .unwrap()
+            .unwrap()
+            .typ,
+        Type::Group
+    );
+    assert_eq!(
+        handle
+            .query(QueryBy::Name("doe, jane"), true)
+            .await
+            .unwrap()
+            .unwrap()
+            .member_of,
+        vec![group_id]
+    );
+
+    // Rename the account, it must stay under the same parent and keep its groups
+    handle
+        .update_account(
+            QueryBy::Name("doe, jane"),
+            vec![
+                PrincipalUpdate::set(
+                    PrincipalField::Name,
+                    PrincipalValue::String("jane.doe".to_string()),
+                ),
+                PrincipalUpdate::set(
+                    PrincipalField::Description,
+                    PrincipalValue::String("Jane M. Doe".to_string()),
+                ),
+                PrincipalUpdate::add_item(
+                    PrincipalField::Emails,
+                    PrincipalValue::String("jane@example.org".to_string()),
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+    assert!(handle
+        .query(QueryBy::Name("doe, jane"), true)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        handle
+            .query(QueryBy::Name("jane.doe"), true)
+            .await
+            .unwrap()
+            .unwrap()
+            .into_sorted(),
+        Principal {
+            id: account_id,
+            name: "jane.doe".to_string(),
+            description: "Jane M. Doe".to_string().into(),
+            secrets: vec!["secret".to_string()],
+            typ: Type::Individual,
+            member_of: vec![group_id],
+            emails: vec![
+                "jane.doe@example.org".to_string(),
+                "jane@example.org".to_string()
+            ],
+            ..Default::default()
+        }
+        .into_sorted()
+    );
+    assert_eq!(
+        base_store.get_account_id("jane.doe").await.unwrap(),
+        Some(account_id)
+    );
+
+    // List accounts
+    assert_eq!(
+        handle.list_accounts(Some("jane"), None).await.unwrap(),
+        vec!["jane.doe".to_string()]
+    );
+    assert!(handle
+        .list_accounts(None, Some(Type::Group))
+        .await
+        .unwrap()
+        .contains(&"staff".to_string()));
+
+    // Delete accounts
+    for name in ["staff", "jane.doe"] {
+        handle.delete_account(QueryBy::Name(name)).await.unwrap();
+        assert!(handle
+            .query(QueryBy::Name(name), true)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(base_store.get_account_id(name).await.unwrap().is_none());
+    }
+}
+
 fn compare_sorted<T: Eq + Debug>(v1: Vec<T>, v2: Vec<T>) {
     for val in v1.iter() {
         assert!(v2.contains(val), "{v1:?} != {v2:?}");
@@ -21,6 +21,7 @@
  * for more details.
 */
 
+pub mod composite;
 pub mod imap;
 pub mod internal;
 pub mod ldap;
@@ -50,6 +51,16 @@ store = "rocksdb"
 catch-all = true
 subaddressing = true
 
+[directory."policy"]
+type = "internal"
+store = "sqlite"
+
+[directory."policy".password-policy]
+min-length = 8
+require.digit = true
+history = 2
+max-age = "1s"
+
 [directory."foundationdb"]
 type = "internal"
 store = "foundationdb"
@@ -93,6 +104,16 @@ emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY
 verify = "SELECT address FROM emails WHERE address LIKE '%' || ? || '%' AND type = 'primary' ORDER BY address LIMIT 5"
 expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
+insert-account = "INSERT INTO accounts (name, type, secret, description, quota, active) VALUES (?, ?, ?, ?, ?, true)"
+update-account = "UPDATE accounts SET type = ?, secret = ?, description = ?, quota = ? WHERE name = ?"
+rename-account = "UPDATE accounts SET name = ? WHERE name = ?"
+delete-account = "DELETE FROM accounts WHERE name = ?"
+insert-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+delete-emails = "DELETE FROM emails WHERE name = ? AND type != 'list'"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES (?, ?)"
+delete-member = "DELETE FROM group_members WHERE name = ? AND member_of = ?"
+list-accounts = "SELECT name, type FROM accounts ORDER BY name"
+list-domains = "SELECT DISTINCT SUBSTR(address, INSTR(address, '@') + 1) FROM emails WHERE address LIKE '%@%' ORDER BY 1"
 
 [storage]
 lookup = "sqlite"
@@ -131,6 +152,16 @@ emails = "SELECT address FROM emails WHERE name = $1 AND type != 'list' ORDER BY
 verify = "SELECT address FROM emails WHERE address LIKE '%' || $1 || '%' AND type = 'primary' ORDER BY address LIMIT 5"
 expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = $1 AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || $1 LIMIT 1"
+insert-account = "INSERT INTO accounts (name, type, secret, description, quota, active) VALUES ($1, $2, $3, $4, $5, true)"
+update-account = "UPDATE accounts SET type = $1, secret = $2, description = $3, quota = $4 WHERE name = $5"
+rename-account = "UPDATE accounts SET name = $1 WHERE name = $2"
+delete-account = "DELETE FROM accounts WHERE name = $1"
+insert-email = "INSERT INTO emails (name, address, type) VALUES ($1, $2, $3)"
+delete-emails = "DELETE FROM emails WHERE name = $1 AND type != 'list'"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES ($1, $2)"
+delete-member = "DELETE FROM group_members WHERE name = $1 AND member_of = $2"
+list-accounts = "SELECT name, type FROM accounts ORDER BY name"
+list-domains = "SELECT DISTINCT SPLIT_PART(address, '@', 2) FROM emails WHERE address LIKE '%@%' ORDER BY 1"
 
 ##############################################################################
 
@@ -166,6 +197,16 @@ emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY
 verify = "SELECT address FROM emails WHERE address LIKE CONCAT('%', ?, '%') AND type = 'primary' ORDER BY address LIMIT 5"
 expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE CONCAT('%@', ?) LIMIT 1"
+insert-account = "INSERT INTO accounts (name, type, secret, description, quota, active) VALUES (?, ?, ?, ?, ?, true)"
+update-account = "UPDATE accounts SET type = ?, secret = ?, description = ?, quota = ? WHERE name = ?"
+rename-account = "UPDATE accounts SET name = ? WHERE name = ?"
+delete-account = "DELETE FROM accounts WHERE name = ?"
+insert-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+delete-emails = "DELETE FROM emails WHERE name = ? AND type != 'list'"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES (?, ?)"
+delete-member = "DELETE FROM group_members WHERE name = ? AND member_of = ?"
+list-accounts = "SELECT name, type FROM accounts ORDER BY name"
+list-domains = "SELECT DISTINCT SUBSTRING_INDEX(address, '@', -1) FROM emails WHERE address LIKE '%@%' ORDER BY 1"
 
 ##############################################################################
 
@@ -208,6 +249,45 @@ type = "objectClass"
 
 ##############################################################################
 
+# Writable OpenLDAP server with the memberof overlay enabled, such as
+# bitnami/openldap with LDAP_ROOT=dc=example,dc=org
+[directory."ldap-manage"]
+type = "ldap"
+address = "ldap://localhost:1389"
+base-dn = "dc=example,dc=org"
+
+[directory."ldap-manage".bind]
+dn = "cn=admin,dc=example,dc=org"
+secret = "adminpassword"
+
+[directory."ldap-manage".filter]
+name = "(&(|(objectClass=organizationalRole)(objectClass=groupOfNames))(cn=?))"
+email = "(&(objectClass=organizationalRole)(mail=?))"
+verify = "(&(objectClass=organizationalRole)(mail=*?*))"
+expand = "(&(objectClass=organizationalRole)(mail=?))"
+domains = "(&(objectClass=organizationalRole)(mail=*@?))"
+
+[directory."ldap-manage".attributes]
+name = "cn"
+description = "description"
+secret = "userPassword"
+groups = "memberOf"
+email = "mail"
+type = "objectClass"
+
+[directory."ldap-manage".manage]
+member-attribute = "member"
+
+[directory."ldap-manage".manage.individual]
+dn = "cn=?,dc=example,dc=org"
+object-class = ["top", "organizationalRole", "simpleSecurityObject", "extensibleObject"]
+
+[directory."ldap-manage".manage.group]
+dn = "cn=?,dc=example,dc=org"
+object-class = ["top", "groupOfNames"]
+
+##############################################################################
+
 [directory."imap"]
 type = "imap"
 address = "127.0.0.1"
@@ -288,6 +368,24 @@ name = "support"
 type = "group"
 description = "Support Team"
 
+##############################################################################
+
+[directory."composite"]
+type = "composite"
+members = ["sqlite", "local"]
+
+[directory."composite-merge"]
+type = "composite"
+members = ["sqlite", "local"]
+
+[directory."composite-merge".merge]
+groups = true
+
+[directory."composite-reject"]
+type = "composite"
+members = ["sqlite", "local"]
+collision = "reject"
+
 "#;
 
 pub struct DirectoryStore {
@@ -21,7 +21,10 @@
  * for more details.
 */
 
-use directory::{backend::internal::manage::ManageDirectory, Principal, QueryBy, Type};
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField, PrincipalUpdate, PrincipalValue},
+    DirectoryError, ManagementError, Principal, QueryBy, Type,
+};
 use mail_send::Credentials;
 use store::{LookupStore, Store};
 
@@ -281,6 +284,177 @@ async fn sql_directory() {
             handle.expn("john@example.org").await.unwrap(),
             Vec::<String>::new()
         );
+
+        // Create account
+        let account_id = handle
+            .create_account(
+                Principal {
+                    name: "Mike".to_string(),
+                    description: "Mike Foobar".to_string().into(),
+                    secrets: vec!["secret".to_string()],
+                    typ: Type::Individual,
+                    quota: 1024,
+                    member_of: vec!["sales".to_string()],
+                    emails: vec!["mike@example.org".to_string(), "m@example.org".to_string()],
+                    ..Default::default()
+                },
+                vec![],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            handle
+                .query(QueryBy::Name("mike"), true)
+                .await
+                .unwrap()
+                .unwrap(),
+            Principal {
+                id: account_id,
+                name: "mike".to_string(),
+                description: "Mike Foobar".to_string().into(),
+                secrets: vec!["secret".to_string()],
+                typ: Type::Individual,
+                quota: 1024,
+                member_of: map_account_ids(base_store, vec!["sales"]).await,
+                emails: vec!["mike@example.org".to_string(), "m@example.org".to_string()],
+            }
+        );
+
+        // Duplicate names and addresses are not allowed
+        assert_eq!(
+            handle
+                .create_account(
+                    Principal {
+                        name: "mike".to_string(),
+                        ..Default::default()
+                    },
+                    vec![],
+                )
+                .await,
+            Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Name,
+                value: "mike".to_string()
+            }))
+        );
+        assert_eq!(
+            handle
+                .create_account(
+                    Principal {
+                        name: "other".to_string(),
+                        emails: vec!["jane@example.org".to_string()],
+                        ..Default::default()
+                    },
+                    vec![],
+                )
+                .await,
+            Err(DirectoryError::Management(ManagementError::AlreadyExists {
+                field: PrincipalField::Emails,
+                value: "jane@example.org".to_string()
+            }))
+        );
+
+        // Update account
+        handle
+            .update_account(
+                QueryBy::Name("mike"),
+                vec![
+                    PrincipalUpdate::set(
+                        PrincipalField::Name,
+                        PrincipalValue::String("michael".to_string()),
+                    ),
+                    PrincipalUpdate::set(
+                        PrincipalField::Description,
+                        PrincipalValue::String("Michael Foobar".to_string()),
+                    ),
+                    PrincipalUpdate::add_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("michael@example.org".to_string()),
+                    ),
+                    PrincipalUpdate::remove_item(
+                        PrincipalField::Emails,
+                        PrincipalValue::String("m@example.org".to_string()),
+                    ),
+                    PrincipalUpdate::remove_item(
+                        PrincipalField::MemberOf,
+                        PrincipalValue::String("sales".to_string()),
+                    ),
+                    PrincipalUpdate::add_item(
+                        PrincipalField::MemberOf,
+                        PrincipalValue::String("support".to_string()),
+                    ),
+                ],
+            )
+            .await
+            .unwrap();
+        handle
+            .update_account(
+                QueryBy::Name("sales"),
+                vec![PrincipalUpdate::add_item(
+                    PrincipalField::Members,
+                    PrincipalValue::String("michael".to_string()),
+                )],
+            )
+            .await
+            .unwrap();
+        assert!(handle
+            .query(QueryBy::Name("mike"), true)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            handle
+                .query(QueryBy::Name("michael"), true)
+                .await
+                .unwrap()
+                .unwrap(),
+            Principal {
+                id: account_id,
+                name: "michael".to_string(),
+                description: "Michael Foobar".to_string().into(),
+                secrets: vec!["secret".to_string()],
+                typ: Type::Individual,
+                quota: 1024,
+                member_of: map_account_ids(base_store, vec!["sales", "support"]).await,
+                emails: vec![
+                    "mike@example.org".to_string(),
+                    "michael@example.org".to_string()
+                ],
+            }
+        );
+
+        // List accounts and domains
+        assert_eq!(
+            handle.list_accounts(Some("mich"), None).await.unwrap(),
+            vec!["michael".to_string()]
+        );
+        assert_eq!(
+            handle.list_accounts(None, Some(Type::Group)).await.unwrap(),
+            vec!["sales".to_string(), "support".to_string()]
+        );
+        assert_eq!(
+            handle.list_domains(None).await.unwrap(),
+            vec!["catchall.org".to_string(), "example.org".to_string()]
+        );
+
+        // Delete account
+        handle
+            .delete_account(QueryBy::Name("michael"))
+            .await
+            .unwrap();
+        assert!(handle
+            .query(QueryBy::Name("michael"), true)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(base_store
+            .get_account_id("michael")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            handle.email_to_ids("mike@example.org").await.unwrap(),
+            Vec::<u32>::new()
+        );
     }
 }
 
@@ -387,6 +561,32 @@ impl DirectoryStore {
             .unwrap();
     }
 
+    pub async fn create_test_list(&self, login: &str, name: &str) {
+        self.store
+            .query::<usize>(
+                if self.is_postgresql() {
+                    concat!(
+                        "INSERT INTO accounts (name, description, ",
+                        "type, active) VALUES ($1, $2, $3, $4) ON CONFLICT (name) DO NOTHING"
+                    )
+                } else if self.is_mysql() {
+                    concat!(
+                        "INSERT IGNORE INTO accounts (name, description, ",
+                        "type, active) VALUES (?, ?, ?, ?)"
+                    )
+                } else {
+                    concat!(
+                        "INSERT OR IGNORE INTO accounts (name, description, ",
+                        "type, active) VALUES (?, ?, ?, ?)"
+                    )
+                },
+                vec![login.into(), name.into(), "list".into(), true.into()],
+            )
+            .await
+            .unwrap();
+        self.link_test_address(login, login, "primary").await;
+    }
+
     pub async fn create_test_group_with_email(&self, login: &str, name: &str) {
         self.create_test_group(login, name).await;
         self.link_test_address(login, login, "primary").await;
@@ -31,6 +31,8 @@ pub mod fetch;
 pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
+pub mod public;
+pub mod sasl;
 pub mod search;
 pub mod store;
 pub mod thread;
@@ -68,6 +70,14 @@ bind = ["127.0.0.1:9992"]
 protocol = "imap"
 max-connections = 81920
 tls.implicit = true
+tls.client-auth.ca = "file://{CLIENT_CA}"
+
+[server.listener.submissions]
+bind = ["127.0.0.1:4650"]
+protocol = "smtp"
+max-connections = 81920
+tls.implicit = true
+tls.client-auth.ca = "file://{CLIENT_CA}"
 
 [server.listener.sieve]
 bind = ["127.0.0.1:4190"]
@@ -92,6 +102,11 @@ certificate = "default"
 [session.ehlo]
 reject-non-fqdn = false
 
+[session.auth]
+mechanisms = [ { if = "listener = 'submissions'", then = "[plain, scram-sha-256, scram-sha-256-plus, external]"},
+               { else = false } ]
+directory = "'auth'"
+
 [session.rcpt]
 relay = [ { if = "!is_empty(authenticated_as)", then = true }, 
           { else = false } ]
@@ -174,6 +189,9 @@ private-key = "file://{PK}"
 [imap.protocol]
 uidplus = true
 
+[sharing.public."Public"]
+principal = "public@example.com"
+
 [storage]
 data = "{STORE}"
 fts = "{STORE}"
@@ -262,6 +280,7 @@ refresh-token-renew = "2s"
 pub struct IMAPTest {
     jmap: Arc<JMAP>,
     imap: Arc<IMAP>,
+    lookup: DirectoryStore,
     temp_dir: TempDir,
     shutdown_tx: watch::Sender<bool>,
 }
@@ -285,15 +304,24 @@ async fn init_imap_tests(store_id: &str, delete_if_exists: bool) -> IMAPTest {
     // Start JMAP and SMTP servers
     servers.bind(&config);
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &stores, &directory, delivery_tx)
-        .await
-        .failed("Invalid configuration file");
+    let (webhook_tx, webhook_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+    let smtp = SMTP::init(
+        &config,
+        &servers,
+        &stores,
+        &directory,
+        delivery_tx,
+        webhook_tx,
+    )
+    .await
+    .failed("Invalid configuration file");
     let jmap = JMAP::init(
         &config,
         &stores,
         &directory,
         &mut servers,
         delivery_rx,
+        webhook_rx,
         smtp.clone(),
     )
     .await
@@ -355,6 +383,7 @@ async fn init_imap_tests(store_id: &str, delete_if_exists: bool) -> IMAPTest {
     IMAPTest {
         jmap,
         imap,
+        lookup,
         temp_dir,
         shutdown_tx,
     }
@@ -419,6 +448,8 @@ pub async fn imap_tests() {
     idle::test(&mut imap, &mut imap_check).await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    public::test(&handle).await;
+    sasl::test().await;
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
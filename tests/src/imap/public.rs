@@ -0,0 +1,182 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use imap_proto::ResponseType;
+
+use crate::jmap::delivery::SmtpConnection;
+
+use super::{AssertResult, IMAPTest, ImapConnection, Type};
+
+pub async fn test(handle: &IMAPTest) {
+    println!("Running public folder tests...");
+
+    // Create the principal backing the "Public" namespace and deliver a message to it
+    handle
+        .lookup
+        .create_test_group_with_email("public@example.com", "Public Folders")
+        .await;
+    let mut lmtp = SmtpConnection::connect_port(11201).await;
+    lmtp.ingest(
+        "bill@example.com",
+        &["public@example.com"],
+        concat!(
+            "From: bill@example.com\r\n",
+            "To: public@example.com\r\n",
+            "Subject: Company announcement\r\n",
+            "\r\n",
+            "The office will be closed on Friday."
+        ),
+    )
+    .await;
+
+    // Members of the principal manage the public folders
+    handle
+        .lookup
+        .create_test_user_with_email("editor@example.com", "secret", "Public Editor")
+        .await;
+    handle
+        .lookup
+        .add_to_group("editor@example.com", "public@example.com")
+        .await;
+
+    // Connect John, Jane and the editor
+    let mut imap_john = ImapConnection::connect(b"_p ").await;
+    let mut imap_jane = ImapConnection::connect(b"_q ").await;
+    let mut imap_editor = ImapConnection::connect(b"_r ").await;
+    for (imap, secret) in [
+        (&mut imap_john, "AGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0"),
+        (&mut imap_jane, "AGphbmUuc21pdGhAZXhhbXBsZS5jb20Ac2VjcmV0"),
+        (&mut imap_editor, "AGVkaXRvckBleGFtcGxlLmNvbQBzZWNyZXQ="),
+    ] {
+        imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+        imap.send(&format!(
+            "AUTHENTICATE PLAIN {{{}+}}\r\n{}",
+            secret.len(),
+            secret
+        ))
+        .await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+
+    // Both users should see the public namespace
+    for imap in [&mut imap_john, &mut imap_jane] {
+        imap.send("NAMESPACE").await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_contains("((\"Public\" \"/\"))");
+        imap.send("LIST \"\" \"Public*\"").await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_equals("* LIST (\\NoSelect) \"/\" \"Public\"")
+            .assert_contains("\"Public/Inbox\"");
+        imap.send("STATUS \"Public/Inbox\" (MESSAGES UNSEEN)").await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_contains("MESSAGES 1 UNSEEN 1");
+    }
+
+    // Default rights are read-only
+    imap_john.send("SELECT \"Public/Inbox\"").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_john.send("MYRIGHTS \"Public/Inbox\"").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"Public/Inbox\" rl");
+    imap_john.send("STORE 1 +FLAGS (\\Flagged)").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_john
+        .send("SETACL \"Public/Inbox\" jdoe@example.com lrw")
+        .await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Seen state is tracked per user
+    imap_john.send("STORE 1 +FLAGS (\\Seen)").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\\Seen");
+    imap_john.send("SEARCH SEEN").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1");
+    imap_john.send("STATUS \"Public/Inbox\" (UNSEEN)").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UNSEEN 0");
+
+    imap_jane.send("EXAMINE \"Public/Inbox\"").await;
+    imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_jane.send("FETCH 1 (FLAGS)").await;
+    imap_jane
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("\\Seen", 0);
+    imap_jane.send("SEARCH UNSEEN").await;
+    imap_jane
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 1");
+
+    // Clearing the flag only affects John
+    imap_john.send("STORE 1 -FLAGS (\\Seen)").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_john.send("FETCH 1 (FLAGS)").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("\\Seen", 0);
+
+    // An explicit ACL replaces the default rights of the public folder
+    imap_editor
+        .send("SETACL \"Public/Inbox\" jane.smith@example.com lr")
+        .await;
+    imap_editor
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await;
+
+    imap_john.send("LIST \"\" \"Public*\"").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("\"Public/Inbox\"", 0);
+    imap_john.send("STATUS \"Public/Inbox\" (MESSAGES)").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_jane.send("LIST \"\" \"Public*\"").await;
+    imap_jane
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"Public/Inbox\"");
+    imap_jane.send("MYRIGHTS \"Public/Inbox\"").await;
+    imap_jane
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("\"Public/Inbox\" lr");
+
+    for imap in [&mut imap_john, &mut imap_jane, &mut imap_editor] {
+        imap.send("LOGOUT").await;
+        imap.assert_read(Type::Untagged, ResponseType::Bye).await;
+    }
+}
@@ -0,0 +1,431 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose, Engine};
+use hmac::{Hmac, Mac};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    ClientConfig, DigitallySignedStruct, SignatureScheme,
+};
+use rustls_pemfile::{certs, pkcs8_private_keys};
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_rustls::{client::TlsStream, TlsConnector};
+
+pub async fn test() {
+    println!("Running SASL tests...");
+
+    // IMAP SCRAM-SHA-256 and SCRAM-SHA-256-PLUS
+    for is_plus in [false, true] {
+        let mut imap = TlsConnection::connect("127.0.0.1:9992", false).await;
+        imap.read("* OK").await;
+        let mut scram = ScramClient::new("jdoe@example.com", is_plus);
+        imap.send(&format!(
+            "a AUTHENTICATE {} {}",
+            scram.mechanism(),
+            encode(scram.client_first.as_bytes())
+        ))
+        .await;
+        let server_first = imap.read_challenge("+ ").await;
+        imap.send(&encode(
+            scram
+                .client_final(&server_first, "secret", &imap.channel_binding())
+                .as_bytes(),
+        ))
+        .await;
+        assert_eq!(imap.read_challenge("+ ").await, scram.server_final);
+        imap.send("").await;
+        imap.read("a OK").await;
+    }
+
+    // IMAP SCRAM failures
+    for (password, tamper_binding) in [("wrong", false), ("secret", true)] {
+        let mut imap = TlsConnection::connect("127.0.0.1:9992", false).await;
+        imap.read("* OK").await;
+        let mut scram = ScramClient::new("jdoe@example.com", true);
+        imap.send(&format!(
+            "a AUTHENTICATE {} {}",
+            scram.mechanism(),
+            encode(scram.client_first.as_bytes())
+        ))
+        .await;
+        let server_first = imap.read_challenge("+ ").await;
+        let mut channel_binding = imap.channel_binding();
+        if tamper_binding {
+            channel_binding[0] ^= 0xff;
+        }
+        imap.send(&encode(
+            scram
+                .client_final(&server_first, password, &channel_binding)
+                .as_bytes(),
+        ))
+        .await;
+        imap.read("a NO").await;
+    }
+
+    // Unknown users receive the same salt and iteration count on every attempt,
+    // just like existing users
+    for username in ["jdoe@example.com", "unknown@example.com"] {
+        let mut salts = Vec::new();
+        for _ in 0..2 {
+            let mut imap = TlsConnection::connect("127.0.0.1:9992", false).await;
+            imap.read("* OK").await;
+            let scram = ScramClient::new(username, false);
+            imap.send(&format!(
+                "a AUTHENTICATE {} {}",
+                scram.mechanism(),
+                encode(scram.client_first.as_bytes())
+            ))
+            .await;
+            let server_first = imap.read_challenge("+ ").await;
+            let (_, salt) = server_first.split_once(",s=").unwrap();
+            assert!(salt.ends_with(",i=4096"), "{server_first}");
+            salts.push(salt.to_string());
+        }
+        assert_eq!(salts[0], salts[1], "{username}");
+    }
+
+    // IMAP EXTERNAL with a client certificate
+    let mut imap = TlsConnection::connect("127.0.0.1:9992", true).await;
+    imap.read("* OK").await;
+    imap.send("a AUTHENTICATE EXTERNAL =").await;
+    imap.read("a OK").await;
+    imap.send("b NAMESPACE").await;
+    imap.read("b OK").await;
+
+    // Authorization identities have to match the certificate
+    let mut imap = TlsConnection::connect("127.0.0.1:9992", true).await;
+    imap.read("* OK").await;
+    imap.send(&format!(
+        "a AUTHENTICATE EXTERNAL {}",
+        encode(b"jane.smith@example.com")
+    ))
+    .await;
+    imap.read("a NO").await;
+
+    // EXTERNAL fails without a client certificate
+    let mut imap = TlsConnection::connect("127.0.0.1:9992", false).await;
+    imap.read("* OK").await;
+    imap.send("a AUTHENTICATE EXTERNAL =").await;
+    imap.read("a NO").await;
+
+    // SMTP SCRAM-SHA-256 and SCRAM-SHA-256-PLUS
+    for is_plus in [false, true] {
+        let mut smtp = TlsConnection::connect("127.0.0.1:4650", false).await;
+        smtp.read("220 ").await;
+        smtp.send("EHLO client.example.org").await;
+        let capabilities = smtp.read("250 ").await.join("\n");
+        for mechanism in ["SCRAM-SHA-256", "SCRAM-SHA-256-PLUS", "EXTERNAL"] {
+            assert!(
+                capabilities
+                    .split_ascii_whitespace()
+                    .any(|item| item == mechanism),
+                "{capabilities}"
+            );
+        }
+        let mut scram = ScramClient::new("jdoe@example.com", is_plus);
+        smtp.send(&format!(
+            "AUTH {} {}",
+            scram.mechanism(),
+            encode(scram.client_first.as_bytes())
+        ))
+        .await;
+        let server_first = smtp.read_challenge("334 ").await;
+        smtp.send(&encode(
+            scram
+                .client_final(&server_first, "secret", &smtp.channel_binding())
+                .as_bytes(),
+        ))
+        .await;
+        assert_eq!(smtp.read_challenge("334 ").await, scram.server_final);
+        smtp.send("").await;
+        smtp.read("235 ").await;
+    }
+
+    // SMTP SCRAM with an invalid password
+    let mut smtp = TlsConnection::connect("127.0.0.1:4650", false).await;
+    smtp.read("220 ").await;
+    smtp.send("EHLO client.example.org").await;
+    smtp.read("250 ").await;
+    let mut scram = ScramClient::new("jdoe@example.com", true);
+    smtp.send(&format!(
+        "AUTH {} {}",
+        scram.mechanism(),
+        encode(scram.client_first.as_bytes())
+    ))
+    .await;
+    let server_first = smtp.read_challenge("334 ").await;
+    smtp.send(&encode(
+        scram
+            .client_final(&server_first, "wrong", &smtp.channel_binding())
+            .as_bytes(),
+    ))
+    .await;
+    smtp.read("535 ").await;
+
+    // SMTP EXTERNAL with and without a client certificate
+    for (with_certificate, code) in [(true, "235 "), (false, "535 ")] {
+        let mut smtp = TlsConnection::connect("127.0.0.1:4650", with_certificate).await;
+        smtp.read("220 ").await;
+        smtp.send("EHLO client.example.org").await;
+        smtp.read("250 ").await;
+        smtp.send("AUTH EXTERNAL =").await;
+        smtp.read(code).await;
+    }
+}
+
+struct ScramClient {
+    gs2_header: String,
+    client_first: String,
+    server_final: String,
+}
+
+impl ScramClient {
+    fn new(username: &str, is_plus: bool) -> Self {
+        let gs2_header = if is_plus { "p=tls-exporter,," } else { "n,," };
+        ScramClient {
+            gs2_header: gs2_header.to_string(),
+            client_first: format!("{gs2_header}n={username},r=fyko+d2lbbFgONRv9qkxdawL"),
+            server_final: String::new(),
+        }
+    }
+
+    fn mechanism(&self) -> &'static str {
+        if self.gs2_header.starts_with("p=") {
+            "SCRAM-SHA-256-PLUS"
+        } else {
+            "SCRAM-SHA-256"
+        }
+    }
+
+    fn client_final(
+        &mut self,
+        server_first: &str,
+        password: &str,
+        channel_binding: &[u8],
+    ) -> String {
+        let mut nonce = "";
+        let mut salt = Vec::new();
+        let mut iterations = 0;
+        for attribute in server_first.split(',') {
+            match attribute.split_once('=').unwrap() {
+                ("r", value) => nonce = value,
+                ("s", value) => salt = general_purpose::STANDARD.decode(value).unwrap(),
+                ("i", value) => iterations = value.parse().unwrap(),
+                _ => (),
+            }
+        }
+        assert!(
+            nonce.starts_with("fyko+d2lbbFgONRv9qkxdawL"),
+            "{server_first}"
+        );
+
+        let mut salted_password = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut salted_password);
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+        let mut cbind_input = self.gs2_header.as_bytes().to_vec();
+        if self.gs2_header.starts_with("p=") {
+            cbind_input.extend_from_slice(channel_binding);
+        }
+        let without_proof = format!("c={},r={nonce}", encode(&cbind_input));
+        let auth_message = format!(
+            "{},{server_first},{without_proof}",
+            &self.client_first[self.gs2_header.len()..]
+        );
+        let proof = client_key
+            .iter()
+            .zip(hmac(&stored_key, auth_message.as_bytes()).iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+        self.server_final = format!(
+            "v={}",
+            encode(&hmac(
+                &hmac(&salted_password, b"Server Key"),
+                auth_message.as_bytes()
+            ))
+        );
+
+        format!("{without_proof},p={}", encode(&proof))
+    }
+}
+
+struct TlsConnection {
+    stream: BufReader<TlsStream<TcpStream>>,
+}
+
+impl TlsConnection {
+    async fn connect(address: &str, with_certificate: bool) -> Self {
+        let config = ClientConfig::builder()
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(DummyVerifier));
+        let config = if with_certificate {
+            let cert_chain = certs(&mut std::io::Cursor::new(
+                std::fs::read(resource("tls_client_cert.pem")).unwrap(),
+            ))
+            .map(|cert| cert.unwrap())
+            .collect();
+            let key = pkcs8_private_keys(&mut std::io::Cursor::new(
+                std::fs::read(resource("tls_client_privatekey.pem")).unwrap(),
+            ))
+            .next()
+            .unwrap()
+            .unwrap();
+            config
+                .with_client_auth_cert(cert_chain, PrivateKeyDer::Pkcs8(key))
+                .unwrap()
+        } else {
+            config.with_no_client_auth()
+        };
+
+        TlsConnection {
+            stream: BufReader::new(
+                TlsConnector::from(Arc::new(config))
+                    .connect(
+                        ServerName::try_from("imap.example.org").unwrap().to_owned(),
+                        TcpStream::connect(address).await.unwrap(),
+                    )
+                    .await
+                    .unwrap(),
+            ),
+        }
+    }
+
+    fn channel_binding(&self) -> Vec<u8> {
+        self.stream
+            .get_ref()
+            .get_ref()
+            .1
+            .export_keying_material(vec![0u8; 32], b"EXPORTER-Channel-Binding", Some(b""))
+            .unwrap()
+    }
+
+    async fn send(&mut self, line: &str) {
+        self.stream
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await
+            .unwrap();
+        self.stream.flush().await.unwrap();
+    }
+
+    async fn read(&mut self, prefix: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            match tokio::time::timeout(
+                Duration::from_millis(1500),
+                self.stream.read_line(&mut line),
+            )
+            .await
+            {
+                Ok(Ok(bytes)) if bytes > 0 => {
+                    let line = line.trim_end().to_string();
+                    let is_done = line.starts_with(prefix);
+                    let is_partial =
+                        line.starts_with("* ") || line.as_bytes().get(3) == Some(&b'-');
+                    lines.push(line);
+                    if is_done {
+                        return lines;
+                    } else if !is_partial {
+                        panic!("Expected {prefix:?} from server but got: {lines:?}");
+                    }
+                }
+                Ok(result) => panic!("Connection closed: {result:?} ({lines:?})"),
+                Err(_) => panic!("Timeout while waiting for server response: {lines:?}"),
+            }
+        }
+    }
+
+    async fn read_challenge(&mut self, prefix: &str) -> String {
+        let line = self.read(prefix).await.pop().unwrap();
+        String::from_utf8(
+            general_purpose::STANDARD
+                .decode(line.strip_prefix(prefix).unwrap())
+                .unwrap(),
+        )
+        .unwrap()
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    general_purpose::STANDARD.encode(bytes)
+}
+
+fn resource(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("resources");
+    path.push(name);
+    path
+}
+
+#[derive(Debug)]
+struct DummyVerifier;
+
+impl ServerCertVerifier for DummyVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        _message: &[u8],
+        _cert: &CertificateDer<'_>,
+        _dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        Ok(HandshakeSignatureValid::assertion())
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        rustls::crypto::ring::default_provider()
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use jmap::{
+    services::audit::{AuditAction, AuditEntry, AuditEvent, AuditMarker},
+    JMAP,
+};
+use store::{
+    write::{now, BatchBuilder, Bincode, ValueClass},
+    Serialize, ValueKey,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running audit log tests...");
+    let server = params.server.clone();
+
+    // Record a few entries and verify the chain
+    for target in ["alpha", "beta", "gamma"] {
+        server
+            .audit(
+                AuditEvent::new(AuditAction::PrincipalUpdate)
+                    .with_actor("admin")
+                    .with_target(target),
+            )
+            .await;
+    }
+    let result = server.audit_verify().await.unwrap();
+    assert!(result.valid, "{result:?}");
+    assert!(result.total >= 3, "{result:?}");
+    assert_eq!(result.checkpoint_id, None);
+    let first_id = result.first_id.unwrap();
+    let last_id = result.last_id.unwrap();
+
+    // Rewriting an entry without the key is detected
+    let original = get_entry(&server, last_id - 1).await;
+    let mut forged = original.clone();
+    forged.target = Some("mallory".to_string());
+    forged.hash = forged.digest(&[0u8; 32]);
+    set_entry(&server, &forged).await;
+    assert_failed_at(&server, last_id - 1).await;
+    set_entry(&server, &original).await;
+    assert!(server.audit_verify().await.unwrap().valid);
+
+    // Truncating the log at either end is detected
+    for id in [first_id, last_id] {
+        let original = get_entry(&server, id).await;
+        let mut batch = BatchBuilder::new();
+        batch.clear(ValueClass::Audit(id));
+        server.store.write(batch.build()).await.unwrap();
+        assert_failed_at(&server, id).await;
+        set_entry(&server, &original).await;
+        assert!(server.audit_verify().await.unwrap().valid);
+    }
+
+    // Purging stores a checkpoint from which the chain is verified
+    let last_hash = get_entry(&server, last_id).await.hash;
+    assert_eq!(server.audit_purge(now() + 1).await.unwrap(), Some(last_id));
+    let result = server.audit_verify().await.unwrap();
+    assert!(result.valid, "{result:?}");
+    assert_eq!(result.total, 0);
+    assert_eq!(result.checkpoint_id, Some(last_id));
+    server
+        .audit(AuditEvent::new(AuditAction::SettingsReload).with_actor("admin"))
+        .await;
+    let result = server.audit_verify().await.unwrap();
+    assert!(result.valid, "{result:?}");
+    assert_eq!(result.total, 1);
+    assert_eq!(result.first_id, Some(last_id + 1));
+    assert_eq!(get_entry(&server, last_id + 1).await.prev_hash, last_hash);
+
+    // A forged checkpoint is rejected
+    let checkpoint = server
+        .store
+        .get_value::<Bincode<AuditMarker>>(ValueKey::from(ValueClass::Audit(0)))
+        .await
+        .unwrap()
+        .unwrap()
+        .inner;
+    set_checkpoint(
+        &server,
+        &AuditMarker::new(&[0u8; 32], last_id + 1, [1u8; 32]),
+    )
+    .await;
+    assert_failed_at(&server, last_id + 1).await;
+    set_checkpoint(&server, &checkpoint).await;
+
+    // Removing entries following the checkpoint is detected
+    let original = get_entry(&server, last_id + 1).await;
+    let mut batch = BatchBuilder::new();
+    batch.clear(ValueClass::Audit(last_id + 1));
+    server.store.write(batch.build()).await.unwrap();
+    assert_failed_at(&server, last_id + 1).await;
+    set_entry(&server, &original).await;
+    assert!(server.audit_verify().await.unwrap().valid);
+}
+
+async fn assert_failed_at(server: &JMAP, id: u64) {
+    let result = server.audit_verify().await.unwrap();
+    assert!(!result.valid, "{result:?}");
+    assert_eq!(result.failed_id, Some(id), "{result:?}");
+}
+
+async fn get_entry(server: &JMAP, id: u64) -> AuditEntry {
+    server
+        .store
+        .get_value::<Bincode<AuditEntry>>(ValueKey::from(ValueClass::Audit(id)))
+        .await
+        .unwrap()
+        .unwrap()
+        .inner
+}
+
+async fn set_entry(server: &JMAP, entry: &AuditEntry) {
+    let mut batch = BatchBuilder::new();
+    batch.set(
+        ValueClass::Audit(entry.id),
+        Bincode::new(entry.clone()).serialize(),
+    );
+    server.store.write(batch.build()).await.unwrap();
+}
+
+async fn set_checkpoint(server: &JMAP, checkpoint: &AuditMarker) {
+    let mut batch = BatchBuilder::new();
+    batch.set(
+        ValueClass::Audit(0),
+        Bincode::new(checkpoint.clone()).serialize(),
+    );
+    server.store.write(batch.build()).await.unwrap();
+}
@@ -21,9 +21,15 @@
  * for more details.
 */
 
+use std::time::Duration;
+
 use directory::backend::internal::manage::ManageDirectory;
 use jmap::mailbox::INBOX_ID;
 use jmap_proto::types::id::Id;
+use reqwest::{
+    header::{self, HeaderMap},
+    Method, StatusCode,
+};
 use serde_json::Value;
 
 use crate::jmap::{assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes};
@@ -493,8 +499,197 @@ pub async fn test(params: &mut JMAPTest) {
         );
     }
 
+    // Range downloads
+    let download_path = format!("download/{account_id}/{blob_id}/report.eml");
+    let (status, headers, full_blob) =
+        blob_request(Method::GET, &download_path, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+    let etag = headers[header::ETAG].to_str().unwrap().to_string();
+    let (status, headers, bytes) = blob_request(
+        Method::GET,
+        &download_path,
+        &[(header::RANGE.as_str(), "bytes=4-15")],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(bytes, &full_blob[4..16]);
+    assert_eq!(
+        headers[header::CONTENT_RANGE],
+        format!("bytes 4-15/{}", full_blob.len()).as_str()
+    );
+    let (status, _, bytes) = blob_request(
+        Method::GET,
+        &download_path,
+        &[
+            (header::RANGE.as_str(), "bytes=-10"),
+            (header::IF_RANGE.as_str(), &etag),
+        ],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(bytes, &full_blob[full_blob.len() - 10..]);
+    let (status, _, bytes) = blob_request(
+        Method::GET,
+        &download_path,
+        &[
+            (header::RANGE.as_str(), "bytes=-10"),
+            (header::IF_RANGE.as_str(), "\"outdated\""),
+        ],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bytes, full_blob);
+    let range = format!("bytes={}-", full_blob.len());
+    let (status, headers, _) = blob_request(
+        Method::GET,
+        &download_path,
+        &[(header::RANGE.as_str(), &range)],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        headers[header::CONTENT_RANGE],
+        format!("bytes */{}", full_blob.len()).as_str()
+    );
+
+    // Resumable uploads
+    let upload_data = (0..3000u32)
+        .map(|n| b'a' + (n % 26) as u8)
+        .collect::<Vec<_>>();
+    let size = upload_data.len().to_string();
+    let (status, headers, session) = blob_request(
+        Method::POST,
+        &format!("upload/{account_id}/"),
+        &[
+            ("Upload-Length", &size),
+            (header::CONTENT_TYPE.as_str(), "text/plain"),
+        ],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(headers["Upload-Offset"], "0");
+    let session: Value = serde_json::from_slice(&session).unwrap();
+    let upload_path = format!(
+        "upload/{account_id}/{}",
+        session["uploadId"].as_str().unwrap()
+    );
+    assert_eq!(
+        headers[header::LOCATION],
+        format!("/jmap/{upload_path}").as_str()
+    );
+
+    for (offset, chunk) in [(0, 0..1000), (1000, 1000..2000)] {
+        let offset = offset.to_string();
+        let (status, headers, _) = blob_request(
+            Method::PATCH,
+            &upload_path,
+            &[("Upload-Offset", &offset)],
+            upload_data[chunk.clone()].to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["Upload-Offset"], chunk.end.to_string().as_str());
+    }
+
+    // Resume after an interruption
+    let (status, _, _) = blob_request(
+        Method::PATCH,
+        &upload_path,
+        &[("Upload-Offset", "500")],
+        upload_data[500..1000].to_vec(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, headers, _) = blob_request(Method::HEAD, &upload_path, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["Upload-Offset"], "2000");
+    assert_eq!(headers["Upload-Length"], size.as_str());
+    let (status, _, upload) = blob_request(
+        Method::PATCH,
+        &upload_path,
+        &[("Upload-Offset", "2000")],
+        upload_data[2000..].to_vec(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let upload: Value = serde_json::from_slice(&upload).unwrap();
+    assert_eq!(upload["size"], 3000);
+    assert_eq!(upload["type"], "text/plain");
+    let (status, _, bytes) = blob_request(
+        Method::GET,
+        &format!(
+            "download/{account_id}/{}/upload.txt",
+            upload["blobId"].as_str().unwrap()
+        ),
+        &[],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bytes, upload_data);
+    let (status, _, _) = blob_request(Method::HEAD, &upload_path, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Cancelled uploads are discarded
+    let (status, _, session) = blob_request(
+        Method::POST,
+        &format!("upload/{account_id}/"),
+        &[("Upload-Length", "100")],
+        Vec::new(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let session: Value = serde_json::from_slice(&session).unwrap();
+    let upload_path = format!(
+        "upload/{account_id}/{}",
+        session["uploadId"].as_str().unwrap()
+    );
+    let (status, _, _) = blob_request(
+        Method::PATCH,
+        &upload_path,
+        &[("Upload-Offset", "0")],
+        vec![b'x'; 200],
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _, _) = blob_request(Method::DELETE, &upload_path, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _, _) = blob_request(Method::HEAD, &upload_path, &[], Vec::new()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
     // Remove test data
     params.client.set_default_account_id(account_id.to_string());
     destroy_all_mailboxes(params).await;
     assert_is_empty(server).await;
 }
+
+async fn blob_request(
+    method: Method,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Vec<u8>,
+) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(1000))
+        .build()
+        .unwrap()
+        .request(method, format!("https://127.0.0.1:8899/jmap/{path}"))
+        .basic_auth("jdoe@example.com", Some("12345"))
+        .body(body);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = request.send().await.unwrap();
+    (
+        response.status(),
+        response.headers().clone(),
+        response.bytes().await.unwrap().to_vec(),
+    )
+}
@@ -23,6 +23,7 @@
 
 use ahash::AHashMap;
 use directory::backend::internal::manage::ManageDirectory;
+use jmap::JMAP;
 use jmap_client::{
     core::set::{SetError, SetErrorType, SetObject},
     email_submission::{query::Filter, Address, Delivered, DeliveryStatus, Displayed, UndoStatus},
@@ -35,13 +36,21 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use store::parking_lot::Mutex;
+use store::{
+    parking_lot::Mutex,
+    write::{now, BatchBuilder, BlobOp},
+    Serialize,
+};
 
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     net::TcpListener,
     sync::mpsc,
 };
+use utils::{
+    ipc::{DeliveryResult, IngestMessage},
+    BlobHash,
+};
 
 use crate::jmap::{
     assert_is_empty, email_set::assert_email_properties, mailbox::destroy_all_mailboxes,
@@ -449,6 +458,167 @@ pub async fn test(params: &mut JMAPTest) {
         ),])
     );
 
+    // Delivery and disposition notifications are attached to the submission
+    let email_body = concat!(
+        "From: jdoe@example.com\r\n",
+        "To: jane_smith@remote.org\r\n",
+        "Message-ID: <report-test@example.com>\r\n",
+        "Subject: hey\r\n\r\ntest"
+    );
+    let report_email_id = client
+        .email_import(
+            email_body.as_bytes().to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let email_submission_id = client
+        .email_submission_create_envelope(
+            &report_email_id,
+            &identity_id,
+            Address::new("jdoe@example.com").parameter("ENVID", Some("report-test-envid")),
+            ["jane_smith@remote.org"],
+        )
+        .await
+        .unwrap()
+        .take_id();
+    assert_message_delivery(
+        &mut smtp_rx,
+        MockMessage::new(
+            "<jdoe@example.com>",
+            ["<jane_smith@remote.org>"],
+            email_body,
+        ),
+    )
+    .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let mut request = client.build();
+    request.get_email_submission().ids([&email_submission_id]);
+    let state = request
+        .send_get_email_submission()
+        .await
+        .unwrap()
+        .state()
+        .to_string();
+
+    // Deliver a DSN referencing the envelope id
+    deliver_report(
+        &server,
+        concat!(
+            "From: MAILER-DAEMON@remote.org\r\n",
+            "To: jdoe@example.com\r\n",
+            "Message-ID: <dsn-test@remote.org>\r\n",
+            "Subject: Delivery Status Notification\r\n",
+            "Content-Type: multipart/report; report-type=\"delivery-status\";\r\n",
+            "\tboundary=\"bnd\"\r\n",
+            "\r\n",
+            "--bnd\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Your message was delivered.\r\n",
+            "--bnd\r\n",
+            "Content-Type: message/delivery-status\r\n",
+            "\r\n",
+            "Reporting-MTA: dns;mx.remote.org\r\n",
+            "Original-Envelope-Id: report-test-envid\r\n",
+            "\r\n",
+            "Final-Recipient: rfc822;jane_smith@remote.org\r\n",
+            "Action: delivered\r\n",
+            "Status: 2.0.0\r\n",
+            "Diagnostic-Code: smtp;250 2.0.0 Message accepted\r\n",
+            "\r\n",
+            "--bnd--\r\n"
+        ),
+    )
+    .await;
+    let changes = client.email_submission_changes(&state, 0).await.unwrap();
+    assert_eq!(changes.updated(), [email_submission_id.clone()]);
+    let state = changes.new_state().to_string();
+    let email_submission = client
+        .email_submission_get(&email_submission_id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email_submission.dsn_blob_ids().unwrap().len(), 1);
+    assert_eq!(
+        email_submission.mdn_blob_ids().unwrap_or_default(),
+        &[] as &[String]
+    );
+    assert!(String::from_utf8(
+        client
+            .download(&email_submission.dsn_blob_ids().unwrap()[0])
+            .await
+            .unwrap()
+    )
+    .unwrap()
+    .contains("Original-Envelope-Id: report-test-envid"));
+    assert_eq!(
+        email_submission.delivery_status().unwrap(),
+        &AHashMap::from_iter([(
+            "jane_smith@remote.org".to_string(),
+            DeliveryStatus::new(
+                "250 2.0.0 Message accepted",
+                Delivered::Yes,
+                Displayed::Unknown
+            )
+        )])
+    );
+
+    // Deliver an MDN referencing the Message-ID
+    deliver_report(
+        &server,
+        concat!(
+            "From: jane_smith@remote.org\r\n",
+            "To: jdoe@example.com\r\n",
+            "Message-ID: <mdn-test@remote.org>\r\n",
+            "Subject: Read: hey\r\n",
+            "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+            "\tboundary=\"bnd\"\r\n",
+            "\r\n",
+            "--bnd\r\n",
+            "Content-Type: text/plain\r\n",
+            "\r\n",
+            "Your message was displayed.\r\n",
+            "--bnd\r\n",
+            "Content-Type: message/disposition-notification\r\n",
+            "\r\n",
+            "Reporting-UA: mail.remote.org; Mail Client\r\n",
+            "Final-Recipient: rfc822;jane_smith@remote.org\r\n",
+            "Original-Message-ID: <report-test@example.com>\r\n",
+            "Disposition: manual-action/MDN-sent-manually; displayed\r\n",
+            "\r\n",
+            "--bnd--\r\n"
+        ),
+    )
+    .await;
+    let changes = client.email_submission_changes(&state, 0).await.unwrap();
+    assert_eq!(changes.updated(), [email_submission_id.clone()]);
+    let email_submission = client
+        .email_submission_get(&email_submission_id, None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email_submission.dsn_blob_ids().unwrap().len(), 1);
+    assert_eq!(email_submission.mdn_blob_ids().unwrap().len(), 1);
+    assert!(String::from_utf8(
+        client
+            .download(&email_submission.mdn_blob_ids().unwrap()[0])
+            .await
+            .unwrap()
+    )
+    .unwrap()
+    .contains("Original-Message-ID: <report-test@example.com>"));
+    assert_eq!(
+        email_submission.delivery_status().unwrap(),
+        &AHashMap::from_iter([(
+            "jane_smith@remote.org".to_string(),
+            DeliveryStatus::new("250 2.0.0 Message accepted", Delivered::Yes, Displayed::Yes)
+        )])
+    );
+
     // Verify onSuccessUpdateEmail action
     let mut request = client.build();
     let set_request = request.set_email_submission();
@@ -510,6 +680,39 @@ pub async fn test(params: &mut JMAPTest) {
     assert_is_empty(server).await;
 }
 
+async fn deliver_report(server: &JMAP, raw_message: &str) {
+    let blob_hash = BlobHash::from(raw_message.as_bytes());
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(0).set(
+        BlobOp::Reserve {
+            hash: blob_hash.clone(),
+            until: now() + 60,
+        },
+        0u32.serialize(),
+    );
+    server.store.write(batch.build()).await.unwrap();
+    server
+        .blob_store
+        .put_blob(blob_hash.as_ref(), raw_message.as_bytes())
+        .await
+        .unwrap();
+
+    let result = server
+        .deliver_message(IngestMessage {
+            sender_address: String::new(),
+            recipients: vec!["jdoe@example.com".to_string()],
+            message_blob: blob_hash,
+            message_size: raw_message.len(),
+            sender_authenticated: false,
+            author_authenticated: false,
+        })
+        .await;
+    assert!(
+        matches!(result.as_slice(), [DeliveryResult::Success]),
+        "{result:?}"
+    );
+}
+
 pub fn spawn_mock_smtp_server() -> (mpsc::Receiver<MockMessage>, Arc<Mutex<MockSMTPSettings>>) {
     // Create channels
     let (event_tx, event_rx) = mpsc::channel::<MockMessage>(100);
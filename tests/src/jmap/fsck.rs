@@ -0,0 +1,209 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap::mailbox::INBOX_ID;
+use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Value};
+use store::write::{BatchBuilder, F_CLEAR, F_INDEX};
+
+use crate::jmap::{
+    admin_request, assert_is_empty, mailbox::destroy_all_mailboxes, test_account_login,
+    wait_for_index,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running store consistency check tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("fsck@example.com", "secret", "Fsck Test")
+        .await;
+    let account_id = server
+        .store
+        .get_or_create_account_id("fsck@example.com")
+        .await
+        .unwrap();
+    let client = test_account_login("fsck@example.com", "secret").await;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let document_id = Id::from_bytes(
+        client
+            .email_import(
+                b"From: bill@example.com\r\nSubject: Consistency\r\n\r\nCheck me.\r\n".to_vec(),
+                [&inbox_id],
+                Some(["$seen"]),
+                Some(1000000000),
+            )
+            .await
+            .unwrap()
+            .take_id()
+            .as_bytes(),
+    )
+    .unwrap()
+    .document_id();
+
+    // A consistent store reports no issues
+    let report = server.fsck_account(account_id, false).await.unwrap();
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+
+    // Break a mailbox bitmap, a keyword bitmap and the received at index
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::Email)
+        .update_document(document_id)
+        .tag(Property::MailboxIds, INBOX_ID, F_CLEAR)
+        .tag(Property::Keywords, Keyword::Seen, F_CLEAR)
+        .value(Property::ReceivedAt, 1000000000u64, F_INDEX | F_CLEAR);
+    server.store.write(batch.build()).await.unwrap();
+
+    // Checking without repair reports the issues but leaves the store untouched
+    let report = server.fsck_account(account_id, false).await.unwrap();
+    let issues = serde_json::to_value(&report.issues).unwrap();
+    for (typ, property) in [
+        ("mailboxBitmap", "id"),
+        ("keywordBitmap", "keyword"),
+        ("missingIndex", "property"),
+    ] {
+        assert!(
+            issues
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|issue| issue["type"] == json!(typ) && issue.get(property).is_some()),
+            "{typ} not found in {issues}"
+        );
+    }
+    assert!(!report.repaired);
+    assert_eq!(
+        server
+            .fsck_account(account_id, false)
+            .await
+            .unwrap()
+            .issues
+            .len(),
+        report.issues.len()
+    );
+
+    // Repair must be requested with POST
+    let (status, _) = admin_request(
+        Method::GET,
+        "store/fsck?account=fsck@example.com&repair=true",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        server
+            .fsck_account(account_id, false)
+            .await
+            .unwrap()
+            .issues
+            .len(),
+        report.issues.len()
+    );
+
+    // Run the repair in the background and wait for it to finish
+    let (status, started) = admin_request(
+        Method::POST,
+        "store/fsck?account=fsck@example.com&repair=true",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{started}");
+    assert_eq!(started["data"]["total"], json!(1));
+    assert_eq!(started["data"]["repair"], json!(true));
+    let status = wait_for_fsck().await;
+    assert_eq!(status["checked"], json!(1));
+    assert_eq!(status["error"], Value::Null);
+    assert_eq!(status["reports"][0]["accountId"], json!(account_id));
+    assert_eq!(status["reports"][0]["repaired"], json!(true));
+
+    // The store is consistent again
+    let report = server.fsck_account(account_id, false).await.unwrap();
+    assert!(report.issues.is_empty(), "{:?}", report.issues);
+    let email = client
+        .email_query(
+            jmap_client::email::query::Filter::in_mailbox(&inbox_id).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap();
+    assert_eq!(email.ids().len(), 1);
+
+    // Rebuild the full-text index in the background
+    let (status, _) = admin_request(Method::GET, "store/reindex", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, started) =
+        admin_request(Method::POST, "store/reindex?account=fsck@example.com", None).await;
+    assert_eq!(status, StatusCode::OK, "{started}");
+    assert_eq!(started["data"]["total"], json!(1));
+    let status = wait_for_reindex().await;
+    assert_eq!(status["queued"], json!(1));
+    assert_eq!(status["error"], Value::Null);
+    wait_for_index(&server).await;
+    let email = client
+        .email_query(
+            jmap_client::email::query::Filter::text("consistency").into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap();
+    assert_eq!(email.ids().len(), 1);
+
+    // Remove test data
+    params
+        .client
+        .set_default_account_id(Id::from(account_id).to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn wait_for_fsck() -> Value {
+    for _ in 0..100 {
+        let (status, response) = admin_request(Method::GET, "store/fsck", None).await;
+        assert_eq!(status, StatusCode::OK, "{response}");
+        if response["data"]["isRunning"] == json!(false) {
+            return response["data"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Store consistency check did not finish.");
+}
+
+async fn wait_for_reindex() -> Value {
+    for _ in 0..100 {
+        let (status, response) = admin_request(Method::GET, "store/reindex", None).await;
+        assert_eq!(status, StatusCode::OK, "{response}");
+        if response["data"]["isRunning"] == json!(false) {
+            return response["data"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Full-text index rebuild did not finish.");
+}
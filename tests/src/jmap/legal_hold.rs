@@ -0,0 +1,291 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::{
+    backend::internal::manage::ManageDirectory,
+    core::retention::{RetentionAction, RetentionRule},
+    DirectoryError, ManagementError, QueryBy,
+};
+use std::time::Duration;
+
+use jmap::{mailbox::INBOX_ID, JMAP};
+use jmap_client::mailbox::Role;
+use jmap_proto::types::id::Id;
+use reqwest::{Method, StatusCode};
+use serde_json::json;
+use store::{
+    write::{log::ChangeLogBuilder, ValueClass},
+    IterateParams, ValueKey,
+};
+use utils::BlobHash;
+
+use crate::jmap::{
+    admin_request, assert_is_empty, auth_acl::assert_forbidden, mailbox::destroy_all_mailboxes,
+    manage_request, test_account_login,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running legal hold tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("karen@example.com", "secret123", "Karen Foobar")
+        .await;
+    let account_id = server
+        .store
+        .get_or_create_account_id("karen@example.com")
+        .await
+        .unwrap();
+    let client = test_account_login("karen@example.com", "secret123").await;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+    let mailbox_id = client
+        .mailbox_create("Evidence", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+
+    // Import messages
+    let mut email_ids = Vec::new();
+    for mailbox_id in [&inbox_id, &mailbox_id] {
+        email_ids.push(
+            client
+                .email_import(
+                    b"From: bill@example.com\r\nSubject: Quarterly figures\r\n\r\nSee attached.\r\n"
+                        .to_vec(),
+                    [mailbox_id],
+                    None::<Vec<&str>>,
+                    Some(1000000000),
+                )
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+
+    // Domain administrators can inspect but not place legal holds
+    params
+        .directory
+        .create_test_user_with_email("domain-admin@example.com", "secret", "Domain Admin")
+        .await;
+    let (status, response) = manage_request(
+        "domain-admin@example.com",
+        "secret",
+        Method::GET,
+        "legal-hold/karen@example.com",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{response}");
+    assert_eq!(response["data"]["enabled"], json!(false));
+    for method in [Method::PUT, Method::DELETE] {
+        let (status, response) = manage_request(
+            "domain-admin@example.com",
+            "secret",
+            method,
+            "legal-hold/karen@example.com",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{response}");
+    }
+
+    // Routes that do not map to a permission are forbidden
+    let (status, _) = manage_request(
+        "domain-admin@example.com",
+        "secret",
+        Method::GET,
+        "unknown/route",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Delete a message without keeping a recoverable copy for long
+    let deleted_message =
+        b"From: bill@example.com\r\nSubject: Draft figures\r\n\r\nPlease discard.\r\n";
+    let deleted_id = client
+        .email_import(
+            deleted_message.to_vec(),
+            [&inbox_id],
+            None::<Vec<&str>>,
+            Some(1000000000),
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let mut changelog = ChangeLogBuilder::new();
+    changelog.merge(
+        server
+            .email_delete_with_hold(
+                account_id,
+                Id::from_bytes(deleted_id.as_bytes()).unwrap().document_id(),
+                Some(Duration::ZERO),
+            )
+            .await
+            .unwrap()
+            .unwrap(),
+    );
+    server.commit_changes(account_id, changelog).await.unwrap();
+    let deleted_hash = BlobHash::from(deleted_message.as_slice());
+    assert_eq!(deleted_item_count(&server, account_id).await, 1);
+
+    // Place the account under legal hold
+    assert_eq!(server.store.legal_hold(account_id).await.unwrap(), None);
+    let (status, response) = admin_request(Method::PUT, "legal-hold/karen@example.com", None).await;
+    assert_eq!(status, StatusCode::OK, "{response}");
+    assert!(server.store.legal_hold(account_id).await.unwrap().is_some());
+
+    // Messages cannot be expunged
+    assert_forbidden(client.email_destroy(&email_ids[0]).await);
+    assert_forbidden(client.mailbox_destroy(&mailbox_id, true).await);
+
+    // Retention rules are not applied
+    server
+        .directory
+        .set_retention_rules(
+            account_id,
+            vec![RetentionRule {
+                role: Some("inbox".to_string()),
+                mailbox: None,
+                action: RetentionAction::Delete,
+                after: Some("1d".to_string()),
+                move_to: None,
+            }],
+        )
+        .await
+        .unwrap();
+    server
+        .apply_account_retention_rules(account_id)
+        .await
+        .unwrap();
+    for email_id in &email_ids {
+        assert!(client
+            .email_get(email_id, None::<Vec<_>>)
+            .await
+            .unwrap()
+            .is_some());
+    }
+    server
+        .directory
+        .set_retention_rules(account_id, Vec::new())
+        .await
+        .unwrap();
+
+    // Expired recoverable items and their blobs are not purged
+    server.store.purge_store().await.unwrap();
+    server
+        .store
+        .purge_blobs(server.blob_store.clone())
+        .await
+        .unwrap();
+    assert_eq!(deleted_item_count(&server, account_id).await, 1);
+    assert!(server
+        .blob_store
+        .get_blob(deleted_hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_some());
+
+    // The account cannot be deleted or purged
+    assert!(matches!(
+        server
+            .directory
+            .delete_account(QueryBy::Id(account_id))
+            .await,
+        Err(DirectoryError::Management(ManagementError::LegalHold(_)))
+    ));
+    assert!(server.store.purge_account(account_id).await.is_err());
+    assert!(client
+        .email_get(&email_ids[0], None::<Vec<_>>)
+        .await
+        .unwrap()
+        .is_some());
+
+    // Release the legal hold and expunge messages
+    server
+        .store
+        .set_legal_hold(account_id, false)
+        .await
+        .unwrap();
+    assert_eq!(server.store.legal_hold(account_id).await.unwrap(), None);
+    client.email_destroy(&email_ids[0]).await.unwrap();
+    client.mailbox_destroy(&mailbox_id, true).await.unwrap();
+
+    // Expired recoverable items are purged once the hold is lifted
+    server.store.purge_store().await.unwrap();
+    server
+        .store
+        .purge_blobs(server.blob_store.clone())
+        .await
+        .unwrap();
+    assert_eq!(deleted_item_count(&server, account_id).await, 0);
+    assert!(server
+        .blob_store
+        .get_blob(deleted_hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Remove test data
+    params
+        .client
+        .set_default_account_id(Id::from(account_id).to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn deleted_item_count(server: &JMAP, account_id: u32) -> usize {
+    let mut count = 0;
+    server
+        .store
+        .iterate(
+            IterateParams::new(
+                ValueKey {
+                    account_id,
+                    collection: 0,
+                    document_id: 0,
+                    class: ValueClass::DeletedItem { expires: 0, id: 0 },
+                },
+                ValueKey {
+                    account_id,
+                    collection: 0,
+                    document_id: 0,
+                    class: ValueClass::DeletedItem {
+                        expires: u64::MAX,
+                        id: u64::MAX,
+                    },
+                },
+            )
+            .ascending()
+            .no_values(),
+            |_, _| {
+                count += 1;
+                Ok(true)
+            },
+        )
+        .await
+        .unwrap();
+    count
+}
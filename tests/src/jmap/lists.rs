@@ -0,0 +1,382 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Instant;
+
+use jmap::{
+    list::{FromRewrite, ListSettings, PostingPolicy},
+    JMAP,
+};
+use reqwest::{Method, StatusCode};
+use store::{
+    write::{now, BatchBuilder, BlobOp, ListClass, ValueClass},
+    Serialize,
+};
+use tokio::sync::mpsc;
+use utils::{
+    ipc::{DeliveryResult, IngestMessage},
+    BlobHash,
+};
+
+use crate::jmap::{
+    admin_request, assert_is_empty,
+    email_submission::{
+        expect_message_delivery, expect_nothing, spawn_mock_smtp_server, MockMessage,
+    },
+};
+
+use super::JMAPTest;
+
+#[derive(Debug, Clone, Copy)]
+enum Auth {
+    None,
+    Sender,
+    Author,
+}
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running mailing list tests...");
+    let server = params.server.clone();
+    let (mut smtp_rx, smtp_settings) = spawn_mock_smtp_server();
+    server.smtp.resolvers.dns.ipv4_add(
+        "localhost",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + std::time::Duration::from_secs(10),
+    );
+    params
+        .directory
+        .create_test_list("team@example.com", "Team")
+        .await;
+    let list = server
+        .directory
+        .query_list("team@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Subscription requests have to be confirmed
+    assert_success(
+        deliver(
+            &server,
+            "team+subscribe@example.com",
+            "jane@remote.org",
+            Auth::None,
+            "Subscribe",
+        )
+        .await,
+    );
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(notice.rcpt_to, vec!["<jane@remote.org>"]);
+    let confirm = command_address(&notice, "+confirm-");
+    assert!(!server
+        .is_list_subscriber(list.id, "jane@remote.org")
+        .await
+        .unwrap());
+    assert_success(deliver(&server, &confirm, "jane@remote.org", Auth::None, "Confirm").await);
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(notice.rcpt_to, vec!["<jane@remote.org>"]);
+    assert!(notice.message.contains("is now subscribed"));
+    assert!(server
+        .is_list_subscriber(list.id, "jane@remote.org")
+        .await
+        .unwrap());
+
+    // Confirmation tokens can only be used once
+    assert_success(deliver(&server, &confirm, "jane@remote.org", Auth::None, "Confirm").await);
+    expect_nothing(&mut smtp_rx).await;
+    server
+        .add_list_subscriber(list.id, "bill@remote.org")
+        .await
+        .unwrap();
+
+    // Members policy: only authenticated members may post
+    set_posting(&server, list.id, PostingPolicy::Members).await;
+    for (from, auth) in [
+        ("jane@remote.org", Auth::None),
+        ("mike@remote.org", Auth::Author),
+        ("mike@remote.org", Auth::Sender),
+    ] {
+        assert_rejected(deliver(&server, "team@example.com", from, auth, "Members").await);
+    }
+    for auth in [Auth::Author, Auth::Sender] {
+        assert_success(
+            deliver(
+                &server,
+                "team@example.com",
+                "jane@remote.org",
+                auth,
+                "Members",
+            )
+            .await,
+        );
+        assert_distributed(&mut smtp_rx, "Members").await;
+    }
+
+    // Open policy: anyone may post
+    set_posting(&server, list.id, PostingPolicy::Open).await;
+    assert_success(
+        deliver(
+            &server,
+            "team@example.com",
+            "mike@remote.org",
+            Auth::None,
+            "Open",
+        )
+        .await,
+    );
+    assert_distributed(&mut smtp_rx, "Open").await;
+
+    // Announce policy: only authenticated moderators may post
+    set_posting(&server, list.id, PostingPolicy::Announce).await;
+    for (from, auth) in [
+        ("mod@remote.org", Auth::None),
+        ("jane@remote.org", Auth::Sender),
+    ] {
+        assert_rejected(deliver(&server, "team@example.com", from, auth, "Announce").await);
+    }
+    assert_success(
+        deliver(
+            &server,
+            "team@example.com",
+            "mod@remote.org",
+            Auth::Sender,
+            "Announce",
+        )
+        .await,
+    );
+    assert_distributed(&mut smtp_rx, "Announce").await;
+
+    // Moderated policy: posts are held until a moderator approves them
+    set_posting(&server, list.id, PostingPolicy::Moderated).await;
+    assert_success(
+        deliver(
+            &server,
+            "team@example.com",
+            "mod@remote.org",
+            Auth::Author,
+            "Moderated by moderator",
+        )
+        .await,
+    );
+    assert_distributed(&mut smtp_rx, "Moderated by moderator").await;
+    assert_success(
+        deliver(
+            &server,
+            "team@example.com",
+            "jane@remote.org",
+            Auth::Sender,
+            "Moderated approve",
+        )
+        .await,
+    );
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(notice.rcpt_to, vec!["<mod@remote.org>"]);
+    let approve = command_address(&notice, "+approve-");
+    assert_eq!(server.list_held_messages(list.id).await.unwrap().len(), 1);
+
+    // Only moderators can approve held messages
+    assert_success(deliver(&server, &approve, "jane@remote.org", Auth::None, "Approve").await);
+    expect_nothing(&mut smtp_rx).await;
+    assert_eq!(server.list_held_messages(list.id).await.unwrap().len(), 1);
+    assert_success(deliver(&server, &approve, "mod@remote.org", Auth::None, "Approve").await);
+    assert_distributed(&mut smtp_rx, "Moderated approve").await;
+    assert_eq!(server.list_held_messages(list.id).await.unwrap().len(), 0);
+
+    // Rejected messages are not distributed and the author is notified
+    assert_success(
+        deliver(
+            &server,
+            "team@example.com",
+            "jane@remote.org",
+            Auth::None,
+            "Moderated reject",
+        )
+        .await,
+    );
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    let reject = command_address(&notice, "+reject-");
+    assert_success(deliver(&server, &reject, "mod@remote.org", Auth::None, "Reject").await);
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(notice.rcpt_to, vec!["<jane@remote.org>"]);
+    assert!(notice.message.contains("was rejected by a moderator"));
+    assert_eq!(server.list_held_messages(list.id).await.unwrap().len(), 0);
+
+    // One-click unsubscription requires a valid token
+    let token = server.list_unsubscribe_token(list.id, "bill@remote.org");
+    for (token, expected_status) in [
+        ("0".repeat(token.len()), StatusCode::NOT_FOUND),
+        (token[..token.len() - 1].to_string(), StatusCode::NOT_FOUND),
+        (token, StatusCode::OK),
+    ] {
+        assert_eq!(
+            admin_request(
+                Method::POST,
+                &format!(
+                    "unsubscribe?list={}&address=bill%40remote.org&token={token}",
+                    list.id
+                ),
+                None,
+            )
+            .await
+            .0,
+            expected_status
+        );
+    }
+    assert!(!server
+        .is_list_subscriber(list.id, "bill@remote.org")
+        .await
+        .unwrap());
+
+    // Unsubscription requests have to be confirmed as well
+    assert_success(
+        deliver(
+            &server,
+            "team+unsubscribe@example.com",
+            "jane@remote.org",
+            Auth::None,
+            "Unsubscribe",
+        )
+        .await,
+    );
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    let confirm = command_address(&notice, "+confirm-");
+    smtp_settings.lock().do_stop = true;
+    assert_success(deliver(&server, &confirm, "jane@remote.org", Auth::None, "Confirm").await);
+    let notice = expect_message_delivery(&mut smtp_rx).await;
+    assert!(notice.message.contains("has been removed from the list"));
+    assert!(server
+        .list_stored_subscribers(list.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Remove test data
+    let mut batch = BatchBuilder::new();
+    batch.clear(ValueClass::List(ListClass::Settings(list.id)));
+    server.store.write(batch.build()).await.unwrap();
+    assert_is_empty(server).await;
+}
+
+async fn deliver(
+    server: &JMAP,
+    rcpt: &str,
+    from: &str,
+    auth: Auth,
+    subject: &str,
+) -> DeliveryResult {
+    let raw_message =
+        format!("From: {from}\r\nTo: {rcpt}\r\nSubject: {subject}\r\n\r\nMessage from {from}.\r\n");
+    let blob_hash = BlobHash::from(raw_message.as_bytes());
+    let mut batch = BatchBuilder::new();
+    batch.with_account_id(0).set(
+        BlobOp::Reserve {
+            hash: blob_hash.clone(),
+            until: now() + 60,
+        },
+        0u32.serialize(),
+    );
+    server.store.write(batch.build()).await.unwrap();
+    server
+        .blob_store
+        .put_blob(blob_hash.as_ref(), raw_message.as_bytes())
+        .await
+        .unwrap();
+
+    server
+        .deliver_message(IngestMessage {
+            sender_address: from.to_string(),
+            recipients: vec![rcpt.to_string()],
+            message_blob: blob_hash,
+            message_size: raw_message.len(),
+            sender_authenticated: matches!(auth, Auth::Sender),
+            author_authenticated: matches!(auth, Auth::Author),
+        })
+        .await
+        .pop()
+        .unwrap()
+}
+
+async fn set_posting(server: &JMAP, list_id: u32, posting: PostingPolicy) {
+    server
+        .set_list_settings(
+            list_id,
+            ListSettings {
+                posting,
+                moderators: vec!["mod@remote.org".to_string()],
+                from_rewrite: FromRewrite::Never,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+}
+
+async fn assert_distributed(smtp_rx: &mut mpsc::Receiver<MockMessage>, subject: &str) {
+    let mut rcpts = Vec::new();
+    for _ in 0..2 {
+        let message = expect_message_delivery(smtp_rx).await;
+        assert_eq!(message.mail_from, "<team+bounces@example.com>");
+        assert!(
+            message.message.contains(&format!("Subject: {subject}")),
+            "{}",
+            message.message
+        );
+        assert!(message
+            .message
+            .contains("List-Id: \"Team\" <team.example.com>"));
+        rcpts.extend(message.rcpt_to);
+    }
+    rcpts.sort_unstable();
+    assert_eq!(rcpts, vec!["<bill@remote.org>", "<jane@remote.org>"]);
+}
+
+fn command_address(message: &MockMessage, command: &str) -> String {
+    let token = message
+        .message
+        .split_once(command)
+        .map(|(_, token)| {
+            token
+                .chars()
+                .take_while(|ch| ch.is_ascii_alphanumeric())
+                .collect::<String>()
+        })
+        .unwrap_or_else(|| panic!("Command {command} not found in {}", message.message));
+    format!("team{command}{token}@example.com")
+}
+
+fn assert_success(result: DeliveryResult) {
+    assert!(matches!(result, DeliveryResult::Success), "{result:?}");
+}
+
+fn assert_rejected(result: DeliveryResult) {
+    assert!(
+        matches!(
+            result,
+            DeliveryResult::PermanentFailure {
+                code: [5, 7, 1],
+                ..
+            }
+        ),
+        "{result:?}"
+    );
+}
@@ -0,0 +1,317 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Instant;
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap_client::{email::Property, mailbox::Role};
+use jmap_proto::types::id::Id;
+
+use crate::jmap::{
+    assert_is_empty,
+    email_submission::{expect_message_delivery, expect_nothing, spawn_mock_smtp_server},
+    jmap_json_request,
+    mailbox::destroy_all_mailboxes,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running MDN tests...");
+
+    // Create test account
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("mary@example.com", "12345", "Mary Dunn")
+        .await;
+    let account_id = Id::from(
+        server
+            .store
+            .get_or_create_account_id("mary@example.com")
+            .await
+            .unwrap(),
+    )
+    .to_string();
+    let client = &mut params.client;
+    client.set_default_account_id(&account_id);
+
+    // Start mock SMTP server
+    let (mut smtp_rx, smtp_settings) = spawn_mock_smtp_server();
+    server.smtp.resolvers.dns.ipv4_add(
+        "localhost",
+        vec!["127.0.0.1".parse().unwrap()],
+        Instant::now() + std::time::Duration::from_secs(10),
+    );
+
+    // Import a message requesting a disposition notification
+    let identity_id = client
+        .identity_create("Mary Dunn", "mary@example.com")
+        .await
+        .unwrap()
+        .take_id();
+    let mailbox_id = client
+        .mailbox_create("JMAP MDN", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let email_id = client
+        .email_import(
+            concat!(
+                "From: jane_smith@remote.org\r\n",
+                "To: mary@example.com\r\n",
+                "Message-ID: <mdn-original@remote.org>\r\n",
+                "Disposition-Notification-To: Jane Smith <jane_smith@remote.org>\r\n",
+                "Subject: Meeting notes\r\n",
+                "\r\n",
+                "Please confirm that you have read this.\r\n"
+            )
+            .as_bytes()
+            .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+
+    // Send a disposition notification from the identity
+    let response = mdn_send(&account_id, &identity_id, &email_id).await;
+    let sent = &response["methodResponses"][0][1]["sent"]["k1"];
+    assert_eq!(
+        sent["finalRecipient"], "rfc822; mary@example.com",
+        "{response}"
+    );
+    assert_eq!(
+        sent["originalMessageId"], "<mdn-original@remote.org>",
+        "{response}"
+    );
+    assert_eq!(sent["includeOriginalMessage"], false, "{response}");
+    assert!(
+        sent["reportingUA"]
+            .as_str()
+            .is_some_and(|ua| ua.ends_with("; Stalwart JMAP")),
+        "{response}"
+    );
+    assert_eq!(response["methodResponses"][1][0], "Email/set", "{response}");
+    assert!(
+        response["methodResponses"][1][1]["updated"]
+            .as_object()
+            .is_some_and(|updated| updated.contains_key(&email_id)),
+        "{response}"
+    );
+
+    // The report is addressed to the Disposition-Notification-To address
+    let message = expect_message_delivery(&mut smtp_rx).await;
+    assert_eq!(message.mail_from, "<mary@example.com>");
+    assert_eq!(message.rcpt_to, ["<jane_smith@remote.org>"]);
+    for needle in [
+        "Mary Dunn",
+        "<mary@example.com>",
+        "Subject: Disposition notification: Meeting notes",
+        "In-Reply-To: <mdn-original@remote.org>",
+        "Content-Type: message/disposition-notification",
+        "Final-Recipient: rfc822; mary@example.com",
+        "Original-Message-ID: <mdn-original@remote.org>",
+        "Disposition: manual-action/MDN-sent-manually; displayed",
+    ] {
+        assert!(
+            message.message.contains(needle),
+            "Missing [{needle}] in [{}]",
+            message.message
+        );
+    }
+
+    // The original message is flagged with $MDNSent
+    let mut keywords = client
+        .email_get(&email_id, [Property::Keywords].into())
+        .await
+        .unwrap()
+        .unwrap()
+        .keywords()
+        .into_iter()
+        .map(|keyword| keyword.to_string())
+        .collect::<Vec<_>>();
+    keywords.sort_unstable();
+    assert_eq!(keywords, ["$mdnsent", "$seen"]);
+
+    // A second notification for the same message is refused
+    let response = mdn_send(&account_id, &identity_id, &email_id).await;
+    assert_eq!(
+        response["methodResponses"][0][1]["notSent"]["k1"]["type"], "mdnAlreadySent",
+        "{response}"
+    );
+    assert!(
+        response["methodResponses"][0][1]["sent"].is_null(),
+        "{response}"
+    );
+    expect_nothing(&mut smtp_rx).await;
+
+    // Parse a received disposition notification
+    let sent_email_id = client
+        .email_import(
+            concat!(
+                "From: Mary Dunn <mary@example.com>\r\n",
+                "To: jane_smith@remote.org\r\n",
+                "Message-ID: <mdn-sent@example.com>\r\n",
+                "Subject: Budget\r\n",
+                "\r\n",
+                "Please read this.\r\n"
+            )
+            .as_bytes()
+            .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let mdn_blob_id = client
+        .email_import(
+            concat!(
+                "From: jane_smith@remote.org\r\n",
+                "To: mary@example.com\r\n",
+                "Message-ID: <mdn-received@remote.org>\r\n",
+                "Subject: Read: Budget\r\n",
+                "Content-Type: multipart/report; report-type=disposition-notification;\r\n",
+                "\tboundary=\"bnd\"\r\n",
+                "\r\n",
+                "--bnd\r\n",
+                "Content-Type: text/plain\r\n",
+                "\r\n",
+                "Your message was deleted without being read.\r\n",
+                "--bnd\r\n",
+                "Content-Type: message/disposition-notification\r\n",
+                "\r\n",
+                "Reporting-UA: mail.remote.org; Mail Client\r\n",
+                "Final-Recipient: rfc822; jane_smith@remote.org\r\n",
+                "Original-Message-ID: <mdn-sent@example.com>\r\n",
+                "Disposition: automatic-action/MDN-sent-automatically; deleted\r\n",
+                "\r\n",
+                "--bnd--\r\n"
+            )
+            .as_bytes()
+            .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .blob_id()
+        .unwrap()
+        .to_string();
+    let plain_blob_id = client
+        .email_get(&email_id, [Property::BlobId].into())
+        .await
+        .unwrap()
+        .unwrap()
+        .blob_id()
+        .unwrap()
+        .to_string();
+    let response = jmap_json_request(
+        r#"[[ "MDN/parse", {
+            "accountId": "$$",
+            "blobIds": ["%%", "@@"]
+          }, "0" ]]"#
+            .replace("$$", &account_id)
+            .replace("%%", &mdn_blob_id)
+            .replace("@@", &plain_blob_id),
+        "mary@example.com",
+        "12345",
+    )
+    .await;
+    let result = &response["methodResponses"][0][1];
+    let parsed = &result["parsed"][&mdn_blob_id];
+    assert_eq!(parsed["forEmailId"], sent_email_id.as_str(), "{response}");
+    assert_eq!(parsed["subject"], "Read: Budget", "{response}");
+    assert_eq!(
+        parsed["reportingUA"], "mail.remote.org; Mail Client",
+        "{response}"
+    );
+    assert_eq!(
+        parsed["finalRecipient"], "rfc822; jane_smith@remote.org",
+        "{response}"
+    );
+    assert_eq!(
+        parsed["originalMessageId"], "<mdn-sent@example.com>",
+        "{response}"
+    );
+    assert_eq!(
+        parsed["disposition"],
+        serde_json::json!({
+            "actionMode": "automatic-action",
+            "sendingMode": "mdn-sent-automatically",
+            "type": "deleted"
+        }),
+        "{response}"
+    );
+    assert_eq!(
+        result["notParsable"],
+        serde_json::json!([plain_blob_id]),
+        "{response}"
+    );
+    smtp_settings.lock().do_stop = true;
+
+    // Cleanup
+    let mut request = client.build();
+    request.get_identity();
+    for identity in request.send_get_identity().await.unwrap().take_list() {
+        client
+            .identity_destroy(identity.id().unwrap())
+            .await
+            .unwrap();
+    }
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn mdn_send(account_id: &str, identity_id: &str, email_id: &str) -> serde_json::Value {
+    jmap_json_request(
+        r##"[[ "MDN/send", {
+            "accountId": "$$",
+            "identityId": "%%",
+            "send": {
+                "k1": {
+                    "forEmailId": "@@",
+                    "disposition": {
+                        "actionMode": "manual-action",
+                        "sendingMode": "mdn-sent-manually",
+                        "type": "displayed"
+                    }
+                }
+            },
+            "onSuccessUpdateEmail": {
+                "#k1": { "keywords/$seen": true }
+            }
+          }, "0" ]]"##
+            .replace("$$", account_id)
+            .replace("%%", identity_id)
+            .replace("@@", email_id),
+        "mary@example.com",
+        "12345",
+    )
+    .await
+}
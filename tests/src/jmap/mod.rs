@@ -41,6 +41,7 @@ use utils::{config::ServerProtocol, UnwrapFailure};
 
 use crate::{add_test_certs, directory::DirectoryStore, store::TempDir};
 
+pub mod audit;
 pub mod auth_acl;
 pub mod auth_limits;
 pub mod auth_oauth;
@@ -57,14 +58,23 @@ pub mod email_search_snippet;
 pub mod email_set;
 pub mod email_submission;
 pub mod event_source;
+pub mod fsck;
+pub mod legal_hold;
+pub mod lists;
 pub mod mailbox;
+pub mod mdn;
 pub mod push_subscription;
 pub mod quota;
+pub mod recovery;
+pub mod retention;
+pub mod scim;
+pub mod sharing;
 pub mod sieve_script;
 pub mod stress_test;
 pub mod thread_get;
 pub mod thread_merge;
 pub mod vacation_response;
+pub mod webhooks;
 pub mod websocket;
 
 const SERVER: &str = r#"
@@ -200,6 +210,12 @@ max-objects = 100000
 [jmap.protocol.request]
 max-concurrent = 8
 
+[sieve.untrusted.global.scripts]
+file-before = '''require ["fileinto", "mailbox"];
+    fileinto :create "Before";'''
+file-global = '''require ["fileinto", "mailbox"];
+    fileinto :create "Global";'''
+
 [jmap.protocol.upload]
 max-size = 5000000
 max-concurrent = 4
@@ -236,6 +252,16 @@ emails = "SELECT address FROM emails WHERE name = ? AND type != 'list' ORDER BY
 verify = "SELECT address FROM emails WHERE address LIKE '%' || ? || '%' AND type = 'primary' ORDER BY address LIMIT 5"
 expand = "SELECT p.address FROM emails AS p JOIN emails AS l ON p.name = l.name WHERE p.type = 'primary' AND l.address = ? AND l.type = 'list' ORDER BY p.address LIMIT 50"
 domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
+insert-account = "INSERT INTO accounts (name, type, secret, description, quota, active) VALUES (?, ?, ?, ?, ?, true)"
+update-account = "UPDATE accounts SET type = ?, secret = ?, description = ?, quota = ? WHERE name = ?"
+rename-account = "UPDATE accounts SET name = ? WHERE name = ?"
+delete-account = "DELETE FROM accounts WHERE name = ?"
+insert-email = "INSERT INTO emails (name, address, type) VALUES (?, ?, ?)"
+delete-emails = "DELETE FROM emails WHERE name = ? AND type != 'list'"
+insert-member = "INSERT INTO group_members (name, member_of) VALUES (?, ?)"
+delete-member = "DELETE FROM group_members WHERE name = ? AND member_of = ?"
+list-accounts = "SELECT name, type FROM accounts ORDER BY name"
+list-domains = "SELECT DISTINCT SUBSTR(address, INSTR(address, '@') + 1) FROM emails WHERE address LIKE '%@%' ORDER BY 1"
 
 [directory."auth"]
 type = "sql"
@@ -259,6 +285,24 @@ type = "memory"
 format = "list"
 values = ["remote.org", "foobar.com", "test.com", "other_domain.com"]
 
+[scim]
+token = "scim_provisioning_token"
+
+[authorization.role."example-admin"]
+permissions = ["principal-get", "principal-update", "legal-hold"]
+domains = ["example.com"]
+members = ["domain-admin@example.com"]
+
+[webhook."test"]
+url = "https://127.0.0.1:9001/hook"
+events = ["message.ingested", "auth.failure", "principal.created", "principal.deleted"]
+signature-key = "ovos-moles"
+headers = ["X-Api-Key: webhook-key"]
+allow-invalid-certs = true
+throttle = "100ms"
+attempts.max = 3
+attempts.interval = "500ms"
+
 [oauth]
 key = "parerga_und_paralipomena"
 
@@ -319,8 +363,18 @@ pub async fn jmap_tests() {
     email_submission::test(&mut params).await;
     websocket::test(&mut params).await;
     quota::test(&mut params).await;
+    retention::test(&mut params).await;
+    recovery::test(&mut params).await;
+    sharing::test(&mut params).await;
+    legal_hold::test(&mut params).await;
     crypto::test(&mut params).await;
     blob::test(&mut params).await;
+    scim::test(&mut params).await;
+    webhooks::test(&mut params).await;
+    fsck::test(&mut params).await;
+    audit::test(&mut params).await;
+    lists::test(&mut params).await;
+    mdn::test(&mut params).await;
 
     if delete {
         params.temp_dir.delete();
@@ -410,15 +464,24 @@ async fn init_jmap_tests(store_id: &str, delete_if_exists: bool) -> JMAPTest {
     // Start JMAP and SMTP servers
     servers.bind(&config);
     let (delivery_tx, delivery_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
-    let smtp = SMTP::init(&config, &servers, &stores, &directory, delivery_tx)
-        .await
-        .failed("Invalid configuration file");
+    let (webhook_tx, webhook_rx) = mpsc::channel(IPC_CHANNEL_BUFFER);
+    let smtp = SMTP::init(
+        &config,
+        &servers,
+        &stores,
+        &directory,
+        delivery_tx,
+        webhook_tx,
+    )
+    .await
+    .failed("Invalid configuration file");
     let jmap = JMAP::init(
         &config,
         &stores,
         &directory,
         &mut servers,
         delivery_rx,
+        webhook_rx,
         smtp.clone(),
     )
     .await
@@ -521,6 +584,42 @@ pub async fn jmap_json_request(
     serde_json::from_str(&jmap_raw_request(body, username, secret).await).unwrap()
 }
 
+pub async fn admin_request(
+    method: reqwest::Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    manage_request("admin", "secret", method, path, body).await
+}
+
+pub async fn manage_request(
+    login: &str,
+    secret: &str,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<serde_json::Value>,
+) -> (reqwest::StatusCode, serde_json::Value) {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(10000))
+        .build()
+        .unwrap()
+        .request(method, format!("https://127.0.0.1:8899/api/{path}"))
+        .basic_auth(login, Some(secret));
+    if let Some(body) = body {
+        request = request
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.to_string());
+    }
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    let bytes = response.bytes().await.unwrap();
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null),
+    )
+}
+
 pub fn find_values(string: &str, name: &str) -> Vec<String> {
     let mut last_pos = 0;
     let mut values = Vec::new();
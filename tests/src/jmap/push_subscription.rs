@@ -40,7 +40,7 @@ use jmap::{
         HtmlResponse, StateChangeResponse,
     },
     auth::AccessToken,
-    push::ece::ece_encrypt,
+    push::{ece::ece_encrypt, PushNotification},
 };
 use jmap_client::{mailbox::Role, push_subscription::Keys};
 use jmap_proto::types::{id::Id, type_state::DataType};
@@ -51,7 +51,10 @@ use utils::listener::SessionData;
 
 use crate::{
     add_test_certs,
-    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, test_account_login},
+    jmap::{
+        assert_is_empty, delivery::SmtpConnection, mailbox::destroy_all_mailboxes,
+        test_account_login,
+    },
 };
 
 use super::JMAPTest;
@@ -221,6 +224,47 @@ pub async fn test(params: &mut JMAPTest) {
     assert_state(&mut event_rx, &account_id, &[DataType::Mailbox]).await;
     expect_nothing(&mut event_rx).await;
 
+    // Sieve notifications should be delivered to push subscriptions
+    let script_id = client
+        .sieve_script_create(
+            "test_notify_push",
+            concat!(
+                "require \"enotify\";\r\n",
+                "if valid_notify_method \"https://jmap/push\" {\r\n",
+                "  notify :importance \"1\" :message \"Your boss wrote\" \"https://jmap/push\";\r\n",
+                "}\r\n",
+            ),
+            true,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    SmtpConnection::connect()
+        .await
+        .ingest(
+            "boss@remote.org",
+            &["jdoe@example.com"],
+            concat!(
+                "From: boss@remote.org\r\n",
+                "To: jdoe@example.com\r\n",
+                "Subject: Quarterly numbers\r\n",
+                "\r\n",
+                "Please send them today.\r\n"
+            ),
+        )
+        .await;
+    let notification = expect_notification(&mut event_rx).await;
+    assert_eq!(notification.account_id, account_id);
+    assert_eq!(notification.importance, "high");
+    assert_eq!(notification.message, "Your boss wrote");
+    assert!(notification.email_id.is_some());
+    client.sieve_script_deactivate().await.unwrap();
+    client.sieve_script_destroy(&script_id).await.unwrap();
+    while tokio::time::timeout(Duration::from_millis(1500), event_rx.recv())
+        .await
+        .is_ok()
+    {}
+
     // Destroy mailbox
     client.push_subscription_destroy(&push_id).await.unwrap();
     client.mailbox_destroy(&mailbox_id, true).await.unwrap();
@@ -252,6 +296,7 @@ pub struct PushServer {
 enum PushMessage {
     StateChange(StateChangeResponse),
     Verification(PushVerification),
+    Notification(PushNotification),
 }
 
 impl PushMessage {
@@ -374,6 +419,14 @@ async fn expect_push(event_rx: &mut mpsc::Receiver<PushMessage>) -> PushMessage
     }
 }
 
+async fn expect_notification(event_rx: &mut mpsc::Receiver<PushMessage>) -> PushNotification {
+    loop {
+        if let PushMessage::Notification(notification) = expect_push(event_rx).await {
+            return notification;
+        }
+    }
+}
+
 async fn expect_nothing(event_rx: &mut mpsc::Receiver<PushMessage>) {
     match tokio::time::timeout(Duration::from_millis(1000), event_rx.recv()).await {
         Err(_) => {}
@@ -0,0 +1,177 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap::mailbox::INBOX_ID;
+use jmap_client::email::Property;
+use jmap_proto::types::id::Id;
+
+use crate::jmap::{
+    assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes, test_account_login,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running deleted items recovery tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("sarah@example.com", "secret123", "Sarah Foobar")
+        .await;
+    let account_id = Id::from(
+        server
+            .store
+            .get_or_create_account_id("sarah@example.com")
+            .await
+            .unwrap(),
+    );
+    let client = test_account_login("sarah@example.com", "secret123").await;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+
+    // Import and destroy a message
+    let email_id = client
+        .email_import(
+            b"From: bill@example.com\r\nSubject: Please do not lose me\r\n\r\nImportant.\r\n"
+                .to_vec(),
+            [&inbox_id],
+            Some(["$flagged"]),
+            Some(1700000000),
+        )
+        .await
+        .unwrap()
+        .take_id();
+    let used_quota = server
+        .get_used_quota(account_id.document_id())
+        .await
+        .unwrap();
+    assert!(used_quota > 0);
+    client.email_destroy(&email_id).await.unwrap();
+    assert!(client
+        .email_get(&email_id, None::<Vec<_>>)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        0
+    );
+
+    // The deleted message should be listed along with its original mailboxes and keywords
+    let response = jmap_json_request(
+        r#"[[ "DeletedEmail/get", {
+            "accountId": "$$",
+            "ids": null
+          }, "0" ]]"#
+            .replace("$$", &account_id.to_string()),
+        "sarah@example.com",
+        "secret123",
+    )
+    .await;
+    let list = response["methodResponses"][0][1]["list"]
+        .as_array()
+        .unwrap_or_else(|| panic!("Unexpected response: {response}"));
+    assert_eq!(list.len(), 1, "{response}");
+    let deleted = &list[0];
+    assert_eq!(deleted["subject"], "Please do not lose me", "{response}");
+    assert_eq!(deleted["from"], "bill@example.com", "{response}");
+    assert_eq!(deleted["mailboxIds"][&inbox_id], true, "{response}");
+    assert_eq!(deleted["keywords"]["$flagged"], true, "{response}");
+    let deleted_id = deleted["id"].as_str().unwrap().to_string();
+
+    // Restore the message
+    let response = jmap_json_request(
+        r#"[[ "DeletedEmail/restore", {
+            "accountId": "$$",
+            "ids": ["%%"]
+          }, "0" ]]"#
+            .replace("$$", &account_id.to_string())
+            .replace("%%", &deleted_id),
+        "sarah@example.com",
+        "secret123",
+    )
+    .await;
+    let restored_id = response["methodResponses"][0][1]["restored"][&deleted_id]
+        .as_str()
+        .unwrap_or_else(|| panic!("Unexpected response: {response}"))
+        .to_string();
+    let email = client
+        .email_get(
+            &restored_id,
+            [
+                Property::MailboxIds,
+                Property::Keywords,
+                Property::ReceivedAt,
+            ]
+            .into(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(email.mailbox_ids(), vec![inbox_id.as_str()]);
+    assert_eq!(email.keywords(), vec!["$flagged"]);
+    assert_eq!(email.received_at(), Some(1700000000));
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        used_quota
+    );
+
+    // Restored messages are no longer listed and can't be restored twice
+    let response = jmap_json_request(
+        r#"[[ "DeletedEmail/get", {
+            "accountId": "$$",
+            "ids": null
+          }, "0" ],
+          [ "DeletedEmail/restore", {
+            "accountId": "$$",
+            "ids": ["%%"]
+          }, "1" ]]"#
+            .replace("$$", &account_id.to_string())
+            .replace("%%", &deleted_id),
+        "sarah@example.com",
+        "secret123",
+    )
+    .await;
+    assert_eq!(
+        response["methodResponses"][0][1]["list"]
+            .as_array()
+            .map(|list| list.len()),
+        Some(0),
+        "{response}"
+    );
+    assert_eq!(
+        response["methodResponses"][1][1]["notRestored"][&deleted_id]["type"], "notFound",
+        "{response}"
+    );
+
+    // Remove test data
+    params.client.set_default_account_id(account_id.to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
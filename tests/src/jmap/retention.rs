@@ -0,0 +1,168 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::core::retention::{RetentionAction, RetentionRule};
+use jmap_client::{email::Property, mailbox::Role};
+use jmap_proto::types::id::Id;
+use store::write::now;
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running retention tests...");
+    let server = params.server.clone();
+    let client = &mut params.client;
+    client.set_default_account_id(Id::from(0u64));
+
+    // Create test mailboxes
+    let mut mailbox_ids = Vec::new();
+    for (name, role) in [
+        ("Inbox", Role::Inbox),
+        ("Deleted Items", Role::Trash),
+        ("Archive", Role::Archive),
+        ("Newsletters", Role::None),
+    ] {
+        mailbox_ids.push(
+            client
+                .mailbox_create(name, None::<String>, role)
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+    let [inbox_id, trash_id, archive_id, newsletters_id] =
+        [0, 1, 2, 3].map(|idx| &mailbox_ids[idx]);
+
+    // Import old and recent messages
+    let old = (now() - 90 * 86400) as i64;
+    let recent = (now() - 86400) as i64;
+    let mut email_ids = Vec::new();
+    for (num, (mailboxes, received_at)) in [
+        (vec![trash_id], old),
+        (vec![trash_id], recent),
+        (vec![trash_id, inbox_id], old),
+        (vec![newsletters_id], old),
+        (vec![newsletters_id], recent),
+        (vec![inbox_id], old),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        email_ids.push(
+            client
+                .email_import(
+                    format!(
+                        "From: bill@example.com\r\nSubject: Retention test {num}\r\n\r\nTest message {num}.\r\n"
+                    )
+                    .into_bytes(),
+                    mailboxes,
+                    None::<Vec<String>>,
+                    Some(received_at),
+                )
+                .await
+                .unwrap()
+                .take_id(),
+        );
+    }
+
+    // Set account rules
+    let rules = vec![
+        RetentionRule {
+            role: Some("trash".to_string()),
+            mailbox: None,
+            action: RetentionAction::Delete,
+            after: Some("30d".to_string()),
+            move_to: None,
+        },
+        RetentionRule {
+            role: None,
+            mailbox: Some("Newsletters".to_string()),
+            action: RetentionAction::Move,
+            after: Some("30d".to_string()),
+            move_to: Some("archive".to_string()),
+        },
+        RetentionRule {
+            role: Some("inbox".to_string()),
+            mailbox: None,
+            action: RetentionAction::Keep,
+            after: None,
+            move_to: None,
+        },
+    ];
+    server
+        .directory
+        .set_retention_rules(0, rules.clone())
+        .await
+        .unwrap();
+    assert_eq!(server.directory.retention_rules(0).await.unwrap(), rules);
+
+    // Apply rules and verify that only old messages were expunged or moved
+    server.apply_account_retention_rules(0).await.unwrap();
+    let mut results = Vec::new();
+    for email_id in &email_ids {
+        results.push(
+            client
+                .email_get(email_id, [Property::MailboxIds].into())
+                .await
+                .unwrap()
+                .map(|email| {
+                    let mut ids = email
+                        .mailbox_ids()
+                        .into_iter()
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>();
+                    ids.sort_unstable();
+                    ids
+                }),
+        );
+    }
+    assert_eq!(
+        results,
+        vec![
+            None,
+            Some(vec![trash_id.to_string()]),
+            Some(vec![inbox_id.to_string()]),
+            Some(vec![archive_id.to_string()]),
+            Some(vec![newsletters_id.to_string()]),
+            Some(vec![inbox_id.to_string()]),
+        ]
+    );
+
+    // Clearing the rules removes them from the directory
+    server
+        .directory
+        .set_retention_rules(0, Vec::new())
+        .await
+        .unwrap();
+    assert!(server
+        .directory
+        .retention_rules(0)
+        .await
+        .unwrap()
+        .is_empty());
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
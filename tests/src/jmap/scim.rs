@@ -0,0 +1,291 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::time::Duration;
+
+use reqwest::{header, Method, StatusCode};
+use serde_json::{json, Value};
+
+use super::JMAPTest;
+
+const SCIM_TOKEN: &str = "scim_provisioning_token";
+
+pub async fn test(_params: &mut JMAPTest) {
+    println!("Running SCIM tests...");
+
+    // Requests without a valid token are rejected
+    let (status, _) = scim_request(Method::GET, "ServiceProviderConfig", None, "invalid").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, config) =
+        scim_request(Method::GET, "ServiceProviderConfig", None, SCIM_TOKEN).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(config["patch"]["supported"], json!(true));
+
+    // Create a user
+    let (status, user) = scim_request(
+        Method::POST,
+        "Users",
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "userName": "scim.user",
+            "name": { "givenName": "Scim", "familyName": "User" },
+            "password": "provisioned",
+            "emails": [
+                { "value": "scim.alias@example.com" },
+                { "value": "scim.user@example.com", "primary": true }
+            ],
+            "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User": { "quota": 1024 }
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{user}");
+    let user_id = user["id"].as_str().unwrap().to_string();
+    assert_eq!(user["userName"], json!("scim.user"));
+    assert_eq!(user["displayName"], json!("Scim User"));
+    assert_eq!(user["active"], json!(true));
+    assert_eq!(
+        user["emails"][0],
+        json!({ "value": "scim.user@example.com", "type": "work", "primary": true })
+    );
+    assert_eq!(
+        user["urn:ietf:params:scim:schemas:extension:stalwart:2.0:User"]["quota"],
+        json!(1024)
+    );
+    assert!(user.get("password").is_none());
+
+    // Creating a duplicate user fails
+    let (status, error) = scim_request(
+        Method::POST,
+        "Users",
+        json!({ "userName": "scim.user" }).into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CONFLICT, "{error}");
+    assert_eq!(error["scimType"], json!("uniqueness"));
+
+    // Look up users by name and by filter
+    for (filter, expected) in [
+        ("userName eq \"scim.user\"", 1),
+        ("userName eq \"unknown\"", 0),
+        ("emails[value ew \"@example.com\" and primary eq true]", 1),
+        ("displayName sw \"scim\" and not (active eq false)", 1),
+    ] {
+        let (status, list) = scim_request(
+            Method::GET,
+            &format!(
+                "Users?filter={}",
+                filter
+                    .replace(' ', "%20")
+                    .replace('"', "%22")
+                    .replace('[', "%5B")
+                    .replace(']', "%5D")
+            ),
+            None,
+            SCIM_TOKEN,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{list}");
+        assert_eq!(list["totalResults"], json!(expected), "{filter}");
+    }
+    let (status, error) = scim_request(
+        Method::GET,
+        "Users?filter=userName%20xx%20%22a%22",
+        None,
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["scimType"], json!("invalidFilter"));
+
+    // The new password can be used to authenticate
+    assert_eq!(
+        reqwest_session("scim.user", "provisioned").await,
+        StatusCode::OK
+    );
+
+    // Patch the user using the operations sent by common identity providers
+    let (status, user) = scim_request(
+        Method::PATCH,
+        &format!("Users/{user_id}"),
+        json!({
+            "schemas": ["urn:ietf:params:scim:api:messages:2.0:PatchOp"],
+            "Operations": [
+                { "op": "Replace", "path": "displayName", "value": "Scim Patched" },
+                { "op": "replace", "path": "emails[type eq \"work\" and primary eq true].value", "value": "scim.new@example.com" },
+                { "op": "remove", "path": "emails[value eq \"scim.alias@example.com\"]" },
+                { "op": "add", "value": { "urn:ietf:params:scim:schemas:extension:stalwart:2.0:User": { "quota": 2048 }, "title": "Ignored" } }
+            ]
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{user}");
+    assert_eq!(user["displayName"], json!("Scim Patched"));
+    assert_eq!(
+        user["emails"],
+        json!([{ "value": "scim.new@example.com", "type": "work", "primary": true }])
+    );
+    assert_eq!(
+        user["urn:ietf:params:scim:schemas:extension:stalwart:2.0:User"]["quota"],
+        json!(2048)
+    );
+
+    // Create a group containing the user
+    let (status, group) = scim_request(
+        Method::POST,
+        "Groups",
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:Group"],
+            "displayName": "scim.group",
+            "members": [{ "value": user_id }]
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{group}");
+    let group_id = group["id"].as_str().unwrap().to_string();
+    let (_, user) = scim_request(Method::GET, &format!("Users/{user_id}"), None, SCIM_TOKEN).await;
+    assert_eq!(user["groups"][0]["value"], json!(group_id), "{user}");
+
+    // Remove the user from the group
+    let (status, group) = scim_request(
+        Method::PATCH,
+        &format!("Groups/{group_id}"),
+        json!({
+            "Operations": [
+                { "op": "remove", "path": "members", "value": [{ "value": user_id }] }
+            ]
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{group}");
+    let (_, user) = scim_request(Method::GET, &format!("Users/{user_id}"), None, SCIM_TOKEN).await;
+    assert_eq!(user["groups"], json!([]), "{user}");
+
+    // Users and groups are not interchangeable
+    let (status, _) =
+        scim_request(Method::GET, &format!("Groups/{user_id}"), None, SCIM_TOKEN).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Deactivated users can no longer authenticate
+    let (status, user) = scim_request(
+        Method::PATCH,
+        &format!("Users/{user_id}"),
+        json!({
+            "Operations": [
+                { "op": "replace", "value": { "active": false } }
+            ]
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{user}");
+    assert_eq!(user["active"], json!(false));
+    assert_eq!(
+        reqwest_session("scim.user", "provisioned").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Reactivated users keep their password
+    let (status, user) = scim_request(
+        Method::PATCH,
+        &format!("Users/{user_id}"),
+        json!({
+            "Operations": [
+                { "op": "replace", "path": "active", "value": true }
+            ]
+        })
+        .into(),
+        SCIM_TOKEN,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{user}");
+    assert_eq!(user["active"], json!(true));
+    assert_eq!(
+        reqwest_session("scim.user", "provisioned").await,
+        StatusCode::OK
+    );
+
+    // Delete user and group
+    for path in [format!("Users/{user_id}"), format!("Groups/{group_id}")] {
+        let (status, _) = scim_request(Method::DELETE, &path, None, SCIM_TOKEN).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = scim_request(Method::GET, &path, None, SCIM_TOKEN).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
+
+pub async fn scim_request(
+    method: Method,
+    path: &str,
+    body: Option<Value>,
+    token: &str,
+) -> (StatusCode, Value) {
+    let mut request = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(1000))
+        .build()
+        .unwrap()
+        .request(method, format!("https://127.0.0.1:8899/scim/v2/{path}"))
+        .header(header::AUTHORIZATION, format!("Bearer {token}"));
+    if let Some(body) = body {
+        request = request
+            .header(header::CONTENT_TYPE, "application/scim+json")
+            .body(body.to_string());
+    }
+
+    let response = request.send().await.unwrap();
+    let status = response.status();
+    let bytes = response.bytes().await.unwrap();
+
+    (
+        status,
+        if !bytes.is_empty() {
+            serde_json::from_slice(&bytes).unwrap()
+        } else {
+            Value::Null
+        },
+    )
+}
+
+pub async fn reqwest_session(username: &str, secret: &str) -> StatusCode {
+    reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .timeout(Duration::from_millis(1000))
+        .build()
+        .unwrap()
+        .get("https://127.0.0.1:8899/.well-known/jmap")
+        .basic_auth(username, Some(secret))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
@@ -0,0 +1,173 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use directory::backend::internal::manage::ManageDirectory;
+use jmap::mailbox::INBOX_ID;
+use jmap_client::principal::ACL;
+use jmap_proto::types::id::Id;
+
+use crate::jmap::{
+    assert_is_empty, jmap_json_request, mailbox::destroy_all_mailboxes, test_account_login,
+};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running sharing notification tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("tom@example.com", "secret1", "Tom Sharer")
+        .await;
+    params
+        .directory
+        .create_test_user_with_email("ana@example.com", "secret2", "Ana Sharee")
+        .await;
+    let tom_id = Id::from(
+        server
+            .store
+            .get_or_create_account_id("tom@example.com")
+            .await
+            .unwrap(),
+    );
+    let ana_id = Id::from(
+        server
+            .store
+            .get_or_create_account_id("ana@example.com")
+            .await
+            .unwrap(),
+    );
+    let tom_client = test_account_login("tom@example.com", "secret1").await;
+    let ana_client = test_account_login("ana@example.com", "secret2").await;
+    let inbox_id = Id::from(INBOX_ID).to_string();
+
+    // The principals capability should be advertised in the session
+    assert!(ana_client
+        .session()
+        .has_capability("urn:ietf:params:jmap:principals"));
+
+    // Tom shares his Inbox with Ana
+    tom_client
+        .mailbox_update_acl(&inbox_id, "ana@example.com", [ACL::Read, ACL::ReadItems])
+        .await
+        .unwrap();
+
+    // Ana should have received a share notification
+    let response = jmap_json_request(
+        r##"[[ "ShareNotification/query", {
+            "accountId": "$$",
+            "filter": {"objectType": "Mailbox"}
+          }, "0" ],
+          [ "ShareNotification/get", {
+            "accountId": "$$",
+            "#ids": {
+                "resultOf": "0",
+                "name": "ShareNotification/query",
+                "path": "/ids"
+            }
+          }, "1" ]]"##
+            .replace("$$", &ana_id.to_string()),
+        "ana@example.com",
+        "secret2",
+    )
+    .await;
+    let list = response["methodResponses"][1][1]["list"]
+        .as_array()
+        .unwrap_or_else(|| panic!("Unexpected response: {response}"));
+    assert_eq!(list.len(), 1, "{response}");
+    let notification = &list[0];
+    assert_eq!(notification["objectType"], "Mailbox", "{response}");
+    assert_eq!(
+        notification["objectAccountId"],
+        tom_id.to_string(),
+        "{response}"
+    );
+    assert_eq!(notification["objectId"], inbox_id, "{response}");
+    assert_eq!(notification["name"], "Inbox", "{response}");
+    assert_eq!(
+        notification["changedBy"]["email"], "tom@example.com",
+        "{response}"
+    );
+    assert!(notification["oldRights"].is_null(), "{response}");
+    assert_eq!(
+        notification["newRights"]["mayReadItems"], true,
+        "{response}"
+    );
+    assert_eq!(notification["newRights"]["mayDelete"], false, "{response}");
+    let notification_id = notification["id"].as_str().unwrap().to_string();
+
+    // Notifications are read-only and can only be destroyed
+    let response = jmap_json_request(
+        r#"[[ "ShareNotification/set", {
+            "accountId": "$$",
+            "create": {"a": {"objectType": "Mailbox"}},
+            "destroy": ["%%"]
+          }, "0" ],
+          [ "ShareNotification/query", {
+            "accountId": "$$"
+          }, "1" ]]"#
+            .replace("$$", &ana_id.to_string())
+            .replace("%%", &notification_id),
+        "ana@example.com",
+        "secret2",
+    )
+    .await;
+    assert_eq!(
+        response["methodResponses"][0][1]["notCreated"]["a"]["type"], "forbidden",
+        "{response}"
+    );
+    assert_eq!(
+        response["methodResponses"][0][1]["destroyed"][0], notification_id,
+        "{response}"
+    );
+    assert_eq!(
+        response["methodResponses"][1][1]["ids"]
+            .as_array()
+            .map(|ids| ids.len()),
+        Some(0),
+        "{response}"
+    );
+
+    // Ana should not be able to read Tom's notifications
+    let response = jmap_json_request(
+        r#"[[ "ShareNotification/get", {
+            "accountId": "$$",
+            "ids": null
+          }, "0" ]]"#
+            .replace("$$", &tom_id.to_string()),
+        "ana@example.com",
+        "secret2",
+    )
+    .await;
+    assert_eq!(
+        response["methodResponses"][0][1]["type"], "forbidden",
+        "{response}"
+    );
+
+    // Remove test data
+    for account_id in [tom_id, ana_id] {
+        params.client.set_default_account_id(account_id.to_string());
+        destroy_all_mailboxes(params).await;
+    }
+    assert_is_empty(server).await;
+}
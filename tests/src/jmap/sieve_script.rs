@@ -21,7 +21,7 @@
  * for more details.
 */
 
-use directory::backend::internal::manage::ManageDirectory;
+use directory::{backend::internal::manage::ManageDirectory, core::domain::DomainSettings};
 use jmap_client::{
     core::set::{SetError, SetErrorType},
     email, mailbox,
@@ -487,6 +487,131 @@ pub async fn test(params: &mut JMAPTest) {
         panic!("Email {:?} not found in: {:#?}", subject, emails);
     }
 
+    // Run extlists tests
+    server
+        .add_to_address_book(
+            Id::from_bytes(account_id.as_bytes()).unwrap().document_id(),
+            &["Bill@Remote.org"],
+        )
+        .await;
+    client
+        .sieve_script_create("test_extlists", get_script("test_extlists"), true)
+        .await
+        .unwrap();
+    for (from, subject) in [
+        ("Bill Lumbergh <bill@remote.org>", "Known contact"),
+        ("milton@remote.org", "Unknown contact"),
+    ] {
+        lmtp.ingest(
+            "bill@remote.org",
+            &["jdoe@example.com"],
+            &format!(
+                concat!(
+                    "From: {}\r\n",
+                    "To: jdoe@example.com\r\n",
+                    "Subject: {}\r\n",
+                    "\r\n",
+                    "Have you seen my stapler?"
+                ),
+                from, subject
+            ),
+        )
+        .await;
+    }
+    let mailbox_id = client
+        .mailbox_query(
+            mailbox::query::Filter::name("Contacts").into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .expect("Mailbox Contacts not found");
+    let message_ids = client
+        .email_query(
+            email::query::Filter::in_mailbox(&mailbox_id).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(message_ids.len(), 1, "{:?}", message_ids);
+    assert_eq!(
+        client
+            .email_get(&message_ids[0], [email::Property::Subject].into())
+            .await
+            .unwrap()
+            .unwrap()
+            .subject(),
+        Some("Known contact")
+    );
+
+    // Run global and mandatory script tests
+    server
+        .directory
+        .set_domain_settings(
+            "example.com",
+            DomainSettings {
+                sieve_before: Some("file-before".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    client
+        .sieve_script_create("test_global", get_script("test_global"), true)
+        .await
+        .unwrap();
+    lmtp.ingest(
+        "bill@remote.org",
+        &["jdoe@example.com"],
+        concat!(
+            "From: bill@remote.org\r\n",
+            "To: jdoe@example.com\r\n",
+            "Subject: Corporate rules\r\n",
+            "\r\n",
+            "Did you get the memo?"
+        ),
+    )
+    .await;
+    server
+        .directory
+        .set_domain_settings("example.com", DomainSettings::default())
+        .await
+        .unwrap();
+    let mut mailbox_ids = Vec::new();
+    for folder in ["Before", "Global"] {
+        mailbox_ids.push(
+            client
+                .mailbox_query(mailbox::query::Filter::name(folder).into(), None::<Vec<_>>)
+                .await
+                .unwrap()
+                .take_ids()
+                .pop()
+                .unwrap_or_else(|| panic!("Mailbox {:?} not found", folder)),
+        );
+    }
+    let message_ids = client
+        .email_query(
+            email::query::Filter::in_mailbox(&mailbox_ids[0]).into(),
+            None::<Vec<_>>,
+        )
+        .await
+        .unwrap()
+        .take_ids();
+    assert_eq!(message_ids.len(), 1, "{:?}", message_ids);
+    let email = client
+        .email_get(&message_ids[0], [email::Property::MailboxIds].into())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        email.mailbox_ids().contains(&mailbox_ids[1].as_str()),
+        "{:?}",
+        email.mailbox_ids()
+    );
+
     // Remove test data
     client.sieve_script_deactivate().await.unwrap();
     let mut request = client.build();
@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of the Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use directory::backend::internal::manage::ManageDirectory;
+use hyper::{body, server::conn::http1, service::service_fn, StatusCode};
+use hyper_util::rt::TokioIo;
+use jmap::{
+    api::{
+        http::{fetch_body, ToHttpResponse},
+        HtmlResponse,
+    },
+    auth::AccessToken,
+};
+use jmap_proto::types::id::Id;
+use reqwest::Method;
+use serde_json::{json, Value};
+use store::write::now;
+use tokio::sync::mpsc;
+use utils::listener::SessionData;
+
+use crate::{
+    add_test_certs,
+    jmap::{
+        assert_is_empty,
+        delivery::SmtpConnection,
+        mailbox::destroy_all_mailboxes,
+        scim::{reqwest_session, scim_request},
+    },
+};
+
+use super::JMAPTest;
+
+const SERVER: &str = "
+[server]
+hostname = 'webhook.example.org'
+
+[server.listener.webhook]
+bind = ['127.0.0.1:9001']
+url = 'https://127.0.0.1:9001'
+protocol = 'jmap'
+
+[server.socket]
+reuse-addr = true
+
+[server.tls]
+enable = true
+implicit = false
+certificate = 'default'
+
+[certificate.default]
+cert = 'file://{CERT}'
+private-key = 'file://{PK}'
+";
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running webhook tests...");
+    let server = params.server.clone();
+    params
+        .directory
+        .create_test_user_with_email("ticketing@example.com", "secret", "Ticketing")
+        .await;
+    let account_id = Id::from(
+        server
+            .store
+            .get_or_create_account_id("ticketing@example.com")
+            .await
+            .unwrap(),
+    );
+
+    // Start mock webhook endpoint
+    let (event_tx, mut event_rx) = mpsc::channel::<WebhookRequest>(100);
+    let endpoint = Arc::new(WebhookEndpoint {
+        tx: event_tx,
+        fail_requests: false.into(),
+    });
+    let settings = utils::config::Config::new(&add_test_certs(SERVER)).unwrap();
+    let servers = settings.parse_servers().unwrap();
+    let manager = SessionManager::from(endpoint.clone());
+    servers.bind(&settings);
+    let _shutdown_tx = servers.spawn(|server, shutdown_rx| {
+        server.spawn(manager.clone(), shutdown_rx);
+    });
+
+    // Ingested messages include the envelope and headers
+    SmtpConnection::connect()
+        .await
+        .ingest(
+            "customer@remote.org",
+            &["ticketing@example.com"],
+            concat!(
+                "From: customer@remote.org\r\n",
+                "To: ticketing@example.com\r\n",
+                "Subject: Printer on fire\r\n",
+                "\r\n",
+                "Please advise.\r\n"
+            ),
+        )
+        .await;
+    let event = expect_event(&params.server, &mut event_rx, |event| {
+        event["type"] == "message.ingested" && event["data"]["recipient"] == "ticketing@example.com"
+    })
+    .await;
+    assert_eq!(event["data"]["accountId"], json!(account_id.document_id()));
+    assert_eq!(event["data"]["sender"], "customer@remote.org");
+    assert!(event["data"]["headers"]
+        .as_array()
+        .unwrap()
+        .contains(&json!(["Subject", "Printer on fire"])));
+
+    // Failed deliveries are retried
+    endpoint.fail_requests.store(true, Ordering::Relaxed);
+    assert_eq!(
+        reqwest_session("ticketing@example.com", "wrong_secret").await,
+        StatusCode::UNAUTHORIZED
+    );
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    endpoint.fail_requests.store(false, Ordering::Relaxed);
+    expect_event(&params.server, &mut event_rx, |event| {
+        event["type"] == "auth.failure" && event["data"]["login"] == "ticketing@example.com"
+    })
+    .await;
+
+    // Principal changes
+    let (status, user) = scim_request(
+        Method::POST,
+        "Users",
+        json!({
+            "schemas": ["urn:ietf:params:scim:schemas:core:2.0:User"],
+            "userName": "webhook.user",
+            "password": "webhook_secret"
+        })
+        .into(),
+        "scim_provisioning_token",
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{user}");
+    let event = expect_event(&params.server, &mut event_rx, |event| {
+        event["type"] == "principal.created"
+            && event["data"]["changes"]["userName"] == "webhook.user"
+    })
+    .await;
+    assert_eq!(event["data"]["actor"], "scim");
+    assert_eq!(event["data"]["changes"]["password"], "[redacted]");
+    let user_path = format!("Users/{}", user["id"].as_str().unwrap());
+    let (status, _) =
+        scim_request(Method::DELETE, &user_path, None, "scim_provisioning_token").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    expect_event(&params.server, &mut event_rx, |event| {
+        event["type"] == "principal.deleted" && event["data"]["name"] == user_path.as_str()
+    })
+    .await;
+
+    // Only the batch that exhausted its attempts is discarded
+    endpoint.fail_requests.store(true, Ordering::Relaxed);
+    for login in ["discarded@example.com", "retained@example.com"] {
+        assert_eq!(
+            reqwest_session(login, "wrong_secret").await,
+            StatusCode::UNAUTHORIZED
+        );
+        tokio::time::sleep(Duration::from_millis(300)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(700)).await;
+    endpoint.fail_requests.store(false, Ordering::Relaxed);
+    let events = expect_request(&params.server, &mut event_rx).await;
+    assert_eq!(events.len(), 1, "{events:?}");
+    assert_eq!(events[0]["type"], "auth.failure");
+    assert_eq!(events[0]["data"]["login"], "retained@example.com");
+
+    // Remove test data
+    params.client.set_default_account_id(account_id.to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn expect_event(
+    server: &jmap::JMAP,
+    event_rx: &mut mpsc::Receiver<WebhookRequest>,
+    matches: impl Fn(&Value) -> bool,
+) -> Value {
+    loop {
+        if let Some(event) = expect_request(server, event_rx)
+            .await
+            .into_iter()
+            .find(|event| matches(event))
+        {
+            return event;
+        }
+    }
+}
+
+async fn expect_request(
+    server: &jmap::JMAP,
+    event_rx: &mut mpsc::Receiver<WebhookRequest>,
+) -> Vec<Value> {
+    let request = match tokio::time::timeout(Duration::from_secs(3), event_rx.recv()).await {
+        Ok(Some(request)) => request,
+        result => panic!("Timeout waiting for webhook: {result:?}"),
+    };
+
+    // Verify signature, timestamp and custom headers
+    let timestamp = request
+        .timestamp
+        .as_deref()
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .unwrap();
+    assert!(now().abs_diff(timestamp) <= 60, "{timestamp}");
+    assert_eq!(
+        request.signature,
+        server.smtp.webhooks.hooks[0].sign(timestamp, &request.body)
+    );
+    assert_ne!(
+        request.signature,
+        server.smtp.webhooks.hooks[0].sign(timestamp + 1, &request.body)
+    );
+    assert_eq!(request.api_key.as_deref(), Some("webhook-key"));
+
+    serde_json::from_slice::<Value>(&request.body).unwrap()["events"]
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+#[derive(Debug)]
+struct WebhookRequest {
+    signature: Option<String>,
+    timestamp: Option<String>,
+    api_key: Option<String>,
+    body: Vec<u8>,
+}
+
+struct WebhookEndpoint {
+    tx: mpsc::Sender<WebhookRequest>,
+    fail_requests: AtomicBool,
+}
+
+#[derive(Clone)]
+struct SessionManager {
+    inner: Arc<WebhookEndpoint>,
+}
+
+impl From<Arc<WebhookEndpoint>> for SessionManager {
+    fn from(inner: Arc<WebhookEndpoint>) -> Self {
+        SessionManager { inner }
+    }
+}
+
+impl utils::listener::SessionManager for SessionManager {
+    #[allow(clippy::manual_async_fn)]
+    fn handle<T: utils::listener::SessionStream>(
+        self,
+        session: SessionData<T>,
+    ) -> impl std::future::Future<Output = ()> + Send {
+        async move {
+            let endpoint = self.inner;
+            let _ = http1::Builder::new()
+                .keep_alive(false)
+                .serve_connection(
+                    TokioIo::new(
+                        session
+                            .instance
+                            .acceptor
+                            .accept(session.stream)
+                            .await
+                            .unwrap_tls()
+                            .await
+                            .unwrap(),
+                    ),
+                    service_fn(|mut req: hyper::Request<body::Incoming>| {
+                        let endpoint = endpoint.clone();
+
+                        async move {
+                            if endpoint.fail_requests.load(Ordering::Relaxed) {
+                                return Ok(HtmlResponse::with_status(
+                                    StatusCode::SERVICE_UNAVAILABLE,
+                                    "unavailable".to_string(),
+                                )
+                                .into_http_response());
+                            }
+                            let header = |name: &str| {
+                                req.headers()
+                                    .get(name)
+                                    .and_then(|value| value.to_str().ok())
+                                    .map(|value| value.to_string())
+                            };
+                            let signature = header("X-Signature");
+                            let timestamp = header("X-Signature-Timestamp");
+                            let api_key = header("X-Api-Key");
+                            let body = fetch_body(&mut req, 1024 * 1024, &AccessToken::default())
+                                .await
+                                .unwrap();
+                            endpoint
+                                .tx
+                                .send(WebhookRequest {
+                                    signature,
+                                    timestamp,
+                                    api_key,
+                                    body,
+                                })
+                                .await
+                                .unwrap();
+
+                            Ok::<_, hyper::Error>(
+                                HtmlResponse::new("ok".to_string()).into_http_response(),
+                            )
+                        }
+                    }),
+                )
+                .await;
+        }
+    }
+
+    #[allow(clippy::manual_async_fn)]
+    fn shutdown(&self) -> impl std::future::Future<Output = ()> + Send {
+        async {}
+    }
+
+    fn is_ip_blocked(&self, _: &std::net::IpAddr) -> bool {
+        false
+    }
+}
@@ -48,8 +48,11 @@ pub fn add_test_certs(config: &str) -> String {
     cert.push("tls_cert.pem");
     let mut pk = cert_path.clone();
     pk.push("tls_privatekey.pem");
+    let mut client_ca = cert_path.clone();
+    client_ca.push("tls_client_ca.pem");
 
     config
         .replace("{CERT}", cert.as_path().to_str().unwrap())
         .replace("{PK}", pk.as_path().to_str().unwrap())
+        .replace("{CLIENT_CA}", client_ca.as_path().to_str().unwrap())
 }
@@ -23,7 +23,7 @@
 
 use std::time::Duration;
 
-use directory::core::config::ConfigDirectory;
+use directory::core::{config::ConfigDirectory, domain::DomainSettings};
 use smtp_proto::{RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_SUCCESS};
 use store::Store;
 use utils::config::{if_block::IfBlock, Config};
@@ -31,7 +31,7 @@ use utils::config::{if_block::IfBlock, Config};
 use crate::smtp::{
     inbound::dummy_stores,
     session::{TestSession, VerifyResponse},
-    ParseTestConfig, TestConfig,
+    ParseTestConfig, TestConfig, TestSMTP,
 };
 use smtp::core::{Session, State, SMTP};
 
@@ -174,3 +174,63 @@ async fn rcpt() {
     assert!((rcpt.flags & (RCPT_NOTIFY_DELAY | RCPT_NOTIFY_SUCCESS | RCPT_NOTIFY_FAILURE)) != 0);
     assert_eq!(rcpt.dsn_info.as_ref().unwrap(), "Jane.Doe@Foobar.org");
 }
+
+#[tokio::test]
+async fn rcpt_domain_rate() {
+    let mut core = SMTP::test();
+    let _rx = core.init_test_queue("smtp_rcpt_domain_rate");
+    core.shared.directories = Config::new(DIRECTORY)
+        .unwrap()
+        .parse_directory(&dummy_stores(), core.shared.default_data_store.clone())
+        .await
+        .unwrap()
+        .directories;
+    core.shared
+        .directories
+        .get("local")
+        .unwrap()
+        .set_domain_settings(
+            "foobar.org",
+            DomainSettings {
+                send_rate: Some("2/1s".to_string()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+    let config = &mut core.session.config.rcpt;
+    config.directory = IfBlock::new("local".to_string());
+    config.relay = IfBlock::new(true);
+
+    // The rate is keyed on the authenticated principal's domain
+    let mut session = Session::test(core);
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx1.foobar.org").await;
+    session.data.authenticated_as = "john".to_string();
+    session.data.authenticated_emails = vec!["john@foobar.org".to_string()];
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+    session.rcpt_to("external@domain.com", "250").await;
+    session.rcpt_to("bill@foobar.org", "451 4.4.5").await;
+
+    // Changing the envelope sender does not reset it
+    session.rset().await;
+    session.mail_from("john@otherdomain.net", "250").await;
+    session.rcpt_to("mike@foobar.org", "451 4.4.5").await;
+
+    // Unauthenticated senders are not subject to the domain rate
+    session.rset().await;
+    session.data.authenticated_as.clear();
+    session.data.authenticated_emails.clear();
+    session.mail_from("jane@foobar.org", "250").await;
+    session.rcpt_to("mike@foobar.org", "250").await;
+
+    // Restore rate limit
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    session.rset().await;
+    session.data.authenticated_as = "john".to_string();
+    session.data.authenticated_emails = vec!["john@foobar.org".to_string()];
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("bill@foobar.org", "250").await;
+}
@@ -46,7 +46,7 @@ use smtp::{
         session::{ConfigSession, Mechanism},
         throttle::ConfigThrottle,
         AggregateReport, ArcAuthConfig, Auth, Connect, Data, DkimAuthConfig, DmarcAuthConfig, Dsn,
-        Ehlo, Extensions, IpRevAuthConfig, Mail, MailAuthConfig, Milter, QueueConfig,
+        Ehlo, Extensions, IpRevAuthConfig, Journal, Mail, MailAuthConfig, Milter, QueueConfig,
         QueueOutboundSourceIp, QueueOutboundTimeout, QueueOutboundTls, QueueQuotas, QueueThrottle,
         Rcpt, Report, ReportAnalysis, ReportConfig, SessionConfig, SessionThrottle, SpfAuthConfig,
         Throttle, VerifyStrategy,
@@ -60,6 +60,7 @@ use smtp::{
 use utils::{
     config::{if_block::IfBlock, utils::ConstantValue, Config},
     snowflake::SnowflakeIdGenerator,
+    webhooks::Webhooks,
 };
 
 pub mod config;
@@ -190,6 +191,12 @@ impl TestConfig for SMTP {
             mail_auth: MailAuthConfig::test(),
             report: ReportCore::test(),
             sieve: SieveCore::test(),
+            webhooks: Webhooks {
+                hooks: vec![],
+                events: Default::default(),
+                tx: mpsc::channel(1).0,
+                id_generator: SnowflakeIdGenerator::new(),
+            },
             delivery_tx: mpsc::channel(1).0,
             shared: Shared {
                 scripts: Default::default(),
@@ -203,6 +210,8 @@ impl TestConfig for SMTP {
                     catch_all: AddressMapping::Disable,
                     subaddressing: AddressMapping::Disable,
                     cache: None,
+                    policy: None,
+                    salt_key: Vec::new(),
                     blocked_ips: Arc::new(BlockedIps::new(store.clone().into())),
                 }),
                 default_lookup_store: LookupStore::Store(store.clone()),
@@ -342,6 +351,12 @@ impl TestConfig for QueueConfig {
                 address: IfBlock::new("MAILER-DAEMON@example.org".to_string()),
                 sign: IfBlock::default(),
             },
+            journal: Journal {
+                address: IfBlock::default(),
+                name: IfBlock::new("Journal Service".to_string()),
+                from_address: IfBlock::new("MAILER-DAEMON@example.org".to_string()),
+                sign: IfBlock::default(),
+            },
             timeout: QueueOutboundTimeout {
                 connect: IfBlock::new(Duration::from_secs(1)),
                 greeting: IfBlock::new(Duration::from_secs(1)),
@@ -0,0 +1,113 @@
+/*
+ * Copyright (c) 2023 Stalwart Labs Ltd.
+ *
+ * This file is part of Stalwart Mail Server.
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of
+ * the License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ * in the LICENSE file at the top-level directory of this distribution.
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ *
+ * You can be released from the requirements of the AGPLv3 license by
+ * purchasing a commercial license. Please contact licensing@stalw.art
+ * for more details.
+*/
+
+use utils::config::if_block::IfBlock;
+
+use crate::smtp::{
+    inbound::TestMessage,
+    session::{TestSession, VerifyResponse},
+    ParseTestConfig, TestConfig, TestSMTP,
+};
+use smtp::core::{Session, SMTP};
+
+#[tokio::test]
+async fn journal() {
+    /*tracing::subscriber::set_global_default(
+        tracing_subscriber::FmtSubscriber::builder()
+            .with_max_level(tracing::Level::DEBUG)
+            .finish(),
+    )
+    .unwrap();*/
+
+    let mut core = SMTP::test();
+    let mut qr = core.init_test_queue("smtp_journal_test");
+    core.session.config.rcpt.relay = IfBlock::new(true);
+    core.queue.config.journal.address = r#"[{if = "rcpt_domain = 'foobar.org' || sender = 'jane@domain.net'", then = "'journal@example.org'"},
+    {else = false}]"#
+        .parse_if();
+
+    let core = std::sync::Arc::new(core);
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx.doe.org").await;
+
+    // Messages to journaled recipients should be copied to the journal
+    session
+        .send_message(
+            "john@doe.org",
+            &["bill@foobar.org", "mike@test.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    let journal = qr.consume_message(&core).await;
+    assert_eq!(journal.return_path, "");
+    assert_eq!(
+        journal
+            .recipients
+            .iter()
+            .map(|r| r.address.as_str())
+            .collect::<Vec<_>>(),
+        vec!["journal@example.org"]
+    );
+    journal
+        .read_lines(&qr)
+        .await
+        .assert_contains("X-Journal-Report: 1")
+        .assert_contains("Sender: john@doe.org")
+        .assert_contains("Recipient: bill@foobar.org")
+        .assert_contains("Recipient: mike@test.com")
+        .assert_contains("Remote-IP: 10.0.0.1")
+        .assert_contains("Content-Type: message/rfc822")
+        .assert_contains("Content-Transfer-Encoding: 8bit");
+    let message = qr.consume_message(&core).await;
+    assert_eq!(message.return_path, "john@doe.org");
+    assert_eq!(message.recipients.len(), 2);
+    qr.assert_no_events();
+
+    // Messages from journaled senders should be copied to the journal
+    session
+        .send_message("jane@domain.net", &["mike@test.com"], "test:no_dkim", "250")
+        .await;
+    qr.consume_message(&core)
+        .await
+        .read_lines(&qr)
+        .await
+        .assert_contains("Sender: jane@domain.net")
+        .assert_contains("Recipient: mike@test.com");
+    assert_eq!(
+        qr.consume_message(&core).await.return_path,
+        "jane@domain.net"
+    );
+    qr.assert_no_events();
+
+    // Other messages should not be journaled
+    session
+        .send_message("john@doe.org", &["mike@test.com"], "test:no_dkim", "250")
+        .await;
+    assert_eq!(qr.consume_message(&core).await.return_path, "john@doe.org");
+    qr.assert_no_events();
+    qr.assert_queue_is_empty().await;
+}
@@ -23,5 +23,6 @@
 
 pub mod concurrent;
 pub mod dsn;
+pub mod journal;
 pub mod manager;
 pub mod retry;
@@ -23,9 +23,10 @@
 
 use ahash::AHashMap;
 use store::{
+    backend::tiered::BlobTier,
     config::ConfigStore,
     write::{blob::BlobQuota, now, BatchBuilder, BlobOp},
-    BlobClass, BlobStore, Serialize,
+    BlobBackend, BlobClass, BlobStore, Serialize,
 };
 use utils::{config::Config, BlobHash};
 
@@ -421,6 +422,128 @@ pub async fn blob_tests() {
     temp_dir.delete();
 }
 
+const TIERED_CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/sqlite.db"
+
+[store."hot"]
+type = "fs"
+path = "{TMP}/hot"
+
+[store."cold"]
+type = "fs"
+path = "{TMP}/cold"
+
+[store."tiered"]
+type = "tiered"
+hot = "hot"
+cold = "cold"
+migrate.after = "0s"
+"#;
+
+#[tokio::test]
+pub async fn tiered_blob_tests() {
+    let temp_dir = TempDir::new("tiered_blob_tests", true);
+    let mut config =
+        Config::new(&TIERED_CONFIG.replace("{TMP}", temp_dir.path.as_path().to_str().unwrap()))
+            .unwrap();
+    let stores = config.parse_stores().await.unwrap();
+    let blob_store = stores.blob_stores.get("tiered").unwrap().clone();
+    let hot_store = stores.blob_stores.get("hot").unwrap().clone();
+    let cold_store = stores.blob_stores.get("cold").unwrap().clone();
+    let tiered = if let BlobBackend::Tiered(tiered) = &blob_store.backend {
+        tiered.clone()
+    } else {
+        panic!("Expected tiered blob store");
+    };
+
+    // Tiered stores should behave like any other blob store
+    test_store(blob_store.clone()).await;
+
+    // New blobs are written to the hot tier
+    let hash = BlobHash::from(b"tiered".as_slice());
+    blob_store.put_blob(hash.as_ref(), b"tiered").await.unwrap();
+    assert_eq!(
+        tiered.placement(&hash).await.unwrap().unwrap().tier,
+        BlobTier::Hot
+    );
+    assert!(hot_store
+        .get_blob(hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_some());
+    assert!(cold_store
+        .get_blob(hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Unlinked blobs are left in the hot tier for the purge task
+    assert_eq!(tiered.migrate_blobs().await.unwrap().migrated, 0);
+    assert_eq!(
+        tiered.placement(&hash).await.unwrap().unwrap().tier,
+        BlobTier::Hot
+    );
+
+    // Migrate linked blobs to the cold tier, reads should fall through
+    stores
+        .stores
+        .get("sqlite")
+        .unwrap()
+        .write(
+            BatchBuilder::new()
+                .with_account_id(0)
+                .with_collection(0)
+                .update_document(0)
+                .set(BlobOp::Link { hash: hash.clone() }, vec![])
+                .set(BlobOp::Commit { hash: hash.clone() }, vec![])
+                .build_batch(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(tiered.migrate_blobs().await.unwrap().migrated, 1);
+    assert_eq!(
+        tiered.placement(&hash).await.unwrap().unwrap().tier,
+        BlobTier::Cold
+    );
+    assert!(hot_store
+        .get_blob(hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        blob_store
+            .get_blob(hash.as_ref(), 0..usize::MAX)
+            .await
+            .unwrap()
+            .unwrap(),
+        b"tiered"
+    );
+
+    // Migrated blobs are not written again to the hot tier
+    blob_store.put_blob(hash.as_ref(), b"tiered").await.unwrap();
+    assert!(hot_store
+        .get_blob(hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Deleting a blob removes it from all tiers
+    assert!(blob_store.delete_blob(hash.as_ref()).await.unwrap());
+    assert!(tiered.placement(&hash).await.unwrap().is_none());
+    assert!(blob_store
+        .get_blob(hash.as_ref(), 0..usize::MAX)
+        .await
+        .unwrap()
+        .is_none());
+
+    temp_dir.delete();
+}
+
 async fn test_store(store: BlobStore) {
     // Test small blob
     const DATA: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit. Fusce erat nisl, dignissim a porttitor id, varius nec arcu. Sed mauris.";
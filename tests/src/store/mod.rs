@@ -80,6 +80,10 @@ password = "password"
 type = "redis"
 url = "redis://127.0.0.1"
 
+[store."tantivy"]
+type = "tantivy"
+path = "{TMP}/tantivy"
+
 "#;
 
 #[tokio::test(flavor = "multi_thread")]
@@ -96,13 +100,22 @@ pub async fn store_tests() {
         .get(&store_id)
         .expect("Store not found")
         .clone();
+    let fts_store = if let Ok(fts_id) = std::env::var("FTS") {
+        stores
+            .fts_stores
+            .get(&fts_id)
+            .expect("FTS store not found")
+            .clone()
+    } else {
+        FtsStore::Store(store.clone())
+    };
 
     println!("Testing store {}...", store_id);
     if insert {
         store.destroy().await;
     }
     ops::test(store.clone()).await;
-    query::test(store.clone(), FtsStore::Store(store.clone()), insert).await;
+    query::test(store.clone(), fts_store, insert).await;
     assign_id::test(store).await;
 
     if insert {